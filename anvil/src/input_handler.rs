@@ -1,6 +1,9 @@
-use std::{process::Command, sync::atomic::Ordering};
+use std::{process::Command, sync::atomic::Ordering, time::Instant};
 
-use crate::{shell::FullscreenSurface, AnvilState};
+use crate::{
+    shell::{FullscreenSurface, MoveSurfaceGrab, ResizeEdge, ResizeSurfaceGrab},
+    AnvilState,
+};
 
 #[cfg(feature = "udev")]
 use crate::udev::UdevData;
@@ -10,14 +13,15 @@ use smithay::{
         self, Event, InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent,
         PointerButtonEvent,
     },
-    desktop::{layer_map_for_output, WindowSurfaceType},
+    desktop::{layer_map_for_output, LayerSurface, Space, WindowSurfaceType},
     reexports::wayland_server::protocol::{wl_pointer, wl_surface::WlSurface},
     utils::{Logical, Point},
     wayland::{
         compositor::with_states,
         output::Scale,
-        seat::{keysyms as xkb, AxisFrame, FilterResult, Keysym, ModifiersState},
+        seat::{keysyms as xkb, AxisFrame, FilterResult, Keysym, ModifiersState, PointerGrabStartData},
         shell::wlr_layer::{KeyboardInteractivity, Layer as WlrLayer, LayerSurfaceCachedState},
+        xdg_activation::UserInteraction,
         Serial, SERIAL_COUNTER as SCOUNTER,
     },
 };
@@ -36,7 +40,10 @@ use smithay::{
         },
         session::Session,
     },
-    wayland::tablet_manager::{TabletDescriptor, TabletSeatTrait},
+    wayland::{
+        input_timestamps::set_event_time,
+        tablet_manager::{TabletDescriptor, TabletSeatTrait},
+    },
 };
 
 impl<Backend> AnvilState<Backend> {
@@ -61,6 +68,67 @@ impl<Backend> AnvilState<Backend> {
                 }
             }
 
+            KeyAction::MoveWindow(button) => {
+                let space = self.space.clone();
+                let window = space.borrow().window_under(self.pointer_location).cloned();
+                if let Some(window) = window {
+                    let initial_window_location = space.borrow().window_location(&window).unwrap();
+                    let start_data = PointerGrabStartData {
+                        focus: None,
+                        button,
+                        location: self.pointer_location,
+                    };
+                    let grab = MoveSurfaceGrab {
+                        start_data,
+                        space,
+                        window,
+                        initial_window_location,
+                        snap: None,
+                    };
+                    self.pointer.set_grab(grab, SCOUNTER.next_serial(), 0);
+                }
+            }
+
+            KeyAction::ResizeWindow(button) => {
+                let grab = {
+                    let space = self.space.borrow();
+                    let window = match space.window_under(self.pointer_location) {
+                        Some(window) => window.clone(),
+                        None => return,
+                    };
+                    let location = space.window_location(&window).unwrap();
+                    let size = window.geometry().size;
+
+                    // resize by the edges closest to the pointer, the bottom right corner from
+                    // the middle of the window
+                    let relative = self.pointer_location - location.to_f64();
+                    let mut edges = ResizeEdge::NONE;
+                    if relative.x < size.w as f64 / 3.0 {
+                        edges |= ResizeEdge::LEFT;
+                    } else if relative.x > size.w as f64 * 2.0 / 3.0 {
+                        edges |= ResizeEdge::RIGHT;
+                    }
+                    if relative.y < size.h as f64 / 3.0 {
+                        edges |= ResizeEdge::TOP;
+                    } else if relative.y > size.h as f64 * 2.0 / 3.0 {
+                        edges |= ResizeEdge::BOTTOM;
+                    }
+                    if edges == ResizeEdge::NONE {
+                        edges = ResizeEdge::BOTTOM_RIGHT;
+                    }
+
+                    let start_data = PointerGrabStartData {
+                        focus: None,
+                        button,
+                        location: self.pointer_location,
+                    };
+                    ResizeSurfaceGrab::start(start_data, &space, window, edges, Some(self.keyboard.clone()))
+                };
+                if let Some(grab) = grab {
+                    self.pointer.set_grab(grab, SCOUNTER.next_serial(), 0);
+                }
+            }
+
             _ => unreachable!(
                 "Common key action handler encountered backend specific action {:?}",
                 action
@@ -75,7 +143,15 @@ impl<Backend> AnvilState<Backend> {
         let serial = SCOUNTER.next_serial();
         let log = &self.log;
         let time = Event::time(&evt);
+        if state == KeyState::Pressed {
+            self.last_interaction = Some(UserInteraction {
+                serial,
+                time: Instant::now(),
+            });
+        }
         let suppressed_keys = &mut self.suppressed_keys;
+        let modifier_tap = &mut self.modifier_tap;
+        let mut tap_action = None;
 
         for layer in self
             .shells
@@ -95,6 +171,7 @@ impl<Backend> AnvilState<Backend> {
                 if data.keyboard_interactivity == KeyboardInteractivity::Exclusive
                     && (data.layer == WlrLayer::Top || data.layer == WlrLayer::Overlay)
                 {
+                    self.modifier_tap = None;
                     self.keyboard
                         .set_focus(Some(layer.get_surface().unwrap()), serial);
                     self.keyboard
@@ -104,7 +181,8 @@ impl<Backend> AnvilState<Backend> {
             }
         }
 
-        self.keyboard
+        let action = self
+            .keyboard
             .input(keycode, state, serial, time, |modifiers, handle| {
                 let keysym = handle.modified_sym();
 
@@ -120,6 +198,11 @@ impl<Backend> AnvilState<Backend> {
                 // so that we can decide on a release if the key
                 // should be forwarded to the client or not.
                 if let KeyState::Pressed = state {
+                    // a modifier pressed on its own may become a tap, any other key cancels it
+                    *modifier_tap = Some(keysym)
+                        .filter(|keysym| is_tap_modifier(*keysym))
+                        .filter(|_| !(modifiers.ctrl || modifiers.alt || modifiers.shift));
+
                     let action = process_keyboard_shortcut(*modifiers, keysym);
 
                     if action.is_some() {
@@ -130,6 +213,12 @@ impl<Backend> AnvilState<Backend> {
                         .map(FilterResult::Intercept)
                         .unwrap_or(FilterResult::Forward)
                 } else {
+                    // The press of a tapped modifier was already sent to the client, as it could
+                    // not be known to become a tap back then, so the release is forwarded as well.
+                    if modifier_tap.take() == Some(keysym) {
+                        tap_action = process_modifier_tap(keysym);
+                    }
+
                     let suppressed = suppressed_keys.contains(&keysym);
                     if suppressed {
                         suppressed_keys.retain(|k| *k != keysym);
@@ -138,11 +227,11 @@ impl<Backend> AnvilState<Backend> {
                         FilterResult::Forward
                     }
                 }
-            })
-            .unwrap_or(KeyAction::None)
+            });
+        action.or(tap_action).unwrap_or(KeyAction::None)
     }
 
-    fn on_pointer_button<B: InputBackend>(&mut self, evt: B::PointerButtonEvent) {
+    fn on_pointer_button<B: InputBackend>(&mut self, evt: B::PointerButtonEvent) -> KeyAction {
         let serial = SCOUNTER.next_serial();
         let button = evt.button_code();
         let state = match evt.state() {
@@ -151,9 +240,23 @@ impl<Backend> AnvilState<Backend> {
         };
 
         if wl_pointer::ButtonState::Pressed == state {
+            self.modifier_tap = None;
+            self.last_interaction = Some(UserInteraction {
+                serial,
+                time: Instant::now(),
+            });
             self.update_keyboard_focus(serial);
+
+            // bindings intercept the press, the release is still forwarded to end their grabs
+            if !self.pointer.is_grabbed() {
+                let modifiers = self.keyboard.modifier_state();
+                if let Some(action) = process_pointer_shortcut(modifiers, PointerTrigger::Button(button)) {
+                    return action;
+                }
+            }
         };
         self.pointer.button(button, state, serial, evt.time());
+        KeyAction::None
     }
 
     fn update_keyboard_focus(&mut self, serial: Serial) {
@@ -161,6 +264,12 @@ impl<Backend> AnvilState<Backend> {
         if !self.pointer.is_grabbed() {
             let mut space = self.space.borrow_mut();
 
+            // X11 menus and tooltips never take the keyboard focus
+            #[cfg(feature = "xwayland")]
+            if space.override_redirect_under(self.pointer_location).is_some() {
+                return;
+            }
+
             if let Some(output) = space.output_under(self.pointer_location).next() {
                 let output_geo = space.output_geometry(output).unwrap();
                 if let Some(window) = output
@@ -231,51 +340,10 @@ impl<Backend> AnvilState<Backend> {
     }
 
     pub fn surface_under(&self) -> Option<(WlSurface, Point<i32, Logical>)> {
-        let pos = self.pointer_location;
-        let space = self.space.borrow();
-        let output = space.outputs().find(|o| {
-            let geometry = space.output_geometry(o).unwrap();
-            geometry.contains(pos.to_i32_round())
-        })?;
-        let output_geo = space.output_geometry(output).unwrap();
-        let layers = layer_map_for_output(output);
-
-        let mut under = None;
-        if let Some(window) = output
-            .user_data()
-            .get::<FullscreenSurface>()
-            .and_then(|f| f.get())
-        {
-            under = window.surface_under(pos - output_geo.loc.to_f64(), WindowSurfaceType::ALL);
-        } else if let Some(layer) = layers
-            .layer_under(WlrLayer::Overlay, pos)
-            .or_else(|| layers.layer_under(WlrLayer::Top, pos))
-        {
-            let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-            under = layer
-                .surface_under(
-                    pos - output_geo.loc.to_f64() - layer_loc.to_f64(),
-                    WindowSurfaceType::ALL,
-                )
-                .map(|(s, loc)| (s, loc + layer_loc));
-        } else if let Some((_, surface, location)) = space.surface_under(pos, WindowSurfaceType::ALL) {
-            under = Some((surface, location));
-        } else if let Some(layer) = layers
-            .layer_under(WlrLayer::Bottom, pos)
-            .or_else(|| layers.layer_under(WlrLayer::Background, pos))
-        {
-            let layer_loc = layers.layer_geometry(layer).unwrap().loc;
-            under = layer
-                .surface_under(
-                    pos - output_geo.loc.to_f64() - layer_loc.to_f64(),
-                    WindowSurfaceType::ALL,
-                )
-                .map(|(s, loc)| (s, loc + layer_loc));
-        };
-        under
+        surface_under(&self.space.borrow(), self.pointer_location)
     }
 
-    fn on_pointer_axis<B: InputBackend>(&mut self, evt: B::PointerAxisEvent) {
+    fn on_pointer_axis<B: InputBackend>(&mut self, evt: B::PointerAxisEvent) -> KeyAction {
         let source = match evt.source() {
             input::AxisSource::Continuous => wl_pointer::AxisSource::Continuous,
             input::AxisSource::Finger => wl_pointer::AxisSource::Finger,
@@ -290,6 +358,16 @@ impl<Backend> AnvilState<Backend> {
         let horizontal_amount_discrete = evt.amount_discrete(input::Axis::Horizontal);
         let vertical_amount_discrete = evt.amount_discrete(input::Axis::Vertical);
 
+        self.modifier_tap = None;
+
+        // only scroll wheels trigger bindings, smooth scrolling would trigger them too often
+        if let Some(discrete) = vertical_amount_discrete.filter(|discrete| *discrete != 0.0) {
+            let modifiers = self.keyboard.modifier_state();
+            if let Some(action) = process_pointer_shortcut(modifiers, PointerTrigger::Scroll(discrete)) {
+                return action;
+            }
+        }
+
         {
             let mut frame = AxisFrame::new(evt.time()).source(source);
             if horizontal_amount != 0.0 {
@@ -310,49 +388,74 @@ impl<Backend> AnvilState<Backend> {
             }
             self.pointer.axis(frame);
         }
+        KeyAction::None
     }
 }
 
+/// Finds the surface under a point of the space, taking layers, fullscreen and override-redirect windows into account
+pub fn surface_under(space: &Space, pos: Point<f64, Logical>) -> Option<(WlSurface, Point<i32, Logical>)> {
+    let output = space.outputs().find(|o| {
+        let geometry = space.output_geometry(o).unwrap();
+        geometry.contains(pos.to_i32_round())
+    })?;
+    let output_geo = space.output_geometry(output).unwrap();
+    let layers = layer_map_for_output(output);
+
+    // X11 menus and tooltips are stacked above regular windows and the top layer,
+    // but below the overlay layer, see `RenderZindex::OverrideRedirect`
+    #[cfg(feature = "xwayland")]
+    let override_redirect_under = || {
+        space
+            .override_redirect_under(pos)
+            .map(|(_, surface, location)| (surface, location))
+    };
+    #[cfg(not(feature = "xwayland"))]
+    let override_redirect_under = || None;
+
+    let layer_surface_under = |layer: &LayerSurface| {
+        let layer_loc = layers.layer_geometry(layer).unwrap().loc;
+        layer
+            .surface_under(
+                pos - output_geo.loc.to_f64() - layer_loc.to_f64(),
+                WindowSurfaceType::ALL,
+            )
+            .map(|(s, loc)| (s, loc + layer_loc))
+    };
+
+    let mut under = None;
+    if let Some(window) = output
+        .user_data()
+        .get::<FullscreenSurface>()
+        .and_then(|f| f.get())
+    {
+        // override-redirect windows are drawn on top of fullscreen windows
+        under = override_redirect_under()
+            .or_else(|| window.surface_under(pos - output_geo.loc.to_f64(), WindowSurfaceType::ALL));
+    } else if let Some(layer) = layers.layer_under(WlrLayer::Overlay, pos) {
+        under = layer_surface_under(layer);
+    } else if let Some(or_under) = override_redirect_under() {
+        under = Some(or_under);
+    } else if let Some(layer) = layers.layer_under(WlrLayer::Top, pos) {
+        under = layer_surface_under(layer);
+    } else if let Some((_, surface, location)) = space.surface_under(pos, WindowSurfaceType::ALL) {
+        under = Some((surface, location));
+    } else if let Some(layer) = layers
+        .layer_under(WlrLayer::Bottom, pos)
+        .or_else(|| layers.layer_under(WlrLayer::Background, pos))
+    {
+        under = layer_surface_under(layer);
+    };
+    under
+}
+
 #[cfg(any(feature = "winit", feature = "x11"))]
 impl<Backend: crate::state::Backend> AnvilState<Backend> {
     pub fn process_input_event_windowed<B: InputBackend>(&mut self, event: InputEvent<B>, output_name: &str) {
         match event {
-            InputEvent::Keyboard { event } => match self.keyboard_key_to_action::<B>(event) {
-                KeyAction::ScaleUp => {
-                    let mut space = self.space.borrow_mut();
-                    let output = space.outputs().find(|o| o.name() == output_name).unwrap().clone();
-
-                    let current_scale = output.current_scale().fractional_scale();
-                    let new_scale = current_scale + 0.25;
-                    output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
-
-                    crate::shell::fixup_positions(&mut *space);
-                    self.backend_data.reset_buffers(&output);
-                }
-
-                KeyAction::ScaleDown => {
-                    let mut space = self.space.borrow_mut();
-                    let output = space.outputs().find(|o| o.name() == output_name).unwrap().clone();
-
-                    let current_scale = output.current_scale().fractional_scale();
-                    let new_scale = f64::max(1.0, current_scale - 0.25);
-                    output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
-
-                    crate::shell::fixup_positions(&mut *space);
-                    self.backend_data.reset_buffers(&output);
-                }
-
-                action => match action {
-                    KeyAction::None | KeyAction::Quit | KeyAction::Run(_) => {
-                        self.process_common_key_action(action)
-                    }
-
-                    _ => warn!(
-                        self.log,
-                        "Key action {:?} unsupported on on output {} backend.", action, output_name
-                    ),
-                },
-            },
+            InputEvent::Keyboard { event } => {
+                let action = self.keyboard_key_to_action::<B>(event);
+                self.process_key_action_windowed(action, output_name)
+            }
 
             InputEvent::PointerMotionAbsolute { event } => {
                 let output = self
@@ -364,12 +467,59 @@ impl<Backend: crate::state::Backend> AnvilState<Backend> {
                     .clone();
                 self.on_pointer_move_absolute_windowed::<B>(event, &output)
             }
-            InputEvent::PointerButton { event } => self.on_pointer_button::<B>(event),
-            InputEvent::PointerAxis { event } => self.on_pointer_axis::<B>(event),
+            InputEvent::PointerButton { event } => {
+                let action = self.on_pointer_button::<B>(event);
+                self.process_key_action_windowed(action, output_name)
+            }
+            InputEvent::PointerAxis { event } => {
+                let action = self.on_pointer_axis::<B>(event);
+                self.process_key_action_windowed(action, output_name)
+            }
             _ => (), // other events are not handled in anvil (yet)
         }
     }
 
+    fn process_key_action_windowed(&mut self, action: KeyAction, output_name: &str) {
+        match action {
+            KeyAction::ScaleUp => {
+                let mut space = self.space.borrow_mut();
+                let output = space.outputs().find(|o| o.name() == output_name).unwrap().clone();
+
+                let current_scale = output.current_scale().fractional_scale();
+                let new_scale = current_scale + 0.25;
+                output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
+
+                crate::shell::fixup_positions(&mut *space);
+                self.backend_data.reset_buffers(&output);
+            }
+
+            KeyAction::ScaleDown => {
+                let mut space = self.space.borrow_mut();
+                let output = space.outputs().find(|o| o.name() == output_name).unwrap().clone();
+
+                let current_scale = output.current_scale().fractional_scale();
+                let new_scale = f64::max(1.0, current_scale - 0.25);
+                output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
+
+                crate::shell::fixup_positions(&mut *space);
+                self.backend_data.reset_buffers(&output);
+            }
+
+            action => match action {
+                KeyAction::None
+                | KeyAction::Quit
+                | KeyAction::Run(_)
+                | KeyAction::MoveWindow(_)
+                | KeyAction::ResizeWindow(_) => self.process_common_key_action(action),
+
+                _ => warn!(
+                    self.log,
+                    "Key action {:?} unsupported on on output {} backend.", action, output_name
+                ),
+            },
+        }
+    }
+
     fn on_pointer_move_absolute_windowed<B: InputBackend>(
         &mut self,
         evt: B::PointerMotionAbsoluteEvent,
@@ -390,103 +540,26 @@ impl<Backend: crate::state::Backend> AnvilState<Backend> {
 impl AnvilState<UdevData> {
     pub fn process_input_event<B: InputBackend>(&mut self, event: InputEvent<B>) {
         match event {
-            InputEvent::Keyboard { event, .. } => match self.keyboard_key_to_action::<B>(event) {
-                #[cfg(feature = "udev")]
-                KeyAction::VtSwitch(vt) => {
-                    info!(self.log, "Trying to switch to vt {}", vt);
-                    if let Err(err) = self.backend_data.session.change_vt(vt) {
-                        error!(self.log, "Error switching to vt {}: {}", vt, err);
-                    }
-                }
-                KeyAction::Screen(num) => {
-                    let space = self.space.borrow();
-                    let geometry = space
-                        .outputs()
-                        .nth(num)
-                        .map(|o| space.output_geometry(o).unwrap());
-
-                    if let Some(geometry) = geometry {
-                        let x = geometry.loc.x as f64 + geometry.size.w as f64 / 2.0;
-                        let y = geometry.size.h as f64 / 2.0;
-                        self.pointer_location = (x, y).into()
-                    }
-                }
-                KeyAction::ScaleUp => {
-                    let mut space = self.space.borrow_mut();
-
-                    let pos = self.pointer_location.to_i32_round();
-                    let output = space
-                        .outputs()
-                        .find(|o| space.output_geometry(o).unwrap().contains(pos))
-                        .cloned();
-
-                    if let Some(output) = output {
-                        let (output_location, scale) = (
-                            space.output_geometry(&output).unwrap().loc,
-                            output.current_scale().fractional_scale(),
-                        );
-                        let new_scale = scale + 0.25;
-                        output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
-
-                        let rescale = scale as f64 / new_scale as f64;
-                        let output_location = output_location.to_f64();
-                        let mut pointer_output_location = self.pointer_location - output_location;
-                        pointer_output_location.x *= rescale;
-                        pointer_output_location.y *= rescale;
-                        self.pointer_location = output_location + pointer_output_location;
-
-                        crate::shell::fixup_positions(&mut *space);
-                        std::mem::drop(space);
-                        let under = self.surface_under();
-                        self.pointer
-                            .motion(self.pointer_location, under, SCOUNTER.next_serial(), 0);
-                        self.backend_data.reset_buffers(&output);
-                    }
-                }
-                KeyAction::ScaleDown => {
-                    let mut space = self.space.borrow_mut();
-
-                    let pos = self.pointer_location.to_i32_round();
-                    let output = space
-                        .outputs()
-                        .find(|o| space.output_geometry(o).unwrap().contains(pos))
-                        .cloned();
-
-                    if let Some(output) = output {
-                        let (output_location, scale) = (
-                            space.output_geometry(&output).unwrap().loc,
-                            output.current_scale().fractional_scale(),
-                        );
-                        let new_scale = f64::max(1.0, scale - 0.25);
-                        output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
-
-                        let rescale = scale as f64 / new_scale as f64;
-                        let output_location = output_location.to_f64();
-                        let mut pointer_output_location = self.pointer_location - output_location;
-                        pointer_output_location.x *= rescale;
-                        pointer_output_location.y *= rescale;
-                        self.pointer_location = output_location + pointer_output_location;
-
-                        crate::shell::fixup_positions(&mut *space);
-                        std::mem::drop(space);
-                        let under = self.surface_under();
-                        self.pointer
-                            .motion(self.pointer_location, under, SCOUNTER.next_serial(), 0);
-                        self.backend_data.reset_buffers(&output);
-                    }
-                }
-
-                action => match action {
-                    KeyAction::None | KeyAction::Quit | KeyAction::Run(_) => {
-                        self.process_common_key_action(action)
-                    }
-
-                    _ => unreachable!(),
-                },
-            },
-            InputEvent::PointerMotion { event, .. } => self.on_pointer_move::<B>(event),
-            InputEvent::PointerButton { event, .. } => self.on_pointer_button::<B>(event),
-            InputEvent::PointerAxis { event, .. } => self.on_pointer_axis::<B>(event),
+            InputEvent::Keyboard { event, .. } => {
+                set_event_time(event.time_usec());
+                let action = self.keyboard_key_to_action::<B>(event);
+                self.process_key_action(action)
+            }
+            InputEvent::PointerMotion { event, .. } => {
+                set_event_time(event.time_usec());
+                let action = self.on_pointer_move::<B>(event);
+                self.process_key_action(action)
+            }
+            InputEvent::PointerButton { event, .. } => {
+                set_event_time(event.time_usec());
+                let action = self.on_pointer_button::<B>(event);
+                self.process_key_action(action)
+            }
+            InputEvent::PointerAxis { event, .. } => {
+                set_event_time(event.time_usec());
+                let action = self.on_pointer_axis::<B>(event);
+                self.process_key_action(action)
+            }
             InputEvent::TabletToolAxis { event, .. } => self.on_tablet_tool_axis::<B>(event),
             InputEvent::TabletToolProximity { event, .. } => self.on_tablet_tool_proximity::<B>(event),
             InputEvent::TabletToolTip { event, .. } => self.on_tablet_tool_tip::<B>(event),
@@ -516,17 +589,133 @@ impl AnvilState<UdevData> {
         }
     }
 
-    fn on_pointer_move<B: InputBackend>(&mut self, evt: B::PointerMotionEvent) {
+    fn process_key_action(&mut self, action: KeyAction) {
+        match action {
+            #[cfg(feature = "udev")]
+            KeyAction::VtSwitch(vt) => {
+                info!(self.log, "Trying to switch to vt {}", vt);
+                if let Err(err) = self.backend_data.session.change_vt(vt) {
+                    error!(self.log, "Error switching to vt {}: {}", vt, err);
+                }
+            }
+            KeyAction::Screen(num) => {
+                let space = self.space.borrow();
+                let geometry = space
+                    .outputs()
+                    .nth(num)
+                    .map(|o| space.output_geometry(o).unwrap());
+
+                if let Some(geometry) = geometry {
+                    let x = geometry.loc.x as f64 + geometry.size.w as f64 / 2.0;
+                    let y = geometry.size.h as f64 / 2.0;
+                    self.pointer_location = (x, y).into()
+                }
+            }
+            KeyAction::ScaleUp => {
+                let mut space = self.space.borrow_mut();
+
+                let pos = self.pointer_location.to_i32_round();
+                let output = space
+                    .outputs()
+                    .find(|o| space.output_geometry(o).unwrap().contains(pos))
+                    .cloned();
+
+                if let Some(output) = output {
+                    let (output_location, scale) = (
+                        space.output_geometry(&output).unwrap().loc,
+                        output.current_scale().fractional_scale(),
+                    );
+                    let new_scale = scale + 0.25;
+                    output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
+
+                    let rescale = scale as f64 / new_scale as f64;
+                    let output_location = output_location.to_f64();
+                    let mut pointer_output_location = self.pointer_location - output_location;
+                    pointer_output_location.x *= rescale;
+                    pointer_output_location.y *= rescale;
+                    self.pointer_location = output_location + pointer_output_location;
+
+                    crate::shell::fixup_positions(&mut *space);
+                    std::mem::drop(space);
+                    let under = self.surface_under();
+                    self.pointer
+                        .motion(self.pointer_location, under, SCOUNTER.next_serial(), 0);
+                    self.backend_data.reset_buffers(&output);
+                }
+            }
+            KeyAction::ScaleDown => {
+                let mut space = self.space.borrow_mut();
+
+                let pos = self.pointer_location.to_i32_round();
+                let output = space
+                    .outputs()
+                    .find(|o| space.output_geometry(o).unwrap().contains(pos))
+                    .cloned();
+
+                if let Some(output) = output {
+                    let (output_location, scale) = (
+                        space.output_geometry(&output).unwrap().loc,
+                        output.current_scale().fractional_scale(),
+                    );
+                    let new_scale = f64::max(1.0, scale - 0.25);
+                    output.change_current_state(None, None, Some(Scale::Fractional(new_scale)), None);
+
+                    let rescale = scale as f64 / new_scale as f64;
+                    let output_location = output_location.to_f64();
+                    let mut pointer_output_location = self.pointer_location - output_location;
+                    pointer_output_location.x *= rescale;
+                    pointer_output_location.y *= rescale;
+                    self.pointer_location = output_location + pointer_output_location;
+
+                    crate::shell::fixup_positions(&mut *space);
+                    std::mem::drop(space);
+                    let under = self.surface_under();
+                    self.pointer
+                        .motion(self.pointer_location, under, SCOUNTER.next_serial(), 0);
+                    self.backend_data.reset_buffers(&output);
+                }
+            }
+
+            action => match action {
+                KeyAction::None
+                | KeyAction::Quit
+                | KeyAction::Run(_)
+                | KeyAction::MoveWindow(_)
+                | KeyAction::ResizeWindow(_) => self.process_common_key_action(action),
+
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    fn on_pointer_move<B: InputBackend>(&mut self, evt: B::PointerMotionEvent) -> KeyAction {
         let serial = SCOUNTER.next_serial();
-        self.pointer_location += evt.delta();
+        let requested = self.pointer_location + evt.delta();
 
         // clamp to screen limits
         // this event is never generated by winit
-        self.pointer_location = self.clamp_coords(self.pointer_location);
+        self.pointer_location = self.clamp_coords(requested);
 
         let under = self.surface_under();
         self.pointer
             .motion(self.pointer_location, under, serial, evt.time());
+
+        // the screen limits act as a barrier, pushing against it triggers screen edges
+        let blocked = requested - self.pointer_location;
+        let action = self.screen_edges.pointer_motion(
+            &*self.space.borrow(),
+            self.pointer_location,
+            blocked,
+            Instant::now(),
+        );
+        action.map(KeyAction::Run).unwrap_or(KeyAction::None)
+    }
+
+    /// Runs the command of a screen edge, once the pointer rested in it long enough
+    pub fn poll_screen_edges(&mut self) {
+        if let Some(command) = self.screen_edges.poll(Instant::now()) {
+            self.process_key_action(KeyAction::Run(command));
+        }
     }
 
     fn on_tablet_tool_axis<B: InputBackend>(&mut self, evt: B::TabletToolAxisEvent) {
@@ -539,6 +728,7 @@ impl AnvilState<UdevData> {
             self.pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
 
             let under = self.surface_under();
+            self.stylus_focus = under.as_ref().map(|(surface, _)| surface.clone());
             let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
             let tool = tablet_seat.get_tool(&evt.tool());
 
@@ -586,6 +776,10 @@ impl AnvilState<UdevData> {
             self.pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
 
             let under = self.surface_under();
+            self.stylus_focus = match evt.state() {
+                ProximityState::In => under.as_ref().map(|(surface, _)| surface.clone()),
+                ProximityState::Out => None,
+            };
             let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
             let tool = tablet_seat.get_tool(&tool);
 
@@ -664,7 +858,7 @@ impl AnvilState<UdevData> {
     }
 }
 
-/// Possible results of a keyboard action
+/// Possible results of a keyboard or pointer binding
 #[derive(Debug)]
 enum KeyAction {
     /// Quit the compositor
@@ -677,6 +871,10 @@ enum KeyAction {
     Screen(usize),
     ScaleUp,
     ScaleDown,
+    /// Move the window under the pointer, while the given button is held
+    MoveWindow(u32),
+    /// Resize the window under the pointer, while the given button is held
+    ResizeWindow(u32),
     /// Do nothing more
     None,
 }
@@ -706,3 +904,42 @@ fn process_keyboard_shortcut(modifiers: ModifiersState, keysym: Keysym) -> Optio
         None
     }
 }
+
+/// Checks if a modifier may trigger a binding, when it is pressed and released without any
+/// other input in between
+fn is_tap_modifier(keysym: Keysym) -> bool {
+    keysym == xkb::KEY_Super_L || keysym == xkb::KEY_Super_R
+}
+
+fn process_modifier_tap(keysym: Keysym) -> Option<KeyAction> {
+    if is_tap_modifier(keysym) {
+        // tap logo = run the launcher set by ANVIL_LAUNCHER
+        std::env::var("ANVIL_LAUNCHER").ok().map(KeyAction::Run)
+    } else {
+        None
+    }
+}
+
+/// Pointer input, that may trigger a binding
+enum PointerTrigger {
+    /// A button was pressed
+    Button(u32),
+    /// The vertical scroll wheel was turned by the given amount of steps
+    Scroll(f64),
+}
+
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+
+fn process_pointer_shortcut(modifiers: ModifiersState, trigger: PointerTrigger) -> Option<KeyAction> {
+    match trigger {
+        // logo + left button = move window
+        PointerTrigger::Button(BTN_LEFT) if modifiers.logo => Some(KeyAction::MoveWindow(BTN_LEFT)),
+        // logo + right button = resize window
+        PointerTrigger::Button(BTN_RIGHT) if modifiers.logo => Some(KeyAction::ResizeWindow(BTN_RIGHT)),
+        // logo + scroll = change scale
+        PointerTrigger::Scroll(steps) if modifiers.logo && steps < 0.0 => Some(KeyAction::ScaleUp),
+        PointerTrigger::Scroll(steps) if modifiers.logo && steps > 0.0 => Some(KeyAction::ScaleDown),
+        _ => None,
+    }
+}
@@ -23,6 +23,13 @@ fn main() {
     let _guard = slog_scope::set_global_logger(log.clone());
     slog_stdlog::init().expect("Could not setup log backend");
 
+    // keep compiled shaders around, to speed up the next start
+    #[cfg(any(feature = "winit", feature = "udev", feature = "x11"))]
+    {
+        use smithay::backend::renderer::gles2;
+        gles2::set_program_cache_dir(gles2::default_program_cache_dir());
+    }
+
     let arg = ::std::env::args().nth(1);
     match arg.as_ref().map(|s| &s[..]) {
         #[cfg(feature = "winit")]
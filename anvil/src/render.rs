@@ -1,3 +1,5 @@
+#[cfg(feature = "xwayland")]
+use smithay::desktop::draw_override_redirect;
 use smithay::{
     backend::renderer::{Frame, ImportAll, Renderer},
     desktop::{
@@ -53,6 +55,27 @@ where
                     )],
                     log,
                 )?;
+                // X11 menus and tooltips of the fullscreen window, like in `Space::render_output`
+                #[cfg(feature = "xwayland")]
+                for or_window in space.override_redirect_windows() {
+                    if !or_window.geometry().overlaps(output_geo) {
+                        continue;
+                    }
+                    let location = or_window.location() - output_geo.loc;
+                    draw_override_redirect(
+                        renderer,
+                        frame,
+                        or_window,
+                        scale,
+                        location,
+                        &[or_window.bbox()],
+                        log,
+                    )?;
+                    damage.extend(or_window.accumulated_damage(None).into_iter().map(|mut rect| {
+                        rect.loc += location;
+                        rect
+                    }));
+                }
                 for elem in elements {
                     let geo = elem.geometry();
                     let location = geo.loc - output_geo.loc;
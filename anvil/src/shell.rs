@@ -7,8 +7,10 @@ use std::{
 use smithay::{
     backend::renderer::utils::on_commit_buffer_handler,
     desktop::{
-        layer_map_for_output, Kind as SurfaceKind, LayerSurface, PopupKeyboardGrab, PopupKind, PopupManager,
-        PopupPointerGrab, PopupUngrabStrategy, Space, Window,
+        layer_map_for_output,
+        space::{SnapConfig, SnapPosition, SnapPreview},
+        Kind as SurfaceKind, LayerSurface, PopupKeyboardGrab, PopupKind, PopupManager, PopupPointerGrab,
+        PopupUngrabStrategy, Space, Window,
     },
     reexports::{
         wayland_protocols::xdg_shell::server::xdg_toplevel,
@@ -21,11 +23,11 @@ use smithay::{
     wayland::{
         compositor::{compositor_init, with_states, with_surface_tree_upward, TraversalAction},
         output::Output,
-        seat::{AxisFrame, PointerGrab, PointerGrabStartData, PointerInnerHandle, Seat},
+        seat::{AxisFrame, KeyboardHandle, PointerGrab, PointerGrabStartData, PointerInnerHandle, Seat},
         shell::{
             wlr_layer::{LayerShellRequest, LayerShellState, LayerSurfaceAttributes},
             xdg::{
-                xdg_shell_init, Configure, ShellState as XdgShellState, SurfaceCachedState,
+                xdg_shell_init, Configure, ShellState as XdgShellState, ToplevelSurface,
                 XdgPopupSurfaceRoleAttributes, XdgRequest, XdgToplevelSurfaceRoleAttributes,
             },
         },
@@ -35,11 +37,46 @@ use smithay::{
 
 use crate::state::{AnvilState, Backend};
 
-struct MoveSurfaceGrab {
-    start_data: PointerGrabStartData,
-    space: Rc<RefCell<Space>>,
-    window: Window,
-    initial_window_location: Point<i32, Logical>,
+pub struct MoveSurfaceGrab {
+    pub start_data: PointerGrabStartData,
+    pub space: Rc<RefCell<Space>>,
+    pub window: Window,
+    pub initial_window_location: Point<i32, Logical>,
+    pub snap: Option<SnapPreview>,
+}
+
+impl MoveSurfaceGrab {
+    /// Tiles the window into the snap zone it was dropped in
+    fn commit_snap(&mut self) {
+        let preview = match self.snap.take() {
+            Some(preview) => preview,
+            None => return,
+        };
+        let surface = match self.window.toplevel() {
+            SurfaceKind::Xdg(surface) => surface,
+            #[cfg(feature = "xwayland")]
+            SurfaceKind::X11(_) => return,
+        };
+
+        if preview.position == SnapPosition::Maximized {
+            self.window.store_restore_geometry(Rectangle::from_loc_and_size(
+                self.initial_window_location,
+                self.window.geometry().size,
+            ));
+        }
+        let ret = surface.with_pending_state(|state| {
+            if preview.position == SnapPosition::Maximized {
+                state.states.set(xdg_toplevel::State::Maximized);
+            }
+            state.size = Some(preview.geometry.size);
+        });
+        if ret.is_ok() {
+            self.space
+                .borrow_mut()
+                .map_window(&self.window, preview.geometry.loc, true);
+            surface.send_configure();
+        }
+    }
 }
 
 impl PointerGrab for MoveSurfaceGrab {
@@ -57,9 +94,17 @@ impl PointerGrab for MoveSurfaceGrab {
         let delta = location - self.start_data.location;
         let new_location = self.initial_window_location.to_f64() + delta;
 
-        self.space
-            .borrow_mut()
-            .map_window(&self.window, new_location.to_i32_round(), true);
+        let mut space = self.space.borrow_mut();
+        space.map_window(&self.window, new_location.to_i32_round(), true);
+        // anvil does not draw a preview, the window is tiled when it is dropped
+        self.snap = space.snap_preview(
+            &self.window,
+            location,
+            &SnapConfig {
+                snap_to_windows: true,
+                ..Default::default()
+            },
+        );
     }
 
     fn button(
@@ -74,6 +119,7 @@ impl PointerGrab for MoveSurfaceGrab {
         if handle.current_pressed().is_empty() {
             // No more buttons are pressed, release the grab.
             handle.unset_grab(serial, time);
+            self.commit_snap();
         }
     }
 
@@ -87,7 +133,7 @@ impl PointerGrab for MoveSurfaceGrab {
 }
 
 bitflags::bitflags! {
-    struct ResizeEdge: u32 {
+    pub struct ResizeEdge: u32 {
         const NONE = 0;
         const TOP = 1;
         const BOTTOM = 2;
@@ -128,12 +174,62 @@ impl From<ResizeEdge> for xdg_toplevel::ResizeEdge {
     }
 }
 
-struct ResizeSurfaceGrab {
+pub struct ResizeSurfaceGrab {
     start_data: PointerGrabStartData,
     window: Window,
+    keyboard: Option<KeyboardHandle>,
     edges: ResizeEdge,
     initial_window_size: Size<i32, Logical>,
     last_window_size: Size<i32, Logical>,
+    centered: bool,
+}
+
+impl ResizeSurfaceGrab {
+    /// Starts resizing the window by the given edges, if it is an xdg toplevel mapped in the space
+    ///
+    /// The modifiers of the keyboard change the resize while it is in progress: shift keeps the
+    /// aspect ratio of the window and ctrl resizes it around its center.
+    pub fn start(
+        start_data: PointerGrabStartData,
+        space: &Space,
+        window: Window,
+        edges: ResizeEdge,
+        keyboard: Option<KeyboardHandle>,
+    ) -> Option<ResizeSurfaceGrab> {
+        #[cfg_attr(not(feature = "xwayland"), allow(irrefutable_let_patterns))]
+        let surface = if let SurfaceKind::Xdg(xdg) = window.toplevel() {
+            xdg.get_surface()?.clone()
+        } else {
+            return None;
+        };
+        let initial_window_location = space.window_location(&window)?;
+        let initial_window_size = window.geometry().size;
+
+        with_states(&surface, |states| {
+            states
+                .data_map
+                .get::<RefCell<SurfaceData>>()?
+                .borrow_mut()
+                .resize_state = ResizeState::Resizing(ResizeData {
+                edges,
+                initial_window_location,
+                initial_window_size,
+                centered: false,
+            });
+            Some(())
+        })
+        .ok()??;
+
+        Some(ResizeSurfaceGrab {
+            start_data,
+            window,
+            keyboard,
+            edges,
+            initial_window_size,
+            last_window_size: initial_window_size,
+            centered: false,
+        })
+    }
 }
 
 impl PointerGrab for ResizeSurfaceGrab {
@@ -154,7 +250,15 @@ impl PointerGrab for ResizeSurfaceGrab {
         // While the grab is active, no client has pointer focus
         handle.motion(location, None, serial, time);
 
-        let (mut dx, mut dy) = (location - self.start_data.location).into();
+        let modifiers = self
+            .keyboard
+            .as_ref()
+            .map(KeyboardHandle::modifier_state)
+            .unwrap_or_default();
+        let centered = modifiers.ctrl;
+
+        let delta = location - self.start_data.location;
+        let (mut dx, mut dy) = (delta.x.round() as i32, delta.y.round() as i32);
 
         let mut new_window_width = self.initial_window_size.w;
         let mut new_window_height = self.initial_window_size.h;
@@ -166,41 +270,56 @@ impl PointerGrab for ResizeSurfaceGrab {
             if self.edges.intersects(ResizeEdge::LEFT) {
                 dx = -dx;
             }
+            // when resizing around the center, both edges follow the pointer, and the window
+            // grows by an even amount, so its center stays on the same logical pixel
+            if centered {
+                dx *= 2;
+            }
 
-            new_window_width = (self.initial_window_size.w as f64 + dx) as i32;
+            new_window_width = self.initial_window_size.w + dx;
         }
 
         if self.edges.intersects(top_bottom) {
             if self.edges.intersects(ResizeEdge::TOP) {
                 dy = -dy;
             }
+            if centered {
+                dy *= 2;
+            }
 
-            new_window_height = (self.initial_window_size.h as f64 + dy) as i32;
+            new_window_height = self.initial_window_size.h + dy;
         }
 
-        let (min_size, max_size) = with_states(self.window.toplevel().get_surface().unwrap(), |states| {
-            let data = states.cached_state.current::<SurfaceCachedState>();
-            (data.min_size, data.max_size)
-        })
-        .unwrap();
-
-        let min_width = min_size.w.max(1);
-        let min_height = min_size.h.max(1);
-        let max_width = if max_size.w == 0 {
-            i32::max_value()
-        } else {
-            max_size.w
-        };
-        let max_height = if max_size.h == 0 {
-            i32::max_value()
-        } else {
-            max_size.h
-        };
+        let mut constraints = self.window.size_constraints();
+        if modifiers.shift && constraints.aspect_ratio.is_none() {
+            let initial = self.initial_window_size;
+            constraints.aspect_ratio = Some(initial.w.max(1) as f64 / initial.h.max(1) as f64);
+        }
+        let new_window_size = constraints.clamp_resize(
+            self.initial_window_size,
+            (new_window_width, new_window_height).into(),
+        );
 
-        new_window_width = new_window_width.max(min_width).min(max_width);
-        new_window_height = new_window_height.max(min_height).min(max_height);
+        // past the min/max size, the window stays at its limit without flooding the client
+        if new_window_size == self.last_window_size && centered == self.centered {
+            return;
+        }
+        self.last_window_size = new_window_size;
 
-        self.last_window_size = (new_window_width, new_window_height).into();
+        if centered != self.centered {
+            self.centered = centered;
+            with_states(self.window.toplevel().get_surface().unwrap(), |states| {
+                let mut data = states
+                    .data_map
+                    .get::<RefCell<SurfaceData>>()
+                    .unwrap()
+                    .borrow_mut();
+                if let ResizeState::Resizing(ref mut resize_data) = data.resize_state {
+                    resize_data.centered = centered;
+                }
+            })
+            .unwrap();
+        }
 
         match &self.window.toplevel() {
             SurfaceKind::Xdg(xdg) => {
@@ -305,7 +424,7 @@ fn fullscreen_output_geometry(
         .and_then(Output::from_resource)
         .or_else(|| {
             let w = space.window_for_surface(wl_surface).cloned();
-            w.and_then(|w| space.outputs_for_window(&w).get(0).cloned())
+            w.and_then(|w| space.primary_output(&w))
         })
         .and_then(|o| space.output_geometry(&o))
 }
@@ -327,6 +446,23 @@ impl FullscreenSurface {
     }
 }
 
+/// Moves a window back to where it was before being maximized or fullscreened
+fn restore_window(space: &mut Space, surface: &ToplevelSurface) {
+    let window = match surface
+        .get_surface()
+        .and_then(|surface| space.window_for_surface(surface))
+    {
+        Some(window) => window.clone(),
+        None => return,
+    };
+    if let Some(geometry) = window.take_restore_geometry() {
+        let _ = surface.with_pending_state(|state| {
+            state.size = Some(geometry.size);
+        });
+        space.map_window(&window, geometry.loc, false);
+    }
+}
+
 pub fn init_shell<BackendData: Backend + 'static>(
     display: Rc<RefCell<Display>>,
     log: ::slog::Logger,
@@ -338,6 +474,7 @@ pub fn init_shell<BackendData: Backend + 'static>(
             on_commit_buffer_handler(&surface);
             let anvil_state = ddata.get::<AnvilState<BackendData>>().unwrap();
             anvil_state.backend_data.early_import(&surface);
+            anvil_state.stylus_commit(&surface);
             let mut popups = anvil_state.popups.borrow_mut();
             let space = anvil_state.space.as_ref();
             space.borrow_mut().commit(&surface);
@@ -472,6 +609,7 @@ pub fn init_shell<BackendData: Backend + 'static>(
                         space,
                         window,
                         initial_window_location,
+                        snap: None,
                     };
 
                     pointer.set_grab(grab, serial, 0);
@@ -507,36 +645,22 @@ pub fn init_shell<BackendData: Backend + 'static>(
                         return;
                     }
 
-                    let space = state.space.clone();
-                    let window = space
-                        .borrow_mut()
-                        .window_for_surface(surface.get_surface().unwrap())
-                        .unwrap()
-                        .clone();
-                    let geometry = window.geometry();
-                    let loc = space.borrow().window_location(&window).unwrap();
-                    let (initial_window_location, initial_window_size) = (loc, geometry.size);
-
-                    with_states(surface.get_surface().unwrap(), move |states| {
-                        states
-                            .data_map
-                            .get::<RefCell<SurfaceData>>()
-                            .unwrap()
-                            .borrow_mut()
-                            .resize_state = ResizeState::Resizing(ResizeData {
-                            edges: edges.into(),
-                            initial_window_location,
-                            initial_window_size,
-                        });
-                    })
-                    .unwrap();
-
-                    let grab = ResizeSurfaceGrab {
-                        start_data,
-                        window,
-                        edges: edges.into(),
-                        initial_window_size,
-                        last_window_size: initial_window_size,
+                    let grab = {
+                        let space = state.space.borrow();
+                        let window = match space.window_for_surface(surface.get_surface().unwrap()) {
+                            Some(window) => window.clone(),
+                            None => return,
+                        };
+                        match ResizeSurfaceGrab::start(
+                            start_data,
+                            &space,
+                            window,
+                            edges.into(),
+                            seat.get_keyboard(),
+                        ) {
+                            Some(grab) => grab,
+                            None => return,
+                        }
                     };
 
                     pointer.set_grab(grab, serial, 0);
@@ -631,6 +755,14 @@ pub fn init_shell<BackendData: Backend + 'static>(
                             wl_output = Some(output.clone());
                         });
 
+                        let window = space.window_for_surface(wl_surface).unwrap();
+                        if let Some(location) = space.window_location(window) {
+                            window.store_restore_geometry(Rectangle::from_loc_and_size(
+                                location,
+                                window.geometry().size,
+                            ));
+                        }
+
                         let ret = surface.with_pending_state(|state| {
                             state.states.set(xdg_toplevel::State::Fullscreen);
                             state.size = Some(geometry.size);
@@ -638,7 +770,6 @@ pub fn init_shell<BackendData: Backend + 'static>(
                         });
 
                         if ret.is_ok() {
-                            let window = space.window_for_surface(wl_surface).unwrap();
                             window.configure();
                             output.user_data().insert_if_missing(FullscreenSurface::default);
                             output
@@ -658,6 +789,7 @@ pub fn init_shell<BackendData: Backend + 'static>(
                         state.fullscreen_output.take()
                     });
                     if let Ok(output) = ret {
+                        restore_window(&mut *state.space.borrow_mut(), &surface);
                         if let Some(output) = output {
                             let output = Output::from_resource(&output).unwrap();
                             if let Some(fullscreen) = output.user_data().get::<FullscreenSurface>() {
@@ -672,25 +804,33 @@ pub fn init_shell<BackendData: Backend + 'static>(
                 }
 
                 XdgRequest::Maximize { surface } => {
-                    // NOTE: This should use layer-shell when it is implemented to
-                    // get the correct maximum size
                     let mut space = state.space.borrow_mut();
                     let window = space
                         .window_for_surface(surface.get_surface().unwrap())
                         .unwrap()
                         .clone();
-                    let output = &space.outputs_for_window(&window)[0];
-                    let geometry = space.output_geometry(output).unwrap();
-
-                    space.map_window(&window, geometry.loc, true);
-                    let ret = surface.with_pending_state(|state| {
-                        state.states.set(xdg_toplevel::State::Maximized);
-                        state.size = Some(geometry.size);
-                    });
-
-                    if ret.is_ok() {
-                        window.configure();
+                    // windows outside of every output are maximized on the first one
+                    let geometry = space
+                        .primary_output(&window)
+                        .or_else(|| space.outputs().next().cloned())
+                        .and_then(|output| space.usable_area(&output));
+
+                    if let Some(geometry) = geometry {
+                        if let Some(location) = space.window_location(&window) {
+                            window.store_restore_geometry(Rectangle::from_loc_and_size(
+                                location,
+                                window.geometry().size,
+                            ));
+                        }
+                        space.map_window(&window, geometry.loc, true);
+                        let _ = surface.with_pending_state(|state| {
+                            state.states.set(xdg_toplevel::State::Maximized);
+                            state.size = Some(geometry.size);
+                        });
                     }
+
+                    // the client expects a configure in any case
+                    window.configure();
                 }
                 XdgRequest::UnMaximize { surface } => {
                     let ret = surface.with_pending_state(|state| {
@@ -699,6 +839,7 @@ pub fn init_shell<BackendData: Backend + 'static>(
                     });
 
                     if ret.is_ok() {
+                        restore_window(&mut *state.space.borrow_mut(), &surface);
                         surface.send_configure();
                     }
                 }
@@ -784,6 +925,8 @@ pub struct ResizeData {
     initial_window_location: Point<i32, Logical>,
     /// The initial window size (geometry width and height).
     initial_window_size: Size<i32, Logical>,
+    /// Whether the window is resized around its center instead of its opposite edges.
+    centered: bool,
 }
 
 /// State of the resize operation.
@@ -870,20 +1013,32 @@ fn surface_commit(surface: &wl_surface::WlSurface, space: &RefCell<Space>, popup
                         edges,
                         initial_window_location,
                         initial_window_size,
+                        centered,
                     } = resize_data;
 
-                    if edges.intersects(ResizeEdge::TOP_LEFT) {
-                        let mut location = window_loc;
-
-                        if edges.intersects(ResizeEdge::LEFT) {
-                            location.x =
-                                initial_window_location.x + (initial_window_size.w - geometry.size.w);
+                    // The opposite edges, or the center, stay where they were when the resize
+                    // started, whatever size the client picked.
+                    let mut location = window_loc;
+                    let (dw, dh) = (
+                        initial_window_size.w - geometry.size.w,
+                        initial_window_size.h - geometry.size.h,
+                    );
+                    if edges.intersects(ResizeEdge::LEFT | ResizeEdge::RIGHT) {
+                        if centered {
+                            location.x = initial_window_location.x + dw / 2;
+                        } else if edges.intersects(ResizeEdge::LEFT) {
+                            location.x = initial_window_location.x + dw;
                         }
-                        if edges.intersects(ResizeEdge::TOP) {
-                            location.y =
-                                initial_window_location.y + (initial_window_size.h - geometry.size.h);
+                    }
+                    if edges.intersects(ResizeEdge::TOP | ResizeEdge::BOTTOM) {
+                        if centered {
+                            location.y = initial_window_location.y + dh / 2;
+                        } else if edges.intersects(ResizeEdge::TOP) {
+                            location.y = initial_window_location.y + dh;
                         }
+                    }
 
+                    if location != window_loc {
                         new_location = Some(location);
                     }
                 }
@@ -960,12 +1115,7 @@ fn place_new_window(space: &mut Space, window: &Window, activate: bool) {
 
     let output = space.outputs().next().cloned();
     let output_geometry = output
-        .and_then(|o| {
-            let geo = space.output_geometry(&o)?;
-            let map = layer_map_for_output(&o);
-            let zone = map.non_exclusive_zone();
-            Some(Rectangle::from_loc_and_size(geo.loc + zone.loc, zone.size))
-        })
+        .and_then(|o| space.usable_area(&o))
         .unwrap_or_else(|| Rectangle::from_loc_and_size((0, 0), (800, 800)));
 
     let max_x = output_geometry.loc.x + (((output_geometry.size.w as f32) / 3.0) * 2.0) as i32;
@@ -996,12 +1146,7 @@ pub fn fixup_positions(space: &mut Space) {
     let mut orphaned_windows = Vec::new();
     let outputs = space
         .outputs()
-        .flat_map(|o| {
-            let geo = space.output_geometry(o)?;
-            let map = layer_map_for_output(o);
-            let zone = map.non_exclusive_zone();
-            Some(Rectangle::from_loc_and_size(geo.loc + zone.loc, zone.size))
-        })
+        .flat_map(|o| space.usable_area(o))
         .collect::<Vec<_>>();
     for window in space.windows() {
         let window_location = match space.window_location(window) {
@@ -5,29 +5,43 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc, Mutex,
     },
+    time::Duration,
 };
 
 use smithay::{
-    desktop::{PopupManager, Space},
+    desktop::{
+        edges::{EdgeConfig, EdgeTrigger, ScreenEdge, ScreenEdges},
+        PopupManager, Space,
+    },
     reexports::{
         calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
         wayland_protocols::unstable::xdg_decoration,
         wayland_server::{protocol::wl_surface::WlSurface, Display},
     },
-    utils::{Logical, Point},
+    utils::{Logical, Point, Rectangle},
     wayland::{
+        compositor::get_parent,
         data_device::{default_action_chooser, init_data_device, set_data_device_focus, DataDeviceEvent},
+        input_timestamps::init_input_timestamps_manager,
         output::{xdg::init_xdg_output_manager, Output},
         seat::{CursorImageStatus, KeyboardHandle, PointerHandle, Seat, XkbConfig},
         shell::xdg::decoration::{init_xdg_decoration_manager, XdgDecorationRequest},
         shm::init_shm_global,
         tablet_manager::{init_tablet_manager_global, TabletSeatTrait},
-        xdg_activation::{init_xdg_activation_global, XdgActivationEvent},
+        xdg_activation::{
+            init_xdg_activation_global, ActivationDecision, ActivationRequest, DefaultFocusStealingPolicy,
+            FocusStealingPolicy, UserInteraction, XdgActivationEvent,
+        },
     },
 };
 
 #[cfg(feature = "xwayland")]
-use smithay::xwayland::{XWayland, XWaylandEvent};
+use crate::xwayland::{X11State, XWaylandRestart};
+#[cfg(feature = "xwayland")]
+use smithay::{
+    wayland::{xwayland_keyboard_grab::init_xwayland_keyboard_grab_manager, SERIAL_COUNTER as SCOUNTER},
+    xwayland::{XWayland, XWaylandEvent},
+};
 
 use crate::shell::{init_shell, ShellHandles};
 
@@ -47,7 +61,13 @@ pub struct AnvilState<BackendData> {
     pub pointer: PointerHandle,
     pub keyboard: KeyboardHandle,
     pub suppressed_keys: Vec<u32>,
+    pub modifier_tap: Option<u32>,
+    pub last_interaction: Option<UserInteraction>,
     pub pointer_location: Point<f64, Logical>,
+    /// Surface under the stylus of a drawing tablet, whose damage is repainted right away
+    pub stylus_focus: Option<WlSurface>,
+    /// Hot corners and screen edges, running the contained command
+    pub screen_edges: ScreenEdges<String>,
     pub cursor_status: Arc<Mutex<CursorImageStatus>>,
     pub seat_name: String,
     pub seat: Seat,
@@ -55,6 +75,10 @@ pub struct AnvilState<BackendData> {
     // things we must keep alive
     #[cfg(feature = "xwayland")]
     pub xwayland: XWayland<AnvilState<BackendData>>,
+    #[cfg(feature = "xwayland")]
+    pub xwm: Option<Rc<RefCell<X11State>>>,
+    #[cfg(feature = "xwayland")]
+    pub xwayland_restart: XWaylandRestart,
 }
 
 impl<BackendData: Backend + 'static> AnvilState<BackendData> {
@@ -94,9 +118,11 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
         // Init the shell states
         let shells = init_shell::<BackendData>(display.clone(), log.clone());
         init_xdg_output_manager(&mut display.borrow_mut(), log.clone());
+        init_input_timestamps_manager(&mut display.borrow_mut(), log.clone());
+        let mut focus_stealing_policy = DefaultFocusStealingPolicy::default();
         init_xdg_activation_global(
             &mut display.borrow_mut(),
-            |state, req, mut ddata| {
+            move |state, req, mut ddata| {
                 let anvil_state = ddata.get::<AnvilState<BackendData>>().unwrap();
                 match req {
                     XdgActivationEvent::RequestActivation {
@@ -104,16 +130,28 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
                         token_data,
                         surface,
                     } => {
-                        if token_data.timestamp.elapsed().as_secs() < 10 {
-                            // Just grant the wish
-                            let mut space = anvil_state.space.borrow_mut();
-                            let w = space.window_for_surface(&surface).cloned();
-                            if let Some(window) = w {
-                                space.raise_window(&window, true);
+                        let focus = anvil_state.keyboard.current_focus();
+                        let request = ActivationRequest {
+                            token_data: &token_data,
+                            surface: &surface,
+                            focus: focus.as_ref(),
+                            last_interaction: anvil_state.last_interaction,
+                        };
+                        match focus_stealing_policy.decide(&request) {
+                            ActivationDecision::Activate => {
+                                let mut space = anvil_state.space.borrow_mut();
+                                let w = space.window_for_surface(&surface).cloned();
+                                if let Some(window) = w {
+                                    space.raise_window(&window, true);
+                                }
+                            }
+                            ActivationDecision::DemandAttention => {
+                                // anvil has no urgency hints, keep the request around for now
+                                info!(anvil_state.log, "Prevented focus stealing"; "token" => token.as_str());
+                            }
+                            ActivationDecision::Deny => {
+                                state.lock().unwrap().remove_request(&token);
                             }
-                        } else {
-                            // Discard the request
-                            state.lock().unwrap().remove_request(&token);
                         }
                     }
                     XdgActivationEvent::DestroyActivationRequest { .. } => {}
@@ -122,6 +160,30 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             log.clone(),
         );
 
+        #[cfg(feature = "xwayland")]
+        init_xwayland_keyboard_grab_manager(
+            &mut display.borrow_mut(),
+            |grab, _ddata| {
+                // Only XWayland may grab the keyboard, e.g. for virtual machines
+                let from_xwayland = grab
+                    .surface()
+                    .as_ref()
+                    .client()
+                    .map(|client| client.data_map().get::<Rc<RefCell<X11State>>>().is_some())
+                    .unwrap_or(false);
+                if !from_xwayland {
+                    return;
+                }
+                if let Some(keyboard) = Seat::from_resource(grab.seat()).and_then(|seat| seat.get_keyboard())
+                {
+                    let serial = SCOUNTER.next_serial();
+                    keyboard.set_focus(Some(grab.surface()), serial);
+                    keyboard.set_grab(grab, serial);
+                }
+            },
+            log.clone(),
+        );
+
         init_xdg_decoration_manager(
             &mut display.borrow_mut(),
             |req, _ddata| match req {
@@ -188,6 +250,10 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             // TODO: hide winit system cursor when relevant
             *cursor_status2.lock().unwrap() = new_status
         });
+        // refocus the pointer when windows move under it
+        space
+            .borrow_mut()
+            .track_pointer(&pointer, crate::input_handler::surface_under);
 
         init_tablet_manager_global(&mut display.borrow_mut());
 
@@ -203,6 +269,16 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             })
             .expect("Failed to initialize the keyboard");
 
+        let mut screen_edges = ScreenEdges::new(EdgeConfig::default());
+        if let Ok(launcher) = std::env::var("ANVIL_LAUNCHER") {
+            // hot corner, like tapping Super
+            screen_edges.add_trigger(EdgeTrigger {
+                dwell: Duration::from_millis(100),
+                pressure: 20.0,
+                ..EdgeTrigger::new(ScreenEdge::TopLeft, launcher)
+            });
+        }
+
         #[cfg(feature = "xwayland")]
         let xwayland = {
             let (xwayland, channel) = XWayland::new(handle.clone(), display.clone(), log.clone());
@@ -233,13 +309,47 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             pointer,
             keyboard,
             suppressed_keys: Vec::new(),
+            modifier_tap: None,
+            last_interaction: None,
             cursor_status,
             pointer_location: (0.0, 0.0).into(),
+            stylus_focus: None,
+            screen_edges,
             seat_name,
             seat,
             start_time: std::time::Instant::now(),
             #[cfg(feature = "xwayland")]
             xwayland,
+            #[cfg(feature = "xwayland")]
+            xwm: None,
+            #[cfg(feature = "xwayland")]
+            xwayland_restart: XWaylandRestart::default(),
+        }
+    }
+
+    /// Hands the damage of a window drawn on with a stylus to the backend, to repaint it right away
+    pub fn stylus_commit(&mut self, surface: &WlSurface) {
+        let root = |surface: &WlSurface| {
+            let mut root = surface.clone();
+            while let Some(parent) = get_parent(&root) {
+                root = parent;
+            }
+            root
+        };
+        let root = match self.stylus_focus {
+            Some(ref focus) if root(focus) == root(surface) => root(surface),
+            _ => return,
+        };
+
+        let space = self.space.borrow();
+        if let Some(window) = space.window_for_surface(&root) {
+            let location = space.window_location(window).unwrap() - window.geometry().loc;
+            let damage = window
+                .accumulated_damage(None)
+                .into_iter()
+                .map(|rect| Rectangle::from_loc_and_size(rect.loc + location, rect.size))
+                .collect::<Vec<_>>();
+            self.backend_data.stylus_damage(&damage);
         }
     }
 }
@@ -248,4 +358,5 @@ pub trait Backend {
     fn seat_name(&self) -> String;
     fn reset_buffers(&mut self, output: &Output);
     fn early_import(&mut self, surface: &WlSurface);
+    fn stylus_damage(&mut self, damage: &[Rectangle<i32, Logical>]);
 }
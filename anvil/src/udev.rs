@@ -1,5 +1,4 @@
 use std::{
-    borrow::Cow,
     cell::RefCell,
     collections::hash_map::{Entry, HashMap},
     os::unix::io::{AsRawFd, RawFd},
@@ -24,33 +23,29 @@ use smithay::{
 };
 use smithay::{
     backend::{
-        drm::{DrmDevice, DrmError, DrmEvent, DrmNode, GbmBufferedSurface, NodeType},
+        allocator::dmabuf::Dmabuf,
+        drm::{
+            connected_connectors, connector_crtcs, connector_name, DrmDevice, DrmEvent, DrmNode, FastRepaint,
+            GbmBufferedSurface, NodeType,
+        },
         egl::{EGLContext, EGLDevice, EGLDisplay},
-        libinput::{LibinputInputBackend, LibinputSessionInterface},
+        libinput::{DevicePowerPolicy, LibinputInputBackend, LibinputSessionInterface},
         renderer::{
             gles2::Gles2Renderbuffer,
             multigpu::{egl::EglGlesBackend, GpuManager, MultiRenderer, MultiTexture},
             Bind, Frame, ImportMem, Renderer,
         },
-        session::{auto::AutoSession, Session, Signal as SessionSignal},
-        udev::{all_gpus, primary_gpu, UdevBackend, UdevEvent},
-        SwapBuffersError,
+        session::{
+            auto::{AutoSession, SessionBackend},
+            Session, Signal as SessionSignal,
+        },
+        udev::{all_gpus_with_filter, primary_gpu_with_filter, DeviceFilter, UdevBackend, UdevEvent},
+        RecoveryHint, SwapBuffersError,
     },
     desktop::space::{RenderError, Space, SurfaceTree},
     reexports::{
-        calloop::{
-            timer::{TimeoutAction, Timer},
-            Dispatcher, EventLoop, LoopHandle, RegistrationToken,
-        },
-        drm::{
-            self,
-            control::{
-                connector::{Info as ConnectorInfo, State as ConnectorState},
-                crtc,
-                encoder::Info as EncoderInfo,
-                Device as ControlDevice,
-            },
-        },
+        calloop::{Dispatcher, EventLoop, LoopHandle, RegistrationToken},
+        drm::control::crtc,
         gbm::Device as GbmDevice,
         input::Libinput,
         nix::{fcntl::OFlag, sys::stat::dev_t},
@@ -61,10 +56,11 @@ use smithay::{
     },
     utils::{
         signaling::{Linkable, SignalToken, Signaler},
+        timer::{monotonic_time, DeadlineTimer, DeadlineTimerHandle},
         Logical, Point, Rectangle, Transform,
     },
     wayland::{
-        output::{Mode, Output, PhysicalProperties},
+        output::{config::OutputConfig, Mode, Output, PhysicalProperties},
         seat::CursorImageStatus,
     },
 };
@@ -102,6 +98,10 @@ pub struct UdevData {
     fps_texture: MultiTexture,
     signaler: Signaler<SessionSignal>,
     pointer_image: crate::cursor::Cursor,
+    output_config: OutputConfig,
+    // largest area in pixels repainted right away for a stylus, if enabled
+    fast_repaint_area: Option<i32>,
+    stylus_damage: Vec<Rectangle<i32, Logical>>,
     logger: slog::Logger,
 }
 
@@ -129,6 +129,12 @@ impl Backend for UdevData {
             warn!(self.logger, "Early buffer import failed: {}", err);
         }
     }
+
+    fn stylus_damage(&mut self, damage: &[Rectangle<i32, Logical>]) {
+        if self.fast_repaint_area.is_some() {
+            self.stylus_damage.extend_from_slice(damage);
+        }
+    }
 }
 
 pub fn run_udev(log: Logger) {
@@ -138,10 +144,10 @@ pub fn run_udev(log: Logger) {
     /*
      * Initialize session
      */
-    let (session, notifier) = match AutoSession::new(log.clone()) {
-        Some(ret) => ret,
-        None => {
-            crit!(log, "Could not initialize a session");
+    let (session, notifier) = match AutoSession::with_backends(&SessionBackend::DEFAULT_ORDER, log.clone()) {
+        Ok(ret) => ret,
+        Err(err) => {
+            crit!(log, "Could not initialize a session"; "error" => %err);
             return;
         }
     };
@@ -150,14 +156,21 @@ pub fn run_udev(log: Logger) {
     /*
      * Initialize the compositor
      */
+    // comma separated `ID_PATH`s of devices to ignore, e.g. `pci-0000:01:00.0`
+    let device_filter = DeviceFilter {
+        deny_id_paths: std::env::var("ANVIL_DENY_DRM_DEVICES")
+            .map(|var| var.split(',').map(String::from).collect())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
     let primary_gpu = if let Ok(var) = std::env::var("ANVIL_DRM_DEVICE") {
         DrmNode::from_path(var).expect("Invalid drm device path")
     } else {
-        primary_gpu(&session.seat())
+        primary_gpu_with_filter(session.seat(), |properties| device_filter.matches(properties))
             .unwrap()
             .and_then(|x| DrmNode::from_path(x).ok()?.node_with_type(NodeType::Render)?.ok())
             .unwrap_or_else(|| {
-                all_gpus(&session.seat())
+                all_gpus_with_filter(session.seat(), |properties| device_filter.matches(properties))
                     .unwrap()
                     .into_iter()
                     .find_map(|x| DrmNode::from_path(x).ok())
@@ -166,6 +179,18 @@ pub fn run_udev(log: Logger) {
     };
     info!(log, "Using {} as primary gpu.", primary_gpu);
 
+    let output_config = match std::env::var("ANVIL_OUTPUT_CONFIG") {
+        Ok(path) => OutputConfig::load(&path).unwrap_or_else(|err| {
+            error!(log, "Failed to load output configuration {}: {}", path, err);
+            OutputConfig::default()
+        }),
+        Err(_) => OutputConfig::default(),
+    };
+    // repaint the strokes of drawing tablets right away, if they are smaller than the given area
+    let fast_repaint_area = std::env::var("ANVIL_FAST_REPAINT")
+        .ok()
+        .and_then(|area| area.parse().ok());
+
     #[cfg_attr(not(feature = "egl"), allow(unused_mut))]
     let mut gpus = GpuManager::new(EglGlesBackend, log.clone()).unwrap();
     #[cfg_attr(not(feature = "egl"), allow(unused_mut))]
@@ -212,6 +237,9 @@ pub fn run_udev(log: Logger) {
         pointer_images: Vec::new(),
         #[cfg(feature = "debug")]
         fps_texture,
+        output_config,
+        fast_repaint_area,
+        stylus_damage: Vec::new(),
         logger: log.clone(),
     };
     let mut state = AnvilState::init(display.clone(), event_loop.handle(), data, log.clone(), true);
@@ -219,7 +247,11 @@ pub fn run_udev(log: Logger) {
     /*
      * Initialize the udev backend
      */
-    let udev_backend = match UdevBackend::new(&state.seat_name, log.clone()) {
+    let udev_backend = match UdevBackend::with_filter(
+        &state.seat_name,
+        move |properties| device_filter.matches(properties),
+        log.clone(),
+    ) {
         Ok(ret) => ret,
         Err(err) => {
             crit!(log, "Failed to initialize udev backend"; "error" => err);
@@ -240,6 +272,11 @@ pub fn run_udev(log: Logger) {
     libinput_context.udev_assign_seat(&state.seat_name).unwrap();
     let mut libinput_backend = LibinputInputBackend::new(libinput_context, log.clone());
     libinput_backend.link(session_signal);
+    // ignore touchpads while typing, if they support it
+    libinput_backend.set_power_policy(|_| DevicePowerPolicy {
+        disable_while_typing: Some(true),
+        disable_on_external_mouse: None,
+    });
 
     /*
      * Bind all our objects that get driven by the event loop
@@ -252,7 +289,10 @@ pub fn run_udev(log: Logger) {
         .unwrap();
     event_loop
         .handle()
-        .insert_source(notifier, |(), &mut (), _anvil_state| {})
+        .insert_source(notifier, {
+            let log = log.clone();
+            move |event, &mut (), _anvil_state| info!(log, "Session state changed"; "event" => ?event)
+        })
         .unwrap();
     for (dev, path) in udev_backend.device_list() {
         state.device_added(dev, path.into())
@@ -285,8 +325,8 @@ pub fn run_udev(log: Logger) {
     event_loop
         .handle()
         .insert_source(udev_backend, move |event, _, state| match event {
-            UdevEvent::Added { device_id, path } => state.device_added(device_id, path),
-            UdevEvent::Changed { device_id } => state.device_changed(device_id),
+            UdevEvent::Added { device_id, path, .. } => state.device_added(device_id, path),
+            UdevEvent::Changed { device_id, .. } => state.device_changed(device_id),
             UdevEvent::Removed { device_id } => state.device_removed(device_id),
         })
         .unwrap();
@@ -310,6 +350,10 @@ pub fn run_udev(log: Logger) {
         } else {
             state.space.borrow_mut().refresh();
             state.popups.borrow_mut().cleanup();
+            state.poll_screen_edges();
+            #[cfg(feature = "xwayland")]
+            state.update_xwayland_hints();
+            state.repaint_stylus_damage();
             display.borrow_mut().flush_clients(&mut state);
         }
     }
@@ -317,20 +361,56 @@ pub fn run_udev(log: Logger) {
 
 pub type RenderSurface = GbmBufferedSurface<Rc<RefCell<GbmDevice<SessionFd>>>, SessionFd>;
 
+// regular frames are rendered into the front buffer for this long after the last stroke of a stylus
+const FAST_REPAINT_LINGER: Duration = Duration::from_millis(500);
+
 struct SurfaceData {
     device_id: DrmNode,
     render_node: DrmNode,
     surface: RenderSurface,
     global: Option<Global<wl_output::WlOutput>>,
+    // renders again after a frame, if no vblank is going to trigger it
+    render_timer: DeadlineTimerHandle,
+    render_timer_token: RegistrationToken,
+    loop_handle: LoopHandle<'static, AnvilState<UdevData>>,
+    frame_duration: Duration,
+    fast_repaint: Option<FastRepaint>,
     #[cfg(feature = "debug")]
     fps: fps_ticker::Fps,
 }
 
+impl SurfaceData {
+    fn schedule_render(&self) -> std::io::Result<()> {
+        self.render_timer.set_timeout(self.frame_duration)
+    }
+
+    // returns the front buffer, if the stylus damage or the next frame is repainted into it
+    fn fast_repaint_buffer(&mut self, stylus: bool, now: Duration) -> Option<Dmabuf> {
+        let fast_repaint = self.fast_repaint.as_mut()?;
+        let repaint = if stylus {
+            fast_repaint.take().is_some()
+        } else {
+            fast_repaint.is_engaged(now)
+        };
+        if !repaint {
+            return None;
+        }
+        match self.surface.front_buffer() {
+            Ok(dmabuf) => Some(dmabuf),
+            Err(_) => {
+                fast_repaint.fall_back();
+                None
+            }
+        }
+    }
+}
+
 impl Drop for SurfaceData {
     fn drop(&mut self) {
         if let Some(global) = self.global.take() {
             global.destroy();
         }
+        self.loop_handle.remove(self.render_timer_token);
     }
 }
 
@@ -342,6 +422,7 @@ struct BackendData {
     event_dispatcher: Dispatcher<'static, DrmDevice<SessionFd>, AnvilState<UdevData>>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn scan_connectors(
     device_id: DrmNode,
     device: &DrmDevice<SessionFd>,
@@ -349,19 +430,19 @@ fn scan_connectors(
     display: &mut Display,
     space: &mut Space,
     signaler: &Signaler<SessionSignal>,
+    loop_handle: &LoopHandle<'static, AnvilState<UdevData>>,
+    output_config: &OutputConfig,
+    fast_repaint_area: Option<i32>,
     logger: &::slog::Logger,
 ) -> HashMap<crtc::Handle, Rc<RefCell<SurfaceData>>> {
-    // Get a set of all modesetting resource handles (excluding planes):
-    let res_handles = device.resource_handles().unwrap();
-
     // Find all connected output ports.
-    let connector_infos: Vec<ConnectorInfo> = res_handles
-        .connectors()
-        .iter()
-        .map(|conn| device.get_connector(*conn).unwrap())
-        .filter(|conn| conn.state() == ConnectorState::Connected)
-        .inspect(|conn| info!(logger, "Connected: {:?}", conn.interface()))
-        .collect();
+    let connector_infos = match connected_connectors(device) {
+        Ok(connector_infos) => connector_infos,
+        Err(err) => {
+            warn!(logger, "Failed to find connected connectors: {}", err);
+            return HashMap::new();
+        }
+    };
 
     let mut backends = HashMap::new();
 
@@ -380,16 +461,52 @@ fn scan_connectors(
 
     // very naive way of finding good crtc/encoder/connector combinations. This problem is np-complete
     for connector_info in connector_infos {
-        let encoder_infos = connector_info
-            .encoders()
+        let output_name = connector_name(&connector_info);
+        info!(logger, "Connected: {}", output_name);
+
+        let (phys_w, phys_h) = connector_info.size().unwrap_or((0, 0));
+        let output = Output::new(
+            output_name,
+            PhysicalProperties {
+                size: (phys_w as i32, phys_h as i32).into(),
+                subpixel: wl_output::Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Generic DRM".into(),
+            },
+            None,
+        );
+        let settings = output_config.settings_for(&output);
+        if settings.enabled == Some(false) {
+            info!(
+                logger,
+                "Output {} is disabled by the output configuration",
+                output.name()
+            );
+            continue;
+        }
+        if settings.vrr == Some(true) {
+            warn!(logger, "Variable refresh rate is not supported by anvil");
+        }
+        let modes = connector_info
+            .modes()
             .iter()
-            .flatten()
-            .flat_map(|encoder_handle| device.get_encoder(*encoder_handle))
-            .collect::<Vec<EncoderInfo>>();
+            .map(|mode| (*mode, Mode::from(*mode)))
+            .collect::<Vec<_>>();
+        let (drm_mode, mode) = settings
+            .mode
+            .and_then(|setting| {
+                let selected = setting.select(&modes.iter().map(|(_, mode)| *mode).collect::<Vec<_>>())?;
+                modes.iter().find(|(_, mode)| *mode == selected).copied()
+            })
+            .unwrap_or(modes[0]);
 
-        let crtcs = encoder_infos
-            .iter()
-            .flat_map(|encoder_info| res_handles.filter_crtcs(encoder_info.possible_crtcs()));
+        let crtcs = match connector_crtcs(device, &connector_info) {
+            Ok(crtcs) => crtcs,
+            Err(err) => {
+                warn!(logger, "Failed to find crtcs for {}: {}", output.name(), err);
+                continue;
+            }
+        };
 
         for crtc in crtcs {
             // Skip CRTCs used by previous connectors.
@@ -406,8 +523,7 @@ fn scan_connectors(
                 crtc,
             );
 
-            let mode = connector_info.modes()[0];
-            let mut surface = match device.create_surface(crtc, mode, &[connector_info.handle()]) {
+            let mut surface = match device.create_surface(crtc, drm_mode, &[connector_info.handle()]) {
                 Ok(surface) => surface,
                 Err(err) => {
                     warn!(logger, "Failed to create drm surface: {}", err);
@@ -416,7 +532,7 @@ fn scan_connectors(
             };
             surface.link(signaler.clone());
 
-            let gbm_surface =
+            let mut gbm_surface =
                 match GbmBufferedSurface::new(surface, gbm.clone(), formats.clone(), logger.clone()) {
                     Ok(renderer) => renderer,
                     Err(err) => {
@@ -424,38 +540,29 @@ fn scan_connectors(
                         continue;
                     }
                 };
+            // keep the output running, if the GPU runs out of memory
+            if let Err(err) = gbm_surface.set_degradation(Some(formats.clone())) {
+                warn!(logger, "Failed to enable buffer degradation: {}", err);
+            }
 
-            let size = mode.size();
-            let mode = Mode {
-                size: (size.0 as i32, size.1 as i32).into(),
-                refresh: mode.vrefresh() as i32 * 1000,
+            let frame_duration = match mode.refresh {
+                0 => Duration::from_millis(1000 / 60),
+                refresh => Duration::from_nanos(1_000_000_000_000 / refresh as u64),
             };
 
-            let interface_short_name = match connector_info.interface() {
-                drm::control::connector::Interface::DVII => Cow::Borrowed("DVI-I"),
-                drm::control::connector::Interface::DVID => Cow::Borrowed("DVI-D"),
-                drm::control::connector::Interface::DVIA => Cow::Borrowed("DVI-A"),
-                drm::control::connector::Interface::SVideo => Cow::Borrowed("S-VIDEO"),
-                drm::control::connector::Interface::DisplayPort => Cow::Borrowed("DP"),
-                drm::control::connector::Interface::HDMIA => Cow::Borrowed("HDMI-A"),
-                drm::control::connector::Interface::HDMIB => Cow::Borrowed("HDMI-B"),
-                drm::control::connector::Interface::EmbeddedDisplayPort => Cow::Borrowed("eDP"),
-                other => Cow::Owned(format!("{:?}", other)),
+            let (render_timer, render_timer_handle) = match DeadlineTimer::new() {
+                Ok(timer) => timer,
+                Err(err) => {
+                    warn!(logger, "Failed to create frame timer: {}", err);
+                    continue;
+                }
             };
+            let render_timer_token = loop_handle
+                .insert_source(render_timer, move |_, _, anvil_state| {
+                    anvil_state.render(device_id, Some(crtc))
+                })
+                .expect("failed to insert frame timer");
 
-            let output_name = format!("{}-{}", interface_short_name, connector_info.interface_id());
-
-            let (phys_w, phys_h) = connector_info.size().unwrap_or((0, 0));
-            let output = Output::new(
-                output_name,
-                PhysicalProperties {
-                    size: (phys_w as i32, phys_h as i32).into(),
-                    subpixel: wl_output::Subpixel::Unknown,
-                    make: "Smithay".into(),
-                    model: "Generic DRM".into(),
-                },
-                None,
-            );
             let global = output.create_global(display);
             let position = (
                 space
@@ -465,8 +572,9 @@ fn scan_connectors(
             )
                 .into();
             output.change_current_state(Some(mode), None, None, Some(position));
-            output.set_preferred(mode);
-            space.map_output(&output, position);
+            output.set_preferred(modes[0].1);
+            settings.apply(&output);
+            space.map_output(&output, output.current_location());
 
             output
                 .user_data()
@@ -477,6 +585,11 @@ fn scan_connectors(
                 render_node,
                 surface: gbm_surface,
                 global: Some(global),
+                render_timer: render_timer_handle,
+                render_timer_token,
+                loop_handle: loop_handle.clone(),
+                frame_duration,
+                fast_repaint: fast_repaint_area.map(|area| FastRepaint::new(area, FAST_REPAINT_LINGER)),
                 #[cfg(feature = "debug")]
                 fps: fps_ticker::Fps::default(),
             })));
@@ -533,6 +646,9 @@ impl AnvilState<UdevData> {
             &mut *self.display.borrow_mut(),
             &mut *self.space.borrow_mut(),
             &self.backend_data.signaler,
+            &self.handle,
+            &self.backend_data.output_config,
+            self.backend_data.fast_repaint_area,
             &self.log,
         )));
 
@@ -618,6 +734,9 @@ impl AnvilState<UdevData> {
                 &mut *self.display.borrow_mut(),
                 &mut *space,
                 &signaler,
+                &loop_handle,
+                &self.backend_data.output_config,
+                self.backend_data.fast_repaint_area,
                 &logger,
             );
 
@@ -644,18 +763,29 @@ impl AnvilState<UdevData> {
             debug!(self.log, "Surfaces dropped");
             let mut space = self.space.borrow_mut();
 
-            for output in space
-                .outputs()
-                .filter(|o| {
-                    o.user_data()
-                        .get::<UdevOutputId>()
-                        .map(|id| id.device_id == node)
-                        .unwrap_or(false)
-                })
-                .cloned()
-                .collect::<Vec<_>>()
-                .into_iter()
-            {
+            let (removed, remaining): (Vec<_>, Vec<_>) = space.outputs().cloned().partition(|o| {
+                o.user_data()
+                    .get::<UdevOutputId>()
+                    .map(|id| id.device_id == node)
+                    .unwrap_or(false)
+            });
+            // keep the windows of the removed outputs where they were relative to their output
+            if let Some(target) = remaining.first() {
+                let windows = space
+                    .windows()
+                    .filter(|w| {
+                        space
+                            .primary_output(w)
+                            .map(|o| removed.contains(&o))
+                            .unwrap_or(false)
+                    })
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for window in windows {
+                    space.move_window_to_output(&window, target);
+                }
+            }
+            for output in removed {
                 space.unmap_output(&output);
             }
             crate::shell::fixup_positions(&mut *space);
@@ -669,6 +799,64 @@ impl AnvilState<UdevData> {
 
     // If crtc is `Some()`, render it, else render all crtcs
     fn render(&mut self, dev_id: DrmNode, crtc: Option<crtc::Handle>) {
+        self.render_surfaces(dev_id, crtc, false)
+    }
+
+    // Repaints the damage of windows drawn on with a stylus right away, ahead of the next frame
+    fn repaint_stylus_damage(&mut self) {
+        if self.backend_data.stylus_damage.is_empty() {
+            return;
+        }
+        let damage = std::mem::take(&mut self.backend_data.stylus_damage);
+        let now = monotonic_time();
+
+        let mut to_repaint = Vec::new();
+        let space = self.space.borrow();
+        for output in space.outputs() {
+            let id = match output.user_data().get::<UdevOutputId>() {
+                Some(id) => id,
+                None => continue,
+            };
+            let surface = match self.backend_data.backends.get(&id.device_id) {
+                Some(backend) => match backend.surfaces.borrow().get(&id.crtc) {
+                    Some(surface) => surface.clone(),
+                    None => continue,
+                },
+                None => continue,
+            };
+            let mut surface = surface.borrow_mut();
+            let fast_repaint = match surface.fast_repaint.as_mut() {
+                Some(fast_repaint) => fast_repaint,
+                None => continue,
+            };
+
+            let output_geometry = space.output_geometry(output).unwrap();
+            let scale = output.current_scale().fractional_scale();
+            fast_repaint.damage(
+                damage
+                    .iter()
+                    .filter_map(|rect| rect.intersection(output_geometry))
+                    .map(|rect| {
+                        Rectangle::from_loc_and_size(rect.loc - output_geometry.loc, rect.size)
+                            .to_f64()
+                            .to_physical(scale)
+                            .to_i32_up()
+                    }),
+                now,
+            );
+            if fast_repaint.has_damage() {
+                to_repaint.push((id.device_id, id.crtc));
+            }
+        }
+        drop(space);
+
+        for (device_id, crtc) in to_repaint {
+            self.render_surfaces(device_id, Some(crtc), true);
+        }
+    }
+
+    // Renders into the front buffer only, if `stylus` is set
+    fn render_surfaces(&mut self, dev_id: DrmNode, crtc: Option<crtc::Handle>, stylus: bool) {
         let device_backend = match self.backend_data.backends.get_mut(&dev_id) {
             Some(backend) => backend,
             None => {
@@ -732,37 +920,26 @@ impl AnvilState<UdevData> {
                 &self.backend_data.fps_texture,
                 &*self.dnd_icon.lock().unwrap(),
                 &mut *self.cursor_status.lock().unwrap(),
+                stylus,
                 &self.log,
             );
             let reschedule = match result {
                 Ok(has_rendered) => !has_rendered,
                 Err(err) => {
                     warn!(self.log, "Error during rendering: {:?}", err);
-                    match err {
-                        SwapBuffersError::AlreadySwapped => false,
-                        SwapBuffersError::TemporaryFailure(err) => !matches!(
-                            err.downcast_ref::<DrmError>(),
-                            Some(&DrmError::DeviceInactive)
-                                | Some(&DrmError::Access {
-                                    source: drm::SystemError::PermissionDenied,
-                                    ..
-                                })
-                        ),
-                        SwapBuffersError::ContextLost(err) => panic!("Rendering loop lost: {}", err),
+                    match err.recovery_hint() {
+                        RecoveryHint::Retry => true,
+                        // rendering resumes, once the session is active again
+                        RecoveryHint::None | RecoveryHint::WaitForDevice => false,
+                        RecoveryHint::Recreate => panic!("Rendering loop lost: {}", err),
                     }
                 }
             };
 
             if reschedule {
-                let timer = Timer::from_duration(Duration::from_millis(
-                    1000 /*a seconds*/ / 60, /*refresh rate*/
-                ));
-                self.handle
-                    .insert_source(timer, move |_, _, anvil_state| {
-                        anvil_state.render(dev_id, Some(crtc));
-                        TimeoutAction::Drop
-                    })
-                    .expect("failed to schedule frame timer");
+                if let Err(err) = surface.borrow().schedule_render() {
+                    warn!(self.log, "Failed to schedule frame: {}", err);
+                }
             }
 
             // Send frame events so that client start drawing their next frame
@@ -784,9 +961,13 @@ fn render_surface(
     #[cfg(feature = "debug")] fps_texture: &MultiTexture,
     dnd_icon: &Option<wl_surface::WlSurface>,
     cursor_status: &mut CursorImageStatus,
+    stylus: bool,
     logger: &slog::Logger,
 ) -> Result<bool, SwapBuffersError> {
-    surface.surface.frame_submitted()?;
+    // repaints of the stylus damage do not follow a vblank
+    if !stylus {
+        surface.surface.frame_submitted()?;
+    }
 
     let output = if let Some(output) = space.outputs().find(|o| {
         o.user_data().get::<UdevOutputId>()
@@ -802,7 +983,25 @@ fn render_surface(
     };
     let output_geometry = space.output_geometry(&output).unwrap();
 
-    let (dmabuf, age) = surface.surface.next_buffer()?;
+    let front_buffer = surface.fast_repaint_buffer(stylus, monotonic_time());
+    let (dmabuf, age) = match front_buffer {
+        // the front buffer holds the last frame
+        Some(ref dmabuf) => (dmabuf.clone(), 1),
+        // leave the damage to the next regular frame
+        None if stylus => return Ok(true),
+        None => {
+            let (dmabuf, age) = surface.surface.next_buffer()?;
+            for degradation in surface.surface.take_degradations() {
+                warn!(
+                    logger,
+                    "Buffer allocation of {} failed, degraded to {:?}",
+                    output.name(),
+                    degradation
+                );
+            }
+            (dmabuf, age)
+        }
+    };
     renderer.bind(dmabuf)?;
 
     let mut elements: Vec<CustomElem> = Vec::new();
@@ -846,21 +1045,48 @@ fn render_surface(
 
     // and draw to our buffer
     // TODO we can pass the damage rectangles inside a AtomicCommitRequest
-    let render_res = crate::render::render_output(&output, space, renderer, age.into(), &*elements, logger)
-        .map(|x| x.is_some());
+    let damage = crate::render::render_output(&output, space, renderer, age.into(), &*elements, logger)
+        .map_err(|err| match err {
+            RenderError::Rendering(err) => SwapBuffersError::from(err),
+            _ => unreachable!(),
+        })?;
+
+    if front_buffer.is_some() {
+        if let Some(damage) = damage {
+            let transform: Transform = output.current_transform().into();
+            let scale = output.current_scale().fractional_scale();
+            let size = transform.transform_size(output.current_mode().unwrap().size);
+            let damage = damage
+                .into_iter()
+                .map(|rect| {
+                    transform
+                        .invert()
+                        .transform_rect_in(rect.to_f64().to_physical(scale).to_i32_up(), &size)
+                })
+                .collect::<Vec<_>>();
+            surface
+                .surface
+                .front_buffer_damaged(&damage)
+                .map_err(Into::<SwapBuffersError>::into)?;
+        }
+        if stylus {
+            let fast_repaint = surface.fast_repaint.as_mut().unwrap();
+            fast_repaint.repainted(monotonic_time());
+            trace!(logger, "Repainted stylus damage"; "latency" => ?fast_repaint.stats().last_latency);
+        }
+        // no page flip is going to trigger the next frame
+        return Ok(stylus);
+    }
 
-    match render_res.map_err(|err| match err {
-        RenderError::Rendering(err) => err.into(),
-        _ => unreachable!(),
-    }) {
-        Ok(true) => {
+    match damage {
+        Some(_) => {
             surface
                 .surface
                 .queue_buffer()
                 .map_err(Into::<SwapBuffersError>::into)?;
             Ok(true)
         }
-        x => x,
+        None => Ok(false),
     }
 }
 
@@ -877,9 +1103,10 @@ fn schedule_initial_render(
         initial_render(&mut surface.surface, &mut renderer)
     };
     if let Err(err) = result {
-        match err {
-            SwapBuffersError::AlreadySwapped => {}
-            SwapBuffersError::TemporaryFailure(err) => {
+        match err.recovery_hint() {
+            // everything is rendered again, once the session is active again
+            RecoveryHint::None | RecoveryHint::WaitForDevice => {}
+            RecoveryHint::Retry => {
                 // TODO dont reschedule after 3(?) retries
                 warn!(logger, "Failed to submit page_flip: {}", err);
                 let handle = evt_handle.clone();
@@ -887,7 +1114,7 @@ fn schedule_initial_render(
                     schedule_initial_render(&mut data.backend_data.gpus, surface, &handle, logger)
                 });
             }
-            SwapBuffersError::ContextLost(err) => panic!("Rendering loop lost: {}", err),
+            RecoveryHint::Recreate => panic!("Rendering loop lost: {}", err),
         }
     }
 }
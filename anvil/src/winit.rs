@@ -24,6 +24,7 @@ use smithay::{
             Display,
         },
     },
+    utils::{Logical, Rectangle},
     wayland::{
         output::{Mode, Output, PhysicalProperties},
         seat::CursorImageStatus,
@@ -53,6 +54,7 @@ impl Backend for WinitData {
         self.full_redraw = 4;
     }
     fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+    fn stylus_damage(&mut self, _damage: &[Rectangle<i32, Logical>]) {}
 }
 
 pub fn run_winit(log: Logger) {
@@ -176,6 +178,22 @@ pub fn run_winit(log: Logger) {
 
                 WinitEvent::Input(event) => state.process_input_event_windowed(event, OUTPUT_NAME),
 
+                // Use the keymap of the host, so keys match the layout of the user
+                WinitEvent::Keymap(keymap) => {
+                    if let Err(err) = state.keyboard.set_keymap_from_string(keymap) {
+                        warn!(log, "Failed to apply the keymap of the host: {}", err);
+                    }
+                }
+
+                WinitEvent::ModifiersChanged {
+                    depressed,
+                    latched,
+                    locked,
+                    layout,
+                } => state
+                    .keyboard
+                    .set_modifier_masks(depressed, latched, locked, layout),
+
                 _ => (),
             })
             .is_err()
@@ -259,7 +277,7 @@ pub fn run_winit(log: Logger) {
                     backend.window().set_cursor_visible(cursor_visible);
                 }
                 Ok(None) => backend.window().set_cursor_visible(cursor_visible),
-                Err(SwapBuffersError::ContextLost(err)) => {
+                Err(err @ SwapBuffersError::ContextLost { .. }) => {
                     error!(log, "Critical Rendering Error: {}", err);
                     state.running.store(false, Ordering::SeqCst);
                 }
@@ -281,6 +299,8 @@ pub fn run_winit(log: Logger) {
         } else {
             state.space.borrow_mut().refresh();
             state.popups.borrow_mut().cleanup();
+            #[cfg(feature = "xwayland")]
+            state.update_xwayland_hints();
             display.borrow_mut().flush_clients(&mut state);
         }
 
@@ -26,6 +26,7 @@ use smithay::{
             Display,
         },
     },
+    utils::{Logical, Rectangle},
     wayland::{
         output::{Mode, Output, PhysicalProperties},
         seat::CursorImageStatus,
@@ -53,6 +54,7 @@ impl Backend for X11Data {
         self.surface.reset_buffers();
     }
     fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+    fn stylus_damage(&mut self, _damage: &[Rectangle<i32, Logical>]) {}
 }
 
 pub fn run_x11(log: Logger) {
@@ -152,6 +154,17 @@ pub fn run_x11(log: Logger) {
     };
 
     let mut state = AnvilState::init(display.clone(), event_loop.handle(), data, log.clone(), true);
+
+    // Use the keymap of the host X server, so keys match the layout of the user
+    match handle.xkb_rule_names() {
+        Ok(Some(names)) => {
+            if let Err(err) = state.keyboard.set_xkb_config(names.as_xkb_config()) {
+                warn!(log, "Failed to apply the keymap of the X server: {}", err);
+            }
+        }
+        Ok(None) => {}
+        Err(err) => warn!(log, "Failed to query the keymap of the X server: {}", err),
+    }
     let output = Output::new(
         OUTPUT_NAME.to_string(),
         PhysicalProperties {
@@ -193,6 +206,15 @@ pub fn run_x11(log: Logger) {
                 state.backend_data.render = true;
             }
             X11Event::Input(event) => state.process_input_event_windowed(event, OUTPUT_NAME),
+            X11Event::ModifiersChanged {
+                depressed,
+                latched,
+                locked,
+                layout,
+                ..
+            } => state
+                .keyboard
+                .set_modifier_masks(depressed, latched, locked, layout),
         })
         .expect("Failed to insert X11 Backend into event loop");
 
@@ -298,6 +320,8 @@ pub fn run_x11(log: Logger) {
         } else {
             state.space.borrow_mut().refresh();
             state.popups.borrow_mut().cleanup();
+            #[cfg(feature = "xwayland")]
+            state.update_xwayland_hints();
             display.borrow_mut().flush_clients(&mut state);
         }
     }
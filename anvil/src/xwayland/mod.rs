@@ -1,11 +1,17 @@
 use std::{
-    cell::RefCell, collections::HashMap, convert::TryFrom, os::unix::net::UnixStream, rc::Rc, sync::Arc,
+    cell::RefCell,
+    collections::HashMap,
+    convert::TryFrom,
+    os::unix::net::UnixStream,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 use smithay::{
-    desktop::{Kind, Space, Window, X11Surface},
+    desktop::{Kind, OverrideRedirectWindow, Space, Window, WindowIcon, X11Surface},
     reexports::wayland_server::{protocol::wl_surface::WlSurface, Client},
-    utils::{x11rb::X11Source, Logical, Point},
+    utils::{x11rb::X11Source, Logical, Point, Rectangle, Size},
     wayland::compositor::give_role,
 };
 
@@ -14,13 +20,15 @@ use x11rb::{
     errors::ReplyOrIdError,
     protocol::{
         composite::{ConnectionExt as _, Redirect},
+        randr::{ConnectionExt as _, MonitorInfo, Notify, NotifyMask},
         xproto::{
-            ChangeWindowAttributesAux, ConfigWindow, ConfigureWindowAux, ConnectionExt as _, EventMask,
-            Window as X11Window, WindowClass,
+            AtomEnum, ChangeWindowAttributesAux, ConfigWindow, ConfigureWindowAux, ConnectionExt as _,
+            EventMask, PropMode, Window as X11Window, WindowClass,
         },
         Event,
     },
     rust_connection::{DefaultStream, RustConnection},
+    wrapper::ConnectionExt as _,
 };
 
 use crate::AnvilState;
@@ -33,9 +41,13 @@ impl<BackendData: 'static> AnvilState<BackendData> {
     }
 
     pub fn xwayland_ready(&mut self, connection: UnixStream, client: Client) {
-        let (wm, source) = X11State::start_wm(connection, self.space.clone(), self.log.clone()).unwrap();
+        let restore = std::mem::take(&mut self.xwayland_restart.placements);
+        let (wm, source) =
+            X11State::start_wm(connection, self.space.clone(), restore, self.log.clone()).unwrap();
         let wm = Rc::new(RefCell::new(wm));
         client.data_map().insert_if_missing(|| Rc::clone(&wm));
+        self.xwm = Some(wm.clone());
+        self.update_xwayland_hints();
         let log = self.log.clone();
         self.handle
             .insert_source(source, move |event, _, _| {
@@ -49,7 +61,83 @@ impl<BackendData: 'static> AnvilState<BackendData> {
 
     pub fn xwayland_exited(&mut self) {
         error!(self.log, "Xwayland crashed");
+        if let Some(wm) = self.xwm.take() {
+            let mut wm = wm.borrow_mut();
+            let mut placements = wm
+                .placements
+                .drain()
+                .map(|(_, placement)| placement)
+                .collect::<Vec<_>>();
+            placements.sort_by_key(|placement| placement.stacking);
+            // windows of the previous session, that did not come back, stay at the bottom
+            let mut restore = std::mem::take(&mut wm.restore);
+            restore.extend(placements);
+            self.xwayland_restart.placements = restore;
+        }
+
+        // X11 clients die with XWayland, but clients started again, e.g. by a session manager,
+        // get their windows back where they were
+        let now = Instant::now();
+        let crashes = &mut self.xwayland_restart.crashes;
+        crashes.retain(|time| now.duration_since(*time) < CRASH_INTERVAL);
+        crashes.push(now);
+        if crashes.len() > MAX_CRASHES {
+            error!(
+                self.log,
+                "Xwayland crashed {} times within {:?}, not restarting it",
+                crashes.len(),
+                CRASH_INTERVAL
+            );
+            return;
+        }
+        info!(self.log, "Restarting Xwayland");
+        self.start_xwayland();
     }
+
+    /// Updates the geometry related hints of the X11 root window and remembers the placement
+    /// of X11 windows, if XWayland is running.
+    ///
+    /// The hints are only sent to XWayland, if they changed or if XWayland created new RandR outputs
+    /// since they were last sent, so this can be called on every iteration of the event loop.
+    pub fn update_xwayland_hints(&mut self) {
+        if let Some(wm) = self.xwm.as_ref() {
+            let mut wm = wm.borrow_mut();
+            if let Err(err) = wm.update_geometry_hints() {
+                warn!(self.log, "Failed to update X11 geometry hints: {}", err);
+            }
+            wm.track_placements();
+        }
+    }
+}
+
+/// Number of crashes within [`CRASH_INTERVAL`] after which XWayland is not restarted anymore
+const MAX_CRASHES: usize = 3;
+const CRASH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What is kept of crashed XWayland sessions
+#[derive(Debug, Default)]
+pub struct XWaylandRestart {
+    /// Times of the recent crashes
+    crashes: Vec<Instant>,
+    /// Placement of the windows of the crashed session, back to front
+    placements: Vec<WindowPlacement>,
+}
+
+/// Properties used to recognize the window of a restarted X11 client
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WindowIdentity {
+    /// Raw `WM_CLASS`, holding the instance and class names
+    class: Vec<u8>,
+    /// Raw `WM_NAME` at the time the window was mapped
+    title: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+struct WindowPlacement {
+    identity: WindowIdentity,
+    location: Point<i32, Logical>,
+    /// Index in the z-order of the space, back to front
+    stacking: usize,
 }
 
 x11rb::atom_manager! {
@@ -57,22 +145,62 @@ x11rb::atom_manager! {
         WM_S0,
         WL_SURFACE_ID,
         _ANVIL_CLOSE_CONNECTION,
+        _NET_SUPPORTED,
+        _NET_DESKTOP_GEOMETRY,
+        _NET_WORKAREA,
+        _NET_WM_ICON,
     }
 }
 
+/// Geometry related hints advertised to X11 clients.
+#[derive(Debug, Clone, PartialEq)]
+struct GeometryHints {
+    /// Size of the whole desktop, spanning all outputs
+    desktop_size: Size<i32, Logical>,
+    /// Area of the primary output not covered by the exclusive zones of layer surfaces
+    workarea: Rectangle<i32, Logical>,
+    /// Name of the primary output
+    primary_output: Option<String>,
+    /// RandR 1.5 monitors, one per output
+    monitors: Vec<MonitorHint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MonitorHint {
+    /// Name of the output, XWayland uses the same name for its RandR output
+    name: String,
+    geometry: Rectangle<i32, Logical>,
+    /// Physical size in millimeters
+    size_mm: (i32, i32),
+}
+
 /// The actual runtime state of the XWayland integration.
-struct X11State {
+#[derive(Debug)]
+pub struct X11State {
     conn: Arc<RustConnection>,
     atoms: Atoms,
     log: slog::Logger,
-    unpaired_surfaces: HashMap<u32, (X11Window, Point<i32, Logical>)>,
+    unpaired_surfaces: HashMap<u32, (X11Window, Point<i32, Logical>, bool)>,
+    override_redirect: HashMap<X11Window, OverrideRedirectWindow>,
+    windows: HashMap<X11Window, WlSurface>,
     space: Rc<RefCell<Space>>,
+    /// Hints last sent to XWayland, even if some of its outputs were unknown to it
+    geometry_hints: Option<GeometryHints>,
+    /// Whether XWayland lacked the RandR output of a monitor when the hints were last sent
+    geometry_hints_incomplete: bool,
+    /// Last known placement of the mapped windows, kept after their surfaces died with XWayland
+    placements: HashMap<X11Window, WindowPlacement>,
+    /// Placements of a crashed session, waiting for their windows to come back
+    restore: Vec<WindowPlacement>,
+    /// Restored windows by their stacking index in the crashed session
+    restored: Vec<(usize, Window)>,
 }
 
 impl X11State {
     fn start_wm(
         connection: UnixStream,
         space: Rc<RefCell<Space>>,
+        restore: Vec<WindowPlacement>,
         log: slog::Logger,
     ) -> Result<(Self, X11Source), Box<dyn std::error::Error>> {
         // Create an X11 connection. XWayland only uses screen 0.
@@ -86,7 +214,8 @@ impl X11State {
         // Actually become the WM by redirecting some operations
         conn.change_window_attributes(
             screen.root,
-            &ChangeWindowAttributesAux::default().event_mask(EventMask::SUBSTRUCTURE_REDIRECT),
+            &ChangeWindowAttributesAux::default()
+                .event_mask(EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY),
         )?;
 
         // Tell XWayland that we are the WM by acquiring the WM_S0 selection. No X11 clients are accepted before this.
@@ -107,6 +236,23 @@ impl X11State {
         )?;
         conn.set_selection_owner(win, atoms.WM_S0, x11rb::CURRENT_TIME)?;
 
+        // Advertise the EWMH hints we are going to maintain
+        conn.change_property32(
+            PropMode::REPLACE,
+            screen.root,
+            atoms._NET_SUPPORTED,
+            AtomEnum::ATOM,
+            &[atoms._NET_DESKTOP_GEOMETRY, atoms._NET_WORKAREA],
+        )?;
+
+        // RandR 1.5 is needed to publish the monitor layout
+        conn.randr_query_version(1, 5)?.reply()?;
+        // get notified once XWayland creates the RandR outputs of new wl_outputs
+        conn.randr_select_input(
+            screen.root,
+            NotifyMask::OUTPUT_CHANGE | NotifyMask::RESOURCE_CHANGE,
+        )?;
+
         // XWayland wants us to do this to function properly...?
         conn.composite_redirect_subwindows(screen.root, Redirect::MANUAL)?;
 
@@ -117,8 +263,15 @@ impl X11State {
             conn: Arc::clone(&conn),
             atoms,
             unpaired_surfaces: Default::default(),
+            override_redirect: Default::default(),
+            windows: Default::default(),
             space,
             log: log.clone(),
+            geometry_hints: None,
+            geometry_hints_incomplete: false,
+            placements: Default::default(),
+            restore,
+            restored: Vec::new(),
         };
 
         Ok((wm, X11Source::new(conn, win, atoms._ANVIL_CLOSE_CONNECTION, log)))
@@ -157,6 +310,41 @@ impl X11State {
                 // Just grant the wish
                 self.conn.map_window(r.window)?;
             }
+            Event::ConfigureNotify(n) => {
+                // Override-redirect windows move themselves, so we just follow along
+                let location = (n.x as i32, n.y as i32).into();
+                if let Some(window) = self.override_redirect.get(&n.window) {
+                    window.set_location(location);
+                } else if let Some((_, unpaired_location, _)) = self
+                    .unpaired_surfaces
+                    .values_mut()
+                    .find(|(window, _, _)| *window == n.window)
+                {
+                    // the surface is not known yet, so keep the location up to date for when it is
+                    *unpaired_location = location;
+                }
+            }
+            Event::PropertyNotify(n) if n.atom == self.atoms._NET_WM_ICON => self.update_icon(n.window),
+            Event::RandrNotify(n)
+                if self.geometry_hints_incomplete
+                    && (n.sub_code == Notify::OUTPUT_CHANGE || n.sub_code == Notify::RESOURCE_CHANGE) =>
+            {
+                // retry with the new outputs on the next update
+                self.geometry_hints = None;
+            }
+            Event::UnmapNotify(n) => {
+                if let Some(window) = self.override_redirect.remove(&n.window) {
+                    self.space.borrow_mut().unmap_override_redirect(&window);
+                }
+                self.placements.remove(&n.window);
+            }
+            Event::DestroyNotify(n) => {
+                if let Some(window) = self.override_redirect.remove(&n.window) {
+                    self.space.borrow_mut().unmap_override_redirect(&window);
+                }
+                self.windows.remove(&n.window);
+                self.placements.remove(&n.window);
+            }
             Event::ClientMessage(msg) => {
                 if msg.type_ == self.atoms.WL_SURFACE_ID {
                     // We get a WL_SURFACE_ID message when Xwayland creates a WlSurface for a
@@ -180,6 +368,12 @@ impl X11State {
                         }
                     };
 
+                    // Menus and tooltips bypass the WM and need to be handled differently
+                    let override_redirect = match self.conn.get_window_attributes(msg.window)?.reply() {
+                        Ok(attrs) => attrs.override_redirect,
+                        Err(_) => false,
+                    };
+
                     let id = msg.data.as_data32()[0];
                     let surface = client.get_resource::<WlSurface>(id);
                     info!(
@@ -188,9 +382,10 @@ impl X11State {
                     );
                     match surface {
                         None => {
-                            self.unpaired_surfaces.insert(id, (msg.window, location));
+                            self.unpaired_surfaces
+                                .insert(id, (msg.window, location, override_redirect));
                         }
-                        Some(surface) => self.new_window(msg.window, surface, location),
+                        Some(surface) => self.new_window(msg.window, surface, location, override_redirect),
                     }
                 }
             }
@@ -200,7 +395,213 @@ impl X11State {
         Ok(())
     }
 
-    fn new_window(&mut self, window: X11Window, surface: WlSurface, location: Point<i32, Logical>) {
+    fn update_geometry_hints(&mut self) -> Result<(), ReplyOrIdError> {
+        let hints = {
+            let space = self.space.borrow();
+            let mut desktop = Rectangle::<i32, Logical>::default();
+            let mut monitors = Vec::new();
+            for output in space.outputs() {
+                let geometry = match space.output_geometry(output) {
+                    Some(geometry) => geometry,
+                    None => continue,
+                };
+                desktop = desktop.merge(geometry);
+                let size = output.physical_properties().size;
+                monitors.push(MonitorHint {
+                    name: output.name(),
+                    geometry,
+                    size_mm: (size.w, size.h),
+                });
+            }
+            // anvil places new windows on the first output, so treat it as primary
+            let primary = space.outputs().next();
+            // X11 clients maximize to the workarea, so it must not span outputs with different
+            // exclusive zones. Use the one of the primary output, like other compositors.
+            let workarea = primary.and_then(|output| space.usable_area(output));
+            GeometryHints {
+                desktop_size: (desktop.loc.x + desktop.size.w, desktop.loc.y + desktop.size.h).into(),
+                workarea: workarea.unwrap_or(desktop),
+                primary_output: primary.map(|output| output.name()),
+                monitors,
+            }
+        };
+
+        if self.geometry_hints.as_ref() == Some(&hints) {
+            return Ok(());
+        }
+        debug!(self.log, "Updating X11 geometry hints"; "hints" => ?hints);
+        // remembered right away, so failures are not retried on every call either
+        self.geometry_hints = Some(hints.clone());
+        self.geometry_hints_incomplete = false;
+
+        let root = self.conn.setup().roots[0].root;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            root,
+            self.atoms._NET_DESKTOP_GEOMETRY,
+            AtomEnum::CARDINAL,
+            &[hints.desktop_size.w as u32, hints.desktop_size.h as u32],
+        )?;
+        // anvil has no workspaces, so there is only one desktop to provide a workarea for
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            root,
+            self.atoms._NET_WORKAREA,
+            AtomEnum::CARDINAL,
+            &[
+                hints.workarea.loc.x as u32,
+                hints.workarea.loc.y as u32,
+                hints.workarea.size.w as u32,
+                hints.workarea.size.h as u32,
+            ],
+        )?;
+
+        // XWayland creates a RandR output for every wl_output using the output name,
+        // but only once it has received the output, which might happen after we got here.
+        let resources = self.conn.randr_get_screen_resources_current(root)?.reply()?;
+        let mut randr_outputs = HashMap::new();
+        for output in resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+            randr_outputs.insert(info.name, output);
+        }
+
+        let mut complete = true;
+        for monitor in &hints.monitors {
+            let output = match randr_outputs.get(monitor.name.as_bytes()) {
+                Some(output) => *output,
+                None => {
+                    complete = false;
+                    continue;
+                }
+            };
+            let primary = hints.primary_output.as_ref() == Some(&monitor.name);
+            if primary {
+                self.conn.randr_set_output_primary(root, output)?;
+            }
+            let name = self
+                .conn
+                .intern_atom(false, monitor.name.as_bytes())?
+                .reply()?
+                .atom;
+            self.conn.randr_set_monitor(
+                root,
+                MonitorInfo {
+                    name,
+                    primary,
+                    automatic: false,
+                    x: monitor.geometry.loc.x as i16,
+                    y: monitor.geometry.loc.y as i16,
+                    width: monitor.geometry.size.w as u16,
+                    height: monitor.geometry.size.h as u16,
+                    width_in_millimeters: monitor.size_mm.0 as u32,
+                    height_in_millimeters: monitor.size_mm.1 as u32,
+                    outputs: vec![output],
+                },
+            )?;
+        }
+
+        self.conn.flush()?;
+        if !complete {
+            // try again, once XWayland announces new outputs
+            trace!(self.log, "Not all outputs are known to XWayland yet");
+            self.geometry_hints_incomplete = true;
+        }
+        Ok(())
+    }
+
+    /// Updates the placement of windows still alive, so it survives a crash of XWayland
+    fn track_placements(&mut self) {
+        let space = self.space.borrow();
+        let stack = space.windows().collect::<Vec<_>>();
+        for (window, surface) in &self.windows {
+            if !surface.as_ref().is_alive() {
+                continue;
+            }
+            let placement = match self.placements.get_mut(window) {
+                Some(placement) => placement,
+                None => continue,
+            };
+            let mapped = stack
+                .iter()
+                .enumerate()
+                .find_map(|(stacking, w)| match w.toplevel() {
+                    Kind::X11(x11surface) if &x11surface.surface == surface => {
+                        space.window_location(w).map(|location| (stacking, location))
+                    }
+                    _ => None,
+                });
+            if let Some((stacking, location)) = mapped {
+                placement.stacking = stacking;
+                placement.location = location;
+            }
+        }
+    }
+
+    fn window_identity(&self, window: X11Window) -> WindowIdentity {
+        let property = |atom: AtomEnum| {
+            self.conn
+                .get_property(false, window, atom, AtomEnum::ANY, 0, 1024)
+                .ok()
+                .and_then(|cookie| cookie.reply().ok())
+                .map(|reply| reply.value)
+                .unwrap_or_default()
+        };
+        WindowIdentity {
+            class: property(AtomEnum::WM_CLASS),
+            title: property(AtomEnum::WM_NAME),
+        }
+    }
+
+    /// Takes the placement of a window of the crashed session, preferring one with the same title
+    fn take_placement(&mut self, identity: &WindowIdentity) -> Option<WindowPlacement> {
+        let idx = self
+            .restore
+            .iter()
+            .position(|placement| placement.identity == *identity)
+            .or_else(|| {
+                self.restore.iter().position(|placement| {
+                    !identity.class.is_empty() && placement.identity.class == identity.class
+                })
+            })?;
+        Some(self.restore.remove(idx))
+    }
+
+    /// Maps a window of a restarted client and restores its stacking relative to the
+    /// other restored windows
+    fn restore_window(&mut self, window: X11Window, smithay_window: Window, placement: &WindowPlacement) {
+        debug!(self.log, "Restoring X11 window {:x?}", window; "location" => ?placement.location);
+        // move the X11 window as well, so client and compositor agree on its location
+        let aux = ConfigureWindowAux::new()
+            .x(placement.location.x)
+            .y(placement.location.y);
+        if let Err(err) = self.conn.configure_window(window, &aux) {
+            warn!(self.log, "Failed to move restored X11 window: {}", err);
+        }
+
+        let mut space = self.space.borrow_mut();
+        space.map_window(&smithay_window, placement.location, false);
+        self.restored.retain(|(_, w)| w.toplevel().alive());
+        let idx = self
+            .restored
+            .iter()
+            .position(|(stacking, _)| *stacking > placement.stacking)
+            .unwrap_or(self.restored.len());
+        self.restored.insert(idx, (placement.stacking, smithay_window));
+        for (_, w) in &self.restored[idx + 1..] {
+            space.raise_window(w, false);
+        }
+    }
+
+    fn new_window(
+        &mut self,
+        window: X11Window,
+        surface: WlSurface,
+        location: Point<i32, Logical>,
+        override_redirect: bool,
+    ) {
         debug!(self.log, "Matched X11 surface {:x?} to {:x?}", window, surface);
 
         if give_role(&surface, "x11_surface").is_err() {
@@ -210,9 +611,82 @@ impl X11State {
         }
 
         let x11surface = X11Surface { surface };
-        self.space
-            .borrow_mut()
-            .map_window(&Window::new(Kind::X11(x11surface)), location, true);
+        if override_redirect {
+            let or_window = OverrideRedirectWindow::new(x11surface, location);
+            self.space.borrow_mut().map_override_redirect(&or_window);
+            self.override_redirect.insert(window, or_window);
+            return;
+        }
+
+        // Dialogs are kept above the window they are transient for
+        let transient_for = self
+            .conn
+            .get_property(false, window, AtomEnum::WM_TRANSIENT_FOR, AtomEnum::WINDOW, 0, 1)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().and_then(|mut value| value.next()))
+            .and_then(|parent| self.windows.get(&parent));
+        x11surface.set_transient_for(transient_for);
+        self.windows.insert(window, x11surface.surface.clone());
+        // watch for icon changes
+        let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE);
+        if let Err(err) = self.conn.change_window_attributes(window, &aux) {
+            warn!(
+                self.log,
+                "Failed to watch properties of X11 window {:x?}: {}", window, err
+            );
+        }
+
+        let identity = self.window_identity(window);
+        let restored = self.take_placement(&identity);
+        let smithay_window = Window::new(Kind::X11(x11surface));
+        let placement = match restored {
+            Some(placement) => {
+                self.restore_window(window, smithay_window, &placement);
+                placement
+            }
+            None => {
+                self.space
+                    .borrow_mut()
+                    .map_window(&smithay_window, location, true);
+                WindowPlacement {
+                    identity,
+                    location,
+                    stacking: 0,
+                }
+            }
+        };
+        self.placements.insert(window, placement);
+        self.update_icon(window);
+    }
+
+    /// Forwards `_NET_WM_ICON` of an X11 window to its smithay window
+    fn update_icon(&self, window: X11Window) {
+        let surface = match self.windows.get(&window) {
+            Some(surface) => surface,
+            None => return,
+        };
+        let space = self.space.borrow();
+        let smithay_window = match space.window_for_surface(surface) {
+            Some(smithay_window) => smithay_window,
+            None => return,
+        };
+        // the length is counted in 32 bit values, enough for a few 512x512 images
+        let icon = self
+            .conn
+            .get_property(
+                false,
+                window,
+                self.atoms._NET_WM_ICON,
+                AtomEnum::CARDINAL,
+                0,
+                1 << 20,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .and_then(|reply| reply.value32().map(|value| value.collect::<Vec<_>>()))
+            .and_then(|value| WindowIcon::from_net_wm_icon(&value));
+        smithay_window.set_icon(icon);
     }
 }
 
@@ -224,8 +698,10 @@ pub fn commit_hook(surface: &WlSurface) {
             let mut inner = x11.borrow_mut();
             // Is the surface among the unpaired surfaces (see comment next to WL_SURFACE_ID
             // handling above)
-            if let Some((window, location)) = inner.unpaired_surfaces.remove(&surface.as_ref().id()) {
-                inner.new_window(window, surface.clone(), location);
+            if let Some((window, location, override_redirect)) =
+                inner.unpaired_surfaces.remove(&surface.as_ref().id())
+            {
+                inner.new_window(window, surface.clone(), location, override_redirect);
             }
         }
     }
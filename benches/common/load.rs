@@ -0,0 +1,240 @@
+//! Synthetic client load for benchmarking [`Space::render_output`](smithay::desktop::Space::render_output)
+//!
+//! Real clients can not be spawned inside of a benchmark, so [`Load`] simulates them with
+//! custom render elements, that behave like mapped windows: they have a position, a size,
+//! a z-index and report damage whenever their "client" updates them.
+//!
+//! Rendering is done by a [`DummyRenderer`], that does not touch the GPU at all, so the
+//! measurements only contain the cpu-side cost of sorting elements, tracking damage
+//! and issuing draw calls.
+
+use std::cell::{Cell, RefCell};
+
+use rand::{rngs::StdRng, Rng};
+use slog::Logger;
+use smithay::{
+    backend::{
+        renderer::{Frame, ImportAll, Renderer, Texture, TextureFilter},
+        SwapBuffersError,
+    },
+    desktop::space::{RenderElement, RenderZindex, SpaceOutputTuple},
+    reexports::wayland_server::protocol::wl_buffer,
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Size, Transform},
+    wayland::compositor::SurfaceData,
+};
+
+use super::{random_rect, rng};
+
+/// Renderer discarding every operation
+#[derive(Debug)]
+pub struct DummyRenderer;
+
+/// Frame of the [`DummyRenderer`]
+#[derive(Debug)]
+pub struct DummyFrame;
+
+/// Texture of the [`DummyRenderer`]
+#[derive(Debug)]
+pub struct DummyTexture;
+
+impl Texture for DummyTexture {
+    fn width(&self) -> u32 {
+        0
+    }
+    fn height(&self) -> u32 {
+        0
+    }
+}
+
+impl Renderer for DummyRenderer {
+    type Error = SwapBuffersError;
+    type TextureId = DummyTexture;
+    type Frame = DummyFrame;
+
+    fn id(&self) -> usize {
+        0
+    }
+
+    fn downscale_filter(&mut self, _filter: TextureFilter) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn upscale_filter(&mut self, _filter: TextureFilter) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn render<F, R>(
+        &mut self,
+        _size: Size<i32, Physical>,
+        _dst_transform: Transform,
+        rendering: F,
+    ) -> Result<R, Self::Error>
+    where
+        F: FnOnce(&mut Self, &mut Self::Frame) -> R,
+    {
+        Ok(rendering(self, &mut DummyFrame))
+    }
+}
+
+impl ImportAll for DummyRenderer {
+    fn import_buffer(
+        &mut self,
+        _buffer: &wl_buffer::WlBuffer,
+        _surface: Option<&SurfaceData>,
+        _damage: &[Rectangle<i32, Buffer>],
+    ) -> Option<Result<DummyTexture, SwapBuffersError>> {
+        None
+    }
+}
+
+impl Frame for DummyFrame {
+    type Error = SwapBuffersError;
+    type TextureId = DummyTexture;
+
+    fn clear(&mut self, _color: [f32; 4], at: &[Rectangle<f64, Physical>]) -> Result<(), Self::Error> {
+        criterion::black_box(at);
+        Ok(())
+    }
+
+    fn render_texture_from_to(
+        &mut self,
+        texture: &Self::TextureId,
+        src: Rectangle<i32, Buffer>,
+        dst: Rectangle<f64, Physical>,
+        damage: &[Rectangle<f64, Physical>],
+        _src_transform: Transform,
+        _alpha: f32,
+    ) -> Result<(), Self::Error> {
+        criterion::black_box((texture, src, dst, damage));
+        Ok(())
+    }
+
+    fn transformation(&self) -> Transform {
+        Transform::Normal
+    }
+}
+
+/// A simulated client window
+#[derive(Debug)]
+pub struct SyntheticWindow {
+    id: usize,
+    geometry: Cell<Rectangle<i32, Logical>>,
+    damage: RefCell<Vec<Rectangle<i32, Logical>>>,
+    z_index: u8,
+    texture: DummyTexture,
+}
+
+impl RenderElement<DummyRenderer> for SyntheticWindow {
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.geometry.get()
+    }
+
+    fn accumulated_damage(
+        &self,
+        _for_values: Option<SpaceOutputTuple<'_, '_>>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        self.damage.take()
+    }
+
+    fn draw(
+        &self,
+        _renderer: &mut DummyRenderer,
+        frame: &mut DummyFrame,
+        scale: f64,
+        location: Point<i32, Logical>,
+        damage: &[Rectangle<i32, Logical>],
+        _log: &Logger,
+    ) -> Result<(), SwapBuffersError> {
+        let size = self.geometry.get().size;
+        frame.render_texture_from_to(
+            &self.texture,
+            Rectangle::from_loc_and_size((0, 0), size.to_buffer(1, Transform::Normal)),
+            Rectangle::from_loc_and_size(location, size)
+                .to_f64()
+                .to_physical(scale),
+            &damage
+                .iter()
+                .map(|rect| rect.to_f64().to_physical(scale))
+                .collect::<Vec<_>>(),
+            Transform::Normal,
+            1.0,
+        )
+    }
+
+    fn z_index(&self) -> u8 {
+        self.z_index
+    }
+}
+
+/// Generator of synthetic client activity
+///
+/// The windows are placed randomly on an output of the given size. Most of them are regular
+/// shell windows, but some are placed on the background and overlay layers, so that sorting
+/// them has some work to do.
+#[derive(Debug)]
+pub struct Load {
+    rng: StdRng,
+    output_size: Size<i32, Logical>,
+    windows: Vec<SyntheticWindow>,
+}
+
+impl Load {
+    /// Creates `count` windows on an output of `output_size`
+    pub fn new<S: Into<Size<i32, Logical>>>(count: usize, output_size: S) -> Load {
+        let mut rng = rng();
+        let output_size = output_size.into();
+        let max_size = Size::from((output_size.w / 2, output_size.h / 2));
+        let windows = (0..count)
+            .map(|id| SyntheticWindow {
+                id,
+                geometry: Cell::new(random_rect(&mut rng, output_size, max_size)),
+                damage: RefCell::new(Vec::new()),
+                z_index: match rng.gen_range(0..10) {
+                    0 => RenderZindex::Background as u8,
+                    1 => RenderZindex::Overlay as u8,
+                    _ => RenderZindex::Shell as u8,
+                },
+                texture: DummyTexture,
+            })
+            .collect();
+
+        Load {
+            rng,
+            output_size,
+            windows,
+        }
+    }
+
+    /// The simulated windows, to be passed as custom elements to `Space::render_output`
+    pub fn windows(&self) -> &[SyntheticWindow] {
+        &self.windows
+    }
+
+    /// Lets `count` random windows commit a buffer with a random damaged region
+    pub fn damage(&mut self, count: usize) {
+        for _ in 0..count {
+            let window = &self.windows[self.rng.gen_range(0..self.windows.len())];
+            let size = window.geometry.get().size;
+            let damage = random_rect(&mut self.rng, size, size);
+            window.damage.borrow_mut().push(damage);
+        }
+    }
+
+    /// Moves `count` random windows to a new random position
+    pub fn shuffle(&mut self, count: usize) {
+        for _ in 0..count {
+            let window = &self.windows[self.rng.gen_range(0..self.windows.len())];
+            let mut geometry = window.geometry.get();
+            geometry.loc = (
+                self.rng.gen_range(0..=self.output_size.w - geometry.size.w),
+                self.rng.gen_range(0..=self.output_size.h - geometry.size.h),
+            )
+                .into();
+            window.geometry.set(geometry);
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the benchmarks
+//!
+//! Every bench target includes this module on its own, so not all of it is used everywhere.
+#![allow(dead_code)]
+
+#[cfg(feature = "desktop")]
+pub mod load;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use smithay::utils::{Logical, Rectangle, Size};
+
+/// Window counts the benchmarks are run with
+pub const WINDOW_COUNTS: &[usize] = &[1, 10, 50, 100, 250];
+
+/// Size of the synthetic output, a common 1080p monitor
+pub const OUTPUT_SIZE: (i32, i32) = (1920, 1080);
+
+/// Returns a seeded rng, so every run benchmarks the same workload
+pub fn rng() -> StdRng {
+    StdRng::seed_from_u64(0x5EED)
+}
+
+/// Generates a rectangle of at most `max_size` placed somewhere inside of `bounds`
+pub fn random_rect<R: Rng>(
+    rng: &mut R,
+    bounds: Size<i32, Logical>,
+    max_size: Size<i32, Logical>,
+) -> Rectangle<i32, Logical> {
+    let w = rng.gen_range(1..=max_size.w.min(bounds.w));
+    let h = rng.gen_range(1..=max_size.h.min(bounds.h));
+    let x = rng.gen_range(0..=bounds.w - w);
+    let y = rng.gen_range(0..=bounds.h - h);
+    Rectangle::from_loc_and_size((x, y), (w, h))
+}
+
+/// Generates `count` rectangles using [`random_rect`]
+pub fn random_rects<R: Rng>(
+    rng: &mut R,
+    count: usize,
+    bounds: Size<i32, Logical>,
+    max_size: Size<i32, Logical>,
+) -> Vec<Rectangle<i32, Logical>> {
+    (0..count).map(|_| random_rect(rng, bounds, max_size)).collect()
+}
@@ -0,0 +1,68 @@
+//! Benchmarks of the rectangle math used for damage tracking
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use smithay::utils::{Logical, Rectangle, Size};
+
+mod common;
+use common::{random_rects, rng, OUTPUT_SIZE, WINDOW_COUNTS};
+
+fn rects(count: usize) -> Vec<Rectangle<i32, Logical>> {
+    let output_size = Size::from(OUTPUT_SIZE);
+    random_rects(
+        &mut rng(),
+        count,
+        output_size,
+        (output_size.w / 4, output_size.h / 4).into(),
+    )
+}
+
+fn rectangle_ops(c: &mut Criterion) {
+    let rects = rects(1000);
+    let pairs = rects.iter().zip(rects.iter().rev()).collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("rectangle");
+    group.bench_function("overlaps", |b| {
+        b.iter(|| {
+            pairs
+                .iter()
+                .filter(|(a, b)| black_box(a).overlaps(**black_box(b)))
+                .count()
+        })
+    });
+    group.bench_function("intersection", |b| {
+        b.iter(|| {
+            pairs
+                .iter()
+                .filter_map(|(a, b)| black_box(a).intersection(**black_box(b)))
+                .count()
+        })
+    });
+    group.bench_function("merge", |b| {
+        b.iter(|| {
+            pairs
+                .iter()
+                .map(|(a, b)| black_box(a).merge(**black_box(b)))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+fn damage_to_physical(c: &mut Criterion) {
+    let mut group = c.benchmark_group("damage_to_physical");
+    for count in WINDOW_COUNTS {
+        let rects = rects(*count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &rects, |b, rects| {
+            b.iter(|| {
+                rects
+                    .iter()
+                    .map(|rect| rect.to_f64().to_physical(black_box(1.5)))
+                    .collect::<Vec<_>>()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rectangle_ops, damage_to_physical);
+criterion_main!(benches);
@@ -0,0 +1,96 @@
+//! Benchmarks of [`Space::render_output`] with a growing number of windows
+//!
+//! See the `load` module for how the windows are simulated.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use smithay::{
+    desktop::Space,
+    reexports::wayland_server::protocol::wl_output::Subpixel,
+    wayland::output::{Mode, Output, PhysicalProperties},
+};
+
+mod common;
+use common::{
+    load::{DummyRenderer, Load},
+    OUTPUT_SIZE, WINDOW_COUNTS,
+};
+
+const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+fn setup() -> (Space, Output) {
+    let log = slog::Logger::root(slog::Discard, slog::o!());
+    let mut space = Space::new(log.clone());
+    let output = Output::new(
+        "bench".into(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "Smithay".into(),
+            model: "Benchmark".into(),
+        },
+        log,
+    );
+    output.change_current_state(
+        Some(Mode {
+            size: OUTPUT_SIZE.into(),
+            refresh: 60_000,
+        }),
+        None,
+        None,
+        None,
+    );
+    space.map_output(&output, (0, 0));
+    (space, output)
+}
+
+/// Benchmarks a frame after the client activity simulated by `update`
+fn bench_frames<F>(c: &mut Criterion, name: &str, age: usize, mut update: F)
+where
+    F: FnMut(&mut Load, usize),
+{
+    let mut group = c.benchmark_group(name);
+    for count in WINDOW_COUNTS {
+        let (mut space, output) = setup();
+        let mut renderer = DummyRenderer;
+        let mut load = Load::new(*count, OUTPUT_SIZE);
+        // the first frame is always fully damaged, get it out of the way
+        space
+            .render_output(&mut renderer, &output, 0, CLEAR_COLOR, load.windows())
+            .unwrap();
+
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter(|| {
+                update(&mut load, *count);
+                space
+                    .render_output(&mut renderer, &output, age, CLEAR_COLOR, load.windows())
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Nothing changed, this only measures sorting the elements and collecting their damage
+fn idle(c: &mut Criterion) {
+    bench_frames(c, "space/idle", 1, |_, _| {});
+}
+
+/// A tenth of the windows commit new content
+fn damaged(c: &mut Criterion) {
+    bench_frames(c, "space/damaged", 1, |load, count| {
+        load.damage((count / 10).max(1))
+    });
+}
+
+/// A single window moves, like while it is being dragged
+fn moving(c: &mut Criterion) {
+    bench_frames(c, "space/moving", 1, |load, _| load.shuffle(1));
+}
+
+/// Buffers without age, every frame redraws the full output
+fn full(c: &mut Criterion) {
+    bench_frames(c, "space/full", 0, |_, _| {});
+}
+
+criterion_group!(benches, idle, damaged, moving, full);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Benchmarks of uploading client buffers into textures of the [`Gles2Renderer`]
+//!
+//! The renderer is created on the first available [`EGLDevice`], no windowing system
+//! is required. If none is usable the benchmarks are skipped.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use smithay::{
+    backend::{
+        egl::{EGLContext, EGLDevice, EGLDisplay},
+        renderer::{gles2::Gles2Renderer, ImportMem},
+    },
+    utils::{Buffer, Rectangle, Size},
+};
+
+const SIZES: &[(i32, i32)] = &[(64, 64), (256, 256), (1920, 1080)];
+
+fn renderer() -> Option<Gles2Renderer> {
+    let log = slog::Logger::root(slog::Discard, slog::o!());
+    EGLDevice::enumerate().ok()?.find_map(|device| {
+        let display = EGLDisplay::new(&device, log.clone()).ok()?;
+        let context = EGLContext::new(&display, log.clone()).ok()?;
+        unsafe { Gles2Renderer::new(context, log.clone()).ok() }
+    })
+}
+
+fn texture_import(c: &mut Criterion) {
+    let mut renderer = match renderer() {
+        Some(renderer) => renderer,
+        None => {
+            eprintln!("No usable EGL device found, skipping texture import benchmarks");
+            return;
+        }
+    };
+
+    let mut group = c.benchmark_group("texture_import");
+    for (w, h) in SIZES {
+        let size = Size::<i32, Buffer>::from((*w, *h));
+        let data = vec![0xAAu8; (w * h * 4) as usize];
+        group.throughput(Throughput::Bytes(data.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("import_memory", format!("{}x{}", w, h)),
+            &data,
+            |b, data| b.iter(|| renderer.import_memory(data, size, false).unwrap()),
+        );
+
+        let texture = renderer.import_memory(&data, size, false).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("update_memory", format!("{}x{}", w, h)),
+            &data,
+            |b, data| {
+                b.iter(|| {
+                    renderer
+                        .update_memory(&texture, data, Rectangle::from_loc_and_size((0, 0), size))
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, texture_import);
+criterion_main!(benches);
@@ -58,6 +58,9 @@ fn gl_generate() {
                 "GL_OES_EGL_image_external",
                 "GL_EXT_texture_format_BGRA8888",
                 "GL_EXT_unpack_subimage",
+                "GL_EXT_disjoint_timer_query",
+                "GL_OES_get_program_binary",
+                "GL_EXT_buffer_storage",
             ],
         )
         .write_bindings(gl_generator::StructGenerator, &mut file)
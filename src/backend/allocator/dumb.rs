@@ -1,11 +1,17 @@
 //! Module for [DumbBuffer](https://01.org/linuxgraphics/gfx-docs/drm/gpu/drm-kms.html#dumb-buffer-objects) buffers
 
 use std::fmt;
+use std::ops::{Deref, DerefMut};
 use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicIsize, Ordering};
 use std::sync::Arc;
 
 use drm::buffer::Buffer as DrmBuffer;
 use drm::control::{dumbbuffer::DumbBuffer as Handle, Device as ControlDevice};
+use nix::{
+    errno::Errno,
+    sys::mman::{self, MapFlags, ProtFlags},
+};
 
 use super::{Allocator, Buffer, Format, Fourcc, Modifier};
 use crate::backend::drm::device::{DrmDevice, DrmDeviceInternal, FdWrapper};
@@ -16,8 +22,12 @@ pub struct DumbBuffer<A: AsRawFd + 'static> {
     fd: Arc<FdWrapper<A>>,
     handle: Handle,
     format: Format,
+    /// Number of read-only mappings, or `WRITE_MAPPED`
+    mappings: AtomicIsize,
 }
 
+const WRITE_MAPPED: isize = -1;
+
 impl<A: AsRawFd + 'static> fmt::Debug for DumbBuffer<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("DumbBuffer")
@@ -57,6 +67,7 @@ impl<A: AsRawFd + 'static> Allocator<DumbBuffer<A>> for DrmDevice<A> {
                 code: fourcc,
                 modifier: Modifier::Linear,
             },
+            mappings: AtomicIsize::new(0),
         })
     }
 }
@@ -80,6 +91,147 @@ impl<A: AsRawFd + 'static> DumbBuffer<A> {
     pub fn handle(&self) -> &Handle {
         &self.handle
     }
+
+    /// Maps the buffer into memory for reading its pixels
+    ///
+    /// The pixels are laid out row by row, each row taking [`pitch`](DrmBuffer::pitch) bytes of the
+    /// [`handle`](DumbBuffer::handle). Reading from the mapping is slow, as dumb buffers are
+    /// usually uncached.
+    ///
+    /// Any number of read-only mappings can exist at once. Fails with `EBUSY`, while the buffer
+    /// is mapped by [`DumbBuffer::map_mut`].
+    pub fn map(&self) -> Result<DumbMapping<'_>, drm::SystemError> {
+        self.mappings
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |mappings| {
+                if mappings == WRITE_MAPPED {
+                    None
+                } else {
+                    Some(mappings + 1)
+                }
+            })
+            .map_err(|_| drm::SystemError::from(Errno::EBUSY))?;
+        match self.mmap(ProtFlags::PROT_READ) {
+            Ok((ptr, len)) => Ok(DumbMapping {
+                ptr,
+                len,
+                mappings: &self.mappings,
+            }),
+            Err(err) => {
+                self.mappings.fetch_sub(1, Ordering::Release);
+                Err(err)
+            }
+        }
+    }
+
+    /// Maps the buffer into memory for writing its pixels, e.g. by a software renderer or to
+    /// upload a cursor image
+    ///
+    /// This only requires a shared reference, so buffers of a [`Slot`](super::Slot), e.g. the
+    /// one returned by [`DumbBufferedSurface::next_buffer`](crate::backend::drm::DumbBufferedSurface::next_buffer),
+    /// can be written to. Like a `RefCell`, the buffer tracks its mappings instead: this fails with
+    /// `EBUSY`, while the buffer is mapped elsewhere, so the returned slice is never aliased.
+    ///
+    /// See [`DumbBuffer::map`] for the layout of the pixels.
+    pub fn map_mut(&self) -> Result<DumbMappingMut<'_>, drm::SystemError> {
+        self.mappings
+            .compare_exchange(0, WRITE_MAPPED, Ordering::Acquire, Ordering::Relaxed)
+            .map_err(|_| drm::SystemError::from(Errno::EBUSY))?;
+        match self.mmap(ProtFlags::PROT_READ | ProtFlags::PROT_WRITE) {
+            Ok((ptr, len)) => Ok(DumbMappingMut {
+                ptr,
+                len,
+                mappings: &self.mappings,
+            }),
+            Err(err) => {
+                self.mappings.store(0, Ordering::Release);
+                Err(err)
+            }
+        }
+    }
+
+    fn mmap(&self, prot: ProtFlags) -> Result<(*mut u8, usize), drm::SystemError> {
+        let len = self.handle.pitch() as usize * self.handle.size().1 as usize;
+        let info = drm_ffi::mode::dumbbuffer::map(self.fd.as_raw_fd(), self.handle.handle().into(), 0, 0)?;
+        // Safety: the offset was handed out by the kernel for a buffer of at least `len` bytes,
+        // which stays alive as long as the mapping borrows the `DumbBuffer`.
+        let ptr = unsafe {
+            mman::mmap(
+                std::ptr::null_mut(),
+                len,
+                prot,
+                MapFlags::MAP_SHARED,
+                self.fd.as_raw_fd(),
+                info.offset as _,
+            )?
+        };
+        Ok((ptr as *mut u8, len))
+    }
+}
+
+/// Read-only memory mapping of a [`DumbBuffer`], unmapped on drop
+pub struct DumbMapping<'a> {
+    ptr: *mut u8,
+    len: usize,
+    mappings: &'a AtomicIsize,
+}
+
+/// Writable memory mapping of a [`DumbBuffer`], unmapped on drop
+pub struct DumbMappingMut<'a> {
+    ptr: *mut u8,
+    len: usize,
+    mappings: &'a AtomicIsize,
+}
+
+impl<'a> fmt::Debug for DumbMapping<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DumbMapping").field("len", &self.len).finish()
+    }
+}
+
+impl<'a> fmt::Debug for DumbMappingMut<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DumbMappingMut").field("len", &self.len).finish()
+    }
+}
+
+impl<'a> Deref for DumbMapping<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the memory is mapped for `len` bytes until drop
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a> Deref for DumbMappingMut<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the memory is mapped for `len` bytes until drop
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<'a> DerefMut for DumbMappingMut<'a> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // Safety: the memory is mapped writable for `len` bytes until drop and no other mapping
+        // of the buffer can exist meanwhile, see `DumbBuffer::map_mut`
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl<'a> Drop for DumbMapping<'a> {
+    fn drop(&mut self) {
+        let _ = unsafe { mman::munmap(self.ptr as *mut _, self.len) };
+        self.mappings.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<'a> Drop for DumbMappingMut<'a> {
+    fn drop(&mut self) {
+        let _ = unsafe { mman::munmap(self.ptr as *mut _, self.len) };
+        self.mappings.store(0, Ordering::Release);
+    }
 }
 
 impl<A: AsRawFd + 'static> Drop for DumbBuffer<A> {
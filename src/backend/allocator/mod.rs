@@ -29,7 +29,7 @@ use std::{
 };
 
 use crate::utils::{Buffer as BufferCoords, Size};
-pub use swapchain::{Slot, Swapchain};
+pub use swapchain::{Degradation, Slot, SwapStrategy, Swapchain, SwapchainStats};
 
 pub use drm_fourcc::{
     DrmFormat as Format, DrmFourcc as Fourcc, DrmModifier as Modifier, DrmVendor as Vendor,
@@ -7,7 +7,7 @@ use std::{
     },
 };
 
-use crate::backend::allocator::{Allocator, Buffer, Fourcc, Modifier};
+use crate::backend::allocator::{Allocator, Buffer, Format, Fourcc, Modifier};
 use crate::utils::user_data::UserDataMap;
 
 pub const SLOT_CAP: usize = 4;
@@ -39,6 +39,22 @@ pub const SLOT_CAP: usize = 4;
 /// If you have associated resources for each buffer that can be reused (e.g. framebuffer `Handle`s for a `DrmDevice`),
 /// you can store then in the `Slot`s userdata field. If a buffer is re-used, its userdata is preserved for the next time
 /// it is returned by `acquire()`.
+///
+/// ## Choosing the number of buffers
+///
+/// How many buffers are used is decided by the [`SwapStrategy`] set with
+/// [`set_strategy`](Swapchain::set_strategy), trading latency and memory usage against stutter.
+/// The number can also be given directly to [`with_buffers`](Swapchain::with_buffers) or
+/// [`set_buffers`](Swapchain::set_buffers).
+/// [`acquired_slots`](Swapchain::acquired_slots) tells how many buffers are currently in use and
+/// [`stats`](Swapchain::stats) how often [`acquire`](Swapchain::acquire) found no free buffer,
+/// which is a sign that more buffers are needed.
+///
+/// ## Running out of memory
+///
+/// By default failed allocations are returned as errors by [`acquire`](Swapchain::acquire).
+/// [`set_degradation`](Swapchain::set_degradation) makes the swapchain recover from them instead,
+/// by stepping down a ladder of [`Degradation`]s, until buffers can be allocated again.
 pub struct Swapchain<A: Allocator<B>, B: Buffer> {
     /// Allocator used by the swapchain
     pub allocator: A,
@@ -49,6 +65,86 @@ pub struct Swapchain<A: Allocator<B>, B: Buffer> {
     modifiers: Vec<Modifier>,
 
     slots: [Arc<InternalSlot<B>>; SLOT_CAP],
+    slot_limit: usize,
+    buffers: usize,
+    strategy: Option<SwapStrategy>,
+    stats: SwapchainStats,
+    fallback_formats: Option<Vec<Format>>,
+    degradations: Vec<Degradation>,
+}
+
+/// Number of buffers used by a [`Swapchain`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SwapStrategy {
+    /// Two buffers, one displayed and one to render into
+    ///
+    /// This has the lowest latency and memory usage, but the next frame can only be rendered
+    /// once the previous one is displayed, so a late frame delays the following ones as well.
+    Double,
+    /// Three buffers, so the next frame can be rendered while the previous one waits to be displayed
+    Triple,
+    /// Four buffers, so a frame waiting to be displayed can be replaced by a newer one,
+    /// dropping the older frame
+    ///
+    /// This is the default.
+    #[default]
+    Mailbox,
+}
+
+impl SwapStrategy {
+    /// Returns the number of buffers used with this strategy
+    pub fn buffers(&self) -> usize {
+        match self {
+            SwapStrategy::Double => 2,
+            SwapStrategy::Triple => 3,
+            SwapStrategy::Mailbox => SLOT_CAP,
+        }
+    }
+}
+
+/// Statistics about the buffers acquired from a [`Swapchain`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SwapchainStats {
+    /// Number of buffers acquired
+    pub acquired: u64,
+    /// Number of times no buffer was free, because all of them were still in use
+    pub starved: u64,
+    /// Number of the most recent acquisitions in a row, that found no free buffer
+    pub consecutive_starved: u64,
+}
+
+/// Step taken by a [`Swapchain`] to recover from a failed allocation
+///
+/// The steps are tried in the order of the variants, every step is retried with the next allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Degradation {
+    /// The swapchain stopped allocating more than two buffers
+    DoubleBuffering,
+    /// New buffers are allocated with the linear modifier, which usually needs less memory for
+    /// compression data and alignment
+    LinearModifier,
+    /// New buffers are allocated with a format of fewer bits per pixel
+    ReducedDepth {
+        /// The previous format
+        from: Fourcc,
+        /// The new format
+        to: Fourcc,
+    },
+}
+
+/// Returns a format with the same channels but fewer bits per pixel
+fn reduced_depth(fourcc: Fourcc) -> Option<Fourcc> {
+    Some(match fourcc {
+        Fourcc::Abgr16161616f => Fourcc::Abgr2101010,
+        Fourcc::Xbgr16161616f => Fourcc::Xbgr2101010,
+        Fourcc::Argb2101010 => Fourcc::Argb8888,
+        Fourcc::Xrgb2101010 => Fourcc::Xrgb8888,
+        Fourcc::Abgr2101010 => Fourcc::Abgr8888,
+        Fourcc::Xbgr2101010 => Fourcc::Xbgr8888,
+        Fourcc::Argb8888 | Fourcc::Xrgb8888 => Fourcc::Rgb565,
+        Fourcc::Abgr8888 | Fourcc::Xbgr8888 => Fourcc::Bgr565,
+        _ => return None,
+    })
 }
 
 impl<A: Allocator<B>, B: Buffer> fmt::Debug for Swapchain<A, B> {
@@ -58,6 +154,10 @@ impl<A: Allocator<B>, B: Buffer> fmt::Debug for Swapchain<A, B> {
             .field("height", &self.height)
             .field("fourcc", &self.fourcc)
             .field("modifiers", &self.modifiers)
+            .field("slot_limit", &self.slot_limit)
+            .field("buffers", &self.buffers)
+            .field("stats", &self.stats)
+            .field("degradations", &self.degradations)
             .finish_non_exhaustive()
     }
 }
@@ -133,35 +233,195 @@ where
             fourcc,
             modifiers,
             slots: Default::default(),
+            slot_limit: SLOT_CAP,
+            buffers: SLOT_CAP,
+            strategy: Some(SwapStrategy::default()),
+            stats: SwapchainStats::default(),
+            fallback_formats: None,
+            degradations: Vec::new(),
         }
     }
 
+    /// Create a new swapchain like [`Swapchain::new`], using `buffers` buffers
+    ///
+    /// See [`set_buffers`](Swapchain::set_buffers) for the supported numbers of buffers.
+    pub fn with_buffers(
+        allocator: A,
+        width: u32,
+        height: u32,
+        fourcc: Fourcc,
+        modifiers: Vec<Modifier>,
+        buffers: usize,
+    ) -> Swapchain<A, B> {
+        let mut swapchain = Swapchain::new(allocator, width, height, fourcc, modifiers);
+        swapchain.set_buffers(buffers);
+        swapchain
+    }
+
     /// Acquire a new slot from the swapchain, if one is still free.
     ///
-    /// The swapchain has an internal maximum of four re-usable buffers.
-    /// This function returns the first free one.
+    /// The swapchain has as many re-usable buffers as given by its [`SwapStrategy`] or
+    /// [`set_buffers`](Swapchain::set_buffers), or two after
+    /// [`Degradation::DoubleBuffering`]. This function returns the first free one.
     pub fn acquire(&mut self) -> Result<Option<Slot<B>>, A::Error> {
-        if let Some(free_slot) = self
-            .slots
-            .iter_mut()
-            .find(|s| !s.acquired.swap(true, Ordering::SeqCst))
+        let idx = match self.slots[..self.slot_limit]
+            .iter()
+            .position(|s| !s.acquired.swap(true, Ordering::SeqCst))
         {
-            if free_slot.buffer.is_none() {
-                let mut free_slot =
-                    Arc::get_mut(free_slot).expect("Acquired was false, but Arc is not unique?");
-                free_slot.buffer = Some(self.allocator.create_buffer(
-                    self.width,
-                    self.height,
-                    self.fourcc,
-                    &self.modifiers,
-                )?);
+            Some(idx) => idx,
+            // no free slots
+            None => {
+                self.starved();
+                return Ok(None);
+            }
+        };
+
+        while self.slots[idx].buffer.is_none() {
+            let err =
+                match self
+                    .allocator
+                    .create_buffer(self.width, self.height, self.fourcc, &self.modifiers)
+                {
+                    Ok(buffer) => {
+                        let free_slot = Arc::get_mut(&mut self.slots[idx])
+                            .expect("Acquired was false, but Arc is not unique?");
+                        free_slot.buffer = Some(buffer);
+                        break;
+                    }
+                    Err(err) => err,
+                };
+
+            let allocated = self.slots.iter().filter(|slot| slot.buffer.is_some()).count();
+            if self.degrade(allocated).is_none() || idx >= self.slot_limit {
+                self.slots[idx].acquired.store(false, Ordering::SeqCst);
+                return if idx >= self.slot_limit {
+                    self.starved();
+                    Ok(None)
+                } else {
+                    Err(err)
+                };
             }
-            assert!(free_slot.buffer.is_some());
-            return Ok(Some(Slot(free_slot.clone())));
         }
 
-        // no free slots
-        Ok(None)
+        self.stats.acquired += 1;
+        self.stats.consecutive_starved = 0;
+        Ok(Some(Slot(self.slots[idx].clone())))
+    }
+
+    fn starved(&mut self) {
+        self.stats.starved += 1;
+        self.stats.consecutive_starved += 1;
+    }
+
+    /// Changes the number of buffers used by the swapchain
+    ///
+    /// Buffers dropped by a smaller number are freed, once they are no longer acquired.
+    /// A swapchain degraded to [`Degradation::DoubleBuffering`] stays double buffered.
+    pub fn set_strategy(&mut self, strategy: SwapStrategy) {
+        self.set_buffers(strategy.buffers());
+        self.strategy = Some(strategy);
+    }
+
+    /// Returns the strategy set by [`set_strategy`](Swapchain::set_strategy)
+    ///
+    /// Returns `None`, if the number of buffers was set directly with
+    /// [`set_buffers`](Swapchain::set_buffers) instead.
+    pub fn strategy(&self) -> Option<SwapStrategy> {
+        self.strategy
+    }
+
+    /// Changes the number of buffers used by the swapchain to two, three or [`SLOT_CAP`]
+    ///
+    /// Other numbers are clamped to this range. Like with [`set_strategy`](Swapchain::set_strategy),
+    /// buffers dropped by a smaller number are freed, once they are no longer acquired, and a
+    /// swapchain degraded to [`Degradation::DoubleBuffering`] stays double buffered.
+    pub fn set_buffers(&mut self, buffers: usize) {
+        let buffers = buffers.clamp(2, SLOT_CAP);
+        let degraded = self.slot_limit < self.buffers;
+        self.buffers = buffers;
+        self.strategy = None;
+        self.slot_limit = if degraded { buffers.min(2) } else { buffers };
+        for slot in &mut self.slots[self.slot_limit..] {
+            *slot = Default::default();
+        }
+    }
+
+    /// Returns the number of buffers used by the swapchain, unless it is degraded
+    pub fn buffers(&self) -> usize {
+        self.buffers
+    }
+
+    /// Returns statistics about the buffers acquired from this swapchain
+    pub fn stats(&self) -> SwapchainStats {
+        self.stats
+    }
+
+    /// Returns the number of slots currently acquired and not yet released
+    ///
+    /// [Stale](Swapchain::is_stale) slots are not counted, as they no longer occupy a buffer of
+    /// the swapchain.
+    pub fn acquired_slots(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.acquired.load(Ordering::SeqCst))
+            .count()
+    }
+
+    // takes the next step of the degradation ladder, if possible
+    fn degrade(&mut self, allocated: usize) -> Option<Degradation> {
+        let formats = self.fallback_formats.as_ref()?;
+        let degradation = if allocated >= 2 && self.slot_limit > 2 {
+            self.slot_limit = 2;
+            // free the memory of the dropped buffers, acquired ones are released by their users
+            for slot in &mut self.slots[2..] {
+                *slot = Default::default();
+            }
+            Degradation::DoubleBuffering
+        } else if self.modifiers != [Modifier::Linear]
+            && formats.contains(&Format {
+                code: self.fourcc,
+                modifier: Modifier::Linear,
+            })
+        {
+            self.modifiers = vec![Modifier::Linear];
+            Degradation::LinearModifier
+        } else {
+            let from = self.fourcc;
+            let (to, modifiers) = std::iter::successors(reduced_depth(from), |code| reduced_depth(*code))
+                .map(|code| {
+                    let modifiers = formats
+                        .iter()
+                        .filter(|format| format.code == code)
+                        .map(|format| format.modifier)
+                        .collect::<Vec<_>>();
+                    (code, modifiers)
+                })
+                .find(|(_, modifiers)| !modifiers.is_empty())?;
+            self.fourcc = to;
+            self.modifiers = modifiers;
+            Degradation::ReducedDepth { from, to }
+        };
+        self.degradations.push(degradation);
+        Some(degradation)
+    }
+
+    /// Enables recovering from failed allocations by degrading the swapchain
+    ///
+    /// `formats` are the formats and modifiers acceptable for buffers of the swapchain,
+    /// e.g. the ones supported by the renderer and for scan-out. Allocation failures are only
+    /// returned by [`acquire`](Swapchain::acquire) once no further [`Degradation`] is possible.
+    /// Every step taken is reported by [`take_degradations`](Swapchain::take_degradations).
+    ///
+    /// Passing `None` disables degradation. Either way the number of buffers given by the
+    /// [`SwapStrategy`] is restored, while the format of the swapchain is kept.
+    pub fn set_degradation(&mut self, formats: Option<Vec<Format>>) {
+        self.fallback_formats = formats;
+        self.slot_limit = self.buffers;
+    }
+
+    /// Returns the degradations taken since the last call, oldest first
+    pub fn take_degradations(&mut self) -> Vec<Degradation> {
+        std::mem::take(&mut self.degradations)
     }
 
     /// Mark a given buffer as submitted.
@@ -175,7 +435,7 @@ where
     /// (e.g. by not tracking the buffer age).
     pub fn submitted(&self, slot: &Slot<B>) {
         // don't mess up the state, if the user submitted and old buffer, after e.g. a resize
-        if !self.slots.iter().any(|other| Arc::ptr_eq(&slot.0, other)) {
+        if self.is_stale(slot) {
             return;
         }
 
@@ -198,7 +458,9 @@ where
 
     /// Change the dimensions of newly returned buffers.
     ///
-    /// Already obtained buffers are unaffected and will be cleaned up on drop.
+    /// The buffers are reallocated lazily by [`acquire`](Swapchain::acquire), keeping the allocator,
+    /// format, number of buffers and degradations of the swapchain. Already obtained buffers are
+    /// unaffected and will be cleaned up on drop, see [`is_stale`](Swapchain::is_stale).
     pub fn resize(&mut self, width: u32, height: u32) {
         if self.width == width && self.height == height {
             return;
@@ -209,6 +471,44 @@ where
         self.slots = Default::default();
     }
 
+    /// Returns the dimensions of newly returned buffers
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Checks if a slot was acquired before the buffers were re-created
+    ///
+    /// This is the case after [`resize`](Swapchain::resize), [`set_format`](Swapchain::set_format)
+    /// or [`reset_buffers`](Swapchain::reset_buffers) replaced the buffers. Stale slots are no
+    /// longer tracked by the swapchain, e.g. [`submitted`](Swapchain::submitted) ignores them,
+    /// and should be dropped once they are no longer displayed.
+    pub fn is_stale(&self, slot: &Slot<B>) -> bool {
+        !self.slots.iter().any(|other| Arc::ptr_eq(&slot.0, other))
+    }
+
+    /// Change the format and the modifiers of newly returned buffers.
+    ///
+    /// Already obtained buffers are unaffected and will be cleaned up on drop.
+    pub fn set_format(&mut self, fourcc: Fourcc, modifiers: Vec<Modifier>) {
+        if self.fourcc == fourcc && self.modifiers == modifiers {
+            return;
+        }
+
+        self.fourcc = fourcc;
+        self.modifiers = modifiers;
+        self.slots = Default::default();
+    }
+
+    /// Returns the format of newly returned buffers
+    pub fn format(&self) -> Fourcc {
+        self.fourcc
+    }
+
+    /// Returns the modifiers newly returned buffers may be allocated with
+    pub fn modifiers(&self) -> &[Modifier] {
+        &self.modifiers
+    }
+
     /// Remove all internally cached buffers to e.g. reset age values
     pub fn reset_buffers(&mut self) {
         for slot in &mut self.slots {
@@ -223,3 +523,207 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{Buffer as BufferCoords, Size};
+
+    #[derive(Debug)]
+    struct TestBuffer(Format);
+
+    impl Buffer for TestBuffer {
+        fn size(&self) -> Size<i32, BufferCoords> {
+            (1, 1).into()
+        }
+        fn format(&self) -> Format {
+            self.0
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    #[error("Out of memory")]
+    struct OutOfMemory;
+
+    /// Allocates at most `budget` buffers and only linear buffers of `Xrgb8888`
+    struct TestAllocator {
+        budget: usize,
+    }
+
+    impl Allocator<TestBuffer> for TestAllocator {
+        type Error = OutOfMemory;
+
+        fn create_buffer(
+            &mut self,
+            _width: u32,
+            _height: u32,
+            fourcc: Fourcc,
+            modifiers: &[Modifier],
+        ) -> Result<TestBuffer, OutOfMemory> {
+            if self.budget == 0 || fourcc != Fourcc::Xrgb8888 || modifiers != [Modifier::Linear] {
+                return Err(OutOfMemory);
+            }
+            self.budget -= 1;
+            Ok(TestBuffer(Format {
+                code: fourcc,
+                modifier: modifiers[0],
+            }))
+        }
+    }
+
+    fn format(code: Fourcc, modifier: Modifier) -> Format {
+        Format { code, modifier }
+    }
+
+    #[test]
+    fn allocation_failures_are_errors_by_default() {
+        let mut swapchain = Swapchain::new(
+            TestAllocator { budget: 1 },
+            1,
+            1,
+            Fourcc::Xrgb8888,
+            vec![Modifier::Linear],
+        );
+        let _first = swapchain.acquire().unwrap().unwrap();
+        assert!(swapchain.acquire().is_err());
+        assert!(swapchain.take_degradations().is_empty());
+    }
+
+    #[test]
+    fn degradation_ladder() {
+        let mut swapchain = Swapchain::new(
+            TestAllocator { budget: 2 },
+            1,
+            1,
+            Fourcc::Xrgb2101010,
+            vec![Modifier::I915_x_tiled],
+        );
+        swapchain.set_degradation(Some(vec![
+            format(Fourcc::Xrgb2101010, Modifier::I915_x_tiled),
+            format(Fourcc::Xrgb2101010, Modifier::Linear),
+            format(Fourcc::Xrgb8888, Modifier::I915_x_tiled),
+            format(Fourcc::Xrgb8888, Modifier::Linear),
+        ]));
+
+        let first = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(first.format(), format(Fourcc::Xrgb8888, Modifier::Linear));
+        assert_eq!(
+            swapchain.take_degradations(),
+            vec![
+                Degradation::LinearModifier,
+                Degradation::ReducedDepth {
+                    from: Fourcc::Xrgb2101010,
+                    to: Fourcc::Xrgb8888
+                },
+                Degradation::LinearModifier,
+            ]
+        );
+
+        let _second = swapchain.acquire().unwrap().unwrap();
+        // a third buffer does not fit, so the swapchain stays double buffered
+        assert!(swapchain.acquire().unwrap().is_none());
+        assert_eq!(swapchain.take_degradations(), vec![Degradation::DoubleBuffering]);
+        drop(first);
+        assert!(swapchain.acquire().unwrap().is_some());
+    }
+
+    #[test]
+    fn strategies_and_starvation() {
+        let mut swapchain = Swapchain::new(
+            TestAllocator { budget: 4 },
+            1,
+            1,
+            Fourcc::Xrgb8888,
+            vec![Modifier::Linear],
+        );
+        swapchain.set_strategy(SwapStrategy::Triple);
+        let slots = (0..3)
+            .map(|_| swapchain.acquire().unwrap().unwrap())
+            .collect::<Vec<_>>();
+        assert!(swapchain.acquire().unwrap().is_none());
+        assert!(swapchain.acquire().unwrap().is_none());
+        assert_eq!(
+            swapchain.stats(),
+            SwapchainStats {
+                acquired: 3,
+                starved: 2,
+                consecutive_starved: 2,
+            }
+        );
+
+        // the dropped buffer is freed, once it is released
+        swapchain.set_strategy(SwapStrategy::Double);
+        drop(slots);
+        let _first = swapchain.acquire().unwrap().unwrap();
+        let _second = swapchain.acquire().unwrap().unwrap();
+        assert!(swapchain.acquire().unwrap().is_none());
+        assert_eq!(swapchain.stats().consecutive_starved, 1);
+
+        swapchain.set_strategy(SwapStrategy::Mailbox);
+        let _third = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.stats().consecutive_starved, 0);
+    }
+
+    #[test]
+    fn resize_reallocates_lazily() {
+        let mut swapchain = Swapchain::new(
+            TestAllocator { budget: 3 },
+            1,
+            1,
+            Fourcc::Xrgb8888,
+            vec![Modifier::Linear],
+        );
+        swapchain.set_strategy(SwapStrategy::Double);
+        let old = swapchain.acquire().unwrap().unwrap();
+        swapchain.resize(1, 1);
+        assert!(!swapchain.is_stale(&old));
+
+        swapchain.resize(2, 2);
+        assert_eq!(swapchain.size(), (2, 2));
+        assert!(swapchain.is_stale(&old));
+        assert_eq!(swapchain.allocator.budget, 2);
+
+        // the stale slot no longer counts against the strategy
+        let first = swapchain.acquire().unwrap().unwrap();
+        swapchain.submitted(&old);
+        assert_eq!(first.age(), 0);
+        let _second = swapchain.acquire().unwrap().unwrap();
+        assert!(swapchain.acquire().unwrap().is_none());
+        assert_eq!(swapchain.format(), Fourcc::Xrgb8888);
+    }
+
+    #[test]
+    fn buffer_count_and_acquired_slots() {
+        let mut swapchain = Swapchain::with_buffers(
+            TestAllocator { budget: 4 },
+            1,
+            1,
+            Fourcc::Xrgb8888,
+            vec![Modifier::Linear],
+            3,
+        );
+        assert_eq!((swapchain.buffers(), swapchain.strategy()), (3, None));
+        let first = swapchain.acquire().unwrap().unwrap();
+        let second = swapchain.acquire().unwrap().unwrap();
+        assert_eq!(swapchain.acquired_slots(), 2);
+        drop(first);
+        assert_eq!(swapchain.acquired_slots(), 1);
+        let _third = swapchain.acquire().unwrap().unwrap();
+        let _fourth = swapchain.acquire().unwrap().unwrap();
+        assert!(swapchain.acquire().unwrap().is_none());
+        assert_eq!(swapchain.acquired_slots(), 3);
+
+        // stale slots are not counted
+        swapchain.resize(2, 2);
+        assert_eq!(swapchain.acquired_slots(), 0);
+        drop(second);
+
+        swapchain.set_buffers(8);
+        assert_eq!(swapchain.buffers(), SLOT_CAP);
+        swapchain.set_strategy(SwapStrategy::Double);
+        assert_eq!(
+            (swapchain.buffers(), swapchain.strategy()),
+            (2, Some(SwapStrategy::Double))
+        );
+    }
+}
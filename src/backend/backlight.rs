@@ -0,0 +1,485 @@
+//! Discovery and control of backlights and LEDs
+//!
+//! This module provides [`backlights`] to find the backlight devices of a seat through udev,
+//! so compositors can implement brightness keys without relying on an external daemon.
+//!
+//! A system might expose multiple interfaces to the same backlight. They are returned in the order
+//! of preference, firmware interfaces first, then platform specific ones and raw interfaces to the
+//! GPU last, so [`primary_backlight`] usually returns the one controlling the internal panel.
+//!
+//! Other lights, like keyboard backlights or the lock indicators of keyboards, are exposed
+//! by the kernel as LED devices and can be found using [`leds`] and [`keyboard_backlights`].
+//!
+//! Changing the brightness through sysfs via [`Backlight::set_brightness`] or [`Led::set_brightness`]
+//! requires write access to the device. When running inside of a logind session, use
+//! [`Backlight::set_brightness_logind`] or [`Led::set_brightness_logind`] instead, which work
+//! without any special permissions as long as the session is active. [`Backlight::set_brightness_session`]
+//! and [`Led::set_brightness_session`] pick the right way for an [`AutoSession`].
+//!
+//! Backlights might appear or disappear at runtime, e.g. when a driver is loaded late, and their
+//! brightness might be changed by the firmware. The [`BacklightMonitor`] can be inserted into
+//! [`calloop`] to be notified about these changes.
+//!
+//! ```no_run
+//! use smithay::backend::backlight::primary_backlight;
+//!
+//! if let Some(backlight) = primary_backlight("seat0").expect("Failed to scan backlights") {
+//!     let brightness = backlight.brightness().unwrap();
+//!     let step = (backlight.max_brightness() / 20).max(1);
+//!     backlight.set_brightness(brightness.saturating_add(step)).unwrap();
+//! }
+//! ```
+
+use std::{
+    ffi::OsStr,
+    fmt, fs, io,
+    os::unix::io::{AsRawFd, RawFd},
+    path::{Path, PathBuf},
+};
+
+use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
+use slog::{debug, info, o, warn};
+use udev::{Enumerator, EventType, MonitorBuilder, MonitorSocket};
+
+#[cfg(feature = "backend_session")]
+use super::session::auto::{self, AutoSession};
+#[cfg(feature = "backend_session_logind")]
+use super::session::logind::{self, LogindSession};
+
+/// Interface used to control a [`Backlight`], as reported by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BacklightType {
+    /// Controlled through the firmware, e.g. ACPI
+    Firmware,
+    /// Controlled through a platform specific driver
+    Platform,
+    /// Controlled directly through the registers of the GPU
+    Raw,
+}
+
+/// A backlight device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlight {
+    name: String,
+    syspath: PathBuf,
+    kind: BacklightType,
+    max_brightness: u32,
+    connector: Option<String>,
+}
+
+fn read_attribute(syspath: &Path, attribute: &str) -> io::Result<u32> {
+    fs::read_to_string(syspath.join(attribute))?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn read_brightness(syspath: &Path) -> io::Result<u32> {
+    // the actual brightness might differ from the last requested one, e.g. if
+    // the firmware changed it on its own, so prefer it, if available.
+    read_attribute(syspath, "actual_brightness").or_else(|_| read_attribute(syspath, "brightness"))
+}
+
+fn write_brightness(syspath: &Path, brightness: u32) -> io::Result<()> {
+    fs::write(syspath.join("brightness"), brightness.to_string())
+}
+
+fn is_on_seat(device: &udev::Device, seat: &str) -> bool {
+    device
+        .property_value("ID_SEAT")
+        .map(|seat_name| seat_name == seat)
+        .unwrap_or_else(|| seat == "seat0")
+}
+
+fn scan_devices(subsystem: &str, seat: &str) -> io::Result<Vec<udev::Device>> {
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem(subsystem)?;
+    Ok(enumerator
+        .scan_devices()?
+        .filter(|device| is_on_seat(device, seat))
+        .collect())
+}
+
+impl Backlight {
+    fn from_device(device: &udev::Device) -> io::Result<Backlight> {
+        let kind = match device.attribute_value("type").and_then(OsStr::to_str) {
+            Some("firmware") => BacklightType::Firmware,
+            Some("platform") => BacklightType::Platform,
+            _ => BacklightType::Raw,
+        };
+        // raw backlights are children of the drm connector of the panel, e.g. `card0-eDP-1`
+        let connector = device
+            .parent()
+            .filter(|parent| parent.subsystem() == Some(OsStr::new("drm")))
+            .and_then(|parent| {
+                let name = parent.sysname().to_string_lossy();
+                name.split_once('-').map(|(_, connector)| connector.to_string())
+            });
+        let syspath = device.syspath().to_path_buf();
+
+        Ok(Backlight {
+            name: device.sysname().to_string_lossy().into_owned(),
+            max_brightness: read_attribute(&syspath, "max_brightness")?,
+            syspath,
+            kind,
+            connector,
+        })
+    }
+
+    /// Name of the backlight in sysfs, e.g. `intel_backlight`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Interface used to control this backlight
+    pub fn kind(&self) -> BacklightType {
+        self.kind
+    }
+
+    /// Name of the connector of the panel this backlight belongs to, e.g. `eDP-1`, if known
+    ///
+    /// Only raw backlights are associated with a connector by the kernel.
+    pub fn connector(&self) -> Option<&str> {
+        self.connector.as_deref()
+    }
+
+    /// Maximum brightness value supported by this backlight
+    pub fn max_brightness(&self) -> u32 {
+        self.max_brightness
+    }
+
+    /// Reads the current brightness of this backlight
+    pub fn brightness(&self) -> io::Result<u32> {
+        read_brightness(&self.syspath)
+    }
+
+    /// Sets the brightness of this backlight by writing to sysfs
+    ///
+    /// The value is clamped to [`Backlight::max_brightness`]. This requires write access
+    /// to the `brightness` attribute of the device, which is usually restricted to root.
+    pub fn set_brightness(&self, brightness: u32) -> io::Result<()> {
+        write_brightness(&self.syspath, brightness.min(self.max_brightness))
+    }
+
+    /// Sets the brightness of this backlight through logind
+    ///
+    /// The value is clamped to [`Backlight::max_brightness`].
+    #[cfg(feature = "backend_session_logind")]
+    pub fn set_brightness_logind(
+        &self,
+        session: &LogindSession,
+        brightness: u32,
+    ) -> Result<(), logind::Error> {
+        session.set_brightness("backlight", &self.name, brightness.min(self.max_brightness))
+    }
+
+    /// Sets the brightness of this backlight through an [`AutoSession`]
+    ///
+    /// Uses logind for logind sessions and sysfs otherwise, see [`AutoSession::set_brightness`].
+    /// The value is clamped to [`Backlight::max_brightness`].
+    #[cfg(feature = "backend_session")]
+    pub fn set_brightness_session(&self, session: &AutoSession, brightness: u32) -> Result<(), auto::Error> {
+        session.set_brightness("backlight", &self.name, brightness.min(self.max_brightness))
+    }
+}
+
+/// Returns all backlights of a given seat, in the order of preference
+pub fn backlights<S: AsRef<str>>(seat: S) -> io::Result<Vec<Backlight>> {
+    let mut backlights = scan_devices("backlight", seat.as_ref())?
+        .iter()
+        .flat_map(|device| Backlight::from_device(device).ok())
+        .collect::<Vec<_>>();
+    backlights.sort_by_key(|backlight| backlight.kind);
+    Ok(backlights)
+}
+
+/// Returns the preferred backlight of a given seat, if any
+///
+/// Might be used to implement brightness keys, as most systems only have a single
+/// internal panel with a backlight.
+pub fn primary_backlight<S: AsRef<str>>(seat: S) -> io::Result<Option<Backlight>> {
+    backlights(seat).map(|backlights| backlights.into_iter().next())
+}
+
+/// Changes of the backlights of a seat, generated by the [`BacklightMonitor`]
+#[derive(Debug)]
+pub enum BacklightEvent {
+    /// A new backlight has been detected
+    Added(Backlight),
+    /// A backlight has changed, e.g. because the firmware changed its brightness
+    Changed(Backlight),
+    /// A backlight has been removed
+    Removed {
+        /// Name of the removed backlight in sysfs
+        name: String,
+    },
+}
+
+/// Event source monitoring the backlights of a seat
+///
+/// Like the [`UdevBackend`](crate::backend::udev::UdevBackend), this only notifies about *changes*,
+/// use [`backlights`] to get the initial list of backlights.
+///
+/// ```no_run
+/// use smithay::backend::backlight::{BacklightEvent, BacklightMonitor};
+///
+/// let monitor = BacklightMonitor::new("seat0", None).expect("Failed to monitor backlights.");
+/// # let event_loop = smithay::reexports::calloop::EventLoop::<()>::try_new().unwrap();
+/// # let loop_handle = event_loop.handle();
+/// loop_handle.insert_source(monitor, |event, _, _| match event {
+///     BacklightEvent::Added(backlight) | BacklightEvent::Changed(backlight) => {
+///         // e.g. update the brightness displayed to the user
+///     }
+///     BacklightEvent::Removed { name } => {
+///         // a backlight has been removed
+///     }
+/// }).expect("Failed to insert the backlight monitor into the event loop");
+/// ```
+pub struct BacklightMonitor {
+    seat: String,
+    monitor: MonitorSocket,
+    token: Option<Token>,
+    logger: ::slog::Logger,
+}
+
+// MonitorSocket does not implement debug, so we have to impl Debug manually
+impl fmt::Debug for BacklightMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use udev::AsRaw;
+        f.debug_struct("BacklightMonitor")
+            .field("seat", &self.seat)
+            .field("monitor", &format!("MonitorSocket ({:?})", self.monitor.as_raw()))
+            .field("logger", &self.logger)
+            .finish()
+    }
+}
+
+impl AsRawFd for BacklightMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.monitor.as_raw_fd()
+    }
+}
+
+impl BacklightMonitor {
+    /// Creates a new [`BacklightMonitor`] for the backlights of a given seat
+    pub fn new<L, S: AsRef<str>>(seat: S, logger: L) -> io::Result<BacklightMonitor>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_backlight"));
+        Ok(BacklightMonitor {
+            seat: seat.as_ref().to_owned(),
+            monitor: MonitorBuilder::new()?.match_subsystem("backlight")?.listen()?,
+            token: None,
+            logger,
+        })
+    }
+}
+
+impl EventSource for BacklightMonitor {
+    type Event = BacklightEvent;
+    type Metadata = ();
+    type Ret = ();
+    type Error = io::Error;
+
+    fn process_events<F>(&mut self, _: Readiness, token: Token, mut callback: F) -> io::Result<PostAction>
+    where
+        F: FnMut(BacklightEvent, &mut ()),
+    {
+        if Some(token) != self.token {
+            return Ok(PostAction::Continue);
+        }
+        let monitor = self.monitor.clone();
+        for event in monitor {
+            debug!(
+                self.logger,
+                "Udev event: type={}, syspath={:?}",
+                event.event_type(),
+                event.syspath()
+            );
+            if !is_on_seat(&event, &self.seat) {
+                continue;
+            }
+            match event.event_type() {
+                EventType::Add | EventType::Change => match Backlight::from_device(&event) {
+                    Ok(backlight) => {
+                        let event = if event.event_type() == EventType::Add {
+                            info!(self.logger, "New backlight: {}", backlight.name());
+                            BacklightEvent::Added(backlight)
+                        } else {
+                            BacklightEvent::Changed(backlight)
+                        };
+                        callback(event, &mut ());
+                    }
+                    Err(err) => warn!(
+                        self.logger,
+                        "Failed to read backlight {:?}: {}",
+                        event.sysname(),
+                        err
+                    ),
+                },
+                EventType::Remove => {
+                    let name = event.sysname().to_string_lossy().into_owned();
+                    info!(self.logger, "Backlight removed: {}", name);
+                    callback(BacklightEvent::Removed { name }, &mut ());
+                }
+                _ => {}
+            }
+        }
+        Ok(PostAction::Continue)
+    }
+
+    fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.token = Some(factory.token());
+        poll.register(self.as_raw_fd(), Interest::READ, Mode::Level, self.token.unwrap())
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.token = Some(factory.token());
+        poll.reregister(self.as_raw_fd(), Interest::READ, Mode::Level, self.token.unwrap())
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.token = None;
+        poll.unregister(self.as_raw_fd())
+    }
+}
+
+/// An LED device, like a keyboard backlight or the caps lock indicator of a keyboard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Led {
+    name: String,
+    syspath: PathBuf,
+    max_brightness: u32,
+}
+
+impl Led {
+    fn from_device(device: &udev::Device) -> io::Result<Led> {
+        let syspath = device.syspath().to_path_buf();
+        Ok(Led {
+            name: device.sysname().to_string_lossy().into_owned(),
+            max_brightness: read_attribute(&syspath, "max_brightness")?,
+            syspath,
+        })
+    }
+
+    /// Name of the LED in sysfs, e.g. `tpacpi::kbd_backlight` or `input3::capslock`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Function of the LED, e.g. `kbd_backlight` or `capslock`
+    ///
+    /// LED names follow the `devicename:color:function` scheme, this returns the last part.
+    pub fn function(&self) -> &str {
+        self.name.rsplit(':').next().unwrap_or_default()
+    }
+
+    /// Color of the LED, if named by the kernel
+    pub fn color(&self) -> Option<&str> {
+        let mut parts = self.name.split(':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(_), Some(color), Some(_)) if !color.is_empty() => Some(color),
+            _ => None,
+        }
+    }
+
+    /// Returns true if this LED is the backlight of a keyboard
+    pub fn is_keyboard_backlight(&self) -> bool {
+        self.function() == "kbd_backlight"
+    }
+
+    /// Maximum brightness value supported by this LED
+    ///
+    /// Simple LEDs, that can only be turned on or off, have a maximum brightness of `1`.
+    pub fn max_brightness(&self) -> u32 {
+        self.max_brightness
+    }
+
+    /// Reads the current brightness of this LED
+    pub fn brightness(&self) -> io::Result<u32> {
+        read_brightness(&self.syspath)
+    }
+
+    /// Sets the brightness of this LED by writing to sysfs
+    ///
+    /// The value is clamped to [`Led::max_brightness`]. This requires write access
+    /// to the `brightness` attribute of the device, which is usually restricted to root.
+    pub fn set_brightness(&self, brightness: u32) -> io::Result<()> {
+        write_brightness(&self.syspath, brightness.min(self.max_brightness))
+    }
+
+    /// Sets the brightness of this LED through logind
+    ///
+    /// The value is clamped to [`Led::max_brightness`].
+    #[cfg(feature = "backend_session_logind")]
+    pub fn set_brightness_logind(
+        &self,
+        session: &LogindSession,
+        brightness: u32,
+    ) -> Result<(), logind::Error> {
+        session.set_brightness("leds", &self.name, brightness.min(self.max_brightness))
+    }
+
+    /// Sets the brightness of this LED through an [`AutoSession`]
+    ///
+    /// Uses logind for logind sessions and sysfs otherwise, see [`AutoSession::set_brightness`].
+    /// The value is clamped to [`Led::max_brightness`].
+    #[cfg(feature = "backend_session")]
+    pub fn set_brightness_session(&self, session: &AutoSession, brightness: u32) -> Result<(), auto::Error> {
+        session.set_brightness("leds", &self.name, brightness.min(self.max_brightness))
+    }
+}
+
+/// Returns all LEDs of a given seat
+pub fn leds<S: AsRef<str>>(seat: S) -> io::Result<Vec<Led>> {
+    Ok(scan_devices("leds", seat.as_ref())?
+        .iter()
+        .flat_map(|device| Led::from_device(device).ok())
+        .collect())
+}
+
+/// Returns the keyboard backlights of a given seat
+///
+/// Might be used to implement the keyboard backlight keys of laptops.
+pub fn keyboard_backlights<S: AsRef<str>>(seat: S) -> io::Result<Vec<Led>> {
+    leds(seat).map(|leds| leds.into_iter().filter(Led::is_keyboard_backlight).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn led(name: &str) -> Led {
+        Led {
+            name: String::from(name),
+            syspath: Path::new("/sys/class/leds").join(name),
+            max_brightness: 1,
+        }
+    }
+
+    #[test]
+    fn led_function() {
+        assert_eq!(led("tpacpi::kbd_backlight").function(), "kbd_backlight");
+        assert_eq!(led("input3::capslock").function(), "capslock");
+        assert_eq!(led("phy0:green:wlan").function(), "wlan");
+        // names not following the scheme are used as a whole
+        assert_eq!(led("mmc0").function(), "mmc0");
+    }
+
+    #[test]
+    fn led_color() {
+        assert_eq!(led("phy0:green:wlan").color(), Some("green"));
+        assert_eq!(led("tpacpi::kbd_backlight").color(), None);
+        assert_eq!(led("input3::capslock").color(), None);
+        assert_eq!(led("mmc0").color(), None);
+    }
+
+    #[test]
+    fn led_keyboard_backlight() {
+        assert!(led("tpacpi::kbd_backlight").is_keyboard_backlight());
+        assert!(led("asus:white:kbd_backlight").is_keyboard_backlight());
+        assert!(!led("input3::capslock").is_keyboard_backlight());
+        assert!(!led("kbd_backlight_2").is_keyboard_backlight());
+    }
+}
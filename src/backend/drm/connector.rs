@@ -0,0 +1,77 @@
+//! Discovery of the connected outputs of a drm device
+
+use std::borrow::Cow;
+
+use drm::control::{connector, crtc, Device as ControlDevice};
+
+use super::{device::DevPath, DrmError};
+
+/// Returns the name of a connector, like `eDP-1` or `HDMI-A-2`
+///
+/// Common interfaces use the short names of the kernel, others are named after their
+/// [`Interface`](connector::Interface) variant, e.g. `VGA-1` or `LVDS-1`. The names are stable
+/// across restarts and can be used to configure outputs.
+pub fn connector_name(info: &connector::Info) -> String {
+    let interface = match info.interface() {
+        connector::Interface::DVII => Cow::Borrowed("DVI-I"),
+        connector::Interface::DVID => Cow::Borrowed("DVI-D"),
+        connector::Interface::DVIA => Cow::Borrowed("DVI-A"),
+        connector::Interface::SVideo => Cow::Borrowed("S-VIDEO"),
+        connector::Interface::DisplayPort => Cow::Borrowed("DP"),
+        connector::Interface::HDMIA => Cow::Borrowed("HDMI-A"),
+        connector::Interface::HDMIB => Cow::Borrowed("HDMI-B"),
+        connector::Interface::EmbeddedDisplayPort => Cow::Borrowed("eDP"),
+        other => Cow::Owned(format!("{:?}", other)),
+    };
+    format!("{}-{}", interface, info.interface_id())
+}
+
+/// Returns the connectors of a device, that have a display connected
+pub fn connected_connectors(device: &impl ControlDevice) -> Result<Vec<connector::Info>, DrmError> {
+    let resources = device.resource_handles().map_err(|source| DrmError::Access {
+        errmsg: "Error loading resource handles",
+        dev: device.dev_path(),
+        source,
+    })?;
+    let mut connected = Vec::new();
+    for handle in resources.connectors() {
+        let info = device.get_connector(*handle).map_err(|source| DrmError::Access {
+            errmsg: "Error loading connector info",
+            dev: device.dev_path(),
+            source,
+        })?;
+        if info.state() == connector::State::Connected {
+            connected.push(info);
+        }
+    }
+    Ok(connected)
+}
+
+/// Returns the crtcs, that can drive a connector through any of its encoders
+///
+/// The crtcs are ordered by the encoders of the connector. Crtcs already used by other
+/// connectors have to be skipped by the caller.
+pub fn connector_crtcs(
+    device: &impl ControlDevice,
+    info: &connector::Info,
+) -> Result<Vec<crtc::Handle>, DrmError> {
+    let resources = device.resource_handles().map_err(|source| DrmError::Access {
+        errmsg: "Error loading resource handles",
+        dev: device.dev_path(),
+        source,
+    })?;
+    let mut crtcs = Vec::new();
+    for encoder in info.encoders().iter().flatten() {
+        let encoder = device.get_encoder(*encoder).map_err(|source| DrmError::Access {
+            errmsg: "Error loading encoder info",
+            dev: device.dev_path(),
+            source,
+        })?;
+        for crtc in resources.filter_crtcs(encoder.possible_crtcs()) {
+            if !crtcs.contains(&crtc) {
+                crtcs.push(crtc);
+            }
+        }
+    }
+    Ok(crtcs)
+}
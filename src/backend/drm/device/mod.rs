@@ -7,7 +7,9 @@ use std::sync::{atomic::AtomicBool, Arc};
 use std::time::{Instant, SystemTime};
 
 use calloop::{EventSource, Interest, Poll, PostAction, Readiness, Token, TokenFactory};
-use drm::control::{connector, crtc, Device as ControlDevice, Event, Mode, ResourceHandles};
+use drm::control::{
+    connector, crtc, Device as ControlDevice, Event, Mode, RawResourceHandle, ResourceHandles,
+};
 use drm::{ClientCapability, Device as BasicDevice, DriverCapability};
 use nix::libc::dev_t;
 use nix::sys::stat::fstat;
@@ -15,7 +17,7 @@ use nix::sys::stat::fstat;
 pub(super) mod atomic;
 pub(super) mod legacy;
 use super::surface::{atomic::AtomicDrmSurface, legacy::LegacyDrmSurface, DrmSurface, DrmSurfaceInternal};
-use super::{error::Error, planes, Planes};
+use super::{error::Error, lease::DrmLease, planes, Planes};
 use atomic::AtomicDrmDevice;
 use legacy::LegacyDrmDevice;
 
@@ -290,6 +292,15 @@ impl<A: AsRawFd + 'static> DrmDevice<A> {
         })
     }
 
+    /// Leases the given resources to another drm client, see [`DrmLease`]
+    ///
+    /// A lessee needs at least a connector, a crtc and, on atomic devices, the primary plane
+    /// of the crtc to display anything. Leased resources are not accessible by this device anymore,
+    /// until the lease ends, so they should not be used by any [`DrmSurface`] of it.
+    pub fn create_lease(&self, objects: &[RawResourceHandle]) -> Result<DrmLease<A>, Error> {
+        DrmLease::new(self.internal.clone(), objects, self.logger.clone())
+    }
+
     /// Returns the device_id of the underlying drm node
     pub fn device_id(&self) -> dev_t {
         self.dev_id
@@ -329,7 +340,7 @@ pub struct EventMetadata {
 }
 
 /// Either a realtime or monotonic timestamp
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Time {
     /// Monotonic time stamp
     Monotonic(Instant),
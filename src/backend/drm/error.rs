@@ -1,4 +1,4 @@
-use crate::backend::SwapBuffersError;
+use crate::backend::{SwapBuffersError, TemporaryFailureReason};
 use drm::control::{connector, crtc, plane, Mode, RawResourceHandle};
 use std::path::PathBuf;
 
@@ -40,6 +40,9 @@ pub enum Error {
     /// The given plane is not a primary plane and therefor not supported by the underlying implementation
     #[error("Non-Primary Planes (provided was `{0:?}`) are not available for use with legacy devices")]
     NonPrimaryPlane(plane::Handle),
+    /// The given plane is not set up on the surface
+    #[error("Plane `{1:?}` is not in use on crtc `{0:?}`")]
+    PlaneNotInUse(crtc::Handle, plane::Handle),
     /// No encoder was found for a given connector on the set crtc
     #[error("No encoder found for the given connector '{connector:?}' on crtc `{crtc:?}`")]
     NoSuitableEncoder {
@@ -63,24 +66,39 @@ pub enum Error {
 
 impl From<Error> for SwapBuffersError {
     fn from(err: Error) -> SwapBuffersError {
-        match err {
-            x @ Error::DeviceInactive => SwapBuffersError::TemporaryFailure(Box::new(x)),
+        let device = match &err {
+            Error::Access { dev, .. } => dev.clone(),
+            _ => None,
+        };
+        let reason = match &err {
+            Error::DeviceInactive
+            | Error::Access {
+                source: drm::SystemError::PermissionDenied,
+                ..
+            } => Some(TemporaryFailureReason::DeviceInactive),
             Error::Access {
-                errmsg, dev, source, ..
-            } if matches!(
-                source,
-                drm::SystemError::PermissionDenied
-                    | drm::SystemError::Unknown {
+                source:
+                    drm::SystemError::Unknown {
                         errno: nix::errno::Errno::EBUSY,
-                    }
-                    | drm::SystemError::Unknown {
+                    },
+                ..
+            } => Some(TemporaryFailureReason::Busy),
+            Error::Access {
+                source:
+                    drm::SystemError::Unknown {
                         errno: nix::errno::Errno::EINTR,
-                    }
-            ) =>
-            {
-                SwapBuffersError::TemporaryFailure(Box::new(Error::Access { errmsg, dev, source }))
-            }
-            x => SwapBuffersError::ContextLost(Box::new(x)),
+                    },
+                ..
+            } => Some(TemporaryFailureReason::Other),
+            _ => None,
+        };
+        let err = match reason {
+            Some(reason) => SwapBuffersError::temporary_failure(reason, err),
+            None => SwapBuffersError::context_lost(err),
+        };
+        match device {
+            Some(device) => err.with_device(device),
+            None => err,
         }
     }
 }
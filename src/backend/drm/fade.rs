@@ -0,0 +1,270 @@
+//! Gamma ramps and fade animations of outputs
+
+use std::time::Duration;
+
+/// Gamma lookup table of a crtc, see [`DrmSurface::gamma`](super::DrmSurface::gamma)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GammaRamp {
+    /// Values of the red channel
+    pub red: Vec<u16>,
+    /// Values of the green channel
+    pub green: Vec<u16>,
+    /// Values of the blue channel
+    pub blue: Vec<u16>,
+}
+
+impl GammaRamp {
+    /// Creates a linear ramp with `size` entries per channel, that leaves colors unchanged
+    pub fn linear(size: usize) -> GammaRamp {
+        let channel = (0..size)
+            .map(|i| {
+                if size > 1 {
+                    (i * u16::MAX as usize / (size - 1)) as u16
+                } else {
+                    u16::MAX
+                }
+            })
+            .collect::<Vec<_>>();
+        GammaRamp {
+            red: channel.clone(),
+            green: channel.clone(),
+            blue: channel,
+        }
+    }
+
+    /// Returns the number of entries per channel
+    pub fn len(&self) -> usize {
+        self.red.len()
+    }
+
+    /// Checks if the ramp has no entries
+    pub fn is_empty(&self) -> bool {
+        self.red.is_empty()
+    }
+
+    /// Returns a copy of this ramp with all values multiplied by `brightness`, between `0.0` (black) and `1.0`
+    pub fn scaled(&self, brightness: f64) -> GammaRamp {
+        let brightness = brightness.clamp(0.0, 1.0);
+        let scale = |channel: &[u16]| {
+            channel
+                .iter()
+                .map(|value| (*value as f64 * brightness).round() as u16)
+                .collect()
+        };
+        GammaRamp {
+            red: scale(&self.red),
+            green: scale(&self.green),
+            blue: scale(&self.blue),
+        }
+    }
+}
+
+/// State of a [`GammaFade`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FadeState {
+    /// The output is displayed with its original gamma ramp
+    On,
+    /// The output is fading to black
+    FadingOut,
+    /// The output is black
+    Off,
+    /// The output is fading back to its original gamma ramp
+    FadingIn,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Animation {
+    from: f64,
+    to: f64,
+    start: Duration,
+    duration: Duration,
+}
+
+impl Animation {
+    fn brightness(&self, now: Duration) -> f64 {
+        let elapsed = now.saturating_sub(self.start).as_secs_f64();
+        let duration = self.duration.as_secs_f64();
+        let t = if duration > 0.0 {
+            (elapsed / duration).min(1.0)
+        } else {
+            1.0
+        };
+        // smoothstep, so the fade neither starts nor ends abruptly
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.from + (self.to - self.from) * eased
+    }
+
+    fn finished(&self, now: Duration) -> bool {
+        now >= self.start + self.duration
+    }
+}
+
+/// Animates the gamma ramp of an output to fade it to black and back
+///
+/// Used to fade out outputs before they are turned off on idle or before locking the session, and
+/// to fade them back in on resume. The fade starts from the current brightness, so interrupting a
+/// fade-out with [`GammaFade::fade_in`], e.g. because the user moved the pointer, reverses it smoothly.
+///
+/// All times are absolute `CLOCK_MONOTONIC` times, like the ones returned by
+/// [`monotonic_time`](crate::utils::monotonic_time). While [`GammaFade::is_animating`],
+/// apply [`GammaFade::ramp`] through [`DrmSurface::set_gamma`](super::DrmSurface::set_gamma) once
+/// per frame. Once the state reached [`FadeState::Off`], the output can be turned off, e.g. by
+/// removing its connectors, and it has to be turned on again before fading it back in.
+///
+/// The gamma ramp is lost, when another DRM master takes over the device, so apply
+/// [`GammaFade::ramp`] again, after the session was activated again.
+///
+/// ```no_run
+/// # use smithay::backend::drm::{GammaFade, GammaRamp, FadeState};
+/// # use smithay::utils::monotonic_time;
+/// # use std::time::Duration;
+/// # let original = GammaRamp::linear(256);
+/// let mut fade = GammaFade::new(original);
+/// fade.fade_out(monotonic_time(), Duration::from_millis(500));
+///
+/// // for every frame
+/// let now = monotonic_time();
+/// let ramp = fade.ramp(now);
+/// // apply `ramp` to the surface
+/// if fade.state(now) == FadeState::Off {
+///     // turn the output off
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct GammaFade {
+    original: GammaRamp,
+    animation: Option<Animation>,
+    brightness: f64,
+}
+
+impl GammaFade {
+    /// Creates a new fade of the given original gamma ramp, starting fully on
+    pub fn new(original: GammaRamp) -> GammaFade {
+        GammaFade {
+            original,
+            animation: None,
+            brightness: 1.0,
+        }
+    }
+
+    /// Returns the original gamma ramp
+    pub fn original(&self) -> &GammaRamp {
+        &self.original
+    }
+
+    /// Replaces the original gamma ramp, e.g. after night light adjusted it
+    pub fn set_original(&mut self, original: GammaRamp) {
+        self.original = original;
+    }
+
+    /// Starts fading to black, taking `duration` from full brightness
+    pub fn fade_out(&mut self, now: Duration, duration: Duration) {
+        self.animate(now, 0.0, duration);
+    }
+
+    /// Starts fading back to the original gamma ramp, taking `duration` from black
+    pub fn fade_in(&mut self, now: Duration, duration: Duration) {
+        self.animate(now, 1.0, duration);
+    }
+
+    fn animate(&mut self, now: Duration, to: f64, duration: Duration) {
+        let from = self.brightness(now);
+        self.brightness = from;
+        // a partial fade takes the same fraction of the duration
+        let duration = duration.mul_f64((to - from).abs());
+        self.animation = Some(Animation {
+            from,
+            to,
+            start: now,
+            duration,
+        });
+    }
+
+    /// Returns the brightness at the given time, between `0.0` (black) and `1.0`
+    pub fn brightness(&self, now: Duration) -> f64 {
+        self.animation
+            .map(|animation| animation.brightness(now))
+            .unwrap_or(self.brightness)
+    }
+
+    /// Returns the state of the fade at the given time
+    pub fn state(&self, now: Duration) -> FadeState {
+        match self.animation {
+            Some(animation) if !animation.finished(now) => {
+                if animation.to < animation.from {
+                    FadeState::FadingOut
+                } else {
+                    FadeState::FadingIn
+                }
+            }
+            _ => {
+                if self.brightness(now) <= 0.0 {
+                    FadeState::Off
+                } else {
+                    FadeState::On
+                }
+            }
+        }
+    }
+
+    /// Checks if a fade is in progress at the given time
+    pub fn is_animating(&self, now: Duration) -> bool {
+        matches!(self.state(now), FadeState::FadingOut | FadeState::FadingIn)
+    }
+
+    /// Returns the gamma ramp to apply at the given time
+    pub fn ramp(&self, now: Duration) -> GammaRamp {
+        let brightness = self.brightness(now);
+        if brightness >= 1.0 {
+            self.original.clone()
+        } else {
+            self.original.scaled(brightness)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn linear_ramp() {
+        let ramp = GammaRamp::linear(3);
+        assert_eq!(ramp.red, vec![0, 32767, 65535]);
+        assert_eq!(ramp.scaled(0.5).green, vec![0, 16384, 32768]);
+        assert_eq!(ramp.scaled(0.0).blue, vec![0, 0, 0]);
+        assert_eq!(GammaRamp::linear(1).red, vec![u16::MAX]);
+    }
+
+    #[test]
+    fn fade_out_and_in() {
+        let mut fade = GammaFade::new(GammaRamp::linear(256));
+        assert_eq!(fade.state(ms(0)), FadeState::On);
+
+        fade.fade_out(ms(1000), ms(500));
+        assert_eq!(fade.state(ms(1000)), FadeState::FadingOut);
+        assert!((fade.brightness(ms(1250)) - 0.5).abs() < 1e-9);
+        assert_eq!(fade.state(ms(1500)), FadeState::Off);
+        assert_eq!(fade.ramp(ms(1500)), GammaRamp::linear(256).scaled(0.0));
+
+        fade.fade_in(ms(2000), ms(500));
+        assert!(fade.is_animating(ms(2100)));
+        assert_eq!(fade.state(ms(2500)), FadeState::On);
+        assert_eq!(fade.ramp(ms(2500)), GammaRamp::linear(256));
+    }
+
+    #[test]
+    fn interrupted_fade_reverses() {
+        let mut fade = GammaFade::new(GammaRamp::linear(256));
+        fade.fade_out(ms(0), ms(1000));
+        let brightness = fade.brightness(ms(500));
+        fade.fade_in(ms(500), ms(1000));
+        // continues from the current brightness
+        assert!((fade.brightness(ms(500)) - brightness).abs() < 1e-9);
+        assert_eq!(fade.state(ms(1000)), FadeState::On);
+    }
+}
@@ -0,0 +1,174 @@
+//! Low-latency repaints of small regions ahead of the frame clock
+
+use std::time::Duration;
+
+use crate::utils::{Physical, Rectangle};
+
+/// Decides when small updates, like the strokes of a stylus, are rendered into the front buffer
+///
+/// Rendering a frame and flipping to it takes up to two refresh cycles until it is visible, which
+/// is noticeable when writing by hand. Small regions can instead be rendered right away into
+/// the front buffer through [`GbmBufferedSurface::front_buffer`](super::GbmBufferedSurface::front_buffer),
+/// which makes them visible as soon as the display controller scans them out, at the cost of tearing.
+///
+/// Feed the damage of the surfaces driven by a stylus to [`FastRepaint::damage`] and repaint it
+/// immediately if [`FastRepaint::take`] returns it. Once a region was repainted this way, the
+/// path stays engaged for the configured `linger` time, during which regular frames should be
+/// rendered into the front buffer as well, as flipping would block further fast repaints. Damage
+/// larger than `max_area` falls back to regular frames, as it would tear visibly.
+///
+/// All times are absolute `CLOCK_MONOTONIC` times, like the ones returned by
+/// [`monotonic_time`](crate::utils::monotonic_time).
+#[derive(Debug, Clone)]
+pub struct FastRepaint {
+    max_area: i64,
+    linger: Duration,
+    damage: Vec<Rectangle<i32, Physical>>,
+    damaged_at: Option<Duration>,
+    last_repaint: Option<Duration>,
+    stats: FastRepaintStats,
+}
+
+/// Statistics of a [`FastRepaint`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FastRepaintStats {
+    /// Number of regions repainted into the front buffer
+    pub repaints: u64,
+    /// Number of regions left to regular frames, because they were too large or the front buffer
+    /// was unavailable
+    pub fallbacks: u64,
+    /// Time between the damage and the repaint of the last fast repaint
+    pub last_latency: Option<Duration>,
+    /// Highest time between damage and repaint of any fast repaint
+    pub max_latency: Duration,
+}
+
+impl FastRepaint {
+    /// Creates a new policy repainting at most `max_area` pixels at once
+    pub fn new(max_area: i32, linger: Duration) -> FastRepaint {
+        FastRepaint {
+            max_area: max_area.max(0) as i64,
+            linger,
+            damage: Vec::new(),
+            damaged_at: None,
+            last_repaint: None,
+            stats: FastRepaintStats::default(),
+        }
+    }
+
+    /// Adds damaged regions in buffer coordinates, that happened at `now`
+    pub fn damage(&mut self, damage: impl IntoIterator<Item = Rectangle<i32, Physical>>, now: Duration) {
+        let len = self.damage.len();
+        for mut rect in damage {
+            if rect.size.w <= 0 || rect.size.h <= 0 {
+                continue;
+            }
+            // overlapping regions would be counted twice
+            while let Some(idx) = self.damage.iter().position(|other| other.overlaps(rect)) {
+                rect = rect.merge(self.damage.swap_remove(idx));
+            }
+            self.damage.push(rect);
+        }
+        if self.damaged_at.is_none() && self.damage.len() > len {
+            self.damaged_at = Some(now);
+        }
+    }
+
+    /// Returns true if damage is waiting to be repainted
+    pub fn has_damage(&self) -> bool {
+        !self.damage.is_empty()
+    }
+
+    /// Takes the pending damage, if it is small enough to be repainted right away
+    ///
+    /// Returns `None` if nothing is pending or if the damage is too large, in which case it is
+    /// dropped, as the next regular frame repaints it, and the path disengages.
+    pub fn take(&mut self) -> Option<Vec<Rectangle<i32, Physical>>> {
+        if self.damage.is_empty() {
+            return None;
+        }
+        let area = self
+            .damage
+            .iter()
+            .map(|rect| rect.size.w as i64 * rect.size.h as i64)
+            .sum::<i64>();
+        if area > self.max_area {
+            self.fall_back();
+            return None;
+        }
+        Some(std::mem::take(&mut self.damage))
+    }
+
+    /// Notes that the damage returned by [`FastRepaint::take`] was repainted into the front buffer
+    pub fn repainted(&mut self, now: Duration) {
+        if let Some(damaged_at) = self.damaged_at.take() {
+            let latency = now.saturating_sub(damaged_at);
+            self.stats.last_latency = Some(latency);
+            self.stats.max_latency = self.stats.max_latency.max(latency);
+        }
+        self.stats.repaints += 1;
+        self.last_repaint = Some(now);
+    }
+
+    /// Notes that the pending damage is left to the next regular frame, e.g. because the front
+    /// buffer is unavailable while a page flip is pending
+    ///
+    /// This disengages the path, until the next fast repaint.
+    pub fn fall_back(&mut self) {
+        self.damage.clear();
+        self.damaged_at = None;
+        self.last_repaint = None;
+        self.stats.fallbacks += 1;
+    }
+
+    /// Checks if regular frames should be rendered into the front buffer at the given time
+    ///
+    /// This is the case for `linger` after every fast repaint.
+    pub fn is_engaged(&self, now: Duration) -> bool {
+        self.last_repaint
+            .map(|last_repaint| now < last_repaint + self.linger)
+            .unwrap_or(false)
+    }
+
+    /// Returns the statistics of this policy
+    pub fn stats(&self) -> FastRepaintStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Physical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    #[test]
+    fn repaints_small_damage() {
+        let ms = Duration::from_millis;
+        let mut fast = FastRepaint::new(400, ms(500));
+        assert_eq!(fast.take(), None);
+        assert!(!fast.is_engaged(ms(0)));
+
+        fast.damage([rect(0, 0, 10, 10), rect(5, 5, 10, 10)], ms(10));
+        fast.damage([rect(100, 100, 10, 10)], ms(12));
+        assert!(fast.has_damage());
+        assert_eq!(
+            fast.take(),
+            Some(vec![rect(0, 0, 15, 15), rect(100, 100, 10, 10)])
+        );
+        fast.repainted(ms(15));
+        assert!(fast.is_engaged(ms(400)));
+        assert!(!fast.is_engaged(ms(515)));
+
+        // too large, left to regular frames
+        fast.damage([rect(0, 0, 30, 30)], ms(20));
+        assert_eq!(fast.take(), None);
+        assert!(!fast.is_engaged(ms(20)));
+
+        let stats = fast.stats();
+        assert_eq!((stats.repaints, stats.fallbacks), (1, 1));
+        assert_eq!(stats.last_latency, Some(ms(5)));
+    }
+}
@@ -0,0 +1,214 @@
+//! Leasing of drm resources to other drm clients
+
+use std::{
+    io,
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        process::CommandExt,
+    },
+    process::{Child, Command},
+    sync::Arc,
+};
+
+use drm::control::RawResourceHandle;
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, FdFlag, OFlag},
+    unistd::close,
+};
+use slog::{info, warn};
+
+use super::{
+    device::{DevPath, DrmDeviceInternal},
+    error::Error,
+};
+
+mod ioctl {
+    use drm_ffi::{drm_mode_create_lease, drm_mode_list_lessees, drm_mode_revoke_lease, DRM_IOCTL_BASE};
+
+    nix::ioctl_readwrite!(create_lease, DRM_IOCTL_BASE, 0xC6, drm_mode_create_lease);
+    nix::ioctl_readwrite!(list_lessees, DRM_IOCTL_BASE, 0xC7, drm_mode_list_lessees);
+    nix::ioctl_readwrite!(revoke_lease, DRM_IOCTL_BASE, 0xC9, drm_mode_revoke_lease);
+}
+
+/// Resources of a [`DrmDevice`](super::DrmDevice) leased to another drm client
+///
+/// Created by [`DrmDevice::create_lease`](super::DrmDevice::create_lease). The lessee gets a new drm
+/// file descriptor, which only has access to the leased resources and is drm master for them,
+/// e.g. to be used by a dedicated video player process. The lease ends, when it is revoked,
+/// which happens at the latest when this object is dropped, or when all copies of the lessee
+/// file descriptor are closed, e.g. because the process holding it exited.
+///
+/// The lessee file descriptor can be
+/// - used in-process, e.g. by creating another [`DrmDevice`](super::DrmDevice) from [`DrmLease::take_fd`],
+/// - handed to a child process by [`DrmLease::spawn`],
+/// - or sent to any other process over a unix socket using [`DrmLease::fd`].
+#[derive(Debug)]
+pub struct DrmLease<A: AsRawFd + 'static> {
+    dev: Arc<DrmDeviceInternal<A>>,
+    lessee_id: u32,
+    objects: Vec<RawResourceHandle>,
+    fd: Option<RawFd>,
+    revoked: bool,
+    logger: ::slog::Logger,
+}
+
+impl<A: AsRawFd + 'static> DrmLease<A> {
+    pub(super) fn new(
+        dev: Arc<DrmDeviceInternal<A>>,
+        objects: &[RawResourceHandle],
+        logger: ::slog::Logger,
+    ) -> Result<DrmLease<A>, Error> {
+        let ids = objects
+            .iter()
+            .map(|handle| u32::from(*handle))
+            .collect::<Vec<_>>();
+        let mut lease = drm_ffi::drm_mode_create_lease {
+            object_ids: ids.as_ptr() as u64,
+            object_count: ids.len() as u32,
+            flags: OFlag::O_CLOEXEC.bits() as u32,
+            ..Default::default()
+        };
+        unsafe { ioctl::create_lease(dev.as_raw_fd(), &mut lease) }.map_err(|errno| Error::Access {
+            errmsg: "Failed to create lease",
+            dev: dev.dev_path(),
+            source: errno.into(),
+        })?;
+
+        info!(logger, "Leased {:?} to lessee {}", objects, lease.lessee_id);
+        Ok(DrmLease {
+            dev,
+            lessee_id: lease.lessee_id,
+            objects: objects.to_vec(),
+            fd: Some(lease.fd as RawFd),
+            revoked: false,
+            logger,
+        })
+    }
+
+    /// Returns the id of the lessee assigned by the kernel
+    pub fn lessee_id(&self) -> u32 {
+        self.lessee_id
+    }
+
+    /// Returns the leased resources
+    pub fn objects(&self) -> &[RawResourceHandle] {
+        &self.objects
+    }
+
+    /// Returns the lessee file descriptor, unless it was already handed out
+    ///
+    /// The file descriptor stays owned by the lease and is closed, when the lease is dropped or revoked.
+    pub fn fd(&self) -> Option<RawFd> {
+        self.fd
+    }
+
+    /// Takes ownership of the lessee file descriptor
+    ///
+    /// The caller is responsible for closing it. Revoking the lease still cuts off its access.
+    pub fn take_fd(&mut self) -> Option<RawFd> {
+        self.fd.take()
+    }
+
+    /// Spawns a child process holding the lessee file descriptor
+    ///
+    /// The file descriptor is inherited by the child and its number is passed in the
+    /// environment variable `env`. The copy held by the lease is closed afterwards, so the lease
+    /// ends on its own, once the child exited. Use [`DrmLease::is_active`] to find out, if it did.
+    pub fn spawn(&mut self, command: &mut Command, env: &str) -> io::Result<Child> {
+        let fd = self
+            .fd
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Lessee fd was already taken"))?;
+        command.env(env, fd.to_string());
+        unsafe {
+            command.pre_exec(move || {
+                fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))
+                    .map(|_| ())
+                    .map_err(|errno| io::Error::from_raw_os_error(errno as i32))
+            });
+        }
+        let child = command.spawn()?;
+        self.close_fd();
+        Ok(child)
+    }
+
+    /// Returns whether the lessee still exists
+    ///
+    /// The lessee ceases to exist, once the lease was revoked or all copies of the lessee
+    /// file descriptor were closed.
+    pub fn is_active(&self) -> Result<bool, Error> {
+        if self.revoked {
+            return Ok(false);
+        }
+        let list = |lessees: &mut Vec<u32>| -> Result<u32, Error> {
+            let mut list = drm_ffi::drm_mode_list_lessees {
+                count_lessees: lessees.len() as u32,
+                lessees_ptr: lessees.as_mut_ptr() as u64,
+                ..Default::default()
+            };
+            unsafe { ioctl::list_lessees(self.dev.as_raw_fd(), &mut list) }.map_err(|errno| {
+                Error::Access {
+                    errmsg: "Failed to list lessees",
+                    dev: self.dev.dev_path(),
+                    source: errno.into(),
+                }
+            })?;
+            Ok(list.count_lessees)
+        };
+
+        // the number of lessees may change between the calls
+        let mut lessees = Vec::new();
+        loop {
+            let count = list(&mut lessees)? as usize;
+            if count <= lessees.len() {
+                lessees.truncate(count);
+                return Ok(lessees.contains(&self.lessee_id));
+            }
+            lessees.resize(count, 0);
+        }
+    }
+
+    /// Revokes the lease, the lessee immediately loses access to the leased resources
+    ///
+    /// Revoking a lease, that already ended, is not an error.
+    pub fn revoke(&mut self) -> Result<(), Error> {
+        if self.revoked {
+            return Ok(());
+        }
+        let mut revoke = drm_ffi::drm_mode_revoke_lease {
+            lessee_id: self.lessee_id,
+        };
+        match unsafe { ioctl::revoke_lease(self.dev.as_raw_fd(), &mut revoke) } {
+            // the lessee is already gone
+            Ok(_) | Err(Errno::ENOENT) => {}
+            Err(errno) => {
+                return Err(Error::Access {
+                    errmsg: "Failed to revoke lease",
+                    dev: self.dev.dev_path(),
+                    source: errno.into(),
+                })
+            }
+        };
+        info!(self.logger, "Revoked lease of lessee {}", self.lessee_id);
+        self.revoked = true;
+        self.close_fd();
+        Ok(())
+    }
+
+    fn close_fd(&mut self) {
+        if let Some(fd) = self.fd.take() {
+            if let Err(err) = close(fd) {
+                warn!(self.logger, "Failed to close lessee fd: {}", err);
+            }
+        }
+    }
+}
+
+impl<A: AsRawFd + 'static> Drop for DrmLease<A> {
+    fn drop(&mut self) {
+        if let Err(err) = self.revoke() {
+            warn!(self.logger, "{}", err);
+        }
+        self.close_fd();
+    }
+}
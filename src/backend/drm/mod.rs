@@ -54,14 +54,32 @@
 //!
 //! A commit/page_flip may be triggered to apply the pending state.
 //!
+//! The connected connectors of a device and the crtcs able to drive them are found through
+//! [`connected_connectors`] and [`connector_crtcs`], while [`connector_name`] names them like the kernel does.
+//!
 //! ## Rendering
 //!
 //! The drm infrastructure makes no assumptions about the used renderer and does not interface with them directly.
 //! It just provides a way to create framebuffers from various buffer types (mainly `DumbBuffer`s and hardware-backed gbm `BufferObject`s).
+//! [`GbmBufferedSurface`] and [`DumbBufferedSurface`] manage a swapchain of these buffers for a [`DrmSurface`].
 //!
 //! Buffer management and details about the various types can be found in the [`allocator`-Module](crate::backend::allocator) and
 //! rendering abstractions, which can target these buffers can be found in the [`renderer`-Module](crate::backend::renderer).
 //!
+//! Slow-refresh displays, like e-ink panels, are usually driven by rendering into the front buffer
+//! (see [`GbmBufferedSurface::front_buffer`]) and committing the damage in batches through a
+//! [`PartialUpdateScheduler`]. The same mechanism keeps the latency of drawing tablets low, by
+//! repainting the strokes of a stylus right away, as decided by a [`FastRepaint`].
+//!
+//! The gamma ramp of a surface can be changed with [`DrmSurface::set_gamma`], e.g. to fade outputs
+//! to black before turning them off using a [`GammaFade`].
+//!
+//! Multiple surfaces of the same device, e.g. the tiles of a video wall, can be flipped by a single atomic
+//! commit through [`page_flip_synchronized`], while a [`VblankDrift`] tracks how far their vblanks are apart.
+//!
+//! Connectors, crtcs and planes can be handed to other drm clients, e.g. a separate video player process,
+//! through a [`DrmLease`] created by [`DrmDevice::create_lease`].
+//!
 //! ## [`DrmNode`]
 //!
 //! A drm node refers to a drm device and the capabilities that may be performed using the node.
@@ -69,19 +87,32 @@
 //! to allocate buffers for use in X11 or Wayland. If you need to do mode setting, you should use
 //! [`DrmDevice`] instead.
 
+mod connector;
 pub(crate) mod device;
 pub(self) mod error;
+pub(self) mod fade;
+pub(self) mod fast_repaint;
+pub(self) mod lease;
 pub mod node;
+pub(self) mod partial_update;
 #[cfg(feature = "backend_session")]
 pub(self) mod session;
 pub(self) mod surface;
+pub(self) mod sync;
 
+pub use connector::{connected_connectors, connector_crtcs, connector_name};
 pub use device::{DevPath, DrmDevice, DrmEvent, EventMetadata as DrmEventMetadata, Time as DrmEventTime};
 pub use error::Error as DrmError;
+pub use fade::{FadeState, GammaFade, GammaRamp};
+pub use fast_repaint::{FastRepaint, FastRepaintStats};
+pub use lease::DrmLease;
 pub use node::{CreateDrmNodeError, DrmNode, NodeType};
+pub use partial_update::{PartialUpdate, PartialUpdateScheduler};
+pub use surface::dumb::{DumbBufferedSurface, Error as DumbBufferedSurfaceError};
 #[cfg(feature = "backend_gbm")]
-pub use surface::gbm::{Error as GbmBufferedSurfaceError, GbmBufferedSurface};
-pub use surface::DrmSurface;
+pub use surface::gbm::{Error as GbmBufferedSurfaceError, FrameStats, GbmBufferedSurface, QueuePolicy};
+pub use surface::{page_flip_synchronized, DrmSurface};
+pub use sync::VblankDrift;
 
 use drm::control::{crtc, plane, Device as ControlDevice, PlaneType};
 
@@ -0,0 +1,185 @@
+//! Scheduling of partial updates for slow-refresh displays
+
+use std::time::Duration;
+
+use crate::utils::{Physical, Rectangle};
+
+/// Batches damage of slow-refresh displays, like e-ink panels, into partial updates
+///
+/// Such panels take a long time to update and flicker on every update, so committing every
+/// frame is neither possible nor desirable. Instead damage is collected and committed at most
+/// once every `min_interval`, e.g. through
+/// [`GbmBufferedSurface::front_buffer_damaged`](super::GbmBufferedSurface::front_buffer_damaged).
+///
+/// All times are absolute `CLOCK_MONOTONIC` times, like the ones used by
+/// [`DeadlineTimer`](crate::utils::timer::DeadlineTimer), so [`PartialUpdateScheduler::deadline`]
+/// can be used to schedule the next repaint:
+///
+/// ```no_run
+/// # use smithay::backend::drm::PartialUpdateScheduler;
+/// # use smithay::utils::{timer::{monotonic_time, DeadlineTimer}, Rectangle};
+/// # use std::time::Duration;
+/// # let (_, timer) = DeadlineTimer::new().unwrap();
+/// let mut scheduler = PartialUpdateScheduler::new(Duration::from_millis(250));
+/// scheduler.damage([Rectangle::from_loc_and_size((0, 0), (100, 20))]);
+/// if let Some(deadline) = scheduler.deadline() {
+///     timer.set_deadline(deadline).unwrap();
+/// }
+///
+/// // once the timer fired
+/// if let Some(update) = scheduler.take_update(monotonic_time()) {
+///     // render `update.damage` and commit it, refreshing the whole panel if `update.full_refresh` is set
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PartialUpdateScheduler {
+    min_interval: Duration,
+    full_refresh_interval: Option<u32>,
+    last_update: Option<Duration>,
+    partial_updates: u32,
+    damage: Vec<Rectangle<i32, Physical>>,
+}
+
+/// A batch of damage to be committed to a slow-refresh display
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialUpdate {
+    /// Damaged regions, overlapping regions are already merged
+    pub damage: Vec<Rectangle<i32, Physical>>,
+    /// Hint that the whole panel should be refreshed, e.g. with a waveform removing ghosting
+    pub full_refresh: bool,
+}
+
+impl PartialUpdateScheduler {
+    /// Creates a new scheduler committing at most once every `min_interval`
+    pub fn new(min_interval: Duration) -> PartialUpdateScheduler {
+        PartialUpdateScheduler {
+            min_interval,
+            full_refresh_interval: None,
+            last_update: None,
+            partial_updates: 0,
+            damage: Vec::new(),
+        }
+    }
+
+    /// Requests a full refresh after every `updates` partial updates
+    ///
+    /// Partial updates of e-ink panels leave ghosting behind, that is only removed by refreshing
+    /// the whole panel. `None` disables full refreshes, which is the default.
+    pub fn set_full_refresh_interval(&mut self, updates: Option<u32>) {
+        self.full_refresh_interval = updates;
+    }
+
+    /// Adds damaged regions to the next update
+    pub fn damage(&mut self, damage: impl IntoIterator<Item = Rectangle<i32, Physical>>) {
+        for mut rect in damage {
+            if rect.size.w <= 0 || rect.size.h <= 0 {
+                continue;
+            }
+            // merge overlapping regions, as every region might be updated separately by the panel
+            while let Some(idx) = self.damage.iter().position(|other| other.overlaps(rect)) {
+                rect = rect.merge(self.damage.swap_remove(idx));
+            }
+            self.damage.push(rect);
+        }
+    }
+
+    /// Returns true if damage is waiting to be committed
+    pub fn has_damage(&self) -> bool {
+        !self.damage.is_empty()
+    }
+
+    /// Returns the earliest time the pending damage may be committed, if any damage is pending
+    pub fn deadline(&self) -> Option<Duration> {
+        if self.damage.is_empty() {
+            return None;
+        }
+        Some(
+            self.last_update
+                .map(|last_update| last_update + self.min_interval)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Takes the pending damage, if any is pending and `min_interval` has passed since the last update
+    pub fn take_update(&mut self, now: Duration) -> Option<PartialUpdate> {
+        if self.deadline()? > now {
+            return None;
+        }
+        self.last_update = Some(now);
+
+        self.partial_updates += 1;
+        let full_refresh = match self.full_refresh_interval {
+            Some(interval) if self.partial_updates > interval => {
+                self.partial_updates = 0;
+                true
+            }
+            _ => false,
+        };
+
+        Some(PartialUpdate {
+            damage: std::mem::take(&mut self.damage),
+            full_refresh,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Physical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    #[test]
+    fn merges_overlapping_damage() {
+        let mut scheduler = PartialUpdateScheduler::new(Duration::from_millis(100));
+        scheduler.damage([rect(0, 0, 10, 10), rect(100, 100, 10, 10), rect(0, 0, 0, 10)]);
+        scheduler.damage([rect(5, 5, 10, 10)]);
+        // bridges the two remaining regions
+        scheduler.damage([rect(10, 10, 95, 95)]);
+
+        let update = scheduler.take_update(Duration::from_secs(1)).unwrap();
+        assert_eq!(update.damage, vec![rect(0, 0, 110, 110)]);
+    }
+
+    #[test]
+    fn respects_min_interval() {
+        let mut scheduler = PartialUpdateScheduler::new(Duration::from_millis(100));
+        assert_eq!(scheduler.deadline(), None);
+        assert_eq!(scheduler.take_update(Duration::from_secs(1)), None);
+
+        // the first update is not delayed
+        scheduler.damage([rect(0, 0, 10, 10)]);
+        assert_eq!(scheduler.deadline(), Some(Duration::from_secs(0)));
+        assert!(scheduler.take_update(Duration::from_secs(1)).is_some());
+        assert!(!scheduler.has_damage());
+
+        scheduler.damage([rect(20, 0, 10, 10)]);
+        let deadline = Duration::from_millis(1100);
+        assert_eq!(scheduler.deadline(), Some(deadline));
+        assert_eq!(scheduler.take_update(Duration::from_millis(1050)), None);
+        assert!(scheduler.has_damage());
+
+        let update = scheduler.take_update(deadline).unwrap();
+        assert_eq!(update.damage, vec![rect(20, 0, 10, 10)]);
+        assert_eq!(scheduler.deadline(), None);
+    }
+
+    #[test]
+    fn full_refresh_interval() {
+        let mut scheduler = PartialUpdateScheduler::new(Duration::from_millis(100));
+        scheduler.set_full_refresh_interval(Some(2));
+
+        let full_refreshes = (1..=6)
+            .map(|i| {
+                scheduler.damage([rect(0, 0, 10, 10)]);
+                scheduler
+                    .take_update(Duration::from_secs(i))
+                    .unwrap()
+                    .full_refresh
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(full_refreshes, vec![false, false, true, false, false, true]);
+    }
+}
@@ -438,6 +438,44 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
         Ok(())
     }
 
+    pub fn move_plane(&self, plane: plane::Handle, position: (i32, i32)) -> Result<(), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        let mut planes = self.additional_planes.lock().unwrap();
+        let info = planes
+            .iter_mut()
+            .find(|info| info.handle == plane)
+            .ok_or(Error::PlaneNotInUse(self.crtc, plane))?;
+        // remember the position in any case, so the next page flip picks it up,
+        // if the commit below fails because of a pending flip
+        info.x = position.0;
+        info.y = position.1;
+
+        // only the position changes, so this never needs a modeset or a new framebuffer
+        let mut req = AtomicModeReq::new();
+        req.add_property(
+            plane,
+            self.plane_prop_handle(plane, "CRTC_X")?,
+            property::Value::SignedRange(position.0 as i64),
+        );
+        req.add_property(
+            plane,
+            self.plane_prop_handle(plane, "CRTC_Y")?,
+            property::Value::SignedRange(position.1 as i64),
+        );
+
+        trace!(self.logger, "Moving plane {:?} to {:?}", plane, position);
+        self.fd
+            .atomic_commit(AtomicCommitFlags::NONBLOCK, req)
+            .map_err(|source| Error::Access {
+                errmsg: "Failed to commit on move_plane",
+                dev: self.fd.dev_path(),
+                source,
+            })
+    }
+
     pub fn commit_pending(&self) -> bool {
         *self.pending.read().unwrap() != *self.state.read().unwrap()
     }
@@ -761,8 +799,34 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
         mode: Option<Mode>,
         blob: Option<property::Value<'static>>,
     ) -> Result<AtomicModeReq, Error> {
-        // okay, here we build the actual requests used by the surface.
         let mut req = AtomicModeReq::new();
+        self.extend_request(
+            &mut req,
+            new_connectors,
+            removed_connectors,
+            primary,
+            planes,
+            framebuffers,
+            mode,
+            blob,
+        )?;
+        Ok(req)
+    }
+
+    // Adds the properties of the given state to a request, that may contain other surfaces of the device
+    #[allow(clippy::too_many_arguments)]
+    fn extend_request<'a>(
+        &self,
+        req: &mut AtomicModeReq,
+        new_connectors: &mut dyn Iterator<Item = &connector::Handle>,
+        removed_connectors: &mut dyn Iterator<Item = &connector::Handle>,
+        primary: plane::Handle,
+        planes: &[PlaneInfo],
+        framebuffers: Option<impl Iterator<Item = &'a (framebuffer::Handle, plane::Handle)>>,
+        mode: Option<Mode>,
+        blob: Option<property::Value<'static>>,
+    ) -> Result<(), Error> {
+        // okay, here we build the actual requests used by the surface.
 
         // requests consist out of a set of properties and their new values
         // for different drm objects (crtc, plane, connector, ...).
@@ -916,7 +980,25 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
             }
         }
 
-        Ok(req)
+        Ok(())
+    }
+
+    // Adds the properties of a page flip to a request shared with other surfaces of the device
+    pub(super) fn add_page_flip<'a>(
+        &self,
+        req: &mut AtomicModeReq,
+        framebuffers: impl Iterator<Item = &'a (framebuffer::Handle, plane::Handle)>,
+    ) -> Result<(), Error> {
+        self.extend_request(
+            req,
+            &mut [].iter(),
+            &mut [].iter(),
+            self.plane,
+            &*self.additional_planes.lock().unwrap(),
+            Some(framebuffers),
+            None,
+            None,
+        )
     }
 
     // this helper function disconnects the plane.
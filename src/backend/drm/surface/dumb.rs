@@ -0,0 +1,365 @@
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+
+use drm::control::{connector, crtc, plane, Device, Mode};
+
+use crate::backend::allocator::{dumb::DumbBuffer, Allocator, Format, Fourcc, Modifier, Slot, Swapchain};
+use crate::backend::drm::{device::DevPath, surface::FbHandle, DrmError, DrmSurface};
+use crate::backend::{CompositorSurface, SwapBuffersError, TemporaryFailureReason};
+use crate::utils::{Physical, Rectangle};
+
+use slog::{debug, o, warn};
+
+/// Simplified abstraction of a swapchain for dumb buffers displayed on a [`DrmSurface`].
+///
+/// This is the counterpart of the [`GbmBufferedSurface`](super::gbm::GbmBufferedSurface) for
+/// software rendering, dumb buffers are always linear and can be written to by the cpu.
+#[derive(Debug)]
+pub struct DumbBufferedSurface<A: Allocator<DumbBuffer<D>> + 'static, D: AsRawFd + 'static> {
+    current_fb: Slot<DumbBuffer<D>>,
+    pending_fb: Option<Slot<DumbBuffer<D>>>,
+    queued_fb: Option<Slot<DumbBuffer<D>>>,
+    next_fb: Option<Slot<DumbBuffer<D>>>,
+    swapchain: Swapchain<A, DumbBuffer<D>>,
+    drm: Arc<DrmSurface<D>>,
+}
+
+impl<A, D> DumbBufferedSurface<A, D>
+where
+    A: Allocator<DumbBuffer<D>>,
+    A::Error: std::error::Error + Send + Sync,
+    D: AsRawFd + 'static,
+{
+    /// Create a new `DumbBufferedSurface` from a given surface and an allocator of dumb buffers,
+    /// usually the [`DrmDevice`](crate::backend::drm::DrmDevice) of the surface.
+    ///
+    /// Buffers use the `XRGB8888` format, which is supported by virtually every device.
+    pub fn new<L>(
+        drm: DrmSurface<D>,
+        allocator: A,
+        log: L,
+    ) -> Result<DumbBufferedSurface<A, D>, Error<A::Error>>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(log).new(o!("backend" => "drm_dumb"));
+
+        let code = Fourcc::Xrgb8888;
+        if !drm
+            .supported_formats(drm.plane())?
+            .iter()
+            .any(|format| format.code == code)
+        {
+            return Err(Error::NoSupportedPlaneFormat);
+        }
+
+        let drm = Arc::new(drm);
+        let mode = drm.pending_mode();
+        let mut swapchain: Swapchain<A, DumbBuffer<D>> = Swapchain::new(
+            allocator,
+            mode.size().0 as u32,
+            mode.size().1 as u32,
+            code,
+            vec![Modifier::Linear],
+        );
+
+        // Test format
+        let buffer = swapchain.acquire().map_err(Error::DumbError)?.unwrap();
+        let fb = attach_framebuffer(&drm, &*buffer)?;
+        let handle = fb.fb;
+        buffer.userdata().insert_if_missing(|| fb);
+
+        match drm.test_buffer(handle, &mode, true) {
+            Ok(_) => {
+                debug!(
+                    logger,
+                    "Choosen format: {:?}",
+                    Format {
+                        code,
+                        modifier: Modifier::Linear
+                    }
+                );
+                Ok(DumbBufferedSurface {
+                    current_fb: buffer,
+                    pending_fb: None,
+                    queued_fb: None,
+                    next_fb: None,
+                    swapchain,
+                    drm,
+                })
+            }
+            Err(err) => {
+                warn!(logger, "Mode-setting failed with dumb buffers: {}", err);
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Retrieves the next buffer to be rendered into and it's age.
+    ///
+    /// The pixels of the buffer can be written with [`DumbBuffer::map_mut`].
+    ///
+    /// *Note*: This function can be called multiple times and
+    /// will return the same buffer until it is queued (see [`DumbBufferedSurface::queue_buffer`]).
+    pub fn next_buffer(&mut self) -> Result<(&DumbBuffer<D>, u8), Error<A::Error>> {
+        if self.next_fb.is_none() {
+            let slot = self
+                .swapchain
+                .acquire()
+                .map_err(Error::DumbError)?
+                .ok_or(Error::NoFreeSlotsError)?;
+
+            if slot.userdata().get::<FbHandle<D>>().is_none() {
+                let fb_handle = attach_framebuffer(&self.drm, &*slot)?;
+                slot.userdata().insert_if_missing(|| fb_handle);
+            }
+
+            self.next_fb = Some(slot);
+        }
+
+        let slot = self.next_fb.as_ref().unwrap();
+        Ok((&**slot, slot.age()))
+    }
+
+    /// Queues the current buffer for rendering.
+    ///
+    /// *Note*: This function needs to be followed up with [`DumbBufferedSurface::frame_submitted`]
+    /// when a vblank event is received, that denotes successful scanout of the buffer.
+    /// Otherwise the underlying swapchain will eventually run out of buffers.
+    pub fn queue_buffer(&mut self) -> Result<(), Error<A::Error>> {
+        self.queued_fb = self.next_fb.take();
+        if self.pending_fb.is_none() && self.queued_fb.is_some() {
+            self.submit()?;
+        }
+        Ok(())
+    }
+
+    /// Marks the current frame as submitted.
+    ///
+    /// *Note*: Needs to be called, after the vblank event of the matching [`DrmDevice`](super::super::DrmDevice)
+    /// was received after calling [`DumbBufferedSurface::queue_buffer`] on this surface.
+    /// Otherwise the underlying swapchain will run out of buffers eventually.
+    pub fn frame_submitted(&mut self) -> Result<(), Error<A::Error>> {
+        if let Some(mut pending) = self.pending_fb.take() {
+            std::mem::swap(&mut pending, &mut self.current_fb);
+            if self.queued_fb.is_some() {
+                self.submit()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn submit(&mut self) -> Result<(), Error<A::Error>> {
+        // yes it does not look like it, but both of these lines should be safe in all cases.
+        let slot = self.queued_fb.take().unwrap();
+        let fb = slot.userdata().get::<FbHandle<D>>().unwrap().fb;
+
+        let flip = if self.drm.commit_pending() {
+            self.drm.commit([(fb, self.drm.plane())].iter(), true)
+        } else {
+            self.drm.page_flip([(fb, self.drm.plane())].iter(), true)
+        };
+        if flip.is_ok() {
+            self.swapchain.submitted(&slot);
+            self.pending_fb = Some(slot);
+        }
+        flip.map_err(Error::DrmError)
+    }
+
+    /// Reset the underlying buffers
+    pub fn reset_buffers(&mut self) {
+        self.swapchain.reset_buffers()
+    }
+
+    /// Returns the underlying [`crtc`](drm::control::crtc) of this surface
+    pub fn crtc(&self) -> crtc::Handle {
+        self.drm.crtc()
+    }
+
+    /// Returns the underlying [`plane`](drm::control::plane) of this surface
+    pub fn plane(&self) -> plane::Handle {
+        self.drm.plane()
+    }
+
+    /// Currently used [`connector`](drm::control::connector)s of this `Surface`
+    pub fn current_connectors(&self) -> impl IntoIterator<Item = connector::Handle> {
+        self.drm.current_connectors()
+    }
+
+    /// Returns the pending [`connector`](drm::control::connector)s
+    /// used for the next frame queued via [`queue_buffer`](DumbBufferedSurface::queue_buffer).
+    pub fn pending_connectors(&self) -> impl IntoIterator<Item = connector::Handle> {
+        self.drm.pending_connectors()
+    }
+
+    /// Tries to add a new [`connector`](drm::control::connector)
+    /// to be used after the next commit.
+    ///
+    /// See [`DrmSurface::add_connector`].
+    pub fn add_connector(&self, connector: connector::Handle) -> Result<(), Error<A::Error>> {
+        self.drm.add_connector(connector).map_err(Error::DrmError)
+    }
+
+    /// Tries to mark a [`connector`](drm::control::connector)
+    /// for removal on the next commit.
+    pub fn remove_connector(&self, connector: connector::Handle) -> Result<(), Error<A::Error>> {
+        self.drm.remove_connector(connector).map_err(Error::DrmError)
+    }
+
+    /// Tries to replace the current connector set with the newly provided one on the next commit.
+    ///
+    /// See [`DrmSurface::set_connectors`].
+    pub fn set_connectors(&self, connectors: &[connector::Handle]) -> Result<(), Error<A::Error>> {
+        self.drm.set_connectors(connectors).map_err(Error::DrmError)
+    }
+
+    /// Returns the currently active [`Mode`](drm::control::Mode)
+    /// of the underlying [`crtc`](drm::control::crtc)
+    pub fn current_mode(&self) -> Mode {
+        self.drm.current_mode()
+    }
+
+    /// Returns the currently pending [`Mode`](drm::control::Mode)
+    /// to be used after the next commit.
+    pub fn pending_mode(&self) -> Mode {
+        self.drm.pending_mode()
+    }
+
+    /// Tries to set a new [`Mode`](drm::control::Mode)
+    /// to be used after the next commit.
+    ///
+    /// Fails if the mode is not compatible with the underlying
+    /// [`crtc`](drm::control::crtc) or any of the
+    /// pending [`connector`](drm::control::connector)s.
+    pub fn use_mode(&mut self, mode: Mode) -> Result<(), Error<A::Error>> {
+        self.drm.use_mode(mode).map_err(Error::DrmError)?;
+        let (w, h) = mode.size();
+        self.swapchain.resize(w as _, h as _);
+        Ok(())
+    }
+}
+
+impl<A, D> CompositorSurface for DumbBufferedSurface<A, D>
+where
+    A: Allocator<DumbBuffer<D>>,
+    A::Error: std::error::Error + Send + Sync,
+    D: AsRawFd + 'static,
+{
+    type Buffer = DumbBuffer<D>;
+    type Error = Error<A::Error>;
+
+    fn next_buffer(&mut self) -> Result<(&DumbBuffer<D>, u8), Self::Error> {
+        DumbBufferedSurface::next_buffer(self)
+    }
+
+    /// Queues the buffer, see [`DumbBufferedSurface::queue_buffer`].
+    ///
+    /// Page flips always replace the whole buffer, so `damage` is ignored.
+    fn present(&mut self, _damage: Option<&[Rectangle<i32, Physical>]>) -> Result<(), Self::Error> {
+        self.queue_buffer()
+    }
+
+    fn reset_buffers(&mut self) {
+        DumbBufferedSurface::reset_buffers(self)
+    }
+}
+
+fn attach_framebuffer<E, D>(drm: &Arc<DrmSurface<D>>, buffer: &DumbBuffer<D>) -> Result<FbHandle<D>, Error<E>>
+where
+    E: std::error::Error + Send + Sync,
+    D: AsRawFd + 'static,
+{
+    let fb = drm
+        .add_framebuffer(buffer.handle(), 24, 32)
+        .map_err(|source| DrmError::Access {
+            errmsg: "Failed to add framebuffer",
+            dev: drm.dev_path(),
+            source,
+        })?;
+    Ok(FbHandle { drm: drm.clone(), fb })
+}
+
+/// Errors thrown by a [`DumbBufferedSurface`]
+#[derive(Debug, thiserror::Error)]
+pub enum Error<E: std::error::Error + Send + Sync + 'static> {
+    /// The plane does not support the pixel format of the dumb buffers
+    #[error("No supported plane buffer format found")]
+    NoSupportedPlaneFormat,
+    /// The swapchain is exhausted, you need to call `frame_submitted`
+    #[error("Failed to allocate a new buffer")]
+    NoFreeSlotsError,
+    /// Error accessing the drm device
+    #[error("The underlying drm surface encounted an error: {0}")]
+    DrmError(#[from] DrmError),
+    /// Error allocating a dumb buffer
+    #[error("Failed to allocate a dumb buffer: {0}")]
+    DumbError(#[source] E),
+}
+
+impl<E: std::error::Error + Send + Sync + 'static> From<Error<E>> for SwapBuffersError {
+    fn from(err: Error<E>) -> SwapBuffersError {
+        match err {
+            x @ Error::NoSupportedPlaneFormat => SwapBuffersError::context_lost(x),
+            x @ Error::NoFreeSlotsError => {
+                SwapBuffersError::temporary_failure(TemporaryFailureReason::Busy, x)
+            }
+            Error::DrmError(err) => err.into(),
+            Error::DumbError(err) => SwapBuffersError::context_lost(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::drm::{connected_connectors, connector_crtcs, DrmDevice};
+    use std::{fs::OpenOptions, os::unix::io::RawFd};
+
+    #[derive(Debug, Clone)]
+    struct Card(Arc<std::fs::File>);
+
+    impl AsRawFd for Card {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a drm device with a connected output, that is not driven by a compositor"]
+    fn writes_through_next_buffer() {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/dri/card0")
+            .expect("No drm device");
+        let card = Card(Arc::new(file));
+        let device = DrmDevice::new(card.clone(), false, None).unwrap();
+        let connector = connected_connectors(&device)
+            .unwrap()
+            .into_iter()
+            .next()
+            .expect("No connected output");
+        let crtc = connector_crtcs(&device, &connector).unwrap()[0];
+        let surface = device
+            .create_surface(crtc, connector.modes()[0], &[connector.handle()])
+            .unwrap();
+        let allocator = DrmDevice::new(card, false, None).unwrap();
+        let mut surface = DumbBufferedSurface::new(surface, allocator, None).unwrap();
+
+        let (buffer, _) = surface.next_buffer().unwrap();
+        let mut pixels = buffer.map_mut().unwrap();
+        pixels[..4].copy_from_slice(&[0x11, 0x22, 0x33, 0xff]);
+        // no aliasing mapping while writing
+        assert!(buffer.map().is_err());
+        assert!(buffer.map_mut().is_err());
+        drop(pixels);
+
+        let (buffer, _) = surface.next_buffer().unwrap();
+        let first = buffer.map().unwrap();
+        let second = buffer.map().unwrap();
+        assert_eq!(&first[..4], &[0x11, 0x22, 0x33, 0xff]);
+        assert_eq!(&first[..4], &second[..4]);
+        assert!(buffer.map_mut().is_err());
+    }
+}
@@ -1,18 +1,24 @@
 use std::collections::HashSet;
 use std::os::unix::io::AsRawFd;
 use std::sync::Arc;
+use std::time::Duration;
 
 use drm::buffer::PlanarBuffer;
-use drm::control::{connector, crtc, framebuffer, plane, Device, Mode};
+use drm::control::{connector, crtc, plane, Device, Mode};
 use gbm::BufferObject;
 
 use crate::backend::allocator::{
     dmabuf::{AsDmabuf, Dmabuf},
     gbm::GbmConvertError,
-    Allocator, Format, Fourcc, Modifier, Slot, Swapchain,
+    Allocator, Degradation, Format, Fourcc, Modifier, Slot, SwapStrategy, Swapchain, SwapchainStats,
 };
-use crate::backend::drm::{device::DevPath, surface::DrmSurfaceInternal, DrmError, DrmSurface};
-use crate::backend::SwapBuffersError;
+use crate::backend::drm::{
+    device::DevPath,
+    surface::{DrmSurfaceInternal, FbHandle},
+    DrmError, DrmSurface,
+};
+use crate::backend::{CompositorSurface, SwapBuffersError, TemporaryFailureReason};
+use crate::utils::{monotonic_time, Physical, Rectangle};
 
 use slog::{debug, error, o, trace, warn};
 
@@ -25,6 +31,112 @@ pub struct GbmBufferedSurface<A: Allocator<BufferObject<()>> + 'static, D: AsRaw
     next_fb: Option<Slot<BufferObject<()>>>,
     swapchain: Swapchain<A, BufferObject<()>>,
     drm: Arc<DrmSurface<D>>,
+    front_buffer_rendered: bool,
+    queue_policy: QueuePolicy,
+    clock: FrameClock,
+    logger: ::slog::Logger,
+}
+
+/// What happens to frames queued, while a page flip is still pending
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Keep the newest frame to be flipped after the pending one, replacing frames queued earlier
+    Coalesce,
+    /// Like [`QueuePolicy::Coalesce`], but drop queued frames entirely, while page flips miss vblanks
+    ///
+    /// Once flips are late, a queued frame is at least one refresh cycle old by the time it is
+    /// displayed. Dropping it lets the compositor render a fresh frame after the pending flip
+    /// completed instead, so latency does not build up while the output catches up.
+    DropWhenLate,
+}
+
+impl Default for QueuePolicy {
+    fn default() -> Self {
+        QueuePolicy::Coalesce
+    }
+}
+
+/// Statistics about the page flips of a [`GbmBufferedSurface`]
+///
+/// All times are in the clock of [`monotonic_time`](crate::utils::monotonic_time).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Number of vblanks missed by page flips, that completed later than the vblank following their submission
+    pub missed_flips: u64,
+    /// Number of vblanks missed by the most recent page flips in a row, `0` if the last flip was on time
+    pub consecutive_missed_flips: u64,
+    /// Number of queued frames, that were replaced by a newer frame or dropped before being flipped
+    pub dropped_frames: u64,
+    /// Time the last page flip completed
+    pub last_flip: Option<Duration>,
+    /// Refresh interval of the current mode
+    pub refresh_interval: Option<Duration>,
+}
+
+impl FrameStats {
+    /// Estimates the time of the first vblank after `now`, at which a page flip submitted at `now` completes
+    pub fn next_slot(&self, now: Duration) -> Option<Duration> {
+        let last_flip = self.last_flip?;
+        let refresh = self.refresh_interval?.as_nanos();
+        if refresh == 0 {
+            return None;
+        }
+        let elapsed = now.saturating_sub(last_flip).as_nanos();
+        let slots = elapsed / refresh + 1;
+        Some(last_flip + Duration::from_nanos((slots * refresh) as u64))
+    }
+}
+
+/// Tracks the timing of page flips to detect missed vblanks
+#[derive(Debug, Default)]
+struct FrameClock {
+    stats: FrameStats,
+    // vblank the pending flip was submitted for
+    expected_flip: Option<Duration>,
+}
+
+impl FrameClock {
+    fn submitted(&mut self, now: Duration, refresh_interval: Option<Duration>) {
+        self.stats.refresh_interval = refresh_interval;
+        self.expected_flip = self.stats.next_slot(now);
+    }
+
+    /// Returns the number of vblanks missed by the completed flip
+    fn flipped(&mut self, now: Duration) -> u64 {
+        let missed = match (self.expected_flip.take(), self.stats.refresh_interval) {
+            (Some(expected), Some(refresh)) if refresh.as_nanos() > 0 => {
+                // round to the nearest vblank, as the flip is only noticed after the event was dispatched
+                let late = now.saturating_sub(expected) + refresh / 2;
+                (late.as_nanos() / refresh.as_nanos()) as u64
+            }
+            _ => 0,
+        };
+        self.stats.missed_flips += missed;
+        self.stats.consecutive_missed_flips = if missed > 0 {
+            self.stats.consecutive_missed_flips + missed
+        } else {
+            0
+        };
+        self.stats.last_flip = Some(now);
+        missed
+    }
+
+    fn late(&self) -> bool {
+        self.stats.consecutive_missed_flips > 0
+    }
+}
+
+/// Returns the time between two vblanks of a mode
+fn refresh_interval(mode: &Mode) -> Option<Duration> {
+    let (_, _, htotal) = mode.hsync();
+    let (_, _, vtotal) = mode.vsync();
+    let pixels = htotal as u64 * vtotal as u64;
+    // the pixel clock is in kHz
+    let clock = mode.clock() as u64;
+    if pixels == 0 || clock == 0 {
+        return None;
+    }
+    Some(Duration::from_nanos(pixels * 1_000_000 / clock))
 }
 
 impl<A, D> GbmBufferedSurface<A, D>
@@ -157,6 +269,10 @@ where
                     next_fb: None,
                     swapchain,
                     drm,
+                    front_buffer_rendered: false,
+                    queue_policy: QueuePolicy::default(),
+                    clock: FrameClock::default(),
+                    logger,
                 })
             }
             Err(err) => {
@@ -174,6 +290,11 @@ where
     /// *Note*: This function can be called multiple times and
     /// will return the same buffer until it is queued (see [`GbmBufferedSurface::queue_buffer`]).
     pub fn next_buffer(&mut self) -> Result<(Dmabuf, u8), Error<A::Error>> {
+        if self.front_buffer_rendered {
+            // the front buffer changed without a page flip, so the ages of all buffers are off
+            self.swapchain.reset_buffers();
+            self.front_buffer_rendered = false;
+        }
         if self.next_fb.is_none() {
             let slot = self
                 .swapchain
@@ -203,9 +324,27 @@ where
     /// *Note*: This function needs to be followed up with [`GbmBufferedSurface::frame_submitted`]
     /// when a vblank event is received, that denotes successful scanout of the buffer.
     /// Otherwise the underlying swapchain will eventually run out of buffers.
+    ///
+    /// If a page flip is still pending, the buffer is flipped after it completed, replacing any
+    /// buffer queued before. See [`QueuePolicy`] for how late page flips are handled.
     pub fn queue_buffer(&mut self) -> Result<(), Error<A::Error>> {
-        self.queued_fb = self.next_fb.take();
-        if self.pending_fb.is_none() && self.queued_fb.is_some() {
+        let next = self.next_fb.take();
+        if next.is_none() {
+            return Ok(());
+        }
+        if self.pending_fb.is_some() {
+            if self.queue_policy == QueuePolicy::DropWhenLate && self.clock.late() {
+                trace!(self.logger, "Dropping frame queued behind a late page flip");
+                self.clock.stats.dropped_frames += 1 + self.queued_fb.take().map_or(0, |_| 1);
+                return Ok(());
+            }
+            if self.queued_fb.is_some() {
+                trace!(self.logger, "Replacing queued frame with a newer one");
+                self.clock.stats.dropped_frames += 1;
+            }
+        }
+        self.queued_fb = next;
+        if self.pending_fb.is_none() {
             self.submit()?;
         }
         Ok(())
@@ -219,6 +358,10 @@ where
     pub fn frame_submitted(&mut self) -> Result<(), Error<A::Error>> {
         if let Some(mut pending) = self.pending_fb.take() {
             std::mem::swap(&mut pending, &mut self.current_fb);
+            let missed = self.clock.flipped(monotonic_time());
+            if missed > 0 {
+                debug!(self.logger, "Page flip missed {} vblank(s)", missed);
+            }
             if self.queued_fb.is_some() {
                 self.submit()?;
             }
@@ -238,12 +381,192 @@ where
             self.drm.page_flip([(fb, self.drm.plane())].iter(), true)
         };
         if flip.is_ok() {
+            self.clock
+                .submitted(monotonic_time(), refresh_interval(&self.drm.pending_mode()));
             self.swapchain.submitted(&slot);
             self.pending_fb = Some(slot);
         }
         flip.map_err(Error::DrmError)
     }
 
+    /// Returns statistics about the page flips of this surface
+    ///
+    /// A growing number of [`FrameStats::consecutive_missed_flips`] means frames are submitted
+    /// too late for their vblank, e.g. because rendering takes longer than a refresh cycle.
+    /// [`FrameStats::next_slot`] estimates the vblank the next frame can be displayed at.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.clock.stats
+    }
+
+    /// Sets the policy for frames queued, while a page flip is still pending
+    pub fn set_queue_policy(&mut self, policy: QueuePolicy) {
+        self.queue_policy = policy;
+    }
+
+    /// Sets the number of buffers of this surface
+    ///
+    /// With [`SwapStrategy::Double`] the next frame can only be rendered after the pending page flip
+    /// completed, [`GbmBufferedSurface::next_buffer`] fails with [`Error::NoFreeSlotsError`] before.
+    /// [`SwapStrategy::Triple`] allows rendering the next frame while a page flip is pending, to be
+    /// flipped after it. [`SwapStrategy::Mailbox`] additionally allows replacing that frame with a newer one,
+    /// as decided by the [`QueuePolicy`]. This is the default.
+    pub fn set_swap_strategy(&mut self, strategy: SwapStrategy) {
+        self.swapchain.set_strategy(strategy);
+    }
+
+    /// Returns statistics about the buffers of this surface
+    ///
+    /// [`SwapchainStats::starved`] counts the calls to [`GbmBufferedSurface::next_buffer`], that found
+    /// no free buffer, because all of them were displayed or waiting to be displayed.
+    pub fn swapchain_stats(&self) -> SwapchainStats {
+        self.swapchain.stats()
+    }
+
+    /// Returns the buffer currently scanned out, to be rendered into directly.
+    ///
+    /// Rendering into the front buffer skips page flips entirely, so changes become visible as soon
+    /// as the display controller reads the buffer again, at the cost of tearing. This is only useful
+    /// for special cases like e-ink panels or drawing tablets, where latency matters more than tearing.
+    /// Report the changed regions through [`GbmBufferedSurface::front_buffer_damaged`] afterwards,
+    /// as some devices only update the panel on request.
+    ///
+    /// A frame has to be displayed through [`GbmBufferedSurface::queue_buffer`] first and no page flip
+    /// may be pending, otherwise this fails with [`Error::FrontBufferUnavailable`].
+    /// Buffers returned by [`GbmBufferedSurface::next_buffer`] afterwards have an age of `0`.
+    pub fn front_buffer(&mut self) -> Result<Dmabuf, Error<A::Error>> {
+        if self.pending_fb.is_some() || self.queued_fb.is_some() || self.drm.commit_pending() {
+            return Err(Error::FrontBufferUnavailable);
+        }
+        self.front_buffer_rendered = true;
+        Ok(self.current_fb.userdata().get::<Dmabuf>().unwrap().clone())
+    }
+
+    /// Notifies the device about regions of the front buffer, that were rendered into.
+    ///
+    /// See [`GbmBufferedSurface::front_buffer`].
+    pub fn front_buffer_damaged(&self, damage: &[Rectangle<i32, Physical>]) -> Result<(), Error<A::Error>> {
+        let fb = self.current_fb.userdata().get::<FbHandle<D>>().unwrap().fb;
+        let clips = damage
+            .iter()
+            .map(|rect| {
+                let clamp = |value: i32| value.max(0).min(u16::MAX as i32) as u16;
+                drm_ffi::drm_clip_rect {
+                    x1: clamp(rect.loc.x),
+                    y1: clamp(rect.loc.y),
+                    x2: clamp(rect.loc.x.saturating_add(rect.size.w)),
+                    y2: clamp(rect.loc.y.saturating_add(rect.size.h)),
+                }
+            })
+            .collect::<Vec<_>>();
+        match self.drm.dirty_framebuffer(fb, &clips) {
+            // most drivers scan out continuously and do not need to be notified
+            Err(drm::SystemError::Unknown {
+                errno: nix::errno::Errno::ENOSYS,
+            }) => Ok(()),
+            res => res.map_err(|source| {
+                Error::DrmError(DrmError::Access {
+                    errmsg: "Failed to mark the front buffer as dirty",
+                    dev: self.drm.dev_path(),
+                    source,
+                })
+            }),
+        }
+    }
+
+    /// Returns the format of the buffers returned by [`GbmBufferedSurface::next_buffer`]
+    pub fn format(&self) -> Fourcc {
+        self.swapchain.format()
+    }
+
+    /// Changes the format of the buffers returned by [`GbmBufferedSurface::next_buffer`] afterwards.
+    ///
+    /// This renegotiates the format while the surface is displayed, e.g. when the formats and modifiers
+    /// preferred for scan-out changed, because a fullscreen client may be scanned out directly.
+    /// `modifiers` have to be supported by the renderer for `code` and are restricted to the ones
+    /// supported by the plane. A buffer of the new format is tested first, so the surface keeps its
+    /// current format on errors. The legacy drm api cannot test buffers without a modeset,
+    /// so only adding a framebuffer for it is tested on legacy devices.
+    ///
+    /// Buffers already rendered, queued or displayed are unaffected and released once they were
+    /// replaced on screen, so scan-out is not interrupted. Buffers of the new format have an age of `0`.
+    pub fn set_format(&mut self, code: Fourcc, modifiers: &[Modifier]) -> Result<(), Error<A::Error>> {
+        let plane_formats = self.drm.supported_formats(self.drm.plane())?;
+        let modifiers = modifiers
+            .iter()
+            .copied()
+            .filter(|modifier| {
+                plane_formats.contains(&Format {
+                    code,
+                    modifier: *modifier,
+                })
+            })
+            .collect::<Vec<_>>();
+        if modifiers.is_empty() {
+            return Err(Error::FormatsNotCompatible);
+        }
+        if code == self.swapchain.format() && modifiers == self.swapchain.modifiers() {
+            return Ok(());
+        }
+
+        let mode = self.drm.pending_mode();
+        let buffer = self
+            .swapchain
+            .allocator
+            .create_buffer(mode.size().0 as u32, mode.size().1 as u32, code, &modifiers)
+            .map_err(Error::GbmError)?;
+        let fb = attach_framebuffer(&self.drm, &buffer)?;
+        let legacy = matches!(&*self.drm.internal, DrmSurfaceInternal::Legacy(_));
+        if !self.drm.test_buffer(fb.fb, &mode, false)? && !legacy {
+            warn!(
+                self.logger,
+                "Buffer format {:?} with modifiers {:?} is not supported for scan-out", code, modifiers
+            );
+            return Err(Error::FormatsNotCompatible);
+        }
+
+        debug!(
+            self.logger,
+            "Switching to format {:?} with modifiers {:?}", code, modifiers
+        );
+        self.swapchain.set_format(code, modifiers);
+        Ok(())
+    }
+
+    /// Enables recovering from failed allocations, e.g. when the GPU runs out of memory
+    ///
+    /// Instead of failing [`GbmBufferedSurface::next_buffer`], the surface drops to double buffering and
+    /// falls back to linear buffers and formats of a lower depth, see [`Degradation`].
+    /// Fallback formats have to be supported by the renderer, as given by `renderer_formats`,
+    /// and by the plane. Passing `None` disables it again.
+    ///
+    /// Poll [`GbmBufferedSurface::take_degradations`] after rendering to find out, if the surface was degraded.
+    pub fn set_degradation(
+        &mut self,
+        renderer_formats: Option<HashSet<Format>>,
+    ) -> Result<(), Error<A::Error>> {
+        let formats = match renderer_formats {
+            Some(renderer_formats) => {
+                let plane_formats = self.drm.supported_formats(self.drm.plane())?;
+                Some(
+                    plane_formats
+                        .iter()
+                        .filter(|format| renderer_formats.contains(format))
+                        .copied()
+                        .collect(),
+                )
+            }
+            None => None,
+        };
+        self.swapchain.set_degradation(formats);
+        Ok(())
+    }
+
+    /// Returns the degradations taken to recover from failed allocations since the last call,
+    /// see [`GbmBufferedSurface::set_degradation`]
+    pub fn take_degradations(&mut self) -> Vec<Degradation> {
+        self.swapchain.take_degradations()
+    }
+
     /// Reset the underlying buffers
     pub fn reset_buffers(&mut self) {
         self.swapchain.reset_buffers()
@@ -328,15 +651,30 @@ where
     }
 }
 
-#[derive(Debug)]
-struct FbHandle<D: AsRawFd + 'static> {
-    drm: Arc<DrmSurface<D>>,
-    fb: framebuffer::Handle,
-}
+impl<A, D> CompositorSurface for GbmBufferedSurface<A, D>
+where
+    A: Allocator<BufferObject<()>>,
+    A::Error: std::error::Error + Send + Sync,
+    D: AsRawFd + 'static,
+{
+    type Buffer = Dmabuf;
+    type Error = Error<A::Error>;
+
+    fn next_buffer(&mut self) -> Result<(&Dmabuf, u8), Self::Error> {
+        let (_, age) = GbmBufferedSurface::next_buffer(self)?;
+        let slot = self.next_fb.as_ref().unwrap();
+        Ok((slot.userdata().get::<Dmabuf>().unwrap(), age))
+    }
 
-impl<A: AsRawFd + 'static> Drop for FbHandle<A> {
-    fn drop(&mut self) {
-        let _ = self.drm.destroy_framebuffer(self.fb);
+    /// Queues the buffer, see [`GbmBufferedSurface::queue_buffer`].
+    ///
+    /// Page flips always replace the whole buffer, so `damage` is ignored.
+    fn present(&mut self, _damage: Option<&[Rectangle<i32, Physical>]>) -> Result<(), Self::Error> {
+        self.queue_buffer()
+    }
+
+    fn reset_buffers(&mut self) {
+        GbmBufferedSurface::reset_buffers(self)
     }
 }
 
@@ -417,6 +755,9 @@ pub enum Error<E: std::error::Error + Send + Sync + 'static> {
     /// Error exporting as Dmabuf
     #[error("The allocated buffer could not be exported as a dmabuf: {0}")]
     AsDmabufError(#[from] GbmConvertError),
+    /// The front buffer is not displayed yet or a page flip is pending
+    #[error("The front buffer is not available for rendering")]
+    FrontBufferUnavailable,
 }
 
 impl<E: std::error::Error + Send + Sync + 'static> From<Error<E>> for SwapBuffersError {
@@ -425,11 +766,59 @@ impl<E: std::error::Error + Send + Sync + 'static> From<Error<E>> for SwapBuffer
             x @ Error::NoSupportedPlaneFormat
             | x @ Error::NoSupportedRendererFormat
             | x @ Error::FormatsNotCompatible
-            | x @ Error::InitialRenderingError => SwapBuffersError::ContextLost(Box::new(x)),
-            x @ Error::NoFreeSlotsError => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            | x @ Error::InitialRenderingError => SwapBuffersError::context_lost(x),
+            x @ Error::NoFreeSlotsError | x @ Error::FrontBufferUnavailable => {
+                SwapBuffersError::temporary_failure(TemporaryFailureReason::Busy, x)
+            }
             Error::DrmError(err) => err.into(),
-            Error::GbmError(err) => SwapBuffersError::ContextLost(Box::new(err)),
-            Error::AsDmabufError(err) => SwapBuffersError::ContextLost(Box::new(err)),
+            Error::GbmError(err) => SwapBuffersError::context_lost(err),
+            Error::AsDmabufError(err) => SwapBuffersError::context_lost(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: Duration = Duration::from_millis(16);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn next_slot_estimate() {
+        let stats = FrameStats {
+            last_flip: Some(ms(100)),
+            refresh_interval: Some(REFRESH),
+            ..Default::default()
+        };
+        assert_eq!(stats.next_slot(ms(101)), Some(ms(116)));
+        assert_eq!(stats.next_slot(ms(116)), Some(ms(132)));
+        assert_eq!(stats.next_slot(ms(150)), Some(ms(164)));
+        assert_eq!(FrameStats::default().next_slot(ms(150)), None);
+    }
+
+    #[test]
+    fn missed_flips() {
+        let mut clock = FrameClock::default();
+        // the first flip has nothing to compare against
+        clock.submitted(ms(0), Some(REFRESH));
+        assert_eq!(clock.flipped(ms(16)), 0);
+
+        // submitted for the vblank at 32, displayed at 48
+        clock.submitted(ms(20), Some(REFRESH));
+        assert_eq!(clock.flipped(ms(49)), 1);
+        clock.submitted(ms(50), Some(REFRESH));
+        assert_eq!(clock.flipped(ms(96)), 2);
+        assert!(clock.late());
+        assert_eq!(clock.stats.missed_flips, 3);
+        assert_eq!(clock.stats.consecutive_missed_flips, 3);
+
+        clock.submitted(ms(100), Some(REFRESH));
+        assert_eq!(clock.flipped(ms(113)), 0);
+        assert!(!clock.late());
+        assert_eq!(clock.stats.missed_flips, 3);
+    }
+}
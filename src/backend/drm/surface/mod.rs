@@ -3,7 +3,7 @@ use std::cell::RefCell;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::os::unix::io::{AsRawFd, RawFd};
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
 
 use drm::control::{connector, crtc, framebuffer, plane, property, Device as ControlDevice, Mode};
 use drm::{Device as BasicDevice, DriverCapability};
@@ -11,10 +11,11 @@ use drm::{Device as BasicDevice, DriverCapability};
 use nix::libc::dev_t;
 
 pub(super) mod atomic;
+pub(super) mod dumb;
 #[cfg(feature = "backend_gbm")]
 pub(super) mod gbm;
 pub(super) mod legacy;
-use super::{device::DevPath, error::Error, plane_type, planes, PlaneType, Planes};
+use super::{device::DevPath, error::Error, fade::GammaRamp, plane_type, planes, PlaneType, Planes};
 use crate::backend::allocator::{Format, Fourcc, Modifier};
 use atomic::AtomicDrmSurface;
 use legacy::LegacyDrmSurface;
@@ -176,6 +177,26 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         }
     }
 
+    /// Moves a plane set up with [`use_plane`](DrmSurface::use_plane) to a new position,
+    /// without waiting for the next commit/page_flip.
+    ///
+    /// This is meant for cursor planes, to keep the cursor moving at the rate of input events
+    /// while rendering a frame takes longer. Only the position of the plane is committed,
+    /// its framebuffer and size are unchanged.
+    ///
+    /// Drivers may reject the update with `EBUSY`, while a page flip is still pending.
+    /// The new position is used by the next commit/page_flip in that case.
+    ///
+    /// Errors if the plane is not in use on this surface or if the underlying implementation
+    /// does not support the use of planes. Legacy devices can use
+    /// [`move_cursor`](drm::control::Device::move_cursor) for their hardware cursor instead.
+    pub fn move_plane(&self, plane: plane::Handle, position: (i32, i32)) -> Result<(), Error> {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.move_plane(plane, position),
+            DrmSurfaceInternal::Legacy(_) => Err(Error::NonPrimaryPlane(plane)),
+        }
+    }
+
     /// Disables the given plane.
     ///
     /// Errors if the plane is not supported by this crtc or if the underlying
@@ -441,6 +462,50 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         }
     }
 
+    /// Returns the current gamma ramp of the crtc
+    ///
+    /// The ramp is empty, if the crtc does not support gamma correction.
+    pub fn gamma(&self) -> Result<GammaRamp, Error> {
+        let access = |source| Error::Access {
+            errmsg: "Error reading the gamma ramp",
+            dev: self.dev_path(),
+            source,
+        };
+        let size = self.get_crtc(self.crtc).map_err(access)?.gamma_length() as usize;
+        let mut ramp = GammaRamp {
+            red: vec![0; size],
+            green: vec![0; size],
+            blue: vec![0; size],
+        };
+        if size > 0 {
+            self.get_gamma(self.crtc, &mut ramp.red, &mut ramp.green, &mut ramp.blue)
+                .map_err(access)?;
+        }
+        Ok(ramp)
+    }
+
+    /// Sets the gamma ramp of the crtc, which takes effect with the next vblank
+    ///
+    /// The ramp has to have as many entries as the one returned by [`gamma`](DrmSurface::gamma).
+    /// See [`GammaFade`](super::GammaFade) to animate it.
+    pub fn set_gamma(&self, ramp: &GammaRamp) -> Result<(), Error> {
+        let active = match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => &surf.active,
+            DrmSurfaceInternal::Legacy(surf) => &surf.active,
+        };
+        if !active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+        // the legacy ioctl is translated to the GAMMA_LUT property by atomic drivers
+        ControlDevice::set_gamma(self, self.crtc, &ramp.red, &ramp.green, &ramp.blue).map_err(|source| {
+            Error::Access {
+                errmsg: "Error setting the gamma ramp",
+                dev: self.dev_path(),
+                source,
+            }
+        })
+    }
+
     /// Re-evaluates the current state of the crtc.
     ///
     /// Usually you do not need to call this, but if the state of
@@ -453,3 +518,81 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         }
     }
 }
+
+/// Page-flips multiple surfaces at once
+///
+/// If all surfaces belong to the same atomic device and have no pending commit, all framebuffers
+/// are flipped by a single atomic commit, so the new contents appear together, e.g. on the tiles of a
+/// video wall or on mirrored outputs, and `true` is returned. The flips still complete on the
+/// vblanks of the individual crtcs, so track their phase with a
+/// [`VblankDrift`](crate::backend::drm::VblankDrift), if the outputs are not genlocked by the hardware.
+///
+/// Otherwise every surface is flipped, or committed if it has pending changes, on its own and
+/// `false` is returned. If one of these fails, the surfaces before it were flipped already.
+#[allow(clippy::type_complexity)]
+pub fn page_flip_synchronized<A: AsRawFd + 'static>(
+    flips: &[(&DrmSurface<A>, &[(framebuffer::Handle, plane::Handle)])],
+    event: bool,
+) -> Result<bool, Error> {
+    let atomic = flips
+        .iter()
+        .map(|(surface, _)| match &*surface.internal {
+            DrmSurfaceInternal::Atomic(surf) if !surf.commit_pending() => Some(surf),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .filter(|surfaces| {
+            !surfaces.is_empty()
+                && surfaces
+                    .windows(2)
+                    .all(|pair| Arc::ptr_eq(&pair[0].fd, &pair[1].fd))
+        });
+
+    let surfaces = match atomic {
+        Some(surfaces) => surfaces,
+        None => {
+            for (surface, framebuffers) in flips {
+                if surface.commit_pending() {
+                    surface.commit(framebuffers.iter(), event)?;
+                } else {
+                    surface.page_flip(framebuffers.iter(), event)?;
+                }
+            }
+            return Ok(false);
+        }
+    };
+
+    if surfaces.iter().any(|surf| !surf.active.load(Ordering::SeqCst)) {
+        return Err(Error::DeviceInactive);
+    }
+    let mut req = drm::control::atomic::AtomicModeReq::new();
+    for (surf, (_, framebuffers)) in surfaces.iter().zip(flips) {
+        surf.add_page_flip(&mut req, framebuffers.iter())?;
+    }
+    let flags = if event {
+        drm::control::AtomicCommitFlags::PAGE_FLIP_EVENT | drm::control::AtomicCommitFlags::NONBLOCK
+    } else {
+        drm::control::AtomicCommitFlags::NONBLOCK
+    };
+    let fd = &surfaces[0].fd;
+    trace!(surfaces[0].logger, "Queueing synchronized page flip: {:?}", req);
+    fd.atomic_commit(flags, req).map_err(|source| Error::Access {
+        errmsg: "Synchronized page flip commit failed",
+        dev: fd.dev_path(),
+        source,
+    })?;
+    Ok(true)
+}
+
+/// Framebuffer of a buffer attached to a [`DrmSurface`], destroyed on drop
+#[derive(Debug)]
+pub(super) struct FbHandle<D: AsRawFd + 'static> {
+    pub(super) drm: Arc<DrmSurface<D>>,
+    pub(super) fb: framebuffer::Handle,
+}
+
+impl<A: AsRawFd + 'static> Drop for FbHandle<A> {
+    fn drop(&mut self) {
+        let _ = self.drm.destroy_framebuffer(self.fb);
+    }
+}
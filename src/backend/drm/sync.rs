@@ -0,0 +1,167 @@
+//! Phase tracking of the vblanks of multiple crtcs
+
+use std::{collections::HashMap, time::Duration};
+
+use drm::control::crtc;
+
+use super::DrmEventTime;
+
+/// Returns the time between two timestamps, if they were taken from the same clock
+fn distance(a: DrmEventTime, b: DrmEventTime) -> Option<Duration> {
+    match (a, b) {
+        (DrmEventTime::Monotonic(a), DrmEventTime::Monotonic(b)) => Some(if a > b { a - b } else { b - a }),
+        (DrmEventTime::Realtime(a), DrmEventTime::Realtime(b)) => {
+            Some(a.duration_since(b).unwrap_or_else(|err| err.duration()))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the distance between two vblanks of outputs with the same refresh interval,
+/// at most half the interval
+fn phase_offset(a: DrmEventTime, b: DrmEventTime, refresh: Duration) -> Option<Duration> {
+    let distance = distance(a, b)?;
+    let refresh = refresh.as_nanos();
+    if refresh == 0 {
+        return Some(Duration::ZERO);
+    }
+    let phase = distance.as_nanos() % refresh;
+    Some(Duration::from_nanos(phase.min(refresh - phase) as u64))
+}
+
+/// Tracks how far apart the vblanks of multiple crtcs are
+///
+/// Outputs driven by different crtcs are usually not genlocked, so even with the same mode their
+/// vblanks drift apart over time and content flipped at once, e.g. through
+/// [`page_flip_synchronized`](crate::backend::drm::page_flip_synchronized), tears across the bezels
+/// of a video wall for up to a frame. Feed the timestamps of the vblank events of all involved crtcs,
+/// as provided by [`DrmEventMetadata::time`](crate::backend::drm::DrmEventMetadata::time), into this
+/// tracker to monitor the offset, e.g. to warn about it or to adjust the modes of the outputs.
+///
+/// All crtcs are expected to use the same refresh interval. Vblanks timestamped with different
+/// clocks, e.g. from devices using monotonic and realtime timestamps, are not compared.
+#[derive(Debug, Clone)]
+pub struct VblankDrift {
+    refresh: Duration,
+    last: HashMap<crtc::Handle, DrmEventTime>,
+    max_offset: Duration,
+}
+
+impl VblankDrift {
+    /// Creates a new tracker for crtcs with the given refresh interval
+    pub fn new(refresh: Duration) -> VblankDrift {
+        VblankDrift {
+            refresh,
+            last: HashMap::new(),
+            max_offset: Duration::ZERO,
+        }
+    }
+
+    /// Sets the refresh interval, e.g. after the mode of the outputs changed, and resets the statistics
+    pub fn set_refresh(&mut self, refresh: Duration) {
+        self.refresh = refresh;
+        self.reset();
+    }
+
+    /// Records a vblank of a crtc
+    pub fn vblank(&mut self, crtc: crtc::Handle, time: DrmEventTime) {
+        self.last.insert(crtc, time);
+        if let Some(offset) = self.current_offset() {
+            self.max_offset = self.max_offset.max(offset);
+        }
+    }
+
+    /// Stops tracking a crtc, e.g. because its output was disabled
+    pub fn remove(&mut self, crtc: crtc::Handle) {
+        self.last.remove(&crtc);
+    }
+
+    /// Returns the distance between the last vblanks of two crtcs, at most half the refresh interval
+    pub fn offset(&self, a: crtc::Handle, b: crtc::Handle) -> Option<Duration> {
+        phase_offset(*self.last.get(&a)?, *self.last.get(&b)?, self.refresh)
+    }
+
+    /// Returns the largest distance between the last vblanks of any two tracked crtcs
+    pub fn current_offset(&self) -> Option<Duration> {
+        if self.last.len() < 2 {
+            return None;
+        }
+        let times = self.last.values().copied().collect::<Vec<_>>();
+        times
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, a)| times[idx + 1..].iter().map(move |b| (*a, *b)))
+            .filter_map(|(a, b)| phase_offset(a, b, self.refresh))
+            .max()
+    }
+
+    /// Returns the largest distance between vblanks seen since the tracker was created or reset
+    pub fn max_offset(&self) -> Duration {
+        self.max_offset
+    }
+
+    /// Forgets all vblanks recorded so far
+    pub fn reset(&mut self) {
+        self.last.clear();
+        self.max_offset = Duration::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Instant, SystemTime};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn monotonic(time: Instant) -> DrmEventTime {
+        DrmEventTime::Monotonic(time)
+    }
+
+    fn realtime(time: SystemTime) -> DrmEventTime {
+        DrmEventTime::Realtime(time)
+    }
+
+    #[test]
+    fn offsets_wrap_around_the_refresh_interval() {
+        let now = Instant::now();
+        assert_eq!(
+            phase_offset(monotonic(now), monotonic(now + ms(3)), ms(16)),
+            Some(ms(3))
+        );
+        // a vblank 15ms later is only 1ms before the next one
+        assert_eq!(
+            phase_offset(monotonic(now + ms(15)), monotonic(now), ms(16)),
+            Some(ms(1))
+        );
+        assert_eq!(
+            phase_offset(monotonic(now), monotonic(now + ms(35)), ms(16)),
+            Some(ms(3))
+        );
+        assert_eq!(
+            phase_offset(monotonic(now), monotonic(now + ms(35)), Duration::ZERO),
+            Some(Duration::ZERO)
+        );
+
+        let now = SystemTime::now();
+        assert_eq!(
+            phase_offset(realtime(now + ms(15)), realtime(now), ms(16)),
+            Some(ms(1))
+        );
+        assert_eq!(
+            phase_offset(realtime(now), realtime(now + ms(35)), ms(16)),
+            Some(ms(3))
+        );
+    }
+
+    #[test]
+    fn different_clocks_are_not_compared() {
+        assert_eq!(
+            phase_offset(monotonic(Instant::now()), realtime(SystemTime::now()), ms(16)),
+            None
+        );
+    }
+}
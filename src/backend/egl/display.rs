@@ -493,6 +493,25 @@ impl EGLDisplay {
 
     /// Imports a [`Dmabuf`] as an [`EGLImage`]
     pub fn create_image_from_dmabuf(&self, dmabuf: &Dmabuf) -> Result<EGLImage, Error> {
+        self.create_image_from_dmabuf_with_colorspace(dmabuf, false)
+    }
+
+    /// Imports a [`Dmabuf`] as an [`EGLImage`] using the sRGB colorspace
+    ///
+    /// GL renderbuffers created from the image encode written colors as sRGB, so
+    /// blending happens in linear space. Requires `EGL_EXT_image_gl_colorspace`.
+    pub fn create_srgb_image_from_dmabuf(&self, dmabuf: &Dmabuf) -> Result<EGLImage, Error> {
+        if !self.extensions.iter().any(|s| s == "EGL_EXT_image_gl_colorspace") {
+            return Err(Error::EglExtensionNotSupported(&["EGL_EXT_image_gl_colorspace"]));
+        }
+        self.create_image_from_dmabuf_with_colorspace(dmabuf, true)
+    }
+
+    fn create_image_from_dmabuf_with_colorspace(
+        &self,
+        dmabuf: &Dmabuf,
+        srgb: bool,
+    ) -> Result<EGLImage, Error> {
         if !self.extensions.iter().any(|s| s == "EGL_KHR_image_base")
             && !self
                 .extensions
@@ -582,6 +601,13 @@ impl EGLDisplay {
             }
         }
 
+        if srgb {
+            out.extend(&[
+                ffi::egl::GL_COLORSPACE as i32,
+                ffi::egl::GL_COLORSPACE_SRGB as i32,
+            ]);
+        }
+
         out.push(ffi::egl::NONE as i32);
 
         unsafe {
@@ -34,7 +34,7 @@ pub use self::context::EGLContext;
 mod device;
 mod error;
 pub use self::error::*;
-use crate::backend::SwapBuffersError as GraphicsSwapBuffersError;
+use crate::backend::{SwapBuffersError as GraphicsSwapBuffersError, TemporaryFailureReason};
 #[cfg(feature = "wayland_frontend")]
 use crate::utils::{Buffer, Size};
 
@@ -142,11 +142,11 @@ impl std::convert::From<SwapBuffersError> for GraphicsSwapBuffersError {
         match value {
             // bad surface is answered with a surface recreation in `swap_buffers`
             x @ SwapBuffersError::EGLSwapBuffers(EGLError::BadSurface) => {
-                GraphicsSwapBuffersError::TemporaryFailure(Box::new(x))
+                GraphicsSwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
             }
             // the rest is either never happening or are unrecoverable
-            x @ SwapBuffersError::EGLSwapBuffers(_) => GraphicsSwapBuffersError::ContextLost(Box::new(x)),
-            x @ SwapBuffersError::EGLCreateSurface(_) => GraphicsSwapBuffersError::ContextLost(Box::new(x)),
+            x @ SwapBuffersError::EGLSwapBuffers(_) => GraphicsSwapBuffersError::context_lost(x),
+            x @ SwapBuffersError::EGLCreateSurface(_) => GraphicsSwapBuffersError::context_lost(x),
         }
     }
 }
@@ -169,11 +169,11 @@ impl From<MakeCurrentError> for GraphicsSwapBuffersError {
             Except for the first case all of these recoverable. This conversation is mostly used in winit & EglSurface, where compatible context and surfaces are build.
             */
             x @ MakeCurrentError(EGLError::BadAccess) => {
-                GraphicsSwapBuffersError::TemporaryFailure(Box::new(x))
+                GraphicsSwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
             }
             // BadSurface would result in a recreation in `eglSwapBuffers` -> recoverable
             x @ MakeCurrentError(EGLError::BadSurface) => {
-                GraphicsSwapBuffersError::TemporaryFailure(Box::new(x))
+                GraphicsSwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
             }
             /*
             From khronos docs:
@@ -182,10 +182,10 @@ impl From<MakeCurrentError> for GraphicsSwapBuffersError {
             This does not consern this or future `makeCurrent`-calls.
             */
             x @ MakeCurrentError(EGLError::BadCurrentSurface) => {
-                GraphicsSwapBuffersError::TemporaryFailure(Box::new(x))
+                GraphicsSwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
             }
             // the rest is either never happening or are unrecoverable
-            x => GraphicsSwapBuffersError::ContextLost(Box::new(x)),
+            x => GraphicsSwapBuffersError::context_lost(x),
         }
     }
 }
@@ -0,0 +1,419 @@
+use super::{Event, InputBackend, InputEvent, UnusedEvent};
+use crate::utils::{Logical, Point};
+use std::{collections::VecDeque, fmt};
+
+/// Trait for the beginning of a swipe gesture on a touchpad
+pub trait GestureSwipeBeginEvent<B: InputBackend>: Event<B> {
+    /// Number of fingers used for the gesture, which does not change during a gesture
+    fn fingers(&self) -> u32;
+}
+
+impl<B: InputBackend> GestureSwipeBeginEvent<B> for UnusedEvent {
+    fn fingers(&self) -> u32 {
+        match *self {}
+    }
+}
+
+/// Trait for the movement of the fingers during a swipe gesture
+pub trait GestureSwipeUpdateEvent<B: InputBackend>: Event<B> {
+    /// Delta of the center of the fingers since the last event, in the same unit as pointer motion
+    fn delta(&self) -> Point<f64, Logical> {
+        (self.delta_x(), self.delta_y()).into()
+    }
+
+    /// Delta on the x axis of the center of the fingers since the last event
+    fn delta_x(&self) -> f64;
+    /// Delta on the y axis of the center of the fingers since the last event
+    fn delta_y(&self) -> f64;
+}
+
+impl<B: InputBackend> GestureSwipeUpdateEvent<B> for UnusedEvent {
+    fn delta_x(&self) -> f64 {
+        match *self {}
+    }
+
+    fn delta_y(&self) -> f64 {
+        match *self {}
+    }
+}
+
+/// Trait for the end of a swipe gesture
+pub trait GestureSwipeEndEvent<B: InputBackend>: Event<B> {
+    /// Whether the gesture was cancelled, e.g. because a finger was added or lifted
+    fn cancelled(&self) -> bool;
+}
+
+impl<B: InputBackend> GestureSwipeEndEvent<B> for UnusedEvent {
+    fn cancelled(&self) -> bool {
+        match *self {}
+    }
+}
+
+/// Axis a swipe gesture is locked to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SwipeAxis {
+    /// Left and right swipes
+    Horizontal,
+    /// Up and down swipes
+    Vertical,
+}
+
+/// Direction of a completed swipe gesture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SwipeDirection {
+    /// The fingers moved to the left
+    Left,
+    /// The fingers moved to the right
+    Right,
+    /// The fingers moved up
+    Up,
+    /// The fingers moved down
+    Down,
+}
+
+impl SwipeDirection {
+    fn new(axis: SwipeAxis, progress: f64) -> SwipeDirection {
+        match (axis, progress < 0.0) {
+            (SwipeAxis::Horizontal, true) => SwipeDirection::Left,
+            (SwipeAxis::Horizontal, false) => SwipeDirection::Right,
+            (SwipeAxis::Vertical, true) => SwipeDirection::Up,
+            (SwipeAxis::Vertical, false) => SwipeDirection::Down,
+        }
+    }
+}
+
+/// State changes of a swipe gesture reported by a [`SwipeRecognizer`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwipeGesture {
+    /// The fingers moved far enough for the gesture to be locked to an axis
+    Begin {
+        /// Number of fingers of the gesture
+        fingers: u32,
+        /// Axis the gesture is locked to
+        axis: SwipeAxis,
+    },
+    /// The fingers moved along the axis of the gesture
+    Update {
+        /// Number of fingers of the gesture
+        fingers: u32,
+        /// Axis the gesture is locked to
+        axis: SwipeAxis,
+        /// Distance moved along the axis relative to [`SwipeConfig::distance`], between `-1.0`
+        /// (left or up) and `1.0` (right or down)
+        progress: f64,
+    },
+    /// The fingers were lifted
+    End {
+        /// Number of fingers of the gesture
+        fingers: u32,
+        /// Axis the gesture is locked to
+        axis: SwipeAxis,
+        /// Progress when the fingers were lifted
+        progress: f64,
+        /// Speed of the fingers along the axis when they were lifted, in progress per second
+        ///
+        /// Use it as the initial speed of the animation completing or reverting the transition.
+        velocity: f64,
+        /// Direction of the swipe, if it completed, `None` if it was cancelled
+        completed: Option<SwipeDirection>,
+    },
+}
+
+/// Configuration of a [`SwipeRecognizer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwipeConfig {
+    /// Finger counts of the swipes to recognize, other gestures are ignored
+    pub fingers: Vec<u32>,
+    /// Distance the fingers have to move for the progress to reach `1.0`
+    pub distance: f64,
+    /// Distance the fingers have to move before the gesture is locked to an axis and begins
+    pub lock_distance: f64,
+    /// Progress the gesture has to reach to complete, once the kinetic movement is accounted for
+    pub completion_threshold: f64,
+    /// Time in seconds the movement of the fingers is extrapolated at the end of the gesture
+    ///
+    /// A quick flick completes a gesture, even if the fingers did not move very far.
+    pub kinetic_time: f64,
+}
+
+impl Default for SwipeConfig {
+    fn default() -> Self {
+        SwipeConfig {
+            fingers: vec![3, 4],
+            distance: 300.0,
+            lock_distance: 16.0,
+            completion_threshold: 0.5,
+            kinetic_time: 0.15,
+        }
+    }
+}
+
+// time window in microseconds used to compute the velocity at the end of a gesture
+const VELOCITY_WINDOW: u64 = 100_000;
+
+#[derive(Debug)]
+struct SwipeState {
+    fingers: u32,
+    // total movement of the fingers
+    offset: Point<f64, Logical>,
+    axis: Option<SwipeAxis>,
+    // recent (time in microseconds, progress) samples
+    samples: VecDeque<(u64, f64)>,
+}
+
+/// Recognizes multi-finger swipe gestures of touchpads, e.g. to switch workspaces
+///
+/// Feed it all input events with [`SwipeRecognizer::process_event`], or the gesture events with
+/// the `begin`, `update` and `end` methods. The callback is notified of the
+/// [`SwipeGesture`]s, whose progress can directly drive an animated transition.
+pub struct SwipeRecognizer {
+    config: SwipeConfig,
+    state: Option<SwipeState>,
+    callback: Box<dyn FnMut(SwipeGesture)>,
+}
+
+impl fmt::Debug for SwipeRecognizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SwipeRecognizer")
+            .field("config", &self.config)
+            .field("state", &self.state)
+            .field("callback", &"...")
+            .finish()
+    }
+}
+
+impl SwipeRecognizer {
+    /// Creates a new recognizer notifying the given callback about swipe gestures
+    pub fn new<F>(config: SwipeConfig, callback: F) -> SwipeRecognizer
+    where
+        F: FnMut(SwipeGesture) + 'static,
+    {
+        SwipeRecognizer {
+            config,
+            state: None,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Returns the configuration of this recognizer
+    pub fn config(&self) -> &SwipeConfig {
+        &self.config
+    }
+
+    /// Checks if a recognized swipe gesture is in progress
+    pub fn is_active(&self) -> bool {
+        self.state
+            .as_ref()
+            .map(|state| state.axis.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Handles an input event, returns `true` if it was a swipe gesture event
+    pub fn process_event<B: InputBackend>(&mut self, event: &InputEvent<B>) -> bool {
+        match event {
+            InputEvent::GestureSwipeBegin { event } => self.begin(event.fingers(), event.time_usec()),
+            InputEvent::GestureSwipeUpdate { event } => self.update(event.delta(), event.time_usec()),
+            InputEvent::GestureSwipeEnd { event } => self.end(event.cancelled(), event.time_usec()),
+            _ => return false,
+        }
+        true
+    }
+
+    /// A swipe gesture with the given number of fingers began
+    pub fn begin(&mut self, fingers: u32, time_usec: u64) {
+        self.state = if self.config.fingers.contains(&fingers) {
+            let mut samples = VecDeque::new();
+            samples.push_back((time_usec, 0.0));
+            Some(SwipeState {
+                fingers,
+                offset: (0.0, 0.0).into(),
+                axis: None,
+                samples,
+            })
+        } else {
+            None
+        };
+    }
+
+    /// The fingers of the current swipe gesture moved
+    pub fn update(&mut self, delta: Point<f64, Logical>, time_usec: u64) {
+        let state = match self.state.as_mut() {
+            Some(state) => state,
+            None => return,
+        };
+        state.offset += delta;
+
+        let axis = match state.axis {
+            Some(axis) => axis,
+            None => {
+                let (x, y) = (state.offset.x.abs(), state.offset.y.abs());
+                if x.max(y) < self.config.lock_distance {
+                    return;
+                }
+                let axis = if x >= y {
+                    SwipeAxis::Horizontal
+                } else {
+                    SwipeAxis::Vertical
+                };
+                state.axis = Some(axis);
+                (self.callback)(SwipeGesture::Begin {
+                    fingers: state.fingers,
+                    axis,
+                });
+                axis
+            }
+        };
+
+        let offset = match axis {
+            SwipeAxis::Horizontal => state.offset.x,
+            SwipeAxis::Vertical => state.offset.y,
+        };
+        let progress = (offset / self.config.distance).clamp(-1.0, 1.0);
+        state.samples.push_back((time_usec, progress));
+        while state.samples.len() > 2 && time_usec.saturating_sub(state.samples[0].0) > VELOCITY_WINDOW {
+            state.samples.pop_front();
+        }
+
+        (self.callback)(SwipeGesture::Update {
+            fingers: state.fingers,
+            axis,
+            progress,
+        });
+    }
+
+    /// The current swipe gesture ended, possibly `cancelled` by the touchpad
+    pub fn end(&mut self, cancelled: bool, time_usec: u64) {
+        let state = match self.state.take() {
+            Some(state) => state,
+            None => return,
+        };
+        let axis = match state.axis {
+            Some(axis) => axis,
+            // the gesture never began
+            None => return,
+        };
+
+        let (start_time, start_progress) = state.samples.front().copied().unwrap_or((time_usec, 0.0));
+        let (last_time, progress) = state.samples.back().copied().unwrap_or((time_usec, 0.0));
+        // the fingers stopped moving, if the touchpad reported no motion for a while
+        let velocity = if last_time > start_time && time_usec.saturating_sub(last_time) <= VELOCITY_WINDOW {
+            (progress - start_progress) / ((last_time - start_time) as f64 / 1_000_000.0)
+        } else {
+            0.0
+        };
+
+        let projected = progress + velocity * self.config.kinetic_time;
+        let completed = if !cancelled && projected.abs() >= self.config.completion_threshold {
+            // a flick back towards the start reverts the gesture
+            if projected.signum() == progress.signum() || progress == 0.0 {
+                Some(SwipeDirection::new(axis, projected))
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+        (self.callback)(SwipeGesture::End {
+            fingers: state.fingers,
+            axis,
+            progress,
+            velocity,
+            completed,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn recognizer() -> (SwipeRecognizer, Rc<RefCell<Vec<SwipeGesture>>>) {
+        let gestures = Rc::new(RefCell::new(Vec::new()));
+        let callback_gestures = gestures.clone();
+        let recognizer = SwipeRecognizer::new(SwipeConfig::default(), move |gesture| {
+            callback_gestures.borrow_mut().push(gesture)
+        });
+        (recognizer, gestures)
+    }
+
+    fn last_end(gestures: &[SwipeGesture]) -> Option<SwipeDirection> {
+        match gestures.last() {
+            Some(SwipeGesture::End { completed, .. }) => *completed,
+            other => panic!("expected the end of a gesture, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn axis_lock_and_progress() {
+        let (mut recognizer, gestures) = recognizer();
+        recognizer.begin(3, 0);
+        recognizer.update((10.0, 2.0).into(), 10_000);
+        assert!(gestures.borrow().is_empty());
+        recognizer.update((-40.0, 1.0).into(), 20_000);
+        assert!(recognizer.is_active());
+        assert_eq!(
+            gestures.borrow()[..],
+            [
+                SwipeGesture::Begin {
+                    fingers: 3,
+                    axis: SwipeAxis::Horizontal
+                },
+                SwipeGesture::Update {
+                    fingers: 3,
+                    axis: SwipeAxis::Horizontal,
+                    progress: -0.1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn other_finger_counts_are_ignored() {
+        let (mut recognizer, gestures) = recognizer();
+        recognizer.begin(2, 0);
+        recognizer.update((100.0, 0.0).into(), 10_000);
+        recognizer.end(false, 20_000);
+        assert!(gestures.borrow().is_empty());
+    }
+
+    #[test]
+    fn slow_swipe_completes_past_threshold() {
+        let (mut recognizer, gestures) = recognizer();
+        recognizer.begin(4, 0);
+        for i in 1..=20 {
+            // 200 logical pixels in 2 seconds
+            recognizer.update((0.0, 10.0).into(), i * 100_000);
+        }
+        recognizer.end(false, 2_000_000);
+        assert_eq!(last_end(&gestures.borrow()), Some(SwipeDirection::Down));
+
+        recognizer.begin(4, 3_000_000);
+        for i in 1..=10 {
+            recognizer.update((0.0, -10.0).into(), 3_000_000 + i * 100_000);
+        }
+        recognizer.end(false, 4_000_000);
+        assert_eq!(last_end(&gestures.borrow()), None);
+    }
+
+    #[test]
+    fn flick_completes_kinetically() {
+        let (mut recognizer, gestures) = recognizer();
+        recognizer.begin(3, 0);
+        for i in 1..=5 {
+            // 100 logical pixels in 50ms
+            recognizer.update((20.0, 0.0).into(), i * 10_000);
+        }
+        recognizer.end(false, 50_000);
+        assert_eq!(last_end(&gestures.borrow()), Some(SwipeDirection::Right));
+
+        // the same flick is reverted if the touchpad cancels it
+        recognizer.begin(3, 100_000);
+        for i in 1..=5 {
+            recognizer.update((20.0, 0.0).into(), 100_000 + i * 10_000);
+        }
+        recognizer.end(true, 150_000);
+        assert_eq!(last_end(&gestures.borrow()), None);
+    }
+}
@@ -2,12 +2,19 @@
 
 use std::path::PathBuf;
 
+mod gesture;
 mod tablet;
+mod touch_gesture;
 
+pub use gesture::{
+    GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, SwipeAxis, SwipeConfig,
+    SwipeDirection, SwipeGesture, SwipeRecognizer,
+};
 pub use tablet::{
     ProximityState, TabletToolAxisEvent, TabletToolButtonEvent, TabletToolCapabilitys, TabletToolDescriptor,
     TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState, TabletToolType,
 };
+pub use touch_gesture::{TouchEdge, TouchGesture, TouchGestureConfig, TouchGestureRecognizer, TouchZone};
 
 use crate::utils::{Logical, Point, Raw, Size};
 
@@ -53,6 +60,12 @@ pub trait Event<B: InputBackend> {
     // - check if events can even arrive out of order.
     // - Make stronger time guarantees, if possible
     fn time(&self) -> u32;
+    /// Returns the time of this event in microseconds, in the same clock as [`Event::time`]
+    ///
+    /// Backends not providing more precise timestamps return the milliseconds of [`Event::time`].
+    fn time_usec(&self) -> u64 {
+        self.time() as u64 * 1000
+    }
     /// Returns the device, that generated this event
     fn device(&self) -> B::Device;
 }
@@ -542,6 +555,12 @@ pub trait InputBackend: Sized {
     type TabletToolTipEvent: TabletToolTipEvent<Self>;
     /// Type representing button events on tablet tool devices
     type TabletToolButtonEvent: TabletToolButtonEvent<Self>;
+    /// Type representing the beginning of swipe gestures on touchpads
+    type GestureSwipeBeginEvent: GestureSwipeBeginEvent<Self>;
+    /// Type representing the movement of swipe gestures on touchpads
+    type GestureSwipeUpdateEvent: GestureSwipeUpdateEvent<Self>;
+    /// Type representing the end of swipe gestures on touchpads
+    type GestureSwipeEndEvent: GestureSwipeEndEvent<Self>;
 
     /// Special events that are custom to this backend
     type SpecialEvent;
@@ -638,6 +657,24 @@ pub enum InputEvent<B: InputBackend> {
         event: B::TabletToolButtonEvent,
     },
 
+    /// A swipe gesture began on a touchpad
+    GestureSwipeBegin {
+        /// The gesture swipe begin event
+        event: B::GestureSwipeBeginEvent,
+    },
+
+    /// The fingers of a swipe gesture moved
+    GestureSwipeUpdate {
+        /// The gesture swipe update event
+        event: B::GestureSwipeUpdateEvent,
+    },
+
+    /// A swipe gesture ended
+    GestureSwipeEnd {
+        /// The gesture swipe end event
+        event: B::GestureSwipeEndEvent,
+    },
+
     /// Special event specific of this backend
     Special(B::SpecialEvent),
 }
@@ -0,0 +1,461 @@
+use std::{collections::HashMap, fmt};
+
+use super::{
+    Event, InputBackend, InputEvent, TouchCancelEvent, TouchDownEvent, TouchMotionEvent, TouchSlot,
+    TouchUpEvent,
+};
+use crate::utils::{Logical, Point, Rectangle};
+
+/// Edge of an output a touch swipe can start from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TouchEdge {
+    /// The left edge, e.g. for a back gesture
+    Left,
+    /// The right edge
+    Right,
+    /// The top edge, e.g. to pull down quick settings
+    Top,
+    /// The bottom edge, e.g. to open an overview
+    Bottom,
+}
+
+/// Area of the touchscreen, that edge swipes are recognized in
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchZone {
+    /// Area of the zone in the global compositor space, usually the geometry of an output
+    pub area: Rectangle<i32, Logical>,
+    /// Edges of the area swipes can start from
+    pub edges: Vec<TouchEdge>,
+    /// Distance from an edge, that touch points have to go down within, to start a swipe from it
+    pub edge_size: f64,
+}
+
+impl TouchZone {
+    /// Creates a zone recognizing swipes from all edges of `area`
+    pub fn new(area: Rectangle<i32, Logical>, edge_size: f64) -> TouchZone {
+        TouchZone {
+            area,
+            edges: vec![
+                TouchEdge::Left,
+                TouchEdge::Right,
+                TouchEdge::Top,
+                TouchEdge::Bottom,
+            ],
+            edge_size,
+        }
+    }
+
+    fn edge_at(&self, position: Point<f64, Logical>) -> Option<TouchEdge> {
+        let area = self.area.to_f64();
+        if !area.contains(position) {
+            return None;
+        }
+        self.edges
+            .iter()
+            .map(|edge| (*edge, distance_from(*edge, area, position)))
+            .filter(|(_, distance)| *distance < self.edge_size)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .map(|(edge, _)| edge)
+    }
+}
+
+fn distance_from(edge: TouchEdge, area: Rectangle<f64, Logical>, position: Point<f64, Logical>) -> f64 {
+    match edge {
+        TouchEdge::Left => position.x - area.loc.x,
+        TouchEdge::Right => area.loc.x + area.size.w - position.x,
+        TouchEdge::Top => position.y - area.loc.y,
+        TouchEdge::Bottom => area.loc.y + area.size.h - position.y,
+    }
+}
+
+/// Gestures recognized by a [`TouchGestureRecognizer`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TouchGesture {
+    /// A touch point went down at the edge of a zone
+    EdgeSwipeBegin {
+        /// Edge the swipe started from
+        edge: TouchEdge,
+        /// Area of the zone the swipe started in
+        area: Rectangle<i32, Logical>,
+    },
+    /// The touch point of an edge swipe moved
+    EdgeSwipeUpdate {
+        /// Edge the swipe started from
+        edge: TouchEdge,
+        /// Distance moved away from the edge relative to [`TouchGestureConfig::swipe_distance`],
+        /// between `0.0` and `1.0`
+        progress: f64,
+    },
+    /// The touch point of an edge swipe was lifted or cancelled
+    EdgeSwipeEnd {
+        /// Edge the swipe started from
+        edge: TouchEdge,
+        /// Progress when the touch point was lifted
+        progress: f64,
+        /// Whether the swipe moved far enough to complete, `false` if it was cancelled
+        completed: bool,
+    },
+    /// Several fingers tapped the touchscreen at once
+    Tap {
+        /// Number of fingers of the tap
+        fingers: u32,
+        /// Center of the touch points
+        position: Point<f64, Logical>,
+    },
+}
+
+/// Configuration of a [`TouchGestureRecognizer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchGestureConfig {
+    /// Distance a touch point has to move away from the edge for the progress to reach `1.0`
+    pub swipe_distance: f64,
+    /// Progress an edge swipe has to reach to complete
+    pub completion_threshold: f64,
+    /// Finger counts of the taps to recognize, single finger taps are usually left to clients
+    pub tap_fingers: Vec<u32>,
+    /// Time in microseconds between the first finger going down and the last one being lifted,
+    /// for the touch points to count as a tap
+    pub tap_timeout: u64,
+    /// Distance the fingers of a tap may move
+    pub tap_tolerance: f64,
+}
+
+impl Default for TouchGestureConfig {
+    fn default() -> Self {
+        TouchGestureConfig {
+            swipe_distance: 200.0,
+            completion_threshold: 0.5,
+            tap_fingers: vec![2, 3],
+            tap_timeout: 250_000,
+            tap_tolerance: 16.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EdgeSwipe {
+    slot: TouchSlot,
+    edge: TouchEdge,
+    start: Point<f64, Logical>,
+    progress: f64,
+}
+
+#[derive(Debug)]
+struct TapState {
+    // time in microseconds of the first touch point going down
+    start_time: u64,
+    // start locations of the touch points down at the moment
+    points: HashMap<TouchSlot, Point<f64, Logical>>,
+    fingers: u32,
+    center: Point<f64, Logical>,
+    moved: bool,
+}
+
+/// Recognizes compositor gestures on touchscreens, like swipes from the edges of outputs and multi-finger taps
+///
+/// Pass all touch events to the recognizer before delivering them to clients, either with
+/// [`TouchGestureRecognizer::process_event`] or the `down`, `motion`, `up` and `cancel` methods.
+/// Touch points starting an edge swipe are consumed by the recognizer and must not be delivered
+/// to clients. Taps cannot be told apart from other touches until the fingers are lifted,
+/// so their touch points are delivered to clients as usual.
+pub struct TouchGestureRecognizer {
+    config: TouchGestureConfig,
+    zones: Vec<TouchZone>,
+    swipe: Option<EdgeSwipe>,
+    tap: Option<TapState>,
+    callback: Box<dyn FnMut(TouchGesture)>,
+}
+
+impl fmt::Debug for TouchGestureRecognizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TouchGestureRecognizer")
+            .field("config", &self.config)
+            .field("zones", &self.zones)
+            .field("swipe", &self.swipe)
+            .field("tap", &self.tap)
+            .field("callback", &"...")
+            .finish()
+    }
+}
+
+impl TouchGestureRecognizer {
+    /// Creates a new recognizer notifying the given callback about touch gestures
+    pub fn new<F>(config: TouchGestureConfig, callback: F) -> TouchGestureRecognizer
+    where
+        F: FnMut(TouchGesture) + 'static,
+    {
+        TouchGestureRecognizer {
+            config,
+            zones: Vec::new(),
+            swipe: None,
+            tap: None,
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Returns the configuration of this recognizer
+    pub fn config(&self) -> &TouchGestureConfig {
+        &self.config
+    }
+
+    /// Sets the zones edge swipes are recognized in, usually one per output
+    pub fn set_zones(&mut self, zones: Vec<TouchZone>) {
+        self.zones = zones;
+    }
+
+    /// Returns the zones edge swipes are recognized in
+    pub fn zones(&self) -> &[TouchZone] {
+        &self.zones
+    }
+
+    /// Checks if an edge swipe is in progress
+    pub fn is_swiping(&self) -> bool {
+        self.swipe.is_some()
+    }
+
+    /// Handles an input event of a touchscreen mapped to `area` in the global compositor space
+    ///
+    /// Returns `true`, if the event was consumed by a gesture and must not be delivered to clients.
+    pub fn process_event<B: InputBackend>(
+        &mut self,
+        event: &InputEvent<B>,
+        area: Rectangle<i32, Logical>,
+    ) -> bool {
+        match event {
+            InputEvent::TouchDown { event } => {
+                let position = event.position_transformed(area.size) + area.loc.to_f64();
+                self.down(event.slot(), position, event.time_usec())
+            }
+            InputEvent::TouchMotion { event } => {
+                let position = event.position_transformed(area.size) + area.loc.to_f64();
+                self.motion(event.slot(), position)
+            }
+            InputEvent::TouchUp { event } => self.up(event.slot(), event.time_usec()),
+            InputEvent::TouchCancel { event } => self.cancel(event.slot()),
+            _ => false,
+        }
+    }
+
+    /// A touch point went down, returns `true` if it starts an edge swipe
+    pub fn down(&mut self, slot: TouchSlot, position: Point<f64, Logical>, time_usec: u64) -> bool {
+        if self.swipe.is_none() && self.tap.is_none() {
+            let edge = self
+                .zones
+                .iter()
+                .find_map(|zone| zone.edge_at(position).map(|edge| (edge, zone.area)));
+            if let Some((edge, area)) = edge {
+                self.swipe = Some(EdgeSwipe {
+                    slot,
+                    edge,
+                    start: position,
+                    progress: 0.0,
+                });
+                (self.callback)(TouchGesture::EdgeSwipeBegin { edge, area });
+                return true;
+            }
+        }
+
+        let tap = self.tap.get_or_insert_with(|| TapState {
+            start_time: time_usec,
+            points: HashMap::new(),
+            fingers: 0,
+            center: (0.0, 0.0).into(),
+            moved: false,
+        });
+        if tap.points.insert(slot, position).is_none() {
+            // running average of the start locations of all fingers
+            tap.fingers += 1;
+            tap.center.x += (position.x - tap.center.x) / tap.fingers as f64;
+            tap.center.y += (position.y - tap.center.y) / tap.fingers as f64;
+        }
+        false
+    }
+
+    /// A touch point moved, returns `true` if it belongs to an edge swipe
+    pub fn motion(&mut self, slot: TouchSlot, position: Point<f64, Logical>) -> bool {
+        if let Some(swipe) = self.swipe.as_mut().filter(|swipe| swipe.slot == slot) {
+            let delta = position - swipe.start;
+            let distance = match swipe.edge {
+                TouchEdge::Left => delta.x,
+                TouchEdge::Right => -delta.x,
+                TouchEdge::Top => delta.y,
+                TouchEdge::Bottom => -delta.y,
+            };
+            swipe.progress = (distance / self.config.swipe_distance).clamp(0.0, 1.0);
+            (self.callback)(TouchGesture::EdgeSwipeUpdate {
+                edge: swipe.edge,
+                progress: swipe.progress,
+            });
+            return true;
+        }
+
+        if let Some(tap) = self.tap.as_mut() {
+            if let Some(start) = tap.points.get(&slot) {
+                let delta = position - *start;
+                if delta.x.hypot(delta.y) > self.config.tap_tolerance {
+                    tap.moved = true;
+                }
+            }
+        }
+        false
+    }
+
+    /// A touch point was lifted, returns `true` if it ends an edge swipe
+    pub fn up(&mut self, slot: TouchSlot, time_usec: u64) -> bool {
+        if self
+            .swipe
+            .as_ref()
+            .map(|swipe| swipe.slot == slot)
+            .unwrap_or(false)
+        {
+            let swipe = self.swipe.take().unwrap();
+            (self.callback)(TouchGesture::EdgeSwipeEnd {
+                edge: swipe.edge,
+                progress: swipe.progress,
+                completed: swipe.progress >= self.config.completion_threshold,
+            });
+            return true;
+        }
+
+        let done = match self.tap.as_mut() {
+            Some(tap) => tap.points.remove(&slot).is_some() && tap.points.is_empty(),
+            None => false,
+        };
+        if done {
+            let tap = self.tap.take().unwrap();
+            if !tap.moved
+                && time_usec.saturating_sub(tap.start_time) <= self.config.tap_timeout
+                && self.config.tap_fingers.contains(&tap.fingers)
+            {
+                (self.callback)(TouchGesture::Tap {
+                    fingers: tap.fingers,
+                    position: tap.center,
+                });
+            }
+        }
+        false
+    }
+
+    /// A touch point was cancelled, returns `true` if it cancels an edge swipe
+    ///
+    /// Taps in progress are cancelled as well.
+    pub fn cancel(&mut self, slot: TouchSlot) -> bool {
+        self.tap = None;
+        match self.swipe.take() {
+            Some(swipe) if swipe.slot == slot => {
+                (self.callback)(TouchGesture::EdgeSwipeEnd {
+                    edge: swipe.edge,
+                    progress: swipe.progress,
+                    completed: false,
+                });
+                true
+            }
+            swipe => {
+                self.swipe = swipe;
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn recognizer() -> (TouchGestureRecognizer, Rc<RefCell<Vec<TouchGesture>>>) {
+        let gestures = Rc::new(RefCell::new(Vec::new()));
+        let callback_gestures = gestures.clone();
+        let mut recognizer = TouchGestureRecognizer::new(TouchGestureConfig::default(), move |gesture| {
+            callback_gestures.borrow_mut().push(gesture)
+        });
+        recognizer.set_zones(vec![
+            TouchZone::new(Rectangle::from_loc_and_size((0, 0), (1000, 2000)), 20.0),
+            TouchZone {
+                area: Rectangle::from_loc_and_size((1000, 0), (1000, 1000)),
+                edges: vec![TouchEdge::Top],
+                edge_size: 20.0,
+            },
+        ]);
+        (recognizer, gestures)
+    }
+
+    fn slot(id: u32) -> TouchSlot {
+        Some(id).into()
+    }
+
+    #[test]
+    fn edge_swipe() {
+        let (mut recognizer, gestures) = recognizer();
+        assert!(recognizer.down(slot(0), (5.0, 500.0).into(), 0));
+        assert!(recognizer.motion(slot(0), (155.0, 510.0).into()));
+        assert!(recognizer.up(slot(0), 100_000));
+        assert_eq!(
+            gestures.borrow()[..],
+            [
+                TouchGesture::EdgeSwipeBegin {
+                    edge: TouchEdge::Left,
+                    area: Rectangle::from_loc_and_size((0, 0), (1000, 2000)),
+                },
+                TouchGesture::EdgeSwipeUpdate {
+                    edge: TouchEdge::Left,
+                    progress: 0.75
+                },
+                TouchGesture::EdgeSwipeEnd {
+                    edge: TouchEdge::Left,
+                    progress: 0.75,
+                    completed: true
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn edges_per_zone() {
+        let (mut recognizer, gestures) = recognizer();
+        // the left edge of the second zone is disabled
+        assert!(!recognizer.down(slot(0), (1005.0, 500.0).into(), 0));
+        assert!(!recognizer.up(slot(0), 500_000));
+        assert!(recognizer.down(slot(0), (1500.0, 10.0).into(), 1_000_000));
+        assert!(!recognizer.up(slot(1), 1_100_000));
+        assert!(recognizer.cancel(slot(0)));
+        assert_eq!(
+            gestures.borrow().last(),
+            Some(&TouchGesture::EdgeSwipeEnd {
+                edge: TouchEdge::Top,
+                progress: 0.0,
+                completed: false
+            })
+        );
+    }
+
+    #[test]
+    fn multi_finger_tap() {
+        let (mut recognizer, gestures) = recognizer();
+        assert!(!recognizer.down(slot(0), (400.0, 400.0).into(), 0));
+        assert!(!recognizer.down(slot(1), (600.0, 400.0).into(), 10_000));
+        assert!(!recognizer.motion(slot(1), (605.0, 400.0).into()));
+        assert!(!recognizer.up(slot(0), 100_000));
+        assert!(!recognizer.up(slot(1), 110_000));
+        assert_eq!(
+            gestures.borrow()[..],
+            [TouchGesture::Tap {
+                fingers: 2,
+                position: (500.0, 400.0).into()
+            }]
+        );
+
+        // too much movement
+        recognizer.down(slot(0), (400.0, 400.0).into(), 1_000_000);
+        recognizer.down(slot(1), (600.0, 400.0).into(), 1_000_000);
+        recognizer.motion(slot(1), (700.0, 400.0).into());
+        recognizer.up(slot(0), 1_100_000);
+        recognizer.up(slot(1), 1_100_000);
+        // too slow
+        recognizer.down(slot(0), (400.0, 400.0).into(), 2_000_000);
+        recognizer.down(slot(1), (600.0, 400.0).into(), 2_000_000);
+        recognizer.up(slot(0), 2_500_000);
+        recognizer.up(slot(1), 2_500_000);
+        assert_eq!(gestures.borrow().len(), 1);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::backend::input as backend;
+
+use input as libinput;
+use input::event::{
+    gesture::{
+        GestureEndEvent, GestureEventCoordinates, GestureEventTrait, GestureSwipeBeginEvent,
+        GestureSwipeEndEvent, GestureSwipeUpdateEvent,
+    },
+    EventTrait,
+};
+
+use super::LibinputInputBackend;
+
+macro_rules! gesture_event_impl {
+    ($ty:ty) => {
+        impl backend::Event<LibinputInputBackend> for $ty {
+            fn time(&self) -> u32 {
+                GestureEventTrait::time(self)
+            }
+
+            fn time_usec(&self) -> u64 {
+                GestureEventTrait::time_usec(self)
+            }
+
+            fn device(&self) -> libinput::Device {
+                EventTrait::device(self)
+            }
+        }
+    };
+}
+
+gesture_event_impl!(GestureSwipeBeginEvent);
+gesture_event_impl!(GestureSwipeUpdateEvent);
+gesture_event_impl!(GestureSwipeEndEvent);
+
+impl backend::GestureSwipeBeginEvent<LibinputInputBackend> for GestureSwipeBeginEvent {
+    fn fingers(&self) -> u32 {
+        GestureEventTrait::finger_count(self) as u32
+    }
+}
+
+impl backend::GestureSwipeUpdateEvent<LibinputInputBackend> for GestureSwipeUpdateEvent {
+    fn delta_x(&self) -> f64 {
+        GestureEventCoordinates::dx(self)
+    }
+
+    fn delta_y(&self) -> f64 {
+        GestureEventCoordinates::dy(self)
+    }
+}
+
+impl backend::GestureSwipeEndEvent<LibinputInputBackend> for GestureSwipeEndEvent {
+    fn cancelled(&self) -> bool {
+        GestureEndEvent::cancelled(self)
+    }
+}
@@ -19,10 +19,14 @@ use std::{
 
 use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
 
-use slog::{info, o, trace};
+use slog::{info, o, trace, warn};
 
+mod gesture;
+mod power;
 mod tablet;
 
+pub use self::power::DevicePowerPolicy;
+
 // No idea if this is the same across unix platforms
 // Lets make this linux exclusive for now, once someone tries to build it for
 // any BSD-like system, they can verify if this is right and make a PR to change this.
@@ -40,6 +44,7 @@ pub struct LibinputInputBackend {
     links: Vec<SignalToken>,
     logger: ::slog::Logger,
     token: Option<Token>,
+    power_policy: Option<power::PowerPolicyFn>,
 }
 
 impl LibinputInputBackend {
@@ -57,8 +62,24 @@ impl LibinputInputBackend {
             links: Vec::new(),
             logger: log,
             token: None,
+            power_policy: None,
         }
     }
+
+    /// Sets the power management policy of devices
+    ///
+    /// The `policy` is called for every device added afterwards, before the device is passed
+    /// on as an [`InputEvent::DeviceAdded`], which allows configuring every device individually.
+    /// As devices of a seat are only added once the backend is dispatched, setting a policy right
+    /// after creating the backend covers all devices.
+    ///
+    /// Use [`DevicePowerPolicy::apply`] to change the policy of already added devices.
+    pub fn set_power_policy<F>(&mut self, policy: F)
+    where
+        F: FnMut(&libinput::Device) -> DevicePowerPolicy + 'static,
+    {
+        self.power_policy = Some(power::PowerPolicyFn(Box::new(policy)));
+    }
 }
 
 #[cfg(feature = "backend_session")]
@@ -132,6 +153,10 @@ impl backend::Event<LibinputInputBackend> for event::keyboard::KeyboardKeyEvent
         event::keyboard::KeyboardEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::keyboard::KeyboardEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -158,6 +183,10 @@ impl<'a> backend::Event<LibinputInputBackend> for event::pointer::PointerAxisEve
         event::pointer::PointerEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::pointer::PointerEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -182,6 +211,10 @@ impl backend::Event<LibinputInputBackend> for event::pointer::PointerButtonEvent
         event::pointer::PointerEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::pointer::PointerEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -202,6 +235,10 @@ impl backend::Event<LibinputInputBackend> for event::pointer::PointerMotionEvent
         event::pointer::PointerEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::pointer::PointerEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -221,6 +258,10 @@ impl backend::Event<LibinputInputBackend> for event::pointer::PointerMotionAbsol
         event::pointer::PointerEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::pointer::PointerEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -251,6 +292,10 @@ impl backend::Event<LibinputInputBackend> for event::touch::TouchDownEvent {
         event::touch::TouchEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::touch::TouchEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -283,6 +328,10 @@ impl backend::Event<LibinputInputBackend> for event::touch::TouchMotionEvent {
         event::touch::TouchEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::touch::TouchEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -315,6 +364,10 @@ impl backend::Event<LibinputInputBackend> for event::touch::TouchUpEvent {
         event::touch::TouchEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::touch::TouchEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -331,6 +384,10 @@ impl backend::Event<LibinputInputBackend> for event::touch::TouchCancelEvent {
         event::touch::TouchEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::touch::TouchEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -347,6 +404,10 @@ impl backend::Event<LibinputInputBackend> for event::touch::TouchFrameEvent {
         event::touch::TouchEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        event::touch::TouchEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -370,6 +431,9 @@ impl InputBackend for LibinputInputBackend {
     type TabletToolProximityEvent = event::tablet_tool::TabletToolProximityEvent;
     type TabletToolTipEvent = event::tablet_tool::TabletToolTipEvent;
     type TabletToolButtonEvent = event::tablet_tool::TabletToolButtonEvent;
+    type GestureSwipeBeginEvent = event::gesture::GestureSwipeBeginEvent;
+    type GestureSwipeUpdateEvent = event::gesture::GestureSwipeUpdateEvent;
+    type GestureSwipeEndEvent = event::gesture::GestureSwipeEndEvent;
 
     type SpecialEvent = backend::UnusedEvent;
 }
@@ -475,6 +539,18 @@ impl EventSource for LibinputInputBackend {
 
                             info!(self.logger, "New device {:?}", added.sysname(),);
 
+                            if let Some(policy) = self.power_policy.as_mut() {
+                                let policy = (policy.0)(&added);
+                                if let Err(err) = policy.apply(&added) {
+                                    warn!(
+                                        self.logger,
+                                        "Failed to apply power policy to device {:?}: {:?}",
+                                        added.sysname(),
+                                        err
+                                    );
+                                }
+                            }
+
                             callback(InputEvent::DeviceAdded { device: added }, &mut ());
                         }
                         event::DeviceEvent::Removed(device_removed_event) => {
@@ -555,6 +631,20 @@ impl EventSource for LibinputInputBackend {
                             trace!(self.logger, "Unknown libinput tablet event");
                         }
                     },
+                    libinput::Event::Gesture(event::GestureEvent::Swipe(swipe_event)) => match swipe_event {
+                        event::gesture::GestureSwipeEvent::Begin(event) => {
+                            callback(InputEvent::GestureSwipeBegin { event }, &mut ());
+                        }
+                        event::gesture::GestureSwipeEvent::Update(event) => {
+                            callback(InputEvent::GestureSwipeUpdate { event }, &mut ());
+                        }
+                        event::gesture::GestureSwipeEvent::End(event) => {
+                            callback(InputEvent::GestureSwipeEnd { event }, &mut ());
+                        }
+                        _ => {
+                            trace!(self.logger, "Unknown libinput gesture event");
+                        }
+                    },
                     _ => {} //FIXME: What to do with the rest.
                 }
             }
@@ -0,0 +1,144 @@
+//! Power management policies of libinput devices
+
+use std::fmt;
+
+use input as libinput;
+use input::{DeviceConfigError, SendEventsMode};
+
+/// Power management policy of a libinput device
+///
+/// Both settings are implemented by libinput itself and are usually only available
+/// on built-in touchpads. Settings a device does not support are ignored, `None` keeps
+/// the current setting of the device.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DevicePowerPolicy {
+    /// Disable the device while typing on a keyboard
+    pub disable_while_typing: Option<bool>,
+    /// Disable the device while an external pointer device, like a mouse, is plugged in
+    pub disable_on_external_mouse: Option<bool>,
+}
+
+impl DevicePowerPolicy {
+    /// Reads the current policy of a device
+    ///
+    /// Settings not supported by the device are `None`.
+    pub fn current(device: &libinput::Device) -> DevicePowerPolicy {
+        DevicePowerPolicy {
+            disable_while_typing: if device.config_dwt_is_available() {
+                Some(device.config_dwt_enabled())
+            } else {
+                None
+            },
+            disable_on_external_mouse: if supports_disable_on_external_mouse(device) {
+                Some(
+                    device
+                        .config_send_events_mode()
+                        .contains(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE),
+                )
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Reads the default policy of a device
+    ///
+    /// Settings not supported by the device are `None`.
+    pub fn default_for(device: &libinput::Device) -> DevicePowerPolicy {
+        DevicePowerPolicy {
+            disable_while_typing: if device.config_dwt_is_available() {
+                Some(device.config_dwt_default_enabled())
+            } else {
+                None
+            },
+            // libinput always enables devices by default
+            disable_on_external_mouse: if supports_disable_on_external_mouse(device) {
+                Some(false)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Applies this policy to a device
+    ///
+    /// Settings the device does not support are skipped.
+    pub fn apply(&self, device: &libinput::Device) -> Result<(), DeviceConfigError> {
+        if let Some(enabled) = self.disable_while_typing {
+            if device.config_dwt_is_available() {
+                device.config_dwt_set_enabled(enabled)?;
+            }
+        }
+        if let Some(enabled) = self.disable_on_external_mouse {
+            if supports_disable_on_external_mouse(device) {
+                let mode = send_events_mode(device.config_send_events_mode(), enabled);
+                device.config_send_events_set_mode(mode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the send events mode to switch to, when changing the disable on external mouse setting
+///
+/// The send events modes are exclusive, so enabling the setting replaces any other mode and
+/// disabling it only changes the mode, if the setting is the active one.
+fn send_events_mode(current: SendEventsMode, disable_on_external_mouse: bool) -> SendEventsMode {
+    if disable_on_external_mouse {
+        SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE
+    } else if current == SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE {
+        SendEventsMode::ENABLED
+    } else {
+        current
+    }
+}
+
+fn supports_disable_on_external_mouse(device: &libinput::Device) -> bool {
+    device
+        .config_send_events_modes()
+        .contains(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE)
+}
+
+pub(super) struct PowerPolicyFn(pub(super) Box<dyn FnMut(&libinput::Device) -> DevicePowerPolicy>);
+
+impl fmt::Debug for PowerPolicyFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PowerPolicyFn").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enable_disable_on_external_mouse() {
+        for &current in &[
+            SendEventsMode::ENABLED,
+            SendEventsMode::DISABLED,
+            SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE,
+        ] {
+            assert_eq!(
+                send_events_mode(current, true),
+                SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE
+            );
+        }
+    }
+
+    #[test]
+    fn disable_disable_on_external_mouse() {
+        assert_eq!(
+            send_events_mode(SendEventsMode::DISABLED_ON_EXTERNAL_MOUSE, false),
+            SendEventsMode::ENABLED
+        );
+        assert_eq!(
+            send_events_mode(SendEventsMode::ENABLED, false),
+            SendEventsMode::ENABLED
+        );
+        // a device disabled otherwise stays disabled
+        assert_eq!(
+            send_events_mode(SendEventsMode::DISABLED, false),
+            SendEventsMode::DISABLED
+        );
+    }
+}
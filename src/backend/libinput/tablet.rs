@@ -24,6 +24,10 @@ where
         tablet_tool::TabletToolEventTrait::time(self)
     }
 
+    fn time_usec(&self) -> u64 {
+        tablet_tool::TabletToolEventTrait::time_usec(self)
+    }
+
     fn device(&self) -> libinput::Device {
         event::EventTrait::device(self)
     }
@@ -17,7 +17,9 @@
 //!
 //! This module is tightly coupled with the [`udev`] module (gated by the `backend_udev` cargo
 //! feature), which allows the discovery of usable graphics and input devices on the system, using
-//! the udev system daemon.
+//! the udev system daemon. The [`backlight`] module uses it as well to find and control
+//! the backlights of internal panels, and the [`orientation`] module to find accelerometers
+//! for rotating them automatically.
 //!
 //! ### Input handling
 //!
@@ -80,12 +82,16 @@ pub mod allocator;
 pub mod input;
 pub mod renderer;
 
+#[cfg(feature = "backend_udev")]
+pub mod backlight;
 #[cfg(feature = "backend_drm")]
 pub mod drm;
 #[cfg(feature = "backend_egl")]
 pub mod egl;
 #[cfg(feature = "backend_libinput")]
 pub mod libinput;
+#[cfg(feature = "backend_udev")]
+pub mod orientation;
 #[cfg(feature = "backend_session")]
 pub mod session;
 #[cfg(feature = "backend_udev")]
@@ -97,7 +103,55 @@ pub mod winit;
 #[cfg(feature = "backend_x11")]
 pub mod x11;
 
+use std::path::{Path, PathBuf};
+
+use crate::utils::{Physical, Rectangle};
+
+/// A surface presenting rendered buffers, e.g. to a monitor or a window of the host
+///
+/// This is implemented by the surfaces of all backends, like the
+/// [`GbmBufferedSurface`](drm::GbmBufferedSurface), the
+/// [`DumbBufferedSurface`](drm::DumbBufferedSurface), the [`X11Surface`](x11::X11Surface) and
+/// the [`WinitGraphicsBackend`](winit::WinitGraphicsBackend), so a render loop can be written
+/// once for development and production backends:
+///
+/// ```no_run
+/// # use smithay::backend::{CompositorSurface, SwapBuffersError};
+/// fn render<S: CompositorSurface>(surface: &mut S) -> Result<(), SwapBuffersError> {
+///     let (buffer, age) = surface.next_buffer().map_err(Into::into)?;
+///     // bind `buffer` to a renderer and render, using `age` for damage tracking
+///     surface.present(None).map_err(Into::into)
+/// }
+/// ```
+pub trait CompositorSurface {
+    /// Buffer type to render into
+    ///
+    /// Surfaces rendering with their own renderer, like the `WinitGraphicsBackend`, use `()`
+    /// and bind their renderer in [`CompositorSurface::next_buffer`] instead.
+    type Buffer;
+    /// Error type returned by the surface
+    type Error: Into<SwapBuffersError>;
+
+    /// Retrieves the next buffer to be rendered into and its age
+    ///
+    /// An age of `0` means the contents of the buffer are undefined. This function can be called
+    /// multiple times and will return the same buffer until it is presented.
+    fn next_buffer(&mut self) -> Result<(&Self::Buffer, u8), Self::Error>;
+
+    /// Presents the buffer returned by [`CompositorSurface::next_buffer`]
+    ///
+    /// `damage` is a hint of the regions changed since the last frame, `None` damages the whole
+    /// buffer. Surfaces not supporting damage ignore it.
+    fn present(&mut self, damage: Option<&[Rectangle<i32, Physical>]>) -> Result<(), Self::Error>;
+
+    /// Resets the ages of the buffers, e.g. after their contents became invalid
+    fn reset_buffers(&mut self);
+}
+
 /// Error that can happen when swapping buffers.
+///
+/// Backends map their errors to this type, so compositors can handle rendering errors of all
+/// backends without downcasting. [`SwapBuffersError::recovery_hint`] summarizes how to recover.
 #[derive(Debug, thiserror::Error)]
 pub enum SwapBuffersError {
     /// The buffers have already been swapped.
@@ -114,17 +168,117 @@ pub enum SwapBuffersError {
     ///
     /// Operations will have no effect. Functions that read textures, buffers, etc.
     /// will return uninitialized data instead.
-    #[error("The context has been lost, it needs to be recreated: {0}")]
-    ContextLost(Box<dyn std::error::Error + Send + Sync>),
+    #[error("The context has been lost, it needs to be recreated: {source}")]
+    ContextLost {
+        /// Path of the device, that failed, if known
+        device: Option<PathBuf>,
+        /// The underlying error
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
     /// A temporary condition caused to rendering to fail.
     ///
     /// Depending on the underlying error this *might* require fixing internal state of the rendering backend,
     /// but failures mapped to `TemporaryFailure` are always recoverable without re-creating the entire stack,
     /// as is represented by `ContextLost`.
     ///
-    /// Proceed after investigating the source to reschedule another full rendering step or just this page_flip at a later time.
-    /// If the root cause cannot be discovered and subsequent renderings also fail, it is advised to fallback to
-    /// recreation.
-    #[error("A temporary condition caused the page flip to fail: {0}")]
-    TemporaryFailure(Box<dyn std::error::Error + Send + Sync>),
+    /// The `reason` tells, when to reschedule another full rendering step or just this page_flip.
+    /// If subsequent renderings also fail, it is advised to fallback to recreation.
+    #[error("A temporary condition caused the page flip to fail: {source}")]
+    TemporaryFailure {
+        /// Kind of the condition
+        reason: TemporaryFailureReason,
+        /// Path of the device, that failed, if known
+        device: Option<PathBuf>,
+        /// The underlying error
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// Condition causing a [`SwapBuffersError::TemporaryFailure`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TemporaryFailureReason {
+    /// The device is not accessible right now, e.g. because the session is paused or another
+    /// process is drm master
+    ///
+    /// Retrying is pointless until the device becomes active again, e.g. after the session
+    /// was activated again.
+    DeviceInactive,
+    /// The device or surface is busy, e.g. a page flip is still pending or no buffer is free
+    ///
+    /// Retrying after the next vblank usually succeeds.
+    Busy,
+    /// Any other temporary condition
+    Other,
+}
+
+/// How to recover from a [`SwapBuffersError`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecoveryHint {
+    /// Nothing needs to be done, the frame was already presented
+    None,
+    /// Render again, e.g. on the next vblank or after a short timeout
+    Retry,
+    /// Stop rendering and render again, once the device becomes active again
+    WaitForDevice,
+    /// Recreate the renderer and the surfaces rendered to
+    Recreate,
+}
+
+impl SwapBuffersError {
+    /// Creates a [`SwapBuffersError::ContextLost`] error without a known device
+    pub fn context_lost(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> SwapBuffersError {
+        SwapBuffersError::ContextLost {
+            device: None,
+            source: source.into(),
+        }
+    }
+
+    /// Creates a [`SwapBuffersError::TemporaryFailure`] error without a known device
+    pub fn temporary_failure(
+        reason: TemporaryFailureReason,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> SwapBuffersError {
+        SwapBuffersError::TemporaryFailure {
+            reason,
+            device: None,
+            source: source.into(),
+        }
+    }
+
+    /// Sets the path of the device, that failed
+    pub fn with_device(mut self, path: impl Into<PathBuf>) -> SwapBuffersError {
+        match &mut self {
+            SwapBuffersError::AlreadySwapped => {}
+            SwapBuffersError::ContextLost { device, .. }
+            | SwapBuffersError::TemporaryFailure { device, .. } => *device = Some(path.into()),
+        }
+        self
+    }
+
+    /// Returns the path of the device, that failed, if known
+    pub fn device(&self) -> Option<&Path> {
+        match self {
+            SwapBuffersError::AlreadySwapped => None,
+            SwapBuffersError::ContextLost { device, .. }
+            | SwapBuffersError::TemporaryFailure { device, .. } => device.as_deref(),
+        }
+    }
+
+    /// Returns how to recover from this error
+    pub fn recovery_hint(&self) -> RecoveryHint {
+        match self {
+            SwapBuffersError::AlreadySwapped => RecoveryHint::None,
+            SwapBuffersError::ContextLost { .. } => RecoveryHint::Recreate,
+            SwapBuffersError::TemporaryFailure {
+                reason: TemporaryFailureReason::DeviceInactive,
+                ..
+            } => RecoveryHint::WaitForDevice,
+            SwapBuffersError::TemporaryFailure { .. } => RecoveryHint::Retry,
+        }
+    }
+
+    /// Returns whether rendering again right away may succeed
+    pub fn is_retryable(&self) -> bool {
+        self.recovery_hint() == RecoveryHint::Retry
+    }
 }
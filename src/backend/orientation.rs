@@ -0,0 +1,397 @@
+//! Automatic rotation of outputs through accelerometers
+//!
+//! Phones and tablets contain accelerometers exposed by the kernel as IIO devices. This module
+//! provides [`accelerometers`] to find them through udev and [`Accelerometer::orientation`] to
+//! derive the [`Orientation`] the device is held in from the direction of gravity, using the same
+//! thresholds as iio-sensor-proxy, without depending on that daemon.
+//!
+//! Most accelerometers cannot notify about changes, so the [`OrientationMonitor`] polls one at a
+//! fixed interval and can be inserted into [`calloop`] to be notified about changes of the orientation.
+//! Use [`Orientation::transform`] as the transform of the internal panel and
+//! [`Orientation::input_position`] to rotate the positions of its touchscreen accordingly.
+//!
+//! ```no_run
+//! use smithay::backend::orientation::{accelerometers, OrientationMonitor};
+//! use std::time::Duration;
+//!
+//! let accelerometer = accelerometers("seat0")
+//!     .expect("Failed to scan accelerometers")
+//!     .into_iter()
+//!     .next()
+//!     .expect("No accelerometer");
+//! let monitor = OrientationMonitor::new(accelerometer, Duration::from_millis(500), None);
+//! # let event_loop = smithay::reexports::calloop::EventLoop::<()>::try_new().unwrap();
+//! # let loop_handle = event_loop.handle();
+//! loop_handle.insert_source(monitor, |orientation, _, _| {
+//!     // e.g. apply `orientation.transform()` to the output of the internal panel
+//! }).expect("Failed to insert the orientation monitor into the event loop");
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use calloop::{
+    timer::{TimeoutAction, Timer},
+    EventSource, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use slog::{debug, o, warn};
+use udev::Enumerator;
+
+use crate::utils::{Logical, Point, Size, Transform};
+#[cfg(feature = "wayland_frontend")]
+use crate::wayland::output::Output;
+
+// tilt in degrees the device has to be rotated by, before the orientation changes
+const THRESHOLD: f64 = 35.0;
+// minimal tilt in degrees to switch between the two orientations of the same axis
+const SAME_AXIS_LIMIT: f64 = 5.0;
+
+/// Orientation of a device, named after the edge of the panel pointing up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    /// The device is held in its natural orientation
+    Normal,
+    /// The device is upside down
+    BottomUp,
+    /// The left edge of the panel points up
+    LeftUp,
+    /// The right edge of the panel points up
+    RightUp,
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::Normal
+    }
+}
+
+impl Orientation {
+    /// Derives the orientation from an acceleration in the frame of the panel
+    ///
+    /// The acceleration is in m/s², with the y axis pointing down and the z axis out of the panel,
+    /// so a device held upright in its natural orientation reads about `[0.0, -9.81, 0.0]`.
+    /// Lying flat or only slight tilts keep the `previous` orientation.
+    pub fn from_acceleration(acceleration: [f64; 3], previous: Orientation) -> Orientation {
+        let [x, y, z] = acceleration;
+        let portrait = x.atan2(y.hypot(z)).to_degrees();
+        let landscape = y.atan2(x.hypot(z)).to_degrees();
+
+        if portrait.abs() > THRESHOLD {
+            let orientation = if portrait > 0.0 {
+                Orientation::LeftUp
+            } else {
+                Orientation::RightUp
+            };
+            let same_axis = matches!(previous, Orientation::LeftUp | Orientation::RightUp);
+            if same_axis && portrait.abs() < SAME_AXIS_LIMIT {
+                previous
+            } else {
+                orientation
+            }
+        } else if landscape.abs() > THRESHOLD {
+            let orientation = if landscape > 0.0 {
+                Orientation::BottomUp
+            } else {
+                Orientation::Normal
+            };
+            let same_axis = matches!(previous, Orientation::Normal | Orientation::BottomUp);
+            if same_axis && landscape.abs() < SAME_AXIS_LIMIT {
+                previous
+            } else {
+                orientation
+            }
+        } else {
+            previous
+        }
+    }
+
+    /// Returns the transform of the panel, that keeps its content upright
+    pub fn transform(self) -> Transform {
+        match self {
+            Orientation::Normal => Transform::Normal,
+            Orientation::BottomUp => Transform::_180,
+            Orientation::LeftUp => Transform::_90,
+            Orientation::RightUp => Transform::_270,
+        }
+    }
+
+    /// Rotates a position on the panel, e.g. of its touchscreen, into the rotated output
+    ///
+    /// `panel_size` is the size of the panel in its natural orientation.
+    pub fn input_position(
+        self,
+        position: Point<f64, Logical>,
+        panel_size: Size<f64, Logical>,
+    ) -> Point<f64, Logical> {
+        self.transform()
+            .invert()
+            .transform_point_in(position, &panel_size)
+    }
+
+    /// Applies the transform of this orientation to an output
+    #[cfg(feature = "wayland_frontend")]
+    pub fn apply_to_output(self, output: &Output) {
+        output.change_current_state(None, Some(self.transform().into()), None, None);
+    }
+}
+
+/// An accelerometer exposed through IIO
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accelerometer {
+    name: String,
+    syspath: PathBuf,
+    scale: f64,
+    mount_matrix: [[f64; 3]; 3],
+}
+
+const IDENTITY: [[f64; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+fn read_float(syspath: &Path, attribute: &str) -> io::Result<f64> {
+    fs::read_to_string(syspath.join(attribute))?
+        .trim()
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Parses a mount matrix like `0, 1, 0; -1, 0, 0; 0, 0, 1`
+fn parse_mount_matrix(matrix: &str) -> Option<[[f64; 3]; 3]> {
+    let mut result = [[0.0; 3]; 3];
+    let mut rows = matrix.trim().split(';');
+    for row in result.iter_mut() {
+        let mut values = rows.next()?.split(',');
+        for value in row.iter_mut() {
+            *value = values.next()?.trim().parse().ok()?;
+        }
+        if values.next().is_some() {
+            return None;
+        }
+    }
+    if rows.next().is_some() {
+        return None;
+    }
+    Some(result)
+}
+
+impl Accelerometer {
+    fn from_device(device: &udev::Device) -> io::Result<Accelerometer> {
+        let syspath = device.syspath().to_path_buf();
+        let scale = read_float(&syspath, "in_accel_scale")
+            .or_else(|_| read_float(&syspath, "in_accel_x_scale"))
+            .unwrap_or(1.0);
+        // udev quirks take precedence over the matrix of the driver
+        let mount_matrix = device
+            .property_value("ACCEL_MOUNT_MATRIX")
+            .and_then(|matrix| matrix.to_str())
+            .map(String::from)
+            .or_else(|| fs::read_to_string(syspath.join("in_accel_mount_matrix")).ok())
+            .or_else(|| fs::read_to_string(syspath.join("mount_matrix")).ok())
+            .and_then(|matrix| parse_mount_matrix(&matrix))
+            .unwrap_or(IDENTITY);
+
+        Ok(Accelerometer {
+            name: device.sysname().to_string_lossy().into_owned(),
+            syspath,
+            scale,
+            mount_matrix,
+        })
+    }
+
+    /// Name of the accelerometer in sysfs, e.g. `iio:device0`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Reads the current acceleration in m/s² in the frame of the panel
+    ///
+    /// See [`Orientation::from_acceleration`] for the orientation of the axes.
+    pub fn acceleration(&self) -> io::Result<[f64; 3]> {
+        let mut raw = [0.0; 3];
+        for (value, axis) in raw.iter_mut().zip(["x", "y", "z"].iter()) {
+            *value = read_float(&self.syspath, &format!("in_accel_{}_raw", axis))? * self.scale;
+        }
+        let mut acceleration = [0.0; 3];
+        for (value, row) in acceleration.iter_mut().zip(self.mount_matrix.iter()) {
+            *value = row.iter().zip(raw.iter()).map(|(a, b)| a * b).sum();
+        }
+        Ok(acceleration)
+    }
+
+    /// Reads the current orientation of the device
+    ///
+    /// See [`Orientation::from_acceleration`].
+    pub fn orientation(&self, previous: Orientation) -> io::Result<Orientation> {
+        self.acceleration()
+            .map(|acceleration| Orientation::from_acceleration(acceleration, previous))
+    }
+}
+
+/// Returns all accelerometers of a given seat
+pub fn accelerometers<S: AsRef<str>>(seat: S) -> io::Result<Vec<Accelerometer>> {
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("iio")?;
+    Ok(enumerator
+        .scan_devices()?
+        .filter(|device| {
+            let device_seat = device
+                .property_value("ID_SEAT")
+                .map(|x| x.to_os_string())
+                .unwrap_or_else(|| "seat0".into());
+            device_seat == seat.as_ref() && device.syspath().join("in_accel_x_raw").exists()
+        })
+        .flat_map(|device| Accelerometer::from_device(&device).ok())
+        .collect())
+}
+
+/// Event source polling an [`Accelerometer`] for changes of the [`Orientation`]
+///
+/// Generates an event with the new orientation, whenever it changes. See the [module](self)
+/// documentation for an example.
+#[derive(Debug)]
+pub struct OrientationMonitor {
+    accelerometer: Accelerometer,
+    orientation: Option<Orientation>,
+    interval: Duration,
+    timer: Timer,
+    logger: ::slog::Logger,
+}
+
+impl OrientationMonitor {
+    /// Creates a new [`OrientationMonitor`] reading the accelerometer every `interval`
+    ///
+    /// The orientation is read immediately, so the first event reports the initial orientation.
+    pub fn new<L>(accelerometer: Accelerometer, interval: Duration, logger: L) -> OrientationMonitor
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_orientation"));
+        OrientationMonitor {
+            accelerometer,
+            orientation: None,
+            interval,
+            timer: Timer::immediate(),
+            logger,
+        }
+    }
+
+    /// Returns the last orientation read from the accelerometer, `None` before it was first read
+    pub fn orientation(&self) -> Option<Orientation> {
+        self.orientation
+    }
+}
+
+impl EventSource for OrientationMonitor {
+    type Event = Orientation;
+    type Metadata = ();
+    type Ret = ();
+    type Error = io::Error;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Orientation, &mut ()),
+    {
+        let OrientationMonitor {
+            accelerometer,
+            orientation,
+            interval,
+            timer,
+            logger,
+        } = self;
+        timer.process_events(readiness, token, |_, _| {
+            match accelerometer.orientation(orientation.unwrap_or_default()) {
+                Ok(new) if Some(new) != *orientation => {
+                    debug!(logger, "Orientation changed to {:?}", new);
+                    *orientation = Some(new);
+                    callback(new, &mut ());
+                }
+                Ok(_) => {}
+                Err(err) => warn!(
+                    logger,
+                    "Failed to read accelerometer {}: {}",
+                    accelerometer.name(),
+                    err
+                ),
+            }
+            TimeoutAction::ToDuration(*interval)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.timer.register(poll, factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.timer.reregister(poll, factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.timer.unregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation_from_gravity() {
+        let g = 9.81;
+        assert_eq!(
+            Orientation::from_acceleration([0.0, -g, 0.0], Orientation::LeftUp),
+            Orientation::Normal
+        );
+        assert_eq!(
+            Orientation::from_acceleration([0.0, g, 0.0], Orientation::Normal),
+            Orientation::BottomUp
+        );
+        assert_eq!(
+            Orientation::from_acceleration([g, 0.0, 0.0], Orientation::Normal),
+            Orientation::LeftUp
+        );
+        assert_eq!(
+            Orientation::from_acceleration([-g, 0.0, 0.0], Orientation::Normal),
+            Orientation::RightUp
+        );
+        // lying flat or slightly tilted keeps the orientation
+        assert_eq!(
+            Orientation::from_acceleration([0.0, 0.0, g], Orientation::RightUp),
+            Orientation::RightUp
+        );
+        assert_eq!(
+            Orientation::from_acceleration([3.0, -2.0, g], Orientation::BottomUp),
+            Orientation::BottomUp
+        );
+    }
+
+    #[test]
+    fn mount_matrix() {
+        assert_eq!(parse_mount_matrix("1, 0, 0; 0, 1, 0; 0, 0, 1\n"), Some(IDENTITY));
+        assert_eq!(
+            parse_mount_matrix("0,-1,0;1,0,0;0,0,1"),
+            Some([[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]])
+        );
+        assert_eq!(parse_mount_matrix("1, 0, 0; 0, 1, 0"), None);
+        assert_eq!(parse_mount_matrix("1, 0, 0, 0; 0, 1, 0; 0, 0, 1"), None);
+    }
+
+    #[test]
+    fn rotated_input() {
+        let panel = Size::from((100.0, 200.0));
+        let position = Point::from((10.0, 20.0));
+        assert_eq!(Orientation::Normal.input_position(position, panel), position);
+        assert_eq!(
+            Orientation::BottomUp.input_position(position, panel),
+            Point::from((90.0, 180.0))
+        );
+        // the rotated output is as wide as the panel is tall
+        let rotated = Orientation::LeftUp.input_position(position, panel);
+        assert!(rotated.x >= 0.0 && rotated.x <= panel.h && rotated.y >= 0.0 && rotated.y <= panel.w);
+    }
+}
@@ -0,0 +1,162 @@
+//! Damage history for rendering into buffers of a swapchain
+//!
+//! Buffers of a swapchain are reused every few frames, so they still contain an older frame when
+//! they are rendered into again. Their age tells how many frames old that content is:
+//! an age of `1` means the buffer contains the previous frame, `0` that its content is undefined.
+//! To bring a buffer up to date, the damage of all frames since it was last used has to be
+//! redrawn in addition to the damage of the new frame.
+//!
+//! [`DamageHistory`] keeps the damage of a bounded number of frames to compute that.
+//!
+//! ```
+//! use smithay::backend::renderer::damage::DamageHistory;
+//! use smithay::utils::{Physical, Rectangle};
+//!
+//! let output = Rectangle::<i32, Physical>::from_loc_and_size((0, 0), (1920, 1080));
+//! let mut history = DamageHistory::default();
+//! # let age = 2;
+//! # let new_damage = vec![Rectangle::from_loc_and_size((0, 0), (10, 10))];
+//!
+//! // for every frame, with the age of the buffer bound for rendering
+//! let mut damage = history.damage_for_age(age).unwrap_or_else(|| vec![output]);
+//! damage.extend(new_damage.iter().copied());
+//! // render `damage`, then store the damage of this frame only
+//! history.push(new_damage);
+//! ```
+
+use std::{collections::VecDeque, fmt};
+
+use crate::utils::Rectangle;
+
+/// Number of frames a [`DamageHistory`] remembers by default
+///
+/// This is enough for the swapchains of smithay, which have at most four buffers.
+pub const DEFAULT_MAX_AGE: usize = 4;
+
+/// Damage of the last rendered frames, to compute the damage of reused buffers
+pub struct DamageHistory<Kind> {
+    // newest first
+    frames: VecDeque<Vec<Rectangle<i32, Kind>>>,
+    max_age: usize,
+}
+
+impl<Kind> fmt::Debug for DamageHistory<Kind>
+where
+    Rectangle<i32, Kind>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DamageHistory")
+            .field("frames", &self.frames)
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
+
+impl<Kind> Clone for DamageHistory<Kind> {
+    fn clone(&self) -> Self {
+        DamageHistory {
+            frames: self.frames.clone(),
+            max_age: self.max_age,
+        }
+    }
+}
+
+impl<Kind> Default for DamageHistory<Kind> {
+    fn default() -> Self {
+        DamageHistory::new(DEFAULT_MAX_AGE)
+    }
+}
+
+impl<Kind> DamageHistory<Kind> {
+    /// Creates a new history handling buffers up to the given age
+    ///
+    /// Older buffers are fully damaged.
+    pub fn new(max_age: usize) -> Self {
+        DamageHistory {
+            frames: VecDeque::with_capacity(max_age),
+            max_age,
+        }
+    }
+
+    /// Returns the maximum age of buffers, that are not fully damaged
+    pub fn max_age(&self) -> usize {
+        self.max_age
+    }
+
+    /// Stores the damage of a newly rendered frame
+    ///
+    /// This has to be the damage of the frame itself, not the additional damage returned by
+    /// [`DamageHistory::damage_for_age`].
+    pub fn push(&mut self, damage: Vec<Rectangle<i32, Kind>>) {
+        // a buffer of age `max_age` needs the damage of the `max_age - 1` frames after it
+        if self.max_age <= 1 {
+            return;
+        }
+        self.frames.truncate(self.max_age - 2);
+        self.frames.push_front(damage);
+    }
+
+    /// Returns the damage, that has to be redrawn in a buffer of the given age, in addition to
+    /// the damage of the new frame
+    ///
+    /// Returns `None`, if the whole buffer has to be redrawn, because its content is undefined
+    /// (an age of `0`) or older than the stored history.
+    pub fn damage_for_age(&self, age: usize) -> Option<Vec<Rectangle<i32, Kind>>> {
+        if age == 0 || age > self.max_age || age - 1 > self.frames.len() {
+            return None;
+        }
+        Some(self.frames.iter().take(age - 1).flatten().copied().collect())
+    }
+
+    /// Forgets all damage, e.g. after rendering failed or the output was reconfigured,
+    /// so all buffers are fully damaged
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Logical;
+
+    fn rect(x: i32) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((x, 0), (1, 1))
+    }
+
+    #[test]
+    fn damage_by_age() {
+        let mut history = DamageHistory::default();
+        assert_eq!(history.damage_for_age(1), None);
+
+        history.push(vec![rect(1)]);
+        history.push(vec![rect(2)]);
+        assert_eq!(history.damage_for_age(0), None);
+        // the previous frame needs no additional damage
+        assert_eq!(history.damage_for_age(1), Some(vec![]));
+        assert_eq!(history.damage_for_age(2), Some(vec![rect(2)]));
+        assert_eq!(history.damage_for_age(3), Some(vec![rect(2), rect(1)]));
+        // the content of this buffer predates the history
+        assert_eq!(history.damage_for_age(4), None);
+
+        history.clear();
+        assert_eq!(history.damage_for_age(2), None);
+    }
+
+    #[test]
+    fn bounded_history() {
+        let mut history = DamageHistory::new(3);
+        for x in 0..10 {
+            history.push(vec![rect(x)]);
+        }
+        assert_eq!(history.frames.len(), 2);
+        assert_eq!(history.damage_for_age(3), Some(vec![rect(9), rect(8)]));
+        // saturates to full damage past the maximum age
+        assert_eq!(history.damage_for_age(4), None);
+
+        let mut history = DamageHistory::new(1);
+        history.push(vec![rect(0)]);
+        assert_eq!(history.damage_for_age(1), Some(vec![]));
+        assert_eq!(history.damage_for_age(2), None);
+    }
+}
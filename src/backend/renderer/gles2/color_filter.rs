@@ -0,0 +1,122 @@
+//! Color filters applied to everything rendered by a [`Gles2Renderer`](super::Gles2Renderer)
+
+use cgmath::{prelude::*, Matrix3, Vector3};
+
+/// Accessibility filter applied to all colors of a frame, see [`Gles2Renderer::set_color_filter`](super::Gles2Renderer::set_color_filter)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorFilter {
+    /// Inverts all colors, e.g. for a high-contrast dark mode
+    Invert,
+    /// Shows luminance only
+    Grayscale,
+    /// Shifts the red-green contrast lost by protanopia (missing red cones) into colors, that are still distinguishable
+    Protanopia,
+    /// Shifts the red-green contrast lost by deuteranopia (missing green cones) into colors, that are still distinguishable
+    Deuteranopia,
+}
+
+// rows of the matrices are written like in the literature
+fn rows(m: [[f32; 3]; 3]) -> Matrix3<f32> {
+    Matrix3::new(
+        m[0][0], m[0][1], m[0][2], m[1][0], m[1][1], m[1][2], m[2][0], m[2][1], m[2][2],
+    )
+    .transpose()
+}
+
+// daltonization: simulates the deficiency in LMS space and shifts the lost information
+// into the channels still perceived
+fn daltonize(simulation: [[f32; 3]; 3]) -> Matrix3<f32> {
+    let rgb_to_lms = rows([
+        [17.8824, 43.5161, 4.11935],
+        [3.45565, 27.1554, 3.86714],
+        [0.0299566, 0.184309, 1.46709],
+    ]);
+    let lms_to_rgb = rows([
+        [0.080_944_45, -0.130_504_41, 0.116_721_07],
+        [-0.010_248_533, 0.054_019_33, -0.113_614_71],
+        [-0.000_365_296_94, -0.004_121_614_7, 0.693_511_4],
+    ]);
+    let shift = rows([[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]]);
+    let simulated = lms_to_rgb * rows(simulation) * rgb_to_lms;
+    Matrix3::identity() + shift * (Matrix3::identity() - simulated)
+}
+
+impl ColorFilter {
+    /// Returns the matrix and the offset applied to colors with premultiplied alpha as
+    /// `matrix * color.rgb + offset * color.a`
+    ///
+    /// Filters are affine, so applying them to every drawn color is the same as applying them
+    /// to the blended result afterwards.
+    pub(super) fn matrix(&self) -> (Matrix3<f32>, [f32; 3]) {
+        match self {
+            ColorFilter::Invert => (-Matrix3::identity(), [1.0, 1.0, 1.0]),
+            ColorFilter::Grayscale => {
+                // Rec. 709 luma
+                let luma = [0.2126, 0.7152, 0.0722];
+                (rows([luma, luma, luma]), [0.0; 3])
+            }
+            ColorFilter::Protanopia => (
+                daltonize([[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+                [0.0; 3],
+            ),
+            ColorFilter::Deuteranopia => (
+                daltonize([[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]]),
+                [0.0; 3],
+            ),
+        }
+    }
+
+    /// Applies the filter to a color with premultiplied alpha
+    pub(super) fn apply(&self, color: [f32; 4]) -> [f32; 4] {
+        let (matrix, offset) = self.matrix();
+        let alpha = color[3];
+        let rgb = matrix * Vector3::new(color[0], color[1], color[2]) + Vector3::from(offset) * alpha;
+        let clamp = |c: f32| c.max(0.0).min(alpha);
+        [clamp(rgb.x), clamp(rgb.y), clamp(rgb.z), alpha]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: [f32; 4], b: [f32; 4]) {
+        assert!(
+            a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-2),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn invert() {
+        assert_close(
+            ColorFilter::Invert.apply([1.0, 0.0, 0.25, 1.0]),
+            [0.0, 1.0, 0.75, 1.0],
+        );
+        // premultiplied colors stay premultiplied
+        assert_close(
+            ColorFilter::Invert.apply([0.5, 0.0, 0.25, 0.5]),
+            [0.0, 0.5, 0.25, 0.5],
+        );
+        assert_close(ColorFilter::Invert.apply([0.0; 4]), [0.0; 4]);
+    }
+
+    #[test]
+    fn grayscale() {
+        let gray = ColorFilter::Grayscale.apply([0.0, 1.0, 0.0, 1.0]);
+        assert_close(gray, [0.7152, 0.7152, 0.7152, 1.0]);
+        assert_close(ColorFilter::Grayscale.apply([1.0; 4]), [1.0; 4]);
+    }
+
+    #[test]
+    fn daltonization_keeps_grays() {
+        for filter in [ColorFilter::Protanopia, ColorFilter::Deuteranopia] {
+            assert_close(filter.apply([0.5, 0.5, 0.5, 1.0]), [0.5, 0.5, 0.5, 1.0]);
+            // red is shifted towards the other channels
+            let red = filter.apply([1.0, 0.0, 0.0, 1.0]);
+            assert!(red[1] > 0.0 || red[2] > 0.0, "{:?}", red);
+        }
+    }
+}
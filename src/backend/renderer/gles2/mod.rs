@@ -15,14 +15,30 @@ use std::{
         atomic::{AtomicPtr, Ordering},
         mpsc::{channel, Receiver, Sender},
     },
+    time::Instant,
 };
 
 #[cfg(feature = "wayland_frontend")]
-use std::{cell::RefCell, collections::HashMap};
+use std::{cell::RefCell, collections::HashMap, rc::Weak};
 
+mod color_filter;
+mod pool;
+mod program_cache;
 mod shaders;
+mod timer;
+#[cfg(feature = "wayland_frontend")]
+mod upload;
 mod version;
 
+pub use self::color_filter::ColorFilter;
+use self::pool::{size_class, BufferPool};
+use self::program_cache::ProgramCache;
+pub use self::program_cache::{default_program_cache_dir, set_program_cache_dir};
+use self::timer::GpuTimer;
+pub use self::timer::{FrameTiming, RenderStats, SectionTiming, TimingStats};
+#[cfg(feature = "wayland_frontend")]
+use self::upload::UploadBuffer;
+
 use super::{
     Bind, ExportDma, ExportMem, Frame, ImportDma, ImportMem, Offscreen, Renderer, Texture, TextureFilter,
     TextureMapping, Unbind,
@@ -35,7 +51,7 @@ use crate::backend::egl::{
     ffi::egl::{self as ffi_egl, types::EGLImage},
     EGLContext, EGLSurface, MakeCurrentError,
 };
-use crate::backend::SwapBuffersError;
+use crate::backend::{SwapBuffersError, TemporaryFailureReason};
 use crate::utils::{Buffer, Physical, Rectangle, Size, Transform};
 
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
@@ -63,6 +79,11 @@ struct Gles2TexProgram {
     uniform_tex_matrix: ffi::types::GLint,
     uniform_matrix: ffi::types::GLint,
     uniform_alpha: ffi::types::GLint,
+    uniform_linear_blending: ffi::types::GLint,
+    uniform_tex_step: ffi::types::GLint,
+    uniform_color_filter: ffi::types::GLint,
+    uniform_filter_matrix: ffi::types::GLint,
+    uniform_filter_offset: ffi::types::GLint,
     attrib_vert: ffi::types::GLint,
     attrib_vert_position: ffi::types::GLint,
 }
@@ -147,7 +168,7 @@ enum CleanupResource {
     FramebufferObject(ffi::types::GLuint),
     RenderbufferObject(ffi::types::GLuint),
     EGLImage(EGLImage),
-    Mapping(ffi::types::GLuint, *const nix::libc::c_void),
+    Mapping(ffi::types::GLuint, usize, *const nix::libc::c_void),
 }
 
 impl Texture for Gles2Texture {
@@ -166,6 +187,8 @@ impl Texture for Gles2Texture {
 #[derive(Debug)]
 pub struct Gles2Mapping {
     pbo: ffi::types::GLuint,
+    // allocated size of the pbo in bytes
+    capacity: usize,
     size: Size<i32, Buffer>,
     mapping: AtomicPtr<nix::libc::c_void>,
     destruction_callback_sender: Sender<CleanupResource>,
@@ -192,6 +215,7 @@ impl Drop for Gles2Mapping {
     fn drop(&mut self) {
         let _ = self.destruction_callback_sender.send(CleanupResource::Mapping(
             self.pbo,
+            self.capacity,
             self.mapping.load(Ordering::SeqCst),
         ));
     }
@@ -203,6 +227,17 @@ struct Gles2Buffer {
     image: EGLImage,
     rbo: ffi::types::GLuint,
     fbo: ffi::types::GLuint,
+    srgb: bool,
+}
+
+/// Texture imported from a shm buffer, that might be shared by all surfaces the buffer is attached to
+#[cfg(feature = "wayland_frontend")]
+#[derive(Debug)]
+struct ShmCacheEntry {
+    buffer: wl_buffer::WlBuffer,
+    texture: Weak<Gles2TextureInternal>,
+    // set once a surface other than the one that imported the buffer uses the texture
+    shared: bool,
 }
 
 /// Offscreen render surface
@@ -273,6 +308,12 @@ pub struct Gles2Renderer {
     tex_programs: [Gles2TexProgram; shaders::FRAGMENT_COUNT],
     solid_program: Gles2SolidProgram,
     dmabuf_cache: std::collections::HashMap<WeakDmabuf, Gles2Texture>,
+    #[cfg(feature = "wayland_frontend")]
+    shm_cache: Vec<ShmCacheEntry>,
+    #[cfg(feature = "wayland_frontend")]
+    persistent_shm_upload: bool,
+    #[cfg(feature = "wayland_frontend")]
+    shm_upload_buffer: Option<UploadBuffer>,
     egl: EGLContext,
     #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
     egl_reader: Option<EGLBufferReader>,
@@ -283,9 +324,16 @@ pub struct Gles2Renderer {
     // This field is only accessed if the image or wayland_frontend features are active
     #[allow(dead_code)]
     destruction_callback_sender: Sender<CleanupResource>,
+    // pixel buffer objects of dropped mappings, recycled for later downloads
+    pbo_pool: BufferPool<ffi::types::GLuint>,
     min_filter: TextureFilter,
     max_filter: TextureFilter,
     supports_instancing: bool,
+    gpu_timer: Option<GpuTimer>,
+    last_frame_timing: Option<FrameTiming>,
+    linear_blending: bool,
+    multi_tap_downscaling: bool,
+    color_filter: Option<ColorFilter>,
     logger_ptr: Option<*mut ::slog::Logger>,
     logger: ::slog::Logger,
     _not_send: *mut (),
@@ -310,6 +358,12 @@ pub struct Gles2Frame {
     min_filter: TextureFilter,
     max_filter: TextureFilter,
     supports_instancing: bool,
+    gpu_timer: Option<GpuTimer>,
+    linear_blending: bool,
+    multi_tap_downscaling: bool,
+    color_filter: Option<ColorFilter>,
+    // distance between the samples of the current texture in texture coordinates, zero disables multi-tap sampling
+    tex_step: [f32; 2],
 }
 
 impl fmt::Debug for Gles2Frame {
@@ -407,7 +461,7 @@ impl From<Gles2Error> for SwapBuffersError {
             | x @ Gles2Error::GLExtensionNotSupported(_)
             | x @ Gles2Error::EGLExtensionNotSupported(_)
             | x @ Gles2Error::GLVersionNotSupported(_)
-            | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::ContextLost(Box::new(x)),
+            | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::context_lost(x),
             Gles2Error::ContextActivationError(err) => err.into(),
             x @ Gles2Error::FramebufferBindingError
             | x @ Gles2Error::BindBufferEGLError(_)
@@ -415,7 +469,9 @@ impl From<Gles2Error> for SwapBuffersError {
             | x @ Gles2Error::BufferAccessError(_)
             | x @ Gles2Error::MappingError
             | x @ Gles2Error::UnexpectedSize
-            | x @ Gles2Error::EGLBufferAccessError(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            | x @ Gles2Error::EGLBufferAccessError(_) => {
+                SwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
+            }
         }
     }
     #[cfg(not(feature = "wayland_frontend"))]
@@ -427,12 +483,14 @@ impl From<Gles2Error> for SwapBuffersError {
             | x @ Gles2Error::GLExtensionNotSupported(_)
             | x @ Gles2Error::EGLExtensionNotSupported(_)
             | x @ Gles2Error::GLVersionNotSupported(_)
-            | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::ContextLost(Box::new(x)),
+            | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::context_lost(x),
             Gles2Error::ContextActivationError(err) => err.into(),
             x @ Gles2Error::FramebufferBindingError
             | x @ Gles2Error::MappingError
             | x @ Gles2Error::UnexpectedSize
-            | x @ Gles2Error::BindBufferEGLError(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            | x @ Gles2Error::BindBufferEGLError(_) => {
+                SwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
+            }
         }
     }
 }
@@ -487,14 +545,22 @@ unsafe fn compile_shader(
 
 unsafe fn link_program(
     gl: &ffi::Gles2,
+    cache: Option<&ProgramCache>,
     vert_src: &'static str,
     frag_src: &'static str,
 ) -> Result<ffi::types::GLuint, Gles2Error> {
+    if let Some(program) = cache.and_then(|cache| cache.load(gl, vert_src, frag_src)) {
+        return Ok(program);
+    }
+
     let vert = compile_shader(gl, ffi::VERTEX_SHADER, vert_src)?;
     let frag = compile_shader(gl, ffi::FRAGMENT_SHADER, frag_src)?;
     let program = gl.CreateProgram();
     gl.AttachShader(program, vert);
     gl.AttachShader(program, frag);
+    if let Some(cache) = cache {
+        cache.prepare(gl, program);
+    }
     gl.LinkProgram(program);
     gl.DetachShader(program, vert);
     gl.DetachShader(program, frag);
@@ -508,11 +574,19 @@ unsafe fn link_program(
         return Err(Gles2Error::ProgramLinkError);
     }
 
+    if let Some(cache) = cache {
+        cache.store(gl, program, vert_src, frag_src);
+    }
+
     Ok(program)
 }
 
-unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2TexProgram, Gles2Error> {
-    let program = link_program(gl, shaders::VERTEX_SHADER, frag)?;
+unsafe fn texture_program(
+    gl: &ffi::Gles2,
+    cache: Option<&ProgramCache>,
+    frag: &'static str,
+) -> Result<Gles2TexProgram, Gles2Error> {
+    let program = link_program(gl, cache, shaders::VERTEX_SHADER, frag)?;
 
     let vert = CStr::from_bytes_with_nul(b"vert\0").expect("NULL terminated");
     let vert_position = CStr::from_bytes_with_nul(b"vert_position\0").expect("NULL terminated");
@@ -520,6 +594,11 @@ unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2Te
     let matrix = CStr::from_bytes_with_nul(b"matrix\0").expect("NULL terminated");
     let tex_matrix = CStr::from_bytes_with_nul(b"tex_matrix\0").expect("NULL terminated");
     let alpha = CStr::from_bytes_with_nul(b"alpha\0").expect("NULL terminated");
+    let linear_blending = CStr::from_bytes_with_nul(b"linear_blending\0").expect("NULL terminated");
+    let tex_step = CStr::from_bytes_with_nul(b"tex_step\0").expect("NULL terminated");
+    let color_filter = CStr::from_bytes_with_nul(b"color_filter\0").expect("NULL terminated");
+    let filter_matrix = CStr::from_bytes_with_nul(b"filter_matrix\0").expect("NULL terminated");
+    let filter_offset = CStr::from_bytes_with_nul(b"filter_offset\0").expect("NULL terminated");
 
     Ok(Gles2TexProgram {
         program,
@@ -527,14 +606,31 @@ unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2Te
         uniform_matrix: gl.GetUniformLocation(program, matrix.as_ptr() as *const ffi::types::GLchar),
         uniform_tex_matrix: gl.GetUniformLocation(program, tex_matrix.as_ptr() as *const ffi::types::GLchar),
         uniform_alpha: gl.GetUniformLocation(program, alpha.as_ptr() as *const ffi::types::GLchar),
+        uniform_linear_blending: gl
+            .GetUniformLocation(program, linear_blending.as_ptr() as *const ffi::types::GLchar),
+        uniform_tex_step: gl.GetUniformLocation(program, tex_step.as_ptr() as *const ffi::types::GLchar),
+        uniform_color_filter: gl
+            .GetUniformLocation(program, color_filter.as_ptr() as *const ffi::types::GLchar),
+        uniform_filter_matrix: gl
+            .GetUniformLocation(program, filter_matrix.as_ptr() as *const ffi::types::GLchar),
+        uniform_filter_offset: gl
+            .GetUniformLocation(program, filter_offset.as_ptr() as *const ffi::types::GLchar),
         attrib_vert: gl.GetAttribLocation(program, vert.as_ptr() as *const ffi::types::GLchar),
         attrib_vert_position: gl
             .GetAttribLocation(program, vert_position.as_ptr() as *const ffi::types::GLchar),
     })
 }
 
-unsafe fn solid_program(gl: &ffi::Gles2) -> Result<Gles2SolidProgram, Gles2Error> {
-    let program = link_program(gl, shaders::VERTEX_SHADER_SOLID, shaders::FRAGMENT_SHADER_SOLID)?;
+unsafe fn solid_program(
+    gl: &ffi::Gles2,
+    cache: Option<&ProgramCache>,
+) -> Result<Gles2SolidProgram, Gles2Error> {
+    let program = link_program(
+        gl,
+        cache,
+        shaders::VERTEX_SHADER_SOLID,
+        shaders::FRAGMENT_SHADER_SOLID,
+    )?;
 
     let matrix = CStr::from_bytes_with_nul(b"matrix\0").expect("NULL terminated");
     let color = CStr::from_bytes_with_nul(b"color\0").expect("NULL terminated");
@@ -638,12 +734,13 @@ impl Gles2Renderer {
             (gl, gl_version, exts, logger, supports_instancing)
         };
 
+        let cache = ProgramCache::new(&gl, gl_version, &exts, &log);
         let tex_programs = [
-            texture_program(&gl, shaders::FRAGMENT_SHADER_ABGR)?,
-            texture_program(&gl, shaders::FRAGMENT_SHADER_XBGR)?,
-            texture_program(&gl, shaders::FRAGMENT_SHADER_EXTERNAL)?,
+            texture_program(&gl, cache.as_ref(), shaders::FRAGMENT_SHADER_ABGR)?,
+            texture_program(&gl, cache.as_ref(), shaders::FRAGMENT_SHADER_XBGR)?,
+            texture_program(&gl, cache.as_ref(), shaders::FRAGMENT_SHADER_EXTERNAL)?,
         ];
-        let solid_program = solid_program(&gl)?;
+        let solid_program = solid_program(&gl, cache.as_ref())?;
 
         // Initialize vertices based on drawing methodology.
         let vertices: &[ffi::types::GLfloat] = if supports_instancing {
@@ -679,12 +776,24 @@ impl Gles2Renderer {
             target: None,
             buffers: Vec::new(),
             dmabuf_cache: std::collections::HashMap::new(),
+            #[cfg(feature = "wayland_frontend")]
+            shm_cache: Vec::new(),
+            #[cfg(feature = "wayland_frontend")]
+            persistent_shm_upload: false,
+            #[cfg(feature = "wayland_frontend")]
+            shm_upload_buffer: None,
             destruction_callback: rx,
             destruction_callback_sender: tx,
+            pbo_pool: BufferPool::default(),
             vbos,
             min_filter: TextureFilter::Linear,
             max_filter: TextureFilter::Linear,
             supports_instancing,
+            gpu_timer: None,
+            last_frame_timing: None,
+            linear_blending: false,
+            multi_tap_downscaling: false,
+            color_filter: None,
             logger_ptr,
             logger: log,
             _not_send: std::ptr::null_mut(),
@@ -721,6 +830,9 @@ impl Gles2Renderer {
     fn cleanup(&mut self) {
         #[cfg(feature = "wayland_frontend")]
         self.dmabuf_cache.retain(|entry, _tex| entry.upgrade().is_some());
+        #[cfg(feature = "wayland_frontend")]
+        self.shm_cache
+            .retain(|entry| entry.buffer.as_ref().is_alive() && entry.texture.strong_count() > 0);
         // Free outdated buffer resources
         // TODO: Replace with `drain_filter` once it lands
         let mut i = 0;
@@ -750,16 +862,117 @@ impl Gles2Renderer {
                 CleanupResource::RenderbufferObject(rbo) => unsafe {
                     self.gl.DeleteRenderbuffers(1, &rbo);
                 },
-                CleanupResource::Mapping(pbo, mapping) => unsafe {
+                CleanupResource::Mapping(pbo, capacity, mapping) => unsafe {
                     if !mapping.is_null() {
                         self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, pbo);
                         self.gl.UnmapBuffer(ffi::PIXEL_PACK_BUFFER);
                         self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, 0);
                     }
-                    self.gl.DeleteBuffers(1, &pbo);
+                    if let Some(pbo) = self.pbo_pool.release(pbo, capacity, Instant::now()) {
+                        self.gl.DeleteBuffers(1, &pbo);
+                    }
                 },
             }
         }
+        for pbo in self.pbo_pool.trim(Instant::now(), pool::MAX_IDLE) {
+            unsafe { self.gl.DeleteBuffers(1, &pbo) };
+        }
+    }
+
+    // Returns a pixel buffer object holding at least `len` bytes, together with its capacity
+    unsafe fn acquire_pbo(&mut self, len: usize) -> (ffi::types::GLuint, usize) {
+        let capacity = size_class(len);
+        if let Some(pbo) = self.pbo_pool.acquire(capacity) {
+            return (pbo, capacity);
+        }
+        let mut pbo = 0;
+        self.gl.GenBuffers(1, &mut pbo);
+        self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, pbo);
+        self.gl.BufferData(
+            ffi::PIXEL_PACK_BUFFER,
+            capacity as isize,
+            ptr::null(),
+            ffi::STREAM_READ,
+        );
+        self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, 0);
+        (pbo, capacity)
+    }
+
+    // Uploads a shm buffer or its damaged regions into the bound texture through the persistently
+    // mapped upload buffer, returns `false`, if the upload buffer could not be created
+    #[cfg(feature = "wayland_frontend")]
+    unsafe fn upload_shm_persistent(
+        &mut self,
+        data: &[u8],
+        stride: usize,
+        size: Size<i32, Buffer>,
+        full: bool,
+        damage: &[Rectangle<i32, Buffer>],
+        gl_format: ffi::types::GLenum,
+    ) -> bool {
+        let bounds = Rectangle::from_loc_and_size((0, 0), size);
+        let regions = if full {
+            vec![bounds]
+        } else {
+            damage
+                .iter()
+                .filter_map(|region| region.intersection(bounds))
+                .collect::<Vec<_>>()
+        };
+
+        let len = upload::packed_len(&regions);
+        if self
+            .shm_upload_buffer
+            .as_ref()
+            .map_or(true, |buffer| buffer.capacity() < len)
+        {
+            if let Some(buffer) = self.shm_upload_buffer.take() {
+                buffer.destroy(&self.gl);
+            }
+            self.shm_upload_buffer = UploadBuffer::new(&self.gl, len);
+        }
+        let buffer = match self.shm_upload_buffer.as_mut() {
+            Some(buffer) => buffer,
+            None => {
+                warn!(self.logger, "Failed to map the shm upload buffer");
+                return false;
+            }
+        };
+
+        let offsets = upload::pack_regions(data, stride, &regions, buffer.wait(&self.gl));
+        self.gl.BindBuffer(ffi::PIXEL_UNPACK_BUFFER, buffer.pbo);
+        // the regions are tightly packed
+        self.gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, 0);
+        for (region, offset) in regions.iter().zip(offsets) {
+            if full {
+                self.gl.TexImage2D(
+                    ffi::TEXTURE_2D,
+                    0,
+                    gl_format as i32,
+                    size.w,
+                    size.h,
+                    0,
+                    gl_format,
+                    ffi::UNSIGNED_BYTE as u32,
+                    offset as *const _,
+                );
+            } else {
+                self.gl.TexSubImage2D(
+                    ffi::TEXTURE_2D,
+                    0,
+                    region.loc.x,
+                    region.loc.y,
+                    region.size.w,
+                    region.size.h,
+                    gl_format,
+                    ffi::UNSIGNED_BYTE as u32,
+                    offset as *const _,
+                );
+            }
+        }
+        self.gl.BindBuffer(ffi::PIXEL_UNPACK_BUFFER, 0);
+        buffer.fence(&self.gl);
+        true
     }
 }
 
@@ -803,47 +1016,91 @@ impl ImportMemWl for Gles2Renderer {
             let mut upload_full = false;
 
             let id = self.id();
-            let texture = Gles2Texture(
+            let size = (width, height).into();
+            let cached = surface.and_then(|surface| {
                 surface
-                    .and_then(|surface| {
-                        surface
-                            .data_map
-                            .insert_if_missing(|| Rc::new(RefCell::new(CacheMap::new())));
-                        surface
-                            .data_map
-                            .get::<Rc<RefCell<CacheMap>>>()
-                            .unwrap()
-                            .borrow()
-                            .get(&id)
-                            .cloned()
-                    })
-                    .filter(|texture| texture.size == (width, height).into())
-                    .unwrap_or_else(|| {
-                        let mut tex = 0;
-                        unsafe { self.gl.GenTextures(1, &mut tex) };
-                        // new texture, upload in full
-                        upload_full = true;
-                        let new = Rc::new(Gles2TextureInternal {
-                            texture: tex,
-                            texture_kind: shader_idx,
-                            is_external: false,
-                            y_inverted: false,
-                            size: (width, height).into(),
-                            egl_images: None,
-                            destruction_callback_sender: self.destruction_callback_sender.clone(),
-                        });
-                        if let Some(surface) = surface {
-                            let copy = new.clone();
-                            surface
-                                .data_map
-                                .get::<Rc<RefCell<CacheMap>>>()
-                                .unwrap()
-                                .borrow_mut()
-                                .insert(id, copy);
-                        }
-                        new
-                    }),
-            );
+                    .data_map
+                    .insert_if_missing(|| Rc::new(RefCell::new(CacheMap::new())));
+                surface
+                    .data_map
+                    .get::<Rc<RefCell<CacheMap>>>()
+                    .unwrap()
+                    .borrow()
+                    .get(&id)
+                    .cloned()
+            });
+
+            // the same buffer might be attached to multiple surfaces, e.g. for wallpapers or cursors
+            let existing = self
+                .shm_cache
+                .iter_mut()
+                .find(|entry| entry.buffer == *buffer)
+                .and_then(|entry| {
+                    let texture = entry
+                        .texture
+                        .upgrade()
+                        .filter(|texture| texture.size == size && texture.texture_kind == shader_idx)?;
+                    if !matches!(cached, Some(ref cached) if Rc::ptr_eq(cached, &texture)) {
+                        entry.shared = true;
+                    }
+                    Some(texture)
+                });
+            if let Some(ref texture) = existing {
+                trace!(
+                    self.logger,
+                    "Re-using texture {:?} for {:?}",
+                    texture.texture,
+                    buffer
+                );
+            }
+
+            // a texture shared with other surfaces must not be overwritten with another buffer
+            let reusable = cached.filter(|texture| {
+                texture.size == size
+                    && !self
+                        .shm_cache
+                        .iter()
+                        .any(|entry| entry.shared && entry.texture.as_ptr() == Rc::as_ptr(texture))
+            });
+
+            let texture = Gles2Texture(existing.or(reusable).unwrap_or_else(|| {
+                let mut tex = 0;
+                unsafe { self.gl.GenTextures(1, &mut tex) };
+                // new texture, upload in full
+                upload_full = true;
+                let new = Rc::new(Gles2TextureInternal {
+                    texture: tex,
+                    texture_kind: shader_idx,
+                    is_external: false,
+                    y_inverted: false,
+                    size: (width, height).into(),
+                    egl_images: None,
+                    destruction_callback_sender: self.destruction_callback_sender.clone(),
+                });
+                if let Some(surface) = surface {
+                    let copy = new.clone();
+                    surface
+                        .data_map
+                        .get::<Rc<RefCell<CacheMap>>>()
+                        .unwrap()
+                        .borrow_mut()
+                        .insert(id, copy);
+                }
+                new
+            }));
+            if !self
+                .shm_cache
+                .iter()
+                .any(|entry| entry.buffer == *buffer && entry.texture.as_ptr() == Rc::as_ptr(&texture.0))
+            {
+                self.shm_cache
+                    .retain(|entry| entry.texture.as_ptr() != Rc::as_ptr(&texture.0));
+                self.shm_cache.push(ShmCacheEntry {
+                    buffer: buffer.clone(),
+                    texture: Rc::downgrade(&texture.0),
+                    shared: false,
+                });
+            }
 
             unsafe {
                 self.gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
@@ -853,7 +1110,23 @@ impl ImportMemWl for Gles2Renderer {
                     .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
                 self.gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, stride / pixelsize);
 
-                if upload_full || damage.is_empty() {
+                let full = upload_full || damage.is_empty();
+                if self.persistent_shm_upload
+                    && self.upload_shm_persistent(
+                        &slice[offset as usize..],
+                        stride as usize,
+                        size,
+                        full,
+                        damage,
+                        gl_format,
+                    )
+                {
+                    trace!(
+                        self.logger,
+                        "Uploaded shm texture for {:?} through the upload buffer",
+                        buffer
+                    );
+                } else if full {
                     trace!(self.logger, "Uploading shm texture for {:?}", buffer);
                     self.gl.TexImage2D(
                         ffi::TEXTURE_2D,
@@ -1176,13 +1449,9 @@ impl ExportMem for Gles2Renderer {
         region: Rectangle<i32, Buffer>,
     ) -> Result<Self::TextureMapping, Self::Error> {
         self.make_current()?;
-        let mut pbo = 0;
+        let (pbo, capacity) = unsafe { self.acquire_pbo((region.size.w * region.size.h * 4) as usize) };
         unsafe {
-            self.gl.GenBuffers(1, &mut pbo);
             self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, pbo);
-            let size = (region.size.w * region.size.h * 4) as isize;
-            self.gl
-                .BufferData(ffi::PIXEL_PACK_BUFFER, size, ptr::null(), ffi::STREAM_READ);
             self.gl.ReadBuffer(ffi::COLOR_ATTACHMENT0);
             self.gl.ReadPixels(
                 region.loc.x,
@@ -1198,6 +1467,7 @@ impl ExportMem for Gles2Renderer {
         }
         Ok(Gles2Mapping {
             pbo,
+            capacity,
             size: region.size,
             mapping: AtomicPtr::new(ptr::null_mut()),
             destruction_callback_sender: self.destruction_callback_sender.clone(),
@@ -1209,19 +1479,12 @@ impl ExportMem for Gles2Renderer {
         texture: &Self::TextureId,
         region: Rectangle<i32, Buffer>,
     ) -> Result<Self::TextureMapping, Self::Error> {
-        let mut pbo = 0;
         let old_target = self.target.take();
         self.bind(texture.clone())?;
 
+        let (pbo, capacity) = unsafe { self.acquire_pbo((region.size.w * region.size.h * 4) as usize) };
         unsafe {
-            self.gl.GenBuffers(1, &mut pbo);
             self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, pbo);
-            self.gl.BufferData(
-                ffi::PIXEL_PACK_BUFFER,
-                (region.size.w * region.size.h * 4) as isize,
-                ptr::null(),
-                ffi::STREAM_READ,
-            );
             self.gl.ReadBuffer(ffi::COLOR_ATTACHMENT0);
             self.gl.ReadPixels(
                 region.loc.x,
@@ -1243,6 +1506,7 @@ impl ExportMem for Gles2Renderer {
 
         Ok(Gles2Mapping {
             pbo,
+            capacity,
             size: region.size,
             mapping: AtomicPtr::new(ptr::null_mut()),
             destruction_callback_sender: self.destruction_callback_sender.clone(),
@@ -1447,7 +1711,7 @@ impl Bind<Dmabuf> for Gles2Renderer {
             .iter()
             .find(|buffer| {
                 if let Some(dma) = buffer.dmabuf.upgrade() {
-                    dma == dmabuf
+                    dma == dmabuf && buffer.srgb == self.linear_blending
                 } else {
                     false
                 }
@@ -1455,11 +1719,12 @@ impl Bind<Dmabuf> for Gles2Renderer {
             .map(|buf| Ok((buf.clone(), buf.dmabuf.upgrade().unwrap())))
             .unwrap_or_else(|| {
                 trace!(self.logger, "Creating EGLImage for Dmabuf: {:?}", dmabuf);
-                let image = self
-                    .egl
-                    .display
-                    .create_image_from_dmabuf(&dmabuf)
-                    .map_err(Gles2Error::BindBufferEGLError)?;
+                let image = if self.linear_blending {
+                    self.egl.display.create_srgb_image_from_dmabuf(&dmabuf)
+                } else {
+                    self.egl.display.create_image_from_dmabuf(&dmabuf)
+                }
+                .map_err(Gles2Error::BindBufferEGLError)?;
 
                 unsafe {
                     let mut rbo = 0;
@@ -1491,6 +1756,7 @@ impl Bind<Dmabuf> for Gles2Renderer {
                         image,
                         rbo,
                         fbo,
+                        srgb: self.linear_blending,
                     };
 
                     self.buffers.push(buf.clone());
@@ -1647,6 +1913,16 @@ impl Drop for Gles2Renderer {
                 }
                 self.gl.DeleteProgram(self.solid_program.program);
                 self.gl.DeleteBuffers(self.vbos.len() as i32, self.vbos.as_ptr());
+                for pbo in self.pbo_pool.drain() {
+                    self.gl.DeleteBuffers(1, &pbo);
+                }
+                if let Some(mut timer) = self.gpu_timer.take() {
+                    timer.destroy(&self.gl);
+                }
+                #[cfg(feature = "wayland_frontend")]
+                if let Some(buffer) = self.shm_upload_buffer.take() {
+                    buffer.destroy(&self.gl);
+                }
 
                 if self.extensions.iter().any(|ext| ext == "GL_KHR_debug") {
                     self.gl.Disable(ffi::DEBUG_OUTPUT);
@@ -1692,6 +1968,171 @@ impl Gles2Renderer {
         let gl = self.gl.clone();
         Ok(func(self, &gl))
     }
+
+    /// Returns if the GL implementation supports measuring GPU times via [`Gles2Renderer::set_gpu_timing`]
+    pub fn supports_gpu_timing(&self) -> bool {
+        self.extensions
+            .iter()
+            .any(|ext| ext == "GL_EXT_disjoint_timer_query")
+    }
+
+    /// Enables or disables measuring the GPU time of rendered frames
+    ///
+    /// While enabled, the GPU time spent on every frame and every section started with
+    /// [`Frame::begin_timing_section`] can be queried after [`Renderer::render`] via
+    /// [`Gles2Renderer::last_frame_timing`]. Use a [`RenderStats`] to aggregate them.
+    ///
+    /// Timer queries add a small overhead, so this is meant for debugging and profiling.
+    ///
+    /// Returns [`Gles2Error::GLExtensionNotSupported`], if `GL_EXT_disjoint_timer_query` is not supported.
+    pub fn set_gpu_timing(&mut self, enabled: bool) -> Result<(), Gles2Error> {
+        if enabled {
+            if !self.supports_gpu_timing() {
+                return Err(Gles2Error::GLExtensionNotSupported(&[
+                    "GL_EXT_disjoint_timer_query",
+                ]));
+            }
+            if self.gpu_timer.is_none() {
+                self.gpu_timer = Some(GpuTimer::default());
+            }
+        } else if let Some(mut timer) = self.gpu_timer.take() {
+            self.make_current()?;
+            unsafe { timer.destroy(&self.gl) };
+            self.last_frame_timing = None;
+        }
+        Ok(())
+    }
+
+    /// Returns the GPU timings of the last frame rendered with GPU timing enabled
+    ///
+    /// This is `None` if the measurements of the last frame were invalidated,
+    /// e.g. because the GPU changed its frequency while rendering.
+    pub fn last_frame_timing(&self) -> Option<&FrameTiming> {
+        self.last_frame_timing.as_ref()
+    }
+
+    /// Returns if the implementation supports blending in linear space via [`Gles2Renderer::set_linear_blending`]
+    pub fn supports_linear_blending(&self) -> bool {
+        self.egl
+            .display
+            .extensions
+            .iter()
+            .any(|ext| ext == "EGL_EXT_image_gl_colorspace")
+            && (self.gl_version >= version::GLES_3_0
+                || self.extensions.iter().any(|ext| ext == "GL_EXT_sRGB"))
+    }
+
+    /// Enables or disables blending in linear space
+    ///
+    /// Blending sRGB encoded colors directly darkens semi-transparent edges, which is most visible
+    /// as dark fringes around antialiased text. With linear blending, [`Dmabuf`]s bound afterwards
+    /// are rendered to as sRGB framebuffers and textures are converted to linear space while
+    /// sampling, which costs additional bandwidth. As the renderer is usually shared between outputs,
+    /// enable it before rendering the outputs that should use it and disable it for the others.
+    ///
+    /// Other targets, like [`EGLSurface`]s, always blend sRGB encoded colors.
+    ///
+    /// Returns [`Gles2Error::EGLExtensionNotSupported`], if `EGL_EXT_image_gl_colorspace` is not supported
+    /// or [`Gles2Error::GLExtensionNotSupported`], if sRGB framebuffers are not supported.
+    pub fn set_linear_blending(&mut self, enabled: bool) -> Result<(), Gles2Error> {
+        if enabled && !self.supports_linear_blending() {
+            if !self
+                .egl
+                .display
+                .extensions
+                .iter()
+                .any(|ext| ext == "EGL_EXT_image_gl_colorspace")
+            {
+                return Err(Gles2Error::EGLExtensionNotSupported(&[
+                    "EGL_EXT_image_gl_colorspace",
+                ]));
+            }
+            return Err(Gles2Error::GLExtensionNotSupported(&["GL_EXT_sRGB"]));
+        }
+        self.linear_blending = enabled;
+        Ok(())
+    }
+
+    /// Enables or disables high-quality downscaling of textures
+    ///
+    /// Bilinear filtering only samples the four texels closest to the center of a pixel, so textures
+    /// downscaled by more than a factor of two skip texels, which makes moving content shimmer.
+    /// With multi-tap downscaling, [`Frame::render_texture_from_to`] instead averages a grid of samples
+    /// covering the whole pixel in linear light, which is meant for thumbnails and scaled down previews
+    /// of outputs. This is considerably more expensive, so enable it only while rendering them.
+    ///
+    /// Textures rendered with [`Gles2Frame::render_texture`] or not downscaled are not affected.
+    pub fn set_multi_tap_downscaling(&mut self, enabled: bool) {
+        self.multi_tap_downscaling = enabled;
+    }
+
+    /// Sets the color filter applied to everything rendered afterwards, `None` by default
+    ///
+    /// Filters are applied while drawing and clearing, which gives the same result as filtering
+    /// the finished frame, without an additional render pass. Without a filter no additional work is done.
+    /// As the renderer is usually shared between outputs, set the filter of each output before
+    /// rendering it.
+    ///
+    /// Damage tracking does not know about filters, so render the whole output once after changing
+    /// its filter, e.g. by passing a buffer age of `0` to `Space::render_output`.
+    pub fn set_color_filter(&mut self, filter: Option<ColorFilter>) {
+        self.color_filter = filter;
+    }
+
+    /// Returns the color filter applied to rendered frames
+    pub fn color_filter(&self) -> Option<ColorFilter> {
+        self.color_filter
+    }
+
+    /// Returns if the implementation supports uploading shm buffers via [`Gles2Renderer::set_persistent_shm_upload`]
+    #[cfg(feature = "wayland_frontend")]
+    pub fn supports_persistent_shm_upload(&self) -> bool {
+        self.gl_version >= version::GLES_3_0
+            && self.extensions.iter().any(|ext| ext == "GL_EXT_buffer_storage")
+    }
+
+    /// Enables or disables uploading shm buffers through a persistently mapped pixel buffer
+    ///
+    /// Clients rendering in software, like some browsers, damage large regions of their shm buffers
+    /// every frame. By default these regions are passed to the driver directly, which copies them
+    /// into staging memory before uploading them to the texture. With this enabled, the damaged
+    /// regions are instead copied into a buffer mapped for the lifetime of the renderer, from which the
+    /// GPU uploads them asynchronously, avoiding the copy and the stall of the driver.
+    /// The buffer grows to the largest damage uploaded at once.
+    ///
+    /// Returns [`Gles2Error::GLExtensionNotSupported`], if `GL_EXT_buffer_storage` is not supported.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn set_persistent_shm_upload(&mut self, enabled: bool) -> Result<(), Gles2Error> {
+        if enabled && !self.supports_persistent_shm_upload() {
+            return Err(Gles2Error::GLExtensionNotSupported(&["GL_EXT_buffer_storage"]));
+        }
+        if !enabled {
+            if let Some(buffer) = self.shm_upload_buffer.take() {
+                self.make_current()?;
+                unsafe { buffer.destroy(&self.gl) };
+            }
+        }
+        self.persistent_shm_upload = enabled;
+        Ok(())
+    }
+}
+
+/// Converts a sRGB encoded color with premultiplied alpha to linear space
+fn srgb_to_linear(color: [f32; 4]) -> [f32; 4] {
+    let alpha = color[3];
+    if alpha <= 0.0 {
+        return color;
+    }
+    let convert = |c: f32| {
+        let c = c / alpha;
+        let c = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+        c * alpha
+    };
+    [convert(color[0]), convert(color[1]), convert(color[2]), alpha]
 }
 
 impl Renderer for Gles2Renderer {
@@ -1753,6 +2194,11 @@ impl Renderer for Gles2Renderer {
         // We account for OpenGLs coordinate system here
         let flip180 = Matrix3::new(1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0);
 
+        let mut gpu_timer = self.gpu_timer.take();
+        if let Some(timer) = gpu_timer.as_mut() {
+            unsafe { timer.begin_frame(&self.gl) };
+        }
+
         let mut frame = Gles2Frame {
             gl: self.gl.clone(),
             tex_programs: self.tex_programs.clone(),
@@ -1765,11 +2211,20 @@ impl Renderer for Gles2Renderer {
             min_filter: self.min_filter,
             max_filter: self.max_filter,
             supports_instancing: self.supports_instancing,
+            gpu_timer,
+            linear_blending: matches!(self.target, Some(Gles2Target::Image { ref buf, .. }) if buf.srgb),
+            multi_tap_downscaling: self.multi_tap_downscaling,
+            color_filter: self.color_filter,
+            tex_step: [0.0, 0.0],
         };
 
         let result = rendering(self, &mut frame);
 
+        let mut gpu_timer = frame.gpu_timer.take();
         unsafe {
+            if let Some(timer) = gpu_timer.as_mut() {
+                timer.end_frame(&self.gl);
+            }
             self.gl.Flush();
             // We need to wait for the previously submitted GL commands to complete
             // or otherwise the buffer could be submitted to the drm surface while
@@ -1787,7 +2242,13 @@ impl Renderer for Gles2Renderer {
             // https://gitlab.freedesktop.org/mesa/kmscube/-/blob/9f63f359fab1b5d8e862508e4e51c9dfe339ccb0/drm-atomic.c#L235
             self.gl.Finish();
             self.gl.Disable(ffi::BLEND);
+
+            // The queries are finished as well at this point, so this does not block
+            if let Some(timer) = gpu_timer.as_mut() {
+                self.last_frame_timing = timer.collect(&self.gl);
+            }
         }
+        self.gpu_timer = gpu_timer;
 
         Ok(result)
     }
@@ -1866,6 +2327,16 @@ impl Frame for Gles2Frame {
             })
             .collect::<Vec<ffi::types::GLfloat>>();
 
+        let color = if self.linear_blending {
+            srgb_to_linear(color)
+        } else {
+            color
+        };
+        let color = match self.color_filter {
+            Some(filter) => filter.apply(color),
+            None => color,
+        };
+
         unsafe {
             self.gl.Disable(ffi::BLEND);
             self.gl.UseProgram(self.solid_program.program);
@@ -2042,12 +2513,41 @@ impl Frame for Gles2Frame {
             })
             .collect::<Vec<_>>();
 
-        self.render_texture(texture, tex_mat, mat, Some(&instances), alpha)
+        if self.multi_tap_downscaling {
+            // texels covered by a pixel along the axes of the texture
+            let dest_size = transform.transform_size(dest.size);
+            let footprint = (src_size.w / dest_size.w, src_size.h / dest_size.h);
+            // spread 4 samples per axis over the footprint, each sample averages 2 texels already
+            let step = |footprint: f64, tex_size: f64| {
+                if footprint > 1.0 {
+                    (footprint / 4.0 / tex_size) as f32
+                } else {
+                    0.0
+                }
+            };
+            self.tex_step = [step(footprint.0, tex_size.w), step(footprint.1, tex_size.h)];
+        }
+        let result = self.render_texture(texture, tex_mat, mat, Some(&instances), alpha);
+        self.tex_step = [0.0, 0.0];
+        result
     }
 
     fn transformation(&self) -> Transform {
         self.transform
     }
+
+    /// Does nothing, if GPU timing is not enabled via [`Gles2Renderer::set_gpu_timing`].
+    fn begin_timing_section(&mut self, label: &str) {
+        if let Some(timer) = self.gpu_timer.as_mut() {
+            unsafe { timer.begin_section(&self.gl, label.to_string()) };
+        }
+    }
+
+    fn end_timing_section(&mut self) {
+        if let Some(timer) = self.gpu_timer.as_mut() {
+            unsafe { timer.end_section(&self.gl) };
+        }
+    }
 }
 
 impl Gles2Frame {
@@ -2130,6 +2630,34 @@ impl Gles2Frame {
             );
             self.gl
                 .Uniform1f(self.tex_programs[tex.0.texture_kind].uniform_alpha, alpha);
+            self.gl.Uniform1i(
+                self.tex_programs[tex.0.texture_kind].uniform_linear_blending,
+                self.linear_blending as i32,
+            );
+            self.gl.Uniform2f(
+                self.tex_programs[tex.0.texture_kind].uniform_tex_step,
+                self.tex_step[0],
+                self.tex_step[1],
+            );
+            self.gl.Uniform1i(
+                self.tex_programs[tex.0.texture_kind].uniform_color_filter,
+                self.color_filter.is_some() as i32,
+            );
+            if let Some(filter) = self.color_filter {
+                let (filter_matrix, offset) = filter.matrix();
+                self.gl.UniformMatrix3fv(
+                    self.tex_programs[tex.0.texture_kind].uniform_filter_matrix,
+                    1,
+                    ffi::FALSE,
+                    filter_matrix.as_ptr(),
+                );
+                self.gl.Uniform3f(
+                    self.tex_programs[tex.0.texture_kind].uniform_filter_offset,
+                    offset[0],
+                    offset[1],
+                    offset[2],
+                );
+            }
 
             self.gl
                 .EnableVertexAttribArray(self.tex_programs[tex.0.texture_kind].attrib_vert as u32);
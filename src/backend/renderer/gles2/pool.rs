@@ -0,0 +1,136 @@
+//! Recycling of the pixel buffer objects used to download textures and framebuffers
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Smallest size class, in bytes
+const MIN_SIZE_CLASS: usize = 64 * 1024;
+/// Number of free buffers kept per size class
+const MAX_FREE_PER_CLASS: usize = 4;
+/// Time after which unused buffers are freed
+pub(super) const MAX_IDLE: Duration = Duration::from_secs(2);
+
+/// Returns the size class of a buffer of `len` bytes, the capacity it is allocated with
+///
+/// Capacities are rounded up to powers of two, so buffers can be reused for slightly different
+/// sizes, e.g. of damage rectangles, at the cost of at most twice the memory.
+pub(super) fn size_class(len: usize) -> usize {
+    len.max(MIN_SIZE_CLASS).next_power_of_two()
+}
+
+#[derive(Debug)]
+struct FreeBuffer<T> {
+    buffer: T,
+    released: Instant,
+}
+
+/// Pool of free buffers binned by size class
+#[derive(Debug)]
+pub(super) struct BufferPool<T> {
+    free: HashMap<usize, Vec<FreeBuffer<T>>>,
+}
+
+impl<T> Default for BufferPool<T> {
+    fn default() -> Self {
+        BufferPool { free: HashMap::new() }
+    }
+}
+
+impl<T> BufferPool<T> {
+    /// Takes a free buffer of the given size class, the most recently released one first
+    pub(super) fn acquire(&mut self, class: usize) -> Option<T> {
+        self.free
+            .get_mut(&class)
+            .and_then(|free| free.pop())
+            .map(|free| free.buffer)
+    }
+
+    /// Puts a buffer back into the pool
+    ///
+    /// Returns the buffer, if its size class already holds enough free buffers and it has to be freed.
+    pub(super) fn release(&mut self, buffer: T, class: usize, now: Instant) -> Option<T> {
+        let free = self.free.entry(class).or_default();
+        if free.len() >= MAX_FREE_PER_CLASS {
+            return Some(buffer);
+        }
+        free.push(FreeBuffer {
+            buffer,
+            released: now,
+        });
+        None
+    }
+
+    /// Removes the buffers, that have been unused for longer than `max_idle`, to be freed
+    pub(super) fn trim(&mut self, now: Instant, max_idle: Duration) -> Vec<T> {
+        let mut trimmed = Vec::new();
+        for free in self.free.values_mut() {
+            // buffers are released in order, so the idle ones are at the front
+            let idle = free
+                .iter()
+                .take_while(|free| now.saturating_duration_since(free.released) > max_idle)
+                .count();
+            trimmed.extend(free.drain(..idle).map(|free| free.buffer));
+        }
+        self.free.retain(|_, free| !free.is_empty());
+        trimmed
+    }
+
+    /// Removes all buffers to be freed
+    pub(super) fn drain(&mut self) -> Vec<T> {
+        self.free
+            .drain()
+            .flat_map(|(_, free)| free.into_iter().map(|free| free.buffer))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_classes() {
+        assert_eq!(size_class(1), MIN_SIZE_CLASS);
+        assert_eq!(size_class(MIN_SIZE_CLASS + 1), MIN_SIZE_CLASS * 2);
+        // a 1920x1080 RGBA download
+        assert_eq!(size_class(1920 * 1080 * 4), 8 * 1024 * 1024);
+    }
+
+    #[test]
+    fn reuse_by_class() {
+        let now = Instant::now();
+        let mut pool = BufferPool::default();
+        assert_eq!(pool.release(1, size_class(100), now), None);
+        assert_eq!(pool.release(2, size_class(1_000_000), now), None);
+        assert_eq!(pool.acquire(size_class(200)), Some(1));
+        assert_eq!(pool.acquire(size_class(200)), None);
+        assert_eq!(pool.acquire(size_class(1_000_000)), Some(2));
+    }
+
+    #[test]
+    fn bounded_free_list() {
+        let now = Instant::now();
+        let mut pool = BufferPool::default();
+        for buffer in 0..MAX_FREE_PER_CLASS {
+            assert_eq!(pool.release(buffer, MIN_SIZE_CLASS, now), None);
+        }
+        assert_eq!(pool.release(42, MIN_SIZE_CLASS, now), Some(42));
+        assert_eq!(pool.drain().len(), MAX_FREE_PER_CLASS);
+    }
+
+    #[test]
+    fn trim_idle_buffers() {
+        let start = Instant::now();
+        let mut pool = BufferPool::default();
+        pool.release(1, MIN_SIZE_CLASS, start);
+        pool.release(2, MIN_SIZE_CLASS, start + Duration::from_secs(2));
+        pool.release(3, MIN_SIZE_CLASS * 2, start);
+
+        let trimmed = pool.trim(start + Duration::from_secs(3), MAX_IDLE);
+        assert_eq!(trimmed.len(), 2);
+        assert!(trimmed.contains(&1) && trimmed.contains(&3));
+        assert_eq!(pool.acquire(MIN_SIZE_CLASS), Some(2));
+    }
+}
@@ -0,0 +1,281 @@
+//! Caching of linked GL programs as program binaries
+//!
+//! Binaries of the programs of a [`Gles2Renderer`](super::Gles2Renderer) are kept in memory
+//! for later renderers of the process and, if a directory was set with
+//! [`set_program_cache_dir`], written to disk for later runs of the compositor.
+//! They are only reused for the same GL renderer and version strings, and programs are
+//! compiled from source again, if the driver rejects a cached binary.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    convert::TryInto,
+    ffi::CStr,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    os::raw::c_char,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use slog::{debug, trace, warn};
+
+use super::{ffi, version};
+
+lazy_static::lazy_static! {
+    static ref PROGRAM_BINARIES: Mutex<HashMap<Vec<u8>, ProgramBinary>> = Mutex::new(HashMap::new());
+    static ref CACHE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+const MAGIC: &[u8; 4] = b"SMPB";
+
+/// Sets the directory binaries of GL programs are stored in across runs of the compositor
+///
+/// Persistence is disabled by default, pass [`default_program_cache_dir`] to store them
+/// in the cache directory of the user, or `None` to disable it again.
+/// Only renderers created afterwards use the directory.
+pub fn set_program_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.lock().unwrap() = dir;
+}
+
+/// Returns `$XDG_CACHE_HOME/smithay/gles2`, or `$HOME/.cache/smithay/gles2` if it is unset
+pub fn default_program_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|dir| dir.join("smithay").join("gles2"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ProgramBinary {
+    format: ffi::types::GLenum,
+    data: Vec<u8>,
+}
+
+fn encode(key: &[u8], binary: &ProgramBinary) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(12 + key.len() + binary.data.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(key);
+    bytes.extend_from_slice(&binary.format.to_le_bytes());
+    bytes.extend_from_slice(&binary.data);
+    bytes
+}
+
+fn decode(key: &[u8], bytes: &[u8]) -> Option<ProgramBinary> {
+    let bytes = bytes.strip_prefix(&MAGIC[..])?;
+    let (key_len, bytes) = split_u32(bytes)?;
+    // file names are hashes, so the key of the file has to match exactly
+    let bytes = bytes
+        .strip_prefix(key)
+        .filter(|_| key_len as usize == key.len())?;
+    let (format, data) = split_u32(bytes)?;
+    Some(ProgramBinary {
+        format,
+        data: data.to_vec(),
+    })
+}
+
+fn split_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let value = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+    Some((value, &bytes[4..]))
+}
+
+/// Loads and stores program binaries for a GL context
+#[derive(Debug)]
+pub(super) struct ProgramCache {
+    oes: bool,
+    driver: String,
+    dir: Option<PathBuf>,
+    logger: slog::Logger,
+}
+
+impl ProgramCache {
+    /// Returns `None`, if the context does not support program binaries
+    pub(super) unsafe fn new(
+        gl: &ffi::Gles2,
+        gl_version: version::GlVersion,
+        exts: &[String],
+        logger: &slog::Logger,
+    ) -> Option<ProgramCache> {
+        let oes = if gl_version >= version::GLES_3_0 {
+            false
+        } else if exts.iter().any(|ext| ext == "GL_OES_get_program_binary") {
+            true
+        } else {
+            return None;
+        };
+
+        let mut formats = 0;
+        gl.GetIntegerv(ffi::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        if formats == 0 {
+            debug!(logger, "Program binaries are not supported by the driver");
+            return None;
+        }
+
+        let renderer = CStr::from_ptr(gl.GetString(ffi::RENDERER) as *const c_char);
+        let version = CStr::from_ptr(gl.GetString(ffi::VERSION) as *const c_char);
+        Some(ProgramCache {
+            oes,
+            driver: format!("{}\n{}", renderer.to_string_lossy(), version.to_string_lossy()),
+            dir: CACHE_DIR.lock().unwrap().clone(),
+            logger: logger.clone(),
+        })
+    }
+
+    fn key(&self, vert_src: &str, frag_src: &str) -> Vec<u8> {
+        [self.driver.as_bytes(), vert_src.as_bytes(), frag_src.as_bytes()].join(&0)
+    }
+
+    fn path(&self, key: &[u8]) -> Option<PathBuf> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        Some(self.dir.as_ref()?.join(format!("{:016x}.bin", hasher.finish())))
+    }
+
+    fn read(&self, key: &[u8]) -> Option<ProgramBinary> {
+        if let Some(binary) = PROGRAM_BINARIES.lock().unwrap().get(key) {
+            return Some(binary.clone());
+        }
+        let bytes = fs::read(self.path(key)?).ok()?;
+        decode(key, &bytes)
+    }
+
+    fn write(&self, key: Vec<u8>, binary: ProgramBinary) {
+        if let Some(path) = self.path(&key) {
+            let write = || -> io::Result<()> {
+                fs::create_dir_all(path.parent().unwrap())?;
+                // write to a temporary file first, so other compositors never read partial files
+                let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+                fs::write(&tmp, encode(&key, &binary))?;
+                fs::rename(&tmp, &path)
+            };
+            if let Err(err) = write() {
+                warn!(
+                    self.logger,
+                    "Failed to store program binary in {:?}: {}", path, err
+                );
+            }
+        }
+        PROGRAM_BINARIES.lock().unwrap().insert(key, binary);
+    }
+
+    /// Creates a program from a cached binary, if there is one and the driver accepts it
+    pub(super) unsafe fn load(
+        &self,
+        gl: &ffi::Gles2,
+        vert_src: &str,
+        frag_src: &str,
+    ) -> Option<ffi::types::GLuint> {
+        let key = self.key(vert_src, frag_src);
+        let binary = self.read(&key)?;
+
+        let program = gl.CreateProgram();
+        if self.oes {
+            gl.ProgramBinaryOES(
+                program,
+                binary.format,
+                binary.data.as_ptr() as *const _,
+                binary.data.len() as i32,
+            );
+        } else {
+            gl.ProgramBinary(
+                program,
+                binary.format,
+                binary.data.as_ptr() as *const _,
+                binary.data.len() as i32,
+            );
+        }
+
+        let mut status = ffi::FALSE as i32;
+        gl.GetProgramiv(program, ffi::LINK_STATUS, &mut status as *mut _);
+        if status == ffi::FALSE as i32 {
+            // e.g. after a driver update without a change of the version string
+            debug!(self.logger, "Cached program binary was rejected, recompiling");
+            gl.DeleteProgram(program);
+            PROGRAM_BINARIES.lock().unwrap().remove(&key);
+            return None;
+        }
+
+        trace!(self.logger, "Loaded program from a cached binary");
+        Some(program)
+    }
+
+    /// Marks a program, that is about to be linked, to have its binary retrieved later
+    pub(super) unsafe fn prepare(&self, gl: &ffi::Gles2, program: ffi::types::GLuint) {
+        if !self.oes {
+            gl.ProgramParameteri(program, ffi::PROGRAM_BINARY_RETRIEVABLE_HINT, ffi::TRUE as i32);
+        }
+    }
+
+    /// Stores the binary of a linked program
+    pub(super) unsafe fn store(
+        &self,
+        gl: &ffi::Gles2,
+        program: ffi::types::GLuint,
+        vert_src: &str,
+        frag_src: &str,
+    ) {
+        let mut len = 0;
+        gl.GetProgramiv(program, ffi::PROGRAM_BINARY_LENGTH, &mut len);
+        if len <= 0 {
+            return;
+        }
+
+        let mut data = vec![0u8; len as usize];
+        let mut written = 0;
+        let mut format = 0;
+        if self.oes {
+            gl.GetProgramBinaryOES(
+                program,
+                len,
+                &mut written,
+                &mut format,
+                data.as_mut_ptr() as *mut _,
+            );
+        } else {
+            gl.GetProgramBinary(
+                program,
+                len,
+                &mut written,
+                &mut format,
+                data.as_mut_ptr() as *mut _,
+            );
+        }
+        if written <= 0 {
+            return;
+        }
+        data.truncate(written as usize);
+
+        self.write(self.key(vert_src, frag_src), ProgramBinary { format, data });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_roundtrip() {
+        let binary = ProgramBinary {
+            format: 0x1234,
+            data: vec![1, 2, 3, 4, 5],
+        };
+        let bytes = encode(b"key", &binary);
+        assert_eq!(decode(b"key", &bytes), Some(binary));
+    }
+
+    #[test]
+    fn rejects_other_keys_and_truncated_files() {
+        let binary = ProgramBinary {
+            format: 1,
+            data: vec![42; 16],
+        };
+        let bytes = encode(b"driver\0vert\0frag", &binary);
+        assert_eq!(decode(b"driver\0vert\0frog", &bytes), None);
+        assert_eq!(decode(b"driver", &bytes), None);
+        assert_eq!(decode(b"driver\0vert\0frag", &bytes[..10]), None);
+        assert_eq!(decode(b"", b"SMP"), None);
+    }
+}
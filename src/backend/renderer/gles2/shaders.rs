@@ -35,10 +35,54 @@ pub const FRAGMENT_SHADER_ABGR: &str = r#"
 precision mediump float;
 uniform sampler2D tex;
 uniform float alpha;
+uniform bool linear_blending;
+uniform vec2 tex_step;
+uniform bool color_filter;
+uniform mat3 filter_matrix;
+uniform vec3 filter_offset;
 varying vec2 v_tex_coords;
 
+// converts sRGB encoded colors to linear light, for blending in an sRGB framebuffer
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(vec3(0.04045), color));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), color));
+}
+
+vec4 premultiplied_to_linear(vec4 color) {
+    if (color.a > 0.0) {
+        color.rgb = srgb_to_linear(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
 void main() {
-    gl_FragColor = texture2D(tex, v_tex_coords) * alpha;
+    vec4 color;
+    if (tex_step == vec2(0.0)) {
+        color = texture2D(tex, v_tex_coords);
+        if (linear_blending) {
+            color = premultiplied_to_linear(color);
+        }
+    } else {
+        // downscaling: average a grid of samples covering the pixel in linear light
+        color = vec4(0.0);
+        for (int x = 0; x < 4; x++) {
+            for (int y = 0; y < 4; y++) {
+                vec2 offset = (vec2(float(x), float(y)) - 1.5) * tex_step;
+                color += premultiplied_to_linear(texture2D(tex, v_tex_coords + offset));
+            }
+        }
+        color /= 16.0;
+        if (!linear_blending && color.a > 0.0) {
+            color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+        }
+    }
+    if (color_filter) {
+        color.rgb = clamp(filter_matrix * color.rgb + filter_offset * color.a, 0.0, color.a);
+    }
+    gl_FragColor = color * alpha;
 }
 "#;
 
@@ -48,10 +92,47 @@ pub const FRAGMENT_SHADER_XBGR: &str = r#"
 precision mediump float;
 uniform sampler2D tex;
 uniform float alpha;
+uniform bool linear_blending;
+uniform vec2 tex_step;
+uniform bool color_filter;
+uniform mat3 filter_matrix;
+uniform vec3 filter_offset;
 varying vec2 v_tex_coords;
 
+// converts sRGB encoded colors to linear light, for blending in an sRGB framebuffer
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(vec3(0.04045), color));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), color));
+}
+
 void main() {
-    gl_FragColor = vec4(texture2D(tex, v_tex_coords).rgb, 1.0) * alpha;
+    vec3 color;
+    if (tex_step == vec2(0.0)) {
+        color = texture2D(tex, v_tex_coords).rgb;
+        if (linear_blending) {
+            color = srgb_to_linear(color);
+        }
+    } else {
+        // downscaling: average a grid of samples covering the pixel in linear light
+        color = vec3(0.0);
+        for (int x = 0; x < 4; x++) {
+            for (int y = 0; y < 4; y++) {
+                vec2 offset = (vec2(float(x), float(y)) - 1.5) * tex_step;
+                color += srgb_to_linear(texture2D(tex, v_tex_coords + offset).rgb);
+            }
+        }
+        color /= 16.0;
+        if (!linear_blending) {
+            color = linear_to_srgb(color);
+        }
+    }
+    if (color_filter) {
+        color = clamp(filter_matrix * color + filter_offset, 0.0, 1.0);
+    }
+    gl_FragColor = vec4(color, 1.0) * alpha;
 }
 "#;
 
@@ -62,10 +143,54 @@ pub const FRAGMENT_SHADER_EXTERNAL: &str = r#"
 precision mediump float;
 uniform samplerExternalOES tex;
 uniform float alpha;
+uniform bool linear_blending;
+uniform vec2 tex_step;
+uniform bool color_filter;
+uniform mat3 filter_matrix;
+uniform vec3 filter_offset;
 varying vec2 v_tex_coords;
 
+// converts sRGB encoded colors to linear light, for blending in an sRGB framebuffer
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(vec3(0.04045), color));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), color));
+}
+
+vec4 premultiplied_to_linear(vec4 color) {
+    if (color.a > 0.0) {
+        color.rgb = srgb_to_linear(color.rgb / color.a) * color.a;
+    }
+    return color;
+}
+
 void main() {
-    gl_FragColor = texture2D(tex, v_tex_coords) * alpha;
+    vec4 color;
+    if (tex_step == vec2(0.0)) {
+        color = texture2D(tex, v_tex_coords);
+        if (linear_blending) {
+            color = premultiplied_to_linear(color);
+        }
+    } else {
+        // downscaling: average a grid of samples covering the pixel in linear light
+        color = vec4(0.0);
+        for (int x = 0; x < 4; x++) {
+            for (int y = 0; y < 4; y++) {
+                vec2 offset = (vec2(float(x), float(y)) - 1.5) * tex_step;
+                color += premultiplied_to_linear(texture2D(tex, v_tex_coords + offset));
+            }
+        }
+        color /= 16.0;
+        if (!linear_blending && color.a > 0.0) {
+            color.rgb = linear_to_srgb(color.rgb / color.a) * color.a;
+        }
+    }
+    if (color_filter) {
+        color.rgb = clamp(filter_matrix * color.rgb + filter_offset * color.a, 0.0, color.a);
+    }
+    gl_FragColor = color * alpha;
 }
 "#;
 
@@ -0,0 +1,266 @@
+//! GPU timing of rendered frames using timer queries
+
+use std::{collections::HashMap, time::Duration};
+
+use super::ffi;
+
+/// GPU time spent on a labeled section of a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SectionTiming {
+    /// Label given to [`Frame::begin_timing_section`](crate::backend::renderer::Frame::begin_timing_section)
+    pub label: String,
+    /// GPU time spent between the start and the end of the section
+    pub duration: Duration,
+}
+
+/// GPU timings of a single frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameTiming {
+    /// GPU time spent on the whole frame
+    pub duration: Duration,
+    /// GPU time spent on every section of the frame, in the order they were rendered
+    pub sections: Vec<SectionTiming>,
+}
+
+/// Aggregated GPU times of a series of measurements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimingStats {
+    /// Number of measurements
+    pub count: usize,
+    /// Sum of all measurements
+    pub total: Duration,
+    /// Longest measurement
+    pub max: Duration,
+}
+
+impl TimingStats {
+    fn add(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Average of all measurements
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::from_secs(0)
+        } else {
+            // counts beyond u32::MAX do not fit the divisor of `Duration`
+            Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+        }
+    }
+}
+
+/// Statistics over the GPU timings of multiple frames
+///
+/// Keep one of these per output and feed it the [`FrameTiming`] of every frame
+/// rendered for the output, to find out which sections take up the most time.
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    frames: TimingStats,
+    sections: HashMap<String, TimingStats>,
+}
+
+impl RenderStats {
+    /// Creates empty statistics
+    pub fn new() -> RenderStats {
+        RenderStats::default()
+    }
+
+    /// Adds the timings of a frame
+    pub fn add_frame(&mut self, frame: &FrameTiming) {
+        self.frames.add(frame.duration);
+        for section in &frame.sections {
+            self.sections
+                .entry(section.label.clone())
+                .or_default()
+                .add(section.duration);
+        }
+    }
+
+    /// Statistics over whole frames
+    pub fn frames(&self) -> &TimingStats {
+        &self.frames
+    }
+
+    /// Statistics over all sections with a given label
+    pub fn section(&self, label: &str) -> Option<&TimingStats> {
+        self.sections.get(label)
+    }
+
+    /// Iterator over the statistics of all sections, most expensive first
+    pub fn sections(&self) -> impl Iterator<Item = (&str, &TimingStats)> {
+        let mut sections = self
+            .sections
+            .iter()
+            .map(|(label, stats)| (label.as_str(), stats))
+            .collect::<Vec<_>>();
+        sections.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total));
+        sections.into_iter()
+    }
+
+    /// Discards all measurements
+    pub fn reset(&mut self) {
+        self.frames = TimingStats::default();
+        self.sections.clear();
+    }
+}
+
+/// Pool of timestamp queries recording a single frame
+#[derive(Debug, Default)]
+pub(super) struct GpuTimer {
+    queries: Vec<ffi::types::GLuint>,
+    used: usize,
+    // label, start query and end query
+    sections: Vec<(String, usize, Option<usize>)>,
+}
+
+impl GpuTimer {
+    unsafe fn timestamp(&mut self, gl: &ffi::Gles2) -> usize {
+        if self.used == self.queries.len() {
+            let mut query = 0;
+            gl.GenQueriesEXT(1, &mut query);
+            self.queries.push(query);
+        }
+        gl.QueryCounterEXT(self.queries[self.used], ffi::TIMESTAMP_EXT);
+        self.used += 1;
+        self.used - 1
+    }
+
+    pub(super) unsafe fn begin_frame(&mut self, gl: &ffi::Gles2) {
+        self.used = 0;
+        self.sections.clear();
+        // reading the disjoint flag resets it
+        let mut disjoint = 0;
+        gl.GetIntegerv(ffi::GPU_DISJOINT_EXT, &mut disjoint);
+        self.timestamp(gl);
+    }
+
+    pub(super) unsafe fn begin_section(&mut self, gl: &ffi::Gles2, label: String) {
+        self.end_section(gl);
+        let start = self.timestamp(gl);
+        self.sections.push((label, start, None));
+    }
+
+    pub(super) unsafe fn end_section(&mut self, gl: &ffi::Gles2) {
+        if matches!(self.sections.last(), Some((_, _, None))) {
+            let end = self.timestamp(gl);
+            self.sections.last_mut().unwrap().2 = Some(end);
+        }
+    }
+
+    pub(super) unsafe fn end_frame(&mut self, gl: &ffi::Gles2) {
+        self.end_section(gl);
+        self.timestamp(gl);
+    }
+
+    /// Reads back the results of the last frame, blocks until they are available
+    ///
+    /// Returns `None` if the measurements were invalidated, e.g. by a GPU frequency change.
+    pub(super) unsafe fn collect(&mut self, gl: &ffi::Gles2) -> Option<FrameTiming> {
+        let mut timestamps = Vec::with_capacity(self.used);
+        for query in &self.queries[..self.used] {
+            let mut value = 0u64;
+            gl.GetQueryObjectui64vEXT(*query, ffi::QUERY_RESULT_EXT, &mut value);
+            timestamps.push(value);
+        }
+
+        let mut disjoint = 0;
+        gl.GetIntegerv(ffi::GPU_DISJOINT_EXT, &mut disjoint);
+        if disjoint != 0 || timestamps.len() < 2 {
+            return None;
+        }
+
+        let elapsed = |start: usize, end: usize| {
+            Duration::from_nanos(timestamps[end].saturating_sub(timestamps[start]))
+        };
+        Some(FrameTiming {
+            duration: elapsed(0, timestamps.len() - 1),
+            sections: self
+                .sections
+                .drain(..)
+                .filter_map(|(label, start, end)| {
+                    end.map(|end| SectionTiming {
+                        label,
+                        duration: elapsed(start, end),
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    pub(super) unsafe fn destroy(&mut self, gl: &ffi::Gles2) {
+        gl.DeleteQueriesEXT(self.queries.len() as i32, self.queries.as_ptr());
+        self.queries.clear();
+        self.used = 0;
+        self.sections.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameTiming, RenderStats, SectionTiming, TimingStats};
+    use std::time::Duration;
+
+    fn frame(duration: u64, sections: &[(&str, u64)]) -> FrameTiming {
+        FrameTiming {
+            duration: Duration::from_millis(duration),
+            sections: sections
+                .iter()
+                .map(|(label, duration)| SectionTiming {
+                    label: label.to_string(),
+                    duration: Duration::from_millis(*duration),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_timing_stats_average() {
+        let mut stats = TimingStats::default();
+        assert_eq!(stats.average(), Duration::from_secs(0));
+        stats.add(Duration::from_millis(2));
+        stats.add(Duration::from_millis(4));
+        stats.add(Duration::from_millis(9));
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.total, Duration::from_millis(15));
+        assert_eq!(stats.max, Duration::from_millis(9));
+        assert_eq!(stats.average(), Duration::from_millis(5));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_timing_stats_average_large_count() {
+        let stats = TimingStats {
+            count: u32::MAX as usize + 1,
+            total: Duration::from_nanos(u32::MAX as u64 + 1),
+            max: Duration::from_nanos(1),
+        };
+        assert_eq!(stats.average(), Duration::from_nanos(1));
+    }
+
+    #[test]
+    fn test_render_stats_sections() {
+        let mut stats = RenderStats::new();
+        stats.add_frame(&frame(10, &[("window a", 3), ("window b", 5)]));
+        stats.add_frame(&frame(6, &[("window a", 1)]));
+
+        assert_eq!(stats.frames().count, 2);
+        assert_eq!(stats.frames().total, Duration::from_millis(16));
+        assert_eq!(stats.frames().max, Duration::from_millis(10));
+
+        let a = stats.section("window a").unwrap();
+        assert_eq!(a.count, 2);
+        assert_eq!(a.average(), Duration::from_millis(2));
+        assert_eq!(stats.section("window b").unwrap().count, 1);
+        assert!(stats.section("window c").is_none());
+
+        // most expensive first
+        let labels = stats.sections().map(|(label, _)| label).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["window b", "window a"]);
+
+        stats.reset();
+        assert_eq!(stats.frames(), &TimingStats::default());
+        assert_eq!(stats.sections().count(), 0);
+    }
+}
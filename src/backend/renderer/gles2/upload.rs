@@ -0,0 +1,135 @@
+//! Persistently mapped pixel unpack buffer used to upload shm buffers
+
+use std::{ptr, slice};
+
+use super::{ffi, pool::size_class};
+use crate::utils::{Buffer, Rectangle};
+
+/// Bytes per pixel of the supported shm formats
+const PIXEL_SIZE: usize = 4;
+
+/// Copies the given regions of an image into `dst`, each one tightly packed after the other
+///
+/// `src` starts at the first pixel of the image and has rows of `stride` bytes.
+/// Returns the offset of every region in `dst`.
+pub(super) fn pack_regions(
+    src: &[u8],
+    stride: usize,
+    regions: &[Rectangle<i32, Buffer>],
+    dst: &mut [u8],
+) -> Vec<usize> {
+    let mut offset = 0;
+    regions
+        .iter()
+        .map(|region| {
+            let start = offset;
+            let row_len = region.size.w as usize * PIXEL_SIZE;
+            for row in 0..region.size.h as usize {
+                let src_start = (region.loc.y as usize + row) * stride + region.loc.x as usize * PIXEL_SIZE;
+                dst[offset..offset + row_len].copy_from_slice(&src[src_start..src_start + row_len]);
+                offset += row_len;
+            }
+            start
+        })
+        .collect()
+}
+
+/// Returns the number of bytes needed to pack the given regions
+pub(super) fn packed_len(regions: &[Rectangle<i32, Buffer>]) -> usize {
+    regions
+        .iter()
+        .map(|region| region.size.w as usize * region.size.h as usize * PIXEL_SIZE)
+        .sum()
+}
+
+/// Pixel unpack buffer mapped for the lifetime of the renderer
+///
+/// Requires `GL_EXT_buffer_storage`. The mapping is coherent, so writes do not need to be flushed,
+/// but a fence has to be waited for, before memory read by previous uploads is written again.
+#[derive(Debug)]
+pub(super) struct UploadBuffer {
+    pub(super) pbo: ffi::types::GLuint,
+    capacity: usize,
+    mapping: *mut u8,
+    fence: Option<ffi::types::GLsync>,
+}
+
+impl UploadBuffer {
+    /// Creates a buffer holding at least `len` bytes, returns `None` if it could not be mapped
+    pub(super) unsafe fn new(gl: &ffi::Gles2, len: usize) -> Option<UploadBuffer> {
+        let capacity = size_class(len);
+        let flags = ffi::MAP_WRITE_BIT | ffi::MAP_PERSISTENT_BIT_EXT | ffi::MAP_COHERENT_BIT_EXT;
+
+        let mut pbo = 0;
+        gl.GenBuffers(1, &mut pbo);
+        gl.BindBuffer(ffi::PIXEL_UNPACK_BUFFER, pbo);
+        gl.BufferStorageEXT(ffi::PIXEL_UNPACK_BUFFER, capacity as isize, ptr::null(), flags);
+        let mapping = gl.MapBufferRange(ffi::PIXEL_UNPACK_BUFFER, 0, capacity as isize, flags) as *mut u8;
+        gl.BindBuffer(ffi::PIXEL_UNPACK_BUFFER, 0);
+
+        if mapping.is_null() {
+            gl.DeleteBuffers(1, &pbo);
+            return None;
+        }
+        Some(UploadBuffer {
+            pbo,
+            capacity,
+            mapping,
+            fence: None,
+        })
+    }
+
+    pub(super) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Waits until previous uploads were read and returns the mapped memory
+    pub(super) unsafe fn wait(&mut self, gl: &ffi::Gles2) -> &mut [u8] {
+        if let Some(fence) = self.fence.take() {
+            gl.ClientWaitSync(fence, ffi::SYNC_FLUSH_COMMANDS_BIT, ffi::TIMEOUT_IGNORED);
+            gl.DeleteSync(fence);
+        }
+        slice::from_raw_parts_mut(self.mapping, self.capacity)
+    }
+
+    /// Marks the end of uploads reading the mapped memory
+    pub(super) unsafe fn fence(&mut self, gl: &ffi::Gles2) {
+        if let Some(fence) = self.fence.take() {
+            gl.DeleteSync(fence);
+        }
+        self.fence = Some(gl.FenceSync(ffi::SYNC_GPU_COMMANDS_COMPLETE, 0));
+    }
+
+    pub(super) unsafe fn destroy(mut self, gl: &ffi::Gles2) {
+        self.wait(gl);
+        gl.BindBuffer(ffi::PIXEL_UNPACK_BUFFER, self.pbo);
+        gl.UnmapBuffer(ffi::PIXEL_UNPACK_BUFFER);
+        gl.BindBuffer(ffi::PIXEL_UNPACK_BUFFER, 0);
+        gl.DeleteBuffers(1, &self.pbo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_regions() {
+        // 4x3 image with a stride of 5 pixels, every pixel holds its index in all bytes
+        let stride = 5 * PIXEL_SIZE;
+        let src = (0..15u8)
+            .flat_map(|pixel| [pixel; PIXEL_SIZE])
+            .collect::<Vec<_>>();
+        let regions = [
+            Rectangle::from_loc_and_size((1, 1), (2, 2)),
+            Rectangle::from_loc_and_size((0, 0), (4, 1)),
+        ];
+        let mut dst = vec![0; packed_len(&regions)];
+        assert_eq!(dst.len(), 8 * PIXEL_SIZE);
+
+        let offsets = pack_regions(&src, stride, &regions, &mut dst);
+        assert_eq!(offsets, vec![0, 4 * PIXEL_SIZE]);
+        let pixels = dst.chunks(PIXEL_SIZE).map(|pixel| pixel[0]).collect::<Vec<_>>();
+        assert_eq!(pixels, vec![6, 7, 11, 12, 0, 1, 2, 3]);
+    }
+}
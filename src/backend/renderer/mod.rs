@@ -18,6 +18,7 @@ use cgmath::Matrix3;
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::{wl_buffer, wl_shm};
 
+pub mod damage;
 #[cfg(feature = "renderer_gl")]
 pub mod gles2;
 
@@ -184,6 +185,17 @@ pub trait Frame {
 
     /// Output transformation that is applied to this frame
     fn transformation(&self) -> Transform;
+
+    /// Starts measuring the time spent on a new section of this frame, e.g. a window or an effect
+    ///
+    /// Ends the previous section, if any. Sections that are never ended explicitly
+    /// end with the frame. Renderers not supporting any timing ignore this.
+    fn begin_timing_section(&mut self, label: &str) {
+        let _ = label;
+    }
+
+    /// Ends the current section started with [`Frame::begin_timing_section`]
+    fn end_timing_section(&mut self) {}
 }
 
 /// Abstraction of commonly used rendering operations for compositors.
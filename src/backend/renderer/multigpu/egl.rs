@@ -46,7 +46,7 @@ pub enum Error {
 impl From<Error> for SwapBuffersError {
     fn from(err: Error) -> SwapBuffersError {
         match err {
-            x @ Error::DrmNode(_) | x @ Error::Egl(_) => SwapBuffersError::ContextLost(Box::new(x)),
+            x @ Error::DrmNode(_) | x @ Error::Egl(_) => SwapBuffersError::context_lost(x),
             Error::Gl(x) => x.into(),
         }
     }
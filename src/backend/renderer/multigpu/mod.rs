@@ -50,7 +50,7 @@ use crate::{
     backend::{
         allocator::{dmabuf::WeakDmabuf, Buffer, Format},
         drm::DrmNode,
-        SwapBuffersError,
+        SwapBuffersError, TemporaryFailureReason,
     },
     utils::{Buffer as BufferCoords, Physical, Size},
 };
@@ -141,10 +141,10 @@ where
     fn from(err: Error<R, T>) -> SwapBuffersError {
         match err {
             x @ Error::NoDevices | x @ Error::NoDevice(_) | x @ Error::DeviceMissing => {
-                SwapBuffersError::ContextLost(Box::new(x))
+                SwapBuffersError::context_lost(x)
             }
             x @ Error::MismatchedDevice(_) | x @ Error::ImportFailed => {
-                SwapBuffersError::TemporaryFailure(Box::new(x))
+                SwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x)
             }
             Error::RenderApiError(x) => x.into(),
             Error::TargetApiError(x) => x.into(),
@@ -1238,6 +1238,14 @@ where
     fn transformation(&self) -> Transform {
         unsafe { &mut *self.frame }.transformation()
     }
+
+    fn begin_timing_section(&mut self, label: &str) {
+        unsafe { &mut *self.frame }.begin_timing_section(label)
+    }
+
+    fn end_timing_section(&mut self) {
+        unsafe { &mut *self.frame }.end_timing_section()
+    }
 }
 
 #[cfg(feature = "wayland_frontend")]
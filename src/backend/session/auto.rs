@@ -9,6 +9,10 @@
 //! A new session will be opened, if the any available interface is successful and will be closed once the
 //! [`AutoSessionNotifier`] is dropped.
 //!
+//! The interfaces are tried in the order of [`SessionBackend::DEFAULT_ORDER`]. Use
+//! [`AutoSession::with_backends`] to choose the interfaces and their order yourself and to find out
+//! why each of them failed.
+//!
 //! ### Usage of the session
 //!
 //! The session may be used to open devices manually through the [`Session`] interface
@@ -29,7 +33,8 @@
 //! for notifications are the [`Libinput`](input::Libinput) context or the [`DrmDevice`](crate::backend::drm::DrmDevice).
 //!
 //! The [`AutoSessionNotifier`] is to be inserted into
-//! a calloop event source to have its events processed.
+//! a calloop event source to have its events processed. It generates a [`SessionEvent`]
+//! whenever the session is paused or activated, e.g. to stop rendering while the seat is switched away.
 
 #[cfg(feature = "backend_session_libseat")]
 use super::libseat::{LibSeatSession, LibSeatSessionNotifier};
@@ -39,9 +44,9 @@ use super::{
     direct::{self, DirectSession, DirectSessionNotifier},
     AsErrno, Session, Signal as SessionSignal,
 };
-use crate::utils::signaling::Signaler;
+use crate::utils::signaling::{SignalToken, Signaler};
 use nix::fcntl::OFlag;
-use std::{cell::RefCell, os::unix::io::RawFd, path::Path, rc::Rc};
+use std::{cell::RefCell, fmt, os::unix::io::RawFd, path::Path, rc::Rc};
 
 use calloop::{EventSource, Poll, PostAction, Readiness, Token, TokenFactory};
 
@@ -62,73 +67,168 @@ pub enum AutoSession {
 
 /// Notifier using the best available interface
 #[derive(Debug)]
-pub enum AutoSessionNotifier {
-    /// Logind session notifier
+pub struct AutoSessionNotifier {
+    inner: AutoSessionNotifierInner,
+    events: Rc<RefCell<Vec<SessionEvent>>>,
+    _signal_token: SignalToken,
+}
+
+#[derive(Debug)]
+enum AutoSessionNotifierInner {
     #[cfg(feature = "backend_session_logind")]
     Logind(LogindSessionNotifier),
-    /// Direct / tty session notifier
     Direct(DirectSessionNotifier),
-    /// LibSeat session notifier
     #[cfg(feature = "backend_session_libseat")]
     LibSeat(LibSeatSessionNotifier),
 }
 
+/// Interfaces an [`AutoSession`] can be created with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SessionBackend {
+    /// [`LibSeatSession`](super::libseat::LibSeatSession), requires the `backend_session_libseat` feature
+    LibSeat,
+    /// [`LogindSession`](super::logind::LogindSession), requires the `backend_session_logind` feature
+    Logind,
+    /// [`DirectSession`]
+    Direct,
+}
+
+impl SessionBackend {
+    /// Order the interfaces are tried in by [`AutoSession::new`]
+    pub const DEFAULT_ORDER: [SessionBackend; 3] = [
+        SessionBackend::LibSeat,
+        SessionBackend::Logind,
+        SessionBackend::Direct,
+    ];
+}
+
+impl fmt::Display for SessionBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionBackend::LibSeat => write!(f, "libseat"),
+            SessionBackend::Logind => write!(f, "logind"),
+            SessionBackend::Direct => write!(f, "direct"),
+        }
+    }
+}
+
+/// Change of the state of an [`AutoSession`], generated by the [`AutoSessionNotifier`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// The session was paused, e.g. because the seat was switched away.
+    ///
+    /// Devices can not be accessed until the session is activated again.
+    Paused,
+    /// The session became active again
+    Activated,
+}
+
 impl AutoSession {
     /// Tries to create a new session via the best available interface.
+    ///
+    /// The interfaces are tried in the order of [`SessionBackend::DEFAULT_ORDER`].
     pub fn new<L>(logger: L) -> Option<(AutoSession, AutoSessionNotifier)>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        AutoSession::with_backends(&SessionBackend::DEFAULT_ORDER, logger).ok()
+    }
+
+    /// Tries to create a new session via the given interfaces, in order.
+    ///
+    /// The first interface, that succeeds, is used. If none does, the returned error
+    /// contains the reason every single interface failed for.
+    pub fn with_backends<L>(
+        backends: &[SessionBackend],
+        logger: L,
+    ) -> Result<(AutoSession, AutoSessionNotifier), NoSessionError>
     where
         L: Into<Option<::slog::Logger>>,
     {
         let logger = crate::slog_or_fallback(logger)
             .new(o!("smithay_module" => "backend_session_auto", "session_type" => "auto"));
 
-        #[cfg(feature = "backend_session_libseat")]
-        {
-            info!(logger, "Trying to create libseat session");
-            match LibSeatSession::new(logger.clone()) {
-                Ok((sesstion, notifier)) => {
-                    return Some((
-                        AutoSession::LibSeat(sesstion),
-                        AutoSessionNotifier::LibSeat(notifier),
-                    ))
-                }
+        let mut attempts = Vec::with_capacity(backends.len());
+        for &backend in backends {
+            info!(logger, "Trying to create {} session", backend);
+            match AutoSession::open_backend(backend, &logger) {
+                Ok(ret) => return Ok(ret),
                 Err(err) => {
-                    warn!(logger, "Failed to create libseat session: {}", err);
+                    warn!(logger, "Failed to create {} session: {}", backend, err);
+                    attempts.push((backend, err));
                 }
             }
         }
 
-        #[cfg(feature = "backend_session_logind")]
-        {
-            info!(logger, "Trying to create logind session");
-            match LogindSession::new(logger.clone()) {
-                Ok((session, notifier)) => {
-                    return Some((
+        error!(logger, "Could not create any session, possibilities exhausted");
+        Err(NoSessionError { attempts })
+    }
+
+    fn open_backend(
+        backend: SessionBackend,
+        logger: &::slog::Logger,
+    ) -> Result<(AutoSession, AutoSessionNotifier), Error> {
+        match backend {
+            SessionBackend::LibSeat => {
+                #[cfg(feature = "backend_session_libseat")]
+                {
+                    let (session, notifier) = LibSeatSession::new(logger.clone())?;
+                    Ok((
+                        AutoSession::LibSeat(session),
+                        AutoSessionNotifier::new(AutoSessionNotifierInner::LibSeat(notifier)),
+                    ))
+                }
+                #[cfg(not(feature = "backend_session_libseat"))]
+                {
+                    Err(Error::Unsupported(backend))
+                }
+            }
+            SessionBackend::Logind => {
+                #[cfg(feature = "backend_session_logind")]
+                {
+                    let (session, notifier) = LogindSession::new(logger.clone())?;
+                    Ok((
                         AutoSession::Logind(session),
-                        AutoSessionNotifier::Logind(notifier),
+                        AutoSessionNotifier::new(AutoSessionNotifierInner::Logind(notifier)),
                     ))
                 }
-                Err(err) => {
-                    warn!(logger, "Failed to create logind session: {}", err);
+                #[cfg(not(feature = "backend_session_logind"))]
+                {
+                    Err(Error::Unsupported(backend))
                 }
             }
-        }
-
-        info!(logger, "Trying to create tty session");
-        match DirectSession::new(None, logger.clone()) {
-            Ok((session, notifier)) => {
-                return Some((
+            SessionBackend::Direct => {
+                let (session, notifier) = DirectSession::new(None, logger.clone())?;
+                Ok((
                     AutoSession::Direct(Rc::new(RefCell::new(session))),
-                    AutoSessionNotifier::Direct(notifier),
+                    AutoSessionNotifier::new(AutoSessionNotifierInner::Direct(notifier)),
                 ))
             }
-            Err(err) => {
-                warn!(logger, "Failed to create direct session: {}", err);
-            }
         }
+    }
 
-        error!(logger, "Could not create any session, possibilities exhausted");
-        None
+    /// Sets the brightness of a backlight or LED device
+    ///
+    /// Logind sessions change it through logind, which does not require any special permissions
+    /// as long as the session is active. The other interfaces write to sysfs directly, which
+    /// requires write access to the device.
+    ///
+    /// - `subsystem` is either `"backlight"` or `"leds"`
+    /// - `name` is the name of the device in sysfs, e.g. `"intel_backlight"`
+    pub fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> Result<(), Error> {
+        match *self {
+            #[cfg(feature = "backend_session_logind")]
+            AutoSession::Logind(ref logind) => logind
+                .set_brightness(subsystem, name, brightness)
+                .map_err(|e| e.into()),
+            _ => {
+                let path = Path::new("/sys/class")
+                    .join(subsystem)
+                    .join(name)
+                    .join("brightness");
+                std::fs::write(path, brightness.to_string()).map_err(Error::Sysfs)
+            }
+        }
     }
 }
 
@@ -185,22 +285,47 @@ impl Session for AutoSession {
 }
 
 impl AutoSessionNotifier {
+    fn new(inner: AutoSessionNotifierInner) -> AutoSessionNotifier {
+        // the underlying notifiers only report state changes through their signaler
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let signal_token = inner.signaler().register({
+            let events = events.clone();
+            move |signal| match signal {
+                SessionSignal::PauseSession => events.borrow_mut().push(SessionEvent::Paused),
+                SessionSignal::ActivateSession => events.borrow_mut().push(SessionEvent::Activated),
+                _ => {}
+            }
+        });
+
+        AutoSessionNotifier {
+            inner,
+            events,
+            _signal_token: signal_token,
+        }
+    }
+
     /// Get a handle to the Signaler of this session.
     ///
     /// You can use it to listen for signals generated by the session.
     pub fn signaler(&self) -> Signaler<SessionSignal> {
+        self.inner.signaler()
+    }
+}
+
+impl AutoSessionNotifierInner {
+    fn signaler(&self) -> Signaler<SessionSignal> {
         match *self {
             #[cfg(feature = "backend_session_logind")]
-            AutoSessionNotifier::Logind(ref logind) => logind.signaler(),
-            AutoSessionNotifier::Direct(ref direct) => direct.signaler(),
+            AutoSessionNotifierInner::Logind(ref logind) => logind.signaler(),
+            AutoSessionNotifierInner::Direct(ref direct) => direct.signaler(),
             #[cfg(feature = "backend_session_libseat")]
-            AutoSessionNotifier::LibSeat(ref direct) => direct.signaler(),
+            AutoSessionNotifierInner::LibSeat(ref direct) => direct.signaler(),
         }
     }
 }
 
 impl EventSource for AutoSessionNotifier {
-    type Event = ();
+    type Event = SessionEvent;
     type Metadata = ();
     type Ret = ();
     type Error = Error;
@@ -209,47 +334,52 @@ impl EventSource for AutoSessionNotifier {
         &mut self,
         readiness: Readiness,
         token: Token,
-        callback: F,
+        mut callback: F,
     ) -> Result<PostAction, Error>
     where
-        F: FnMut((), &mut ()),
+        F: FnMut(SessionEvent, &mut ()),
     {
-        match self {
+        let ret = match &mut self.inner {
             #[cfg(feature = "backend_session_logind")]
-            AutoSessionNotifier::Logind(s) => Ok(s.process_events(readiness, token, callback)?),
-            AutoSessionNotifier::Direct(s) => Ok(s.process_events(readiness, token, callback)?),
+            AutoSessionNotifierInner::Logind(s) => s.process_events(readiness, token, |_, _| {})?,
+            AutoSessionNotifierInner::Direct(s) => s.process_events(readiness, token, |_, _| {})?,
             #[cfg(feature = "backend_session_libseat")]
-            AutoSessionNotifier::LibSeat(s) => Ok(s.process_events(readiness, token, callback)?),
+            AutoSessionNotifierInner::LibSeat(s) => s.process_events(readiness, token, |_, _| {})?,
+        };
+
+        for event in self.events.borrow_mut().drain(..) {
+            callback(event, &mut ());
         }
+        Ok(ret)
     }
 
     fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
-        match self {
+        match &mut self.inner {
             #[cfg(feature = "backend_session_logind")]
-            AutoSessionNotifier::Logind(s) => EventSource::register(s, poll, factory),
-            AutoSessionNotifier::Direct(s) => EventSource::register(s, poll, factory),
+            AutoSessionNotifierInner::Logind(s) => EventSource::register(s, poll, factory),
+            AutoSessionNotifierInner::Direct(s) => EventSource::register(s, poll, factory),
             #[cfg(feature = "backend_session_libseat")]
-            AutoSessionNotifier::LibSeat(s) => EventSource::register(s, poll, factory),
+            AutoSessionNotifierInner::LibSeat(s) => EventSource::register(s, poll, factory),
         }
     }
 
     fn reregister(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
-        match self {
+        match &mut self.inner {
             #[cfg(feature = "backend_session_logind")]
-            AutoSessionNotifier::Logind(s) => EventSource::reregister(s, poll, factory),
-            AutoSessionNotifier::Direct(s) => EventSource::reregister(s, poll, factory),
+            AutoSessionNotifierInner::Logind(s) => EventSource::reregister(s, poll, factory),
+            AutoSessionNotifierInner::Direct(s) => EventSource::reregister(s, poll, factory),
             #[cfg(feature = "backend_session_libseat")]
-            AutoSessionNotifier::LibSeat(s) => EventSource::reregister(s, poll, factory),
+            AutoSessionNotifierInner::LibSeat(s) => EventSource::reregister(s, poll, factory),
         }
     }
 
     fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
-        match self {
+        match &mut self.inner {
             #[cfg(feature = "backend_session_logind")]
-            AutoSessionNotifier::Logind(s) => EventSource::unregister(s, poll),
-            AutoSessionNotifier::Direct(s) => EventSource::unregister(s, poll),
+            AutoSessionNotifierInner::Logind(s) => EventSource::unregister(s, poll),
+            AutoSessionNotifierInner::Direct(s) => EventSource::unregister(s, poll),
             #[cfg(feature = "backend_session_libseat")]
-            AutoSessionNotifier::LibSeat(s) => EventSource::unregister(s, poll),
+            AutoSessionNotifierInner::LibSeat(s) => EventSource::unregister(s, poll),
         }
     }
 }
@@ -269,9 +399,33 @@ pub enum Error {
     #[error("LibSeat session error: {0}")]
     LibSeat(#[from] super::libseat::Error),
 
+    /// The session interface is not supported by this build of smithay
+    #[error("{0} sessions are not supported, the corresponding feature is disabled")]
+    Unsupported(SessionBackend),
+
     /// Nix error
     #[error("Nix error: {0}")]
     Nix(#[from] nix::Error),
+
+    /// Writing to sysfs failed
+    #[error("Failed to write to sysfs: {0}")]
+    Sysfs(#[source] std::io::Error),
+}
+
+/// Error returned by [`AutoSession::with_backends`], if no interface could create a session
+#[derive(thiserror::Error, Debug)]
+#[error("Could not create any session{}", format_attempts(.attempts))]
+pub struct NoSessionError {
+    /// Every interface, that was tried, together with the reason it failed
+    pub attempts: Vec<(SessionBackend, Error)>,
+}
+
+fn format_attempts(attempts: &[(SessionBackend, Error)]) -> String {
+    attempts
+        .iter()
+        .enumerate()
+        .map(|(i, (backend, err))| format!("{} {}: {}", if i == 0 { ":" } else { ";" }, backend, err))
+        .collect()
 }
 
 impl AsErrno for Error {
@@ -205,6 +205,28 @@ impl LogindSession {
             LogindSessionNotifier { internal },
         ))
     }
+
+    /// Sets the brightness of a backlight or LED device of this seat.
+    ///
+    /// Unlike writing to sysfs directly, this does not require any special permissions.
+    ///
+    /// - `subsystem` is either `"backlight"` or `"leds"`
+    /// - `name` is the name of the device in sysfs, e.g. `"intel_backlight"`
+    pub fn set_brightness(&self, subsystem: &str, name: &str, brightness: u32) -> Result<(), Error> {
+        if let Some(session) = self.internal.upgrade() {
+            LogindSessionImpl::blocking_call(
+                &*session.conn.borrow(),
+                "org.freedesktop.login1",
+                session.session_path.clone(),
+                "org.freedesktop.login1.Session",
+                "SetBrightness",
+                Some(vec![subsystem.into(), name.into(), brightness.into()]),
+            )
+            .map(|_| ())
+        } else {
+            Err(Error::SessionLost)
+        }
+    }
 }
 
 impl LogindSessionNotifier {
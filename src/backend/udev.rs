@@ -20,10 +20,10 @@
 //! # let loop_handle = event_loop.handle();
 //! // setup the event source for long-term monitoring
 //! loop_handle.insert_source(udev, |event, _, _dispatch_data| match event {
-//!     UdevEvent::Added { device_id, path } => {
+//!     UdevEvent::Added { device_id, path, properties } => {
 //!         // a new device has been added
 //!     },
-//!     UdevEvent::Changed { device_id } => {
+//!     UdevEvent::Changed { device_id, properties } => {
 //!         // a device has been changed
 //!     },
 //!     UdevEvent::Removed { device_id } => {
@@ -32,6 +32,9 @@
 //! }).expect("Failed to insert the udev source into the event loop");
 //! ```
 //!
+//! Devices can be filtered, e.g. to ignore a broken secondary GPU, by creating the backend
+//! with [`UdevBackend::with_filter`]. A [`DeviceFilter`] covers the common cases of allow and deny lists.
+//!
 //! Additionally this contains some utility functions related to scanning.
 //!
 //! See also `anvil/src/udev.rs` for pure hardware backed example of a compositor utilizing this
@@ -51,13 +54,104 @@ use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, T
 
 use slog::{debug, info, o, warn};
 
+/// Properties of a DRM device as reported by udev
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceProperties {
+    /// Seat the device is assigned to (`ID_SEAT`), defaults to `seat0`
+    pub seat: String,
+    /// Persistent path of the device (`ID_PATH`), e.g. `pci-0000:01:00.0`
+    pub id_path: Option<String>,
+    /// Name of the device in sysfs, e.g. `card0`
+    pub sysname: String,
+    /// Kernel driver of the device or its closest parent, e.g. `amdgpu`
+    pub driver: Option<String>,
+    /// Whether the device was used by the firmware to boot, which usually makes it the primary GPU
+    pub boot_vga: bool,
+}
+
+impl DeviceProperties {
+    fn from_device(device: &udev::Device) -> DeviceProperties {
+        let to_string = |value: &std::ffi::OsStr| value.to_string_lossy().into_owned();
+        let mut driver = None;
+        let mut parent = Some(device.clone());
+        while let Some(dev) = parent {
+            if let Some(name) = dev.driver() {
+                driver = Some(to_string(name));
+                break;
+            }
+            parent = dev.parent();
+        }
+
+        DeviceProperties {
+            seat: device
+                .property_value("ID_SEAT")
+                .map(to_string)
+                .unwrap_or_else(|| String::from("seat0")),
+            id_path: device.property_value("ID_PATH").map(to_string),
+            sysname: to_string(device.sysname()),
+            driver,
+            boot_vga: device
+                .parent_with_subsystem(Path::new("pci"))
+                .ok()
+                .flatten()
+                .and_then(|pci| pci.attribute_value("boot_vga").map(|id| id == "1"))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Commonly used device filtering rules for [`UdevBackend::with_filter`], [`primary_gpu_with_filter`]
+/// and [`all_gpus_with_filter`]
+///
+/// ```no_run
+/// use smithay::backend::udev::{DeviceFilter, UdevBackend};
+///
+/// let filter = DeviceFilter {
+///     deny_id_paths: vec![String::from("pci-0000:01:00.0")],
+///     ..Default::default()
+/// };
+/// let udev = UdevBackend::with_filter("seat0", move |properties| filter.matches(properties), None)
+///     .expect("Failed to monitor udev.");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceFilter {
+    /// If not empty, only devices with one of these `ID_PATH`s are used
+    pub allow_id_paths: Vec<String>,
+    /// Devices with one of these `ID_PATH`s are never used
+    pub deny_id_paths: Vec<String>,
+    /// If set, only devices with a matching [`DeviceProperties::boot_vga`] are used
+    pub boot_vga: Option<bool>,
+}
+
+impl DeviceFilter {
+    /// Checks if a device passes this filter
+    pub fn matches(&self, properties: &DeviceProperties) -> bool {
+        let allowed = match properties.id_path {
+            Some(ref id_path) => {
+                (self.allow_id_paths.is_empty() || self.allow_id_paths.contains(id_path))
+                    && !self.deny_id_paths.contains(id_path)
+            }
+            None => self.allow_id_paths.is_empty(),
+        };
+        allowed
+            && self
+                .boot_vga
+                .iter()
+                .all(|&boot_vga| properties.boot_vga == boot_vga)
+    }
+}
+
+type Filter = Box<dyn FnMut(&DeviceProperties) -> bool + Send>;
+
 /// Backend to monitor available drm devices.
 ///
 /// Provides a way to automatically scan for available gpus and notifies the
 /// given handler of any changes. Can be used to provide hot-plug functionality for gpus and
 /// attached monitors.
 pub struct UdevBackend {
-    devices: HashMap<dev_t, PathBuf>,
+    devices: HashMap<dev_t, (PathBuf, DeviceProperties)>,
+    seat: String,
+    filter: Filter,
     monitor: MonitorSocket,
     token: Option<Token>,
     logger: ::slog::Logger,
@@ -69,6 +163,7 @@ impl fmt::Debug for UdevBackend {
         use udev::AsRaw;
         f.debug_struct("UdevBackend")
             .field("devices", &self.devices)
+            .field("seat", &self.seat)
             .field("monitor", &format!("MonitorSocket ({:?})", self.monitor.as_raw()))
             .field("logger", &self.logger)
             .finish()
@@ -90,29 +185,70 @@ impl UdevBackend {
     pub fn new<L, S: AsRef<str>>(seat: S, logger: L) -> io::Result<UdevBackend>
     where
         L: Into<Option<::slog::Logger>>,
+    {
+        UdevBackend::with_filter(seat, |_| true, logger)
+    }
+
+    /// Creates a new [`UdevBackend`] only reporting devices accepted by a `filter`
+    ///
+    /// Devices of other seats are skipped before the filter is called. Devices are checked again
+    /// whenever udev reports a change, and generate [`UdevEvent::Added`] or [`UdevEvent::Removed`]
+    /// if they start or stop passing these checks.
+    ///
+    /// ## Arguments
+    /// `seat`    - system seat which should be bound
+    /// `filter`  - called with the properties of every device, returns if the device should be used
+    /// `logger`  - slog Logger to be used by the backend and its `DrmDevices`.
+    pub fn with_filter<L, S, F>(seat: S, filter: F, logger: L) -> io::Result<UdevBackend>
+    where
+        L: Into<Option<::slog::Logger>>,
+        S: AsRef<str>,
+        F: FnMut(&DeviceProperties) -> bool + Send + 'static,
     {
         let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_udev"));
 
-        let devices = all_gpus(seat)?
-            .into_iter()
+        let mut backend = UdevBackend {
+            devices: HashMap::new(),
+            seat: seat.as_ref().to_owned(),
+            filter: Box::new(filter),
+            monitor: MonitorBuilder::new()?.match_subsystem("drm")?.listen()?,
+            token: None,
+            logger: log,
+        };
+
+        for (path, properties) in scan_gpus(&backend.seat)? {
+            if !(backend.filter)(&properties) {
+                info!(backend.logger, "Ignoring filtered device"; "properties" => ?properties);
+                continue;
+            }
             // Create devices
-            .flat_map(|path| match stat(&path) {
-                Ok(stat) => Some((stat.st_rdev, path)),
+            match stat(&path) {
+                Ok(stat) => {
+                    backend.devices.insert(stat.st_rdev, (path, properties));
+                }
                 Err(err) => {
-                    warn!(log, "Unable to get id of {:?}, Error: {:?}. Skipping", path, err);
-                    None
+                    warn!(
+                        backend.logger,
+                        "Unable to get id of {:?}, Error: {:?}. Skipping", path, err
+                    );
                 }
-            })
-            .collect();
+            }
+        }
 
-        let monitor = MonitorBuilder::new()?.match_subsystem("drm")?.listen()?;
+        Ok(backend)
+    }
 
-        Ok(UdevBackend {
-            devices,
-            monitor,
-            token: None,
-            logger: log,
-        })
+    /// Returns the properties of a device, if it passed the seat and filter checks
+    fn accept(&mut self, device: &udev::Device) -> Option<DeviceProperties> {
+        let properties = DeviceProperties::from_device(device);
+        if properties.seat != self.seat {
+            return None;
+        }
+        if !(self.filter)(&properties) {
+            info!(self.logger, "Ignoring filtered device"; "properties" => ?properties);
+            return None;
+        }
+        Some(properties)
     }
 
     /// Get a list of DRM devices currently known to the backend
@@ -120,7 +256,12 @@ impl UdevBackend {
     /// You should call this once before inserting the event source into your
     /// event loop, to get an initial snapshot of the device state.
     pub fn device_list(&self) -> impl Iterator<Item = (dev_t, &Path)> {
-        self.devices.iter().map(|(&id, path)| (id, path.as_ref()))
+        self.devices.iter().map(|(&id, (path, _))| (id, path.as_ref()))
+    }
+
+    /// Returns the udev properties of a DRM device known to the backend
+    pub fn device_properties(&self, device_id: dev_t) -> Option<&DeviceProperties> {
+        self.devices.get(&device_id).map(|(_, properties)| properties)
     }
 }
 
@@ -155,12 +296,18 @@ impl EventSource for UdevBackend {
                 // New device
                 EventType::Add => {
                     if let (Some(path), Some(devnum)) = (event.devnode(), event.devnum()) {
-                        info!(self.logger, "New device: #{} at {}", devnum, path.display());
-                        if self.devices.insert(devnum, path.to_path_buf()).is_none() {
+                        if self.devices.contains_key(&devnum) {
+                            continue;
+                        }
+                        if let Some(properties) = self.accept(&event) {
+                            info!(self.logger, "New device: #{} at {}", devnum, path.display());
+                            self.devices
+                                .insert(devnum, (path.to_path_buf(), properties.clone()));
                             callback(
                                 UdevEvent::Added {
                                     device_id: devnum,
                                     path: path.to_path_buf(),
+                                    properties,
                                 },
                                 &mut (),
                             );
@@ -170,18 +317,57 @@ impl EventSource for UdevBackend {
                 // Device removed
                 EventType::Remove => {
                     if let Some(devnum) = event.devnum() {
-                        info!(self.logger, "Device removed: #{}", devnum);
                         if self.devices.remove(&devnum).is_some() {
+                            info!(self.logger, "Device removed: #{}", devnum);
                             callback(UdevEvent::Removed { device_id: devnum }, &mut ());
                         }
                     }
                 }
-                // New connector
+                // New connector or changed properties
                 EventType::Change => {
                     if let Some(devnum) = event.devnum() {
-                        info!(self.logger, "Device changed: #{}", devnum);
-                        if self.devices.contains_key(&devnum) {
-                            callback(UdevEvent::Changed { device_id: devnum }, &mut ());
+                        // the seat assignment or the properties checked by the filter might have changed
+                        let known = self.devices.contains_key(&devnum);
+                        match (self.accept(&event), known) {
+                            (Some(properties), true) => {
+                                info!(self.logger, "Device changed: #{}", devnum);
+                                if let Some((_, old)) = self.devices.get_mut(&devnum) {
+                                    *old = properties.clone();
+                                }
+                                callback(
+                                    UdevEvent::Changed {
+                                        device_id: devnum,
+                                        properties,
+                                    },
+                                    &mut (),
+                                );
+                            }
+                            (None, true) => {
+                                info!(self.logger, "Device no longer accepted: #{}", devnum);
+                                self.devices.remove(&devnum);
+                                callback(UdevEvent::Removed { device_id: devnum }, &mut ());
+                            }
+                            (Some(properties), false) => {
+                                if let Some(path) = event.devnode() {
+                                    info!(
+                                        self.logger,
+                                        "Device now accepted: #{} at {}",
+                                        devnum,
+                                        path.display()
+                                    );
+                                    self.devices
+                                        .insert(devnum, (path.to_path_buf(), properties.clone()));
+                                    callback(
+                                        UdevEvent::Added {
+                                            device_id: devnum,
+                                            path: path.to_path_buf(),
+                                            properties,
+                                        },
+                                        &mut (),
+                                    );
+                                }
+                            }
+                            (None, false) => {}
                         }
                     }
                 }
@@ -216,11 +402,15 @@ pub enum UdevEvent {
         device_id: dev_t,
         /// Path of the new device
         path: PathBuf,
+        /// udev properties of the new device
+        properties: DeviceProperties,
     },
     /// A device has changed
     Changed {
         /// ID of the changed device
         device_id: dev_t,
+        /// Updated udev properties of the changed device
+        properties: DeviceProperties,
     },
     /// A device has been removed
     Removed {
@@ -229,38 +419,44 @@ pub enum UdevEvent {
     },
 }
 
+/// Returns the paths and properties of all DRM devices of a seat
+fn scan_gpus(seat: &str) -> io::Result<Vec<(PathBuf, DeviceProperties)>> {
+    let mut enumerator = Enumerator::new()?;
+    enumerator.match_subsystem("drm")?;
+    enumerator.match_sysname("card[0-9]*")?;
+    Ok(enumerator
+        .scan_devices()?
+        .flat_map(|device| {
+            let path = device.devnode()?.to_path_buf();
+            Some((path, DeviceProperties::from_device(&device)))
+        })
+        .filter(|(_, properties)| properties.seat == seat)
+        .collect())
+}
+
 /// Returns the path of the primary GPU device if any
 ///
 /// Might be used for filtering of [`UdevEvent::Added`] or for manual
 /// [`DrmDevice`](crate::backend::drm::DrmDevice) initialization.
 pub fn primary_gpu<S: AsRef<str>>(seat: S) -> io::Result<Option<PathBuf>> {
-    let mut enumerator = Enumerator::new()?;
-    enumerator.match_subsystem("drm")?;
-    enumerator.match_sysname("card[0-9]*")?;
+    primary_gpu_with_filter(seat, |_| true)
+}
 
-    if let Some(path) = enumerator
-        .scan_devices()?
-        .filter(|device| {
-            let seat_name = device
-                .property_value("ID_SEAT")
-                .map(|x| x.to_os_string())
-                .unwrap_or_else(|| OsString::from("seat0"));
-            if seat_name == *seat.as_ref() {
-                if let Ok(Some(pci)) = device.parent_with_subsystem(Path::new("pci")) {
-                    if let Some(id) = pci.attribute_value("boot_vga") {
-                        return id == "1";
-                    }
-                }
-            }
-            false
-        })
-        .flat_map(|device| device.devnode().map(PathBuf::from))
-        .next()
-    {
-        Ok(Some(path))
-    } else {
-        all_gpus(seat).map(|all| all.into_iter().next())
-    }
+/// Returns the path of the primary GPU device accepted by a `filter`, if any
+///
+/// Use the same filter as passed to [`UdevBackend::with_filter`] to never pick
+/// a device the backend ignores.
+pub fn primary_gpu_with_filter<S, F>(seat: S, filter: F) -> io::Result<Option<PathBuf>>
+where
+    S: AsRef<str>,
+    F: FnMut(&DeviceProperties) -> bool,
+{
+    let gpus = gpus_with_filter(seat.as_ref(), filter)?;
+    Ok(gpus
+        .iter()
+        .find(|(_, properties)| properties.boot_vga)
+        .or_else(|| gpus.first())
+        .map(|(path, _)| path.clone()))
 }
 
 /// Returns the paths of all available GPU devices
@@ -268,19 +464,28 @@ pub fn primary_gpu<S: AsRef<str>>(seat: S) -> io::Result<Option<PathBuf>> {
 /// Might be used for manual  [`DrmDevice`](crate::backend::drm::DrmDevice)
 /// initialization.
 pub fn all_gpus<S: AsRef<str>>(seat: S) -> io::Result<Vec<PathBuf>> {
-    let mut enumerator = Enumerator::new()?;
-    enumerator.match_subsystem("drm")?;
-    enumerator.match_sysname("card[0-9]*")?;
-    Ok(enumerator
-        .scan_devices()?
-        .filter(|device| {
-            device
-                .property_value("ID_SEAT")
-                .map(|x| x.to_os_string())
-                .unwrap_or_else(|| OsString::from("seat0"))
-                == *seat.as_ref()
-        })
-        .flat_map(|device| device.devnode().map(PathBuf::from))
+    all_gpus_with_filter(seat, |_| true)
+}
+
+/// Returns the paths of all available GPU devices accepted by a `filter`
+pub fn all_gpus_with_filter<S, F>(seat: S, filter: F) -> io::Result<Vec<PathBuf>>
+where
+    S: AsRef<str>,
+    F: FnMut(&DeviceProperties) -> bool,
+{
+    Ok(gpus_with_filter(seat.as_ref(), filter)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect())
+}
+
+fn gpus_with_filter<F>(seat: &str, mut filter: F) -> io::Result<Vec<(PathBuf, DeviceProperties)>>
+where
+    F: FnMut(&DeviceProperties) -> bool,
+{
+    Ok(scan_gpus(seat)?
+        .into_iter()
+        .filter(|(_, properties)| filter(properties))
         .collect())
 }
 
@@ -304,3 +509,76 @@ pub fn driver(dev: dev_t) -> io::Result<Option<OsString>> {
         })
         .next())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(id_path: Option<&str>, boot_vga: bool) -> DeviceProperties {
+        DeviceProperties {
+            seat: String::from("seat0"),
+            id_path: id_path.map(String::from),
+            sysname: String::from("card0"),
+            driver: None,
+            boot_vga,
+        }
+    }
+
+    #[test]
+    fn default_filter_matches_everything() {
+        let filter = DeviceFilter::default();
+        assert!(filter.matches(&properties(Some("pci-0000:00:02.0"), true)));
+        assert!(filter.matches(&properties(Some("pci-0000:01:00.0"), false)));
+        assert!(filter.matches(&properties(None, false)));
+    }
+
+    #[test]
+    fn filter_allow_list() {
+        let filter = DeviceFilter {
+            allow_id_paths: vec![String::from("pci-0000:00:02.0")],
+            ..Default::default()
+        };
+        assert!(filter.matches(&properties(Some("pci-0000:00:02.0"), false)));
+        assert!(!filter.matches(&properties(Some("pci-0000:01:00.0"), false)));
+        // devices without a persistent path can not be on the allow list
+        assert!(!filter.matches(&properties(None, false)));
+    }
+
+    #[test]
+    fn filter_deny_list() {
+        let filter = DeviceFilter {
+            allow_id_paths: vec![String::from("pci-0000:00:02.0"), String::from("pci-0000:01:00.0")],
+            deny_id_paths: vec![String::from("pci-0000:01:00.0")],
+            ..Default::default()
+        };
+        assert!(filter.matches(&properties(Some("pci-0000:00:02.0"), false)));
+        // the deny list takes precedence
+        assert!(!filter.matches(&properties(Some("pci-0000:01:00.0"), false)));
+
+        let filter = DeviceFilter {
+            deny_id_paths: vec![String::from("pci-0000:01:00.0")],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&properties(Some("pci-0000:01:00.0"), false)));
+        assert!(filter.matches(&properties(None, false)));
+    }
+
+    #[test]
+    fn filter_boot_vga() {
+        let filter = DeviceFilter {
+            boot_vga: Some(true),
+            ..Default::default()
+        };
+        assert!(filter.matches(&properties(Some("pci-0000:00:02.0"), true)));
+        assert!(!filter.matches(&properties(Some("pci-0000:01:00.0"), false)));
+
+        let filter = DeviceFilter {
+            deny_id_paths: vec![String::from("pci-0000:00:02.0")],
+            boot_vga: Some(false),
+            ..Default::default()
+        };
+        assert!(!filter.matches(&properties(Some("pci-0000:00:02.0"), false)));
+        assert!(!filter.matches(&properties(Some("pci-0000:01:00.0"), true)));
+        assert!(filter.matches(&properties(Some("pci-0000:01:00.0"), false)));
+    }
+}
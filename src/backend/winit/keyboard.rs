@@ -0,0 +1,314 @@
+//! Forwarding of the keymap and modifier state of the host
+//!
+//! winit does not expose the keymap of the host, so a second connection (X11) or a second
+//! `wl_keyboard` on the connection of winit (Wayland) is used to query it.
+
+use std::{cell::RefCell, fmt, os::raw::c_void, os::unix::io::RawFd, rc::Rc};
+
+use nix::{sys::uio::pread, unistd::close};
+use slog::{debug, warn};
+use wayland_client::{
+    protocol::{
+        wl_keyboard::{self, KeymapFormat},
+        wl_seat,
+    },
+    Display, EventQueue, GlobalManager, Main,
+};
+
+use super::WinitEvent;
+
+pub(super) enum HostKeyboard {
+    Wayland(WaylandKeyboard),
+    #[cfg(feature = "backend_x11")]
+    X11(Box<X11Keyboard>),
+}
+
+impl fmt::Debug for HostKeyboard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostKeyboard::Wayland(_) => f.debug_struct("HostKeyboard::Wayland").finish_non_exhaustive(),
+            #[cfg(feature = "backend_x11")]
+            HostKeyboard::X11(_) => f.debug_struct("HostKeyboard::X11").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl HostKeyboard {
+    /// Returns the events received since the last call
+    ///
+    /// `focused` is set if the window gained the keyboard focus since the last call.
+    #[cfg_attr(not(feature = "backend_x11"), allow(unused_variables))]
+    pub(super) fn dispatch(&mut self, focused: bool, log: &slog::Logger) -> Vec<WinitEvent> {
+        match self {
+            HostKeyboard::Wayland(keyboard) => keyboard.dispatch(log),
+            #[cfg(feature = "backend_x11")]
+            HostKeyboard::X11(keyboard) => keyboard.dispatch(focused, log),
+        }
+    }
+}
+
+pub(super) struct WaylandKeyboard {
+    queue: EventQueue,
+    events: Rc<RefCell<Vec<WinitEvent>>>,
+    _seat: Main<wl_seat::WlSeat>,
+}
+
+impl WaylandKeyboard {
+    /// Binds a keyboard on the connection of winit
+    ///
+    /// # Safety
+    ///
+    /// `display` has to be a valid `wl_display` pointer, that outlives the keyboard.
+    pub(super) unsafe fn new(display: *mut c_void, log: &slog::Logger) -> Option<WaylandKeyboard> {
+        let display = Display::from_external_display(display as *mut _);
+        let mut queue = display.create_event_queue();
+        let attached = (*display).clone().attach(queue.token());
+        let globals = GlobalManager::new(&attached);
+        queue.sync_roundtrip(&mut (), |_, _, _| {}).ok()?;
+
+        let seat = match globals.instantiate_range::<wl_seat::WlSeat>(1, 5) {
+            Ok(seat) => seat,
+            Err(err) => {
+                warn!(log, "Failed to bind the seat of the host: {:?}", err);
+                return None;
+            }
+        };
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let keyboard_events = events.clone();
+        let log = log.clone();
+        let mut keyboard = None;
+        seat.quick_assign(move |seat, event, _| {
+            if let wl_seat::Event::Capabilities { capabilities } = event {
+                if capabilities.contains(wl_seat::Capability::Keyboard) && keyboard.is_none() {
+                    let events = keyboard_events.clone();
+                    let log = log.clone();
+                    let new_keyboard = seat.get_keyboard();
+                    new_keyboard.quick_assign(move |_, event, _| match event {
+                        wl_keyboard::Event::Keymap { format, fd, size } => {
+                            match read_keymap(format, fd, size) {
+                                Some(keymap) => events.borrow_mut().push(WinitEvent::Keymap(keymap)),
+                                None => warn!(log, "Failed to read the keymap of the host"),
+                            }
+                        }
+                        wl_keyboard::Event::Modifiers {
+                            mods_depressed,
+                            mods_latched,
+                            mods_locked,
+                            group,
+                            ..
+                        } => {
+                            events.borrow_mut().push(WinitEvent::ModifiersChanged {
+                                depressed: mods_depressed,
+                                latched: mods_latched,
+                                locked: mods_locked,
+                                layout: group,
+                            });
+                        }
+                        _ => {}
+                    });
+                    keyboard = Some(new_keyboard);
+                }
+            }
+        });
+        // receive the capabilities and the initial keymap
+        queue.sync_roundtrip(&mut (), |_, _, _| {}).ok()?;
+
+        Some(WaylandKeyboard {
+            queue,
+            events,
+            _seat: seat,
+        })
+    }
+
+    fn dispatch(&mut self, log: &slog::Logger) -> Vec<WinitEvent> {
+        // the socket is read by winit, so only the already queued events have to be dispatched
+        if let Err(err) = self.queue.dispatch_pending(&mut (), |_, _, _| {}) {
+            debug!(log, "Failed to dispatch keyboard events of the host: {}", err);
+        }
+        self.events.borrow_mut().drain(..).collect()
+    }
+}
+
+fn read_keymap(format: KeymapFormat, fd: RawFd, size: u32) -> Option<String> {
+    let mut keymap = vec![0; size as usize];
+    // the fd might be shared with other clients, so do not move its offset
+    let read = if format == KeymapFormat::XkbV1 {
+        pread(fd, &mut keymap, 0).ok()
+    } else {
+        None
+    };
+    let _ = close(fd);
+    keymap.truncate(read?);
+    // the keymap is null-terminated
+    let end = keymap.iter().position(|byte| *byte == 0).unwrap_or(keymap.len());
+    keymap.truncate(end);
+    String::from_utf8(keymap).ok()
+}
+
+#[cfg(feature = "backend_x11")]
+pub(super) struct X11Keyboard {
+    connection: x11rb::rust_connection::RustConnection,
+    root: u32,
+    rules_names: u32,
+    keymap: Option<String>,
+    keymap_sent: bool,
+    xkb: bool,
+}
+
+#[cfg(feature = "backend_x11")]
+impl X11Keyboard {
+    /// Opens a second connection to the X server used by winit
+    pub(super) fn new(log: &slog::Logger) -> Option<X11Keyboard> {
+        use x11rb::{
+            connection::{Connection as _, RequestConnection as _},
+            protocol::{
+                xkb::{self, ConnectionExt as _},
+                xproto::{ChangeWindowAttributesAux, ConnectionExt as _, EventMask},
+            },
+        };
+
+        let (connection, screen) = match x11rb::rust_connection::RustConnection::connect(None) {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!(log, "Failed to connect to the X server: {}", err);
+                return None;
+            }
+        };
+        let root = connection.setup().roots[screen].root;
+
+        // the atom is created if it does not exist yet, so it can be watched for changes
+        let rules_names = match connection
+            .intern_atom(false, b"_XKB_RULES_NAMES")
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+        {
+            Some(reply) => reply.atom,
+            None => {
+                warn!(log, "Failed to intern the _XKB_RULES_NAMES atom");
+                return None;
+            }
+        };
+
+        let xkb = matches!(
+            connection.extension_information(xkb::X11_EXTENSION_NAME),
+            Ok(Some(_))
+        ) && connection
+            .xkb_use_extension(1, 0)
+            .ok()
+            .and_then(|cookie| cookie.reply().ok())
+            .map(|reply| reply.supported)
+            .unwrap_or(false);
+
+        // setxkbmap and friends load the new keymap and update the rule names afterwards,
+        // so both are watched to pick up the final state
+        let _ = connection.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+        );
+        if xkb {
+            let map_parts = xkb::MapPart::KEY_TYPES
+                | xkb::MapPart::KEY_SYMS
+                | xkb::MapPart::MODIFIER_MAP
+                | xkb::MapPart::EXPLICIT_COMPONENTS
+                | xkb::MapPart::KEY_ACTIONS
+                | xkb::MapPart::KEY_BEHAVIORS
+                | xkb::MapPart::VIRTUAL_MODS
+                | xkb::MapPart::VIRTUAL_MOD_MAP;
+            let _ = connection.xkb_select_events(
+                xkb::ID::USE_CORE_KBD.into(),
+                0u16,
+                xkb::EventType::NEW_KEYBOARD_NOTIFY | xkb::EventType::MAP_NOTIFY,
+                map_parts,
+                map_parts,
+                &Default::default(),
+            );
+        }
+        if let Err(err) = connection.flush() {
+            warn!(log, "Failed to select keymap changes of the X server: {}", err);
+        }
+
+        let mut keyboard = X11Keyboard {
+            connection,
+            root,
+            rules_names,
+            keymap: None,
+            keymap_sent: false,
+            xkb,
+        };
+        keyboard.keymap = keyboard.read_keymap();
+        if keyboard.keymap.is_none() {
+            warn!(log, "Failed to read the keymap of the X server");
+        }
+        Some(keyboard)
+    }
+
+    /// Compiles the keymap described by the rule names of the X server
+    fn read_keymap(&self) -> Option<String> {
+        use xkbcommon::xkb as xkbcommon;
+
+        let names = crate::backend::x11::xkb_rule_names(&self.connection, self.root, self.rules_names)
+            .ok()
+            .flatten()?;
+        let context = xkbcommon::Context::new(xkbcommon::CONTEXT_NO_FLAGS);
+        xkbcommon::Keymap::new_from_names(
+            &context,
+            &names.rules,
+            &names.model,
+            &names.layout,
+            &names.variant,
+            names.options,
+            xkbcommon::KEYMAP_COMPILE_NO_FLAGS,
+        )
+        .map(|keymap| keymap.get_as_string(xkbcommon::KEYMAP_FORMAT_TEXT_V1))
+    }
+
+    fn dispatch(&mut self, focused: bool, log: &slog::Logger) -> Vec<WinitEvent> {
+        use x11rb::{connection::Connection as _, protocol::Event};
+
+        let mut keymap_changed = false;
+        loop {
+            match self.connection.poll_for_event() {
+                Ok(Some(Event::XkbNewKeyboardNotify(_))) | Ok(Some(Event::XkbMapNotify(_))) => {
+                    keymap_changed = true
+                }
+                Ok(Some(Event::PropertyNotify(event))) if event.atom == self.rules_names => {
+                    keymap_changed = true
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(err) => {
+                    debug!(log, "Failed to receive keyboard events of the X server: {}", err);
+                    break;
+                }
+            }
+        }
+        if keymap_changed {
+            let keymap = self.read_keymap();
+            if keymap.is_some() && keymap != self.keymap {
+                self.keymap = keymap;
+                self.keymap_sent = false;
+            }
+        }
+
+        let mut events = Vec::new();
+        if !self.keymap_sent {
+            if let Some(keymap) = self.keymap.clone() {
+                events.push(WinitEvent::Keymap(keymap));
+            }
+            self.keymap_sent = true;
+        }
+        if focused && self.xkb {
+            match crate::backend::x11::xkb_modifier_masks(&self.connection) {
+                Ok((depressed, latched, locked, layout)) => events.push(WinitEvent::ModifiersChanged {
+                    depressed,
+                    latched,
+                    locked,
+                    layout,
+                }),
+                Err(err) => debug!(log, "Failed to query the modifier state: {}", err),
+            }
+        }
+        events
+    }
+}
@@ -20,6 +20,7 @@
 //! two traits for the winit backend.
 
 mod input;
+mod keyboard;
 
 use crate::{
     backend::{
@@ -31,6 +32,7 @@ use crate::{
             gles2::{Gles2Error, Gles2Renderer},
             Bind,
         },
+        CompositorSurface, SwapBuffersError,
     },
     utils::{Logical, Physical, Rectangle, Size},
 };
@@ -111,6 +113,7 @@ pub struct WinitEventLoop {
     resize_notification: Rc<Cell<Option<Size<i32, Physical>>>>,
     /// Whether winit is using Wayland or X11 as it's backend.
     is_x11: bool,
+    host_keyboard: Option<keyboard::HostKeyboard>,
 }
 
 /// Create a new [`WinitGraphicsBackend`], which implements the
@@ -221,6 +224,21 @@ where
         scale_factor: winit_window.scale_factor(),
     }));
 
+    let host_keyboard = match winit_window.wayland_display() {
+        Some(display) => {
+            unsafe { keyboard::WaylandKeyboard::new(display, &log) }.map(keyboard::HostKeyboard::Wayland)
+        }
+        #[cfg(feature = "backend_x11")]
+        None => {
+            keyboard::X11Keyboard::new(&log).map(|keyboard| keyboard::HostKeyboard::X11(Box::new(keyboard)))
+        }
+        #[cfg(not(feature = "backend_x11"))]
+        None => None,
+    };
+    if host_keyboard.is_none() {
+        info!(log, "The keymap of the host is not forwarded");
+    }
+
     let window = Rc::new(winit_window);
     let egl = Rc::new(surface);
     let renderer = unsafe { Gles2Renderer::new(context, log.clone())? };
@@ -250,6 +268,7 @@ where
             logger: log.new(o!("smithay_winit_component" => "event_loop")),
             size,
             is_x11,
+            host_keyboard,
         },
     ))
 }
@@ -271,6 +290,31 @@ pub enum WinitEvent {
     /// An input event occurred.
     Input(InputEvent<WinitInput>),
 
+    /// The keymap of the host, in the xkb text format
+    ///
+    /// Sent once after initialization and whenever the host changes the keymap.
+    /// Can be forwarded to
+    /// [`KeyboardHandle::set_keymap_from_string`](crate::wayland::seat::KeyboardHandle::set_keymap_from_string).
+    /// Only supported on X11 hosts, if the `backend_x11` feature is enabled, and on Wayland hosts.
+    /// On X11 hosts the keymap is compiled from the rule names of the X server (as set by
+    /// `setxkbmap`), so keymaps uploaded directly (e.g. with `xkbcomp`) are not picked up.
+    Keymap(String),
+
+    /// The modifier state of the host changed
+    ///
+    /// Contains the serialized xkb modifier masks, which can be forwarded to
+    /// [`KeyboardHandle::set_modifier_masks`](crate::wayland::seat::KeyboardHandle::set_modifier_masks).
+    ModifiersChanged {
+        /// Depressed modifiers
+        depressed: u32,
+        /// Latched modifiers
+        latched: u32,
+        /// Locked modifiers
+        locked: u32,
+        /// Locked layout
+        layout: u32,
+    },
+
     /// A redraw was requested
     Refresh,
 }
@@ -354,6 +398,43 @@ impl WinitGraphicsBackend {
     }
 }
 
+impl CompositorSurface for WinitGraphicsBackend {
+    type Buffer = ();
+    type Error = SwapBuffersError;
+
+    /// Binds the window to the renderer, see [`WinitGraphicsBackend::bind`].
+    fn next_buffer(&mut self) -> Result<(&(), u8), SwapBuffersError> {
+        self.bind()?;
+        let age = self.buffer_age().unwrap_or(0).min(u8::MAX as usize) as u8;
+        Ok((&(), age))
+    }
+
+    fn present(&mut self, damage: Option<&[Rectangle<i32, Physical>]>) -> Result<(), SwapBuffersError> {
+        let mut damage = match damage {
+            Some(damage) if self.damage_tracking && !damage.is_empty() => {
+                let height = self.size.borrow().physical_size.h;
+                // egl expects the origin in the bottom left corner
+                let damage = damage
+                    .iter()
+                    .map(|rect| {
+                        Rectangle::from_loc_and_size(
+                            (rect.loc.x, height - rect.loc.y - rect.size.h),
+                            rect.size,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                Some(damage)
+            }
+            _ => None,
+        };
+        self.egl.swap_buffers(damage.as_deref_mut())?;
+        Ok(())
+    }
+
+    /// Buffer ages are tracked by egl, so this does nothing.
+    fn reset_buffers(&mut self) {}
+}
+
 /// Errors that may happen when driving a [`WinitEventLoop`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, thiserror::Error)]
 pub enum WinitError {
@@ -383,8 +464,11 @@ impl WinitEventLoop {
         use self::WinitEvent::*;
 
         let mut closed = false;
+        let mut focused = false;
 
         {
+            let callback = &mut callback;
+            let focused = &mut focused;
             // NOTE: This ugly pile of references is here, because rustc could not
             // figure out how to reference all these objects correctly into the
             // upcoming closure, which is why all are borrowed manually and the
@@ -435,6 +519,7 @@ impl WinitEventLoop {
                                 });
                             }
                             WindowEvent::Focused(focus) => {
+                                *focused |= focus;
                                 callback(WinitEvent::Focus(focus));
                             }
 
@@ -573,6 +658,12 @@ impl WinitEventLoop {
                 });
         }
 
+        if let Some(host_keyboard) = self.host_keyboard.as_mut() {
+            for event in host_keyboard.dispatch(focused, &self.logger) {
+                callback(event);
+            }
+        }
+
         if closed {
             Err(WinitError::WindowClosed)
         } else {
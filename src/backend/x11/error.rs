@@ -4,7 +4,9 @@ use gbm::DeviceDestroyedError;
 use nix::errno::Errno;
 use x11rb::rust_connection::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
 
-use crate::backend::{allocator::gbm::GbmConvertError, drm::CreateDrmNodeError};
+use crate::backend::{
+    allocator::gbm::GbmConvertError, drm::CreateDrmNodeError, SwapBuffersError, TemporaryFailureReason,
+};
 
 use super::PresentError;
 
@@ -67,6 +69,18 @@ impl From<ConnectionError> for X11Error {
     }
 }
 
+impl From<X11Error> for SwapBuffersError {
+    fn from(err: X11Error) -> SwapBuffersError {
+        match err {
+            x @ X11Error::Allocation(AllocateBuffersError::NoFreeSlots) => {
+                SwapBuffersError::temporary_failure(TemporaryFailureReason::Busy, x)
+            }
+            x @ X11Error::Present(_) => SwapBuffersError::temporary_failure(TemporaryFailureReason::Other, x),
+            x => SwapBuffersError::context_lost(x),
+        }
+    }
+}
+
 /// An error that occurs when a required X11 extension is not present.
 #[derive(Debug, thiserror::Error)]
 pub enum MissingExtensionError {
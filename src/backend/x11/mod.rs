@@ -110,11 +110,14 @@ use std::{
 };
 use x11rb::{
     atom_manager,
-    connection::Connection,
+    connection::{Connection, RequestConnection as _},
     protocol::{
         self as x11,
         dri3::ConnectionExt as _,
-        xproto::{ColormapAlloc, ConnectionExt, CreateWindowAux, VisualClass, WindowClass, WindowWrapper},
+        xkb::{self, ConnectionExt as _},
+        xproto::{
+            AtomEnum, ColormapAlloc, ConnectionExt, CreateWindowAux, VisualClass, WindowClass, WindowWrapper,
+        },
         ErrorKind,
     },
     rust_connection::{ReplyError, RustConnection},
@@ -159,6 +162,25 @@ pub enum X11Event {
         /// XID of the window
         window_id: u32,
     },
+
+    /// The modifier state of the X server changed while the window was not focused.
+    ///
+    /// This is sent whenever the window gains the keyboard focus and contains the serialized xkb
+    /// modifier masks, which can be forwarded to
+    /// [`KeyboardHandle::set_modifier_masks`](crate::wayland::seat::KeyboardHandle::set_modifier_masks).
+    /// Requires the XKB extension of the X server.
+    ModifiersChanged {
+        /// Depressed modifiers
+        depressed: u32,
+        /// Latched modifiers
+        latched: u32,
+        /// Locked modifiers
+        locked: u32,
+        /// Locked layout
+        layout: u32,
+        /// XID of the window
+        window_id: u32,
+    },
 }
 
 /// Represents an active connection to the X to manage events on the Window provided by the backend.
@@ -185,6 +207,17 @@ impl X11Backend {
         info!(logger, "Connected to screen {}", screen_number);
 
         let extensions = Extensions::check_extensions(&*connection, &logger)?;
+        // XKB is only used to synchronize the modifier state, so it is not required
+        let xkb = connection
+            .extension_information(xkb::X11_EXTENSION_NAME)?
+            .is_some()
+            && connection.xkb_use_extension(1, 0)?.reply()?.supported;
+        if !xkb {
+            info!(
+                logger,
+                "XKB extension is not available, modifiers will not be synchronized"
+            );
+        }
 
         let screen = &connection.setup().roots[screen_number];
 
@@ -257,6 +290,7 @@ impl X11Backend {
             depth,
             visual_id,
             devices: false,
+            xkb,
         };
 
         Ok(X11Backend {
@@ -404,6 +438,25 @@ impl X11Handle {
         })
     }
 
+    /// Returns the keymap description (RMLVO) the X server is using.
+    ///
+    /// This is read from the `_XKB_RULES_NAMES` property of the root window, which is set by the
+    /// X server and tools like `setxkbmap`. Returns `None` if the property is not set.
+    ///
+    /// Nested compositors can use this to make keys match the layout of the host, see
+    /// [`KeyboardHandle::set_xkb_config`](crate::wayland::seat::KeyboardHandle::set_xkb_config).
+    pub fn xkb_rule_names(&self) -> Result<Option<XkbRuleNames>, X11Error> {
+        let (root, atom) = {
+            let inner = self.inner.lock().unwrap();
+            (
+                self.connection.setup().roots[inner.screen_number].root,
+                inner.atoms._XKB_RULES_NAMES,
+            )
+        };
+
+        xkb_rule_names(&*self.connection, root, atom)
+    }
+
     /// Get a temporary reference to a window by its XID
     pub fn window_ref_from_id(&self, id: u32) -> Option<impl AsRef<Window> + '_> {
         X11Inner::window_ref_from_id(&self.inner, &id)
@@ -413,6 +466,81 @@ impl X11Handle {
     }
 }
 
+/// Reads the keymap description from the `_XKB_RULES_NAMES` property of the root window
+pub(crate) fn xkb_rule_names<C: Connection>(
+    connection: &C,
+    root: u32,
+    atom: u32,
+) -> Result<Option<XkbRuleNames>, X11Error> {
+    let reply = connection
+        .get_property(false, root, atom, AtomEnum::STRING, 0, 1024)?
+        .reply()?;
+
+    if reply.format != 8 || reply.value.is_empty() {
+        return Ok(None);
+    }
+
+    // The property consists of the null-separated rules, model, layout, variant and options.
+    let mut names = reply
+        .value
+        .split(|byte| *byte == 0)
+        .map(|name| String::from_utf8_lossy(name).into_owned());
+    let mut next = || names.next().unwrap_or_default();
+
+    Ok(Some(XkbRuleNames {
+        rules: next(),
+        model: next(),
+        layout: next(),
+        variant: next(),
+        options: Some(next()).filter(|options| !options.is_empty()),
+    }))
+}
+
+/// Queries the modifier state of the core keyboard as serialized xkb masks
+///
+/// The eight real modifiers of X11 always occupy the first eight modifier indices of
+/// xkbcommon keymaps, so the masks can be used as is.
+pub(crate) fn xkb_modifier_masks<C: Connection>(connection: &C) -> Result<(u32, u32, u32, u32), X11Error> {
+    let state = connection.xkb_get_state(xkb::ID::USE_CORE_KBD.into())?.reply()?;
+    Ok((
+        state.base_mods.into(),
+        state.latched_mods.into(),
+        state.locked_mods.into(),
+        u8::from(state.locked_group).into(),
+    ))
+}
+
+/// The keymap description (rules, model, layout, variant and options) used by the X server.
+///
+/// See [`X11Handle::xkb_rule_names`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct XkbRuleNames {
+    /// The rules file in use
+    pub rules: String,
+    /// The keyboard model
+    pub model: String,
+    /// A comma separated list of layouts
+    pub layout: String,
+    /// A comma separated list of variants, one per layout
+    pub variant: String,
+    /// A comma separated list of options, if any
+    pub options: Option<String>,
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl XkbRuleNames {
+    /// Returns a [`XkbConfig`](crate::wayland::seat::XkbConfig) describing the same keymap.
+    pub fn as_xkb_config(&self) -> crate::wayland::seat::XkbConfig<'_> {
+        crate::wayland::seat::XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout: &self.layout,
+            variant: &self.variant,
+            options: self.options.clone(),
+        }
+    }
+}
+
 /// Builder used to construct a window.
 #[derive(Debug)]
 pub struct WindowBuilder<'a> {
@@ -593,6 +721,7 @@ atom_manager! {
         _NET_WM_NAME,
         UTF8_STRING,
         _SMITHAY_X11_BACKEND_CLOSE,
+        _XKB_RULES_NAMES,
     }
 }
 
@@ -610,6 +739,7 @@ pub(crate) struct X11Inner {
     depth: x11::xproto::Depth,
     visual_id: u32,
     devices: bool,
+    xkb: bool,
 }
 
 impl X11Inner {
@@ -862,6 +992,28 @@ impl X11Inner {
                 }
             }
 
+            x11::Event::FocusIn(focus_in) => {
+                let (connection, xkb) = {
+                    let inner = inner.lock().unwrap();
+                    (inner.connection.clone(), inner.xkb)
+                };
+                if xkb && X11Inner::window_ref_from_id(inner, &focus_in.event).is_some() {
+                    match xkb_modifier_masks(&*connection) {
+                        Ok((depressed, latched, locked, layout)) => (callback)(
+                            X11Event::ModifiersChanged {
+                                depressed,
+                                latched,
+                                locked,
+                                layout,
+                                window_id: focus_in.event,
+                            },
+                            &mut (),
+                        ),
+                        Err(err) => error!(log, "Failed to query the modifier state: {}", err),
+                    }
+                }
+            }
+
             x11::Event::LeaveNotify(leave_notify) => {
                 if let Some(window) =
                     X11Inner::window_ref_from_id(inner, &leave_notify.event).and_then(|w| w.upgrade())
@@ -14,8 +14,9 @@ use crate::{
             Allocator, Slot, Swapchain,
         },
         x11::{buffer::PixmapWrapperExt, window_inner::WindowInner, AllocateBuffersError, Window},
+        CompositorSurface,
     },
-    utils::{Logical, Size},
+    utils::{Logical, Physical, Rectangle, Size},
 };
 
 use super::{WindowTemporary, X11Error};
@@ -140,3 +141,25 @@ impl X11Surface {
         self.height = size.h;
     }
 }
+
+impl CompositorSurface for X11Surface {
+    type Buffer = Dmabuf;
+    type Error = X11Error;
+
+    fn next_buffer(&mut self) -> Result<(&Dmabuf, u8), X11Error> {
+        let (_, age) = self.buffer()?;
+        let slot = self.buffer.as_ref().unwrap();
+        Ok((slot.userdata().get::<Dmabuf>().unwrap(), age))
+    }
+
+    /// Submits the buffer, see [`X11Surface::submit`].
+    ///
+    /// The whole window is presented, so `damage` is ignored.
+    fn present(&mut self, _damage: Option<&[Rectangle<i32, Physical>]>) -> Result<(), X11Error> {
+        self.submit()
+    }
+
+    fn reset_buffers(&mut self) {
+        X11Surface::reset_buffers(self)
+    }
+}
@@ -0,0 +1,495 @@
+//! Hot corners and screen edge actions
+//!
+//! [`ScreenEdges`] watches the pointer and touch points and returns a compositor-defined action,
+//! once they trigger one of the configured [`EdgeTrigger`]s. Only edges of the desktop count,
+//! edges between two outputs can be crossed without triggering anything.
+//!
+//! Pointers trigger an edge, after resting in it for the dwell time of the trigger and after
+//! having been pushed beyond it by the pressure of the trigger. Compositors usually keep the
+//! pointer inside of the desktop, which acts like a barrier, so pass the part of the motion
+//! swallowed by it to [`ScreenEdges::pointer_motion`]. Touch points trigger an edge by swiping
+//! in from it.
+//!
+//! ```no_run
+//! # use smithay::desktop::Space;
+//! # use std::time::{Duration, Instant};
+//! use smithay::desktop::edges::{EdgeConfig, EdgeTrigger, ScreenEdge, ScreenEdges};
+//!
+//! #[derive(Clone)]
+//! enum Action {
+//!     Overview,
+//!     Launcher,
+//! }
+//!
+//! # let space: Space = unimplemented!();
+//! let mut edges = ScreenEdges::new(EdgeConfig::default());
+//! edges.add_trigger(EdgeTrigger::new(ScreenEdge::TopLeft, Action::Overview));
+//! edges.add_trigger(EdgeTrigger {
+//!     dwell: Duration::from_millis(300),
+//!     pressure: 100.0,
+//!     ..EdgeTrigger::new(ScreenEdge::Bottom, Action::Launcher)
+//! });
+//!
+//! // on every pointer motion
+//! # let (location, blocked) = ((0.0, 0.0).into(), (0.0, 0.0).into());
+//! if let Some(action) = edges.pointer_motion(&space, location, blocked, Instant::now()) {
+//!     // run the action like a key binding
+//! }
+//! // and whenever the deadline passed, e.g. using a timer
+//! if let Some(deadline) = edges.next_deadline() {
+//!     # let now = deadline;
+//!     if let Some(action) = edges.poll(now) {
+//!         // run the action
+//!     }
+//! }
+//! ```
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    backend::input::TouchSlot,
+    desktop::Space,
+    utils::{Logical, Point, Rectangle},
+};
+
+/// Corner or edge of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScreenEdge {
+    /// Top left corner
+    TopLeft,
+    /// Top edge
+    Top,
+    /// Top right corner
+    TopRight,
+    /// Right edge
+    Right,
+    /// Bottom right corner
+    BottomRight,
+    /// Bottom edge
+    Bottom,
+    /// Bottom left corner
+    BottomLeft,
+    /// Left edge
+    Left,
+}
+
+impl ScreenEdge {
+    /// Returns whether this is a corner
+    pub fn is_corner(self) -> bool {
+        matches!(
+            self,
+            ScreenEdge::TopLeft | ScreenEdge::TopRight | ScreenEdge::BottomRight | ScreenEdge::BottomLeft
+        )
+    }
+
+    /// Direction pointing out of the output, with components of -1, 0 or 1
+    fn outward(self) -> (f64, f64) {
+        match self {
+            ScreenEdge::TopLeft => (-1.0, -1.0),
+            ScreenEdge::Top => (0.0, -1.0),
+            ScreenEdge::TopRight => (1.0, -1.0),
+            ScreenEdge::Right => (1.0, 0.0),
+            ScreenEdge::BottomRight => (1.0, 1.0),
+            ScreenEdge::Bottom => (0.0, 1.0),
+            ScreenEdge::BottomLeft => (-1.0, 1.0),
+            ScreenEdge::Left => (-1.0, 0.0),
+        }
+    }
+
+    /// Returns the corner or edge of `area` the point is in
+    ///
+    /// Edges are `edge_size` wide, corners extend `corner_size` along both of their edges.
+    fn at(
+        area: Rectangle<i32, Logical>,
+        point: Point<f64, Logical>,
+        edge_size: f64,
+        corner_size: f64,
+    ) -> Option<ScreenEdge> {
+        let area = area.to_f64();
+        if !area.contains(point) {
+            return None;
+        }
+        let left = point.x - area.loc.x;
+        let right = area.loc.x + area.size.w - point.x;
+        let top = point.y - area.loc.y;
+        let bottom = area.loc.y + area.size.h - point.y;
+
+        let horizontal = if left <= edge_size {
+            Some(ScreenEdge::Left)
+        } else if right <= edge_size {
+            Some(ScreenEdge::Right)
+        } else {
+            None
+        };
+        let vertical = if top <= edge_size {
+            Some(ScreenEdge::Top)
+        } else if bottom <= edge_size {
+            Some(ScreenEdge::Bottom)
+        } else {
+            None
+        };
+        let near_left = left <= corner_size;
+        let near_right = right <= corner_size;
+        let near_top = top <= corner_size;
+        let near_bottom = bottom <= corner_size;
+
+        if horizontal.is_none() && vertical.is_none() {
+            return None;
+        }
+        let corner = match (
+            near_left || horizontal == Some(ScreenEdge::Left),
+            near_right || horizontal == Some(ScreenEdge::Right),
+            near_top || vertical == Some(ScreenEdge::Top),
+            near_bottom || vertical == Some(ScreenEdge::Bottom),
+        ) {
+            (true, _, true, _) => Some(ScreenEdge::TopLeft),
+            (_, true, true, _) => Some(ScreenEdge::TopRight),
+            (_, true, _, true) => Some(ScreenEdge::BottomRight),
+            (true, _, _, true) => Some(ScreenEdge::BottomLeft),
+            _ => None,
+        };
+        corner.or(horizontal).or(vertical)
+    }
+
+    /// Returns the points just outside of `area` beyond this edge, at the given point
+    fn beyond(self, area: Rectangle<i32, Logical>, point: Point<f64, Logical>) -> Vec<Point<f64, Logical>> {
+        let area = area.to_f64();
+        let (dx, dy) = self.outward();
+        let x = match dx as i32 {
+            -1 => area.loc.x - 1.0,
+            1 => area.loc.x + area.size.w + 1.0,
+            _ => point.x,
+        };
+        let y = match dy as i32 {
+            -1 => area.loc.y - 1.0,
+            1 => area.loc.y + area.size.h + 1.0,
+            _ => point.y,
+        };
+        if self.is_corner() {
+            vec![(x, point.y).into(), (point.x, y).into(), (x, y).into()]
+        } else {
+            vec![(x, y).into()]
+        }
+    }
+}
+
+/// A corner or edge, that triggers an action
+#[derive(Debug, Clone)]
+pub struct EdgeTrigger<A> {
+    /// The corner or edge
+    pub edge: ScreenEdge,
+    /// Name of the output, whose edge triggers, or `None` for all outputs
+    pub output: Option<String>,
+    /// Time the pointer has to rest in the edge, zero by default
+    pub dwell: Duration,
+    /// Distance in logical pixels the pointer has to be pushed beyond the edge, zero by default
+    pub pressure: f64,
+    /// Distance in logical pixels a touch point starting in the edge has to swipe in,
+    /// or `None` if touch does not trigger, which is the default
+    pub touch_distance: Option<f64>,
+    /// The action returned, when triggered
+    pub action: A,
+}
+
+impl<A> EdgeTrigger<A> {
+    /// Creates a trigger firing right away, when the pointer enters `edge` of any output
+    pub fn new(edge: ScreenEdge, action: A) -> EdgeTrigger<A> {
+        EdgeTrigger {
+            edge,
+            output: None,
+            dwell: Duration::ZERO,
+            pressure: 0.0,
+            touch_distance: None,
+            action,
+        }
+    }
+}
+
+/// Size of the areas of outputs, that count as corners and edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeConfig {
+    /// Width of the edges in logical pixels for the pointer
+    pub edge_size: f64,
+    /// Length in logical pixels of the corners along each edge for the pointer
+    pub corner_size: f64,
+    /// Width of the edges and length of the corners in logical pixels for touch points
+    pub touch_edge_size: f64,
+}
+
+impl Default for EdgeConfig {
+    fn default() -> Self {
+        EdgeConfig {
+            edge_size: 1.0,
+            corner_size: 8.0,
+            touch_edge_size: 16.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PointerState {
+    trigger: usize,
+    entered: Instant,
+    pressure: f64,
+    fired: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TouchState {
+    trigger: usize,
+    start: Point<f64, Logical>,
+    fired: bool,
+}
+
+/// Tracks the pointer and touch points in the corners and edges of the outputs of a [`Space`]
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug)]
+pub struct ScreenEdges<A> {
+    config: EdgeConfig,
+    triggers: Vec<EdgeTrigger<A>>,
+    pointer: Option<PointerState>,
+    touches: HashMap<TouchSlot, TouchState>,
+}
+
+impl<A: Clone> ScreenEdges<A> {
+    /// Creates a new tracker without any triggers
+    pub fn new(config: EdgeConfig) -> ScreenEdges<A> {
+        ScreenEdges {
+            config,
+            triggers: Vec::new(),
+            pointer: None,
+            touches: HashMap::new(),
+        }
+    }
+
+    /// Adds a trigger
+    ///
+    /// If several triggers share an edge and output, the first one added is used.
+    pub fn add_trigger(&mut self, trigger: EdgeTrigger<A>) {
+        self.triggers.push(trigger);
+    }
+
+    /// Removes all triggers
+    pub fn clear_triggers(&mut self) {
+        self.triggers.clear();
+        self.pointer = None;
+        self.touches.clear();
+    }
+
+    /// Returns the configured triggers
+    pub fn triggers(&self) -> &[EdgeTrigger<A>] {
+        &self.triggers
+    }
+
+    /// Returns the index of the trigger for the outer corner or edge of an output under the point
+    fn trigger_at(
+        &self,
+        space: &Space,
+        point: Point<f64, Logical>,
+        edge_size: f64,
+        corner_size: f64,
+    ) -> Option<usize> {
+        let output = space.output_under(point).next()?;
+        let area = space.output_geometry(output)?;
+        let edge = ScreenEdge::at(area, point, edge_size, corner_size)?;
+        // edges next to other outputs can be crossed
+        let outer = edge
+            .beyond(area, point)
+            .into_iter()
+            .all(|beyond| space.output_under(beyond).next().is_none());
+        if !outer {
+            return None;
+        }
+        let name = output.name();
+        self.triggers.iter().position(|trigger| {
+            trigger.edge == edge && trigger.output.as_ref().map(|o| *o == name).unwrap_or(true)
+        })
+    }
+
+    /// Handles a motion of the pointer to `location`
+    ///
+    /// `blocked` is the part of the motion, that did not move the pointer, because it was kept
+    /// inside of the desktop, e.g. the requested location minus the clamped `location`.
+    /// Returns the action of a trigger, if the pointer triggered one.
+    pub fn pointer_motion(
+        &mut self,
+        space: &Space,
+        location: Point<f64, Logical>,
+        blocked: Point<f64, Logical>,
+        time: Instant,
+    ) -> Option<A> {
+        let trigger = self.trigger_at(space, location, self.config.edge_size, self.config.corner_size);
+        self.update_pointer(trigger, blocked, time)
+    }
+
+    fn update_pointer(
+        &mut self,
+        trigger: Option<usize>,
+        blocked: Point<f64, Logical>,
+        time: Instant,
+    ) -> Option<A> {
+        let trigger = match trigger {
+            Some(trigger) => trigger,
+            None => {
+                self.pointer = None;
+                return None;
+            }
+        };
+        let (dx, dy) = self.triggers[trigger].edge.outward();
+        let pushed = (blocked.x * dx).max(0.0) + (blocked.y * dy).max(0.0);
+        match self.pointer {
+            Some(ref mut state) if state.trigger == trigger => state.pressure += pushed,
+            _ => {
+                self.pointer = Some(PointerState {
+                    trigger,
+                    entered: time,
+                    pressure: pushed,
+                    fired: false,
+                })
+            }
+        }
+        self.poll(time)
+    }
+
+    /// Handles the pointer leaving the desktop, e.g. because all outputs were removed
+    pub fn pointer_reset(&mut self) {
+        self.pointer = None;
+    }
+
+    /// Returns the action of a trigger, if the pointer rested long enough in its edge
+    pub fn poll(&mut self, time: Instant) -> Option<A> {
+        let state = self.pointer.as_mut()?;
+        let trigger = &self.triggers[state.trigger];
+        if state.fired
+            || state.pressure < trigger.pressure
+            || time.saturating_duration_since(state.entered) < trigger.dwell
+        {
+            return None;
+        }
+        state.fired = true;
+        Some(trigger.action.clone())
+    }
+
+    /// Returns when [`ScreenEdges::poll`] needs to be called next, if the pointer is waiting
+    /// for the dwell time of a trigger to pass
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let state = self.pointer.as_ref()?;
+        let trigger = &self.triggers[state.trigger];
+        if state.fired || state.pressure < trigger.pressure {
+            return None;
+        }
+        Some(state.entered + trigger.dwell)
+    }
+
+    /// Handles a new touch point
+    pub fn touch_down(&mut self, space: &Space, slot: TouchSlot, location: Point<f64, Logical>) {
+        let size = self.config.touch_edge_size;
+        match self.trigger_at(space, location, size, size) {
+            Some(trigger) if self.triggers[trigger].touch_distance.is_some() => {
+                self.touches.insert(
+                    slot,
+                    TouchState {
+                        trigger,
+                        start: location,
+                        fired: false,
+                    },
+                );
+            }
+            _ => {
+                self.touches.remove(&slot);
+            }
+        }
+    }
+
+    /// Handles a motion of a touch point
+    ///
+    /// Returns the action of a trigger, if the touch point swiped in far enough from its edge.
+    pub fn touch_motion(&mut self, slot: TouchSlot, location: Point<f64, Logical>) -> Option<A> {
+        let state = self.touches.get_mut(&slot)?;
+        let trigger = &self.triggers[state.trigger];
+        let distance = trigger.touch_distance?;
+        let (dx, dy) = trigger.edge.outward();
+        let swiped = (state.start.x - location.x) * dx + (state.start.y - location.y) * dy;
+        if state.fired || swiped < distance {
+            return None;
+        }
+        state.fired = true;
+        Some(trigger.action.clone())
+    }
+
+    /// Handles a touch point being lifted
+    pub fn touch_up(&mut self, slot: TouchSlot) {
+        self.touches.remove(&slot);
+    }
+
+    /// Handles all touch points being cancelled
+    pub fn touch_cancel(&mut self) {
+        self.touches.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area() -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((100, 0), (1000, 800))
+    }
+
+    fn edge(x: f64, y: f64) -> Option<ScreenEdge> {
+        ScreenEdge::at(area(), (x, y).into(), 1.0, 8.0)
+    }
+
+    #[test]
+    fn edges_and_corners() {
+        assert_eq!(edge(500.0, 400.0), None);
+        assert_eq!(edge(100.0, 400.0), Some(ScreenEdge::Left));
+        assert_eq!(edge(1099.5, 400.0), Some(ScreenEdge::Right));
+        assert_eq!(edge(500.0, 0.0), Some(ScreenEdge::Top));
+        assert_eq!(edge(500.0, 799.0), Some(ScreenEdge::Bottom));
+        // corners extend along the edges
+        assert_eq!(edge(100.0, 0.0), Some(ScreenEdge::TopLeft));
+        assert_eq!(edge(106.0, 0.0), Some(ScreenEdge::TopLeft));
+        assert_eq!(edge(100.0, 6.0), Some(ScreenEdge::TopLeft));
+        assert_eq!(edge(1099.5, 795.0), Some(ScreenEdge::BottomRight));
+        // but only at the edges
+        assert_eq!(edge(104.0, 4.0), None);
+        assert_eq!(edge(50.0, 0.0), None);
+    }
+
+    #[test]
+    fn dwell_and_pressure() {
+        let mut edges = ScreenEdges::new(EdgeConfig::default());
+        edges.add_trigger(EdgeTrigger::new(ScreenEdge::TopLeft, 1));
+        edges.add_trigger(EdgeTrigger {
+            dwell: Duration::from_millis(100),
+            pressure: 50.0,
+            ..EdgeTrigger::new(ScreenEdge::Bottom, 2)
+        });
+        let now = Instant::now();
+        let ms = |ms| now + Duration::from_millis(ms);
+
+        assert_eq!(edges.update_pointer(Some(0), (0.0, 0.0).into(), now), Some(1));
+        // only once per visit
+        assert_eq!(edges.update_pointer(Some(0), (-5.0, 0.0).into(), ms(10)), None);
+
+        // pushing sideways or away does not count
+        assert_eq!(edges.update_pointer(Some(1), (30.0, -30.0).into(), ms(20)), None);
+        assert_eq!(edges.update_pointer(Some(1), (0.0, 40.0).into(), ms(30)), None);
+        assert_eq!(edges.next_deadline(), None);
+        assert_eq!(edges.update_pointer(Some(1), (0.0, 20.0).into(), ms(40)), None);
+        assert_eq!(edges.next_deadline(), Some(ms(120)));
+        assert_eq!(edges.poll(ms(100)), None);
+        assert_eq!(edges.poll(ms(120)), Some(2));
+        assert_eq!(edges.next_deadline(), None);
+
+        // leaving the edge resets the pressure
+        edges.update_pointer(None, (0.0, 0.0).into(), ms(130));
+        assert_eq!(edges.update_pointer(Some(1), (0.0, 40.0).into(), ms(140)), None);
+        assert_eq!(edges.poll(ms(300)), None);
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use crate::utils::{Buffer, Size};
+
+/// Icon of a [`Window`](super::Window), e.g. for taskbars and window switchers
+///
+/// Set by the compositor with [`Window::set_icon`](super::Window::set_icon), e.g. from the
+/// `_NET_WM_ICON` property of X11 windows using [`WindowIcon::from_net_wm_icon`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowIcon {
+    /// Name of the icon in the icon theme, preferred over the buffers if it can be found
+    pub name: Option<String>,
+    /// Images of the icon in different sizes
+    pub buffers: Vec<IconBuffer>,
+}
+
+/// Image of a [`WindowIcon`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconBuffer {
+    /// Size of the image in pixels
+    pub size: Size<i32, Buffer>,
+    /// Scale the image is meant for
+    pub scale: i32,
+    /// Pixels in premultiplied `Argb8888`, like `wl_shm`, row by row without padding
+    pub data: Arc<[u8]>,
+}
+
+impl WindowIcon {
+    /// Decodes the value of the `_NET_WM_ICON` property of an X11 window
+    ///
+    /// The property holds any number of images, each consisting of its width and height followed
+    /// by its non-premultiplied ARGB pixels. Returns `None` if it holds no valid image.
+    pub fn from_net_wm_icon(mut data: &[u32]) -> Option<WindowIcon> {
+        let mut buffers = Vec::new();
+        while data.len() >= 2 {
+            let (width, height) = (data[0] as usize, data[1] as usize);
+            let len = match width.checked_mul(height) {
+                Some(len) if len > 0 && len <= data.len() - 2 && width <= i32::MAX as usize => len,
+                _ => break,
+            };
+            let pixels = data[2..2 + len]
+                .iter()
+                .flat_map(|pixel| premultiply(*pixel).to_le_bytes())
+                .collect::<Vec<_>>();
+            buffers.push(IconBuffer {
+                size: (width as i32, height as i32).into(),
+                scale: 1,
+                data: pixels.into(),
+            });
+            data = &data[2 + len..];
+        }
+        if buffers.is_empty() {
+            None
+        } else {
+            Some(WindowIcon { name: None, buffers })
+        }
+    }
+
+    /// Returns the buffer best suited to be shown at `size` logical pixels with `scale`
+    ///
+    /// This is the smallest buffer covering `size` at `scale`, or the largest one if none is
+    /// large enough.
+    pub fn best_buffer(&self, size: i32, scale: i32) -> Option<&IconBuffer> {
+        let wanted = size * scale;
+        let extent = |buffer: &IconBuffer| buffer.size.w.max(buffer.size.h);
+        self.buffers
+            .iter()
+            .filter(|buffer| extent(buffer) >= wanted)
+            .min_by_key(|buffer| (extent(buffer), (buffer.scale - scale).abs()))
+            .or_else(|| self.buffers.iter().max_by_key(|buffer| extent(buffer)))
+    }
+}
+
+fn premultiply(pixel: u32) -> u32 {
+    let alpha = pixel >> 24;
+    let channel = |shift: u32| ((((pixel >> shift) & 0xff) * alpha + 127) / 255) << shift;
+    (alpha << 24) | channel(16) | channel(8) | channel(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn net_wm_icon() {
+        let data = [
+            // a 2x1 image
+            &[2, 1, 0xff00_80ff, 0x8000_ff00][..],
+            &[1, 1, 0x0012_3456],
+            // a truncated image
+            &[4, 4, 0],
+        ]
+        .concat();
+        let icon = WindowIcon::from_net_wm_icon(&data).unwrap();
+        assert_eq!(icon.buffers.len(), 2);
+        assert_eq!(icon.buffers[0].size, (2, 1).into());
+        assert_eq!(
+            &*icon.buffers[0].data,
+            &[0xff, 0x80, 0x00, 0xff, 0x00, 0x80, 0x00, 0x80]
+        );
+        // fully transparent
+        assert_eq!(&*icon.buffers[1].data, &[0, 0, 0, 0]);
+
+        assert_eq!(WindowIcon::from_net_wm_icon(&[0, 0]), None);
+        assert_eq!(icon.best_buffer(1, 1).unwrap().size, (1, 1).into());
+        assert_eq!(icon.best_buffer(1, 2).unwrap().size, (2, 1).into());
+        assert_eq!(icon.best_buffer(48, 1).unwrap().size, (2, 1).into());
+    }
+}
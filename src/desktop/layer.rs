@@ -31,6 +31,8 @@ pub struct LayerMap {
     layers: IndexSet<LayerSurface>,
     output: Weak<(Mutex<OutputInner>, wayland_server::UserDataMap)>,
     zone: Rectangle<i32, Logical>,
+    // set when the zone changed, until the change is taken
+    zone_changed: bool,
     // surfaces for tracking enter and leave events
     surfaces: Vec<WlSurface>,
     logger: ::slog::Logger,
@@ -63,12 +65,14 @@ pub fn layer_map_for_output(o: &Output) -> RefMut<'_, LayerMap> {
                     })
                     .unwrap_or_else(|| (0, 0).into()),
             ),
+            zone_changed: false,
             surfaces: Vec::new(),
             logger: (*o.inner.0.lock().unwrap())
                 .log
                 .new(slog::o!("smithay_module" => "layer_map")),
         })
     });
+    watch_output_changes(o);
     userdata.get::<RefCell<LayerMap>>().unwrap().borrow_mut()
 }
 
@@ -135,10 +139,25 @@ impl LayerMap {
     }
 
     /// Return the area of this output, that is not exclusive to any [`LayerSurface`]s.
+    ///
+    /// The area is relative to the output, use [`Space::usable_area`] to get it in space coordinates.
     pub fn non_exclusive_zone(&self) -> Rectangle<i32, Logical> {
         self.zone
     }
 
+    /// Returns the new [non-exclusive zone](LayerMap::non_exclusive_zone), if it changed since the last call
+    ///
+    /// Use this to e.g. re-layout tiled or maximized windows, when a panel appears or changes its size.
+    /// Changes are only recorded while re-arranging, so they can be handled once the layer map is no
+    /// longer borrowed. [`Space::refresh`] takes the changes of all outputs mapped to the space.
+    pub fn take_zone_change(&mut self) -> Option<Rectangle<i32, Logical>> {
+        if std::mem::take(&mut self.zone_changed) {
+            Some(self.zone)
+        } else {
+            None
+        }
+    }
+
     /// Returns the geometry of a given mapped [`LayerSurface`].
     ///
     /// If the surface was not previously mapped onto this layer map,
@@ -328,7 +347,10 @@ impl LayerMap {
             }
 
             slog::trace!(self.logger, "Remaining zone {:?}", zone);
-            self.zone = zone;
+            if self.zone != zone {
+                self.zone = zone;
+                self.zone_changed = true;
+            }
         }
     }
 
@@ -336,6 +358,14 @@ impl LayerMap {
         self.output.upgrade().map(|inner| Output { inner })
     }
 
+    /// Makes all surfaces of this map leave and re-enter `output`
+    pub(crate) fn reenter_surfaces(&self, output: &Output) {
+        for surface in &self.surfaces {
+            output.leave(surface);
+            output.enter(surface);
+        }
+    }
+
     /// Cleanup some internally used resources.
     ///
     /// This function needs to be called periodically (though not necessarily frequently)
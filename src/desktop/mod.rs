@@ -28,6 +28,12 @@
 //! Windows get a position and stacking order through mapping. Outputs become views of a part of the [`Space`]
 //! and can be rendered via [`Space::render_output`]. Rendering results of spaces are automatically damage-tracked.
 //!
+//! ### [`Scene`]
+//!
+//! A scene is a retained alternative to a [`Space`]. Instead of windows it holds a tree of surfaces,
+//! solid rectangles and textures, that is only updated when something changes.
+//! Rendering it via [`Scene::render_output`] is damage-tracked as well and skips hidden nodes.
+//!
 //! ### Layer Shell
 //!
 //! A [`LayerSurface`] represents a surface as provided by e.g. the layer-shell protocol.
@@ -37,6 +43,11 @@
 //! which [`LayerSurface`]s can be mapped upon. Associated layer maps are automatically rendered by [`Space::render_output`],
 //! but a [draw function](`draw_layer_surface`) is also provided for manual layer-surface management.
 //!
+//! ### Wallpapers
+//!
+//! With the `wallpaper` feature, a [`Wallpaper`](wallpaper::Wallpaper) loads an image and provides elements
+//! drawing it scaled into outputs below everything else rendered by [`Space::render_output`].
+//!
 //! ### Popups
 //!
 //! Provides a [`PopupManager`], which can be used to automatically keep track of popups and their
@@ -49,13 +60,26 @@
 //! to manage client buffers to do so. If you plan to use the provided drawing functions, you need to use
 //! [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler).
 
+pub mod edges;
+mod icon;
 pub(crate) mod layer;
+#[cfg(feature = "xwayland")]
+mod override_redirect;
 mod popup;
+pub mod rotation;
+pub mod scene;
 pub mod space;
 pub mod utils;
+#[cfg(feature = "wallpaper")]
+pub mod wallpaper;
 mod window;
+pub mod window_state;
 
+pub use self::icon::{IconBuffer, WindowIcon};
 pub use self::layer::{draw_layer_surface, layer_map_for_output, LayerMap, LayerSurface};
+#[cfg(feature = "xwayland")]
+pub use self::override_redirect::{draw_override_redirect, OverrideRedirectWindow};
 pub use self::popup::*;
+pub use self::scene::Scene;
 pub use self::space::Space;
 pub use self::window::*;
@@ -0,0 +1,207 @@
+use crate::{
+    backend::renderer::{utils::draw_surface_tree, ImportAll, Renderer},
+    desktop::{utils::*, window::X11Surface, Space},
+    utils::{Logical, Point, Rectangle},
+    wayland::output::Output,
+};
+use std::{
+    cell::Cell,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+use wayland_commons::user_data::UserDataMap;
+use wayland_server::protocol::wl_surface;
+
+crate::utils::ids::id_gen!(
+    next_override_redirect_id,
+    OVERRIDE_REDIRECT_ID,
+    OVERRIDE_REDIRECT_IDS
+);
+
+#[derive(Debug)]
+pub(super) struct OverrideRedirectInner {
+    pub(super) id: usize,
+    surface: X11Surface,
+    location: Cell<Point<i32, Logical>>,
+    bbox: Cell<Rectangle<i32, Logical>>,
+    user_data: UserDataMap,
+}
+
+impl Drop for OverrideRedirectInner {
+    fn drop(&mut self) {
+        OVERRIDE_REDIRECT_IDS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Represents an X11 override-redirect window, like a menu or a tooltip.
+///
+/// Override-redirect windows bypass the window manager. They position themselves
+/// in absolute coordinates of the X11 root window and never receive keyboard focus
+/// from the compositor, which is why they are not represented by a [`Window`](super::Window).
+#[derive(Debug, Clone)]
+pub struct OverrideRedirectWindow(pub(super) Rc<OverrideRedirectInner>);
+
+impl PartialEq for OverrideRedirectWindow {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl Eq for OverrideRedirectWindow {}
+
+impl Hash for OverrideRedirectWindow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.id.hash(state);
+    }
+}
+
+impl OverrideRedirectWindow {
+    /// Construct a new [`OverrideRedirectWindow`] from an [`X11Surface`]
+    /// placed at the given absolute `location`.
+    pub fn new<P: Into<Point<i32, Logical>>>(surface: X11Surface, location: P) -> OverrideRedirectWindow {
+        let id = next_override_redirect_id();
+
+        let window = OverrideRedirectWindow(Rc::new(OverrideRedirectInner {
+            id,
+            surface,
+            location: Cell::new(location.into()),
+            bbox: Cell::new(Rectangle::from_loc_and_size((0, 0), (0, 0))),
+            user_data: UserDataMap::new(),
+        }));
+        window.refresh();
+        window
+    }
+
+    /// Checks if the underlying surface is still alive.
+    pub fn alive(&self) -> bool {
+        self.0.surface.alive()
+    }
+
+    /// Returns the underlying [`WlSurface`](wl_surface::WlSurface), if still any.
+    pub fn get_surface(&self) -> Option<&wl_surface::WlSurface> {
+        self.0.surface.get_surface()
+    }
+
+    /// Returns the underlying [`X11Surface`]
+    pub fn x11_surface(&self) -> &X11Surface {
+        &self.0.surface
+    }
+
+    /// Returns the absolute location of this window.
+    pub fn location(&self) -> Point<i32, Logical> {
+        self.0.location.get()
+    }
+
+    /// Updates the absolute location of this window,
+    /// e.g. after the client reconfigured it.
+    pub fn set_location<P: Into<Point<i32, Logical>>>(&self, location: P) {
+        self.0.location.set(location.into());
+    }
+
+    /// Returns a bounding box over this window and its children,
+    /// relative to its location.
+    pub fn bbox(&self) -> Rectangle<i32, Logical> {
+        if self.get_surface().is_some() {
+            self.0.bbox.get()
+        } else {
+            Rectangle::from_loc_and_size((0, 0), (0, 0))
+        }
+    }
+
+    /// Returns the bounding box of this window in absolute coordinates.
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        let mut geo = self.bbox();
+        geo.loc += self.location();
+        geo
+    }
+
+    /// Sends the frame callback to all the subsurfaces in this
+    /// window that requested it
+    pub fn send_frame(&self, time: u32) {
+        if let Some(surface) = self.get_surface() {
+            send_frames_surface_tree(surface, time);
+        }
+    }
+
+    /// Updates internal values
+    ///
+    /// Needs to be called whenever the surface or any unsynchronized subsurfaces of this window are updated
+    /// to correctly update the bounding box of this window.
+    pub fn refresh(&self) {
+        if let Some(surface) = self.get_surface() {
+            self.0.bbox.set(bbox_from_surface_tree(surface, (0, 0)));
+        }
+    }
+
+    /// Finds the topmost surface under this point matching the input regions of the surface and returns
+    /// it together with the location of this surface.
+    ///
+    /// In case no surface input region matches the point [`None`] is returned.
+    ///
+    /// - `point` should be relative to (0,0) of the window.
+    pub fn surface_under<P: Into<Point<f64, Logical>>>(
+        &self,
+        point: P,
+    ) -> Option<(wl_surface::WlSurface, Point<i32, Logical>)> {
+        self.get_surface().and_then(|surface| {
+            under_from_surface_tree(
+                surface,
+                point.into(),
+                (0, 0),
+                super::WindowSurfaceType::TOPLEVEL | super::WindowSurfaceType::SUBSURFACE,
+            )
+        })
+    }
+
+    /// Damage of all the surfaces of this window.
+    ///
+    /// If `for_values` is `Some(_)` it will only return the damage on the
+    /// first call for a given [`Space`] and [`Output`], if the buffer hasn't changed.
+    /// Subsequent calls will return an empty vector until the buffer is updated again.
+    pub fn accumulated_damage(&self, for_values: Option<(&Space, &Output)>) -> Vec<Rectangle<i32, Logical>> {
+        let mut damage = Vec::new();
+        if let Some(surface) = self.get_surface() {
+            damage.extend(
+                damage_from_surface_tree(surface, (0, 0), for_values)
+                    .into_iter()
+                    .flat_map(|rect| rect.intersection(self.bbox())),
+            );
+        }
+        damage
+    }
+
+    /// Returns a [`UserDataMap`] to allow associating arbitrary data with this window.
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.0.user_data
+    }
+}
+
+/// Renders a given [`OverrideRedirectWindow`] using a provided renderer and frame.
+///
+/// - `scale` needs to be equivalent to the fractional scale the rendered result should have.
+/// - `location` is the position the window should be drawn at.
+/// - `damage` is the set of regions of the window that should be drawn.
+///
+/// Note: This function will render nothing, if you are not using
+/// [`crate::backend::renderer::utils::on_commit_buffer_handler`]
+/// to let smithay handle buffer management.
+pub fn draw_override_redirect<R, P>(
+    renderer: &mut R,
+    frame: &mut <R as Renderer>::Frame,
+    window: &OverrideRedirectWindow,
+    scale: f64,
+    location: P,
+    damage: &[Rectangle<i32, Logical>],
+    log: &slog::Logger,
+) -> Result<(), <R as Renderer>::Error>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+    P: Into<Point<i32, Logical>>,
+{
+    if let Some(surface) = window.get_surface() {
+        draw_surface_tree(renderer, frame, surface, scale, location.into(), damage, log)
+    } else {
+        Ok(())
+    }
+}
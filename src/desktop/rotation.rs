@@ -0,0 +1,246 @@
+//! Animated transitions between output transforms
+//!
+//! Changing the transform of an output, e.g. because a tablet was turned, changes the layout of
+//! everything shown on it at once. A [`RotationTransition`] captures the last frame before the
+//! transform changes and cross-fades from it to the new layout, while the new layout is rendered
+//! as usual underneath.
+//!
+//! ```no_run
+//! # use smithay::backend::renderer::gles2::Gles2Renderer;
+//! # use smithay::desktop::{rotation::RotationTransition, space::SurfaceTree, Space};
+//! # use smithay::utils::{monotonic_time, Transform};
+//! # use smithay::wayland::output::Output;
+//! # use std::time::Duration;
+//! # let (mut space, mut renderer, output): (Space, Gles2Renderer, Output) = unimplemented!();
+//! // when the accelerometer reports a new orientation
+//! let transition = RotationTransition::start(
+//!     &mut space,
+//!     &mut renderer,
+//!     &output,
+//!     Transform::_90,
+//!     [0.0, 0.0, 0.0, 1.0],
+//!     &[] as &[SurfaceTree],
+//!     |_| true,
+//!     monotonic_time(),
+//!     Duration::from_millis(300),
+//! )
+//! .expect("Failed to capture the output");
+//!
+//! // for every frame, until the transition is finished
+//! let now = monotonic_time();
+//! if let Some(element) = transition.element(&space, now) {
+//!     // bind the target of the frame and pass `element` to `Space::render_output`
+//!     // as part of a `custom_elements!` enum
+//! }
+//! if transition.is_finished(now) {
+//!     // drop the transition
+//! }
+//! ```
+
+use std::time::Duration;
+
+use crate::{
+    backend::renderer::{Frame, ImportAll, Offscreen, Renderer, Texture},
+    desktop::space::{RenderElement, RenderError, Space, SpaceOutputTuple},
+    utils::{Buffer, Logical, Point, Rectangle, Size, Transform},
+    wayland::output::Output,
+};
+
+crate::utils::ids::id_gen!(next_transition_id, TRANSITION_ID, TRANSITION_IDS);
+
+/// Cross-fade from the last frame of an output before its transform changed
+///
+/// See the [module documentation](self) for an example.
+#[derive(Debug)]
+pub struct RotationTransition<T> {
+    id: usize,
+    snapshot: T,
+    output: Output,
+    start: Duration,
+    duration: Duration,
+}
+
+impl<T: Texture + Clone + 'static> RotationTransition<T> {
+    /// Captures the current frame of `output` and changes its transform to `transform`
+    ///
+    /// The frame is captured with [`Space::render_output_capture`], which `custom_elements`
+    /// and `keep` are passed to. The transition starts at `now` and takes `duration`, all times
+    /// are absolute `CLOCK_MONOTONIC` times like the ones returned by
+    /// [`monotonic_time`](crate::utils::monotonic_time). The target bound to `renderer`
+    /// changes, so bind the target of the next frame again before rendering it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start<R, E, F>(
+        space: &mut Space,
+        renderer: &mut R,
+        output: &Output,
+        transform: Transform,
+        clear_color: [f32; 4],
+        custom_elements: &[E],
+        keep: F,
+        now: Duration,
+        duration: Duration,
+    ) -> Result<RotationTransition<T>, RenderError<R>>
+    where
+        R: Renderer<TextureId = T> + ImportAll + Offscreen<T>,
+        E: RenderElement<R>,
+        F: Fn(&E) -> bool,
+    {
+        let size = space
+            .output_capture_size(output)
+            .ok_or(RenderError::OutputNoMode)?;
+        let snapshot = renderer
+            .create_buffer((size.w, size.h).into())
+            .map_err(RenderError::Rendering)?;
+        renderer.bind(snapshot.clone()).map_err(RenderError::Rendering)?;
+        space.render_output_capture(renderer, output, clear_color, custom_elements, keep)?;
+        output.change_current_state(None, Some(transform.into()), None, None);
+
+        Ok(RotationTransition {
+            id: next_transition_id(),
+            snapshot,
+            output: output.clone(),
+            start: now,
+            duration,
+        })
+    }
+
+    /// Returns the output, whose transform changed
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Checks if the transition is over at the given time
+    pub fn is_finished(&self, now: Duration) -> bool {
+        now >= self.start + self.duration
+    }
+
+    /// Returns the element drawing the captured frame at the given time
+    ///
+    /// Returns `None` once the transition is finished or if the output is not mapped to `space`.
+    pub fn element(&self, space: &Space, now: Duration) -> Option<RotationElement<T>> {
+        if self.is_finished(now) {
+            return None;
+        }
+        Some(RotationElement {
+            id: self.id,
+            texture: self.snapshot.clone(),
+            geometry: space.output_geometry(&self.output)?,
+            alpha: opacity(self.start, self.duration, now),
+        })
+    }
+}
+
+impl<T> Drop for RotationTransition<T> {
+    fn drop(&mut self) {
+        TRANSITION_IDS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// [`RenderElement`] drawing the frame captured by a [`RotationTransition`]
+///
+/// The frame is scaled to fit into the output with its new transform and fades out.
+#[derive(Debug)]
+pub struct RotationElement<T> {
+    id: usize,
+    texture: T,
+    geometry: Rectangle<i32, Logical>,
+    alpha: f32,
+}
+
+impl<R> RenderElement<R> for RotationElement<<R as Renderer>::TextureId>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.geometry
+    }
+
+    fn accumulated_damage(&self, _: Option<SpaceOutputTuple<'_, '_>>) -> Vec<Rectangle<i32, Logical>> {
+        // the opacity changes every frame
+        vec![Rectangle::from_loc_and_size((0, 0), self.geometry.size)]
+    }
+
+    fn draw(
+        &self,
+        _renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        location: Point<i32, Logical>,
+        damage: &[Rectangle<i32, Logical>],
+        _log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        let damage = damage
+            .iter()
+            .map(|rect| {
+                Rectangle::from_loc_and_size(rect.loc + location, rect.size)
+                    .to_f64()
+                    .to_physical(scale)
+            })
+            .collect::<Vec<_>>();
+        let size = self.texture.size();
+        let dst = fit(size, self.geometry.size);
+        let dst = Rectangle::from_loc_and_size(dst.loc + location.to_f64(), dst.size).to_physical(scale);
+        frame.render_texture_from_to(
+            &self.texture,
+            Rectangle::from_loc_and_size((0, 0), size),
+            dst,
+            &damage,
+            Transform::Normal,
+            self.alpha,
+        )
+    }
+
+    fn z_index(&self) -> u8 {
+        // above everything, as the frame includes overlays and the cursor
+        u8::MAX
+    }
+}
+
+/// Scales an image to fit into an area, keeping its aspect ratio, and centers it
+fn fit(image: Size<i32, Buffer>, area: Size<i32, Logical>) -> Rectangle<f64, Logical> {
+    if image.w <= 0 || image.h <= 0 {
+        return Rectangle::from_loc_and_size((0.0, 0.0), area.to_f64());
+    }
+    let factor = (area.w as f64 / image.w as f64).min(area.h as f64 / image.h as f64);
+    let size: Size<f64, Logical> = (image.w as f64 * factor, image.h as f64 * factor).into();
+    Rectangle::from_loc_and_size(
+        ((area.w as f64 - size.w) / 2.0, (area.h as f64 - size.h) / 2.0),
+        size,
+    )
+}
+
+/// Opacity of the captured frame, easing from opaque to transparent
+fn opacity(start: Duration, duration: Duration, now: Duration) -> f32 {
+    let elapsed = now.saturating_sub(start).as_secs_f64();
+    let duration = duration.as_secs_f64();
+    let t = if duration > 0.0 {
+        (elapsed / duration).min(1.0)
+    } else {
+        1.0
+    };
+    // smoothstep, so the fade neither starts nor ends abruptly
+    (1.0 - t * t * (3.0 - 2.0 * t)) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_and_fade() {
+        // a landscape frame on the output turned to portrait
+        assert_eq!(
+            fit((1920, 1080).into(), (1080, 1920).into()),
+            Rectangle::from_loc_and_size((0.0, 656.25), (1080.0, 607.5))
+        );
+        let ms = Duration::from_millis;
+        assert_eq!(opacity(ms(100), ms(200), ms(50)), 1.0);
+        assert_eq!(opacity(ms(100), ms(200), ms(200)), 0.5);
+        assert_eq!(opacity(ms(100), ms(200), ms(400)), 0.0);
+    }
+}
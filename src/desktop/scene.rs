@@ -0,0 +1,1124 @@
+//! This module contains the [`Scene`] helper, a retained alternative to [`Space`](super::Space)
+//!
+//! Instead of collecting the elements to render every frame, a [`Scene`] is a tree of nodes, that
+//! is built once and then only updated when something changes. The scene keeps track of what was
+//! drawn on every output and computes the damage and the set of visible nodes internally.
+//!
+//! There are four kinds of nodes:
+//! - trees, which do not draw anything, but group their children,
+//! - surfaces, drawing a [`WlSurface`] and its subsurfaces,
+//! - rectangles of a solid color, e.g. for backgrounds or borders,
+//! - textures, that were imported by the compositor itself, e.g. for decorations or wallpapers.
+//!
+//! Every node can have children. They are positioned relative to their parent and stacked above it,
+//! in the order they were added. Disabling a node hides it together with all of its children.
+//!
+//! ```no_run
+//! # use smithay::{
+//! #     backend::renderer::{ImportAll, Renderer},
+//! #     desktop::scene::Scene,
+//! #     reexports::wayland_server::protocol::wl_surface::WlSurface,
+//! #     wayland::output::Output,
+//! # };
+//! # fn example<R: Renderer + ImportAll>(renderer: &mut R, output: &Output, surface: WlSurface)
+//! # where <R as Renderer>::TextureId: 'static
+//! # {
+//! let mut scene = Scene::new(None);
+//! scene.map_output(output, (0, 0));
+//!
+//! let background = scene.add_rect(scene.root(), (1920, 1080), [0.1, 0.1, 0.1, 1.0]);
+//! let window = scene.add_tree(scene.root());
+//! scene.set_location(window, (100, 100));
+//! let border = scene.add_rect(window, (820, 620), [0.5, 0.5, 0.5, 1.0]);
+//! let content = scene.add_surface(window, surface);
+//! scene.set_location(content, (10, 10));
+//!
+//! // later, in your render loop
+//! scene.render_output(renderer, output, 0, [0.0, 0.0, 0.0, 1.0]).unwrap();
+//! # }
+//! ```
+//!
+//! Like every other rendering helper of the [desktop module](super), surfaces are only drawn, if
+//! [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler) is used.
+
+use crate::{
+    backend::renderer::{
+        damage::DamageHistory, utils::draw_surface_tree, Frame, ImportAll, Renderer, Texture,
+    },
+    desktop::{
+        space::{next_scene_id, release_scene_id, RenderError, SpaceOutputHash},
+        utils::{
+            bbox_from_surface_tree, damage_from_surface_tree_keyed, optimize_damage, output_leave_tree,
+            output_update, send_frames_surface_tree, under_from_surface_tree, OutputConfig,
+        },
+        WindowSurfaceType,
+    },
+    utils::{Logical, Point, Rectangle, Size, Transform},
+    wayland::output::Output,
+};
+use indexmap::IndexMap;
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+};
+use wayland_server::protocol::wl_surface::WlSurface;
+
+/// Amount of content updates of a node an output can lag behind,
+/// before the whole node is considered damaged.
+const MAX_DAMAGE: usize = 4;
+
+/// Handle to a node of a [`Scene`]
+///
+/// Handles stay valid until the node is destroyed, they are never reused afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SceneNodeId(usize);
+
+#[derive(Debug)]
+enum NodeKind {
+    Tree,
+    Surface(WlSurface),
+    Rect {
+        size: Size<i32, Logical>,
+        color: [f32; 4],
+    },
+    Texture {
+        texture: Box<dyn Any>,
+        size: Size<i32, Logical>,
+    },
+}
+
+#[derive(Debug)]
+struct Node {
+    parent: Option<SceneNodeId>,
+    // in z-order, back to front
+    children: Vec<SceneNodeId>,
+    // relative to the parent
+    location: Point<i32, Logical>,
+    enabled: bool,
+    kind: NodeKind,
+    // counts content updates, so outputs can tell which damage they have not drawn yet
+    commit: usize,
+    // node-relative damage of the latest content updates, newest first
+    damage: VecDeque<Vec<Rectangle<i32, Logical>>>,
+}
+
+impl Node {
+    fn new(parent: Option<SceneNodeId>, kind: NodeKind) -> Node {
+        Node {
+            parent,
+            children: Vec::new(),
+            location: (0, 0).into(),
+            enabled: true,
+            kind,
+            commit: 0,
+            damage: VecDeque::new(),
+        }
+    }
+
+    fn size(&self) -> Size<i32, Logical> {
+        match &self.kind {
+            NodeKind::Rect { size, .. } | NodeKind::Texture { size, .. } => *size,
+            NodeKind::Tree | NodeKind::Surface(_) => (0, 0).into(),
+        }
+    }
+
+    fn update(&mut self, damage: Vec<Rectangle<i32, Logical>>) {
+        self.commit = self.commit.wrapping_add(1);
+        self.damage.push_front(damage);
+        self.damage.truncate(MAX_DAMAGE);
+    }
+
+    fn damage_since(&self, commit: usize) -> Vec<Rectangle<i32, Logical>> {
+        let missed = self.commit.wrapping_sub(commit);
+        if missed <= self.damage.len() {
+            self.damage.iter().take(missed).flatten().copied().collect()
+        } else {
+            vec![Rectangle::from_loc_and_size((0, 0), self.size())]
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SceneOutputState {
+    location: Point<i32, Logical>,
+
+    // damage and last_state are in scene coordinate space
+    old_damage: DamageHistory<Logical>,
+    // geometry and last drawn commit of every node visible on the output
+    last_state: IndexMap<SceneNodeId, (Rectangle<i32, Logical>, usize)>,
+
+    // surfaces for tracking enter and leave events
+    surfaces: Vec<WlSurface>,
+
+    // mode, scale and transform of the output the damage was tracked for
+    config: OutputConfig,
+}
+
+impl SceneOutputState {
+    /// Checks if the mode, scale or transform of the output changed since the last call
+    ///
+    /// In that case all tracked damage is discarded and the surfaces on the output re-enter it.
+    /// Unlike for a `Space`, the surfaces can not be reached from a change listener of the output,
+    /// so this runs on the next refresh or render of the output.
+    fn update_config(&mut self, output: &Output) -> bool {
+        let changed = self.config.update(output);
+        if changed {
+            self.old_damage.clear();
+            self.last_state.clear();
+            for surface in &self.surfaces {
+                output.leave(surface);
+                output.enter(surface);
+            }
+        }
+        changed
+    }
+}
+
+/// A node, that is visible with all of its parents, in scene coordinates
+#[derive(Debug)]
+struct VisibleNode<'a> {
+    id: SceneNodeId,
+    location: Point<i32, Logical>,
+    geometry: Rectangle<i32, Logical>,
+    node: &'a Node,
+}
+
+/// Retained tree of nodes to render, see the [module-level documentation](self).
+#[derive(Debug)]
+pub struct Scene {
+    id: usize,
+    root: SceneNodeId,
+    nodes: HashMap<SceneNodeId, Node>,
+    next_node_id: usize,
+    outputs: Vec<(Output, SceneOutputState)>,
+    logger: ::slog::Logger,
+}
+
+impl PartialEq for Scene {
+    fn eq(&self, other: &Scene) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Drop for Scene {
+    fn drop(&mut self) {
+        release_scene_id(self.id);
+    }
+}
+
+impl Scene {
+    /// Create a new empty [`Scene`]
+    pub fn new<L>(log: L) -> Scene
+    where
+        L: Into<Option<slog::Logger>>,
+    {
+        let root = SceneNodeId(0);
+        let mut nodes = HashMap::new();
+        nodes.insert(root, Node::new(None, NodeKind::Tree));
+        Scene {
+            id: next_scene_id(),
+            root,
+            nodes,
+            next_node_id: 1,
+            outputs: Vec::new(),
+            logger: crate::slog_or_fallback(log),
+        }
+    }
+
+    /// Returns the root node of this scene
+    ///
+    /// The root node is a tree, that can not be destroyed.
+    pub fn root(&self) -> SceneNodeId {
+        self.root
+    }
+
+    fn add_node(&mut self, parent: SceneNodeId, kind: NodeKind) -> SceneNodeId {
+        let id = SceneNodeId(self.next_node_id);
+        self.next_node_id += 1;
+        self.nodes
+            .get_mut(&parent)
+            .expect("Parent node does not belong to this scene")
+            .children
+            .push(id);
+        self.nodes.insert(id, Node::new(Some(parent), kind));
+        id
+    }
+
+    /// Adds a new tree node on top of the children of `parent`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not a node of this scene.
+    pub fn add_tree(&mut self, parent: SceneNodeId) -> SceneNodeId {
+        self.add_node(parent, NodeKind::Tree)
+    }
+
+    /// Adds a new node drawing `surface` and its subsurfaces on top of the children of `parent`
+    ///
+    /// The location of the node is the location of the surface, subsurfaces may extend beyond it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not a node of this scene.
+    pub fn add_surface(&mut self, parent: SceneNodeId, surface: WlSurface) -> SceneNodeId {
+        self.add_node(parent, NodeKind::Surface(surface))
+    }
+
+    /// Adds a new rectangle of a solid color on top of the children of `parent`
+    ///
+    /// Rectangles are drawn by clearing their area, so they are always opaque.
+    /// Nodes completely covered by a rectangle are not drawn at all.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not a node of this scene.
+    pub fn add_rect<S: Into<Size<i32, Logical>>>(
+        &mut self,
+        parent: SceneNodeId,
+        size: S,
+        color: [f32; 4],
+    ) -> SceneNodeId {
+        self.add_node(
+            parent,
+            NodeKind::Rect {
+                size: size.into(),
+                color,
+            },
+        )
+    }
+
+    /// Adds a new node drawing `texture` on top of the children of `parent`
+    ///
+    /// The texture has to be created by the renderer used for [`Scene::render_output`],
+    /// otherwise it is skipped. `scale` is the buffer scale of the texture.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parent` is not a node of this scene.
+    pub fn add_texture<T: Texture + 'static>(
+        &mut self,
+        parent: SceneNodeId,
+        texture: T,
+        scale: i32,
+    ) -> SceneNodeId {
+        let size = texture.size().to_logical(scale, Transform::Normal);
+        self.add_node(
+            parent,
+            NodeKind::Texture {
+                texture: Box::new(texture),
+                size,
+            },
+        )
+    }
+
+    /// Checks if the given node is part of this scene
+    pub fn contains(&self, node: SceneNodeId) -> bool {
+        self.nodes.contains_key(&node)
+    }
+
+    /// Destroys a node together with all of its children
+    ///
+    /// Does nothing for the root node or nodes not part of this scene.
+    pub fn destroy(&mut self, node: SceneNodeId) {
+        if node == self.root {
+            return;
+        }
+        if let Some(parent) = self.nodes.get(&node).and_then(|n| n.parent) {
+            if let Some(parent) = self.nodes.get_mut(&parent) {
+                parent.children.retain(|child| *child != node);
+            }
+        }
+
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.nodes.remove(&id) {
+                if let NodeKind::Surface(surface) = &node.kind {
+                    for (output, state) in &mut self.outputs {
+                        output_leave_tree(output, &mut state.surfaces, surface, &self.logger);
+                    }
+                }
+                stack.extend(node.children);
+            }
+        }
+    }
+
+    /// Returns the surface of a surface node
+    pub fn surface(&self, node: SceneNodeId) -> Option<&WlSurface> {
+        match self.nodes.get(&node).map(|n| &n.kind) {
+            Some(NodeKind::Surface(surface)) => Some(surface),
+            _ => None,
+        }
+    }
+
+    /// Returns the node drawing the given surface, if any
+    pub fn node_for_surface(&self, surface: &WlSurface) -> Option<SceneNodeId> {
+        self.nodes.iter().find_map(|(id, node)| match &node.kind {
+            NodeKind::Surface(s) if s == surface => Some(*id),
+            _ => None,
+        })
+    }
+
+    /// Returns the parent of a node, `None` for the root node
+    pub fn parent(&self, node: SceneNodeId) -> Option<SceneNodeId> {
+        self.nodes.get(&node).and_then(|n| n.parent)
+    }
+
+    /// Returns the children of a node, in z-order, back to front
+    pub fn children(&self, node: SceneNodeId) -> &[SceneNodeId] {
+        self.nodes.get(&node).map(|n| &n.children[..]).unwrap_or(&[])
+    }
+
+    /// Shows or hides a node together with all of its children
+    pub fn set_enabled(&mut self, node: SceneNodeId, enabled: bool) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.enabled = enabled;
+        }
+    }
+
+    /// Returns if the node itself is enabled
+    ///
+    /// Note that the node is still hidden, if any of its parents is disabled.
+    pub fn is_enabled(&self, node: SceneNodeId) -> bool {
+        self.nodes.get(&node).map(|n| n.enabled).unwrap_or(false)
+    }
+
+    /// Moves a node relative to its parent
+    pub fn set_location<P: Into<Point<i32, Logical>>>(&mut self, node: SceneNodeId, location: P) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            node.location = location.into();
+        }
+    }
+
+    /// Returns the location of a node relative to its parent
+    pub fn location(&self, node: SceneNodeId) -> Option<Point<i32, Logical>> {
+        self.nodes.get(&node).map(|n| n.location)
+    }
+
+    /// Returns the location of a node in scene coordinates
+    pub fn absolute_location(&self, node: SceneNodeId) -> Option<Point<i32, Logical>> {
+        let mut current = self.nodes.get(&node)?;
+        let mut location = current.location;
+        while let Some(parent) = current.parent {
+            current = &self.nodes[&parent];
+            location += current.location;
+        }
+        Some(location)
+    }
+
+    /// Returns the bounding box of a node without its children in scene coordinates
+    ///
+    /// For surface nodes this includes their subsurfaces.
+    pub fn geometry(&self, node: SceneNodeId) -> Option<Rectangle<i32, Logical>> {
+        let location = self.absolute_location(node)?;
+        Some(node_geometry(&self.nodes[&node], location))
+    }
+
+    fn restack<F>(&mut self, node: SceneNodeId, position: F)
+    where
+        F: FnOnce(&[SceneNodeId]) -> Option<usize>,
+    {
+        let parent = match self.parent(node) {
+            Some(parent) => parent,
+            None => return,
+        };
+        let children = &mut self.nodes.get_mut(&parent).unwrap().children;
+        children.retain(|child| *child != node);
+        let index = position(children).unwrap_or(children.len());
+        children.insert(index, node);
+    }
+
+    /// Moves a node on top of its siblings
+    pub fn raise_to_top(&mut self, node: SceneNodeId) {
+        self.restack(node, |children| Some(children.len()));
+    }
+
+    /// Moves a node below its siblings
+    pub fn lower_to_bottom(&mut self, node: SceneNodeId) {
+        self.restack(node, |_| Some(0));
+    }
+
+    /// Moves a node directly above `sibling`
+    ///
+    /// Does nothing if both nodes do not have the same parent.
+    pub fn place_above(&mut self, node: SceneNodeId, sibling: SceneNodeId) {
+        if node != sibling && self.parent(node).is_some() && self.parent(node) == self.parent(sibling) {
+            self.restack(node, |children| {
+                children.iter().position(|child| *child == sibling).map(|i| i + 1)
+            });
+        }
+    }
+
+    /// Moves a node directly below `sibling`
+    ///
+    /// Does nothing if both nodes do not have the same parent.
+    pub fn place_below(&mut self, node: SceneNodeId, sibling: SceneNodeId) {
+        if node != sibling && self.parent(node).is_some() && self.parent(node) == self.parent(sibling) {
+            self.restack(node, |children| {
+                children.iter().position(|child| *child == sibling)
+            });
+        }
+    }
+
+    /// Moves a node together with its children on top of the children of `new_parent`
+    ///
+    /// Does nothing if `new_parent` is the node itself or one of its children.
+    pub fn reparent(&mut self, node: SceneNodeId, new_parent: SceneNodeId) {
+        if !self.contains(node) || !self.contains(new_parent) || node == self.root {
+            return;
+        }
+        let mut ancestor = Some(new_parent);
+        while let Some(id) = ancestor {
+            if id == node {
+                return;
+            }
+            ancestor = self.parent(id);
+        }
+
+        if let Some(old_parent) = self.parent(node) {
+            self.nodes
+                .get_mut(&old_parent)
+                .unwrap()
+                .children
+                .retain(|child| *child != node);
+        }
+        self.nodes.get_mut(&new_parent).unwrap().children.push(node);
+        self.nodes.get_mut(&node).unwrap().parent = Some(new_parent);
+    }
+
+    /// Resizes a rectangle node
+    pub fn set_rect_size<S: Into<Size<i32, Logical>>>(&mut self, node: SceneNodeId, new_size: S) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            if let NodeKind::Rect { size, .. } = &mut node.kind {
+                *size = new_size.into();
+            }
+        }
+    }
+
+    /// Changes the color of a rectangle node
+    pub fn set_rect_color(&mut self, node: SceneNodeId, new_color: [f32; 4]) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            if let NodeKind::Rect { color, size } = &mut node.kind {
+                if *color != new_color {
+                    *color = new_color;
+                    let damage = vec![Rectangle::from_loc_and_size((0, 0), *size)];
+                    node.update(damage);
+                }
+            }
+        }
+    }
+
+    /// Replaces the texture of a texture node
+    pub fn set_texture<T: Texture + 'static>(&mut self, node: SceneNodeId, new_texture: T, scale: i32) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            if let NodeKind::Texture { texture, size } = &mut node.kind {
+                *size = new_texture.size().to_logical(scale, Transform::Normal);
+                *texture = Box::new(new_texture);
+                let damage = vec![Rectangle::from_loc_and_size((0, 0), *size)];
+                node.update(damage);
+            }
+        }
+    }
+
+    /// Marks regions of a texture node as damaged
+    ///
+    /// Use this after updating the contents of the texture in place,
+    /// e.g. with [`ImportMem::update_memory`](crate::backend::renderer::ImportMem::update_memory).
+    /// `damage` is relative to the node.
+    pub fn damage_texture(&mut self, node: SceneNodeId, damage: &[Rectangle<i32, Logical>]) {
+        if let Some(node) = self.nodes.get_mut(&node) {
+            if let NodeKind::Texture { .. } = node.kind {
+                node.update(damage.to_vec());
+            }
+        }
+    }
+
+    fn visible_nodes(&self) -> Vec<VisibleNode<'_>> {
+        visible_nodes(&self.nodes, self.root)
+    }
+
+    /// Returns the topmost visible node, whose bounding box contains the given point
+    pub fn node_under<P: Into<Point<f64, Logical>>>(&self, point: P) -> Option<SceneNodeId> {
+        let point = point.into();
+        self.visible_nodes()
+            .into_iter()
+            .rev()
+            .find(|node| node.geometry.to_f64().contains(point))
+            .map(|node| node.id)
+    }
+
+    /// Finds the topmost visible surface under this point matching the input regions of the surface
+    /// and returns it together with its location in scene coordinates.
+    ///
+    /// Rectangles and textures above a surface do not block input.
+    pub fn surface_under<P: Into<Point<f64, Logical>>>(
+        &self,
+        point: P,
+    ) -> Option<(WlSurface, Point<i32, Logical>)> {
+        let point = point.into();
+        self.visible_nodes()
+            .into_iter()
+            .rev()
+            .find_map(|node| match &node.node.kind {
+                NodeKind::Surface(surface) => under_from_surface_tree(
+                    surface,
+                    point,
+                    node.location,
+                    WindowSurfaceType::TOPLEVEL | WindowSurfaceType::SUBSURFACE,
+                ),
+                _ => None,
+            })
+    }
+
+    /// Maps an [`Output`] inside the scene.
+    ///
+    /// Can be safely called on an already mapped
+    /// [`Output`] to update its location.
+    ///
+    /// *Note:* Remapping an output does reset it's damage memory.
+    pub fn map_output<P: Into<Point<i32, Logical>>>(&mut self, output: &Output, location: P) {
+        let state = SceneOutputState {
+            location: location.into(),
+            ..Default::default()
+        };
+        match self.outputs.iter_mut().find(|(o, _)| o == output) {
+            Some((_, old_state)) => {
+                let surfaces = std::mem::take(&mut old_state.surfaces);
+                *old_state = SceneOutputState { surfaces, ..state };
+            }
+            None => self.outputs.push((output.clone(), state)),
+        }
+    }
+
+    /// Unmap an [`Output`] from this scene.
+    ///
+    /// Does nothing if the output was not previously mapped.
+    pub fn unmap_output(&mut self, output: &Output) {
+        if let Some(index) = self.outputs.iter().position(|(o, _)| o == output) {
+            let (output, mut state) = self.outputs.remove(index);
+            for surface in std::mem::take(&mut state.surfaces) {
+                output.leave(&surface);
+            }
+        }
+    }
+
+    /// Returns an iterator over all mapped outputs
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.outputs.iter().map(|(o, _)| o)
+    }
+
+    /// Returns the geometry of a mapped [`Output`] in scene coordinates, if any.
+    pub fn output_geometry(&self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        let (_, state) = self.outputs.iter().find(|(o, _)| o == output)?;
+        output_geometry(output, state.location)
+    }
+
+    /// Refreshes the state of the scene
+    ///
+    /// Needs to be called periodically, at best before every wayland socket flush.
+    ///
+    /// Destroys nodes of surfaces that no longer exist and sends
+    /// output enter and leave events for the visible surfaces.
+    pub fn refresh(&mut self) {
+        let dead = self
+            .nodes
+            .iter()
+            .filter_map(|(id, node)| match &node.kind {
+                NodeKind::Surface(surface) if !surface.as_ref().is_alive() => Some(*id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for node in dead {
+            self.destroy(node);
+        }
+
+        let visible = self
+            .visible_nodes()
+            .into_iter()
+            .filter_map(|node| match &node.node.kind {
+                NodeKind::Surface(surface) => Some((surface.clone(), node.location, node.geometry)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let hidden = self
+            .nodes
+            .values()
+            .filter_map(|node| match &node.kind {
+                NodeKind::Surface(surface) if !visible.iter().any(|(s, _, _)| s == surface) => {
+                    Some(surface.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (output, state) in &mut self.outputs {
+            state.surfaces.retain(|s| s.as_ref().is_alive());
+            state.update_config(output);
+            let output_geo = match output_geometry(output, state.location) {
+                Some(geo) => geo,
+                None => continue,
+            };
+
+            for (surface, location, bbox) in &visible {
+                if output_geo.overlaps(*bbox) {
+                    output_update(
+                        output,
+                        output_geo,
+                        &mut state.surfaces,
+                        surface,
+                        *location,
+                        &self.logger,
+                    );
+                } else {
+                    output_leave_tree(output, &mut state.surfaces, surface, &self.logger);
+                }
+            }
+            for surface in &hidden {
+                output_leave_tree(output, &mut state.surfaces, surface, &self.logger);
+            }
+        }
+    }
+
+    /// Sends the frame callback to all visible surfaces
+    pub fn send_frames(&self, time: u32) {
+        for node in self.visible_nodes() {
+            if let NodeKind::Surface(surface) = &node.node.kind {
+                send_frames_surface_tree(surface, time);
+            }
+        }
+    }
+
+    /// Render a given [`Output`] using a given [`Renderer`].
+    ///
+    /// All visible nodes overlapping the output are drawn. `clear_color` will be used
+    /// to fill all unoccupied regions.
+    ///
+    /// Rendering is damage-tracked, for this the age of the buffer bound to the `renderer`
+    /// needs to be provided. If you render something else than this scene to the buffers
+    /// of this output, you need to reset the age values accordingly.
+    ///
+    /// Returns a list of updated regions relative to the rendered output
+    /// (or `None` if that list would be empty) in case of success.
+    pub fn render_output<R>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+        age: usize,
+        clear_color: [f32; 4],
+    ) -> Result<Option<Vec<Rectangle<i32, Logical>>>, RenderError<R>>
+    where
+        R: Renderer + ImportAll,
+        R::TextureId: 'static,
+    {
+        let output_index = self
+            .outputs
+            .iter()
+            .position(|(o, _)| o == output)
+            .ok_or(RenderError::UnmappedOutput)?;
+        let output_size = output.current_mode().ok_or(RenderError::OutputNoMode)?.size;
+        let output_scale = output.current_scale().fractional_scale();
+        let key = SpaceOutputHash::new(self.id, output);
+
+        let visible_nodes = visible_nodes(&self.nodes, self.root);
+        let logger = &self.logger;
+        let state = &mut self.outputs[output_index].1;
+        let output_geo = output_geometry(output, state.location).unwrap();
+
+        // A new mode, scale or transform invalidates everything drawn so far
+        state.update_config(output);
+
+        // Culling: nodes outside of the output are ignored completely
+        let elements = visible_nodes
+            .into_iter()
+            .filter(|node| node.geometry.overlaps(output_geo))
+            .collect::<Vec<_>>();
+
+        // This will hold all the damage we need for this rendering step
+        let mut damage = Vec::<Rectangle<i32, Logical>>::new();
+        // First add damage for nodes gone
+        for (id, (geo, _)) in state.last_state.iter() {
+            if !elements.iter().any(|e| e.id == *id) {
+                slog::trace!(logger, "Removing node at: {:?}", geo);
+                damage.push(*geo);
+            }
+        }
+        // then for new, moved and updated nodes
+        for element in &elements {
+            match state.last_state.get(&element.id) {
+                Some((old_geo, _)) if *old_geo != element.geometry => {
+                    damage.push(*old_geo);
+                    damage.push(element.geometry);
+                }
+                None => damage.push(element.geometry),
+                Some((_, commit)) => {
+                    let node_damage = match &element.node.kind {
+                        NodeKind::Surface(surface) => {
+                            damage_from_surface_tree_keyed(surface, (0, 0), Some(key))
+                        }
+                        _ => element.node.damage_since(*commit),
+                    };
+                    damage.extend(node_damage.into_iter().map(|mut rect| {
+                        rect.loc += element.location;
+                        rect
+                    }));
+                }
+            }
+        }
+
+        // That is all completely new damage, which we need to store for subsequent renders
+        let new_damage = damage.clone();
+        // We now add old damage states, if we have an age value
+        if let Some(old_damage) = state.old_damage.damage_for_age(age) {
+            damage.extend(old_damage);
+        } else {
+            // just damage everything, if we have no damage
+            damage = vec![output_geo];
+        }
+
+        // Optimize the damage for rendering
+        let damage = optimize_damage(damage, output_geo);
+
+        if damage.is_empty() {
+            return Ok(None);
+        }
+
+        let to_output_physical = |geo: Rectangle<i32, Logical>| {
+            Rectangle::from_loc_and_size(geo.loc - output_geo.loc, geo.size)
+                .to_f64()
+                .to_physical(output_scale)
+        };
+        let output_transform: Transform = output.current_transform().into();
+        let res = renderer.render(
+            output_transform.transform_size(output_size),
+            output_transform,
+            |renderer, frame| {
+                // First clear all damaged regions
+                slog::trace!(logger, "Clearing at {:#?}", damage);
+                frame.clear(
+                    clear_color,
+                    &damage.iter().copied().map(to_output_physical).collect::<Vec<_>>(),
+                )?;
+
+                // Then re-draw all nodes overlapping with a damage rect,
+                // that are not covered by an opaque rectangle.
+                for (index, element) in elements.iter().enumerate() {
+                    let occluded = elements[index + 1..].iter().any(|above| {
+                        matches!(above.node.kind, NodeKind::Rect { .. })
+                            && above.geometry.contains_rect(element.geometry)
+                    });
+                    if occluded {
+                        continue;
+                    }
+
+                    let element_damage = damage
+                        .iter()
+                        .flat_map(|d| d.intersection(element.geometry))
+                        .collect::<Vec<_>>();
+                    if element_damage.is_empty() {
+                        continue;
+                    }
+                    // Map from scene space to node-relative coordinates
+                    let relative_damage = element_damage
+                        .iter()
+                        .map(|geo| Rectangle::from_loc_and_size(geo.loc - element.location, geo.size))
+                        .collect::<Vec<_>>();
+
+                    match &element.node.kind {
+                        NodeKind::Tree => {}
+                        NodeKind::Surface(surface) => {
+                            draw_surface_tree(
+                                renderer,
+                                frame,
+                                surface,
+                                output_scale,
+                                element.location - output_geo.loc,
+                                &relative_damage,
+                                logger,
+                            )?;
+                        }
+                        NodeKind::Rect { color, .. } => {
+                            frame.clear(
+                                *color,
+                                &element_damage
+                                    .iter()
+                                    .copied()
+                                    .map(to_output_physical)
+                                    .collect::<Vec<_>>(),
+                            )?;
+                        }
+                        NodeKind::Texture { texture, size } => match texture.downcast_ref::<R::TextureId>() {
+                            Some(texture) => frame.render_texture_from_to(
+                                texture,
+                                Rectangle::from_loc_and_size((0, 0), texture.size()),
+                                to_output_physical(Rectangle::from_loc_and_size(element.location, *size)),
+                                &relative_damage
+                                    .iter()
+                                    .map(|geo| geo.to_f64().to_physical(output_scale))
+                                    .collect::<Vec<_>>(),
+                                Transform::Normal,
+                                1.0,
+                            )?,
+                            None => slog::warn!(
+                                logger,
+                                "Texture of node {:?} was not created by this renderer",
+                                element.id
+                            ),
+                        },
+                    }
+                }
+
+                Result::<(), R::Error>::Ok(())
+            },
+        );
+
+        if let Err(err) = res {
+            // if the rendering errors on us, we need to be prepared, that this whole buffer was partially updated and thus now unusable.
+            // thus clean our old states before returning
+            state.old_damage.clear();
+            state.last_state = IndexMap::new();
+            return Err(RenderError::Rendering(err));
+        }
+
+        // If rendering was successful capture the state and add the damage
+        state.last_state = elements
+            .iter()
+            .map(|element| (element.id, (element.geometry, element.node.commit)))
+            .collect();
+        state.old_damage.push(new_damage.clone());
+
+        Ok(Some(
+            new_damage
+                .into_iter()
+                .map(|mut geo| {
+                    geo.loc -= output_geo.loc;
+                    geo
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// Returns all visible nodes in z-order, back to front
+fn visible_nodes(nodes: &HashMap<SceneNodeId, Node>, root: SceneNodeId) -> Vec<VisibleNode<'_>> {
+    let mut visible = Vec::new();
+    // depth first, drawing a node before its children
+    let mut stack = vec![(root, Point::from((0, 0)))];
+    while let Some((id, offset)) = stack.pop() {
+        let node = &nodes[&id];
+        if !node.enabled {
+            continue;
+        }
+        let location = offset + node.location;
+        if !matches!(node.kind, NodeKind::Tree) {
+            visible.push(VisibleNode {
+                id,
+                location,
+                geometry: node_geometry(node, location),
+                node,
+            });
+        }
+        stack.extend(node.children.iter().rev().map(|child| (*child, location)));
+    }
+    visible
+}
+
+fn node_geometry(node: &Node, location: Point<i32, Logical>) -> Rectangle<i32, Logical> {
+    match &node.kind {
+        NodeKind::Surface(surface) => bbox_from_surface_tree(surface, location),
+        _ => Rectangle::from_loc_and_size(location, node.size()),
+    }
+}
+
+fn output_geometry(output: &Output, location: Point<i32, Logical>) -> Option<Rectangle<i32, Logical>> {
+    // We explicitly use ceil for the output geometry size to make sure the damage
+    // spans at least the output size.
+    let transform: Transform = output.current_transform().into();
+    output.current_mode().map(|mode| {
+        Rectangle::from_loc_and_size(
+            location,
+            transform
+                .transform_size(mode.size)
+                .to_f64()
+                .to_logical(output.current_scale().fractional_scale())
+                .to_i32_ceil(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::output::{Mode, PhysicalProperties, Scale};
+    use wayland_server::protocol::wl_output;
+
+    fn output(size: (i32, i32), transform: wl_output::Transform, scale: i32) -> Output {
+        let output = Output::new(
+            "test".into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: wl_output::Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: "Test".into(),
+            },
+            None,
+        );
+        let mode = Mode {
+            size: size.into(),
+            refresh: 60_000,
+        };
+        output.change_current_state(Some(mode), Some(transform), Some(Scale::Integer(scale)), None);
+        output
+    }
+
+    fn visible(scene: &Scene) -> Vec<SceneNodeId> {
+        scene.visible_nodes().into_iter().map(|node| node.id).collect()
+    }
+
+    #[test]
+    fn visible_nodes() {
+        let mut scene = Scene::new(None);
+        let root = scene.root();
+        let tree = scene.add_tree(root);
+        scene.set_location(tree, (10, 20));
+        let a = scene.add_rect(tree, (5, 5), [1.0; 4]);
+        scene.set_location(a, (1, 2));
+        let b = scene.add_rect(root, (5, 5), [1.0; 4]);
+
+        // trees are not drawn, children in z-order after their parent
+        assert_eq!(visible(&scene), vec![a, b]);
+        let nodes = scene.visible_nodes();
+        assert_eq!(nodes[0].location, (11, 22).into());
+        assert_eq!(nodes[0].geometry, Rectangle::from_loc_and_size((11, 22), (5, 5)));
+        assert_eq!(scene.absolute_location(a), Some((11, 22).into()));
+
+        // disabling a tree hides its children
+        scene.set_enabled(tree, false);
+        assert_eq!(visible(&scene), vec![b]);
+        assert!(scene.is_enabled(a));
+        scene.set_enabled(tree, true);
+        assert_eq!(visible(&scene), vec![a, b]);
+
+        assert_eq!(scene.node_under((12.0, 23.0)), Some(a));
+        assert_eq!(scene.node_under((2.0, 2.0)), Some(b));
+        assert_eq!(scene.node_under((50.0, 50.0)), None);
+    }
+
+    #[test]
+    fn restack() {
+        let mut scene = Scene::new(None);
+        let root = scene.root();
+        let a = scene.add_rect(root, (1, 1), [1.0; 4]);
+        let b = scene.add_rect(root, (1, 1), [1.0; 4]);
+        let c = scene.add_rect(root, (1, 1), [1.0; 4]);
+        assert_eq!(scene.children(root), &[a, b, c]);
+
+        scene.raise_to_top(a);
+        assert_eq!(scene.children(root), &[b, c, a]);
+        scene.lower_to_bottom(a);
+        assert_eq!(scene.children(root), &[a, b, c]);
+        scene.place_above(a, b);
+        assert_eq!(scene.children(root), &[b, a, c]);
+        scene.place_below(c, b);
+        assert_eq!(scene.children(root), &[c, b, a]);
+        assert_eq!(visible(&scene), vec![c, b, a]);
+
+        // siblings only
+        let tree = scene.add_tree(root);
+        let d = scene.add_rect(tree, (1, 1), [1.0; 4]);
+        scene.place_above(d, a);
+        scene.place_below(a, d);
+        assert_eq!(scene.children(root), &[c, b, a, tree]);
+        assert_eq!(scene.children(tree), &[d]);
+    }
+
+    #[test]
+    fn reparent() {
+        let mut scene = Scene::new(None);
+        let root = scene.root();
+        let a = scene.add_tree(root);
+        let b = scene.add_tree(a);
+        let rect = scene.add_rect(root, (1, 1), [1.0; 4]);
+        scene.set_location(a, (10, 10));
+
+        scene.reparent(rect, b);
+        assert_eq!(scene.parent(rect), Some(b));
+        assert_eq!(scene.children(root), &[a]);
+        assert_eq!(scene.children(b), &[rect]);
+        assert_eq!(scene.absolute_location(rect), Some((10, 10).into()));
+
+        // no cycles
+        scene.reparent(a, b);
+        assert_eq!(scene.parent(a), Some(root));
+        scene.reparent(a, a);
+        assert_eq!(scene.parent(a), Some(root));
+        scene.reparent(root, a);
+        assert_eq!(scene.parent(root), None);
+    }
+
+    #[test]
+    fn damage_since() {
+        let mut scene = Scene::new(None);
+        let rect = scene.add_rect(scene.root(), (10, 10), [0.0; 4]);
+        let full = vec![Rectangle::from_loc_and_size((0, 0), (10, 10))];
+        let node = &scene.nodes[&rect];
+        assert!(node.damage_since(node.commit).is_empty());
+
+        // the color is unchanged, nothing to damage
+        scene.set_rect_color(rect, [0.0; 4]);
+        assert_eq!(scene.nodes[&rect].commit, 0);
+
+        scene.set_rect_color(rect, [1.0; 4]);
+        let node = &scene.nodes[&rect];
+        assert_eq!(node.commit, 1);
+        assert_eq!(node.damage_since(0), full);
+        assert!(node.damage_since(1).is_empty());
+
+        // too far behind, the whole node is damaged
+        for i in 0..MAX_DAMAGE {
+            scene.set_rect_color(rect, [i as f32; 4]);
+        }
+        let node = scene.nodes.get_mut(&rect).unwrap();
+        node.damage.iter_mut().for_each(|damage| damage.clear());
+        assert!(node.damage_since(1).is_empty());
+        assert_eq!(node.damage_since(0), full);
+    }
+
+    #[test]
+    fn destroy() {
+        let mut scene = Scene::new(None);
+        let root = scene.root();
+        let tree = scene.add_tree(root);
+        let child = scene.add_rect(tree, (1, 1), [1.0; 4]);
+        let other = scene.add_rect(root, (1, 1), [1.0; 4]);
+
+        scene.destroy(tree);
+        assert!(!scene.contains(tree));
+        assert!(!scene.contains(child));
+        assert!(scene.contains(other));
+        assert_eq!(scene.children(root), &[other]);
+        assert_eq!(visible(&scene), vec![other]);
+
+        // the root can not be destroyed and ids are not reused
+        scene.destroy(root);
+        assert!(scene.contains(root));
+        let new = scene.add_tree(root);
+        assert!(new != tree && new != child);
+    }
+
+    #[test]
+    fn output_geometry_transformed() {
+        let mut scene = Scene::new(None);
+        let normal = output((1920, 1080), wl_output::Transform::Normal, 2);
+        let rotated = output((1920, 1080), wl_output::Transform::_90, 1);
+        scene.map_output(&normal, (0, 0));
+        scene.map_output(&rotated, (960, 0));
+
+        assert_eq!(
+            scene.output_geometry(&normal),
+            Some(Rectangle::from_loc_and_size((0, 0), (960, 540)))
+        );
+        assert_eq!(
+            scene.output_geometry(&rotated),
+            Some(Rectangle::from_loc_and_size((960, 0), (1080, 1920)))
+        );
+    }
+}
@@ -1,13 +1,16 @@
 use crate::desktop::space::popup::RenderPopup;
+#[cfg(feature = "xwayland")]
+use crate::desktop::OverrideRedirectWindow;
 use crate::{
     backend::renderer::{ImportAll, Renderer, Texture},
-    desktop::{space::*, utils::*},
+    desktop::{space::*, utils::*, Kind},
     utils::{Logical, Point, Rectangle},
-    wayland::output::Output,
+    wayland::{compositor::with_states, output::Output, shell::xdg::XdgToplevelSurfaceRoleAttributes},
 };
 use std::{
     any::{Any, TypeId},
     hash::{Hash, Hasher},
+    sync::Mutex,
 };
 use wayland_server::protocol::wl_surface::WlSurface;
 
@@ -25,6 +28,9 @@ pub enum RenderZindex {
     Top = 40,
     /// Default zindex for Windows PopUps
     Popups = 50,
+    /// Default zindex for X11 override-redirect windows
+    #[cfg(feature = "xwayland")]
+    OverrideRedirect = 55,
     /// Default Layer for RenderElements
     Overlay = 60,
     /// Default Layer for Overlay PopUp
@@ -91,6 +97,8 @@ where
     Layer(&'a LayerSurface),
     Window(&'a Window),
     Popup(&'a RenderPopup),
+    #[cfg(feature = "xwayland")]
+    OverrideRedirect(&'a OverrideRedirectWindow),
     Custom(&'a E, std::marker::PhantomData<R>),
 }
 
@@ -105,6 +113,8 @@ where
             SpaceElement::Layer(layer) => layer.elem_id(),
             SpaceElement::Window(window) => window.elem_id(),
             SpaceElement::Popup(popup) => popup.elem_id(),
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_id(),
             SpaceElement::Custom(custom, _) => custom.id(),
         }
     }
@@ -113,6 +123,8 @@ where
             SpaceElement::Layer(layer) => layer.elem_type_of(),
             SpaceElement::Window(window) => window.elem_type_of(),
             SpaceElement::Popup(popup) => popup.elem_type_of(),
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_type_of(),
             SpaceElement::Custom(custom, _) => custom.type_of(),
         }
     }
@@ -121,6 +133,8 @@ where
             SpaceElement::Layer(layer) => layer.elem_geometry(space_id).loc,
             SpaceElement::Window(window) => window.elem_location(space_id),
             SpaceElement::Popup(popup) => popup.elem_geometry(space_id).loc,
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_location(),
             SpaceElement::Custom(custom, _) => custom.geometry().loc,
         }
     }
@@ -129,6 +143,8 @@ where
             SpaceElement::Layer(layer) => layer.elem_geometry(space_id),
             SpaceElement::Window(window) => window.elem_geometry(space_id),
             SpaceElement::Popup(popup) => popup.elem_geometry(space_id),
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_geometry(),
             SpaceElement::Custom(custom, _) => custom.geometry(),
         }
     }
@@ -137,6 +153,8 @@ where
             SpaceElement::Layer(layer) => layer.elem_accumulated_damage(for_values),
             SpaceElement::Window(window) => window.elem_accumulated_damage(for_values),
             SpaceElement::Popup(popup) => popup.elem_accumulated_damage(for_values),
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_accumulated_damage(for_values),
             SpaceElement::Custom(custom, _) => {
                 custom.accumulated_damage(for_values.map(|(s, o)| SpaceOutputTuple(s, o)))
             }
@@ -163,19 +181,55 @@ where
             SpaceElement::Popup(popup) => {
                 popup.elem_draw(space_id, renderer, frame, scale, location, damage, log)
             }
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_draw(renderer, frame, scale, location, damage, log),
             SpaceElement::Custom(custom, _) => custom.draw(renderer, frame, scale, location, damage, log),
         }
     }
+    /// Label used to measure the time spent drawing this element, see
+    /// [`Frame::begin_timing_section`](crate::backend::renderer::Frame::begin_timing_section)
+    pub fn timing_label(&self) -> String {
+        match self {
+            SpaceElement::Layer(layer) => format!("layer {}", layer.namespace()),
+            SpaceElement::Window(window) => match window_app_id(window) {
+                Some(app_id) => format!("window {}", app_id),
+                None => format!("window {}", window.elem_id()),
+            },
+            SpaceElement::Popup(popup) => format!("popup {}", popup.elem_id()),
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => format!("override-redirect {}", or.elem_id()),
+            SpaceElement::Custom(custom, _) => format!("custom {}", custom.id()),
+        }
+    }
     pub fn z_index(&self) -> u8 {
         match self {
             SpaceElement::Layer(layer) => layer.elem_z_index(),
             SpaceElement::Window(window) => window.elem_z_index(),
             SpaceElement::Popup(popup) => popup.elem_z_index(),
+            #[cfg(feature = "xwayland")]
+            SpaceElement::OverrideRedirect(or) => or.elem_z_index(),
             SpaceElement::Custom(custom, _) => custom.z_index(),
         }
     }
 }
 
+fn window_app_id(window: &Window) -> Option<String> {
+    match window.toplevel() {
+        Kind::Xdg(toplevel) => toplevel.get_surface().and_then(|surface| {
+            with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                    .and_then(|attributes| attributes.lock().unwrap().app_id.clone())
+            })
+            .ok()
+            .flatten()
+        }),
+        #[cfg(feature = "xwayland")]
+        Kind::X11(_) => None,
+    }
+}
+
 /// Generic helper for drawing [`WlSurface`]s and their subsurfaces
 /// as custom elements via [`RenderElement`].
 ///
@@ -255,10 +309,7 @@ impl<'a, 'b> Hash for SpaceOutputTuple<'a, 'b> {
 impl<'a, 'b> SpaceOutputTuple<'a, 'b> {
     /// Returns an owned version that produces and equivalent hash
     pub fn owned_hash(&self) -> SpaceOutputHash {
-        SpaceOutputHash(
-            self.0.id,
-            std::sync::Arc::as_ptr(&self.1.inner) as *const () as usize,
-        )
+        SpaceOutputHash::new(self.0.id, self.1)
     }
 }
 
@@ -271,3 +322,37 @@ impl<'a, 'b> From<(&'a Space, &'b Output)> for SpaceOutputTuple<'a, 'b> {
 /// Type to use as an owned hashable value equal to [`SpaceOutputTuple`]
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct SpaceOutputHash(usize, usize);
+
+impl SpaceOutputHash {
+    pub(crate) fn new(id: usize, output: &Output) -> SpaceOutputHash {
+        SpaceOutputHash(id, std::sync::Arc::as_ptr(&output.inner) as *const () as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{desktop::space::layer::layer_z_index, wayland::shell::wlr_layer::Layer};
+
+    #[test]
+    #[cfg(feature = "xwayland")]
+    fn override_redirect_stacking() {
+        // compositors route input to override-redirect windows after the overlay layer
+        // and before anything else, so rendering has to follow the same order
+        let or = RenderZindex::OverrideRedirect;
+        assert!(or > RenderZindex::Shell);
+        assert!(or > RenderZindex::Popups);
+        assert!(or > layer_z_index(Layer::Top));
+        assert!(or < layer_z_index(Layer::Overlay));
+        assert!(or < RenderZindex::PopupsOverlay);
+    }
+
+    #[test]
+    fn layer_stacking() {
+        assert!(layer_z_index(Layer::Background) < layer_z_index(Layer::Bottom));
+        assert!(layer_z_index(Layer::Bottom) < RenderZindex::Shell);
+        assert!(RenderZindex::Shell < layer_z_index(Layer::Top));
+        assert!(layer_z_index(Layer::Top) < RenderZindex::Popups);
+        assert!(RenderZindex::Popups < layer_z_index(Layer::Overlay));
+    }
+}
@@ -76,15 +76,18 @@ impl LayerSurface {
 
     pub(super) fn elem_z_index(&self) -> u8 {
         if let Some(layer) = self.layer() {
-            let z_index = match layer {
-                Layer::Background => RenderZindex::Background,
-                Layer::Bottom => RenderZindex::Bottom,
-                Layer::Top => RenderZindex::Top,
-                Layer::Overlay => RenderZindex::Overlay,
-            };
-            z_index as u8
+            layer_z_index(layer) as u8
         } else {
             0
         }
     }
 }
+
+pub(super) fn layer_z_index(layer: Layer) -> RenderZindex {
+    match layer {
+        Layer::Background => RenderZindex::Background,
+        Layer::Bottom => RenderZindex::Bottom,
+        Layer::Top => RenderZindex::Top,
+        Layer::Overlay => RenderZindex::Overlay,
+    }
+}
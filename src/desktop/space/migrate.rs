@@ -0,0 +1,98 @@
+use crate::utils::{Logical, Rectangle, Size};
+
+/// Maps the geometry of a window from the area of one output into the area of another one
+///
+/// Position and size are scaled by the ratio of the sizes of both areas, so a window covering
+/// the right half of one output covers the right half of the other one. As the areas are in
+/// logical coordinates, this takes different output scales into account. The size is clamped
+/// with `clamp`, e.g. to the size constraints of the window, and to `to`, and the window is
+/// moved to lie completely inside of `to`, if it fits.
+pub(super) fn migrate_geometry(
+    geometry: Rectangle<i32, Logical>,
+    from: Rectangle<i32, Logical>,
+    to: Rectangle<i32, Logical>,
+    clamp: impl Fn(Size<i32, Logical>) -> Size<i32, Logical>,
+) -> Rectangle<i32, Logical> {
+    let ratio = |to: i32, from: i32| if from > 0 { to as f64 / from as f64 } else { 1.0 };
+    let (ratio_x, ratio_y) = (ratio(to.size.w, from.size.w), ratio(to.size.h, from.size.h));
+
+    let size = clamp(Size::from((
+        (geometry.size.w as f64 * ratio_x).round() as i32,
+        (geometry.size.h as f64 * ratio_y).round() as i32,
+    )));
+    let size = Size::from((size.w.min(to.size.w), size.h.min(to.size.h)));
+
+    let x = to.loc.x + ((geometry.loc.x - from.loc.x) as f64 * ratio_x).round() as i32;
+    let y = to.loc.y + ((geometry.loc.y - from.loc.y) as f64 * ratio_y).round() as i32;
+    // a window larger than the area, e.g. because of its minimum size, sticks out at the bottom right
+    let x = x.min(to.loc.x + to.size.w - size.w).max(to.loc.x);
+    let y = y.min(to.loc.y + to.size.h - size.h).max(to.loc.y);
+
+    Rectangle::from_loc_and_size((x, y), size)
+}
+
+/// Centers a window in an area, e.g. because the output it was on is gone
+pub(super) fn center_geometry(
+    size: Size<i32, Logical>,
+    to: Rectangle<i32, Logical>,
+    clamp: impl Fn(Size<i32, Logical>) -> Size<i32, Logical>,
+) -> Rectangle<i32, Logical> {
+    let size = clamp(size);
+    let size = Size::from((size.w.min(to.size.w), size.h.min(to.size.h)));
+    Rectangle::from_loc_and_size(
+        (
+            to.loc.x + (to.size.w - size.w) / 2,
+            to.loc.y + (to.size.h - size.h) / 2,
+        ),
+        size,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    #[test]
+    fn keeps_relative_geometry() {
+        // 4k output at scale 2 next to a 1080p output at scale 1 have the same logical size
+        let from = rect(0, 0, 1920, 1080);
+        let to = rect(1920, 0, 1920, 1080);
+        assert_eq!(
+            migrate_geometry(rect(960, 0, 960, 1080), from, to, |size| size),
+            rect(2880, 0, 960, 1080)
+        );
+
+        // a smaller output
+        let to = rect(-1280, 100, 1280, 720);
+        assert_eq!(
+            migrate_geometry(rect(960, 540, 480, 270), from, to, |size| size),
+            rect(-640, 460, 320, 180)
+        );
+    }
+
+    #[test]
+    fn stays_inside_the_target() {
+        let from = rect(0, 0, 1920, 1080);
+        let to = rect(1920, 0, 1280, 720);
+        // the minimum size of the window does not fit the proportional position
+        let min_size = |size: Size<i32, Logical>| Size::from((size.w.max(800), size.h.max(600)));
+        assert_eq!(
+            migrate_geometry(rect(1600, 900, 300, 150), from, to, min_size),
+            rect(2400, 120, 800, 600)
+        );
+        // or the target at all
+        let huge = |_| Size::from((4000, 4000));
+        assert_eq!(
+            migrate_geometry(rect(100, 100, 300, 150), from, to, huge),
+            rect(1920, 0, 1280, 720)
+        );
+        assert_eq!(
+            center_geometry((800, 600).into(), to, |size| size),
+            rect(2160, 60, 800, 600)
+        );
+    }
+}
@@ -2,44 +2,75 @@
 //! rendering helpers to add custom elements or different clients to a space.
 
 use crate::{
-    backend::renderer::{Frame, ImportAll, Renderer},
+    backend::renderer::{ExportMem, Frame, ImportAll, Offscreen, Renderer},
     desktop::{
         layer::{layer_map_for_output, LayerSurface},
         popup::PopupManager,
-        utils::{output_leave, output_update},
-        window::Window,
+        utils::{optimize_damage, output_leave_tree, output_update, overlap_fraction, watch_output_changes},
+        window::{Kind, Window},
     },
-    utils::{Logical, Point, Rectangle, Transform},
+    utils::{Logical, Physical, Point, Rectangle, Size, Transform},
     wayland::{
         compositor::{get_parent, is_sync_subsurface},
-        output::Output,
+        output::{Output, Scale},
+        seat::PointerHandle,
     },
 };
 use indexmap::{IndexMap, IndexSet};
-use std::{collections::VecDeque, fmt};
+use std::{any::Any, fmt, rc::Rc};
+use wayland_protocols::xdg_shell::server::xdg_toplevel;
 use wayland_server::protocol::wl_surface::WlSurface;
 
 mod element;
 mod layer;
+mod migrate;
 mod output;
+mod overlap;
+#[cfg(feature = "xwayland")]
+mod override_redirect;
+mod overview;
+mod pointer;
 mod popup;
+mod snap;
 mod window;
 
 pub use self::element::*;
+pub(crate) use self::output::reenter_output_surfaces;
 use self::output::*;
+pub use self::overlap::*;
+pub use self::overview::{Overview, OverviewElement, OverviewTile};
+use self::pointer::*;
+pub use self::snap::*;
 use self::window::*;
 
+#[cfg(feature = "xwayland")]
+use super::OverrideRedirectWindow;
 use super::WindowSurfaceType;
 
 crate::utils::ids::id_gen!(next_space_id, SPACE_ID, SPACE_IDS);
 
+// Scenes remember which surface damage they have already drawn using the same keys as spaces,
+// so their ids have to be allocated from the same pool.
+pub(super) fn next_scene_id() -> usize {
+    next_space_id()
+}
+
+pub(super) fn release_scene_id(id: usize) {
+    SPACE_IDS.lock().unwrap().remove(&id);
+}
+
 /// Represents two dimensional plane to map windows and outputs upon.
 #[derive(Debug)]
 pub struct Space {
     pub(super) id: usize,
     // in z-order, back to front
     windows: IndexSet<Window>,
+    // in z-order, back to front
+    #[cfg(feature = "xwayland")]
+    override_redirect: IndexSet<OverrideRedirectWindow>,
     outputs: Vec<Output>,
+    overlap_policy: OutputOverlapPolicy,
+    pointers: Vec<TrackedPointer>,
     logger: ::slog::Logger,
 }
 
@@ -64,7 +95,11 @@ impl Space {
         Space {
             id: next_space_id(),
             windows: IndexSet::new(),
+            #[cfg(feature = "xwayland")]
+            override_redirect: IndexSet::new(),
             outputs: Vec::new(),
+            overlap_policy: OutputOverlapPolicy::default(),
+            pointers: Vec::new(),
             logger: crate::slog_or_fallback(log),
         }
     }
@@ -89,12 +124,50 @@ impl Space {
     /// If activate is true it will set the new windows state
     /// to be activate and removes that state from every
     /// other mapped window.
+    ///
+    /// Windows transient for the raised window, like dialogs, are raised as well
+    /// to keep them above their parent, see [`Kind::parent`](crate::desktop::Kind::parent).
     pub fn raise_window(&mut self, window: &Window, activate: bool) {
         if self.windows.shift_remove(window) {
             self.insert_window(window, activate);
+            for child in self.transient_windows(window) {
+                self.windows.shift_remove(&child);
+                self.windows.insert(child);
+            }
         }
     }
 
+    /// Returns the mapped windows transient for `window`, directly or through
+    /// other transient windows, in z-order back to front
+    fn transient_windows(&self, window: &Window) -> Vec<Window> {
+        self.windows
+            .iter()
+            .filter(|w| {
+                // walk up the parents, a client might have created a cycle
+                let mut visited = vec![(*w).clone()];
+                while let Some(parent) = self.parent_window(visited.last().unwrap()) {
+                    if &parent == window {
+                        return true;
+                    }
+                    if visited.contains(&parent) {
+                        break;
+                    }
+                    visited.push(parent);
+                }
+                false
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn parent_window(&self, window: &Window) -> Option<Window> {
+        let parent = window.toplevel().parent()?;
+        self.windows
+            .iter()
+            .find(|w| w.toplevel().get_surface() == Some(&parent))
+            .cloned()
+    }
+
     fn insert_window(&mut self, window: &Window, activate: bool) {
         self.windows.insert(window.clone());
 
@@ -123,6 +196,71 @@ impl Space {
         self.windows.iter()
     }
 
+    /// Map an [`OverrideRedirectWindow`] and move it to the top of the stack
+    /// of override-redirect windows.
+    ///
+    /// Override-redirect windows are rendered at their absolute location
+    /// above all regular windows and their popups, but below the overlay layer.
+    #[cfg(feature = "xwayland")]
+    pub fn map_override_redirect(&mut self, window: &OverrideRedirectWindow) {
+        self.override_redirect.shift_remove(window);
+        self.override_redirect.insert(window.clone());
+    }
+
+    /// Unmap an [`OverrideRedirectWindow`] from this space.
+    ///
+    /// This function does nothing for already unmapped windows
+    #[cfg(feature = "xwayland")]
+    pub fn unmap_override_redirect(&mut self, window: &OverrideRedirectWindow) {
+        self.override_redirect.shift_remove(window);
+    }
+
+    /// Iterate override-redirect windows in z-order back to front
+    #[cfg(feature = "xwayland")]
+    pub fn override_redirect_windows(&self) -> impl DoubleEndedIterator<Item = &OverrideRedirectWindow> {
+        self.override_redirect.iter()
+    }
+
+    /// Finds the topmost surface of an override-redirect window under this point if any
+    /// and returns it together with the location of this surface relative to this space.
+    ///
+    /// Override-redirect windows are stacked above regular windows, their popups and the
+    /// top layer, but below the overlay layer (see [`RenderZindex::OverrideRedirect`]), so
+    /// compositors should query this after layer surfaces of the overlay layer and before
+    /// any other surfaces to route pointer input.
+    /// These windows are not supposed to receive keyboard focus.
+    #[cfg(feature = "xwayland")]
+    pub fn override_redirect_under<P: Into<Point<f64, Logical>>>(
+        &self,
+        point: P,
+    ) -> Option<(OverrideRedirectWindow, WlSurface, Point<i32, Logical>)> {
+        let point = point.into();
+        for window in self.override_redirect.iter().rev() {
+            let loc = window.location();
+            if !window.geometry().to_f64().contains(point) {
+                continue;
+            }
+
+            if let Some((surface, location)) = window.surface_under(point - loc.to_f64()) {
+                return Some((window.clone(), surface, location + loc));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the override-redirect window matching a given surface, if any
+    #[cfg(feature = "xwayland")]
+    pub fn override_redirect_for_surface(&self, surface: &WlSurface) -> Option<&OverrideRedirectWindow> {
+        if !surface.as_ref().is_alive() {
+            return None;
+        }
+
+        self.override_redirect
+            .iter()
+            .find(|w| w.get_surface().map(|x| x == surface).unwrap_or(false))
+    }
+
     /// Finds the topmost surface under this point if any and returns it
     /// together with the location of this surface relative to this space
     /// and the window the surface belongs to.
@@ -176,6 +314,67 @@ impl Space {
         })
     }
 
+    /// Returns the snap zone a point is in, while `window` is interactively moved
+    ///
+    /// Snap zones are the edges and corners of the [usable area](Space::usable_area) of the output
+    /// under the point and, if enabled in `config`, the left and right halves of other windows.
+    /// Call this on every motion of a move grab to show a preview of the returned geometry and
+    /// commit it on release, by mapping the window at [`SnapPreview::geometry`] and configuring
+    /// it with its size.
+    pub fn snap_preview<P: Into<Point<f64, Logical>>>(
+        &self,
+        window: &Window,
+        point: P,
+        config: &SnapConfig,
+    ) -> Option<SnapPreview> {
+        let point = point.into();
+        if let Some(output) = self.output_under(point).next() {
+            let area = self.usable_area(output)?;
+            if let Some(position) = config.edge_zone(area, point) {
+                return Some(SnapPreview {
+                    target: SnapTarget::Output(output.clone()),
+                    position,
+                    geometry: position.geometry(area),
+                });
+            }
+        }
+
+        if !config.snap_to_windows {
+            return None;
+        }
+        let (target, area) = self
+            .windows
+            .iter()
+            .rev()
+            .filter(|w| *w != window)
+            .map(|w| {
+                (
+                    w,
+                    Rectangle::from_loc_and_size(window_loc(w, &self.id), w.geometry().size),
+                )
+            })
+            .find(|(_, area)| area.to_f64().contains(point))?;
+        let center = area.loc.x as f64 + area.size.w as f64 / 2.0;
+        let position = if point.x < center {
+            SnapPosition::Left
+        } else {
+            SnapPosition::Right
+        };
+        Some(SnapPreview {
+            target: SnapTarget::Window(target.clone()),
+            position,
+            geometry: position.geometry(area),
+        })
+    }
+
+    /// Lays out all windows of this space scaled into a grid in `area`, e.g. the usable area of an output
+    ///
+    /// `gap` is the distance in logical pixels between the windows and to the edges of `area`.
+    /// See [`Overview`] for how to render it and map input back to the windows.
+    pub fn overview(&self, area: Rectangle<i32, Logical>, gap: i32) -> Overview {
+        Overview::new(self, area, gap)
+    }
+
     /// Returns the window matching a given surface, if any
     pub fn window_for_surface(&self, surface: &WlSurface) -> Option<&Window> {
         if !surface.as_ref().is_alive() {
@@ -223,12 +422,14 @@ impl Space {
     ///
     /// *Note:* Remapping an output does reset it's damage memory.
     pub fn map_output<P: Into<Point<i32, Logical>>>(&mut self, output: &Output, location: P) {
+        watch_output_changes(output);
         let mut state = output_state(self.id, output);
         *state = OutputState {
             location: location.into(),
             // keep surfaces, we still need to inform them of leaving,
             // if they don't overlap anymore during refresh.
             surfaces: state.surfaces.drain(..).collect::<Vec<_>>(),
+            elements: state.elements.take(),
             // resets last_seen and old_damage, if remapped
             ..Default::default()
         };
@@ -255,6 +456,25 @@ impl Space {
         self.outputs.retain(|o| o != output);
     }
 
+    /// Sets additional elements only rendered on the given [`Output`], like on screen displays
+    /// or recording indicators.
+    ///
+    /// The elements are rendered by [`Space::render_output`] together with its `custom_elements`,
+    /// so they need to be of the same type, otherwise they are ignored. The elements replace any
+    /// elements previously set for the output and are kept until the output is unmapped.
+    ///
+    /// Does nothing if the output is not mapped.
+    pub fn set_output_elements<E: Any>(&mut self, output: &Output, elements: Vec<E>) {
+        if !self.outputs.contains(output) {
+            return;
+        }
+        output_state(self.id, output).elements = if elements.is_empty() {
+            None
+        } else {
+            Some(Rc::new(elements))
+        };
+    }
+
     /// Returns the geometry of the output including it's relative position inside the space.
     ///
     /// The size is matching the amount of logical pixels of the space visible on the output
@@ -278,6 +498,18 @@ impl Space {
         })
     }
 
+    /// Returns the area of an [`Output`] usable by windows, in space coordinates.
+    ///
+    /// This is the geometry of the output without the exclusive zones of its layer surfaces
+    /// (see [`LayerMap::non_exclusive_zone`](crate::desktop::LayerMap::non_exclusive_zone)),
+    /// e.g. the area windows should be maximized to.
+    /// Returns `None` if the output is not mapped or has no mode set.
+    pub fn usable_area(&self, o: &Output) -> Option<Rectangle<i32, Logical>> {
+        let geometry = self.output_geometry(o)?;
+        let zone = layer_map_for_output(o).non_exclusive_zone();
+        Some(Rectangle::from_loc_and_size(geometry.loc + zone.loc, zone.size))
+    }
+
     /// Returns all [`Output`]s a [`Window`] overlaps with.
     pub fn outputs_for_window(&self, w: &Window) -> Vec<Output> {
         if !self.windows.contains(w) {
@@ -308,76 +540,277 @@ impl Space {
         outputs
     }
 
+    /// Returns the [`OutputOverlapPolicy`] used to send enter and leave events and pick primary outputs.
+    pub fn output_overlap_policy(&self) -> OutputOverlapPolicy {
+        self.overlap_policy
+    }
+
+    /// Changes the [`OutputOverlapPolicy`] of this space.
+    ///
+    /// Enter and leave events are updated on the next call to [`Space::refresh`].
+    pub fn set_output_overlap_policy(&mut self, policy: OutputOverlapPolicy) {
+        self.overlap_policy = policy;
+    }
+
+    /// Decides for every mapped output, if an element with the given bounding box enters it
+    fn entered_outputs(
+        &self,
+        bbox: Rectangle<i32, Logical>,
+    ) -> Vec<(&Output, Rectangle<i32, Logical>, bool)> {
+        let outputs = self.outputs.iter().map(|o| {
+            let geometry = self
+                .output_geometry(o)
+                .unwrap_or_else(|| Rectangle::from_loc_and_size((0, 0), (0, 0)));
+            ((o, geometry), overlap_fraction(bbox, geometry))
+        });
+        self.overlap_policy
+            .entered_outputs(outputs)
+            .into_iter()
+            .map(|((o, geometry), entered)| (o, geometry, entered))
+            .collect()
+    }
+
+    /// Returns the primary [`Output`] of a [`Window`] according to the [`OutputOverlapPolicy`] of this space.
+    ///
+    /// Only outputs the window entered are considered, ties are broken by the larger overlap
+    /// and then by the order the outputs were mapped in.
+    /// Returns `None` if the window is not mapped or does not overlap any output.
+    pub fn primary_output(&self, w: &Window) -> Option<Output> {
+        if !self.windows.contains(w) {
+            return None;
+        }
+
+        let bbox = window_rect(w, &self.id);
+        self.overlap_policy
+            .primary_output(self.outputs.iter().filter_map(|o| {
+                let overlap = overlap_fraction(bbox, self.output_geometry(o)?);
+                Some((o, overlap, o.current_scale().fractional_scale()))
+            }))
+            .cloned()
+    }
+
+    /// Returns the scale a [`Window`] should be rendered at by its client,
+    /// which is the scale of its [primary output](Space::primary_output).
+    pub fn preferred_scale(&self, w: &Window) -> Option<Scale> {
+        self.primary_output(w).map(|o| o.current_scale())
+    }
+
+    /// Moves a [`Window`] onto another [`Output`], keeping its geometry relative to the output
+    ///
+    /// The geometry of the window relative to the [usable area](Space::usable_area) of its
+    /// [primary output](Space::primary_output) is mapped proportionally into the usable area of
+    /// `output`, so differently sized or scaled outputs keep the window at the same relative place,
+    /// clamped to the [`SizeConstraints`](crate::desktop::SizeConstraints) of the window and
+    /// inside of the new area. Windows without a primary output are centered. Maximized windows
+    /// are resized to the usable area and fullscreen windows to the whole output.
+    ///
+    /// Xdg toplevels are configured with their new size and fullscreen output, X11 windows have to
+    /// be configured by the window manager. The window stays at its place in the stack and
+    /// enters and leaves outputs right away.
+    ///
+    /// Call this for the windows of an output, before unmapping it, to keep them on screen.
+    /// Returns the new geometry of the window, or `None` if the window or the output is not mapped.
+    pub fn move_window_to_output(
+        &mut self,
+        window: &Window,
+        output: &Output,
+    ) -> Option<Rectangle<i32, Logical>> {
+        if !self.windows.contains(window) {
+            return None;
+        }
+        let usable_area = self.usable_area(output)?;
+        let (maximized, fullscreen) = match window.toplevel() {
+            Kind::Xdg(t) => t
+                .with_pending_state(|state| {
+                    (
+                        state.states.contains(xdg_toplevel::State::Maximized),
+                        state.states.contains(xdg_toplevel::State::Fullscreen),
+                    )
+                })
+                .unwrap_or((false, false)),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(_) => (false, false),
+        };
+
+        let location = window_loc(window, &self.id);
+        let mut geometry = window.geometry();
+        let constraints = window.size_constraints();
+        let clamp = |size| constraints.clamp(size);
+        let new_geometry = if fullscreen {
+            self.output_geometry(output)?
+        } else if maximized {
+            usable_area
+        } else if let Some(area) = self.primary_output(window).and_then(|o| self.usable_area(&o)) {
+            geometry.loc += location;
+            migrate::migrate_geometry(geometry, area, usable_area, clamp)
+        } else {
+            migrate::center_geometry(geometry.size, usable_area, clamp)
+        };
+
+        match window.toplevel() {
+            Kind::Xdg(t) => {
+                let client = t.get_surface().and_then(|surface| surface.as_ref().client());
+                let _ = t.with_pending_state(|state| {
+                    state.size = Some(new_geometry.size);
+                    if fullscreen {
+                        state.fullscreen_output = None;
+                        if let Some(client) = client {
+                            output.with_client_outputs(client, |wl_output| {
+                                state.fullscreen_output = Some(wl_output.clone())
+                            });
+                        }
+                    }
+                });
+                window.configure();
+            }
+            #[cfg(feature = "xwayland")]
+            Kind::X11(_) => {}
+        }
+
+        window_state(self.id, window).location = new_geometry.loc - window.geometry().loc;
+        self.update_window_outputs(window);
+        Some(new_geometry)
+    }
+
     /// Refresh some internal values and update client state,
     /// meaning this will handle output enter and leave events
     /// for mapped outputs and windows based on their position.
     ///
     /// Needs to be called periodically, at best before every
     /// wayland socket flush.
-    pub fn refresh(&mut self) {
+    ///
+    /// Pointers registered with [`Space::track_pointer`] are refocused, if the surface under them
+    /// changed since they were last moved.
+    ///
+    /// Returns the outputs, whose [usable area](Space::usable_area) changed since the last call
+    /// (see [`LayerMap::take_zone_change`](crate::desktop::LayerMap::take_zone_change)),
+    /// together with the new area. Use this to e.g. re-layout maximized windows.
+    pub fn refresh(&mut self) -> Vec<(Output, Rectangle<i32, Logical>)> {
         self.windows.retain(|w| w.toplevel().alive());
+        #[cfg(feature = "xwayland")]
+        self.override_redirect.retain(|w| w.alive());
 
         for output in &mut self.outputs {
-            output_state(self.id, output)
-                .surfaces
-                .retain(|s| s.as_ref().is_alive());
+            let config_changed = {
+                let mut state = output_state(self.id, output);
+                state.surfaces.retain(|s| s.as_ref().is_alive());
+                state.update_config(output)
+            };
+            if config_changed {
+                layer_map_for_output(output).arrange();
+            }
         }
 
         for window in &self.windows {
-            let bbox = window_rect(window, &self.id);
-            let kind = window.toplevel();
+            self.update_window_outputs(window);
+        }
 
-            for output in &self.outputs {
-                let output_geometry = self
-                    .output_geometry(output)
-                    .unwrap_or_else(|| Rectangle::from_loc_and_size((0, 0), (0, 0)));
+        #[cfg(feature = "xwayland")]
+        for window in &self.override_redirect {
+            let surface = match window.get_surface() {
+                Some(surface) => surface,
+                None => continue,
+            };
+            for (output, output_geometry, entered) in self.entered_outputs(window.geometry()) {
                 let mut output_state = output_state(self.id, output);
-
-                // Check if the bounding box of the toplevel intersects with
-                // the output, if not no surface in the tree can intersect with
-                // the output.
-                if !output_geometry.overlaps(bbox) {
-                    if let Some(surface) = kind.get_surface() {
-                        output_leave(output, &mut output_state.surfaces, surface, &self.logger);
-                    }
-                    continue;
-                }
-
-                if let Some(surface) = kind.get_surface() {
+                if entered {
                     output_update(
                         output,
                         output_geometry,
                         &mut output_state.surfaces,
                         surface,
-                        window_loc(window, &self.id),
+                        window.location(),
                         &self.logger,
                     );
+                } else {
+                    output_leave_tree(output, &mut output_state.surfaces, surface, &self.logger);
+                }
+            }
+        }
 
-                    for (popup, location) in PopupManager::popups_for_surface(surface)
-                        .ok()
-                        .into_iter()
-                        .flatten()
-                    {
-                        if let Some(surface) = popup.get_surface() {
-                            let location = window_loc(window, &self.id) + window.geometry().loc + location
-                                - popup.geometry().loc;
-                            output_update(
-                                output,
-                                output_geometry,
-                                &mut output_state.surfaces,
-                                surface,
-                                location,
-                                &self.logger,
-                            );
-                        }
-                    }
+        let mut pointers = std::mem::take(&mut self.pointers);
+        for pointer in &mut pointers {
+            pointer.refocus(self);
+        }
+        self.pointers = pointers;
+
+        self.outputs
+            .iter()
+            .filter(|output| layer_map_for_output(output).take_zone_change().is_some())
+            .filter_map(|output| Some((output.clone(), self.usable_area(output)?)))
+            .collect()
+    }
+
+    /// Sends enter and leave events of the outputs a window entered or left to its surfaces and popups
+    fn update_window_outputs(&self, window: &Window) {
+        let surface = match window.toplevel().get_surface() {
+            Some(surface) => surface,
+            None => return,
+        };
+        let bbox = window_rect(window, &self.id);
+        let popups = PopupManager::popups_for_surface(surface)
+            .ok()
+            .into_iter()
+            .flatten()
+            .filter_map(|(popup, location)| {
+                let location =
+                    window_loc(window, &self.id) + window.geometry().loc + location - popup.geometry().loc;
+                popup.get_surface().map(|surface| (surface.clone(), location))
+            })
+            .collect::<Vec<_>>();
+
+        // the whole window, including its subsurfaces and popups, either enters an output or not
+        for (output, output_geometry, entered) in self.entered_outputs(bbox) {
+            let mut output_state = output_state(self.id, output);
+            let surfaces = std::iter::once((surface.clone(), window_loc(window, &self.id)))
+                .chain(popups.iter().cloned());
+            for (surface, location) in surfaces {
+                if entered {
+                    output_update(
+                        output,
+                        output_geometry,
+                        &mut output_state.surfaces,
+                        &surface,
+                        location,
+                        &self.logger,
+                    );
+                } else {
+                    output_leave_tree(output, &mut output_state.surfaces, &surface, &self.logger);
                 }
             }
         }
     }
 
+    /// Keeps the focus of a pointer in sync with the contents of this space
+    ///
+    /// Windows moving, resizing, being unmapped or restacked under a stationary cursor change the
+    /// surface under it. Every [`Space::refresh`] asks `surface_under` for the surface under the
+    /// current location of the pointer and its location in the space, and sends the needed
+    /// enter, leave and motion events if it is not the current focus of the pointer anymore.
+    /// Use the same logic as when handling pointer motion events, for example
+    /// `|space, point| space.surface_under(point, WindowSurfaceType::ALL).map(|(_, s, loc)| (s, loc))`
+    /// if you only have windows.
+    ///
+    /// Pointers are not refocused while they are grabbed.
+    pub fn track_pointer<F>(&mut self, pointer: &PointerHandle, surface_under: F)
+    where
+        F: FnMut(&Space, Point<f64, Logical>) -> Option<(WlSurface, Point<i32, Logical>)> + 'static,
+    {
+        self.untrack_pointer(pointer);
+        self.pointers.push(TrackedPointer {
+            pointer: pointer.clone(),
+            surface_under: Box::new(surface_under),
+        });
+    }
+
+    /// Stops keeping the focus of a pointer in sync, see [`Space::track_pointer`]
+    pub fn untrack_pointer(&mut self, pointer: &PointerHandle) {
+        self.pointers.retain(|tracked| &tracked.pointer != pointer);
+    }
+
     /// Should be called on commit to let the space automatically call [`Window::refresh`]
-    /// for the window that belongs to the given surface, if managed by this space.
+    /// (or `OverrideRedirectWindow::refresh`) for the window that belongs to the given surface,
+    /// if managed by this space.
     pub fn commit(&self, surface: &WlSurface) {
         if is_sync_subsurface(surface) {
             return;
@@ -389,6 +822,10 @@ impl Space {
         if let Some(window) = self.windows().find(|w| w.toplevel().get_surface() == Some(&root)) {
             window.refresh();
         }
+        #[cfg(feature = "xwayland")]
+        if let Some(window) = self.override_redirect_for_surface(&root) {
+            window.refresh();
+        }
     }
 
     /// Render a given [`Output`] using a given [`Renderer`].
@@ -406,7 +843,8 @@ impl Space {
     ///
     /// To add aditional elements without breaking damage-tracking implement the `RenderElement`
     /// trait and use `custom_elements` to provide them to this function. `custom_elements are rendered
-    /// after every other element.
+    /// after every other element. Elements only shown on this output can also be set once with
+    /// [`Space::set_output_elements`].
     ///
     /// Returns a list of updated regions relative to the rendered output
     /// (or `None` if that list would be empty) in case of success.
@@ -422,12 +860,114 @@ impl Space {
         R: Renderer + ImportAll,
         R::TextureId: 'static,
         E: RenderElement<R>,
+    {
+        self.render_output_internal(renderer, output, Some(age), clear_color, custom_elements, |_| {
+            true
+        })
+    }
+
+    /// Renders a given [`Output`] as it is displayed into the target currently bound to `renderer`
+    ///
+    /// This renders everything [`Space::render_output`] renders, but always redraws the whole
+    /// output and does not influence its damage tracking, so it can be used for screenshots in
+    /// between frames. Unlike [`Space::render_output`] the output transform is not applied, so
+    /// the target needs to have the size of the output mode rotated by the transform, see
+    /// [`Space::output_capture_size`].
+    ///
+    /// `custom_elements` and the elements set by [`Space::set_output_elements`] are only
+    /// drawn, if `keep` returns `true` for them. Pass the elements shown on hardware planes
+    /// instead of being composited, like the cursor, and leave out overlays of the compositor,
+    /// that should not be part of the screenshot.
+    pub fn render_output_capture<R, E, F>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+        clear_color: [f32; 4],
+        custom_elements: &[E],
+        keep: F,
+    ) -> Result<(), RenderError<R>>
+    where
+        R: Renderer + ImportAll,
+        R::TextureId: 'static,
+        E: RenderElement<R>,
+        F: Fn(&E) -> bool,
+    {
+        self.render_output_internal(renderer, output, None, clear_color, custom_elements, keep)
+            .map(|_| ())
+    }
+
+    /// Captures a given [`Output`] as it is displayed into memory
+    ///
+    /// Renders the output with [`Space::render_output_capture`] into a new offscreen buffer of
+    /// type `T` and copies its contents. Use [`ExportMem::map_texture`] to access the pixels.
+    /// This changes the target bound to `renderer`, so bind the target of the next frame again
+    /// before rendering it.
+    ///
+    /// To capture into a dmabuf instead, bind it and use [`Space::render_output_capture`].
+    pub fn capture_output<R, T, E, F>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+        clear_color: [f32; 4],
+        custom_elements: &[E],
+        keep: F,
+    ) -> Result<R::TextureMapping, RenderError<R>>
+    where
+        R: Renderer + ImportAll + Offscreen<T> + ExportMem,
+        R::TextureId: 'static,
+        E: RenderElement<R>,
+        F: Fn(&E) -> bool,
+    {
+        if !self.outputs.contains(output) {
+            return Err(RenderError::UnmappedOutput);
+        }
+        let size = self
+            .output_capture_size(output)
+            .ok_or(RenderError::OutputNoMode)?;
+        let region = Rectangle::from_loc_and_size((0, 0), (size.w, size.h));
+        let buffer = renderer
+            .create_buffer(region.size)
+            .map_err(RenderError::Rendering)?;
+        renderer.bind(buffer).map_err(RenderError::Rendering)?;
+        self.render_output_capture(renderer, output, clear_color, custom_elements, keep)?;
+        renderer.copy_framebuffer(region).map_err(RenderError::Rendering)
+    }
+
+    /// Returns the size of the images captured by [`Space::capture_output`]
+    ///
+    /// This is the size of the current mode of the output rotated by its transform, or `None`
+    /// if it has no mode.
+    pub fn output_capture_size(&self, output: &Output) -> Option<Size<i32, Physical>> {
+        let transform: Transform = output.current_transform().into();
+        output
+            .current_mode()
+            .map(|mode| transform.transform_size(mode.size))
+    }
+
+    /// Renders an output with damage tracking, if `age` is `Some`, or captures it otherwise
+    fn render_output_internal<R, E, F>(
+        &mut self,
+        renderer: &mut R,
+        output: &Output,
+        age: Option<usize>,
+        clear_color: [f32; 4],
+        custom_elements: &[E],
+        keep: F,
+    ) -> Result<Option<Vec<Rectangle<i32, Logical>>>, RenderError<R>>
+    where
+        R: Renderer + ImportAll,
+        R::TextureId: 'static,
+        E: RenderElement<R>,
+        F: Fn(&E) -> bool,
     {
         if !self.outputs.contains(output) {
             return Err(RenderError::UnmappedOutput);
         }
 
         let mut state = output_state(self.id, output);
+        if state.update_config(output) {
+            layer_map_for_output(output).arrange();
+        }
         let output_size = output.current_mode().ok_or(RenderError::OutputNoMode)?.size;
         // We explicitly use ceil for the output geometry size to make sure the damage
         // spans at least the output size. Round and floor would result in parts not drawn as the
@@ -440,6 +980,12 @@ impl Space {
                 .to_i32_ceil(),
         );
         let layer_map = layer_map_for_output(output);
+        let output_elements = state.elements.clone();
+        let output_elements = output_elements
+            .as_deref()
+            .and_then(|elements| elements.downcast_ref::<Vec<E>>())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
 
         let window_popups = self
             .windows
@@ -453,6 +999,7 @@ impl Space {
 
         let mut render_elements: Vec<SpaceElement<'_, R, E>> = Vec::with_capacity(
             custom_elements.len()
+                + output_elements.len()
                 + layer_map.len()
                 + self.windows.len()
                 + window_popups.len()
@@ -462,15 +1009,42 @@ impl Space {
         render_elements.extend(
             custom_elements
                 .iter()
+                .chain(output_elements)
+                .filter(|e| keep(e))
                 .map(|e| SpaceElement::Custom(e, std::marker::PhantomData)),
         );
         render_elements.extend(self.windows.iter().map(SpaceElement::Window));
         render_elements.extend(window_popups.iter().map(SpaceElement::Popup));
         render_elements.extend(layer_map.layers().map(SpaceElement::Layer));
         render_elements.extend(layer_popups.iter().map(SpaceElement::Popup));
+        #[cfg(feature = "xwayland")]
+        render_elements.extend(self.override_redirect.iter().map(SpaceElement::OverrideRedirect));
 
         render_elements.sort_by_key(|e| e.z_index());
 
+        let output_transform: Transform = output.current_transform().into();
+        let output_scale = output.current_scale().fractional_scale();
+        let age = match age {
+            Some(age) => age,
+            None => {
+                // captures redraw everything, without consuming the damage of the next frame
+                let damage = [output_geo];
+                return self
+                    .draw_elements(
+                        renderer,
+                        &render_elements,
+                        output_transform.transform_size(output_size),
+                        Transform::Normal,
+                        output_scale,
+                        output_geo,
+                        &damage,
+                        clear_color,
+                    )
+                    .map(|_| None)
+                    .map_err(RenderError::Rendering);
+            }
+        };
+
         // This will hold all the damage we need for this rendering step
         let mut damage = Vec::<Rectangle<i32, Logical>>::new();
         // First add damage for windows gone
@@ -518,42 +1092,80 @@ impl Space {
         // That is all completely new damage, which we need to store for subsequent renders
         let new_damage = damage.clone();
         // We now add old damage states, if we have an age value
-        if age > 0 && state.old_damage.len() >= age {
-            // We do not need even older states anymore
-            state.old_damage.truncate(age);
-            damage.extend(state.old_damage.iter().flatten().copied());
+        if let Some(old_damage) = state.old_damage.damage_for_age(age) {
+            damage.extend(old_damage);
         } else {
             // just damage everything, if we have no damage
             damage = vec![output_geo];
         }
 
         // Optimize the damage for rendering
-        damage.dedup();
-        damage.retain(|rect| rect.overlaps(output_geo));
-        damage.retain(|rect| rect.size.h > 0 && rect.size.w > 0);
-        // merge overlapping rectangles
-        damage = damage.into_iter().fold(Vec::new(), |new_damage, mut rect| {
-            // replace with drain_filter, when that becomes stable to reuse the original Vec's memory
-            let (overlapping, mut new_damage): (Vec<_>, Vec<_>) =
-                new_damage.into_iter().partition(|other| other.overlaps(rect));
-
-            for overlap in overlapping {
-                rect = rect.merge(overlap);
-            }
-            new_damage.push(rect);
-            new_damage
-        });
+        let damage = optimize_damage(damage, output_geo);
 
         if damage.is_empty() {
             return Ok(None);
         }
 
-        let output_transform: Transform = output.current_transform().into();
-        let output_scale = output.current_scale().fractional_scale();
-        let res = renderer.render(
+        let res = self.draw_elements(
+            renderer,
+            &render_elements,
             output_transform.transform_size(output_size),
             output_transform,
-            |renderer, frame| {
+            output_scale,
+            output_geo,
+            &damage,
+            clear_color,
+        );
+
+        if let Err(err) = res {
+            // if the rendering errors on us, we need to be prepared, that this whole buffer was partially updated and thus now unusable.
+            // thus clean our old states before returning
+            state.old_damage.clear();
+            state.last_state = IndexMap::new();
+            return Err(RenderError::Rendering(err));
+        }
+
+        // If rendering was successful capture the state and add the damage
+        state.last_state = render_elements
+            .iter()
+            .map(|elem| {
+                let geo = elem.geometry(self.id);
+                (ToplevelId::from(elem), geo)
+            })
+            .collect();
+        state.old_damage.push(new_damage.clone());
+
+        Ok(Some(
+            new_damage
+                .into_iter()
+                .map(|mut geo| {
+                    geo.loc -= output_geo.loc;
+                    geo
+                })
+                .collect(),
+        ))
+    }
+
+    /// Draws the elements overlapping with `damage`, which is in global coordinates
+    #[allow(clippy::too_many_arguments)]
+    fn draw_elements<R, E>(
+        &self,
+        renderer: &mut R,
+        render_elements: &[SpaceElement<'_, R, E>],
+        size: Size<i32, Physical>,
+        transform: Transform,
+        output_scale: f64,
+        output_geo: Rectangle<i32, Logical>,
+        damage: &[Rectangle<i32, Logical>],
+        clear_color: [f32; 4],
+    ) -> Result<(), R::Error>
+    where
+        R: Renderer + ImportAll,
+        R::TextureId: 'static,
+        E: RenderElement<R>,
+    {
+        renderer
+            .render(size, transform, |renderer, frame| {
                 // First clear all damaged regions
                 slog::trace!(self.logger, "Clearing at {:#?}", damage);
                 frame.clear(
@@ -568,7 +1180,7 @@ impl Space {
                 )?;
                 // Then re-draw all windows & layers overlapping with a damage rect.
 
-                for element in &render_elements {
+                for element in render_elements {
                     let geo = element.geometry(self.id);
                     if damage.iter().any(|d| d.overlaps(geo)) {
                         let loc = element.location(self.id);
@@ -584,6 +1196,7 @@ impl Space {
                             Rectangle::from_loc_and_size(geo.loc - output_geo.loc, geo.size),
                             damage
                         );
+                        frame.begin_timing_section(&element.timing_label());
                         element.draw(
                             self.id,
                             renderer,
@@ -593,40 +1206,13 @@ impl Space {
                             &damage,
                             &self.logger,
                         )?;
+                        frame.end_timing_section();
                     }
                 }
 
                 Result::<(), R::Error>::Ok(())
-            },
-        );
-
-        if let Err(err) = res {
-            // if the rendering errors on us, we need to be prepared, that this whole buffer was partially updated and thus now unusable.
-            // thus clean our old states before returning
-            state.old_damage = VecDeque::new();
-            state.last_state = IndexMap::new();
-            return Err(RenderError::Rendering(err));
-        }
-
-        // If rendering was successful capture the state and add the damage
-        state.last_state = render_elements
-            .iter()
-            .map(|elem| {
-                let geo = elem.geometry(self.id);
-                (ToplevelId::from(elem), geo)
             })
-            .collect();
-        state.old_damage.push_front(new_damage.clone());
-
-        Ok(Some(
-            new_damage
-                .into_iter()
-                .map(|mut geo| {
-                    geo.loc -= output_geo.loc;
-                    geo
-                })
-                .collect(),
-        ))
+            .and_then(|res| res)
     }
 
     /// Sends the frame callback to mapped [`Window`]s and [`LayerSurface`]s.
@@ -635,6 +1221,11 @@ impl Space {
             window.send_frame(time);
         }
 
+        #[cfg(feature = "xwayland")]
+        for window in self.override_redirect.iter() {
+            window.send_frame(time);
+        }
+
         for output in self.outputs.iter() {
             let map = layer_map_for_output(output);
             for layer in map.layers() {
@@ -1,6 +1,9 @@
 use crate::{
-    backend::renderer::{ImportAll, Renderer},
-    desktop::space::{RenderElement, SpaceElement},
+    backend::renderer::{damage::DamageHistory, ImportAll, Renderer},
+    desktop::{
+        space::{RenderElement, SpaceElement},
+        utils::OutputConfig,
+    },
     utils::{Logical, Point, Rectangle},
     wayland::output::Output,
 };
@@ -8,9 +11,10 @@ use indexmap::IndexMap;
 use wayland_server::protocol::wl_surface::WlSurface;
 
 use std::{
-    any::TypeId,
+    any::{Any, TypeId},
     cell::{RefCell, RefMut},
-    collections::{HashMap, VecDeque},
+    collections::HashMap,
+    rc::Rc,
 };
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -38,14 +42,45 @@ pub struct OutputState {
     pub location: Point<i32, Logical>,
 
     // damage and last_state are in space coordinate space
-    pub old_damage: VecDeque<Vec<Rectangle<i32, Logical>>>,
+    pub old_damage: DamageHistory<Logical>,
     pub last_state: IndexMap<ToplevelId, Rectangle<i32, Logical>>,
 
     // surfaces for tracking enter and leave events
     pub surfaces: Vec<WlSurface>,
+
+    // mode, scale and transform of the output the damage was tracked for
+    pub config: OutputConfig,
+
+    // `Vec<E>` of additional elements only rendered on this output
+    pub elements: Option<Rc<dyn Any>>,
+}
+
+impl OutputState {
+    /// Checks if the mode, scale or transform of the output changed since the last call,
+    /// in which case all tracked damage is discarded, so the output gets fully redrawn.
+    pub fn update_config(&mut self, output: &Output) -> bool {
+        let changed = self.config.update(output);
+        if changed {
+            self.old_damage.clear();
+            self.last_state.clear();
+        }
+        changed
+    }
 }
 
 pub type OutputUserdata = RefCell<HashMap<usize, OutputState>>;
+
+/// Makes the surfaces of all spaces on an output leave and re-enter it
+pub(crate) fn reenter_output_surfaces(output: &Output) {
+    if let Some(states) = output.user_data().get::<OutputUserdata>() {
+        if let Ok(states) = states.try_borrow() {
+            for surface in states.values().flat_map(|state| &state.surfaces) {
+                output.leave(surface);
+                output.enter(surface);
+            }
+        }
+    }
+}
 pub fn output_state(space: usize, o: &Output) -> RefMut<'_, OutputState> {
     let userdata = o.user_data();
     userdata.insert_if_missing(OutputUserdata::default);
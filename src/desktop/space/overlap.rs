@@ -0,0 +1,173 @@
+/// Rule to pick the primary output of a window overlapping multiple outputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputDominance {
+    /// The output showing the largest part of the window
+    LargestOverlap,
+    /// The entered output with the highest scale, so the window is never shown upscaled
+    HighestScale,
+    /// The entered output with the lowest scale, to keep the buffers of the window small
+    LowestScale,
+}
+
+/// Defines how a [`Space`](super::Space) sends `wl_surface.enter`/`leave` events
+/// and chooses the primary output of windows spanning multiple outputs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputOverlapPolicy {
+    /// Fraction (between `0.0` and `1.0`) of the bounding box of a window, that has to overlap
+    /// an output for the window to enter it.
+    ///
+    /// This is decided once for the whole window, its subsurfaces and popups then enter the output,
+    /// if they overlap it at all. `0.0` lets windows enter every output they overlap, while edges merely
+    /// touching an output never count as an overlap. Windows always enter the output covering most
+    /// of them, even if the threshold is not reached.
+    pub enter_threshold: f64,
+    /// Rule to pick the primary output of a window, see [`Space::primary_output`](super::Space::primary_output)
+    pub dominance: OutputDominance,
+}
+
+impl Default for OutputOverlapPolicy {
+    fn default() -> Self {
+        OutputOverlapPolicy {
+            enter_threshold: 0.0,
+            dominance: OutputDominance::LargestOverlap,
+        }
+    }
+}
+
+impl OutputOverlapPolicy {
+    /// Threshold an element needs to reach to enter an output, given its overlap with every output
+    fn threshold(&self, overlaps: impl IntoIterator<Item = f64>) -> f64 {
+        // never let the threshold exceed the best overlap, so the element enters at least one output
+        let best = overlaps.into_iter().fold(0.0, f64::max);
+        self.enter_threshold.min(best)
+    }
+
+    /// Decides for every output, if an element with the given overlap enters it
+    pub(super) fn entered_outputs<T>(&self, outputs: impl IntoIterator<Item = (T, f64)>) -> Vec<(T, bool)> {
+        let outputs = outputs.into_iter().collect::<Vec<_>>();
+        let threshold = self.threshold(outputs.iter().map(|(_, overlap)| *overlap));
+        outputs
+            .into_iter()
+            .map(|(output, overlap)| (output, overlap > 0.0 && overlap >= threshold))
+            .collect()
+    }
+
+    /// Picks the primary output among the outputs an element overlaps, given as `(output, overlap, scale)`
+    ///
+    /// Ties are broken by the larger overlap and then by the order of the outputs.
+    pub(super) fn primary_output<T>(&self, outputs: impl IntoIterator<Item = (T, f64, f64)>) -> Option<T> {
+        let outputs = outputs.into_iter().collect::<Vec<_>>();
+        let threshold = self.threshold(outputs.iter().map(|(_, overlap, _)| *overlap));
+        outputs
+            .into_iter()
+            .filter(|(_, overlap, _)| *overlap > 0.0 && *overlap >= threshold)
+            .map(|(output, overlap, scale)| {
+                let key = match self.dominance {
+                    OutputDominance::LargestOverlap => overlap,
+                    OutputDominance::HighestScale => scale,
+                    OutputDominance::LowestScale => -scale,
+                };
+                (output, key, overlap)
+            })
+            .fold(None, |best: Option<(T, f64, f64)>, candidate| match best {
+                Some(best) if (best.1, best.2) >= (candidate.1, candidate.2) => Some(best),
+                _ => Some(candidate),
+            })
+            .map(|(output, _, _)| output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{desktop::utils::overlap_fraction, utils::Rectangle};
+
+    fn policy(enter_threshold: f64, dominance: OutputDominance) -> OutputOverlapPolicy {
+        OutputOverlapPolicy {
+            enter_threshold,
+            dominance,
+        }
+    }
+
+    #[test]
+    fn overlap_fraction_of_rects() {
+        let rect = Rectangle::from_loc_and_size((0, 0), (100, 100));
+        assert_eq!(overlap_fraction(rect, rect), 1.0);
+        assert_eq!(
+            overlap_fraction(rect, Rectangle::from_loc_and_size((50, 0), (100, 100))),
+            0.5
+        );
+        assert_eq!(
+            overlap_fraction(rect, Rectangle::from_loc_and_size((-50, -50), (100, 100))),
+            0.25
+        );
+        // touching edges do not overlap
+        assert_eq!(
+            overlap_fraction(rect, Rectangle::from_loc_and_size((100, 0), (100, 100))),
+            0.0
+        );
+        assert_eq!(
+            overlap_fraction(Rectangle::from_loc_and_size((0, 0), (0, 10)), rect),
+            0.0
+        );
+    }
+
+    #[test]
+    fn entered_outputs_threshold() {
+        let overlaps = vec![("a", 0.7), ("b", 0.3), ("c", 0.0)];
+        assert_eq!(
+            policy(0.0, OutputDominance::LargestOverlap).entered_outputs(overlaps.clone()),
+            vec![("a", true), ("b", true), ("c", false)]
+        );
+        assert_eq!(
+            policy(0.5, OutputDominance::LargestOverlap).entered_outputs(overlaps),
+            vec![("a", true), ("b", false), ("c", false)]
+        );
+        // the output covering most of the element is always entered
+        assert_eq!(
+            policy(0.9, OutputDominance::LargestOverlap).entered_outputs(vec![("a", 0.4), ("b", 0.4)]),
+            vec![("a", true), ("b", true)]
+        );
+    }
+
+    #[test]
+    fn primary_output_dominance() {
+        // (output, overlap, scale)
+        let outputs = vec![("a", 0.6, 1.0), ("b", 0.3, 2.0), ("c", 0.1, 0.5)];
+        assert_eq!(
+            policy(0.0, OutputDominance::LargestOverlap).primary_output(outputs.clone()),
+            Some("a")
+        );
+        assert_eq!(
+            policy(0.0, OutputDominance::HighestScale).primary_output(outputs.clone()),
+            Some("b")
+        );
+        assert_eq!(
+            policy(0.0, OutputDominance::LowestScale).primary_output(outputs.clone()),
+            Some("c")
+        );
+        // outputs below the threshold are not considered
+        assert_eq!(
+            policy(0.2, OutputDominance::LowestScale).primary_output(outputs.clone()),
+            Some("a")
+        );
+        assert_eq!(
+            policy(0.5, OutputDominance::HighestScale).primary_output(outputs),
+            Some("a")
+        );
+    }
+
+    #[test]
+    fn primary_output_ties() {
+        // equal scales are decided by the overlap, then by the order
+        let outputs = vec![("a", 0.2, 2.0), ("b", 0.5, 2.0), ("c", 0.5, 2.0)];
+        assert_eq!(
+            policy(0.0, OutputDominance::HighestScale).primary_output(outputs),
+            Some("b")
+        );
+        assert_eq!(
+            policy(0.0, OutputDominance::LargestOverlap).primary_output(vec![("a", 0.0, 1.0)]),
+            None
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    backend::renderer::{ImportAll, Renderer},
+    desktop::{override_redirect::*, space::Space},
+    utils::{Logical, Point, Rectangle},
+    wayland::output::Output,
+};
+use std::any::TypeId;
+
+use super::RenderZindex;
+
+impl OverrideRedirectWindow {
+    pub(super) fn elem_id(&self) -> usize {
+        self.0.id
+    }
+
+    pub(super) fn elem_type_of(&self) -> TypeId {
+        TypeId::of::<OverrideRedirectWindow>()
+    }
+
+    pub(super) fn elem_location(&self) -> Point<i32, Logical> {
+        self.location()
+    }
+
+    pub(super) fn elem_geometry(&self) -> Rectangle<i32, Logical> {
+        self.geometry()
+    }
+
+    pub(super) fn elem_accumulated_damage(
+        &self,
+        for_values: Option<(&Space, &Output)>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        self.accumulated_damage(for_values)
+    }
+
+    pub(super) fn elem_draw<R>(
+        &self,
+        renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        location: Point<i32, Logical>,
+        damage: &[Rectangle<i32, Logical>],
+        log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error>
+    where
+        R: Renderer + ImportAll,
+        <R as Renderer>::TextureId: 'static,
+    {
+        draw_override_redirect(renderer, frame, self, scale, location, damage, log)
+    }
+
+    pub(super) fn elem_z_index(&self) -> u8 {
+        RenderZindex::OverrideRedirect as u8
+    }
+}
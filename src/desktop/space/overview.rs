@@ -0,0 +1,287 @@
+use crate::{
+    backend::renderer::{ImportAll, Renderer},
+    desktop::{draw_window, space::*, Window},
+    utils::{Logical, Point, Rectangle, Size},
+};
+
+/// Window of an [`Overview`] and the tile it is scaled into
+#[derive(Debug, Clone)]
+pub struct OverviewTile {
+    window: Window,
+    // bounding box of the window relative to its (0, 0)
+    bbox: Rectangle<i32, Logical>,
+    // location of (0, 0) of the window in the space
+    origin: Point<i32, Logical>,
+    tile: Rectangle<i32, Logical>,
+    scale: f64,
+}
+
+impl OverviewTile {
+    /// Returns the window of this tile
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// Returns the area the window is drawn in by the overview
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.tile
+    }
+
+    /// Returns the factor the window is scaled by, at most `1.0`
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    // maps a point of the tile to a point relative to (0, 0) of the window
+    fn map_point(&self, point: Point<f64, Logical>) -> Point<f64, Logical> {
+        let offset = point - self.tile.loc.to_f64();
+        Point::from((offset.x / self.scale, offset.y / self.scale)) + self.bbox.loc.to_f64()
+    }
+
+    fn window_to_tile(&self, rect: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let loc = rect.loc - self.bbox.loc;
+        let (x, y) = (loc.x as f64 * self.scale, loc.y as f64 * self.scale);
+        let (w, h) = (rect.size.w as f64 * self.scale, rect.size.h as f64 * self.scale);
+        Rectangle::from_extemities(
+            (x.floor() as i32, y.floor() as i32),
+            ((x + w).ceil() as i32, (y + h).ceil() as i32),
+        )
+    }
+
+    fn tile_to_window(&self, rect: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let loc = self.map_point(rect.loc.to_f64() + self.tile.loc.to_f64());
+        let size = (rect.size.w as f64 / self.scale, rect.size.h as f64 / self.scale);
+        Rectangle::from_extemities(
+            (loc.x.floor() as i32, loc.y.floor() as i32),
+            ((loc.x + size.0).ceil() as i32, (loc.y + size.1).ceil() as i32),
+        )
+    }
+}
+
+/// The windows of a [`Space`] scaled into a grid, e.g. to show an overview of all windows
+///
+/// Created by [`Space::overview`]. Windows are only scaled down, never up, and keep their
+/// aspect ratio. They are drawn by the [`OverviewElement`]s returned by
+/// [`Overview::elements`], which do not change the windows or their location in the space.
+/// Render them with [`Space::render_output`] of a separate space without any mapped windows
+/// instead, so the windows are not drawn at their original location as well.
+///
+/// Input in the overview is mapped back to the windows by [`Overview::window_under`].
+#[derive(Debug, Clone)]
+pub struct Overview {
+    tiles: Vec<OverviewTile>,
+}
+
+/// Scales items of the given sizes into a grid in `area`, returning their tiles and scales
+pub(super) fn grid_layout(
+    sizes: &[Size<i32, Logical>],
+    area: Rectangle<i32, Logical>,
+    gap: i32,
+) -> Vec<(Rectangle<i32, Logical>, f64)> {
+    if sizes.is_empty() {
+        return Vec::new();
+    }
+    let count = sizes.len() as i32;
+    // as many columns as rows, a little wider on wide areas
+    let aspect = area.size.w.max(1) as f64 / area.size.h.max(1) as f64;
+    let columns = ((count as f64 * aspect).sqrt().ceil() as i32).clamp(1, count);
+    let rows = (count + columns - 1) / columns;
+    let cell = Size::<i32, Logical>::from((
+        ((area.size.w - gap * (columns + 1)) / columns).max(1),
+        ((area.size.h - gap * (rows + 1)) / rows).max(1),
+    ));
+
+    sizes
+        .iter()
+        .enumerate()
+        .map(|(idx, size)| {
+            let (column, row) = (idx as i32 % columns, idx as i32 / columns);
+            let scale = (cell.w as f64 / size.w.max(1) as f64)
+                .min(cell.h as f64 / size.h.max(1) as f64)
+                .min(1.0);
+            let tile_size = Size::<i32, Logical>::from((
+                (size.w as f64 * scale).round() as i32,
+                (size.h as f64 * scale).round() as i32,
+            ));
+            // center the tile in its cell
+            let cell_loc = Point::<i32, Logical>::from((
+                area.loc.x + gap + column * (cell.w + gap),
+                area.loc.y + gap + row * (cell.h + gap),
+            ));
+            let loc = cell_loc + Point::from(((cell.w - tile_size.w) / 2, (cell.h - tile_size.h) / 2));
+            (Rectangle::from_loc_and_size(loc, tile_size), scale)
+        })
+        .collect()
+}
+
+impl Overview {
+    pub(super) fn new(space: &Space, area: Rectangle<i32, Logical>, gap: i32) -> Overview {
+        let windows = space
+            .windows()
+            .map(|window| (window.clone(), window.bbox(), window.elem_location(space.id)))
+            .collect::<Vec<_>>();
+        let sizes = windows.iter().map(|(_, bbox, _)| bbox.size).collect::<Vec<_>>();
+        let tiles = windows
+            .into_iter()
+            .zip(grid_layout(&sizes, area, gap))
+            .map(|((window, bbox, origin), (tile, scale))| OverviewTile {
+                window,
+                bbox,
+                origin,
+                tile,
+                scale,
+            })
+            .collect();
+        Overview { tiles }
+    }
+
+    /// Returns the tiles of all windows, in the order of the windows in the space
+    pub fn tiles(&self) -> &[OverviewTile] {
+        &self.tiles
+    }
+
+    /// Returns the tile of a window
+    pub fn tile(&self, window: &Window) -> Option<&OverviewTile> {
+        self.tiles.iter().find(|tile| &tile.window == window)
+    }
+
+    /// Returns the window under a point of the overview and the point relative to (0, 0)
+    /// of the window, to be passed to [`Window::surface_under`]
+    pub fn window_under<P: Into<Point<f64, Logical>>>(
+        &self,
+        point: P,
+    ) -> Option<(&Window, Point<f64, Logical>)> {
+        let point = point.into();
+        self.tiles
+            .iter()
+            .rev()
+            .find(|tile| tile.tile.to_f64().contains(point))
+            .map(|tile| (&tile.window, tile.map_point(point)))
+    }
+
+    /// Maps a point of the overview to the location in the space, where the same point
+    /// of the window under it is located
+    pub fn space_location<P: Into<Point<f64, Logical>>>(&self, point: P) -> Option<Point<f64, Logical>> {
+        let point = point.into();
+        self.tiles
+            .iter()
+            .rev()
+            .find(|tile| tile.tile.to_f64().contains(point))
+            .map(|tile| tile.map_point(point) + tile.origin.to_f64())
+    }
+
+    /// Returns the elements drawing the scaled windows
+    pub fn elements(&self) -> Vec<OverviewElement> {
+        self.tiles.iter().cloned().map(OverviewElement).collect()
+    }
+}
+
+/// [`RenderElement`] drawing a window scaled into its tile of an [`Overview`]
+#[derive(Debug, Clone)]
+pub struct OverviewElement(OverviewTile);
+
+impl OverviewElement {
+    /// Returns the tile drawn by this element
+    pub fn tile(&self) -> &OverviewTile {
+        &self.0
+    }
+}
+
+impl<R> RenderElement<R> for OverviewElement
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    fn id(&self) -> usize {
+        self.0.window.elem_id()
+    }
+
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.0.tile
+    }
+
+    fn accumulated_damage(
+        &self,
+        for_values: Option<SpaceOutputTuple<'_, '_>>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        self.0
+            .window
+            .accumulated_damage(for_values.map(|SpaceOutputTuple(space, output)| (space, output)))
+            .into_iter()
+            .map(|rect| self.0.window_to_tile(rect))
+            .collect()
+    }
+
+    fn draw(
+        &self,
+        renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        location: Point<i32, Logical>,
+        damage: &[Rectangle<i32, Logical>],
+        log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        let tile = &self.0;
+        // drawing at a smaller scale shrinks the locations as well, so (0, 0) of the window
+        // has to be placed at the unscaled location
+        let location =
+            Point::<f64, Logical>::from((location.x as f64 / tile.scale, location.y as f64 / tile.scale))
+                - tile.bbox.loc.to_f64();
+        let damage = damage
+            .iter()
+            .map(|rect| tile.tile_to_window(*rect))
+            .collect::<Vec<_>>();
+        draw_window(
+            renderer,
+            frame,
+            &tile.window,
+            scale * tile.scale,
+            location.to_i32_round(),
+            &damage,
+            log,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_of_windows() {
+        let area = Rectangle::from_loc_and_size((0, 0), (1000, 500));
+        let sizes = [(800, 600).into(), (400, 300).into(), (200, 100).into()];
+        let layout = grid_layout(&sizes, area, 10);
+        // three windows fit into three columns on a wide area
+        assert_eq!(layout.len(), 3);
+        assert!(layout.iter().all(|(tile, _)| area.contains_rect(*tile)));
+        for window in 0..2 {
+            assert!(!layout[window].0.overlaps(layout[window + 1].0));
+        }
+        // windows are only scaled down
+        assert_eq!(
+            layout[2],
+            (Rectangle::from_loc_and_size((730, 200), (200, 100)), 1.0)
+        );
+        assert_eq!(layout[0].0.size, (320, 240).into());
+        assert!((layout[0].1 - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rows_on_narrow_areas() {
+        let area = Rectangle::from_loc_and_size((100, 100), (500, 1000));
+        let sizes = vec![(1000, 1000).into(); 4];
+        let layout = grid_layout(&sizes, area, 0);
+        let tiles = layout.iter().map(|(tile, _)| *tile).collect::<Vec<_>>();
+        assert_eq!(
+            tiles,
+            vec![
+                Rectangle::from_loc_and_size((100, 225), (250, 250)),
+                Rectangle::from_loc_and_size((350, 225), (250, 250)),
+                Rectangle::from_loc_and_size((100, 725), (250, 250)),
+                Rectangle::from_loc_and_size((350, 725), (250, 250)),
+            ]
+        );
+        assert!(grid_layout(&[], area, 0).is_empty());
+    }
+}
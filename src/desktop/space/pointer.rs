@@ -0,0 +1,64 @@
+use std::fmt;
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    utils::{monotonic_time, Logical, Point},
+    wayland::{seat::PointerHandle, SERIAL_COUNTER},
+};
+
+use super::Space;
+
+/// Callback returning the surface under a point of a [`Space`] and its location
+pub(super) type SurfaceUnderFn =
+    Box<dyn FnMut(&Space, Point<f64, Logical>) -> Option<(WlSurface, Point<i32, Logical>)>>;
+
+/// Pointer whose focus is kept up to date by [`Space::refresh`]
+pub(super) struct TrackedPointer {
+    pub(super) pointer: PointerHandle,
+    pub(super) surface_under: SurfaceUnderFn,
+}
+
+impl fmt::Debug for TrackedPointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TrackedPointer")
+            .field("pointer", &self.pointer)
+            .field("surface_under", &"...")
+            .finish()
+    }
+}
+
+/// Whether a pointer with the given focus has to be refocused onto `under`
+///
+/// Moving surfaces change the surface-local coordinates of the pointer, so the focus also
+/// changes if only the location of the surface does.
+pub(super) fn focus_changed(
+    focus: Option<&(WlSurface, Point<i32, Logical>)>,
+    under: Option<&(WlSurface, Point<i32, Logical>)>,
+) -> bool {
+    match (focus, under) {
+        (None, None) => false,
+        (Some((focus, focus_loc)), Some((under, under_loc))) => focus != under || focus_loc != under_loc,
+        _ => true,
+    }
+}
+
+impl TrackedPointer {
+    /// Sends the pointer the enter, leave and motion events needed for the surface under it
+    pub(super) fn refocus(&mut self, space: &Space) {
+        // grabs decide the focus themselves, e.g. during an interactive move
+        if self.pointer.is_grabbed() {
+            return;
+        }
+        let location = self.pointer.current_location();
+        let under = (self.surface_under)(space, location);
+        if !focus_changed(self.pointer.current_focus().as_ref(), under.as_ref()) {
+            return;
+        }
+
+        slog::trace!(space.logger, "Refocusing pointer after a layout change"; "location" => ?location);
+        let time = monotonic_time().as_millis() as u32;
+        self.pointer
+            .motion(location, under, SERIAL_COUNTER.next_serial(), time);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    desktop::Window,
+    utils::{Logical, Point, Rectangle},
+    wayland::output::Output,
+};
+
+/// Configuration of the snap zones used by [`Space::snap_preview`](super::Space::snap_preview)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapConfig {
+    /// Distance in logical pixels to the edges of the usable area of an output, at which a window snaps
+    pub edge_threshold: i32,
+    /// Length in logical pixels of the corner zones along each edge
+    pub corner_size: i32,
+    /// Whether a window dropped onto the left or right half of another window snaps to that half
+    pub snap_to_windows: bool,
+}
+
+impl Default for SnapConfig {
+    fn default() -> Self {
+        SnapConfig {
+            edge_threshold: 8,
+            corner_size: 64,
+            snap_to_windows: false,
+        }
+    }
+}
+
+impl SnapConfig {
+    /// Returns the snap position of the edge or corner of `area` the point is in
+    ///
+    /// The top edge maximizes, the bottom edge has no zone except for its corners.
+    pub(super) fn edge_zone(
+        &self,
+        area: Rectangle<i32, Logical>,
+        point: Point<f64, Logical>,
+    ) -> Option<SnapPosition> {
+        let area = area.to_f64();
+        let (threshold, corner) = (self.edge_threshold as f64, self.corner_size as f64);
+        let right_edge = area.loc.x + area.size.w;
+        let bottom_edge = area.loc.y + area.size.h;
+
+        let near_left = point.x < area.loc.x + corner;
+        let near_right = point.x >= right_edge - corner;
+        let near_top = point.y < area.loc.y + corner;
+        let near_bottom = point.y >= bottom_edge - corner;
+
+        let position = if point.x < area.loc.x + threshold {
+            match (near_top, near_bottom) {
+                (true, _) => SnapPosition::TopLeft,
+                (_, true) => SnapPosition::BottomLeft,
+                _ => SnapPosition::Left,
+            }
+        } else if point.x >= right_edge - threshold {
+            match (near_top, near_bottom) {
+                (true, _) => SnapPosition::TopRight,
+                (_, true) => SnapPosition::BottomRight,
+                _ => SnapPosition::Right,
+            }
+        } else if point.y < area.loc.y + threshold {
+            match (near_left, near_right) {
+                (true, _) => SnapPosition::TopLeft,
+                (_, true) => SnapPosition::TopRight,
+                _ => SnapPosition::Maximized,
+            }
+        } else if point.y >= bottom_edge - threshold {
+            match (near_left, near_right) {
+                (true, _) => SnapPosition::BottomLeft,
+                (_, true) => SnapPosition::BottomRight,
+                _ => return None,
+            }
+        } else {
+            return None;
+        };
+        Some(position)
+    }
+}
+
+/// Part of a [`SnapTarget`] a window is snapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SnapPosition {
+    /// The whole area
+    Maximized,
+    /// The left half
+    Left,
+    /// The right half
+    Right,
+    /// The top left quarter
+    TopLeft,
+    /// The top right quarter
+    TopRight,
+    /// The bottom left quarter
+    BottomLeft,
+    /// The bottom right quarter
+    BottomRight,
+}
+
+impl SnapPosition {
+    /// Returns the geometry of this position inside of `area`
+    ///
+    /// Odd sizes are rounded, so that the halves and quarters cover the whole area without overlapping.
+    pub fn geometry(self, area: Rectangle<i32, Logical>) -> Rectangle<i32, Logical> {
+        let (w, h) = (area.size.w, area.size.h);
+        let (half_w, half_h) = (w / 2, h / 2);
+        let (x, y, w, h) = match self {
+            SnapPosition::Maximized => (0, 0, w, h),
+            SnapPosition::Left => (0, 0, half_w, h),
+            SnapPosition::Right => (half_w, 0, w - half_w, h),
+            SnapPosition::TopLeft => (0, 0, half_w, half_h),
+            SnapPosition::TopRight => (half_w, 0, w - half_w, half_h),
+            SnapPosition::BottomLeft => (0, half_h, half_w, h - half_h),
+            SnapPosition::BottomRight => (half_w, half_h, w - half_w, h - half_h),
+        };
+        Rectangle::from_loc_and_size(area.loc + Point::from((x, y)), (w, h))
+    }
+}
+
+/// Area a window is snapped into
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapTarget {
+    /// The usable area of an output
+    Output(Output),
+    /// The geometry of another window
+    Window(Window),
+}
+
+/// Snap zone entered during an interactive move, see [`Space::snap_preview`](super::Space::snap_preview)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapPreview {
+    /// Area the window is snapped into
+    pub target: SnapTarget,
+    /// Part of the target the window is snapped to
+    pub position: SnapPosition,
+    /// Geometry the window gets when it is snapped, in space coordinates
+    pub geometry: Rectangle<i32, Logical>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area() -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((100, 50), (1001, 801))
+    }
+
+    fn zone(x: f64, y: f64) -> Option<SnapPosition> {
+        SnapConfig::default().edge_zone(area(), (x, y).into())
+    }
+
+    #[test]
+    fn edge_zones() {
+        assert_eq!(zone(500.0, 400.0), None);
+        assert_eq!(zone(100.0, 400.0), Some(SnapPosition::Left));
+        // the pointer may be outside of the usable area, e.g. over a panel
+        assert_eq!(zone(50.0, 400.0), Some(SnapPosition::Left));
+        assert_eq!(zone(1100.0, 400.0), Some(SnapPosition::Right));
+        assert_eq!(zone(500.0, 52.0), Some(SnapPosition::Maximized));
+        assert_eq!(zone(500.0, 850.0), None);
+    }
+
+    #[test]
+    fn corner_zones() {
+        assert_eq!(zone(101.0, 60.0), Some(SnapPosition::TopLeft));
+        assert_eq!(zone(150.0, 51.0), Some(SnapPosition::TopLeft));
+        assert_eq!(zone(1099.0, 100.0), Some(SnapPosition::TopRight));
+        assert_eq!(zone(101.0, 840.0), Some(SnapPosition::BottomLeft));
+        assert_eq!(zone(1050.0, 849.0), Some(SnapPosition::BottomRight));
+    }
+
+    #[test]
+    fn position_geometry() {
+        let area = area();
+        assert_eq!(SnapPosition::Maximized.geometry(area), area);
+        assert_eq!(
+            SnapPosition::Left.geometry(area),
+            Rectangle::from_loc_and_size((100, 50), (500, 801))
+        );
+        assert_eq!(
+            SnapPosition::Right.geometry(area),
+            Rectangle::from_loc_and_size((600, 50), (501, 801))
+        );
+        assert_eq!(
+            SnapPosition::BottomRight.geometry(area),
+            Rectangle::from_loc_and_size((600, 450), (501, 401))
+        );
+    }
+}
@@ -2,17 +2,17 @@
 
 use crate::{
     backend::renderer::utils::SurfaceState,
-    desktop::Space,
+    desktop::{space::SpaceOutputHash, LayerMap, Space},
     utils::{Logical, Point, Rectangle},
     wayland::{
         compositor::{
             with_surface_tree_downward, with_surface_tree_upward, SubsurfaceCachedState, SurfaceAttributes,
             TraversalAction,
         },
-        output::Output,
+        output::{Mode, Output, Scale},
     },
 };
-use wayland_server::protocol::wl_surface;
+use wayland_server::protocol::{wl_output, wl_surface};
 
 use std::cell::RefCell;
 
@@ -106,8 +106,22 @@ where
 {
     use super::space::SpaceOutputTuple;
 
+    damage_from_surface_tree_keyed(
+        surface,
+        location,
+        key.map(|x| SpaceOutputTuple::from(x).owned_hash()),
+    )
+}
+
+pub(crate) fn damage_from_surface_tree_keyed<P>(
+    surface: &wl_surface::WlSurface,
+    location: P,
+    key: Option<SpaceOutputHash>,
+) -> Vec<Rectangle<i32, Logical>>
+where
+    P: Into<Point<i32, Logical>>,
+{
     let mut damage = Vec::new();
-    let key = key.map(|x| SpaceOutputTuple::from(x).owned_hash());
     with_surface_tree_upward(
         surface,
         location.into(),
@@ -166,6 +180,30 @@ where
     damage
 }
 
+/// Optimizes a list of damaged regions for rendering an output of the given geometry
+///
+/// Drops empty rectangles and rectangles outside of the output and merges overlapping ones.
+pub(crate) fn optimize_damage(
+    mut damage: Vec<Rectangle<i32, Logical>>,
+    output_geo: Rectangle<i32, Logical>,
+) -> Vec<Rectangle<i32, Logical>> {
+    damage.dedup();
+    damage.retain(|rect| rect.overlaps(output_geo));
+    damage.retain(|rect| rect.size.h > 0 && rect.size.w > 0);
+    // merge overlapping rectangles
+    damage.into_iter().fold(Vec::new(), |new_damage, mut rect| {
+        // replace with drain_filter, when that becomes stable to reuse the original Vec's memory
+        let (overlapping, mut new_damage): (Vec<_>, Vec<_>) =
+            new_damage.into_iter().partition(|other| other.overlaps(rect));
+
+        for overlap in overlapping {
+            rect = rect.merge(overlap);
+        }
+        new_damage.push(rect);
+        new_damage
+    })
+}
+
 /// Returns the topmost (sub-)surface under a given position matching the input regions of the surface.
 ///
 /// In case no surface input region matches the point [`None`] is returned.
@@ -243,6 +281,22 @@ pub fn send_frames_surface_tree(surface: &wl_surface::WlSurface, time: u32) {
     );
 }
 
+/// Fraction of the area of `rect` covered by `other`, rectangles only touching each other do not overlap.
+pub(crate) fn overlap_fraction(rect: Rectangle<i32, Logical>, other: Rectangle<i32, Logical>) -> f64 {
+    let area = rect.size.w as f64 * rect.size.h as f64;
+    if area <= 0.0 {
+        return 0.0;
+    }
+    rect.intersection(other)
+        .map(|overlap| overlap.size.w as f64 * overlap.size.h as f64 / area)
+        .unwrap_or(0.0)
+}
+
+/// Sends enter or leave events for a surface tree depending on the overlap with an output.
+///
+/// Surfaces enter the output, if any part of them overlaps it. Whether an element overlaps
+/// an output enough to enter it at all has to be decided beforehand for the whole element,
+/// see [`output_leave_tree`].
 pub(crate) fn output_update(
     output: &Output,
     output_geometry: Rectangle<i32, Logical>,
@@ -276,7 +330,7 @@ pub(crate) fn output_update(
 
             if let Some(size) = data.and_then(|d| d.borrow().surface_size()) {
                 let surface_rectangle = Rectangle { loc, size };
-                if output_geometry.overlaps(surface_rectangle) {
+                if overlap_fraction(surface_rectangle, output_geometry) > 0.0 {
                     // We found a matching output, check if we already sent enter
                     output_enter(output, surface_list, wl_surface, logger);
                 } else {
@@ -293,6 +347,49 @@ pub(crate) fn output_update(
     );
 }
 
+/// Mode, scale and transform of an output, that damage was tracked for
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct OutputConfig(Option<(Option<Mode>, Scale, wl_output::Transform)>);
+
+impl OutputConfig {
+    /// Remembers the current mode, scale and transform of the output
+    ///
+    /// Returns `true` if any of them changed since the last call,
+    /// in which case everything drawn for the output so far is invalid.
+    pub(crate) fn update(&mut self, output: &Output) -> bool {
+        let config = (
+            output.current_mode(),
+            output.current_scale(),
+            output.current_transform(),
+        );
+        matches!(self.0.replace(config), Some(old) if old != config)
+    }
+}
+
+/// Makes the surfaces of all spaces and of the layer map of an output re-enter it,
+/// whenever its mode, scale or transform changes
+///
+/// Clients pick the scale and transform of their buffers based on the outputs their surfaces
+/// are on, sending `leave` and `enter` again makes them re-evaluate that choice.
+pub(crate) fn watch_output_changes(output: &Output) {
+    struct Watched;
+    if output.user_data().insert_if_missing(|| Watched) {
+        output.add_change_listener(|output, change| {
+            if !change.size_changed() {
+                return;
+            }
+            crate::desktop::space::reenter_output_surfaces(output);
+            // the layer map is skipped instead of panicking,
+            // if the compositor changed the output while holding it
+            if let Some(map) = output.user_data().get::<RefCell<LayerMap>>() {
+                if let Ok(map) = map.try_borrow() {
+                    map.reenter_surfaces(output);
+                }
+            }
+        });
+    }
+}
+
 pub(crate) fn output_enter(
     output: &Output,
     surface_list: &mut Vec<wl_surface::WlSurface>,
@@ -311,6 +408,22 @@ pub(crate) fn output_enter(
     }
 }
 
+/// Sends leave events for all surfaces of a surface tree
+pub(crate) fn output_leave_tree(
+    output: &Output,
+    surface_list: &mut Vec<wl_surface::WlSurface>,
+    surface: &wl_surface::WlSurface,
+    logger: &slog::Logger,
+) {
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |wl_surface, _, _| output_leave(output, surface_list, wl_surface, logger),
+        |_, _, _| true,
+    );
+}
+
 pub(crate) fn output_leave(
     output: &Output,
     surface_list: &mut Vec<wl_surface::WlSurface>,
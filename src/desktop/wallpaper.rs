@@ -0,0 +1,352 @@
+//! Wallpapers rendered by the compositor
+//!
+//! A [`Wallpaper`] holds an image, that is uploaded once per GPU and drawn scaled and cropped into
+//! every output by [`WallpaperElement`]s passed to [`Space::render_output`](super::Space::render_output),
+//! so simple compositors, like kiosks, do not need a wallpaper client.
+//! Elements only report damage, if the geometry of their output or the wallpaper itself changed.
+//!
+//! Images are decoded with the [`image`] crate. Only the formats enabled through its cargo features
+//! can be loaded, e.g. by depending on `image` with the `png` and `jpeg` features.
+//!
+//! ```no_run
+//! # use smithay::desktop::{Space, wallpaper::{Wallpaper, WallpaperMode}};
+//! # use smithay::backend::renderer::{ImportMem, Renderer};
+//! # fn render<R: Renderer + ImportMem>(renderer: &mut R, space: &Space)
+//! # where <R as Renderer>::TextureId: Clone + 'static { let output: smithay::wayland::output::Output = unimplemented!();
+//! let wallpaper = Wallpaper::open("/usr/share/backgrounds/default.png", WallpaperMode::Fill)
+//!     .expect("Failed to load the wallpaper");
+//!
+//! // for every frame of an output
+//! let geometry = space.output_geometry(&output).unwrap();
+//! let element = wallpaper.element(renderer, geometry).expect("Failed to upload the wallpaper");
+//! // pass `element` to `Space::render_output`, e.g. as part of a `custom_elements!` enum
+//! # }
+//! ```
+
+use std::{
+    any::{Any, TypeId},
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    path::Path,
+    rc::Rc,
+};
+
+use image::RgbaImage;
+
+use crate::{
+    backend::renderer::{Frame, ImportAll, ImportMem, Renderer, Texture},
+    desktop::space::{RenderElement, SpaceOutputTuple},
+    utils::{Buffer, Logical, Point, Rectangle, Size, Transform},
+};
+
+crate::utils::ids::id_gen!(next_wallpaper_id, WALLPAPER_ID, WALLPAPER_IDS);
+
+/// How a [`Wallpaper`] is fitted into an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WallpaperMode {
+    /// Scales the image to the size of the output, ignoring its aspect ratio
+    Stretch,
+    /// Scales the image to cover the whole output, cropping the parts sticking out
+    Fill,
+    /// Scales the image to fit into the output, filling the remaining area with the background color
+    Fit,
+    /// Centers the image without scaling it, cropping the parts sticking out
+    Center,
+}
+
+impl Default for WallpaperMode {
+    fn default() -> Self {
+        WallpaperMode::Fill
+    }
+}
+
+/// Returns the part of an image to draw and where to draw it in an area
+fn layout(
+    mode: WallpaperMode,
+    image: Size<i32, Buffer>,
+    area: Size<i32, Logical>,
+) -> (Rectangle<i32, Buffer>, Rectangle<f64, Logical>) {
+    let full_src = Rectangle::from_loc_and_size((0, 0), image);
+    let full_dst = Rectangle::from_loc_and_size((0.0, 0.0), area.to_f64());
+    let (image_w, image_h) = (image.w.max(1) as f64, image.h.max(1) as f64);
+    let (area_w, area_h) = (area.w as f64, area.h as f64);
+
+    match mode {
+        WallpaperMode::Stretch => (full_src, full_dst),
+        WallpaperMode::Fill | WallpaperMode::Center => {
+            let scale = if mode == WallpaperMode::Fill {
+                (area_w / image_w).max(area_h / image_h)
+            } else {
+                1.0
+            };
+            // the part of the image covering the area, centered
+            let src_w = (area_w / scale).min(image_w);
+            let src_h = (area_h / scale).min(image_h);
+            let src = Rectangle::from_loc_and_size(
+                (
+                    ((image_w - src_w) / 2.0).round() as i32,
+                    ((image_h - src_h) / 2.0).round() as i32,
+                ),
+                (src_w.round() as i32, src_h.round() as i32),
+            );
+            let (dst_w, dst_h) = (src_w * scale, src_h * scale);
+            let dst = Rectangle::from_loc_and_size(
+                ((area_w - dst_w) / 2.0, (area_h - dst_h) / 2.0),
+                (dst_w, dst_h),
+            );
+            (src, dst)
+        }
+        WallpaperMode::Fit => {
+            let scale = (area_w / image_w).min(area_h / image_h);
+            let (dst_w, dst_h) = (image_w * scale, image_h * scale);
+            let dst = Rectangle::from_loc_and_size(
+                ((area_w - dst_w) / 2.0, (area_h - dst_h) / 2.0),
+                (dst_w, dst_h),
+            );
+            (full_src, dst)
+        }
+    }
+}
+
+// geometry of an output and generation of the wallpaper last drawn into it
+type DrawnState = (Rectangle<i32, Logical>, usize);
+
+struct WallpaperInner {
+    id: usize,
+    image: RefCell<RgbaImage>,
+    mode: Cell<WallpaperMode>,
+    background: Cell<[f32; 4]>,
+    // bumped whenever the image or its presentation changes
+    generation: Cell<usize>,
+    textures: RefCell<HashMap<(TypeId, usize), Box<dyn Any>>>,
+    // last reported as damaged per (space, output)
+    drawn: RefCell<HashMap<(usize, String), DrawnState>>,
+}
+
+impl Drop for WallpaperInner {
+    fn drop(&mut self) {
+        WALLPAPER_IDS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// A wallpaper image drawn by the compositor
+///
+/// Cloning a wallpaper is cheap and the clones share the image and its textures.
+#[derive(Clone)]
+pub struct Wallpaper(Rc<WallpaperInner>);
+
+impl fmt::Debug for Wallpaper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Wallpaper")
+            .field("id", &self.0.id)
+            .field("size", &self.0.image.borrow().dimensions())
+            .field("mode", &self.0.mode.get())
+            .field("background", &self.0.background.get())
+            .finish()
+    }
+}
+
+impl Wallpaper {
+    /// Creates a new wallpaper from an image
+    pub fn new(image: RgbaImage, mode: WallpaperMode) -> Wallpaper {
+        Wallpaper(Rc::new(WallpaperInner {
+            id: next_wallpaper_id(),
+            image: RefCell::new(image),
+            mode: Cell::new(mode),
+            background: Cell::new([0.0, 0.0, 0.0, 1.0]),
+            generation: Cell::new(0),
+            textures: RefCell::new(HashMap::new()),
+            drawn: RefCell::new(HashMap::new()),
+        }))
+    }
+
+    /// Loads a wallpaper from an image file
+    pub fn open<P: AsRef<Path>>(path: P, mode: WallpaperMode) -> Result<Wallpaper, image::ImageError> {
+        Ok(Wallpaper::new(image::open(path)?.into_rgba8(), mode))
+    }
+
+    fn changed(&self) {
+        self.0.generation.set(self.0.generation.get().wrapping_add(1));
+    }
+
+    /// Replaces the image of this wallpaper
+    pub fn set_image(&self, image: RgbaImage) {
+        *self.0.image.borrow_mut() = image;
+        self.0.textures.borrow_mut().clear();
+        self.changed();
+    }
+
+    /// Returns how the wallpaper is fitted into outputs
+    pub fn mode(&self) -> WallpaperMode {
+        self.0.mode.get()
+    }
+
+    /// Sets how the wallpaper is fitted into outputs
+    pub fn set_mode(&self, mode: WallpaperMode) {
+        if self.0.mode.replace(mode) != mode {
+            self.changed();
+        }
+    }
+
+    /// Sets the color of the areas of outputs not covered by the image, black by default
+    pub fn set_background(&self, color: [f32; 4]) {
+        if self.0.background.replace(color) != color {
+            self.changed();
+        }
+    }
+
+    /// Creates the element drawing this wallpaper into an output with the given geometry
+    ///
+    /// The image is uploaded to a renderer the first time it is used with it.
+    pub fn element<R>(
+        &self,
+        renderer: &mut R,
+        output_geometry: Rectangle<i32, Logical>,
+    ) -> Result<WallpaperElement<<R as Renderer>::TextureId>, <R as Renderer>::Error>
+    where
+        R: Renderer + ImportMem,
+        <R as Renderer>::TextureId: Clone + 'static,
+    {
+        let key = (TypeId::of::<<R as Renderer>::TextureId>(), renderer.id());
+        let cached = self
+            .0
+            .textures
+            .borrow()
+            .get(&key)
+            .and_then(|texture| texture.downcast_ref::<<R as Renderer>::TextureId>())
+            .cloned();
+        let texture = match cached {
+            Some(texture) => texture,
+            None => {
+                let image = self.0.image.borrow();
+                let size = Size::from((image.width() as i32, image.height() as i32));
+                let texture = renderer.import_memory(image.as_raw(), size, false)?;
+                self.0
+                    .textures
+                    .borrow_mut()
+                    .insert(key, Box::new(texture.clone()));
+                texture
+            }
+        };
+
+        Ok(WallpaperElement {
+            wallpaper: self.clone(),
+            texture,
+            geometry: output_geometry,
+        })
+    }
+}
+
+/// [`RenderElement`] drawing a [`Wallpaper`] into an output
+#[derive(Debug)]
+pub struct WallpaperElement<T> {
+    wallpaper: Wallpaper,
+    texture: T,
+    geometry: Rectangle<i32, Logical>,
+}
+
+impl<R> RenderElement<R> for WallpaperElement<<R as Renderer>::TextureId>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    fn id(&self) -> usize {
+        self.wallpaper.0.id
+    }
+
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.geometry
+    }
+
+    fn accumulated_damage(
+        &self,
+        for_values: Option<SpaceOutputTuple<'_, '_>>,
+    ) -> Vec<Rectangle<i32, Logical>> {
+        let full = vec![Rectangle::from_loc_and_size((0, 0), self.geometry.size)];
+        let SpaceOutputTuple(space, output) = match for_values {
+            Some(for_values) => for_values,
+            None => return full,
+        };
+        let state = (self.geometry, self.wallpaper.0.generation.get());
+        let previous = self
+            .wallpaper
+            .0
+            .drawn
+            .borrow_mut()
+            .insert((space.id, output.name()), state);
+        if previous == Some(state) {
+            Vec::new()
+        } else {
+            full
+        }
+    }
+
+    fn draw(
+        &self,
+        _renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        location: Point<i32, Logical>,
+        damage: &[Rectangle<i32, Logical>],
+        _log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        let damage = damage
+            .iter()
+            .map(|rect| {
+                Rectangle::from_loc_and_size(rect.loc + location, rect.size)
+                    .to_f64()
+                    .to_physical(scale)
+            })
+            .collect::<Vec<_>>();
+        frame.clear(self.wallpaper.0.background.get(), &damage)?;
+
+        let (src, dst) = layout(self.wallpaper.mode(), self.texture.size(), self.geometry.size);
+        let dst = Rectangle::from_loc_and_size(dst.loc + location.to_f64(), dst.size).to_physical(scale);
+        frame.render_texture_from_to(&self.texture, src, dst, &damage, Transform::Normal, 1.0)
+    }
+
+    fn z_index(&self) -> u8 {
+        // below everything else, including background layer surfaces
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn src(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Buffer> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    fn dst(x: f64, y: f64, w: f64, h: f64) -> Rectangle<f64, Logical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    #[test]
+    fn fill_crops_to_the_output() {
+        // a 4:3 image on a 16:9 output
+        let (s, d) = layout(WallpaperMode::Fill, (1600, 1200).into(), (1920, 1080).into());
+        assert_eq!(s, src(0, 150, 1600, 900));
+        assert_eq!(d, dst(0.0, 0.0, 1920.0, 1080.0));
+    }
+
+    #[test]
+    fn fit_letterboxes() {
+        let (s, d) = layout(WallpaperMode::Fit, (1600, 1200).into(), (1920, 1080).into());
+        assert_eq!(s, src(0, 0, 1600, 1200));
+        assert_eq!(d, dst(240.0, 0.0, 1440.0, 1080.0));
+    }
+
+    #[test]
+    fn stretch_and_center() {
+        let (s, d) = layout(WallpaperMode::Stretch, (100, 100).into(), (1920, 1080).into());
+        assert_eq!((s, d), (src(0, 0, 100, 100), dst(0.0, 0.0, 1920.0, 1080.0)));
+
+        // smaller images are centered, larger ones cropped
+        let (s, d) = layout(WallpaperMode::Center, (100, 2000).into(), (1920, 1080).into());
+        assert_eq!(s, src(0, 460, 100, 1080));
+        assert_eq!(d, dst(910.0, 0.0, 100.0, 1080.0));
+    }
+}
@@ -1,15 +1,17 @@
 use crate::{
     backend::renderer::{utils::draw_surface_tree, ImportAll, Renderer},
-    desktop::{utils::*, PopupManager, Space},
-    utils::{Logical, Point, Rectangle},
+    desktop::{utils::*, PopupManager, Space, WindowIcon},
+    utils::{Logical, Point, Rectangle, Size},
     wayland::{
         compositor::with_states,
         output::Output,
         shell::xdg::{SurfaceCachedState, ToplevelSurface},
     },
 };
+#[cfg(feature = "xwayland")]
+use std::sync::Mutex;
 use std::{
-    cell::Cell,
+    cell::{Cell, RefCell},
     hash::{Hash, Hasher},
     rc::Rc,
 };
@@ -59,8 +61,42 @@ impl X11Surface {
             None
         }
     }
+
+    /// Sets the surface this window is transient for, e.g. as read from `WM_TRANSIENT_FOR`
+    ///
+    /// A [`Space`] keeps transient windows, like dialogs, stacked above their parent.
+    pub fn set_transient_for(&self, parent: Option<&wl_surface::WlSurface>) {
+        let _ = with_states(&self.surface, |states| {
+            states
+                .data_map
+                .insert_if_missing_threadsafe(|| X11TransientFor(Mutex::new(None)));
+            *states
+                .data_map
+                .get::<X11TransientFor>()
+                .unwrap()
+                .0
+                .lock()
+                .unwrap() = parent.cloned();
+        });
+    }
+
+    /// Returns the surface this window is transient for, if any
+    pub fn transient_for(&self) -> Option<wl_surface::WlSurface> {
+        with_states(&self.surface, |states| {
+            states
+                .data_map
+                .get::<X11TransientFor>()
+                .and_then(|parent| parent.0.lock().unwrap().clone())
+        })
+        .ok()
+        .flatten()
+    }
 }
 
+#[cfg(feature = "xwayland")]
+#[derive(Debug)]
+struct X11TransientFor(Mutex<Option<wl_surface::WlSurface>>);
+
 impl Kind {
     /// Checks if the surface is still alive.
     pub fn alive(&self) -> bool {
@@ -79,6 +115,17 @@ impl Kind {
             Kind::X11(ref t) => t.get_surface(),
         }
     }
+
+    /// Returns the surface of the toplevel this one is a child of, if any
+    ///
+    /// This is the parent of xdg toplevels and the window X11 windows are transient for.
+    pub fn parent(&self) -> Option<wl_surface::WlSurface> {
+        match *self {
+            Kind::Xdg(ref t) => t.parent(),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(ref t) => t.transient_for(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -87,15 +134,131 @@ pub(super) struct WindowInner {
     toplevel: Kind,
     bbox: Cell<Rectangle<i32, Logical>>,
     pub(super) z_index: Cell<Option<u8>>,
+    aspect_ratio: Cell<Option<f64>>,
+    restore_geometry: RestoreGeometry,
+    icon: RefCell<Option<WindowIcon>>,
     user_data: UserDataMap,
 }
 
+/// Geometry of a window before it was maximized or fullscreened
+#[derive(Debug, Default)]
+struct RestoreGeometry(Cell<Option<Rectangle<i32, Logical>>>);
+
+impl RestoreGeometry {
+    fn store(&self, geometry: Rectangle<i32, Logical>, maximized_or_fullscreen: bool) {
+        if !maximized_or_fullscreen || self.0.get().is_none() {
+            self.0.set(Some(geometry));
+        }
+    }
+
+    fn take(&self, maximized_or_fullscreen: bool) -> Option<Rectangle<i32, Logical>> {
+        if maximized_or_fullscreen {
+            None
+        } else {
+            self.0.take()
+        }
+    }
+}
+
 impl Drop for WindowInner {
     fn drop(&mut self) {
         WINDOW_IDS.lock().unwrap().remove(&self.id);
     }
 }
 
+/// Size constraints of a [`Window`]
+///
+/// A value of `0` on any axis of `min_size` or `max_size` means the axis is unconstrained,
+/// just like for the min/max size of an xdg toplevel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeConstraints {
+    /// Minimum size of the window
+    pub min_size: Size<i32, Logical>,
+    /// Maximum size of the window
+    pub max_size: Size<i32, Logical>,
+    /// Ratio of width to height the window should keep, if any
+    pub aspect_ratio: Option<f64>,
+}
+
+impl Default for SizeConstraints {
+    fn default() -> Self {
+        SizeConstraints {
+            min_size: (0, 0).into(),
+            max_size: (0, 0).into(),
+            aspect_ratio: None,
+        }
+    }
+}
+
+fn clamp_axis(value: i32, min: i32, max: i32) -> i32 {
+    let min = min.max(1);
+    let max = if max == 0 { i32::MAX } else { max };
+    value.max(min).min(max)
+}
+
+impl SizeConstraints {
+    fn clamp_axes(&self, size: Size<i32, Logical>) -> Size<i32, Logical> {
+        (
+            clamp_axis(size.w, self.min_size.w, self.max_size.w),
+            clamp_axis(size.h, self.min_size.h, self.max_size.h),
+        )
+            .into()
+    }
+
+    /// Clamps a size chosen by the compositor, e.g. for a newly placed floating window
+    ///
+    /// Axes with a value of `0` are left to the client to decide and kept as is. If an aspect ratio
+    /// is set, the largest size with that ratio fitting into the requested size is returned.
+    ///
+    /// If the constraints can not be satisfied together, the min/max size takes precedence
+    /// over the aspect ratio.
+    pub fn clamp(&self, size: Size<i32, Logical>) -> Size<i32, Logical> {
+        if size.w == 0 || size.h == 0 {
+            let clamped = self.clamp_axes(size);
+            return (
+                if size.w == 0 { 0 } else { clamped.w },
+                if size.h == 0 { 0 } else { clamped.h },
+            )
+                .into();
+        }
+
+        let mut size = self.clamp_axes(size);
+        if let Some(ratio) = self.aspect_ratio {
+            if size.w as f64 / size.h as f64 > ratio {
+                size.w = (size.h as f64 * ratio).round() as i32;
+            } else {
+                size.h = (size.w as f64 / ratio).round() as i32;
+            }
+            size = self.clamp_axes(size);
+        }
+        size
+    }
+
+    /// Clamps the size of an interactive resize starting at `initial_size`
+    ///
+    /// Unlike [`SizeConstraints::clamp`], this never returns an axis of `0`. If an aspect ratio is set,
+    /// the axis that changed the most relative to the `initial_size` determines the other one,
+    /// so dragging any single edge or corner of the window resizes it as expected.
+    pub fn clamp_resize(
+        &self,
+        initial_size: Size<i32, Logical>,
+        size: Size<i32, Logical>,
+    ) -> Size<i32, Logical> {
+        let mut size = self.clamp_axes(size);
+        if let Some(ratio) = self.aspect_ratio {
+            let dw = (size.w - initial_size.w).abs() as f64 / initial_size.w.max(1) as f64;
+            let dh = (size.h - initial_size.h).abs() as f64 / initial_size.h.max(1) as f64;
+            if dw >= dh {
+                size.h = (size.w as f64 / ratio).round() as i32;
+            } else {
+                size.w = (size.h as f64 * ratio).round() as i32;
+            }
+            size = self.clamp_axes(size);
+        }
+        size
+    }
+}
+
 /// Represents a single application window
 #[derive(Debug, Clone)]
 pub struct Window(pub(super) Rc<WindowInner>);
@@ -140,6 +303,9 @@ impl Window {
             bbox: Cell::new(Rectangle::from_loc_and_size((0, 0), (0, 0))),
             user_data: UserDataMap::new(),
             z_index: Cell::new(None),
+            aspect_ratio: Cell::new(None),
+            restore_geometry: RestoreGeometry::default(),
+            icon: RefCell::new(None),
         }))
     }
 
@@ -204,10 +370,127 @@ impl Window {
         }
     }
 
+    /// Returns the minimum size of this window, as requested by the client
+    ///
+    /// A value of `0` on any axis means the axis is unconstrained.
+    pub fn min_size(&self) -> Size<i32, Logical> {
+        match self.0.toplevel {
+            Kind::Xdg(ref t) => t
+                .get_surface()
+                .and_then(|surface| {
+                    with_states(surface, |states| {
+                        states.cached_state.current::<SurfaceCachedState>().min_size
+                    })
+                    .ok()
+                })
+                .unwrap_or_else(|| (0, 0).into()),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(ref _t) => (0, 0).into(),
+        }
+    }
+
+    /// Returns the maximum size of this window, as requested by the client
+    ///
+    /// A value of `0` on any axis means the axis is unconstrained.
+    pub fn max_size(&self) -> Size<i32, Logical> {
+        match self.0.toplevel {
+            Kind::Xdg(ref t) => t
+                .get_surface()
+                .and_then(|surface| {
+                    with_states(surface, |states| {
+                        states.cached_state.current::<SurfaceCachedState>().max_size
+                    })
+                    .ok()
+                })
+                .unwrap_or_else(|| (0, 0).into()),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(ref _t) => (0, 0).into(),
+        }
+    }
+
+    /// Locks the ratio of width to height of this window
+    ///
+    /// The ratio is enforced by [`Window::configure`] and [`SizeConstraints`] of this window,
+    /// pass `None` to unlock it again.
+    pub fn set_aspect_ratio(&self, ratio: Option<f64>) {
+        self.0
+            .aspect_ratio
+            .set(ratio.filter(|ratio| ratio.is_finite() && *ratio > 0.0));
+    }
+
+    /// Returns the locked ratio of width to height of this window, if any
+    pub fn aspect_ratio(&self) -> Option<f64> {
+        self.0.aspect_ratio.get()
+    }
+
+    /// Returns the current size constraints of this window
+    pub fn size_constraints(&self) -> SizeConstraints {
+        SizeConstraints {
+            min_size: self.min_size(),
+            max_size: self.max_size(),
+            aspect_ratio: self.aspect_ratio(),
+        }
+    }
+
+    fn is_maximized_or_fullscreen(&self) -> bool {
+        match self.0.toplevel {
+            Kind::Xdg(ref t) => t
+                .with_pending_state(|state| {
+                    state.states.contains(xdg_toplevel::State::Maximized)
+                        || state.states.contains(xdg_toplevel::State::Fullscreen)
+                })
+                .unwrap_or(false),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(ref _t) => false,
+        }
+    }
+
+    /// Remembers the geometry this window should be restored to, once it is neither maximized nor fullscreen
+    ///
+    /// Call this with the current geometry of the window in your [`Space`] right before setting
+    /// the maximized or fullscreen state. The geometry is only replaced, if the window is in neither
+    /// state yet, so toggling both states in any order keeps the geometry the window had before.
+    pub fn store_restore_geometry(&self, geometry: Rectangle<i32, Logical>) {
+        self.0
+            .restore_geometry
+            .store(geometry, self.is_maximized_or_fullscreen());
+    }
+
+    /// Takes the geometry this window should be restored to
+    ///
+    /// Call this right after unsetting the maximized or fullscreen state. As long as the window
+    /// is still in the other state, `None` is returned and the stored geometry is kept.
+    /// Otherwise the stored geometry is removed, so calling this again returns `None`.
+    pub fn take_restore_geometry(&self) -> Option<Rectangle<i32, Logical>> {
+        self.0.restore_geometry.take(self.is_maximized_or_fullscreen())
+    }
+
     /// Commit any changes to this window
+    ///
+    /// A pending size set by the compositor is clamped to the [`SizeConstraints`] of this window first,
+    /// unless the window is maximized, fullscreen or tiled. Clients have to respect the size in those
+    /// states, so it is sent as is.
     pub fn configure(&self) {
         match self.0.toplevel {
-            Kind::Xdg(ref t) => t.send_configure(),
+            Kind::Xdg(ref t) => {
+                let constraints = self.size_constraints();
+                let _ = t.with_pending_state(|state| {
+                    let fixed_size = [
+                        xdg_toplevel::State::Maximized,
+                        xdg_toplevel::State::Fullscreen,
+                        xdg_toplevel::State::TiledLeft,
+                        xdg_toplevel::State::TiledRight,
+                        xdg_toplevel::State::TiledTop,
+                        xdg_toplevel::State::TiledBottom,
+                    ]
+                    .iter()
+                    .any(|s| state.states.contains(*s));
+                    if !fixed_size {
+                        state.size = state.size.map(|size| constraints.clamp(size));
+                    }
+                });
+                t.send_configure()
+            }
             #[cfg(feature = "xwayland")]
             Kind::X11(ref _t) => unimplemented!(),
         }
@@ -297,6 +580,16 @@ impl Window {
         &self.0.toplevel
     }
 
+    /// Returns the icon of this window, if any was set
+    pub fn icon(&self) -> Option<WindowIcon> {
+        self.0.icon.borrow().clone()
+    }
+
+    /// Sets the icon of this window, e.g. from the `_NET_WM_ICON` property of X11 windows
+    pub fn set_icon(&self, icon: Option<WindowIcon>) {
+        *self.0.icon.borrow_mut() = icon;
+    }
+
     /// Returns a [`UserDataMap`] to allow associating arbitrary data with this window.
     pub fn user_data(&self) -> &UserDataMap {
         &self.0.user_data
@@ -362,3 +655,96 @@ where
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraints(min: (i32, i32), max: (i32, i32), aspect_ratio: Option<f64>) -> SizeConstraints {
+        SizeConstraints {
+            min_size: min.into(),
+            max_size: max.into(),
+            aspect_ratio,
+        }
+    }
+
+    #[test]
+    fn clamp_min_max() {
+        let constraints = constraints((100, 50), (800, 0), None);
+        assert_eq!(constraints.clamp((10, 10).into()), (100, 50).into());
+        assert_eq!(constraints.clamp((1000, 1000).into()), (800, 1000).into());
+        assert_eq!(constraints.clamp((400, 300).into()), (400, 300).into());
+        assert_eq!(SizeConstraints::default().clamp((-5, 20).into()), (1, 20).into());
+    }
+
+    #[test]
+    fn clamp_keeps_client_chosen_axes() {
+        let constraints = constraints((100, 50), (800, 600), Some(2.0));
+        assert_eq!(constraints.clamp((0, 0).into()), (0, 0).into());
+        assert_eq!(constraints.clamp((1000, 0).into()), (800, 0).into());
+        assert_eq!(constraints.clamp((0, 10).into()), (0, 50).into());
+    }
+
+    #[test]
+    fn clamp_aspect_ratio() {
+        let constraints = constraints((0, 0), (0, 0), Some(16.0 / 9.0));
+        // the largest size with the ratio fitting into the requested one
+        assert_eq!(constraints.clamp((1920, 1200).into()), (1920, 1080).into());
+        assert_eq!(constraints.clamp((1600, 1080).into()), (1600, 900).into());
+
+        // min/max size take precedence
+        let constraints = self::constraints((500, 500), (0, 0), Some(2.0));
+        assert_eq!(constraints.clamp((600, 600).into()), (600, 500).into());
+    }
+
+    #[test]
+    fn clamp_resize() {
+        let constraints = constraints((100, 100), (1000, 1000), None);
+        assert_eq!(
+            constraints.clamp_resize((400, 300).into(), (0, 2000).into()),
+            (100, 1000).into()
+        );
+
+        let constraints = self::constraints((0, 0), (0, 0), Some(2.0));
+        let initial = (400, 200).into();
+        // dragging the right edge
+        assert_eq!(
+            constraints.clamp_resize(initial, (600, 200).into()),
+            (600, 300).into()
+        );
+        // dragging the bottom edge
+        assert_eq!(
+            constraints.clamp_resize(initial, (400, 400).into()),
+            (800, 400).into()
+        );
+        // dragging a corner, the relatively larger change wins
+        assert_eq!(
+            constraints.clamp_resize(initial, (500, 300).into()),
+            (600, 300).into()
+        );
+    }
+
+    #[test]
+    fn restore_geometry() {
+        let floating = Rectangle::from_loc_and_size((10, 20), (300, 200));
+        let maximized = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+        let restore = RestoreGeometry::default();
+        assert_eq!(restore.take(false), None);
+
+        // maximize, then fullscreen, then leave both states again
+        restore.store(floating, false);
+        restore.store(maximized, true);
+        assert_eq!(restore.take(true), None);
+        assert_eq!(restore.take(false), Some(floating));
+        assert_eq!(restore.take(false), None);
+
+        // storing again while floating replaces the geometry
+        restore.store(maximized, false);
+        restore.store(floating, false);
+        assert_eq!(restore.take(false), Some(floating));
+
+        // a window mapped maximized has nothing to restore to yet
+        restore.store(maximized, true);
+        assert_eq!(restore.take(false), Some(maximized));
+    }
+}
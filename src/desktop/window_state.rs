@@ -0,0 +1,397 @@
+//! Persistence of window placements across sessions
+//!
+//! [`WindowStateStore`] remembers where windows of an application were placed, so they can be
+//! placed there again when the application maps a new window, even after a restart of the
+//! compositor. Entries are keyed by the app ID of the window and an optional title pattern,
+//! in which `*` matches any sequence of characters.
+//!
+//! ```no_run
+//! # use smithay::desktop::{Space, Window};
+//! use smithay::desktop::window_state::WindowStateStore;
+//!
+//! # let (mut space, window): (Space, Window) = unimplemented!();
+//! let mut store = WindowStateStore::load("/home/user/.local/state/compositor/windows")
+//!     .expect("Failed to load window states");
+//!
+//! // when a window is mapped
+//! let location = store
+//!     .suggest_for_window(&window)
+//!     .and_then(|state| state.geometry_in(&space))
+//!     .map(|geometry| geometry.loc)
+//!     .unwrap_or_else(|| (0, 0).into());
+//! space.map_window(&window, location, true);
+//!
+//! // when it is unmapped
+//! store.record_window(&space, &window, None);
+//! store.save().expect("Failed to save window states");
+//! ```
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    desktop::{Kind, Space, Window},
+    utils::{Logical, Rectangle},
+};
+
+/// Saved placement of a window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedWindowState {
+    /// Geometry of the window, relative to `output` if set and in space coordinates otherwise
+    pub geometry: Rectangle<i32, Logical>,
+    /// Name of the output the window was shown on
+    pub output: Option<String>,
+    /// Compositor-defined name of the workspace the window was on
+    pub workspace: Option<String>,
+}
+
+impl SavedWindowState {
+    /// Returns the saved geometry in the coordinates of the given [`Space`]
+    ///
+    /// Returns `None` if the output the window was shown on is not mapped in the space anymore.
+    pub fn geometry_in(&self, space: &Space) -> Option<Rectangle<i32, Logical>> {
+        match self.output {
+            Some(ref name) => {
+                let output = space.outputs().find(|output| output.name() == *name)?;
+                let output_geometry = space.output_geometry(output)?;
+                Some(Rectangle::from_loc_and_size(
+                    output_geometry.loc + self.geometry.loc,
+                    self.geometry.size,
+                ))
+            }
+            None => Some(self.geometry),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    app_id: String,
+    title: Option<String>,
+    state: SavedWindowState,
+}
+
+/// Store of window placements, see the [module-level documentation](self)
+#[derive(Debug, Default)]
+pub struct WindowStateStore {
+    path: Option<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+impl WindowStateStore {
+    /// Creates an empty store, that is not backed by a file
+    pub fn new() -> WindowStateStore {
+        WindowStateStore::default()
+    }
+
+    /// Loads a store from a file, which is also used by [`WindowStateStore::save`]
+    ///
+    /// A missing file results in an empty store.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<WindowStateStore> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => parse(&content)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(WindowStateStore {
+            path: Some(path),
+            entries,
+        })
+    }
+
+    /// Writes the store to the file it was loaded from
+    ///
+    /// Does nothing if the store was created with [`WindowStateStore::new`].
+    pub fn save(&self) -> io::Result<()> {
+        match self.path {
+            Some(ref path) => self.save_to(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the store to a file
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // write to a temporary file first, so a crash does not leave a truncated store behind
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serialize(&self.entries))?;
+        fs::rename(&tmp, path)
+    }
+
+    /// Records the placement of windows with the given app ID and title pattern
+    ///
+    /// A title pattern of `None` matches every title. An existing entry with the same app ID
+    /// and pattern is replaced.
+    pub fn record(&mut self, app_id: &str, title: Option<&str>, state: SavedWindowState) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.app_id == app_id && entry.title.as_deref() == title)
+        {
+            Some(entry) => entry.state = state,
+            None => self.entries.push(Entry {
+                app_id: app_id.to_owned(),
+                title: title.map(String::from),
+                state,
+            }),
+        }
+    }
+
+    /// Records the current placement of a window of a [`Space`] for its app ID
+    ///
+    /// The geometry is stored relative to the [primary output](Space::primary_output) of the window.
+    /// Returns false if the window is not mapped or has no app ID.
+    pub fn record_window(&mut self, space: &Space, window: &Window, workspace: Option<String>) -> bool {
+        let app_id = match window_identity(window).and_then(|(app_id, _)| app_id) {
+            Some(app_id) => app_id,
+            None => return false,
+        };
+        let location = match space.window_location(window) {
+            Some(location) => location,
+            None => return false,
+        };
+
+        let mut geometry = Rectangle::from_loc_and_size(location, window.geometry().size);
+        let output = space.primary_output(window).and_then(|output| {
+            let output_geometry = space.output_geometry(&output)?;
+            geometry.loc -= output_geometry.loc;
+            Some(output.name())
+        });
+        self.record(
+            &app_id,
+            None,
+            SavedWindowState {
+                geometry,
+                output,
+                workspace,
+            },
+        );
+        true
+    }
+
+    /// Removes all entries of an app ID
+    pub fn forget(&mut self, app_id: &str) {
+        self.entries.retain(|entry| entry.app_id != app_id);
+    }
+
+    /// Suggests a placement for a window with the given app ID and title
+    ///
+    /// An entry with the exact title is preferred over entries with matching title patterns,
+    /// which are preferred over entries matching every title.
+    pub fn suggest(&self, app_id: &str, title: Option<&str>) -> Option<&SavedWindowState> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.app_id == app_id)
+            .filter_map(|entry| {
+                let rank = match (entry.title.as_deref(), title) {
+                    (None, _) => 0,
+                    (Some(pattern), Some(title)) if pattern == title => 2,
+                    (Some(pattern), Some(title)) if matches_pattern(pattern, title) => 1,
+                    _ => return None,
+                };
+                Some((rank, &entry.state))
+            })
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, state)| state)
+    }
+
+    /// Suggests a placement for a window based on its app ID and title
+    pub fn suggest_for_window(&self, window: &Window) -> Option<&SavedWindowState> {
+        let (app_id, title) = window_identity(window)?;
+        self.suggest(&app_id?, title.as_deref())
+    }
+}
+
+/// Returns the app ID and title of a window
+fn window_identity(window: &Window) -> Option<(Option<String>, Option<String>)> {
+    match window.toplevel() {
+        Kind::Xdg(surface) => surface
+            .snapshot()
+            .map(|snapshot| (snapshot.app_id, snapshot.title)),
+        #[cfg(feature = "xwayland")]
+        Kind::X11(_) => None,
+    }
+}
+
+/// Matches a text against a pattern, in which `*` matches any sequence of characters
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // the pattern always has a first part, which has to be a prefix
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match text.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let parts = parts.collect::<Vec<_>>();
+    let (last, middle) = match parts.split_last() {
+        Some(split) => split,
+        // no wildcard at all
+        None => return rest.is_empty(),
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some(c) => result.push(c),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn optional(field: &str) -> Option<String> {
+    if field.is_empty() {
+        None
+    } else {
+        Some(unescape(field))
+    }
+}
+
+/// Serializes entries, one per line with tab-separated fields:
+/// `app_id title x y width height output workspace`, empty fields are unset.
+fn serialize(entries: &[Entry]) -> String {
+    let mut content = String::new();
+    for entry in entries {
+        let geometry = entry.state.geometry;
+        content.push_str(&format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            escape(&entry.app_id),
+            entry.title.as_deref().map(escape).unwrap_or_default(),
+            geometry.loc.x,
+            geometry.loc.y,
+            geometry.size.w,
+            geometry.size.h,
+            entry.state.output.as_deref().map(escape).unwrap_or_default(),
+            entry.state.workspace.as_deref().map(escape).unwrap_or_default(),
+        ));
+    }
+    content
+}
+
+fn parse(content: &str) -> io::Result<Vec<Entry>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(idx, line)| {
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid window state on line {}", idx + 1),
+                )
+            };
+            let fields = line.split('\t').collect::<Vec<_>>();
+            if fields.len() != 8 || fields[0].is_empty() {
+                return Err(invalid());
+            }
+            let number = |field: &str| field.parse::<i32>().map_err(|_| invalid());
+            Ok(Entry {
+                app_id: unescape(fields[0]),
+                title: optional(fields[1]),
+                state: SavedWindowState {
+                    geometry: Rectangle::from_loc_and_size(
+                        (number(fields[2])?, number(fields[3])?),
+                        (number(fields[4])?, number(fields[5])?),
+                    ),
+                    output: optional(fields[6]),
+                    workspace: optional(fields[7]),
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(x: i32) -> SavedWindowState {
+        SavedWindowState {
+            geometry: Rectangle::from_loc_and_size((x, 20), (640, 480)),
+            output: Some("DP-1".into()),
+            workspace: None,
+        }
+    }
+
+    #[test]
+    fn title_patterns() {
+        assert!(matches_pattern("Terminal", "Terminal"));
+        assert!(!matches_pattern("Terminal", "Terminal 2"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("* - Firefox", "Smithay - Firefox"));
+        assert!(matches_pattern("Settings*", "Settings - Display"));
+        assert!(matches_pattern("a*b*c", "a-b-b-c"));
+        assert!(!matches_pattern("a*b*c", "a-c-b"));
+        assert!(!matches_pattern("ab*ba", "aba"));
+    }
+
+    #[test]
+    fn suggests_best_match() {
+        let mut store = WindowStateStore::new();
+        store.record("firefox", None, state(0));
+        store.record("firefox", Some("* - Private Browsing"), state(1));
+        store.record("firefox", Some("Library"), state(2));
+        store.record("firefox", Some("Library"), state(3));
+
+        assert_eq!(store.suggest("firefox", Some("Library")), Some(&state(3)));
+        assert_eq!(
+            store.suggest("firefox", Some("Smithay - Private Browsing")),
+            Some(&state(1))
+        );
+        assert_eq!(store.suggest("firefox", Some("Smithay")), Some(&state(0)));
+        assert_eq!(store.suggest("firefox", None), Some(&state(0)));
+        assert_eq!(store.suggest("alacritty", None), None);
+
+        store.forget("firefox");
+        assert_eq!(store.suggest("firefox", None), None);
+    }
+
+    #[test]
+    fn serialization_roundtrip() {
+        let mut store = WindowStateStore::new();
+        store.record("firefox", Some("Tab\twith \\ escapes\n"), state(-10));
+        store.record(
+            "alacritty",
+            None,
+            SavedWindowState {
+                geometry: Rectangle::from_loc_and_size((5, 5), (100, 100)),
+                output: None,
+                workspace: Some("2".into()),
+            },
+        );
+
+        let content = serialize(&store.entries);
+        assert_eq!(content.lines().count(), 2);
+        assert_eq!(parse(&content).unwrap(), store.entries);
+        assert!(parse("firefox\t\t1\t2\n").is_err());
+    }
+}
@@ -0,0 +1,12 @@
+use std::time::Duration;
+
+use nix::time::{clock_gettime, ClockId};
+
+/// Returns the current time of the `CLOCK_MONOTONIC` clock
+///
+/// This is the clock of DRM page flip events and of the presentation-time protocol.
+pub fn monotonic_time() -> Duration {
+    // CLOCK_MONOTONIC is supported on every platform we run on, so this can not fail
+    let time = clock_gettime(ClockId::CLOCK_MONOTONIC).expect("Failed to query CLOCK_MONOTONIC");
+    Duration::new(time.tv_sec() as u64, time.tv_nsec() as u32)
+}
@@ -0,0 +1,605 @@
+//! Control socket for external tools
+//!
+//! Most compositors expose a socket that tools like `swaymsg` or `hyprctl` use to query state
+//! and to run commands. [`IpcServer`] takes care of the socket handling: it listens on a unix
+//! socket, integrates the listener and every connected client into a calloop event loop, calls
+//! the handlers of registered commands and broadcasts events to interested clients.
+//!
+//! The protocol is newline-delimited JSON, every message is a single line:
+//!
+//! - `{"command": "<name>", "args": <any>}` runs a command, `args` is optional and defaults to `null`.
+//!   The reply is either `{"success": true, "result": <any>}` or `{"success": false, "error": "<message>"}`.
+//! - `{"subscribe": ["<event>", ...]}` subscribes the client to events, `"*"` subscribes to all events.
+//!   The reply is `{"success": true}`.
+//! - Events are sent as `{"event": "<name>", "payload": <any>}` to subscribed clients.
+//!
+//! Replies are sent in the order the messages were received. Handlers run on the thread of the
+//! event loop with access to the compositor state, so no synchronization is required. Messages,
+//! that a client does not read right away, are queued and sent once its socket is writable again,
+//! clients falling too far behind are disconnected.
+//!
+//! ```no_run
+//! use smithay::utils::ipc::IpcServer;
+//! use serde_json::json;
+//!
+//! struct State {
+//!     workspace: u32,
+//! }
+//!
+//! # let event_loop = calloop::EventLoop::<State>::try_new().unwrap();
+//! let server = IpcServer::bind("/run/user/1000/compositor.sock", event_loop.handle(), None)
+//!     .expect("Failed to bind control socket");
+//! server.register_command("workspace", |args, state: &mut State| {
+//!     let workspace = args.as_u64().ok_or_else(|| String::from("Expected a workspace number"))?;
+//!     state.workspace = workspace as u32;
+//!     Ok(json!(null))
+//! });
+//!
+//! // later, e.g. when switching workspaces
+//! server.emit("workspace", json!(2));
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
+
+use calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction, RegistrationToken};
+use serde_json::{json, Value};
+use slog::{debug, info, o, trace};
+
+/// Messages longer than this are considered garbage and the client is disconnected
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+/// Clients with more unsent bytes than this are not reading and get disconnected
+const MAX_PENDING_SIZE: usize = 4 * 1024 * 1024;
+
+type CommandFn<D> = Box<dyn FnMut(Value, &mut D) -> Result<Value, String>>;
+
+#[derive(Debug)]
+struct IpcClient {
+    id: usize,
+    stream: UnixStream,
+    subscriptions: Vec<String>,
+    /// Bytes not yet accepted by the socket
+    pending: Vec<u8>,
+    /// Source flushing `pending`, once the socket is writable
+    writer: Option<RegistrationToken>,
+}
+
+impl IpcClient {
+    fn is_subscribed(&self, event: &str) -> bool {
+        self.subscriptions.iter().any(|sub| sub == event || sub == "*")
+    }
+
+    /// Writes as much of the pending bytes as the socket accepts without blocking
+    fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+struct IpcState<D: 'static> {
+    path: PathBuf,
+    handle: LoopHandle<'static, D>,
+    listener: Cell<Option<RegistrationToken>>,
+    commands: RefCell<HashMap<String, CommandFn<D>>>,
+    clients: RefCell<Vec<IpcClient>>,
+    next_client_id: Cell<usize>,
+    logger: ::slog::Logger,
+}
+
+/// A control socket compositors can expose to external tools
+///
+/// See the [module-level documentation](self) for the protocol. The socket is removed and all
+/// clients are disconnected when the server is dropped.
+pub struct IpcServer<D: 'static> {
+    state: Rc<IpcState<D>>,
+}
+
+impl<D> fmt::Debug for IpcServer<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IpcServer")
+            .field("path", &self.state.path)
+            .field(
+                "commands",
+                &self.state.commands.borrow().keys().collect::<Vec<_>>(),
+            )
+            .field("clients", &self.state.clients.borrow())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: 'static> IpcServer<D> {
+    /// Binds a new control socket at `path` and inserts it into the event loop
+    ///
+    /// A stale socket left behind by a crashed compositor is replaced, but binding fails if
+    /// another process is still listening on `path`.
+    pub fn bind<P, L>(path: P, handle: LoopHandle<'static, D>, logger: L) -> io::Result<IpcServer<D>>
+    where
+        P: AsRef<Path>,
+        L: Into<Option<::slog::Logger>>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let logger = crate::slog_or_fallback(logger).new(o!("smithay_module" => "ipc"));
+
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+                // only replace the socket if nobody is listening anymore
+                match UnixStream::connect(&path) {
+                    Err(connect_err) if connect_err.kind() == io::ErrorKind::ConnectionRefused => {
+                        debug!(logger, "Removing stale socket"; "path" => ?path);
+                        std::fs::remove_file(&path)?;
+                        UnixListener::bind(&path)?
+                    }
+                    _ => return Err(err),
+                }
+            }
+            Err(err) => return Err(err),
+        };
+        listener.set_nonblocking(true)?;
+
+        let state = Rc::new(IpcState {
+            path,
+            handle: handle.clone(),
+            listener: Cell::new(None),
+            commands: RefCell::new(HashMap::new()),
+            clients: RefCell::new(Vec::new()),
+            next_client_id: Cell::new(0),
+            logger,
+        });
+
+        let weak = Rc::downgrade(&state);
+        let token = handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                move |_, listener, _| {
+                    let state = match weak.upgrade() {
+                        Some(state) => state,
+                        None => return Ok(PostAction::Remove),
+                    };
+                    loop {
+                        match listener.accept() {
+                            Ok((stream, _)) => {
+                                if let Err(err) = IpcState::add_client(&state, stream) {
+                                    debug!(state.logger, "Failed to accept client: {}", err);
+                                }
+                            }
+                            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| io::Error::from(err.error))?;
+        state.listener.set(Some(token));
+        info!(state.logger, "Listening for clients"; "path" => ?state.path);
+
+        Ok(IpcServer { state })
+    }
+
+    /// Path of the socket
+    pub fn path(&self) -> &Path {
+        &self.state.path
+    }
+
+    /// Registers a command
+    ///
+    /// The handler receives the arguments of the command and the data of the event loop.
+    /// The returned value is sent back to the client, an error is sent as an error message.
+    /// Registering a command again replaces its previous handler.
+    pub fn register_command<F>(&self, name: impl Into<String>, handler: F)
+    where
+        F: FnMut(Value, &mut D) -> Result<Value, String> + 'static,
+    {
+        self.state
+            .commands
+            .borrow_mut()
+            .insert(name.into(), Box::new(handler));
+    }
+
+    /// Removes a command, returns false if no such command was registered
+    pub fn unregister_command(&self, name: &str) -> bool {
+        self.state.commands.borrow_mut().remove(name).is_some()
+    }
+
+    /// Sends an event to all clients subscribed to it
+    pub fn emit(&self, event: &str, payload: Value) {
+        let message = json!({ "event": event, "payload": payload });
+        let mut failed = Vec::new();
+        for client in self
+            .state
+            .clients
+            .borrow_mut()
+            .iter_mut()
+            .filter(|client| client.is_subscribed(event))
+        {
+            if let Err(err) = IpcState::send(&self.state, client, &message) {
+                debug!(self.state.logger, "Failed to send event: {}", err; "client" => client.id);
+                failed.push(client.id);
+            }
+        }
+        for id in failed {
+            self.state.disconnect(id);
+        }
+    }
+
+    /// Number of currently connected clients
+    pub fn client_count(&self) -> usize {
+        self.state.clients.borrow().len()
+    }
+}
+
+impl<D: 'static> IpcState<D> {
+    fn add_client(state: &Rc<IpcState<D>>, stream: UnixStream) -> io::Result<()> {
+        stream.set_nonblocking(true)?;
+        let id = state.next_client_id.get();
+        state.next_client_id.set(id + 1);
+        state.clients.borrow_mut().push(IpcClient {
+            id,
+            stream: stream.try_clone()?,
+            subscriptions: Vec::new(),
+            pending: Vec::new(),
+            writer: None,
+        });
+        trace!(state.logger, "New client"; "client" => id);
+
+        let weak = Rc::downgrade(state);
+        let mut buffer = Vec::new();
+        let result = state.handle.insert_source(
+            Generic::new(stream, Interest::READ, Mode::Level),
+            move |_, stream, data| Ok(IpcState::dispatch_client(&weak, id, stream, &mut buffer, data)),
+        );
+        if let Err(err) = result {
+            state.remove_client(id);
+            return Err(err.error.into());
+        }
+        Ok(())
+    }
+
+    fn dispatch_client(
+        weak: &Weak<IpcState<D>>,
+        id: usize,
+        stream: &mut UnixStream,
+        buffer: &mut Vec<u8>,
+        data: &mut D,
+    ) -> PostAction {
+        let state = match weak.upgrade() {
+            Some(state) => state,
+            None => return PostAction::Remove,
+        };
+
+        let mut chunk = [0u8; 4096];
+        let mut closed = false;
+        loop {
+            match stream.read(&mut chunk) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    debug!(state.logger, "Failed to read from client: {}", err; "client" => id);
+                    closed = true;
+                    break;
+                }
+            }
+        }
+
+        // answer the complete messages, even if the client hung up afterwards
+        while let Some(pos) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line = buffer.drain(..=pos).collect::<Vec<_>>();
+            let reply = state.handle_message(id, &line[..pos], data);
+            let result = match state
+                .clients
+                .borrow_mut()
+                .iter_mut()
+                .find(|client| client.id == id)
+            {
+                Some(client) => IpcState::send(&state, client, &reply),
+                // the client was disconnected by a command
+                None => return PostAction::Remove,
+            };
+            if let Err(err) = result {
+                debug!(state.logger, "Failed to send reply: {}", err; "client" => id);
+                closed = true;
+                break;
+            }
+        }
+
+        if !closed && buffer.len() > MAX_MESSAGE_SIZE {
+            debug!(state.logger, "Message too long, disconnecting"; "client" => id);
+            closed = true;
+        }
+
+        if closed {
+            trace!(state.logger, "Client disconnected"; "client" => id);
+            state.remove_client(id);
+            PostAction::Remove
+        } else {
+            PostAction::Continue
+        }
+    }
+
+    fn handle_message(&self, id: usize, line: &[u8], data: &mut D) -> Value {
+        let message = match serde_json::from_slice::<Value>(line) {
+            Ok(message) => message,
+            Err(err) => return error_reply(format!("Invalid message: {}", err)),
+        };
+
+        if let Some(events) = message.get("subscribe") {
+            let events: Vec<String> = match events.as_array().and_then(|events| {
+                events
+                    .iter()
+                    .map(|event| event.as_str().map(String::from))
+                    .collect()
+            }) {
+                Some(events) => events,
+                None => return error_reply("Expected a list of event names"),
+            };
+            if let Some(client) = self
+                .clients
+                .borrow_mut()
+                .iter_mut()
+                .find(|client| client.id == id)
+            {
+                client.subscriptions.extend(events);
+            }
+            return json!({ "success": true });
+        }
+
+        let command = match message.get("command").and_then(Value::as_str) {
+            Some(command) => command.to_owned(),
+            None => return error_reply("Missing command"),
+        };
+        let args = message.get("args").cloned().unwrap_or(Value::Null);
+
+        // the handler is taken out of the registry while it runs, so it may (un)register commands
+        let handler = self.commands.borrow_mut().remove(&command);
+        let mut handler = match handler {
+            Some(handler) => handler,
+            None => return error_reply(format!("Unknown command: {}", command)),
+        };
+        trace!(self.logger, "Running command"; "command" => &command, "client" => id);
+        let result = handler(args, data);
+        self.commands.borrow_mut().entry(command).or_insert(handler);
+
+        match result {
+            Ok(result) => json!({ "success": true, "result": result }),
+            Err(err) => error_reply(err),
+        }
+    }
+
+    /// Queues a message and writes as much of it as possible without blocking
+    ///
+    /// The rest is written once the socket becomes writable. Fails if the client stopped reading.
+    fn send(state: &Rc<IpcState<D>>, client: &mut IpcClient, message: &Value) -> io::Result<()> {
+        serde_json::to_writer(&mut client.pending, message)?;
+        client.pending.push(b'\n');
+        if client.pending.len() > MAX_PENDING_SIZE {
+            return Err(io::Error::other("Client is not reading"));
+        }
+        client.flush()?;
+        if client.pending.is_empty() || client.writer.is_some() {
+            return Ok(());
+        }
+
+        let weak = Rc::downgrade(state);
+        let id = client.id;
+        let token = state
+            .handle
+            .insert_source(
+                Generic::new(client.stream.try_clone()?, Interest::WRITE, Mode::Level),
+                move |_, _, _| {
+                    let state = match weak.upgrade() {
+                        Some(state) => state,
+                        None => return Ok(PostAction::Remove),
+                    };
+                    let mut clients = state.clients.borrow_mut();
+                    let client = match clients.iter_mut().find(|client| client.id == id) {
+                        Some(client) => client,
+                        None => return Ok(PostAction::Remove),
+                    };
+                    let result = client.flush();
+                    if result.is_ok() && !client.pending.is_empty() {
+                        return Ok(PostAction::Continue);
+                    }
+                    // this source removes itself
+                    client.writer = None;
+                    if let Err(err) = result {
+                        debug!(state.logger, "Failed to send message: {}", err; "client" => id);
+                        drop(clients);
+                        state.disconnect(id);
+                    }
+                    Ok(PostAction::Remove)
+                },
+            )
+            .map_err(|err| io::Error::from(err.error))?;
+        client.writer = Some(token);
+        Ok(())
+    }
+
+    fn remove_client(&self, id: usize) {
+        let mut clients = self.clients.borrow_mut();
+        if let Some(idx) = clients.iter().position(|client| client.id == id) {
+            if let Some(token) = clients.remove(idx).writer {
+                self.handle.remove(token);
+            }
+        }
+    }
+
+    /// Disconnects a client from outside of its event source
+    fn disconnect(&self, id: usize) {
+        let mut clients = self.clients.borrow_mut();
+        if let Some(idx) = clients.iter().position(|client| client.id == id) {
+            let client = clients.remove(idx);
+            if let Some(token) = client.writer {
+                self.handle.remove(token);
+            }
+            // the event source notices the shutdown and removes itself
+            let _ = client.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+impl<D: 'static> Drop for IpcState<D> {
+    fn drop(&mut self) {
+        if let Some(token) = self.listener.take() {
+            self.handle.remove(token);
+        }
+        for client in self.clients.get_mut().drain(..) {
+            if let Some(token) = client.writer {
+                self.handle.remove(token);
+            }
+            let _ = client.stream.shutdown(std::net::Shutdown::Both);
+        }
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            debug!(self.logger, "Failed to remove socket: {}", err; "path" => ?self.path);
+        }
+    }
+}
+
+fn error_reply(error: impl Into<String>) -> Value {
+    json!({ "success": false, "error": error.into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader},
+        time::Duration,
+    };
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("smithay-ipc-{}-{}.sock", name, std::process::id()))
+    }
+
+    fn read_reply(
+        event_loop: &mut calloop::EventLoop<'static, u32>,
+        reader: &mut BufReader<UnixStream>,
+        data: &mut u32,
+    ) -> Value {
+        for _ in 0..3 {
+            event_loop
+                .dispatch(Some(Duration::from_millis(10)), data)
+                .unwrap();
+        }
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn runs_commands() {
+        let mut event_loop = calloop::EventLoop::<u32>::try_new().unwrap();
+        let path = socket_path("commands");
+        let server = IpcServer::bind(&path, event_loop.handle(), None).unwrap();
+        server.register_command("add", |args, data: &mut u32| {
+            *data += args.as_u64().ok_or_else(|| String::from("Not a number"))? as u32;
+            Ok(json!(*data))
+        });
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut data = 1;
+
+        client
+            .write_all(b"{\"command\": \"add\", \"args\": 2}\n")
+            .unwrap();
+        let reply = read_reply(&mut event_loop, &mut reader, &mut data);
+        assert_eq!(reply, json!({ "success": true, "result": 3 }));
+        assert_eq!(data, 3);
+
+        client.write_all(b"{\"command\": \"add\"}\n").unwrap();
+        let reply = read_reply(&mut event_loop, &mut reader, &mut data);
+        assert_eq!(reply, json!({ "success": false, "error": "Not a number" }));
+
+        client.write_all(b"{\"command\": \"remove\"}\n").unwrap();
+        let reply = read_reply(&mut event_loop, &mut reader, &mut data);
+        assert_eq!(reply["success"], json!(false));
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn sends_subscribed_events() {
+        let mut event_loop = calloop::EventLoop::<u32>::try_new().unwrap();
+        let path = socket_path("events");
+        let server = IpcServer::bind(&path, event_loop.handle(), None).unwrap();
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut data = 0;
+
+        client.write_all(b"{\"subscribe\": [\"focus\"]}\n").unwrap();
+        let reply = read_reply(&mut event_loop, &mut reader, &mut data);
+        assert_eq!(reply, json!({ "success": true }));
+        assert_eq!(server.client_count(), 1);
+
+        server.emit("workspace", json!(1));
+        server.emit("focus", json!("terminal"));
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&line).unwrap(),
+            json!({ "event": "focus", "payload": "terminal" })
+        );
+    }
+
+    #[test]
+    fn queues_events_for_slow_clients() {
+        let mut event_loop = calloop::EventLoop::<u32>::try_new().unwrap();
+        let path = socket_path("slow");
+        let server = IpcServer::bind(&path, event_loop.handle(), None).unwrap();
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let mut reader = BufReader::new(client.try_clone().unwrap());
+        let mut data = 0;
+        client.write_all(b"{\"subscribe\": [\"*\"]}\n").unwrap();
+        read_reply(&mut event_loop, &mut reader, &mut data);
+
+        // larger than the socket buffer, the rest is written while the client reads
+        let payload = "x".repeat(MAX_MESSAGE_SIZE);
+        server.emit("large", json!(payload));
+        let receiver = std::thread::spawn(move || {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            line.len()
+        });
+        while !receiver.is_finished() {
+            event_loop
+                .dispatch(Some(Duration::from_millis(10)), &mut data)
+                .unwrap();
+        }
+        assert!(receiver.join().unwrap() > MAX_MESSAGE_SIZE);
+
+        // a client not reading at all is disconnected instead of blocking the compositor
+        for _ in 0..=MAX_PENDING_SIZE / MAX_MESSAGE_SIZE {
+            server.emit("large", json!(payload));
+        }
+        assert_eq!(server.client_count(), 0);
+    }
+}
@@ -0,0 +1,823 @@
+//! Metrics for monitoring compositors
+//!
+//! A [`MetricsRegistry`] holds counters, gauges and histograms, optionally distinguished by
+//! labels, e.g. the name of an output. Their values can be read directly, e.g. to show them on
+//! screen, or encoded in the [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/)
+//! and served over http by a [`MetricsExporter`] on a tcp or unix socket, so compositors
+//! deployed on many machines, like kiosks, can be monitored.
+//!
+//! [`CompositorMetrics`] provides the metrics most compositors want to record: frame times,
+//! missed vblanks, the number of clients, buffer imports and input latency.
+//!
+//! ```no_run
+//! use smithay::utils::metrics::{CompositorMetrics, MetricsExporter, MetricsRegistry};
+//! use std::{net::TcpListener, time::Duration};
+//!
+//! # let event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+//! let registry = MetricsRegistry::new();
+//! let metrics = CompositorMetrics::new(&registry);
+//! let listener = TcpListener::bind("127.0.0.1:9100").expect("Failed to bind metrics socket");
+//! let exporter = MetricsExporter::serve(registry.clone(), listener, event_loop.handle(), None)
+//!     .expect("Failed to serve metrics");
+//!
+//! // after rendering a frame
+//! metrics.frame_rendered("DP-1", Duration::from_micros(4200));
+//! // whenever a frame was late
+//! metrics.vblank_missed("DP-1");
+//!
+//! let p99 = metrics.input_latency_quantile(0.99);
+//! ```
+
+use std::{
+    fmt::{self, Write as _},
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    os::unix::{
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction, RegistrationToken};
+use slog::{debug, info, o};
+
+/// Requests longer than this are considered garbage and the connection is closed
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Default buckets of histograms measuring durations, in seconds
+pub const DURATION_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.002, 0.004, 0.008, 0.016, 0.033, 0.066, 0.125, 0.25, 0.5, 1.0,
+];
+
+/// Kind of a metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// A value, that only ever increases
+    Counter,
+    /// A value, that can go up and down
+    Gauge,
+    /// Observations counted in buckets
+    Histogram,
+}
+
+impl MetricKind {
+    fn name(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+        }
+    }
+}
+
+/// A counter, see [`MetricsRegistry::counter`]
+#[derive(Debug, Clone)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    /// Increments the counter by one
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    /// Increments the counter by `value`
+    pub fn inc_by(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Returns the current value
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A gauge, see [`MetricsRegistry::gauge`]
+#[derive(Debug, Clone)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    /// Sets the gauge to `value`
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Adds `value` to the gauge, which may be negative
+    pub fn add(&self, value: f64) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + value).to_bits())
+        });
+    }
+
+    /// Returns the current value
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Debug)]
+struct HistogramData {
+    /// Upper bounds of the buckets, sorted, without the implicit `+Inf` bucket
+    bounds: Vec<f64>,
+    /// Observations per bucket, not cumulative, the last one is the `+Inf` bucket
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl HistogramData {
+    fn new(bounds: &[f64]) -> HistogramData {
+        let mut bounds = bounds
+            .iter()
+            .copied()
+            .filter(|bound| bound.is_finite())
+            .collect::<Vec<_>>();
+        bounds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        bounds.dedup();
+        HistogramData {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.counts[bucket] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Estimates a quantile like Prometheus' `histogram_quantile`, by interpolating linearly
+    /// inside of the bucket the quantile falls into
+    fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 || !(0.0..=1.0).contains(&q) {
+            return None;
+        }
+        let rank = q * self.count as f64;
+        let mut cumulative = 0;
+        for (idx, count) in self.counts.iter().enumerate() {
+            let below = cumulative;
+            cumulative += count;
+            if (cumulative as f64) < rank || *count == 0 {
+                continue;
+            }
+            let upper = match self.bounds.get(idx) {
+                Some(upper) => *upper,
+                // the +Inf bucket has no upper bound to interpolate to
+                None => break,
+            };
+            let lower = if idx == 0 {
+                upper.min(0.0)
+            } else {
+                self.bounds[idx - 1]
+            };
+            let fraction = (rank - below as f64) / *count as f64;
+            return Some(lower + (upper - lower) * fraction);
+        }
+        self.bounds.last().copied()
+    }
+}
+
+/// A histogram, see [`MetricsRegistry::histogram`]
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<Mutex<HistogramData>>);
+
+impl Histogram {
+    /// Records an observation
+    pub fn observe(&self, value: f64) {
+        self.0.lock().unwrap().observe(value);
+    }
+
+    /// Records a duration in seconds
+    pub fn observe_duration(&self, duration: Duration) {
+        self.observe(duration.as_secs_f64());
+    }
+
+    /// Returns the number of observations
+    pub fn count(&self) -> u64 {
+        self.0.lock().unwrap().count
+    }
+
+    /// Returns the sum of all observations
+    pub fn sum(&self) -> f64 {
+        self.0.lock().unwrap().sum
+    }
+
+    /// Estimates the `q`-quantile of the observations, e.g. `0.99` for the 99th percentile
+    ///
+    /// The estimate is interpolated linearly inside of the bucket the quantile falls into, so
+    /// its precision depends on the buckets. If it falls into the last bucket without an upper
+    /// bound, the largest bound is returned. Returns `None` if there are no observations or `q`
+    /// is not in `0.0..=1.0`.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        self.0.lock().unwrap().quantile(q)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SeriesValue {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+#[derive(Debug)]
+struct Series {
+    labels: Vec<(String, String)>,
+    value: SeriesValue,
+}
+
+#[derive(Debug)]
+struct Family {
+    name: String,
+    help: String,
+    kind: MetricKind,
+    series: Vec<Series>,
+}
+
+/// Registry of metrics
+///
+/// Cloning the registry is cheap and all clones share the same metrics.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    families: Arc<Mutex<Vec<Family>>>,
+}
+
+impl MetricsRegistry {
+    /// Creates an empty registry
+    pub fn new() -> MetricsRegistry {
+        MetricsRegistry::default()
+    }
+
+    /// Returns the counter with the given name and labels, registering it if needed
+    ///
+    /// The help text is only used, when the first metric with this name is registered.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or a label name is not a valid Prometheus name or if `name` was
+    /// registered as another kind of metric.
+    pub fn counter(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Counter {
+        match self.get_or_register(name, help, MetricKind::Counter, labels, || {
+            SeriesValue::Counter(Counter(Arc::new(AtomicU64::new(0))))
+        }) {
+            SeriesValue::Counter(counter) => counter,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the gauge with the given name and labels, registering it if needed
+    ///
+    /// See [`MetricsRegistry::counter`] for details.
+    pub fn gauge(&self, name: &str, help: &str, labels: &[(&str, &str)]) -> Gauge {
+        match self.get_or_register(name, help, MetricKind::Gauge, labels, || {
+            SeriesValue::Gauge(Gauge(Arc::new(AtomicU64::new(0f64.to_bits()))))
+        }) {
+            SeriesValue::Gauge(gauge) => gauge,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns the histogram with the given name and labels, registering it if needed
+    ///
+    /// `buckets` are the upper bounds of the buckets, a bucket without upper bound is added
+    /// implicitly. They are only used, when the histogram is registered.
+    /// See [`MetricsRegistry::counter`] for details.
+    pub fn histogram(&self, name: &str, help: &str, labels: &[(&str, &str)], buckets: &[f64]) -> Histogram {
+        match self.get_or_register(name, help, MetricKind::Histogram, labels, || {
+            SeriesValue::Histogram(Histogram(Arc::new(Mutex::new(HistogramData::new(buckets)))))
+        }) {
+            SeriesValue::Histogram(histogram) => histogram,
+            _ => unreachable!(),
+        }
+    }
+
+    fn get_or_register(
+        &self,
+        name: &str,
+        help: &str,
+        kind: MetricKind,
+        labels: &[(&str, &str)],
+        create: impl FnOnce() -> SeriesValue,
+    ) -> SeriesValue {
+        assert!(is_valid_name(name), "Invalid metric name {:?}", name);
+        for (label, _) in labels {
+            assert!(
+                is_valid_name(label) && *label != "le",
+                "Invalid label name {:?}",
+                label
+            );
+        }
+
+        let mut families = self.families.lock().unwrap();
+        let family = match families.iter().position(|family| family.name == name) {
+            Some(idx) => &mut families[idx],
+            None => {
+                families.push(Family {
+                    name: name.to_owned(),
+                    help: help.to_owned(),
+                    kind,
+                    series: Vec::new(),
+                });
+                families.last_mut().unwrap()
+            }
+        };
+        assert!(
+            family.kind == kind,
+            "Metric {:?} is a {}, not a {}",
+            name,
+            family.kind.name(),
+            kind.name()
+        );
+
+        let mut labels = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        if let Some(series) = family.series.iter().find(|series| series.labels == labels) {
+            return series.value.clone();
+        }
+        let value = create();
+        family.series.push(Series {
+            labels,
+            value: value.clone(),
+        });
+        value
+    }
+
+    /// Removes all series of a metric with the given labels, e.g. when an output was unplugged
+    ///
+    /// Handles of the removed metrics stay usable, but are not part of the registry anymore.
+    pub fn remove(&self, name: &str, labels: &[(&str, &str)]) {
+        let mut labels = labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<Vec<_>>();
+        labels.sort();
+        for family in self
+            .families
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter(|family| family.name == name)
+        {
+            family.series.retain(|series| series.labels != labels);
+        }
+    }
+
+    /// Encodes all metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        for family in self.families.lock().unwrap().iter() {
+            let _ = writeln!(out, "# HELP {} {}", family.name, escape(&family.help, false));
+            let _ = writeln!(out, "# TYPE {} {}", family.name, family.kind.name());
+            for series in &family.series {
+                let labels = |extra: Option<(&str, String)>| {
+                    let mut labels = series
+                        .labels
+                        .iter()
+                        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value, true)))
+                        .collect::<Vec<_>>();
+                    if let Some((name, value)) = extra {
+                        labels.push(format!("{}=\"{}\"", name, value));
+                    }
+                    if labels.is_empty() {
+                        String::new()
+                    } else {
+                        format!("{{{}}}", labels.join(","))
+                    }
+                };
+                match &series.value {
+                    SeriesValue::Counter(counter) => {
+                        let _ = writeln!(out, "{}{} {}", family.name, labels(None), counter.get());
+                    }
+                    SeriesValue::Gauge(gauge) => {
+                        let _ = writeln!(out, "{}{} {}", family.name, labels(None), Float(gauge.get()));
+                    }
+                    SeriesValue::Histogram(histogram) => {
+                        let data = histogram.0.lock().unwrap();
+                        let mut cumulative = 0;
+                        for (idx, count) in data.counts.iter().enumerate() {
+                            cumulative += count;
+                            let le = Float(data.bounds.get(idx).copied().unwrap_or(f64::INFINITY));
+                            let _ = writeln!(
+                                out,
+                                "{}_bucket{} {}",
+                                family.name,
+                                labels(Some(("le", le.to_string()))),
+                                cumulative
+                            );
+                        }
+                        let _ = writeln!(out, "{}_sum{} {}", family.name, labels(None), Float(data.sum));
+                        let _ = writeln!(out, "{}_count{} {}", family.name, labels(None), data.count);
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+fn escape(text: &str, quotes: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '"' if quotes => escaped.push_str("\\\""),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats floats like Prometheus expects them
+struct Float(f64);
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_nan() {
+            f.write_str("NaN")
+        } else if self.0 == f64::INFINITY {
+            f.write_str("+Inf")
+        } else if self.0 == f64::NEG_INFINITY {
+            f.write_str("-Inf")
+        } else {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+/// Metrics commonly recorded by compositors
+///
+/// All metrics are prefixed with `smithay_`, output specific ones are labeled with the name of
+/// the output:
+///
+/// - `smithay_frame_time_seconds`: histogram of the time spent rendering frames, per output
+/// - `smithay_missed_vblanks_total`: frames not ready in time for their vblank, per output
+/// - `smithay_clients`: number of connected clients
+/// - `smithay_buffer_imports_total`: buffers imported into the renderer, labeled by `kind`,
+///   e.g. `shm` or `dmabuf`
+/// - `smithay_input_latency_seconds`: histogram of the time between input events being
+///   generated by the kernel and being processed
+#[derive(Debug, Clone)]
+pub struct CompositorMetrics {
+    registry: MetricsRegistry,
+    clients: Gauge,
+    input_latency: Histogram,
+}
+
+impl CompositorMetrics {
+    /// Registers the metrics in `registry`
+    pub fn new(registry: &MetricsRegistry) -> CompositorMetrics {
+        CompositorMetrics {
+            registry: registry.clone(),
+            clients: registry.gauge("smithay_clients", "Number of connected clients", &[]),
+            input_latency: registry.histogram(
+                "smithay_input_latency_seconds",
+                "Time between input events being generated and processed",
+                &[],
+                DURATION_BUCKETS,
+            ),
+        }
+    }
+
+    /// Returns the registry of the metrics
+    pub fn registry(&self) -> &MetricsRegistry {
+        &self.registry
+    }
+
+    /// Records the time spent rendering a frame of an output
+    pub fn frame_rendered(&self, output: &str, duration: Duration) {
+        self.registry
+            .histogram(
+                "smithay_frame_time_seconds",
+                "Time spent rendering frames",
+                &[("output", output)],
+                DURATION_BUCKETS,
+            )
+            .observe_duration(duration);
+    }
+
+    /// Records a frame of an output, that was not ready in time for its vblank
+    pub fn vblank_missed(&self, output: &str) {
+        self.registry
+            .counter(
+                "smithay_missed_vblanks_total",
+                "Frames not ready in time for their vblank",
+                &[("output", output)],
+            )
+            .inc();
+    }
+
+    /// Removes the metrics of an output, e.g. after it was unplugged
+    pub fn remove_output(&self, output: &str) {
+        self.registry
+            .remove("smithay_frame_time_seconds", &[("output", output)]);
+        self.registry
+            .remove("smithay_missed_vblanks_total", &[("output", output)]);
+    }
+
+    /// Sets the number of connected clients
+    pub fn set_clients(&self, clients: usize) {
+        self.clients.set(clients as f64);
+    }
+
+    /// Records the import of a buffer into a renderer, `kind` is e.g. `shm` or `dmabuf`
+    pub fn buffer_imported(&self, kind: &str) {
+        self.registry
+            .counter(
+                "smithay_buffer_imports_total",
+                "Buffers imported into the renderer",
+                &[("kind", kind)],
+            )
+            .inc();
+    }
+
+    /// Records the time between an input event being generated and it being processed
+    ///
+    /// For libinput events this is the difference between `CLOCK_MONOTONIC` and
+    /// the time of the event.
+    pub fn input_processed(&self, latency: Duration) {
+        self.input_latency.observe_duration(latency);
+    }
+
+    /// Estimates a quantile of the input latency, e.g. `0.99` for the 99th percentile
+    ///
+    /// See [`Histogram::quantile`].
+    pub fn input_latency_quantile(&self, q: f64) -> Option<Duration> {
+        self.input_latency
+            .quantile(q)
+            .map(|seconds| Duration::from_secs_f64(seconds.max(0.0)))
+    }
+}
+
+/// Listening sockets a [`MetricsExporter`] can serve metrics on
+pub trait MetricsListener: AsRawFd + 'static {
+    /// Connections accepted by this listener
+    type Stream: Read + Write + AsRawFd + 'static;
+
+    /// Moves the listener into or out of non-blocking mode
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+    /// Accepts a new connection
+    fn accept_stream(&self) -> io::Result<Self::Stream>;
+    /// Moves a connection into or out of non-blocking mode
+    fn set_stream_nonblocking(stream: &Self::Stream, nonblocking: bool) -> io::Result<()>;
+    /// Sets the write timeout of a connection
+    fn set_stream_write_timeout(stream: &Self::Stream, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl MetricsListener for TcpListener {
+    type Stream = TcpStream;
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpListener::set_nonblocking(self, nonblocking)
+    }
+    fn accept_stream(&self) -> io::Result<TcpStream> {
+        self.accept().map(|(stream, _)| stream)
+    }
+    fn set_stream_nonblocking(stream: &TcpStream, nonblocking: bool) -> io::Result<()> {
+        stream.set_nonblocking(nonblocking)
+    }
+    fn set_stream_write_timeout(stream: &TcpStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_write_timeout(timeout)
+    }
+}
+
+impl MetricsListener for UnixListener {
+    type Stream = UnixStream;
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixListener::set_nonblocking(self, nonblocking)
+    }
+    fn accept_stream(&self) -> io::Result<UnixStream> {
+        self.accept().map(|(stream, _)| stream)
+    }
+    fn set_stream_nonblocking(stream: &UnixStream, nonblocking: bool) -> io::Result<()> {
+        stream.set_nonblocking(nonblocking)
+    }
+    fn set_stream_write_timeout(stream: &UnixStream, timeout: Option<Duration>) -> io::Result<()> {
+        stream.set_write_timeout(timeout)
+    }
+}
+
+/// Serves the metrics of a [`MetricsRegistry`] over http
+///
+/// Every `GET` request is answered with all metrics in the Prometheus text format, regardless
+/// of its path, so it can be scraped by Prometheus or queried with e.g.
+/// `curl --unix-socket <path> http://localhost/metrics`. The connections are handled in the
+/// calloop event loop. The listener is removed from the event loop when the exporter is dropped.
+pub struct MetricsExporter<D: 'static> {
+    handle: LoopHandle<'static, D>,
+    token: Option<RegistrationToken>,
+}
+
+impl<D> fmt::Debug for MetricsExporter<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsExporter")
+            .field("token", &self.token)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: 'static> MetricsExporter<D> {
+    /// Starts serving the metrics of `registry` on `listener`
+    pub fn serve<S, L>(
+        registry: MetricsRegistry,
+        listener: S,
+        handle: LoopHandle<'static, D>,
+        logger: L,
+    ) -> io::Result<MetricsExporter<D>>
+    where
+        S: MetricsListener,
+        L: Into<Option<::slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(logger).new(o!("smithay_module" => "metrics"));
+        listener.set_nonblocking(true)?;
+
+        let loop_handle = handle.clone();
+        let log = logger.clone();
+        let token = handle
+            .insert_source(
+                Generic::new(listener, Interest::READ, Mode::Level),
+                move |_, listener, _| {
+                    loop {
+                        match listener.accept_stream() {
+                            Ok(stream) => {
+                                if let Err(err) =
+                                    add_connection::<S, D>(stream, &registry, &loop_handle, &log)
+                                {
+                                    debug!(log, "Failed to accept connection: {}", err);
+                                }
+                            }
+                            Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(err) => return Err(err),
+                        }
+                    }
+                    Ok(PostAction::Continue)
+                },
+            )
+            .map_err(|err| io::Error::from(err.error))?;
+        info!(logger, "Serving metrics");
+
+        Ok(MetricsExporter {
+            handle,
+            token: Some(token),
+        })
+    }
+}
+
+impl<D: 'static> Drop for MetricsExporter<D> {
+    fn drop(&mut self) {
+        if let Some(token) = self.token.take() {
+            self.handle.remove(token);
+        }
+    }
+}
+
+fn add_connection<S: MetricsListener, D: 'static>(
+    stream: S::Stream,
+    registry: &MetricsRegistry,
+    handle: &LoopHandle<'static, D>,
+    logger: &::slog::Logger,
+) -> io::Result<()> {
+    S::set_stream_nonblocking(&stream, true)?;
+    let registry = registry.clone();
+    let logger = logger.clone();
+    let mut request = Vec::new();
+    handle
+        .insert_source(
+            Generic::new(stream, Interest::READ, Mode::Level),
+            move |_, stream, _| {
+                let mut chunk = [0u8; 1024];
+                let mut closed = false;
+                loop {
+                    match stream.read(&mut chunk) {
+                        Ok(0) => {
+                            closed = true;
+                            break;
+                        }
+                        Ok(n) => request.extend_from_slice(&chunk[..n]),
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                        Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(err) => {
+                            debug!(logger, "Failed to read request: {}", err);
+                            return Ok(PostAction::Remove);
+                        }
+                    }
+                }
+
+                let complete = request.windows(4).any(|end| end == b"\r\n\r\n")
+                    || request.windows(2).any(|end| end == b"\n\n");
+                if !complete && !closed && request.len() <= MAX_REQUEST_SIZE {
+                    return Ok(PostAction::Continue);
+                }
+
+                let response = if request.starts_with(b"GET ") {
+                    http_response("200 OK", &registry.encode())
+                } else {
+                    http_response("405 Method Not Allowed", "")
+                };
+                // the response is small, so it is fine to block shortly
+                let result = S::set_stream_nonblocking(stream, false)
+                    .and_then(|_| S::set_stream_write_timeout(stream, Some(Duration::from_secs(1))))
+                    .and_then(|_| stream.write_all(response.as_bytes()));
+                if let Err(err) = result {
+                    debug!(logger, "Failed to send metrics: {}", err);
+                }
+                Ok(PostAction::Remove)
+            },
+        )
+        .map_err(|err| io::Error::from(err.error))?;
+    Ok(())
+}
+
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_text_format() {
+        let registry = MetricsRegistry::new();
+        registry
+            .counter("frames_total", "Rendered frames", &[("output", "DP-1")])
+            .inc_by(3);
+        // the same labels return the same counter
+        registry.counter("frames_total", "", &[("output", "DP-1")]).inc();
+        registry.gauge("clients", "Connected \"clients\"\n", &[]).set(2.5);
+        let histogram = registry.histogram("latency_seconds", "Latency", &[], &[0.5, 0.1]);
+        histogram.observe(0.0625);
+        histogram.observe(0.25);
+        histogram.observe(2.0);
+
+        assert_eq!(
+            registry.encode(),
+            "# HELP frames_total Rendered frames\n\
+             # TYPE frames_total counter\n\
+             frames_total{output=\"DP-1\"} 4\n\
+             # HELP clients Connected \"clients\"\\n\n\
+             # TYPE clients gauge\n\
+             clients 2.5\n\
+             # HELP latency_seconds Latency\n\
+             # TYPE latency_seconds histogram\n\
+             latency_seconds_bucket{le=\"0.1\"} 1\n\
+             latency_seconds_bucket{le=\"0.5\"} 2\n\
+             latency_seconds_bucket{le=\"+Inf\"} 3\n\
+             latency_seconds_sum 2.3125\n\
+             latency_seconds_count 3\n"
+        );
+
+        registry.remove("frames_total", &[("output", "DP-1")]);
+        assert!(!registry.encode().contains("frames_total{"));
+    }
+
+    #[test]
+    fn estimates_quantiles() {
+        let registry = MetricsRegistry::new();
+        let histogram = registry.histogram("latency", "", &[], &[1.0, 2.0, 4.0]);
+        assert_eq!(histogram.quantile(0.5), None);
+        for value in &[0.5, 1.5, 1.5, 3.0] {
+            histogram.observe(*value);
+        }
+        assert_eq!(histogram.quantile(0.25), Some(1.0));
+        // half way through the second bucket
+        assert_eq!(histogram.quantile(0.5), Some(1.5));
+        assert_eq!(histogram.quantile(1.0), Some(4.0));
+        histogram.observe(10.0);
+        assert_eq!(histogram.quantile(1.0), Some(4.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_kind_changes() {
+        let registry = MetricsRegistry::new();
+        registry.counter("metric", "", &[]);
+        registry.gauge("metric", "", &[]);
+    }
+}
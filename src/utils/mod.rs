@@ -1,7 +1,14 @@
 //! Various utilities functions and types
 
+mod clock;
 mod geometry;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod signaling;
+#[cfg(target_os = "linux")]
+pub mod timer;
 
 #[cfg(feature = "x11rb_event_source")]
 pub mod x11rb;
@@ -10,6 +17,7 @@ pub mod x11rb;
 pub(crate) mod ids;
 pub mod user_data;
 
+pub use self::clock::monotonic_time;
 pub use self::geometry::{Buffer, Coordinate, Logical, Physical, Point, Raw, Rectangle, Size, Transform};
 
 /// This resource is not managed by Smithay
@@ -0,0 +1,238 @@
+//! High-resolution timer source for frame deadlines
+//!
+//! The timers provided by calloop only have millisecond precision, which is not enough for
+//! repaint strategies like "start rendering 2ms before the next vblank". [`DeadlineTimer`] is
+//! a calloop event source backed by a `timerfd`, that fires at absolute `CLOCK_MONOTONIC`
+//! deadlines with nanosecond resolution.
+//!
+//! `CLOCK_MONOTONIC` is the clock used for the timestamps of DRM page flip events and of the
+//! presentation-time protocol, so deadlines can be derived from them directly. Use [`monotonic_time`]
+//! to query the current time of this clock.
+//!
+//! ```no_run
+//! use smithay::utils::timer::{monotonic_time, DeadlineTimer};
+//! use std::time::Duration;
+//!
+//! # let mut event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+//! let (timer, handle) = DeadlineTimer::new().expect("Failed to create timer");
+//! event_loop
+//!     .handle()
+//!     .insert_source(timer, |deadline, _, _| {
+//!         // render the next frame
+//!     })
+//!     .unwrap();
+//!
+//! // fire in 14ms, e.g. 2ms before the next vblank of a 60Hz output
+//! handle.set_deadline(monotonic_time() + Duration::from_millis(14)).unwrap();
+//! ```
+
+use std::{
+    cell::Cell,
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    rc::Rc,
+    time::Duration,
+};
+
+use calloop::{
+    generic::Generic, EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use nix::sys::{
+    time::TimeSpec,
+    timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags},
+};
+
+pub use super::clock::monotonic_time;
+
+#[derive(Debug)]
+struct Inner {
+    fd: TimerFd,
+    deadline: Cell<Option<Duration>>,
+}
+
+#[derive(Debug)]
+struct TimerFdRef(Rc<Inner>);
+
+impl AsRawFd for TimerFdRef {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.fd.as_raw_fd()
+    }
+}
+
+/// Calloop event source firing at absolute `CLOCK_MONOTONIC` deadlines
+///
+/// The timer is controlled through its associated [`DeadlineTimerHandle`]. Only one deadline
+/// can be pending at a given time, setting a new one replaces the previous one.
+///
+/// The event generated by this source is the deadline that was reached. Comparing it to
+/// [`monotonic_time`] gives the latency of the wake-up.
+#[derive(Debug)]
+pub struct DeadlineTimer {
+    source: Generic<TimerFdRef>,
+}
+
+/// Handle to control a [`DeadlineTimer`]
+///
+/// It can be cloned and all clones control the same timer.
+#[derive(Debug, Clone)]
+pub struct DeadlineTimerHandle {
+    inner: Rc<Inner>,
+}
+
+impl DeadlineTimer {
+    /// Creates a new timer, that is initially disarmed
+    pub fn new() -> io::Result<(DeadlineTimer, DeadlineTimerHandle)> {
+        let fd = TimerFd::new(
+            ClockId::CLOCK_MONOTONIC,
+            TimerFlags::TFD_CLOEXEC | TimerFlags::TFD_NONBLOCK,
+        )
+        .map_err(io::Error::from)?;
+        let inner = Rc::new(Inner {
+            fd,
+            deadline: Cell::new(None),
+        });
+        Ok((
+            DeadlineTimer {
+                source: Generic::new(TimerFdRef(inner.clone()), Interest::READ, Mode::Level),
+            },
+            DeadlineTimerHandle { inner },
+        ))
+    }
+}
+
+impl DeadlineTimerHandle {
+    /// Arms the timer to fire at the given absolute `CLOCK_MONOTONIC` time
+    ///
+    /// Deadlines in the past fire immediately.
+    pub fn set_deadline(&self, deadline: Duration) -> io::Result<()> {
+        // A zero expiration would disarm the timer, so fire as early as possible instead
+        let expiration = if deadline == Duration::from_secs(0) {
+            Duration::from_nanos(1)
+        } else {
+            deadline
+        };
+        self.inner
+            .fd
+            .set(
+                Expiration::OneShot(TimeSpec::from(expiration)),
+                TimerSetTimeFlags::TFD_TIMER_ABSTIME,
+            )
+            .map_err(io::Error::from)?;
+        self.inner.deadline.set(Some(deadline));
+        Ok(())
+    }
+
+    /// Arms the timer to fire after the given duration
+    pub fn set_timeout(&self, timeout: Duration) -> io::Result<()> {
+        self.set_deadline(monotonic_time() + timeout)
+    }
+
+    /// Disarms the timer, if a deadline is pending
+    pub fn cancel(&self) -> io::Result<()> {
+        self.inner.deadline.set(None);
+        self.inner.fd.unset().map_err(io::Error::from)
+    }
+
+    /// Returns the pending deadline, if any
+    pub fn deadline(&self) -> Option<Duration> {
+        self.inner.deadline.get()
+    }
+}
+
+impl EventSource for DeadlineTimer {
+    type Event = Duration;
+    type Metadata = DeadlineTimerHandle;
+    type Ret = ();
+    type Error = io::Error;
+
+    fn process_events<C>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: C,
+    ) -> io::Result<PostAction>
+    where
+        C: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        self.source.process_events(readiness, token, |_, fd| {
+            // Reset the readiness of the timerfd by reading the expiration count
+            let mut count = [0u8; 8];
+            match nix::unistd::read(fd.as_raw_fd(), &mut count) {
+                Ok(_) => {}
+                // The timer was re-armed or cancelled in the meantime
+                Err(nix::errno::Errno::EAGAIN) => return Ok(PostAction::Continue),
+                Err(err) => return Err(err.into()),
+            }
+
+            if let Some(deadline) = fd.0.deadline.take() {
+                let mut handle = DeadlineTimerHandle { inner: fd.0.clone() };
+                callback(deadline, &mut handle);
+            }
+            Ok(PostAction::Continue)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.source.register(poll, factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.source.reregister(poll, factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.source.unregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_at_deadline() {
+        let mut event_loop = calloop::EventLoop::<Option<Duration>>::try_new().unwrap();
+        let (timer, handle) = DeadlineTimer::new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(timer, |deadline, _, fired| {
+                *fired = Some(deadline);
+            })
+            .unwrap();
+
+        let deadline = monotonic_time() + Duration::from_millis(5);
+        handle.set_deadline(deadline).unwrap();
+        assert_eq!(handle.deadline(), Some(deadline));
+
+        let mut fired = None;
+        while fired.is_none() {
+            event_loop
+                .dispatch(Some(Duration::from_millis(100)), &mut fired)
+                .unwrap();
+        }
+        assert_eq!(fired, Some(deadline));
+        assert!(monotonic_time() >= deadline);
+        assert_eq!(handle.deadline(), None);
+    }
+
+    #[test]
+    fn cancel_disarms() {
+        let mut event_loop = calloop::EventLoop::<bool>::try_new().unwrap();
+        let (timer, handle) = DeadlineTimer::new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(timer, |_, _, fired| {
+                *fired = true;
+            })
+            .unwrap();
+
+        handle.set_timeout(Duration::from_millis(1)).unwrap();
+        handle.cancel().unwrap();
+
+        let mut fired = false;
+        event_loop
+            .dispatch(Some(Duration::from_millis(20)), &mut fired)
+            .unwrap();
+        assert!(!fired);
+    }
+}
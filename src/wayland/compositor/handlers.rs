@@ -17,7 +17,7 @@ use crate::utils::{Logical, Point};
 
 use super::{
     cache::Cacheable,
-    tree::{Location, PrivateSurfaceData},
+    tree::{Location, PrivateSurfaceData, SUBSURFACE_ROLE},
     AlreadyHasRole, BufferAssignment, Damage, Rectangle, RectangleKind, RegionAttributes, SurfaceAttributes,
 };
 
@@ -278,10 +278,21 @@ pub(crate) fn implement_subcompositor(
     subcompositor.quick_assign(move |subcompositor, request, _| match request {
         wl_subcompositor::Request::GetSubsurface { id, surface, parent } => {
             if let Err(AlreadyHasRole) = PrivateSurfaceData::set_parent(&surface, &parent) {
-                subcompositor.as_ref().post_error(
-                    wl_subcompositor::Error::BadSurface as u32,
-                    "Surface already has a role.".into(),
-                );
+                let role = PrivateSurfaceData::get_role(&surface);
+                let message = if matches!(role, Some(role) if role != SUBSURFACE_ROLE) {
+                    super::role_error_message(&surface, SUBSURFACE_ROLE)
+                } else if PrivateSurfaceData::get_parent(&surface).is_some() {
+                    format!("wl_surface@{} already is a subsurface", surface.as_ref().id())
+                } else {
+                    format!(
+                        "wl_surface@{} cannot be a subsurface of itself or of its descendant wl_surface@{}",
+                        surface.as_ref().id(),
+                        parent.as_ref().id()
+                    )
+                };
+                subcompositor
+                    .as_ref()
+                    .post_error(wl_subcompositor::Error::BadSurface as u32, message);
                 return;
             }
             implement_subsurface(id, surface);
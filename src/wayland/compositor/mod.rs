@@ -373,6 +373,23 @@ pub fn give_role(surface: &WlSurface, role: &'static str) -> Result<(), AlreadyH
     PrivateSurfaceData::set_role(surface, role)
 }
 
+/// Describes why `role` could not be given to a surface, for protocol errors
+pub(crate) fn role_error_message(surface: &WlSurface, role: &str) -> String {
+    match get_role(surface) {
+        Some(current) => format!(
+            "wl_surface@{} cannot get the {} role, it already has the {} role",
+            surface.as_ref().id(),
+            role,
+            current
+        ),
+        None => format!(
+            "wl_surface@{} cannot get the {} role",
+            surface.as_ref().id(),
+            role
+        ),
+    }
+}
+
 /// Access the states associated to this surface
 pub fn with_states<F, T>(surface: &WlSurface, f: F) -> Result<T, DeadResource>
 where
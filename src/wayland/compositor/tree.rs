@@ -289,7 +289,7 @@ impl PrivateSurfaceData {
         debug_assert!(child.as_ref().is_alive());
         debug_assert!(parent.as_ref().is_alive());
         // ensure the child is not already a parent of the parent
-        if Self::is_ancestor(child, parent) {
+        if child == parent || Self::is_ancestor(child, parent) {
             return Err(AlreadyHasRole);
         }
 
@@ -306,6 +306,10 @@ impl PrivateSurfaceData {
             {
                 return Err(AlreadyHasRole);
             }
+            // a surface can only have one subsurface object at a time
+            if child_guard.parent.is_some() {
+                return Err(AlreadyHasRole);
+            }
             child_guard.public_data.role = Some(SUBSURFACE_ROLE);
             debug_assert!(child_guard.parent.is_none());
             child_guard.parent = Some(parent.clone());
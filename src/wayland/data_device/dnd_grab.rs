@@ -1,4 +1,8 @@
-use std::{cell::RefCell, ops::Deref as _, rc::Rc};
+use std::{
+    cell::RefCell,
+    ops::Deref as _,
+    rc::{Rc, Weak},
+};
 
 use wayland_server::{
     protocol::{wl_data_device_manager::DndAction, wl_data_offer, wl_data_source, wl_pointer, wl_surface},
@@ -6,17 +10,27 @@ use wayland_server::{
 };
 
 use crate::{
+    backend::input::TouchSlot,
     utils::{Logical, Point},
     wayland::{
-        seat::{AxisFrame, PointerGrab, PointerGrabStartData, PointerInnerHandle, Seat},
-        Serial,
+        seat::{
+            AxisFrame, PointerGrab, PointerGrabStartData, PointerInnerHandle, Seat, TouchGrab,
+            TouchGrabStartData, TouchInnerHandle,
+        },
+        Serial, SERIAL_COUNTER,
     },
 };
 
-use super::{with_source_metadata, DataDeviceData, SeatData};
+use super::{negotiate_dnd_action, watch_dnd_action, with_source_metadata, DataDeviceData, SeatData};
+
+/// Drag'n'drop grab of a client, started either by a pointer or by a touch point
+pub(crate) struct DnDGrab<D> {
+    start_data: D,
+    // shared with the keyboard, to update the action when the modifiers change
+    state: Rc<RefCell<DnDState>>,
+}
 
-pub(crate) struct DnDGrab {
-    start_data: PointerGrabStartData,
+struct DnDState {
     data_source: Option<wl_data_source::WlDataSource>,
     current_focus: Option<wl_surface::WlSurface>,
     pending_offers: Vec<wl_data_offer::WlDataOffer>,
@@ -27,17 +41,16 @@ pub(crate) struct DnDGrab {
     seat: Seat,
 }
 
-impl DnDGrab {
+impl<D> DnDGrab<D> {
     pub(crate) fn new(
-        start_data: PointerGrabStartData,
+        start_data: D,
         source: Option<wl_data_source::WlDataSource>,
         origin: wl_surface::WlSurface,
         seat: Seat,
         icon: Option<wl_surface::WlSurface>,
         callback: Rc<RefCell<dyn FnMut(super::DataDeviceEvent)>>,
-    ) -> DnDGrab {
-        DnDGrab {
-            start_data,
+    ) -> DnDGrab<D> {
+        let state = Rc::new(RefCell::new(DnDState {
             data_source: source,
             current_focus: None,
             pending_offers: Vec::with_capacity(1),
@@ -45,23 +58,51 @@ impl DnDGrab {
             origin,
             icon,
             callback,
-            seat,
+            seat: seat.clone(),
+        }));
+        let update_state = Rc::downgrade(&state);
+        watch_dnd_action(&seat, Rc::new(move || DnDState::update_action(&update_state)));
+        DnDGrab { start_data, state }
+    }
+
+    fn dnd_motion(
+        &mut self,
+        location: Point<f64, Logical>,
+        focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        let changed_action = self.state.borrow_mut().motion(location, focus, serial, time);
+        if let Some(action) = changed_action {
+            let (callback, seat) = {
+                let state = self.state.borrow();
+                (state.callback.clone(), state.seat.clone())
+            };
+            (*callback.borrow_mut())(super::DataDeviceEvent::DnDActionChanged { seat, action });
         }
     }
+
+    fn drop(&mut self) {
+        let callback = {
+            let mut state = self.state.borrow_mut();
+            state.drop();
+            state.callback.clone()
+        };
+        let seat = self.state.borrow().seat.clone();
+        (*callback.borrow_mut())(super::DataDeviceEvent::DnDDropped { seat });
+    }
 }
 
-impl PointerGrab for DnDGrab {
+impl DnDState {
+    // Returns the new action, if leaving the previous target reset it
     fn motion(
         &mut self,
-        handle: &mut PointerInnerHandle<'_>,
         location: Point<f64, Logical>,
         focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
         serial: Serial,
         time: u32,
-    ) {
-        // While the grab is active, no client has pointer focus
-        handle.motion(location, None, serial, time);
-
+    ) -> Option<DndAction> {
+        let mut changed_action = None;
         let seat_data = self
             .seat
             .user_data()
@@ -81,7 +122,12 @@ impl PointerGrab for DnDGrab {
                     // disable the offers
                     self.pending_offers.clear();
                     if let Some(offer_data) = self.offer_data.take() {
-                        offer_data.borrow_mut().active = false;
+                        let mut offer_data = offer_data.borrow_mut();
+                        offer_data.active = false;
+                        // there is no target to agree on an action anymore
+                        if !offer_data.chosen_action.is_empty() {
+                            changed_action = Some(DndAction::empty());
+                        }
                     }
                 }
             }
@@ -90,7 +136,7 @@ impl PointerGrab for DnDGrab {
             // early return if the surface is no longer valid
             let client = match surface.as_ref().client() {
                 Some(c) => c,
-                None => return,
+                None => return changed_action,
             };
             let (x, y) = (location - surface_location.to_f64()).into();
             if self.current_focus.is_none() {
@@ -101,6 +147,7 @@ impl PointerGrab for DnDGrab {
                         dropped: false,
                         accepted: true,
                         chosen_action: DndAction::empty(),
+                        negotiation: None,
                     }));
                     for device in seat_data
                         .known_devices
@@ -123,6 +170,8 @@ impl PointerGrab for DnDGrab {
                                     source.clone(),
                                     offer_data.clone(),
                                     action_choice,
+                                    self.seat.clone(),
+                                    self.callback.clone(),
                                 )
                             })
                             .unwrap();
@@ -161,6 +210,128 @@ impl PointerGrab for DnDGrab {
                 }
             }
         }
+        changed_action
+    }
+
+    fn drop(&mut self) {
+        // the user dropped, proceed to the drop
+        let seat_data = self
+            .seat
+            .user_data()
+            .get::<RefCell<SeatData>>()
+            .unwrap()
+            .borrow_mut();
+        let validated = if let Some(ref data) = self.offer_data {
+            let data = data.borrow();
+            data.accepted && (!data.chosen_action.is_empty())
+        } else {
+            false
+        };
+        if let Some(ref surface) = self.current_focus {
+            if self.data_source.is_some() || self.origin.as_ref().same_client_as(surface.as_ref()) {
+                for device in &seat_data.known_devices {
+                    if device.as_ref().same_client_as(surface.as_ref()) && validated {
+                        device.drop();
+                    }
+                }
+            }
+        }
+        if let Some(ref offer_data) = self.offer_data {
+            let mut data = offer_data.borrow_mut();
+            if validated {
+                data.dropped = true;
+            } else {
+                data.active = false;
+            }
+        }
+        if let Some(ref source) = self.data_source {
+            source.dnd_drop_performed();
+            if !validated {
+                source.cancelled();
+            }
+        }
+        self.icon = None;
+        // in all cases abandon the drop
+        if let Some(ref surface) = self.current_focus {
+            for device in &seat_data.known_devices {
+                if device.as_ref().same_client_as(surface.as_ref()) {
+                    device.leave();
+                }
+            }
+        }
+    }
+
+    // Re-evaluate the chosen action, if the user changed the keyboard modifiers or the compositor
+    // forced another action since the last time, so that clients can update their cursor to reflect
+    // the new action.
+    //
+    // Returns false once the drag'n'drop is over.
+    fn update_action(state: &Weak<RefCell<DnDState>>) -> bool {
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return false,
+        };
+        // the drag'n'drop is being updated already, e.g. if the modifiers were changed
+        // from one of the callbacks, it will pick up the new action on its own
+        let mut state = match state.try_borrow_mut() {
+            Ok(state) => state,
+            Err(_) => return true,
+        };
+        if let Some(action) = state.negotiate_action() {
+            let callback = state.callback.clone();
+            let seat = state.seat.clone();
+            // release our borrow, in case the compositor inspects the drag from its callback
+            std::mem::drop(state);
+            (*callback.borrow_mut())(super::DataDeviceEvent::DnDActionChanged { seat, action });
+        }
+        true
+    }
+
+    // Returns the new action, if it changed
+    fn negotiate_action(&mut self) -> Option<DndAction> {
+        let (source, offer_data, surface) = match (&self.data_source, &self.offer_data, &self.current_focus) {
+            (Some(source), Some(offer_data), Some(surface)) => (source, offer_data, surface),
+            _ => return None,
+        };
+        let mut data = offer_data.borrow_mut();
+        // the target did not announce its actions yet
+        let (available, preferred) = data.negotiation?;
+        let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap().borrow();
+        let action_choice = seat_data
+            .known_devices
+            .iter()
+            .find(|d| d.as_ref().same_client_as(surface.as_ref()))?
+            .as_ref()
+            .user_data()
+            .get::<DataDeviceData>()
+            .unwrap()
+            .action_choice
+            .clone();
+        let action = negotiate_dnd_action(&self.seat, &*action_choice, available, preferred);
+        if action == data.chosen_action {
+            return None;
+        }
+        data.chosen_action = action;
+        for offer in &self.pending_offers {
+            offer.action(action);
+        }
+        source.action(action);
+        Some(action)
+    }
+}
+
+impl PointerGrab for DnDGrab<PointerGrabStartData> {
+    fn motion(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        location: Point<f64, Logical>,
+        focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        // While the grab is active, no client has pointer focus
+        handle.motion(location, None, serial, time);
+        self.dnd_motion(location, focus, serial, time);
     }
 
     fn button(
@@ -172,55 +343,8 @@ impl PointerGrab for DnDGrab {
         time: u32,
     ) {
         if handle.current_pressed().is_empty() {
-            // the user dropped, proceed to the drop
-            let seat_data = self
-                .seat
-                .user_data()
-                .get::<RefCell<SeatData>>()
-                .unwrap()
-                .borrow_mut();
-            let validated = if let Some(ref data) = self.offer_data {
-                let data = data.borrow();
-                data.accepted && (!data.chosen_action.is_empty())
-            } else {
-                false
-            };
-            if let Some(ref surface) = self.current_focus {
-                if self.data_source.is_some() || self.origin.as_ref().same_client_as(surface.as_ref()) {
-                    for device in &seat_data.known_devices {
-                        if device.as_ref().same_client_as(surface.as_ref()) && validated {
-                            device.drop();
-                        }
-                    }
-                }
-            }
-            if let Some(ref offer_data) = self.offer_data {
-                let mut data = offer_data.borrow_mut();
-                if validated {
-                    data.dropped = true;
-                } else {
-                    data.active = false;
-                }
-            }
-            if let Some(ref source) = self.data_source {
-                source.dnd_drop_performed();
-                if !validated {
-                    source.cancelled();
-                }
-            }
-            (*self.callback.borrow_mut())(super::DataDeviceEvent::DnDDropped {
-                seat: self.seat.clone(),
-            });
-            self.icon = None;
-            // in all cases abandon the drop
+            self.drop();
             // no more buttons are pressed, release the grab
-            if let Some(ref surface) = self.current_focus {
-                for device in &seat_data.known_devices {
-                    if device.as_ref().same_client_as(surface.as_ref()) {
-                        device.leave();
-                    }
-                }
-            }
             handle.unset_grab(serial, time);
         }
     }
@@ -235,11 +359,66 @@ impl PointerGrab for DnDGrab {
     }
 }
 
+impl TouchGrab for DnDGrab<TouchGrabStartData> {
+    fn down(
+        &mut self,
+        _handle: &mut TouchInnerHandle<'_>,
+        _serial: Serial,
+        _time: u32,
+        _surface: &wl_surface::WlSurface,
+        _surface_offset: Point<i32, Logical>,
+        _slot: TouchSlot,
+        _location: Point<f64, Logical>,
+    ) {
+        // Additional touch points are not delivered to clients during the drag
+    }
+
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, serial: Serial, time: u32, slot: TouchSlot) {
+        // touch points not delivered to clients are ignored
+        handle.up(serial, time, slot);
+        if slot == self.start_data.slot {
+            self.drop();
+            // the touch point driving the drag was lifted, release the grab
+            handle.unset_grab();
+        }
+    }
+
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        time: u32,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
+    ) {
+        handle.motion(time, slot, location);
+        if slot == self.start_data.slot {
+            self.dnd_motion(location, focus, SERIAL_COUNTER.next_serial(), time);
+        }
+    }
+
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        // abort the drag, nothing was accepted
+        if let Some(ref offer_data) = self.state.borrow().offer_data {
+            offer_data.borrow_mut().accepted = false;
+        }
+        self.drop();
+        handle.cancel();
+        handle.unset_grab();
+    }
+
+    fn start_data(&self) -> &TouchGrabStartData {
+        &self.start_data
+    }
+}
+
 struct OfferData {
     active: bool,
     dropped: bool,
     accepted: bool,
     chosen_action: DndAction,
+    // available and preferred actions, as last negotiated by the target
+    negotiation: Option<(DndAction, DndAction)>,
 }
 
 fn implement_dnd_data_offer(
@@ -247,6 +426,8 @@ fn implement_dnd_data_offer(
     source: wl_data_source::WlDataSource,
     offer_data: Rc<RefCell<OfferData>>,
     action_choice: Rc<RefCell<dyn FnMut(DndAction, DndAction) -> DndAction + 'static>>,
+    seat: Seat,
+    callback: Rc<RefCell<dyn FnMut(super::DataDeviceEvent)>>,
 ) -> wl_data_offer::WlDataOffer {
     use self::wl_data_offer::Request;
     offer.quick_assign(move |offer, req, _| {
@@ -326,7 +507,10 @@ fn implement_dnd_data_offer(
                 let source_actions = with_source_metadata(&source, |meta| meta.dnd_action)
                     .unwrap_or_else(|_| DndAction::empty());
                 let possible_actions = source_actions & dnd_actions;
-                data.chosen_action = (*action_choice.borrow_mut())(possible_actions, preferred_action);
+                data.negotiation = Some((possible_actions, preferred_action));
+                let old_action = data.chosen_action;
+                data.chosen_action =
+                    negotiate_dnd_action(&seat, &*action_choice, possible_actions, preferred_action);
                 // check that the user provided callback respects that one precise action should be chosen
                 debug_assert!(
                     [DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask]
@@ -334,6 +518,14 @@ fn implement_dnd_data_offer(
                 );
                 offer.action(data.chosen_action);
                 source.action(data.chosen_action);
+                if data.chosen_action != old_action {
+                    let action = data.chosen_action;
+                    std::mem::drop(data);
+                    (*callback.borrow_mut())(super::DataDeviceEvent::DnDActionChanged {
+                        seat: seat.clone(),
+                        action,
+                    });
+                }
             }
             _ => unreachable!(),
         }
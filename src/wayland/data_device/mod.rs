@@ -22,8 +22,9 @@
 //!   to peek into the the actions of your clients
 //! - the freestanding function [`set_data_device_selection`]
 //!   allows you to set the contents of the selection for your clients
-//! - the freestanding function [`start_dnd`] allows you to initiate a drag'n'drop event from the compositor
-//!   itself and receive interactions of clients with it via an other dedicated callback.
+//! - the freestanding functions [`start_dnd`] and [`start_touch_dnd`] allow you to initiate a drag'n'drop
+//!   event from the compositor itself and receive interactions of clients with it via an other dedicated
+//!   callback.
 //!
 //! The module defines the role `"dnd_icon"` that is assigned to surfaces used as drag'n'drop icons.
 //!
@@ -60,7 +61,7 @@ use slog::{debug, error, o};
 
 use crate::wayland::{
     compositor,
-    seat::{PointerGrabStartData, Seat},
+    seat::{ModifiersState, PointerGrabStartData, Seat, TouchGrabStartData},
     Serial,
 };
 
@@ -100,6 +101,20 @@ pub enum DataDeviceEvent {
         /// The seat on which the DnD action was finished.
         seat: Seat,
     },
+    /// The action of an ongoing drag'n'drop changed
+    ///
+    /// This happens when the target announces the actions it supports, when the user
+    /// changes the keyboard modifiers or when the compositor forces another action
+    /// with [`set_dnd_forced_action`]. The action may be empty, if source and target
+    /// could not agree on any action.
+    ///
+    /// Note that this event will only be generated for client-initiated drag'n'drop session.
+    DnDActionChanged {
+        /// The seat on which the DnD operation is happening
+        seat: Seat,
+        /// The newly chosen action
+        action: DndAction,
+    },
     /// A client requested to read the server-set selection
     SendSelection {
         /// the requested mime type
@@ -120,8 +135,13 @@ struct SeatData {
     selection: Selection,
     log: ::slog::Logger,
     current_focus: Option<Client>,
+    forced_dnd_action: Option<DndAction>,
+    dnd_action_update: Option<DnDActionUpdate>,
 }
 
+// Re-negotiates the action of the ongoing drag'n'drop of a seat, returns false once it is over
+type DnDActionUpdate = Rc<dyn Fn() -> bool>;
+
 impl SeatData {
     fn set_selection(&mut self, new_selection: Selection) {
         self.selection = new_selection;
@@ -251,6 +271,8 @@ impl SeatData {
             selection: Selection::Empty,
             log,
             current_focus: None,
+            forced_dnd_action: None,
+            dnd_action_update: None,
         }
     }
 }
@@ -329,6 +351,32 @@ pub fn set_data_device_selection(seat: &Seat, mime_types: Vec<String>) {
         }));
 }
 
+/// Force the action of drag'n'drop operations on this seat
+///
+/// While set, the given action takes precedence over the action requested through keyboard
+/// modifiers and the action chooser given to [`init_data_device`], as long as both the source
+/// and the target of the drag'n'drop support it. Use `None` to go back to the regular negotiation.
+///
+/// This can for example be used to implement compositor-specific bindings to switch between
+/// copy and move. The action of an ongoing drag'n'drop operation is updated immediately.
+pub fn set_dnd_forced_action(seat: &Seat, action: Option<DndAction>) {
+    // TODO: same question as in set_data_device_focus
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "data_device_mgr")),
+        ))
+    });
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    let update = {
+        let mut seat_data = seat_data.borrow_mut();
+        seat_data.forced_dnd_action = action;
+        seat_data.dnd_action_update.clone()
+    };
+    if let Some(update) = update {
+        update();
+    }
+}
+
 /// Start a drag'n'drop from a resource controlled by the compositor
 ///
 /// You'll receive events generated by the interaction of clients with your
@@ -363,6 +411,38 @@ pub fn start_dnd<C>(
     }
 }
 
+/// Start a drag'n'drop from a resource controlled by the compositor, driven by a touch point
+///
+/// This is the touch counterpart of [`start_dnd`], the drag'n'drop follows the touch point
+/// that started the grab given by `start_data` and ends once it is lifted.
+pub fn start_touch_dnd<C>(
+    seat: &Seat,
+    serial: Serial,
+    start_data: TouchGrabStartData,
+    metadata: SourceMetadata,
+    callback: C,
+) where
+    C: FnMut(ServerDndEvent) + 'static,
+{
+    // TODO: same question as in set_data_device_focus
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "data_device_mgr")),
+        ))
+    });
+    if let Some(touch) = seat.get_touch() {
+        touch.set_grab(
+            server_dnd_grab::ServerDnDGrab::new(
+                start_data,
+                metadata,
+                seat.clone(),
+                Rc::new(RefCell::new(callback)),
+            ),
+            serial,
+        );
+    }
+}
+
 fn implement_ddm<F, C>(
     ddm: Main<wl_data_device_manager::WlDataDeviceManager>,
     callback: Rc<RefCell<C>>,
@@ -433,40 +513,41 @@ where
         } => {
             /* TODO: handle the icon */
             let serial = Serial::from(serial);
-            if let Some(pointer) = seat.get_pointer() {
-                if pointer.has_grab(serial) {
-                    if let Some(ref icon) = icon {
-                        if compositor::give_role(icon, DND_ICON_ROLE).is_err() {
-                            dd.as_ref().post_error(
-                                wl_data_device::Error::Role as u32,
-                                "Given surface already has an other role".into(),
-                            );
-                            return;
-                        }
-                    }
-                    // The StartDrag is in response to a pointer implicit grab, all is good
-                    (*callback.borrow_mut())(DataDeviceEvent::DnDStarted {
-                        source: source.clone(),
-                        icon: icon.clone(),
-                        seat: seat.clone(),
-                    });
-                    let start_data = pointer.grab_start_data().unwrap();
-                    pointer.set_grab(
-                        dnd_grab::DnDGrab::new(
-                            start_data,
-                            source,
-                            origin,
-                            seat.clone(),
-                            icon,
-                            callback.clone(),
-                        ),
-                        serial,
-                        0,
+            let pointer = seat.get_pointer().filter(|pointer| pointer.has_grab(serial));
+            let touch = seat.get_touch().filter(|touch| touch.has_grab(serial));
+            if pointer.is_none() && touch.is_none() {
+                debug!(log, "denying drag from client without implicit grab");
+                return;
+            }
+            if let Some(ref icon) = icon {
+                if compositor::give_role(icon, DND_ICON_ROLE).is_err() {
+                    dd.as_ref().post_error(
+                        wl_data_device::Error::Role as u32,
+                        compositor::role_error_message(icon, DND_ICON_ROLE),
                     );
                     return;
                 }
             }
-            debug!(log, "denying drag from client without implicit grab");
+            // The StartDrag is in response to a pointer or touch implicit grab, all is good
+            (*callback.borrow_mut())(DataDeviceEvent::DnDStarted {
+                source: source.clone(),
+                icon: icon.clone(),
+                seat: seat.clone(),
+            });
+            if let Some(pointer) = pointer {
+                let start_data = pointer.grab_start_data().unwrap();
+                pointer.set_grab(
+                    dnd_grab::DnDGrab::new(start_data, source, origin, seat.clone(), icon, callback.clone()),
+                    serial,
+                    0,
+                );
+            } else if let Some(touch) = touch {
+                let start_data = touch.grab_start_data().unwrap();
+                touch.set_grab(
+                    dnd_grab::DnDGrab::new(start_data, source, origin, seat.clone(), icon, callback.clone()),
+                    serial,
+                );
+            }
         }
         Request::SetSelection { source, .. } => {
             if let Some(keyboard) = seat.get_keyboard() {
@@ -504,6 +585,71 @@ where
     dd.deref().clone()
 }
 
+/// Returns the DnD action requested by the user through keyboard modifiers, if any
+///
+/// This follows the common desktop conventions: holding Shift requests a move,
+/// holding Ctrl requests a copy and holding both asks the user once dropped.
+pub fn dnd_action_from_modifiers(modifiers: &ModifiersState) -> Option<DndAction> {
+    match (modifiers.ctrl, modifiers.shift) {
+        (true, true) => Some(DndAction::Ask),
+        (true, false) => Some(DndAction::Copy),
+        (false, true) => Some(DndAction::Move),
+        (false, false) => None,
+    }
+}
+
+// The DnD action requested by the compositor or by the user, if any
+fn dnd_action_override(seat: &Seat) -> Option<DndAction> {
+    let forced = seat
+        .user_data()
+        .get::<RefCell<SeatData>>()
+        .and_then(|seat_data| seat_data.borrow().forced_dnd_action);
+    forced.or_else(|| {
+        seat.get_keyboard()
+            .and_then(|keyboard| dnd_action_from_modifiers(&keyboard.modifier_state()))
+    })
+}
+
+// Makes the action of a drag'n'drop follow the keyboard modifiers and the action forced by the compositor
+fn watch_dnd_action(seat: &Seat, update: DnDActionUpdate) {
+    if let Some(seat_data) = seat.user_data().get::<RefCell<SeatData>>() {
+        seat_data.borrow_mut().dnd_action_update = Some(update.clone());
+    }
+    if let Some(keyboard) = seat.get_keyboard() {
+        keyboard.add_modifiers_hook(move |_| update());
+    }
+}
+
+// Choose the action of a DnD operation from the actions announced by the target
+// and the action currently requested by the compositor or the user, if any
+fn negotiate_dnd_action(
+    seat: &Seat,
+    action_choice: &RefCell<dyn FnMut(DndAction, DndAction) -> DndAction>,
+    available: DndAction,
+    preferred: DndAction,
+) -> DndAction {
+    choose_dnd_action(
+        &mut *action_choice.borrow_mut(),
+        available,
+        preferred,
+        dnd_action_override(seat),
+    )
+}
+
+// Choose the action of a DnD operation, giving precedence to the action requested by
+// the compositor or through keyboard modifiers over the chooser of the compositor.
+fn choose_dnd_action(
+    action_choice: &mut dyn FnMut(DndAction, DndAction) -> DndAction,
+    available: DndAction,
+    preferred: DndAction,
+    action_override: Option<DndAction>,
+) -> DndAction {
+    match action_override {
+        Some(action) if available.contains(action) => action,
+        _ => action_choice(available, preferred),
+    }
+}
+
 /// A simple action chooser for DnD negociation
 ///
 /// If the preferred action is available, it'll pick it. Otherwise, it'll pick the first
@@ -525,3 +671,86 @@ pub fn default_action_chooser(available: DndAction, preferred: DndAction) -> Dnd
         DndAction::empty()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::input::KeyState,
+        wayland::seat::{FilterResult, XkbConfig},
+    };
+    use std::cell::Cell;
+
+    #[test]
+    fn action_from_modifiers() {
+        let mut modifiers = ModifiersState::default();
+        assert_eq!(dnd_action_from_modifiers(&modifiers), None);
+        modifiers.shift = true;
+        assert_eq!(dnd_action_from_modifiers(&modifiers), Some(DndAction::Move));
+        modifiers.ctrl = true;
+        assert_eq!(dnd_action_from_modifiers(&modifiers), Some(DndAction::Ask));
+        modifiers.shift = false;
+        assert_eq!(dnd_action_from_modifiers(&modifiers), Some(DndAction::Copy));
+        // other modifiers do not matter
+        modifiers.alt = true;
+        modifiers.logo = true;
+        assert_eq!(dnd_action_from_modifiers(&modifiers), Some(DndAction::Copy));
+    }
+
+    #[test]
+    fn action_override() {
+        let mut chooser = default_action_chooser;
+        let available = DndAction::Copy | DndAction::Move;
+
+        assert_eq!(
+            choose_dnd_action(&mut chooser, available, DndAction::Copy, None),
+            DndAction::Copy
+        );
+        assert_eq!(
+            choose_dnd_action(&mut chooser, available, DndAction::Copy, Some(DndAction::Move)),
+            DndAction::Move
+        );
+        // actions not supported by both sides fall back to the chooser
+        assert_eq!(
+            choose_dnd_action(&mut chooser, available, DndAction::Copy, Some(DndAction::Ask)),
+            DndAction::Copy
+        );
+    }
+
+    #[test]
+    fn forced_action_updates_drag() {
+        let mut display = Display::new();
+        let (mut seat, _global) = Seat::new(&mut display, "seat-0".into(), None);
+        let keyboard = seat
+            .add_keyboard(XkbConfig::default(), 200, 25, |_, _| {})
+            .unwrap();
+        set_dnd_forced_action(&seat, None);
+
+        let updates = Rc::new(Cell::new(0));
+        let counter = updates.clone();
+        watch_dnd_action(
+            &seat,
+            Rc::new(move || {
+                counter.set(counter.get() + 1);
+                true
+            }),
+        );
+        assert_eq!(dnd_action_override(&seat), None);
+
+        // the ongoing drag is updated immediately
+        set_dnd_forced_action(&seat, Some(DndAction::Copy));
+        assert_eq!(updates.get(), 1);
+        assert_eq!(dnd_action_override(&seat), Some(DndAction::Copy));
+
+        // evdev keycode of left shift, the forced action takes precedence over it
+        keyboard.input::<(), _>(42, KeyState::Pressed, Serial::from(0), 0, |_, _| {
+            FilterResult::Forward
+        });
+        assert_eq!(updates.get(), 2);
+        assert_eq!(dnd_action_override(&seat), Some(DndAction::Copy));
+
+        set_dnd_forced_action(&seat, None);
+        assert_eq!(updates.get(), 3);
+        assert_eq!(dnd_action_override(&seat), Some(DndAction::Move));
+    }
+}
@@ -1,4 +1,9 @@
-use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc};
+use std::{
+    cell::RefCell,
+    ops::Deref as _,
+    os::unix::io::RawFd,
+    rc::{Rc, Weak},
+};
 
 use wayland_server::{
     protocol::{wl_data_device_manager::DndAction, wl_data_offer, wl_pointer, wl_surface},
@@ -6,14 +11,18 @@ use wayland_server::{
 };
 
 use crate::{
+    backend::input::TouchSlot,
     utils::{Logical, Point},
     wayland::{
-        seat::{AxisFrame, PointerGrab, PointerGrabStartData, PointerInnerHandle, Seat},
-        Serial,
+        seat::{
+            AxisFrame, PointerGrab, PointerGrabStartData, PointerInnerHandle, Seat, TouchGrab,
+            TouchGrabStartData, TouchInnerHandle,
+        },
+        Serial, SERIAL_COUNTER,
     },
 };
 
-use super::{DataDeviceData, SeatData};
+use super::{negotiate_dnd_action, watch_dnd_action, DataDeviceData, SeatData};
 
 /// Event generated by the interactions of clients with a server initiated drag'n'drop
 #[derive(Debug)]
@@ -41,8 +50,14 @@ pub enum ServerDndEvent {
     Finished,
 }
 
-pub(crate) struct ServerDnDGrab<C: 'static> {
-    start_data: PointerGrabStartData,
+/// Drag'n'drop grab of the compositor, started either by a pointer or by a touch point
+pub(crate) struct ServerDnDGrab<D, C: 'static> {
+    start_data: D,
+    // shared with the keyboard, to update the action when the modifiers change
+    state: Rc<RefCell<ServerDnDState<C>>>,
+}
+
+struct ServerDnDState<C: 'static> {
     metadata: super::SourceMetadata,
     current_focus: Option<wl_surface::WlSurface>,
     pending_offers: Vec<wl_data_offer::WlDataOffer>,
@@ -51,32 +66,49 @@ pub(crate) struct ServerDnDGrab<C: 'static> {
     callback: Rc<RefCell<C>>,
 }
 
-impl<C: 'static> ServerDnDGrab<C> {
+impl<D, C> ServerDnDGrab<D, C>
+where
+    C: FnMut(ServerDndEvent) + 'static,
+{
     pub(crate) fn new(
-        start_data: PointerGrabStartData,
+        start_data: D,
         metadata: super::SourceMetadata,
         seat: Seat,
         callback: Rc<RefCell<C>>,
-    ) -> ServerDnDGrab<C> {
-        ServerDnDGrab {
-            start_data,
+    ) -> ServerDnDGrab<D, C> {
+        let state = Rc::new(RefCell::new(ServerDnDState {
             metadata,
             current_focus: None,
             pending_offers: Vec::with_capacity(1),
             offer_data: None,
-            seat,
+            seat: seat.clone(),
             callback,
-        }
+        }));
+        let update_state = Rc::downgrade(&state);
+        watch_dnd_action(
+            &seat,
+            Rc::new(move || ServerDnDState::update_action(&update_state)),
+        );
+        ServerDnDGrab { start_data, state }
+    }
+
+    fn dnd_motion(
+        &mut self,
+        location: Point<f64, Logical>,
+        focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        self.state.borrow_mut().motion(location, focus, serial, time);
     }
 }
 
-impl<C> PointerGrab for ServerDnDGrab<C>
+impl<C> ServerDnDState<C>
 where
     C: FnMut(ServerDndEvent) + 'static,
 {
     fn motion(
         &mut self,
-        _handle: &mut PointerInnerHandle<'_>,
         location: Point<f64, Logical>,
         focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
         serial: Serial,
@@ -117,6 +149,7 @@ where
                     dropped: false,
                     accepted: true,
                     chosen_action: DndAction::empty(),
+                    negotiation: None,
                 }));
                 for device in seat_data
                     .known_devices
@@ -140,6 +173,7 @@ where
                                 offer_data.clone(),
                                 self.callback.clone(),
                                 action_choice,
+                                self.seat.clone(),
                             )
                         })
                         .unwrap();
@@ -165,6 +199,119 @@ where
         }
     }
 
+    fn drop(&mut self) {
+        // the user dropped, proceed to the drop
+        let seat_data = self
+            .seat
+            .user_data()
+            .get::<RefCell<SeatData>>()
+            .unwrap()
+            .borrow_mut();
+        let validated = if let Some(ref data) = self.offer_data {
+            let data = data.borrow();
+            data.accepted && (!data.chosen_action.is_empty())
+        } else {
+            false
+        };
+        if let Some(ref surface) = self.current_focus {
+            for device in &seat_data.known_devices {
+                if device.as_ref().same_client_as(surface.as_ref()) && validated {
+                    device.drop();
+                }
+            }
+        }
+        if let Some(ref offer_data) = self.offer_data {
+            let mut data = offer_data.borrow_mut();
+            if validated {
+                data.dropped = true;
+            } else {
+                data.active = false;
+            }
+        }
+        let mut callback = self.callback.borrow_mut();
+        (*callback)(ServerDndEvent::Dropped);
+        if !validated {
+            (*callback)(ServerDndEvent::Cancelled);
+        }
+        // in all cases abandon the drop
+        if let Some(ref surface) = self.current_focus {
+            for device in &seat_data.known_devices {
+                if device.as_ref().same_client_as(surface.as_ref()) {
+                    device.leave();
+                }
+            }
+        }
+    }
+
+    // Re-evaluate the chosen action, if the user changed the keyboard modifiers or the compositor
+    // forced another action since the last time.
+    //
+    // Returns false once the drag'n'drop is over.
+    fn update_action(state: &Weak<RefCell<ServerDnDState<C>>>) -> bool {
+        let state = match state.upgrade() {
+            Some(state) => state,
+            None => return false,
+        };
+        // the drag'n'drop is being updated already, it will pick up the new action on its own
+        let mut state = match state.try_borrow_mut() {
+            Ok(state) => state,
+            Err(_) => return true,
+        };
+        if let Some(action) = state.negotiate_action() {
+            let callback = state.callback.clone();
+            std::mem::drop(state);
+            (*callback.borrow_mut())(ServerDndEvent::Action(action));
+        }
+        true
+    }
+
+    // Returns the new action, if it changed
+    fn negotiate_action(&mut self) -> Option<DndAction> {
+        let (offer_data, surface) = match (&self.offer_data, &self.current_focus) {
+            (Some(offer_data), Some(surface)) => (offer_data, surface),
+            _ => return None,
+        };
+        let mut data = offer_data.borrow_mut();
+        // the target did not announce its actions yet
+        let (available, preferred) = data.negotiation?;
+        let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap().borrow();
+        let action_choice = seat_data
+            .known_devices
+            .iter()
+            .find(|d| d.as_ref().same_client_as(surface.as_ref()))?
+            .as_ref()
+            .user_data()
+            .get::<DataDeviceData>()
+            .unwrap()
+            .action_choice
+            .clone();
+        let action = negotiate_dnd_action(&self.seat, &*action_choice, available, preferred);
+        if action == data.chosen_action {
+            return None;
+        }
+        data.chosen_action = action;
+        for offer in &self.pending_offers {
+            offer.action(action);
+        }
+        Some(action)
+    }
+}
+
+impl<C> PointerGrab for ServerDnDGrab<PointerGrabStartData, C>
+where
+    C: FnMut(ServerDndEvent) + 'static,
+{
+    fn motion(
+        &mut self,
+        _handle: &mut PointerInnerHandle<'_>,
+        location: Point<f64, Logical>,
+        focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        self.dnd_motion(location, focus, serial, time);
+    }
+
     fn button(
         &mut self,
         handle: &mut PointerInnerHandle<'_>,
@@ -174,48 +321,8 @@ where
         time: u32,
     ) {
         if handle.current_pressed().is_empty() {
-            // the user dropped, proceed to the drop
-            let seat_data = self
-                .seat
-                .user_data()
-                .get::<RefCell<SeatData>>()
-                .unwrap()
-                .borrow_mut();
-            let validated = if let Some(ref data) = self.offer_data {
-                let data = data.borrow();
-                data.accepted && (!data.chosen_action.is_empty())
-            } else {
-                false
-            };
-            if let Some(ref surface) = self.current_focus {
-                for device in &seat_data.known_devices {
-                    if device.as_ref().same_client_as(surface.as_ref()) && validated {
-                        device.drop();
-                    }
-                }
-            }
-            if let Some(ref offer_data) = self.offer_data {
-                let mut data = offer_data.borrow_mut();
-                if validated {
-                    data.dropped = true;
-                } else {
-                    data.active = false;
-                }
-            }
-            let mut callback = self.callback.borrow_mut();
-            (*callback)(ServerDndEvent::Dropped);
-            if !validated {
-                (*callback)(ServerDndEvent::Cancelled);
-            }
-            // in all cases abandon the drop
+            self.state.borrow_mut().drop();
             // no more buttons are pressed, release the grab
-            if let Some(ref surface) = self.current_focus {
-                for device in &seat_data.known_devices {
-                    if device.as_ref().same_client_as(surface.as_ref()) {
-                        device.leave();
-                    }
-                }
-            }
             handle.unset_grab(serial, time);
         }
     }
@@ -230,11 +337,70 @@ where
     }
 }
 
+impl<C> TouchGrab for ServerDnDGrab<TouchGrabStartData, C>
+where
+    C: FnMut(ServerDndEvent) + 'static,
+{
+    fn down(
+        &mut self,
+        _handle: &mut TouchInnerHandle<'_>,
+        _serial: Serial,
+        _time: u32,
+        _surface: &wl_surface::WlSurface,
+        _surface_offset: Point<i32, Logical>,
+        _slot: TouchSlot,
+        _location: Point<f64, Logical>,
+    ) {
+        // Additional touch points are not delivered to clients during the drag
+    }
+
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, serial: Serial, time: u32, slot: TouchSlot) {
+        // touch points not delivered to clients are ignored
+        handle.up(serial, time, slot);
+        if slot == self.start_data.slot {
+            self.state.borrow_mut().drop();
+            // the touch point driving the drag was lifted, release the grab
+            handle.unset_grab();
+        }
+    }
+
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        time: u32,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
+    ) {
+        handle.motion(time, slot, location);
+        if slot == self.start_data.slot {
+            self.dnd_motion(location, focus, SERIAL_COUNTER.next_serial(), time);
+        }
+    }
+
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        // abort the drag, nothing was accepted
+        let mut state = self.state.borrow_mut();
+        if let Some(ref offer_data) = state.offer_data {
+            offer_data.borrow_mut().accepted = false;
+        }
+        state.drop();
+        handle.cancel();
+        handle.unset_grab();
+    }
+
+    fn start_data(&self) -> &TouchGrabStartData {
+        &self.start_data
+    }
+}
+
 struct OfferData {
     active: bool,
     dropped: bool,
     accepted: bool,
     chosen_action: DndAction,
+    // available and preferred actions, as last negotiated by the target
+    negotiation: Option<(DndAction, DndAction)>,
 }
 
 fn implement_dnd_data_offer<C>(
@@ -243,6 +409,7 @@ fn implement_dnd_data_offer<C>(
     offer_data: Rc<RefCell<OfferData>>,
     callback: Rc<RefCell<C>>,
     action_choice: Rc<RefCell<dyn FnMut(DndAction, DndAction) -> DndAction + 'static>>,
+    seat: Seat,
 ) -> wl_data_offer::WlDataOffer
 where
     C: FnMut(ServerDndEvent) + 'static,
@@ -314,7 +481,9 @@ where
                     return;
                 }
                 let possible_actions = metadata.dnd_action & dnd_actions;
-                data.chosen_action = (*action_choice.borrow_mut())(possible_actions, preferred_action);
+                data.negotiation = Some((possible_actions, preferred_action));
+                data.chosen_action =
+                    negotiate_dnd_action(&seat, &*action_choice, possible_actions, preferred_action);
                 // check that the user provided callback respects that one precise action should be chosen
                 debug_assert!(
                     [DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask]
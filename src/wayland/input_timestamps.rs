@@ -0,0 +1,180 @@
+//! Utilities for handling the `zwp_input_timestamps_manager_v1` protocol
+//!
+//! This protocol lets clients request high-resolution timestamps for the events of their
+//! `wl_keyboard`, `wl_pointer` and `wl_touch` objects, which latency-sensitive clients like
+//! games or benchmarks use instead of the millisecond timestamps of the core protocol.
+//!
+//! Once the global is initialized, the timestamps are sent by the handles of the
+//! [`seat`](crate::wayland::seat) module before every key, button, axis, motion and touch event.
+//! As the seat handles only get millisecond timestamps, you should call [`set_event_time`] with
+//! the precise time of an input event before forwarding it, for example using
+//! [`Event::time_usec`](crate::backend::input::Event::time_usec), which is microsecond accurate
+//! for libinput events. Otherwise the timestamps are only as precise as the ones of the core
+//! protocol.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::input_timestamps::{init_input_timestamps_manager, set_event_time};
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_input_timestamps_manager(
+//!     &mut display,
+//!     None, // put a logger if you want
+//! );
+//!
+//! // later, when processing an input event
+//! # let time_usec = 0;
+//! set_event_time(time_usec);
+//! // forward the event to the seat handles
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    ops::Deref,
+};
+
+use slog::{debug, o};
+use wayland_protocols::unstable::input_timestamps::v1::server::{
+    zwp_input_timestamps_manager_v1::{self, ZwpInputTimestampsManagerV1},
+    zwp_input_timestamps_v1::{self, ZwpInputTimestampsV1},
+};
+use wayland_server::{
+    protocol::{wl_keyboard::WlKeyboard, wl_pointer::WlPointer, wl_touch::WlTouch},
+    Display, Filter, Global, Interface, Main, Resource,
+};
+
+thread_local!(static EVENT_TIME: Cell<Option<u64>> = const { Cell::new(None) });
+
+/// Sets the precise time in microseconds of the input event that is forwarded next
+///
+/// The time is used for all following events whose millisecond timestamp matches it,
+/// until it is replaced by the time of the next input event.
+pub fn set_event_time(time_usec: u64) {
+    EVENT_TIME.with(|time| time.set(Some(time_usec)));
+}
+
+/// Returns the time in microseconds of an event sent with the given millisecond timestamp
+fn precise_time(time: u32) -> u64 {
+    match EVENT_TIME.with(Cell::get) {
+        // the millisecond timestamps of the backends are truncated from the precise ones
+        Some(time_usec) if (time_usec / 1000) as u32 == time => time_usec,
+        _ => time as u64 * 1000,
+    }
+}
+
+/// Timestamp objects created for an input device object
+#[derive(Debug, Default)]
+struct InputTimestamps {
+    timestamps: RefCell<Vec<ZwpInputTimestampsV1>>,
+}
+
+/// Sends the precise time of an event with the given millisecond timestamp to all timestamp
+/// objects of the given resource, to be called right before sending the event itself
+pub(crate) fn send_timestamp<I>(resource: &I, time: u32)
+where
+    I: Interface + From<Resource<I>> + AsRef<Resource<I>>,
+{
+    let data = match resource.as_ref().user_data().get::<InputTimestamps>() {
+        Some(data) => data,
+        None => return,
+    };
+    let timestamps = data.timestamps.borrow();
+    if timestamps.is_empty() {
+        return;
+    }
+
+    let time_usec = precise_time(time);
+    let secs = time_usec / 1_000_000;
+    let nsecs = (time_usec % 1_000_000) as u32 * 1000;
+    for timestamp in timestamps.iter() {
+        timestamp.timestamp((secs >> 32) as u32, secs as u32, nsecs);
+    }
+}
+
+fn register_timestamps<I>(id: Main<ZwpInputTimestampsV1>, resource: &I)
+where
+    I: Interface + From<Resource<I>> + AsRef<Resource<I>>,
+{
+    id.quick_assign(|_, request, _| match request {
+        zwp_input_timestamps_v1::Request::Destroy => {
+            // All is handled by destructor.
+        }
+        _ => unreachable!(),
+    });
+
+    let resource = resource.as_ref();
+    if !resource.is_alive() {
+        return;
+    }
+    resource.user_data().set(InputTimestamps::default);
+    if let Some(data) = resource.user_data().get::<InputTimestamps>() {
+        data.timestamps.borrow_mut().push(id.deref().clone());
+        let resource = resource.clone();
+        id.assign_destructor(Filter::new(move |id: ZwpInputTimestampsV1, _, _| {
+            if let Some(data) = resource.user_data().get::<InputTimestamps>() {
+                data.timestamps
+                    .borrow_mut()
+                    .retain(|timestamp| !timestamp.as_ref().equals(id.as_ref()));
+            }
+        }));
+    }
+}
+
+/// Creates a new `zwp_input_timestamps_manager_v1` global
+pub fn init_input_timestamps_manager<L>(
+    display: &mut Display,
+    logger: L,
+) -> Global<ZwpInputTimestampsManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "input_timestamps"));
+
+    display.create_global(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpInputTimestampsManagerV1>, _), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_, request, _| match request {
+                    zwp_input_timestamps_manager_v1::Request::GetKeyboardTimestamps { id, keyboard } => {
+                        debug!(log, "Keyboard timestamps requested");
+                        register_timestamps::<WlKeyboard>(id, &keyboard);
+                    }
+                    zwp_input_timestamps_manager_v1::Request::GetPointerTimestamps { id, pointer } => {
+                        debug!(log, "Pointer timestamps requested");
+                        register_timestamps::<WlPointer>(id, &pointer);
+                    }
+                    zwp_input_timestamps_manager_v1::Request::GetTouchTimestamps { id, touch } => {
+                        debug!(log, "Touch timestamps requested");
+                        register_timestamps::<WlTouch>(id, &touch);
+                    }
+                    zwp_input_timestamps_manager_v1::Request::Destroy => {
+                        // All is handled by destructor.
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precise_time_matches_event() {
+        set_event_time(1_234_567_891);
+        assert_eq!(precise_time(1_234_567), 1_234_567_891);
+        // events of other sources fall back to millisecond precision
+        assert_eq!(precise_time(1_234_600), 1_234_600_000);
+    }
+
+    #[test]
+    fn precise_time_wraps_with_milliseconds() {
+        // the millisecond timestamps wrap around after ~49 days
+        let time_usec = (u32::MAX as u64 + 5) * 1000 + 42;
+        set_event_time(time_usec);
+        assert_eq!(precise_time(4), time_usec);
+    }
+}
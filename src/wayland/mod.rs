@@ -58,6 +58,7 @@ pub mod compositor;
 pub mod data_device;
 pub mod dmabuf;
 pub mod explicit_synchronization;
+pub mod input_timestamps;
 pub mod output;
 pub mod seat;
 pub mod shell;
@@ -65,6 +66,8 @@ pub mod shm;
 pub mod tablet_manager;
 pub mod xdg_activation;
 pub mod xdg_foreign;
+#[cfg(feature = "xwayland")]
+pub mod xwayland_keyboard_grab;
 
 /// A global [`SerialCounter`] for use in your compositor.
 ///
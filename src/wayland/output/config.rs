@@ -0,0 +1,447 @@
+//! Declarative output configuration
+//!
+//! [`OutputConfig`] parses a simple line based configuration format describing how outputs
+//! should be set up, so e.g. kiosk deployments can be configured without writing code:
+//!
+//! ```text
+//! # every line configures all outputs matching its criterion
+//! output * scale 1
+//! output eDP-1 disable
+//! output HDMI-A-* mode 1920x1080@60 position 0,0 transform 90 vrr on
+//! output "Dell Inc. - DELL U2720Q*" mode 3840x2160 position 1080,0 scale 1.5
+//! ```
+//!
+//! The criterion after `output` is matched against the [name](Output::name) and the
+//! [description](Output::description) of an output, `*` matches any number of characters.
+//! Values containing spaces have to be quoted. Lines are applied in order, so later lines
+//! override the settings of earlier ones. The following settings are available:
+//!
+//! - `mode <width>x<height>[@<refresh in Hz>]`
+//! - `position <x>,<y>` in logical coordinates
+//! - `scale <factor>`
+//! - `transform normal|90|180|270|flipped|flipped-90|flipped-180|flipped-270`
+//! - `vrr on|off`
+//! - `enable` or `disable`
+//!
+//! Look up the [`OutputSettings`] of every output, when it is connected, with
+//! [`OutputConfig::settings`] or [`OutputConfig::settings_for`] and use [`OutputSettings::apply`] to
+//! change the state of the [`Output`]. Enabling outputs, setting the mode of the hardware and variable
+//! refresh rate are up to the backend.
+
+use std::{fs, io, path::Path, str::FromStr};
+
+use wayland_server::protocol::wl_output::Transform;
+
+use super::{Mode, Output, Scale};
+use crate::utils::{Logical, Physical, Point, Size};
+
+/// Error parsing an [`OutputConfig`]
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid output configuration on line {line}: {kind}")]
+pub struct ParseError {
+    /// Line of the error, starting at 1
+    pub line: usize,
+    /// What went wrong
+    pub kind: ParseErrorKind,
+}
+
+/// Kind of a [`ParseError`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseErrorKind {
+    /// The line does not start with `output`
+    #[error("Expected `output`, found `{0}`")]
+    ExpectedOutput(String),
+    /// The line has no criterion
+    #[error("Missing output name")]
+    MissingCriterion,
+    /// A quoted value is missing the closing quote
+    #[error("Unterminated quote")]
+    UnterminatedQuote,
+    /// The setting is not known
+    #[error("Unknown setting `{0}`")]
+    UnknownSetting(String),
+    /// The setting is missing its value
+    #[error("Missing value for `{0}`")]
+    MissingValue(&'static str),
+    /// The value of the setting could not be parsed
+    #[error("Invalid value `{value}` for `{setting}`")]
+    InvalidValue {
+        /// The setting
+        setting: &'static str,
+        /// The invalid value
+        value: String,
+    },
+}
+
+/// Error loading an [`OutputConfig`] from a file
+#[derive(Debug, thiserror::Error)]
+pub enum LoadError {
+    /// The file could not be read
+    #[error("Failed to read output configuration")]
+    Io(#[from] io::Error),
+    /// The file could not be parsed
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+}
+
+/// Mode requested by an [`OutputSettings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeSetting {
+    /// Size of the mode in pixels
+    pub size: Size<i32, Physical>,
+    /// Refresh rate in millihertz, if requested
+    pub refresh: Option<i32>,
+}
+
+impl ModeSetting {
+    /// Selects the mode of the given size with the refresh rate closest to the requested one,
+    /// or with the highest refresh rate, if none was requested
+    pub fn select(&self, modes: &[Mode]) -> Option<Mode> {
+        let candidates = modes.iter().filter(|mode| mode.size == self.size);
+        match self.refresh {
+            Some(refresh) => candidates.min_by_key(|mode| (mode.refresh - refresh).abs()),
+            None => candidates.max_by_key(|mode| mode.refresh),
+        }
+        .copied()
+    }
+}
+
+/// Settings of a single output, see [`OutputConfig::settings`]
+///
+/// Settings not configured for the output are `None`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OutputSettings {
+    /// Whether the output should be used
+    pub enabled: Option<bool>,
+    /// The mode of the output
+    pub mode: Option<ModeSetting>,
+    /// The location of the output
+    pub position: Option<Point<i32, Logical>>,
+    /// The scale of the output
+    pub scale: Option<f64>,
+    /// The transform of the output
+    pub transform: Option<Transform>,
+    /// Whether variable refresh rate should be used
+    pub vrr: Option<bool>,
+}
+
+impl OutputSettings {
+    // later settings take precedence
+    fn merge(&mut self, other: &OutputSettings) {
+        self.enabled = other.enabled.or(self.enabled);
+        self.mode = other.mode.or(self.mode);
+        self.position = other.position.or(self.position);
+        self.scale = other.scale.or(self.scale);
+        self.transform = other.transform.or(self.transform);
+        self.vrr = other.vrr.or(self.vrr);
+    }
+
+    /// Returns the [`Scale`] to be set on the output, if any
+    pub fn output_scale(&self) -> Option<Scale> {
+        self.scale.map(|scale| {
+            if scale.fract() == 0.0 {
+                Scale::Integer(scale as i32)
+            } else {
+                Scale::Fractional(scale)
+            }
+        })
+    }
+
+    /// Changes the mode, transform, scale and position of the output to the configured ones
+    ///
+    /// The mode is selected from the [modes](Output::modes) of the output and returned, if the
+    /// configured one was found, so the backend can switch to it.
+    pub fn apply(&self, output: &Output) -> Option<Mode> {
+        let mode = self.mode.and_then(|mode| mode.select(&output.modes()));
+        output.change_current_state(mode, self.transform, self.output_scale(), self.position);
+        mode
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    criterion: String,
+    settings: OutputSettings,
+}
+
+/// Parsed output configuration, see the [module-level docs](self)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OutputConfig {
+    entries: Vec<Entry>,
+}
+
+/// Matches `text` against a pattern, where `*` matches any number of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            text.starts_with(prefix)
+                && (0..=text.len() - prefix.len())
+                    .filter(|offset| text.is_char_boundary(prefix.len() + offset))
+                    .any(|offset| glob_match(rest, &text[prefix.len() + offset..]))
+        }
+    }
+}
+
+/// Splits a line into words, respecting double quotes and stopping at comments
+fn tokenize(line: &str) -> Result<Vec<String>, ParseErrorKind> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            break;
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err(ParseErrorKind::UnterminatedQuote),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_value<T, F>(setting: &'static str, value: &str, parse: F) -> Result<T, ParseErrorKind>
+where
+    F: FnOnce(&str) -> Option<T>,
+{
+    parse(value).ok_or_else(|| ParseErrorKind::InvalidValue {
+        setting,
+        value: value.into(),
+    })
+}
+
+fn parse_mode(value: &str) -> Option<ModeSetting> {
+    let (size, refresh) = match value.split_once('@') {
+        Some((size, refresh)) => (size, Some(refresh.trim_end_matches("Hz"))),
+        None => (value, None),
+    };
+    let (w, h) = size.split_once('x')?;
+    let refresh = match refresh {
+        Some(refresh) => {
+            let hz = refresh.parse::<f64>().ok().filter(|hz| *hz > 0.0)?;
+            Some((hz * 1000.0).round() as i32)
+        }
+        None => None,
+    };
+    Some(ModeSetting {
+        size: (w.parse().ok()?, h.parse().ok()?).into(),
+        refresh,
+    })
+}
+
+fn parse_transform(value: &str) -> Option<Transform> {
+    Some(match value {
+        "normal" => Transform::Normal,
+        "90" => Transform::_90,
+        "180" => Transform::_180,
+        "270" => Transform::_270,
+        "flipped" => Transform::Flipped,
+        "flipped-90" => Transform::Flipped90,
+        "flipped-180" => Transform::Flipped180,
+        "flipped-270" => Transform::Flipped270,
+        _ => return None,
+    })
+}
+
+fn parse_line(tokens: &[String]) -> Result<Entry, ParseErrorKind> {
+    let mut tokens = tokens.iter().map(String::as_str);
+    match tokens.next() {
+        Some("output") => {}
+        Some(other) => return Err(ParseErrorKind::ExpectedOutput(other.into())),
+        None => unreachable!(),
+    }
+    let criterion = tokens.next().ok_or(ParseErrorKind::MissingCriterion)?.to_string();
+
+    let mut settings = OutputSettings::default();
+    while let Some(setting) = tokens.next() {
+        let mut value = |setting: &'static str| tokens.next().ok_or(ParseErrorKind::MissingValue(setting));
+        match setting {
+            "enable" => settings.enabled = Some(true),
+            "disable" => settings.enabled = Some(false),
+            "mode" => settings.mode = Some(parse_value("mode", value("mode")?, parse_mode)?),
+            "position" => {
+                settings.position = Some(parse_value("position", value("position")?, |value| {
+                    let (x, y) = value.split_once(',')?;
+                    Some((x.parse().ok()?, y.parse().ok()?).into())
+                })?)
+            }
+            "scale" => {
+                settings.scale = Some(parse_value("scale", value("scale")?, |value| {
+                    value.parse::<f64>().ok().filter(|scale| *scale > 0.0)
+                })?)
+            }
+            "transform" => {
+                settings.transform = Some(parse_value("transform", value("transform")?, parse_transform)?)
+            }
+            "vrr" => {
+                settings.vrr = Some(parse_value("vrr", value("vrr")?, |value| match value {
+                    "on" => Some(true),
+                    "off" => Some(false),
+                    _ => None,
+                })?)
+            }
+            other => return Err(ParseErrorKind::UnknownSetting(other.into())),
+        }
+    }
+
+    Ok(Entry { criterion, settings })
+}
+
+impl FromStr for OutputConfig {
+    type Err = ParseError;
+
+    fn from_str(config: &str) -> Result<OutputConfig, ParseError> {
+        let mut entries = Vec::new();
+        for (idx, line) in config.lines().enumerate() {
+            let error = |kind| ParseError { line: idx + 1, kind };
+            let tokens = tokenize(line).map_err(error)?;
+            if !tokens.is_empty() {
+                entries.push(parse_line(&tokens).map_err(error)?);
+            }
+        }
+        Ok(OutputConfig { entries })
+    }
+}
+
+impl OutputConfig {
+    /// Reads and parses the configuration file at the given path
+    pub fn load(path: impl AsRef<Path>) -> Result<OutputConfig, LoadError> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    /// Returns whether the configuration has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the settings of an output with the given name and description
+    ///
+    /// This allows looking up the settings before the [`Output`] is created, e.g. to skip disabled
+    /// connectors or to select the mode of the hardware.
+    pub fn settings(&self, name: &str, description: &str) -> OutputSettings {
+        self.entries
+            .iter()
+            .filter(|entry| glob_match(&entry.criterion, name) || glob_match(&entry.criterion, description))
+            .fold(OutputSettings::default(), |mut settings, entry| {
+                settings.merge(&entry.settings);
+                settings
+            })
+    }
+
+    /// Returns the settings of an output
+    pub fn settings_for(&self, output: &Output) -> OutputSettings {
+        self.settings(&output.name(), &output.description())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("eDP-1", "eDP-1"));
+        assert!(!glob_match("eDP-1", "eDP-10"));
+        assert!(glob_match("HDMI-A-*", "HDMI-A-2"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("Dell*U2720Q*", "Dell Inc. - DELL U2720Q - DP-1"));
+        assert!(!glob_match("*HDMI", "HDMI-A-1"));
+    }
+
+    #[test]
+    fn later_lines_override_earlier_ones() {
+        let config: OutputConfig = r#"
+            # defaults
+            output * scale 1 vrr off
+            output HDMI-A-* mode 1920x1080@59.94 position 1920,0 transform flipped-90
+            output "Smithay - Generic DRM*" scale 1.5 # by description
+            output eDP-1 disable
+        "#
+        .parse()
+        .unwrap();
+
+        let settings = config.settings("HDMI-A-1", "Smithay - Generic DRM - HDMI-A-1");
+        assert_eq!(
+            settings,
+            OutputSettings {
+                enabled: None,
+                mode: Some(ModeSetting {
+                    size: (1920, 1080).into(),
+                    refresh: Some(59940),
+                }),
+                position: Some((1920, 0).into()),
+                scale: Some(1.5),
+                transform: Some(Transform::Flipped90),
+                vrr: Some(false),
+            }
+        );
+        assert_eq!(settings.output_scale(), Some(Scale::Fractional(1.5)));
+
+        let settings = config.settings("eDP-1", "Laptop - Panel - eDP-1");
+        assert_eq!(settings.enabled, Some(false));
+        assert_eq!(settings.output_scale(), Some(Scale::Integer(1)));
+        assert_eq!(settings.mode, None);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |config: &str| config.parse::<OutputConfig>().unwrap_err();
+        let err = error("output * scale 1\n\nmonitor eDP-1");
+        assert_eq!(err.line, 3);
+        assert_eq!(err.kind, ParseErrorKind::ExpectedOutput("monitor".into()));
+        assert_eq!(error("output").kind, ParseErrorKind::MissingCriterion);
+        assert_eq!(error("output \"eDP-1").kind, ParseErrorKind::UnterminatedQuote);
+        assert_eq!(error("output * mode").kind, ParseErrorKind::MissingValue("mode"));
+        assert_eq!(
+            error("output * brightness 1").kind,
+            ParseErrorKind::UnknownSetting("brightness".into())
+        );
+        assert_eq!(
+            error("output * transform 45").kind,
+            ParseErrorKind::InvalidValue {
+                setting: "transform",
+                value: "45".into()
+            }
+        );
+    }
+
+    #[test]
+    fn mode_selection() {
+        let mode = |w, h, refresh| Mode {
+            size: (w, h).into(),
+            refresh,
+        };
+        let modes = [
+            mode(1920, 1080, 60000),
+            mode(1920, 1080, 144000),
+            mode(1920, 1080, 59940),
+            mode(1280, 720, 60000),
+        ];
+        let setting = |value| parse_mode(value).unwrap();
+        assert_eq!(setting("1920x1080").select(&modes), Some(modes[1]));
+        assert_eq!(setting("1920x1080@59.94").select(&modes), Some(modes[2]));
+        assert_eq!(setting("1920x1080@60Hz").select(&modes), Some(modes[0]));
+        assert_eq!(setting("1280x720@75").select(&modes), Some(modes[3]));
+        assert_eq!(setting("800x600").select(&modes), None);
+        assert!(parse_mode("1920x").is_none());
+        assert!(parse_mode("1920x1080@0").is_none());
+    }
+}
@@ -60,6 +60,7 @@
 //! output.add_mode(Mode { size: (1024, 768).into(), refresh: 60000 });
 //! ```
 
+pub mod config;
 pub mod xdg;
 
 use std::{
@@ -99,6 +100,36 @@ pub struct Mode {
     pub refresh: i32,
 }
 
+#[cfg(feature = "backend_drm")]
+impl From<drm::control::Mode> for Mode {
+    /// Converts a mode of a drm connector, computing the exact refresh rate from its timings
+    fn from(mode: drm::control::Mode) -> Mode {
+        use drm::control::ModeFlags;
+
+        let (w, h) = mode.size();
+        let (_, _, htotal) = mode.hsync();
+        let (_, _, vtotal) = mode.vsync();
+        let mut pixels = htotal as u64 * vtotal as u64;
+        let mut fields = 1;
+        // interlaced modes scan out every other line per field, doublescan modes every line twice
+        if mode.flags().contains(ModeFlags::INTERLACE) {
+            fields *= 2;
+        }
+        if mode.flags().contains(ModeFlags::DBLSCAN) {
+            pixels *= 2;
+        }
+        // the pixel clock is in kHz
+        let refresh = match pixels {
+            0 => mode.vrefresh() as u64 * 1000,
+            pixels => (mode.clock() as u64 * 1_000_000 * fields + pixels / 2) / pixels,
+        };
+        Mode {
+            size: (w as i32, h as i32).into(),
+            refresh: refresh as i32,
+        }
+    }
+}
+
 /// The physical properties of an output
 #[derive(Debug, Clone)]
 pub struct PhysicalProperties {
@@ -113,7 +144,7 @@ pub struct PhysicalProperties {
 }
 
 /// Describes the scale advertised to clients.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Scale {
     /// Integer based scaling
     Integer(i32),
@@ -154,6 +185,41 @@ impl Scale {
     }
 }
 
+/// Changes made to an [`Output`] by [`Output::change_current_state`]
+///
+/// Only the properties that actually changed are `Some(_)`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct OutputChange {
+    /// The new current mode
+    pub mode: Option<Mode>,
+    /// The new transform
+    pub transform: Option<Transform>,
+    /// The new scale
+    pub scale: Option<Scale>,
+    /// The new location
+    pub location: Option<Point<i32, Logical>>,
+}
+
+impl OutputChange {
+    /// Returns `true` if the logical size of the output changed,
+    /// meaning windows and layer surfaces on it might need to be resized.
+    pub fn size_changed(&self) -> bool {
+        self.mode.is_some() || self.scale.is_some() || self.transform.is_some()
+    }
+}
+
+type ChangeListener = Box<dyn FnMut(&Output, &OutputChange) + Send>;
+
+struct ChangeListeners(Vec<ChangeListener>);
+
+impl std::fmt::Debug for ChangeListeners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChangeListeners")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Inner {
     name: String,
@@ -166,6 +232,7 @@ pub(crate) struct Inner {
     modes: Vec<Mode>,
     current_mode: Option<Mode>,
     preferred_mode: Option<Mode>,
+    listeners: ChangeListeners,
 
     pub(crate) xdg_output: Option<XdgOutput>,
     pub(crate) log: ::slog::Logger,
@@ -253,6 +320,7 @@ impl Output {
                 modes: Vec::new(),
                 current_mode: None,
                 preferred_mode: None,
+                listeners: ChangeListeners(Vec::new()),
                 xdg_output: None,
                 log,
             }),
@@ -392,6 +460,9 @@ impl Output {
     /// internal list.
     ///
     /// By default, transform status is `Normal`, and scale is `1`.
+    ///
+    /// Clients are notified of the new state right away and listeners added via
+    /// [`Output::add_change_listener`] are called, if anything actually changed.
     pub fn change_current_state(
         &self,
         new_mode: Option<Mode>,
@@ -400,6 +471,13 @@ impl Output {
         new_location: Option<Point<i32, Logical>>,
     ) {
         let mut inner = self.inner.0.lock().unwrap();
+        let change = OutputChange {
+            mode: new_mode.filter(|mode| inner.current_mode != Some(*mode)),
+            transform: new_transform.filter(|transform| inner.transform != *transform),
+            scale: new_scale.filter(|scale| inner.scale != *scale),
+            location: new_location.filter(|location| inner.location != *location),
+        };
+
         if let Some(mode) = new_mode {
             if inner.modes.iter().all(|&m| m != mode) {
                 inner.modes.push(mode);
@@ -442,6 +520,31 @@ impl Output {
                 output.done();
             }
         }
+
+        if change == OutputChange::default() {
+            return;
+        }
+        // listeners may query the output, so they can not be called with the lock held
+        let mut listeners = std::mem::take(&mut inner.listeners.0);
+        std::mem::drop(inner);
+        for listener in &mut listeners {
+            listener(self, &change);
+        }
+        let mut inner = self.inner.0.lock().unwrap();
+        listeners.append(&mut inner.listeners.0);
+        inner.listeners.0 = listeners;
+    }
+
+    /// Adds a listener, that is called whenever [`Output::change_current_state`] changes the state of this output
+    ///
+    /// Use this to e.g. resize maximized windows, when the mode or scale of the output changes.
+    /// Re-arranging layer surfaces and redrawing the output is handled by the `desktop::Space`
+    /// the output is mapped to, if any.
+    pub fn add_change_listener<F>(&self, listener: F)
+    where
+        F: FnMut(&Output, &OutputChange) + Send + 'static,
+    {
+        self.inner.0.lock().unwrap().listeners.0.push(Box::new(listener));
     }
 
     /// Check is given [`wl_output`](WlOutput) instance is managed by this [`Output`].
@@ -509,3 +612,33 @@ impl Hash for Output {
         Arc::as_ptr(&self.inner).hash(state);
     }
 }
+
+#[cfg(all(test, feature = "backend_drm"))]
+mod tests {
+    use super::*;
+
+    fn drm_mode(hdisplay: u16, vdisplay: u16, htotal: u16, vtotal: u16, clock: u32, flags: u32) -> Mode {
+        Mode::from(drm::control::Mode::from(drm_ffi::drm_mode_modeinfo {
+            clock,
+            hdisplay,
+            htotal,
+            vdisplay,
+            vtotal,
+            flags,
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn refresh_of_drm_modes() {
+        // CEA 1080p60 and 1080i60
+        assert_eq!(drm_mode(1920, 1080, 2200, 1125, 148_500, 0).refresh, 60_000);
+        assert_eq!(
+            drm_mode(1920, 1080, 2200, 1125, 74_250, drm_ffi::DRM_MODE_FLAG_INTERLACE).refresh,
+            60_000
+        );
+        // 320x200 doublescanned to 400 lines at 70Hz
+        let mode = drm_mode(320, 200, 400, 225, 12_600, drm_ffi::DRM_MODE_FLAG_DBLSCAN);
+        assert_eq!((mode.size, mode.refresh), ((320, 200).into(), 70_000));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::backend::input::KeyState;
-use crate::wayland::Serial;
+use crate::wayland::{input_timestamps::send_timestamp, Serial, SERIAL_COUNTER};
 use slog::{debug, info, o, trace, warn};
 use std::{
     cell::RefCell,
@@ -92,17 +92,25 @@ enum GrabStatus {
     Borrowed,
 }
 
+// called on changes of the modifier state, until it returns false
+type ModifiersHook = Box<dyn FnMut(&ModifiersState) -> bool>;
+
 struct KbdInternal {
     known_kbds: Vec<WlKeyboard>,
     focus: Option<(WlSurface, Serial)>,
     pending_focus: Option<WlSurface>,
+    deferred_focus: Option<(Option<WlSurface>, Serial)>,
     pressed_keys: Vec<u32>,
+    // keys the current focus knows to be pressed
+    forwarded_keys: Vec<u32>,
     mods_state: ModifiersState,
     keymap: xkb::Keymap,
+    keymap_string: String,
     state: xkb::State,
     repeat_rate: i32,
     repeat_delay: i32,
     focus_hook: Box<dyn FnMut(Option<&WlSurface>)>,
+    modifiers_hooks: Vec<ModifiersHook>,
     grab: GrabStatus,
 }
 
@@ -112,13 +120,16 @@ impl fmt::Debug for KbdInternal {
         f.debug_struct("KbdInternal")
             .field("known_kbds", &self.known_kbds)
             .field("focus", &self.focus)
+            .field("deferred_focus", &self.deferred_focus)
             .field("pressed_keys", &self.pressed_keys)
+            .field("forwarded_keys", &self.forwarded_keys)
             .field("mods_state", &self.mods_state)
             .field("keymap", &self.keymap.get_raw_ptr())
             .field("state", &self.state.get_raw_ptr())
             .field("repeat_rate", &self.repeat_rate)
             .field("repeat_delay", &self.repeat_delay)
             .field("focus_hook", &"...")
+            .field("modifiers_hooks", &self.modifiers_hooks.len())
             .finish()
     }
 }
@@ -140,33 +151,36 @@ impl KbdInternal {
         //
         // FIXME: This is an issue with the xkbcommon-rs crate that does not reflect this
         // non-threadsafety properly.
-        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-        let keymap = xkb::Keymap::new_from_names(
-            &context,
-            &xkb_config.rules,
-            &xkb_config.model,
-            &xkb_config.layout,
-            &xkb_config.variant,
-            xkb_config.options,
-            xkb::KEYMAP_COMPILE_NO_FLAGS,
-        )
-        .ok_or(())?;
+        let keymap = keymap_from_config(xkb_config)?;
+        let keymap_string = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
         let state = xkb::State::new(&keymap);
         Ok(KbdInternal {
             known_kbds: Vec::new(),
             focus: None,
             pending_focus: None,
+            deferred_focus: None,
             pressed_keys: Vec::new(),
+            forwarded_keys: Vec::new(),
             mods_state: ModifiersState::default(),
             keymap,
+            keymap_string,
             state,
             repeat_rate,
             repeat_delay,
             focus_hook,
+            modifiers_hooks: Vec::new(),
             grab: GrabStatus::None,
         })
     }
 
+    // replace the keymap, resetting the modifier state
+    fn change_keymap(&mut self, keymap: xkb::Keymap) {
+        self.keymap_string = keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+        self.state = xkb::State::new(&keymap);
+        self.keymap = keymap;
+        self.mods_state.update_with(&self.state);
+    }
+
     // return true if modifier state has changed
     fn key_input(&mut self, keycode: u32, state: KeyState) -> bool {
         // track pressed keys as xkbcommon does not seem to expose it :(
@@ -256,6 +270,41 @@ impl KbdInternal {
     }
 }
 
+fn keymap_from_config(xkb_config: XkbConfig<'_>) -> Result<xkb::Keymap, ()> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_names(
+        &context,
+        &xkb_config.rules,
+        &xkb_config.model,
+        &xkb_config.layout,
+        &xkb_config.variant,
+        xkb_config.options,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or(())
+}
+
+fn keymap_from_string(keymap: String) -> Result<xkb::Keymap, ()> {
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_string(
+        &context,
+        keymap,
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+    .ok_or(())
+}
+
+fn send_keymap(kbd: &WlKeyboard, keymap: &str) -> io::Result<()> {
+    // prepare a tempfile with the keymap, to send it to the client
+    let mut f = tempfile()?;
+    f.write_all(keymap.as_bytes())?;
+    f.flush()?;
+    f.rewind()?;
+    kbd.keymap(KeymapFormat::XkbV1, f.as_raw_fd(), keymap.len() as u32);
+    Ok(())
+}
+
 /// Errors that can be encountered when creating a keyboard handler
 #[derive(Debug, Error)]
 pub enum Error {
@@ -291,12 +340,9 @@ where
 
     info!(log, "Loaded Keymap"; "name" => internal.keymap.layouts().next());
 
-    let keymap = internal.keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
-
     Ok(KeyboardHandle {
         arc: Rc::new(KbdRc {
             internal: RefCell::new(internal),
-            keymap,
             logger: log,
         }),
     })
@@ -305,7 +351,6 @@ where
 #[derive(Debug)]
 struct KbdRc {
     internal: RefCell<KbdInternal>,
-    keymap: String,
     logger: ::slog::Logger,
 }
 
@@ -480,6 +525,9 @@ impl KeyboardHandle {
         trace!(self.arc.logger, "Handling keystroke"; "keycode" => keycode, "state" => format_args!("{:?}", state));
         let mut guard = self.arc.internal.borrow_mut();
         let mods_changed = guard.key_input(keycode, state);
+        // the focus must get the release of keys it knows to be pressed, even if the filter
+        // intercepts it, otherwise they would be stuck for the client
+        let release_pending = state == KeyState::Released && guard.forwarded_keys.contains(&keycode);
         let handle = KeysymHandle {
             // Offset the keycode by 8, as the evdev XKB rules reflect X's
             // broken keycode system, which starts at 8.
@@ -492,10 +540,18 @@ impl KeyboardHandle {
             "mods_state" => format_args!("{:?}", guard.mods_state), "sym" => xkb::keysym_get_name(handle.modified_sym())
         );
 
-        if let FilterResult::Intercept(val) = filter(&guard.mods_state, handle) {
+        let intercepted = match filter(&guard.mods_state, handle) {
+            FilterResult::Intercept(val) => Some(val),
+            FilterResult::Forward => None,
+        };
+        if intercepted.is_some() && !release_pending {
             // the filter returned false, we do not forward to client
             trace!(self.arc.logger, "Input was intercepted by filter");
-            return Some(val);
+            std::mem::drop(guard);
+            if mods_changed {
+                self.run_modifiers_hooks();
+            }
+            return intercepted;
         }
 
         // forward to client if no keybinding is triggered
@@ -519,8 +575,24 @@ impl KeyboardHandle {
         } else {
             trace!(self.arc.logger, "No client currently focused");
         }
+        if guard.forwarded_keys.is_empty() {
+            if let Some((focus, serial)) = guard.deferred_focus.take() {
+                trace!(self.arc.logger, "Applying deferred focus change");
+                guard.pending_focus = focus.clone();
+                guard.with_grab(
+                    move |mut handle, grab| {
+                        grab.set_focus(&mut handle, focus.as_ref(), serial);
+                    },
+                    self.arc.logger.clone(),
+                );
+            }
+        }
+        std::mem::drop(guard);
+        if mods_changed {
+            self.run_modifiers_hooks();
+        }
 
-        None
+        intercepted
     }
 
     /// Set the current focus of this keyboard
@@ -531,6 +603,7 @@ impl KeyboardHandle {
     /// a [`wl_keyboard::Event::Enter`](wayland_server::protocol::wl_keyboard::Event::Enter) event will be sent.
     pub fn set_focus(&self, focus: Option<&WlSurface>, serial: Serial) {
         let mut guard = self.arc.internal.borrow_mut();
+        guard.deferred_focus = None;
         guard.pending_focus = focus.cloned();
         guard.with_grab(
             move |mut handle, grab| {
@@ -540,6 +613,31 @@ impl KeyboardHandle {
         );
     }
 
+    /// Set the focus of this keyboard once the current focus got the release of all its pressed keys
+    ///
+    /// Moving the focus while keys are held, for example from a key binding, makes the new focus
+    /// receive the releases of keys it never saw pressed, while the previous focus only gets a
+    /// [`wl_keyboard::Event::Leave`](wayland_server::protocol::wl_keyboard::Event::Leave) event.
+    /// With this method, the focus change is delayed until the last key known to the current focus
+    /// was released, this release being still delivered to the current focus.
+    ///
+    /// The focus is changed immediately if no such key is pressed. A call to
+    /// [`KeyboardHandle::set_focus`] cancels a pending focus change.
+    pub fn set_focus_deferred(&self, focus: Option<&WlSurface>, serial: Serial) {
+        let pending_release = !self.arc.internal.borrow().forwarded_keys.is_empty();
+        if pending_release {
+            trace!(self.arc.logger, "Deferring focus change until keys are released");
+            self.arc.internal.borrow_mut().deferred_focus = Some((focus.cloned(), serial));
+        } else {
+            self.set_focus(focus, serial);
+        }
+    }
+
+    /// Check if a focus change requested by [`KeyboardHandle::set_focus_deferred`] is pending
+    pub fn has_deferred_focus(&self) -> bool {
+        self.arc.internal.borrow().deferred_focus.is_some()
+    }
+
     /// Check if given client currently has keyboard focus
     pub fn has_focus(&self, client: &Client) -> bool {
         self.arc
@@ -557,6 +655,11 @@ impl KeyboardHandle {
         self.arc.internal.borrow_mut().focus.is_some()
     }
 
+    /// Returns the surface currently having the keyboard focus
+    pub fn current_focus(&self) -> Option<WlSurface> {
+        self.arc.internal.borrow().focus.as_ref().map(|f| f.0.clone())
+    }
+
     /// Register a new keyboard to this handler
     ///
     /// The keymap will automatically be sent to it
@@ -565,20 +668,8 @@ impl KeyboardHandle {
     pub(crate) fn new_kbd(&self, kbd: WlKeyboard) {
         trace!(self.arc.logger, "Sending keymap to client");
 
-        // prepare a tempfile with the keymap, to send it to the client
-        let ret = tempfile().and_then(|mut f| {
-            f.write_all(self.arc.keymap.as_bytes())?;
-            f.flush()?;
-            f.rewind()?;
-            kbd.keymap(
-                KeymapFormat::XkbV1,
-                f.as_raw_fd(),
-                self.arc.keymap.as_bytes().len() as u32,
-            );
-            Ok(())
-        });
-
-        if let Err(e) = ret {
+        let mut guard = self.arc.internal.borrow_mut();
+        if let Err(e) = send_keymap(&kbd, &guard.keymap_string) {
             warn!(self.arc.logger,
                 "Failed write keymap to client in a tempfile";
                 "err" => format!("{:?}", e)
@@ -586,7 +677,6 @@ impl KeyboardHandle {
             return;
         };
 
-        let mut guard = self.arc.internal.borrow_mut();
         if kbd.as_ref().version() >= 4 {
             kbd.repeat_info(guard.repeat_rate, guard.repeat_delay);
         }
@@ -602,6 +692,129 @@ impl KeyboardHandle {
         guard.known_kbds.push(kbd);
     }
 
+    /// Change the keymap of this keyboard using a new set of RMLVO rules
+    ///
+    /// The new keymap is sent to all clients and the modifier state is reset.
+    /// This is typically used to follow layout changes of the host when running nested,
+    /// see for example [`X11Handle::xkb_rule_names`](crate::backend::x11::X11Handle::xkb_rule_names).
+    pub fn set_xkb_config(&self, xkb_config: XkbConfig<'_>) -> Result<(), Error> {
+        info!(self.arc.logger, "Changing keymap";
+            "rules" => xkb_config.rules, "model" => xkb_config.model, "layout" => xkb_config.layout,
+            "variant" => xkb_config.variant, "options" => &xkb_config.options
+        );
+        let keymap = keymap_from_config(xkb_config).map_err(|_| {
+            debug!(self.arc.logger, "Loading keymap failed");
+            Error::BadKeymap
+        })?;
+        self.change_keymap(keymap);
+        Ok(())
+    }
+
+    /// Change the keymap of this keyboard using a keymap in the xkb text format
+    ///
+    /// The new keymap is sent to all clients and the modifier state is reset.
+    /// This is typically used to forward the keymap of a host compositor, when running nested.
+    pub fn set_keymap_from_string(&self, keymap: String) -> Result<(), Error> {
+        info!(self.arc.logger, "Changing keymap from string");
+        let keymap = keymap_from_string(keymap).map_err(|_| {
+            debug!(self.arc.logger, "Loading keymap failed");
+            Error::BadKeymap
+        })?;
+        self.change_keymap(keymap);
+        Ok(())
+    }
+
+    fn change_keymap(&self, keymap: xkb::Keymap) {
+        let mut guard = self.arc.internal.borrow_mut();
+        guard.change_keymap(keymap);
+        info!(self.arc.logger, "Loaded Keymap"; "name" => guard.keymap.layouts().next());
+
+        for kbd in &guard.known_kbds {
+            // a single broken client must not keep the others on the old keymap
+            if let Err(e) = send_keymap(kbd, &guard.keymap_string) {
+                warn!(self.arc.logger,
+                    "Failed write keymap to client in a tempfile";
+                    "err" => format!("{:?}", e)
+                );
+            }
+        }
+        self.send_modifiers(&guard);
+        std::mem::drop(guard);
+        self.run_modifiers_hooks();
+    }
+
+    // notify the focused clients about the current modifier state
+    fn send_modifiers(&self, guard: &KbdInternal) {
+        if guard.focus.is_some() {
+            let serial = SERIAL_COUNTER.next_serial();
+            let (dep, la, lo, gr) = guard.serialize_modifiers();
+            guard.with_focused_kbds(|kbd, _| {
+                kbd.modifiers(serial.into(), dep, la, lo, gr);
+            });
+        }
+    }
+
+    /// Overwrite the modifier state of this keyboard with serialized xkb masks
+    ///
+    /// This is intended to synchronize the state with an external source, e.g. the
+    /// host when running nested, which might have changed locked modifiers (like caps lock)
+    /// while the compositor was not focused. Focused clients are notified if the state changed.
+    pub fn set_modifier_masks(&self, depressed: u32, latched: u32, locked: u32, layout: u32) {
+        let mut guard = self.arc.internal.borrow_mut();
+        let changed = guard.state.update_mask(depressed, latched, locked, 0, 0, layout);
+        if changed == 0 {
+            return;
+        }
+        let KbdInternal {
+            ref mut mods_state,
+            ref state,
+            ..
+        } = *guard;
+        mods_state.update_with(state);
+        trace!(self.arc.logger, "Modifier state overwritten"; "mods_state" => format_args!("{:?}", guard.mods_state));
+        self.send_modifiers(&guard);
+        std::mem::drop(guard);
+        self.run_modifiers_hooks();
+    }
+
+    /// Register a hook called whenever the modifier state of this keyboard changes
+    ///
+    /// The hook is removed once it returns `false`. It is called without any borrow of
+    /// the keyboard, so it may query the keyboard, e.g. with [`KeyboardHandle::modifier_state`].
+    pub(crate) fn add_modifiers_hook<F>(&self, hook: F)
+    where
+        F: FnMut(&ModifiersState) -> bool + 'static,
+    {
+        self.arc
+            .internal
+            .borrow_mut()
+            .modifiers_hooks
+            .push(Box::new(hook));
+    }
+
+    fn run_modifiers_hooks(&self) {
+        let (hooks, mods_state) = {
+            let mut guard = self.arc.internal.borrow_mut();
+            (std::mem::take(&mut guard.modifiers_hooks), guard.mods_state)
+        };
+        if hooks.is_empty() {
+            return;
+        }
+        let mut hooks = hooks
+            .into_iter()
+            .filter_map(|mut hook| if hook(&mods_state) { Some(hook) } else { None })
+            .collect::<Vec<_>>();
+        // keep the hooks registered while running the others
+        let mut guard = self.arc.internal.borrow_mut();
+        hooks.append(&mut guard.modifiers_hooks);
+        guard.modifiers_hooks = hooks;
+    }
+
+    /// Returns the current state of the keyboard modifiers
+    pub fn modifier_state(&self) -> ModifiersState {
+        self.arc.internal.borrow().mods_state
+    }
+
     /// Change the repeat info configured for this keyboard
     pub fn change_repeat_info(&self, rate: i32, delay: i32) {
         let mut guard = self.arc.internal.borrow_mut();
@@ -679,9 +892,30 @@ impl<'a> KeyboardInnerHandle<'a> {
         serial: Serial,
         time: u32,
     ) {
+        if self.inner.focus.is_none() {
+            return;
+        }
+        match key_state {
+            WlKeyState::Pressed => self.inner.forwarded_keys.push(keycode),
+            WlKeyState::Released => {
+                let known = self.inner.forwarded_keys.iter().position(|&k| k == keycode);
+                match known {
+                    Some(idx) => {
+                        self.inner.forwarded_keys.remove(idx);
+                    }
+                    None => {
+                        // the focus did not see this key being pressed
+                        trace!(self.logger, "Ignoring release of a key unknown to the focus");
+                        return;
+                    }
+                }
+            }
+            _ => {}
+        }
         self.inner.with_focused_kbds(|kbd, _| {
             // key event must be sent before modifers event for libxkbcommon
             // to process them correctly
+            send_timestamp(kbd, time);
             kbd.key(serial.into(), time, keycode, key_state);
             if let Some((dep, la, lo, gr)) = modifiers {
                 kbd.modifiers(serial.into(), dep, la, lo, gr);
@@ -709,8 +943,13 @@ impl<'a> KeyboardInnerHandle<'a> {
                 kbd.leave(serial.into(), s);
             });
 
-            // set new focus
+            // set new focus, which receives the pressed keys with the enter event
             self.inner.focus = focus.cloned().map(|f| (f, serial));
+            self.inner.forwarded_keys = if self.inner.focus.is_some() {
+                self.inner.pressed_keys.clone()
+            } else {
+                Vec::new()
+            };
             let (dep, la, lo, gr) = self.inner.serialize_modifiers();
             let keys = self.inner.serialize_pressed_keys();
             self.inner.with_focused_kbds(|kbd, surface| {
@@ -761,3 +1000,156 @@ impl KeyboardGrab for DefaultGrab {
         unreachable!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::{io::IntoRawFd, net::UnixStream};
+
+    fn keyboard() -> KeyboardHandle {
+        let log = ::slog::Logger::root(::slog::Discard, ::slog::o!());
+        create_keyboard_handler(XkbConfig::default(), 200, 25, &log, |_| {}).unwrap()
+    }
+
+    fn mod_mask(keyboard: &KeyboardHandle, name: &str) -> u32 {
+        1 << keyboard.arc.internal.borrow().keymap.mod_get_index(name)
+    }
+
+    #[test]
+    fn set_modifier_masks() {
+        let keyboard = keyboard();
+        let shift = mod_mask(&keyboard, xkb::MOD_NAME_SHIFT);
+        let caps = mod_mask(&keyboard, xkb::MOD_NAME_CAPS);
+
+        keyboard.set_modifier_masks(shift, 0, caps, 0);
+        let mods = keyboard.modifier_state();
+        assert!(mods.shift);
+        assert!(mods.caps_lock);
+        assert!(!mods.ctrl);
+
+        keyboard.set_modifier_masks(0, 0, 0, 0);
+        assert_eq!(keyboard.modifier_state(), ModifiersState::default());
+    }
+
+    #[test]
+    fn change_keymap_resets_modifiers() {
+        let keyboard = keyboard();
+        let caps = mod_mask(&keyboard, xkb::MOD_NAME_CAPS);
+        keyboard.set_modifier_masks(0, 0, caps, 0);
+
+        keyboard
+            .set_xkb_config(XkbConfig {
+                layout: "de",
+                ..XkbConfig::default()
+            })
+            .unwrap();
+        assert_eq!(keyboard.modifier_state(), ModifiersState::default());
+    }
+
+    #[test]
+    fn keymap_from_string() {
+        let keyboard = keyboard();
+        let keymap = keyboard.arc.internal.borrow().keymap_string.clone();
+        keyboard.set_keymap_from_string(keymap.clone()).unwrap();
+        assert_eq!(keyboard.arc.internal.borrow().keymap_string, keymap);
+    }
+
+    #[test]
+    fn bad_keymap_is_rejected() {
+        let keyboard = keyboard();
+        let keymap = keyboard.arc.internal.borrow().keymap_string.clone();
+        assert!(matches!(
+            keyboard.set_keymap_from_string("not a keymap".into()),
+            Err(Error::BadKeymap)
+        ));
+        // the previous keymap is kept
+        assert_eq!(keyboard.arc.internal.borrow().keymap_string, keymap);
+    }
+
+    #[test]
+    fn modifiers_hooks() {
+        let keyboard = keyboard();
+        let shift = mod_mask(&keyboard, xkb::MOD_NAME_SHIFT);
+        let calls = Rc::new(RefCell::new(Vec::new()));
+
+        let hook_calls = calls.clone();
+        let hook_keyboard = keyboard.clone();
+        keyboard.add_modifiers_hook(move |mods| {
+            // the keyboard is not borrowed while running the hooks
+            assert_eq!(*mods, hook_keyboard.modifier_state());
+            hook_calls.borrow_mut().push(mods.shift);
+            // unregister once shift was released
+            mods.shift
+        });
+
+        keyboard.set_modifier_masks(shift, 0, 0, 0);
+        // unchanged state, the hook is not called
+        keyboard.set_modifier_masks(shift, 0, 0, 0);
+        keyboard.set_modifier_masks(0, 0, 0, 0);
+        keyboard.set_modifier_masks(shift, 0, 0, 0);
+        assert_eq!(*calls.borrow(), vec![true, false]);
+    }
+
+    #[test]
+    fn modifiers_hooks_on_input() {
+        let keyboard = keyboard();
+        let calls = Rc::new(RefCell::new(0));
+        let hook_calls = calls.clone();
+        keyboard.add_modifiers_hook(move |_| {
+            *hook_calls.borrow_mut() += 1;
+            true
+        });
+
+        // evdev keycodes of left shift and a
+        let serial = Serial::from(0);
+        keyboard.input::<(), _>(42, KeyState::Pressed, serial, 0, |_, _| FilterResult::Forward);
+        keyboard.input::<(), _>(30, KeyState::Pressed, serial, 0, |_, _| FilterResult::Forward);
+        keyboard.input(42, KeyState::Released, serial, 0, |_, _| {
+            FilterResult::Intercept(())
+        });
+        assert_eq!(*calls.borrow(), 2);
+    }
+
+    #[test]
+    fn deferred_focus_waits_for_release() {
+        let mut display = wayland_server::Display::new();
+        let (server, _client) = UnixStream::pair().unwrap();
+        let client = unsafe { display.create_client(server.into_raw_fd(), &mut ()) };
+        let first = (*client.create_resource::<WlSurface>(4).unwrap()).clone();
+        let second = (*client.create_resource::<WlSurface>(4).unwrap()).clone();
+
+        let keyboard = keyboard();
+        let serial = Serial::from(0);
+        keyboard.set_focus(Some(&first), serial);
+        // the current focus gets the press of a
+        keyboard.input::<(), _>(30, KeyState::Pressed, serial, 0, |_, _| FilterResult::Forward);
+
+        keyboard.set_focus_deferred(Some(&second), serial);
+        assert!(keyboard.has_deferred_focus());
+        assert_eq!(keyboard.current_focus(), Some(first.clone()));
+        // other keys pressed in the meantime do not complete the focus change
+        keyboard.input::<(), _>(42, KeyState::Pressed, serial, 0, |_, _| FilterResult::Forward);
+        keyboard.input::<(), _>(42, KeyState::Released, serial, 0, |_, _| FilterResult::Forward);
+        assert_eq!(keyboard.current_focus(), Some(first));
+
+        // an intercepted release is still delivered, which completes the focus change
+        let intercepted = keyboard.input(30, KeyState::Released, serial, 0, |_, _| {
+            FilterResult::Intercept(true)
+        });
+        assert_eq!(intercepted, Some(true));
+        assert!(!keyboard.has_deferred_focus());
+        assert_eq!(keyboard.current_focus(), Some(second));
+    }
+
+    #[test]
+    fn deferred_focus_without_pressed_keys() {
+        let keyboard = keyboard();
+        let serial = Serial::from(0);
+        // keys intercepted by the filter are unknown to the focus
+        keyboard.input(30, KeyState::Pressed, serial, 0, |_, _| {
+            FilterResult::Intercept(())
+        });
+        keyboard.set_focus_deferred(None, serial);
+        assert!(!keyboard.has_deferred_focus());
+    }
+}
@@ -49,7 +49,7 @@ pub use self::{
         AxisFrame, CursorImageAttributes, CursorImageStatus, GrabStartData as PointerGrabStartData,
         PointerGrab, PointerHandle, PointerInnerHandle,
     },
-    touch::TouchHandle,
+    touch::{GrabStartData as TouchGrabStartData, TouchGrab, TouchHandle, TouchInnerHandle},
 };
 
 use wayland_server::{
@@ -10,7 +10,7 @@ use wayland_server::{
 
 use crate::{
     utils::{Logical, Point},
-    wayland::{compositor, Serial},
+    wayland::{compositor, input_timestamps::send_timestamp, Serial},
 };
 
 static CURSOR_IMAGE_ROLE: &str = "cursor_image";
@@ -152,6 +152,7 @@ impl PointerInternal {
             } else {
                 // we were on top of a surface and remained on it
                 self.with_focused_pointers(|pointer, _| {
+                    send_timestamp(pointer, time);
                     pointer.motion(time, x, y);
                     if pointer.as_ref().version() >= 5 {
                         pointer.frame();
@@ -321,6 +322,17 @@ impl PointerHandle {
     pub fn current_location(&self) -> Point<f64, Logical> {
         self.inner.borrow().location
     }
+
+    /// Access the current focus of this pointer and its location in the global space
+    pub fn current_focus(&self) -> Option<(WlSurface, Point<i32, Logical>)> {
+        self.inner.borrow().focus.clone()
+    }
+}
+
+impl PartialEq for PointerHandle {
+    fn eq(&self, other: &PointerHandle) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
 }
 
 /// Data about the event that started the grab.
@@ -458,6 +470,7 @@ impl<'a> PointerInnerHandle<'a> {
     /// objects matching with the currently focused surface.
     pub fn button(&self, button: u32, state: ButtonState, serial: Serial, time: u32) {
         self.inner.with_focused_pointers(|pointer, _| {
+            send_timestamp(pointer, time);
             pointer.button(serial.into(), time, button, state);
             if pointer.as_ref().version() >= 5 {
                 pointer.frame();
@@ -473,9 +486,11 @@ impl<'a> PointerInnerHandle<'a> {
         self.inner.with_focused_pointers(|pointer, _| {
             // axis
             if details.axis.0 != 0.0 {
+                send_timestamp(pointer, details.time);
                 pointer.axis(details.time, Axis::HorizontalScroll, details.axis.0);
             }
             if details.axis.1 != 0.0 {
+                send_timestamp(pointer, details.time);
                 pointer.axis(details.time, Axis::VerticalScroll, details.axis.1);
             }
             if pointer.as_ref().version() >= 5 {
@@ -492,9 +507,11 @@ impl<'a> PointerInnerHandle<'a> {
                 }
                 // stop
                 if details.stop.0 {
+                    send_timestamp(pointer, details.time);
                     pointer.axis_stop(details.time, Axis::HorizontalScroll);
                 }
                 if details.stop.1 {
+                    send_timestamp(pointer, details.time);
                     pointer.axis_stop(details.time, Axis::VerticalScroll);
                 }
                 // frame
@@ -637,7 +654,7 @@ pub(crate) fn implement_pointer(pointer: Main<WlPointer>, handle: Option<&Pointe
                                     {
                                         pointer.as_ref().post_error(
                                             wl_pointer::Error::Role as u32,
-                                            "Given wl_surface has another role.".into(),
+                                            compositor::role_error_message(&surface, CURSOR_IMAGE_ROLE),
                                         );
                                         return;
                                     }
@@ -1,5 +1,6 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -8,15 +9,42 @@ use wayland_server::{Filter, Main};
 
 use crate::backend::input::TouchSlot;
 use crate::utils::{Logical, Point};
+use crate::wayland::input_timestamps::send_timestamp;
 use crate::wayland::seat::wl_surface::WlSurface;
 use crate::wayland::Serial;
 
+enum GrabStatus {
+    None,
+    Active(Serial, Box<dyn TouchGrab>),
+    Borrowed,
+}
+
+impl Default for GrabStatus {
+    fn default() -> Self {
+        GrabStatus::None
+    }
+}
+
+// TouchGrab is a trait, so we have to impl Debug manually
+impl fmt::Debug for GrabStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrabStatus::None => f.debug_tuple("GrabStatus::None").finish(),
+            GrabStatus::Active(serial, _) => f.debug_tuple("GrabStatus::Active").field(&serial).finish(),
+            GrabStatus::Borrowed => f.debug_tuple("GrabStatus::Borrowed").finish(),
+        }
+    }
+}
+
 /// An handle to a touch handler.
 ///
 /// It can be cloned and all clones manipulate the same internal state.
 ///
 /// This handle gives you access to an interface to send touch events to your
 /// clients.
+///
+/// When sending events using this handle, they will be intercepted by a touch
+/// grab if any is active. See the [`TouchGrab`] trait for details.
 #[derive(Debug, Clone)]
 pub struct TouchHandle {
     inner: Rc<RefCell<TouchInternal>>,
@@ -36,6 +64,42 @@ impl TouchHandle {
         self.inner.borrow_mut().known_handles.push(touch);
     }
 
+    /// Change the current grab on this touch device to the provided grab
+    ///
+    /// Overwrites any current grab.
+    pub fn set_grab<G: TouchGrab + 'static>(&self, grab: G, serial: Serial) {
+        self.inner.borrow_mut().set_grab(serial, grab);
+    }
+
+    /// Remove any current grab on this touch device, resetting it to the default behavior
+    pub fn unset_grab(&self) {
+        self.inner.borrow_mut().unset_grab();
+    }
+
+    /// Check if this touch device is currently grabbed with this serial
+    pub fn has_grab(&self, serial: Serial) -> bool {
+        let guard = self.inner.borrow();
+        match guard.grab {
+            GrabStatus::Active(s, _) => s == serial,
+            _ => false,
+        }
+    }
+
+    /// Check if this touch device is currently being grabbed
+    pub fn is_grabbed(&self) -> bool {
+        let guard = self.inner.borrow();
+        !matches!(guard.grab, GrabStatus::None)
+    }
+
+    /// Returns the start data for the grab, if any.
+    pub fn grab_start_data(&self) -> Option<GrabStartData> {
+        let guard = self.inner.borrow();
+        match &guard.grab {
+            GrabStatus::Active(_, g) => Some(g.start_data().clone()),
+            _ => None,
+        }
+    }
+
     /// Notify clients about new touch points.
     pub fn down(
         &mut self,
@@ -46,19 +110,39 @@ impl TouchHandle {
         slot: TouchSlot,
         location: Point<f64, Logical>,
     ) {
-        self.inner
-            .borrow_mut()
-            .down(serial, time, surface, surface_offset, slot, location);
+        let mut inner = self.inner.borrow_mut();
+        if !inner.pressed_slots.contains(&slot) {
+            inner.pressed_slots.push(slot);
+        }
+        inner.with_grab(|mut handle, grab| {
+            grab.down(&mut handle, serial, time, surface, surface_offset, slot, location);
+        });
     }
 
     /// Notify clients about touch point removal.
     pub fn up(&self, serial: Serial, time: u32, slot: TouchSlot) {
-        self.inner.borrow_mut().up(serial, time, slot);
+        let mut inner = self.inner.borrow_mut();
+        inner.pressed_slots.retain(|s| *s != slot);
+        inner.with_grab(|mut handle, grab| {
+            grab.up(&mut handle, serial, time, slot);
+        });
     }
 
     /// Notify clients about touch motion.
-    pub fn motion(&self, time: u32, slot: TouchSlot, location: Point<f64, Logical>) {
-        self.inner.borrow_mut().motion(time, slot, location);
+    ///
+    /// `focus` is the surface under the new location of the touch point, and the coordinates of
+    /// its origin in the global compositor space, if any. Regular touch points keep the focus of
+    /// their initial `down` event and ignore it, but grabs like drag'n'drop may make use of it.
+    pub fn motion(
+        &self,
+        time: u32,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        focus: Option<(WlSurface, Point<i32, Logical>)>,
+    ) {
+        self.inner.borrow_mut().with_grab(|mut handle, grab| {
+            grab.motion(&mut handle, time, slot, location, focus);
+        });
     }
 
     /// Notify clients about touch shape changes.
@@ -76,7 +160,141 @@ impl TouchHandle {
     /// This should be sent by the compositor when the currently active touch
     /// slot was recognized as a gesture.
     pub fn cancel(&self) {
-        self.inner.borrow_mut().cancel();
+        let mut inner = self.inner.borrow_mut();
+        inner.pressed_slots.clear();
+        inner.with_grab(|mut handle, grab| {
+            grab.cancel(&mut handle);
+        });
+    }
+}
+
+/// Data about the event that started the grab.
+#[derive(Debug, Clone)]
+pub struct GrabStartData {
+    /// The focused surface and its location, if any, at the start of the grab.
+    ///
+    /// The location coordinates are in the global compositor space.
+    pub focus: Option<(WlSurface, Point<i32, Logical>)>,
+    /// The touch point that initiated the grab.
+    pub slot: TouchSlot,
+    /// The location of the touch down event that initiated the grab, in the global compositor space.
+    pub location: Point<f64, Logical>,
+}
+
+/// A trait to implement a touch grab
+///
+/// In some context, it is necessary to temporarily change the behavior of touch points. This is
+/// typically known as a touch grab. A typical example would be, during a drag'n'drop operation,
+/// the underlying surfaces will no longer receive classic touch events, but rather special events.
+///
+/// This trait is the interface to intercept regular touch events and change them as needed, its
+/// interface mimics the [`TouchHandle`] interface.
+///
+/// If your logic decides that the grab should end, both [`TouchInnerHandle`] and [`TouchHandle`] have
+/// a method to change it.
+///
+/// When your grab ends (either as you requested it or if it was forcefully cancelled by the server),
+/// the struct implementing this trait will be dropped. As such you should put clean-up logic in the destructor,
+/// rather than trying to guess when the grab will end.
+pub trait TouchGrab {
+    /// A new touch point appeared
+    ///
+    /// This method allows you attach additional behavior to a down event, possibly altering it.
+    /// You generally will want to invoke `TouchInnerHandle::down()` as part of your processing. If you
+    /// don't, the rest of the compositor will behave as if the down event never occurred.
+    #[allow(clippy::too_many_arguments)]
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        serial: Serial,
+        time: u32,
+        surface: &WlSurface,
+        surface_offset: Point<i32, Logical>,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+    );
+    /// A touch point disappeared
+    ///
+    /// This method allows you attach additional behavior to an up event, possibly altering it.
+    /// You generally will want to invoke `TouchInnerHandle::up()` as part of your processing. If you
+    /// don't, the rest of the compositor will behave as if the up event never occurred.
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, serial: Serial, time: u32, slot: TouchSlot);
+    /// A touch point has moved
+    ///
+    /// This method allows you attach additional behavior to a motion event, possibly altering it.
+    /// You generally will want to invoke `TouchInnerHandle::motion()` as part of your processing. If you
+    /// don't, the rest of the compositor will behave as if the motion event never occurred.
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        time: u32,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        focus: Option<(WlSurface, Point<i32, Logical>)>,
+    );
+    /// The touch session was cancelled
+    ///
+    /// You generally will want to invoke `TouchInnerHandle::cancel()` as part of your processing.
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>);
+    /// The data about the event that started the grab.
+    fn start_data(&self) -> &GrabStartData;
+}
+
+/// This inner handle is accessed from inside a touch grab logic, and directly
+/// sends event to the client
+#[derive(Debug)]
+pub struct TouchInnerHandle<'a> {
+    inner: &'a mut TouchInternal,
+}
+
+impl<'a> TouchInnerHandle<'a> {
+    /// Change the current grab on this touch device to the provided grab
+    ///
+    /// Overwrites any current grab.
+    pub fn set_grab<G: TouchGrab + 'static>(&mut self, serial: Serial, grab: G) {
+        self.inner.set_grab(serial, grab);
+    }
+
+    /// Remove any current grab on this touch device, resetting it to the default behavior
+    pub fn unset_grab(&mut self) {
+        self.inner.unset_grab();
+    }
+
+    /// A list of the touch points currently in contact with the device
+    ///
+    /// This still includes touch points that your grab have intercepted and not sent
+    /// to the client.
+    pub fn current_slots(&self) -> &[TouchSlot] {
+        &self.inner.pressed_slots
+    }
+
+    /// Notify clients about new touch points.
+    pub fn down(
+        &mut self,
+        serial: Serial,
+        time: u32,
+        surface: &WlSurface,
+        surface_offset: Point<i32, Logical>,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+    ) {
+        self.inner
+            .down(serial, time, surface, surface_offset, slot, location);
+    }
+
+    /// Notify clients about touch point removal.
+    pub fn up(&mut self, serial: Serial, time: u32, slot: TouchSlot) {
+        self.inner.up(serial, time, slot);
+    }
+
+    /// Notify clients about touch motion.
+    pub fn motion(&mut self, time: u32, slot: TouchSlot, location: Point<f64, Logical>) {
+        self.inner.motion(time, slot, location);
+    }
+
+    /// Notify clients about touch cancellation.
+    pub fn cancel(&mut self) {
+        self.inner.cancel();
     }
 }
 
@@ -91,9 +309,19 @@ struct TouchFocus {
 struct TouchInternal {
     known_handles: Vec<WlTouch>,
     focus: HashMap<TouchSlot, TouchFocus>,
+    pressed_slots: Vec<TouchSlot>,
+    grab: GrabStatus,
 }
 
 impl TouchInternal {
+    fn set_grab<G: TouchGrab + 'static>(&mut self, serial: Serial, grab: G) {
+        self.grab = GrabStatus::Active(serial, Box::new(grab));
+    }
+
+    fn unset_grab(&mut self) {
+        self.grab = GrabStatus::None;
+    }
+
     fn down(
         &mut self,
         serial: Serial,
@@ -117,12 +345,18 @@ impl TouchInternal {
 
         let (x, y) = (location - focus.surface_offset).into();
         self.with_focused_handles(slot, |handle| {
+            send_timestamp(handle, time);
             handle.down(serial.into(), time, surface, slot.into(), x, y)
         });
     }
 
-    fn up(&self, serial: Serial, time: u32, slot: TouchSlot) {
-        self.with_focused_handles(slot, |handle| handle.up(serial.into(), time, slot.into()));
+    fn up(&mut self, serial: Serial, time: u32, slot: TouchSlot) {
+        self.with_focused_handles(slot, |handle| {
+            send_timestamp(handle, time);
+            handle.up(serial.into(), time, slot.into())
+        });
+        // the slot may be reused by a later touch point on another surface
+        self.focus.remove(&slot);
     }
 
     fn motion(&self, time: u32, slot: TouchSlot, location: Point<f64, Logical>) {
@@ -132,7 +366,10 @@ impl TouchInternal {
         };
 
         let (x, y) = (location - focus.surface_offset).into();
-        self.with_focused_handles(slot, |handle| handle.motion(time, slot.into(), x, y));
+        self.with_focused_handles(slot, |handle| {
+            send_timestamp(handle, time);
+            handle.motion(time, slot.into(), x, y)
+        });
     }
 
     fn shape(&self, slot: TouchSlot, major: f64, minor: f64) {
@@ -152,10 +389,11 @@ impl TouchInternal {
     }
 
     // TODO: In theory doesn't need to be sent for WlTouch that isn't in the focus hashmap?
-    fn cancel(&self) {
+    fn cancel(&mut self) {
         for handle in &self.known_handles {
             handle.cancel();
         }
+        self.focus.clear();
     }
 
     // TODO: Document this also sends frame every time.
@@ -171,6 +409,35 @@ impl TouchInternal {
             }
         }
     }
+
+    fn with_grab<F>(&mut self, f: F)
+    where
+        F: FnOnce(TouchInnerHandle<'_>, &mut dyn TouchGrab),
+    {
+        let mut grab = ::std::mem::replace(&mut self.grab, GrabStatus::Borrowed);
+        match grab {
+            GrabStatus::Borrowed => panic!("Accessed a touch grab from within a touch grab access."),
+            GrabStatus::Active(_, ref mut handler) => {
+                // If this grab is associated with a surface that is no longer alive, discard it
+                if let Some((ref surface, _)) = handler.start_data().focus {
+                    if !surface.as_ref().is_alive() {
+                        self.grab = GrabStatus::None;
+                        f(TouchInnerHandle { inner: self }, &mut DefaultGrab);
+                        return;
+                    }
+                }
+                f(TouchInnerHandle { inner: self }, &mut **handler);
+            }
+            GrabStatus::None => {
+                f(TouchInnerHandle { inner: self }, &mut DefaultGrab);
+            }
+        }
+
+        if let GrabStatus::Borrowed = self.grab {
+            // the grab has not been ended nor replaced, put it back in place
+            self.grab = grab;
+        }
+    }
 }
 
 pub(crate) fn implement_touch(touch: Main<WlTouch>, handle: Option<&TouchHandle>) -> WlTouch {
@@ -190,3 +457,213 @@ pub(crate) fn implement_touch(touch: Main<WlTouch>, handle: Option<&TouchHandle>
 
     touch.deref().clone()
 }
+
+/*
+ * Grabs definition
+ */
+
+// The default grab, the behavior when no particular grab is in progress
+struct DefaultGrab;
+
+impl TouchGrab for DefaultGrab {
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        serial: Serial,
+        time: u32,
+        surface: &WlSurface,
+        surface_offset: Point<i32, Logical>,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+    ) {
+        handle.down(serial, time, surface, surface_offset, slot, location);
+        handle.set_grab(
+            serial,
+            TouchDownGrab {
+                start_data: GrabStartData {
+                    focus: Some((surface.clone(), surface_offset)),
+                    slot,
+                    location,
+                },
+            },
+        );
+    }
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, serial: Serial, time: u32, slot: TouchSlot) {
+        handle.up(serial, time, slot);
+    }
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        time: u32,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+    ) {
+        handle.motion(time, slot, location);
+    }
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.cancel();
+    }
+    fn start_data(&self) -> &GrabStartData {
+        unreachable!()
+    }
+}
+
+// A touch down grab, basic grab started when an user touches a surface,
+// so that clients can start interactive operations with the serial of
+// the first down event.
+//
+// In case the user maintains several simultaneous touch points, release
+// the grab once all are released.
+struct TouchDownGrab {
+    start_data: GrabStartData,
+}
+
+impl TouchGrab for TouchDownGrab {
+    fn down(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        serial: Serial,
+        time: u32,
+        surface: &WlSurface,
+        surface_offset: Point<i32, Logical>,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+    ) {
+        handle.down(serial, time, surface, surface_offset, slot, location);
+    }
+    fn up(&mut self, handle: &mut TouchInnerHandle<'_>, serial: Serial, time: u32, slot: TouchSlot) {
+        handle.up(serial, time, slot);
+        if handle.current_slots().is_empty() {
+            // no more touch points, release the grab
+            handle.unset_grab();
+        }
+    }
+    fn motion(
+        &mut self,
+        handle: &mut TouchInnerHandle<'_>,
+        time: u32,
+        slot: TouchSlot,
+        location: Point<f64, Logical>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+    ) {
+        handle.motion(time, slot, location);
+    }
+    fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+        handle.cancel();
+        handle.unset_grab();
+    }
+    fn start_data(&self) -> &GrabStartData {
+        &self.start_data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Up(TouchSlot),
+        Motion(TouchSlot),
+        Cancel,
+    }
+
+    // records the events and forwards them like the default grab
+    struct RecordingGrab {
+        start_data: GrabStartData,
+        events: Rc<RefCell<Vec<Event>>>,
+    }
+
+    impl TouchGrab for RecordingGrab {
+        fn down(
+            &mut self,
+            _handle: &mut TouchInnerHandle<'_>,
+            _serial: Serial,
+            _time: u32,
+            _surface: &WlSurface,
+            _surface_offset: Point<i32, Logical>,
+            _slot: TouchSlot,
+            _location: Point<f64, Logical>,
+        ) {
+        }
+        fn up(&mut self, handle: &mut TouchInnerHandle<'_>, serial: Serial, time: u32, slot: TouchSlot) {
+            self.events.borrow_mut().push(Event::Up(slot));
+            handle.up(serial, time, slot);
+            if handle.current_slots().is_empty() {
+                handle.unset_grab();
+            }
+        }
+        fn motion(
+            &mut self,
+            handle: &mut TouchInnerHandle<'_>,
+            time: u32,
+            slot: TouchSlot,
+            location: Point<f64, Logical>,
+            _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        ) {
+            self.events.borrow_mut().push(Event::Motion(slot));
+            handle.motion(time, slot, location);
+        }
+        fn cancel(&mut self, handle: &mut TouchInnerHandle<'_>) {
+            self.events.borrow_mut().push(Event::Cancel);
+            handle.cancel();
+        }
+        fn start_data(&self) -> &GrabStartData {
+            &self.start_data
+        }
+    }
+
+    fn grabbed_touch(slot: TouchSlot) -> (TouchHandle, Rc<RefCell<Vec<Event>>>) {
+        let touch = TouchHandle::new();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        touch.set_grab(
+            RecordingGrab {
+                start_data: GrabStartData {
+                    focus: None,
+                    slot,
+                    location: (0.0, 0.0).into(),
+                },
+                events: events.clone(),
+            },
+            Serial::from(1),
+        );
+        (touch, events)
+    }
+
+    #[test]
+    fn grab_receives_events() {
+        let slot = TouchSlot::from(Some(0));
+        let (touch, events) = grabbed_touch(slot);
+        assert!(touch.is_grabbed());
+        assert!(touch.has_grab(Serial::from(1)));
+        assert!(!touch.has_grab(Serial::from(2)));
+        assert_eq!(touch.grab_start_data().unwrap().slot, slot);
+
+        touch.inner.borrow_mut().focus.insert(slot, TouchFocus::default());
+        touch.motion(0, slot, (1.0, 1.0).into(), None);
+        touch.up(Serial::from(3), 0, slot);
+        assert_eq!(*events.borrow(), vec![Event::Motion(slot), Event::Up(slot)]);
+        // the focus of the lifted touch point is forgotten
+        assert!(touch.inner.borrow().focus.is_empty());
+        // the grab released itself once no touch point was left
+        assert!(!touch.is_grabbed());
+
+        // events are no longer delivered to the grab
+        touch.motion(0, slot, (1.0, 1.0).into(), None);
+        assert_eq!(events.borrow().len(), 2);
+    }
+
+    #[test]
+    fn grab_is_cancelled() {
+        let slot = TouchSlot::from(Some(0));
+        let (touch, events) = grabbed_touch(slot);
+        touch.cancel();
+        assert_eq!(*events.borrow(), vec![Event::Cancel]);
+        assert!(touch.inner.borrow().pressed_slots.is_empty());
+
+        touch.unset_grab();
+        assert!(!touch.is_grabbed());
+        assert!(touch.grab_start_data().is_none());
+    }
+}
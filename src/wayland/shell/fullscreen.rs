@@ -0,0 +1,385 @@
+//! Utilities for handling the `zwp_fullscreen_shell_v1` protocol
+//!
+//! The fullscreen shell is meant for kiosk-like compositors, that present exactly one surface per
+//! output, and for nested compositors or screen sharing clients presenting a single surface.
+//! There are no windows to manage: clients present a surface on an output (or any output) and
+//! may request the output mode to be switched to match the size of their surface.
+//!
+//! Your callback receives a [`FullscreenShellRequest`] for every presented surface. How the surface
+//! is shown is up to you, [`present_geometry`] implements the scaling policies clients can ask for
+//! and [`mode_for_surface`] picks an output mode for mode switches. Mode switches requested for an
+//! output are cancelled automatically, when another surface is presented on the same output before
+//! the compositor answered them.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::{
+//!     output::Output,
+//!     shell::fullscreen::{init_fullscreen_shell, mode_for_surface, Capabilities, FullscreenShellRequest},
+//! };
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_fullscreen_shell(
+//!     &mut display,
+//!     Capabilities::empty(),
+//!     |request, _dispatch_data| match request {
+//!         FullscreenShellRequest::Present { surface, method, output } => {
+//!             // show the surface on the output, e.g. scaled with `present_geometry`
+//!         }
+//!         FullscreenShellRequest::PresentForMode { surface, output, framerate, feedback } => {
+//!             # let surface_size = (1920, 1080).into();
+//!             match Output::from_resource(&output).and_then(|output| mode_for_surface(&output, surface_size, framerate)) {
+//!                 Some(mode) => {
+//!                     // switch the output to the mode and show the surface
+//!                     feedback.mode_successful();
+//!                 }
+//!                 None => feedback.mode_failed(),
+//!             }
+//!         }
+//!     },
+//!     None, // put a logger if you want
+//! );
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    ops::Deref,
+    rc::Rc,
+};
+
+use slog::{debug, o};
+use wayland_protocols::unstable::fullscreen_shell::v1::server::{
+    zwp_fullscreen_shell_mode_feedback_v1::ZwpFullscreenShellModeFeedbackV1,
+    zwp_fullscreen_shell_v1::{self, ZwpFullscreenShellV1},
+};
+use wayland_server::{
+    protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    DispatchData, Display, Filter, Global, Main,
+};
+
+use crate::{
+    utils::{Logical, Physical, Rectangle, Size},
+    wayland::{
+        compositor,
+        output::{Mode, Output},
+    },
+};
+
+/// The role of a surface presented with the fullscreen shell
+pub const FULLSCREEN_SHELL_SURFACE_ROLE: &str = "zwp_fullscreen_shell_surface_v1";
+
+bitflags::bitflags! {
+    /// Capabilities advertised to clients of the fullscreen shell
+    pub struct Capabilities: u32 {
+        /// The compositor can set almost any mode on its outputs
+        const ARBITRARY_MODES = 1;
+        /// The compositor shows cursor surfaces without compositing, e.g. on a cursor plane
+        const CURSOR_PLANE = 2;
+    }
+}
+
+/// How a surface should be presented, if its size does not match the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresentMethod {
+    /// No preference, the compositor applies its default policy
+    Default,
+    /// Center the surface on the output
+    Center,
+    /// Scale the surface to the largest size fitting the output, preserving its aspect ratio
+    Zoom,
+    /// Scale the surface to fill the output, preserving its aspect ratio and cropping it if needed
+    ZoomCrop,
+    /// Scale the surface to the size of the output, ignoring its aspect ratio
+    Stretch,
+}
+
+impl From<zwp_fullscreen_shell_v1::PresentMethod> for PresentMethod {
+    fn from(method: zwp_fullscreen_shell_v1::PresentMethod) -> PresentMethod {
+        match method {
+            zwp_fullscreen_shell_v1::PresentMethod::Center => PresentMethod::Center,
+            zwp_fullscreen_shell_v1::PresentMethod::Zoom => PresentMethod::Zoom,
+            zwp_fullscreen_shell_v1::PresentMethod::ZoomCrop => PresentMethod::ZoomCrop,
+            zwp_fullscreen_shell_v1::PresentMethod::Stretch => PresentMethod::Stretch,
+            _ => PresentMethod::Default,
+        }
+    }
+}
+
+/// Returns where a surface is shown on an output with the given present method
+///
+/// The returned geometry is relative to the output and exceeds it for [`PresentMethod::ZoomCrop`].
+/// [`PresentMethod::Default`] is handled like [`PresentMethod::Center`].
+pub fn present_geometry(
+    method: PresentMethod,
+    surface_size: Size<i32, Logical>,
+    output_size: Size<i32, Logical>,
+) -> Rectangle<i32, Logical> {
+    let (surface_w, surface_h) = (surface_size.w.max(1) as f64, surface_size.h.max(1) as f64);
+    let (output_w, output_h) = (output_size.w as f64, output_size.h as f64);
+    let size: Size<i32, Logical> = match method {
+        PresentMethod::Default | PresentMethod::Center => surface_size,
+        PresentMethod::Stretch => output_size,
+        PresentMethod::Zoom | PresentMethod::ZoomCrop => {
+            let scale_w = output_w / surface_w;
+            let scale_h = output_h / surface_h;
+            let scale = if method == PresentMethod::Zoom {
+                scale_w.min(scale_h)
+            } else {
+                scale_w.max(scale_h)
+            };
+            (
+                (surface_w * scale).round() as i32,
+                (surface_h * scale).round() as i32,
+            )
+                .into()
+        }
+    };
+    let loc = ((output_size.w - size.w) / 2, (output_size.h - size.h) / 2);
+    Rectangle::from_loc_and_size(loc, size)
+}
+
+/// Picks the mode of an output matching the size of a surface presented for a mode switch
+///
+/// Among the modes of the requested size, the one with the refresh rate closest to `framerate`
+/// (in mHz) is chosen. Without a preferred framerate, the preferred mode is picked if it matches,
+/// otherwise the mode with the highest refresh rate.
+pub fn mode_for_surface(output: &Output, size: Size<i32, Physical>, framerate: i32) -> Option<Mode> {
+    let modes = output.modes().into_iter().filter(|mode| mode.size == size);
+    if framerate > 0 {
+        modes.min_by_key(|mode| (mode.refresh - framerate).abs())
+    } else {
+        let preferred = output.preferred_mode().filter(|mode| mode.size == size);
+        preferred.or_else(|| modes.max_by_key(|mode| mode.refresh))
+    }
+}
+
+/// Feedback about a mode switch requested with [`FullscreenShellRequest::PresentForMode`]
+///
+/// Only the first answer is sent to the client, later ones are ignored.
+#[derive(Debug, Clone)]
+pub struct ModeFeedback {
+    feedback: ZwpFullscreenShellModeFeedbackV1,
+    answered: Rc<Cell<bool>>,
+}
+
+impl PartialEq for ModeFeedback {
+    fn eq(&self, other: &Self) -> bool {
+        self.feedback == other.feedback
+    }
+}
+
+impl ModeFeedback {
+    fn answer(&self, send: impl FnOnce(&ZwpFullscreenShellModeFeedbackV1)) {
+        if !self.answered.replace(true) && self.feedback.as_ref().is_alive() {
+            send(&self.feedback);
+        }
+    }
+
+    /// Checks if the mode switch was already answered or cancelled
+    pub fn answered(&self) -> bool {
+        self.answered.get()
+    }
+
+    /// The output now shows the surface unscaled in the requested mode
+    pub fn mode_successful(&self) {
+        self.answer(ZwpFullscreenShellModeFeedbackV1::mode_successful)
+    }
+
+    /// The mode could not be switched, the output keeps showing the previously presented surface
+    pub fn mode_failed(&self) {
+        self.answer(ZwpFullscreenShellModeFeedbackV1::mode_failed)
+    }
+
+    /// The mode switch was cancelled, e.g. because another surface was presented on the output
+    pub fn present_cancelled(&self) {
+        self.answer(ZwpFullscreenShellModeFeedbackV1::present_cancelled)
+    }
+}
+
+/// Requests of fullscreen shell clients
+#[derive(Debug)]
+pub enum FullscreenShellRequest {
+    /// A surface should be presented on an output
+    Present {
+        /// The surface to present, `None` removes the surface presented on the output
+        surface: Option<WlSurface>,
+        /// How the surface should be scaled, if its size does not match the output
+        method: PresentMethod,
+        /// The output to present the surface on, `None` lets the compositor choose one or several outputs
+        output: Option<WlOutput>,
+    },
+    /// A surface should be presented on an output, after switching the output to a mode matching its size
+    PresentForMode {
+        /// The surface to present
+        surface: WlSurface,
+        /// The output to present the surface on
+        output: WlOutput,
+        /// The preferred refresh rate in mHz, `0` if the client has no preference
+        framerate: i32,
+        /// Feedback to send the result of the mode switch to
+        feedback: ModeFeedback,
+    },
+}
+
+/// Creates a new `zwp_fullscreen_shell_v1` global
+///
+/// The capabilities are advertised to every client binding the global.
+pub fn init_fullscreen_shell<F, L>(
+    display: &mut Display,
+    capabilities: Capabilities,
+    implementation: F,
+    logger: L,
+) -> Global<ZwpFullscreenShellV1>
+where
+    F: FnMut(FullscreenShellRequest, DispatchData<'_>) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "fullscreen_shell"));
+    let implementation = Rc::new(RefCell::new(implementation));
+    // mode switches waiting for an answer of the compositor
+    let pending = Rc::new(RefCell::new(Vec::<(WlOutput, ModeFeedback)>::new()));
+
+    display.create_global(
+        1,
+        Filter::new(move |(shell, _version): (Main<ZwpFullscreenShellV1>, _), _, _| {
+            if capabilities.contains(Capabilities::ARBITRARY_MODES) {
+                shell.capability(zwp_fullscreen_shell_v1::Capability::ArbitraryModes);
+            }
+            if capabilities.contains(Capabilities::CURSOR_PLANE) {
+                shell.capability(zwp_fullscreen_shell_v1::Capability::CursorPlane);
+            }
+
+            let implementation = implementation.clone();
+            let pending = pending.clone();
+            let log = log.clone();
+            shell.quick_assign(move |shell, request, ddata| {
+                let request = match request {
+                    zwp_fullscreen_shell_v1::Request::PresentSurface {
+                        surface,
+                        method,
+                        output,
+                    } => {
+                        if let Some(ref surface) = surface {
+                            if !give_role(&shell, surface) {
+                                return;
+                            }
+                        }
+                        if let Some(ref output) = output {
+                            cancel_pending(&pending, output);
+                        }
+                        FullscreenShellRequest::Present {
+                            surface,
+                            method: method.into(),
+                            output,
+                        }
+                    }
+                    zwp_fullscreen_shell_v1::Request::PresentSurfaceForMode {
+                        surface,
+                        output,
+                        framerate,
+                        feedback,
+                    } => {
+                        feedback.quick_assign(|_, _, _| {});
+                        let feedback = ModeFeedback {
+                            feedback: feedback.deref().clone(),
+                            answered: Rc::new(Cell::new(false)),
+                        };
+                        if !give_role(&shell, &surface) {
+                            return;
+                        }
+                        cancel_pending(&pending, &output);
+                        pending.borrow_mut().push((output.clone(), feedback.clone()));
+                        FullscreenShellRequest::PresentForMode {
+                            surface,
+                            output,
+                            framerate,
+                            feedback,
+                        }
+                    }
+                    zwp_fullscreen_shell_v1::Request::Release => {
+                        // All is handled by destructor.
+                        return;
+                    }
+                    _ => unreachable!(),
+                };
+                debug!(log, "New fullscreen shell request"; "request" => ?request);
+                (*implementation.borrow_mut())(request, ddata);
+            });
+        }),
+    )
+}
+
+/// Gives the fullscreen shell role to a surface, posts an error if it already has another role
+fn give_role(shell: &ZwpFullscreenShellV1, surface: &WlSurface) -> bool {
+    if compositor::get_role(surface) == Some(FULLSCREEN_SHELL_SURFACE_ROLE)
+        || compositor::give_role(surface, FULLSCREEN_SHELL_SURFACE_ROLE).is_ok()
+    {
+        return true;
+    }
+    shell.as_ref().post_error(
+        zwp_fullscreen_shell_v1::Error::Role as u32,
+        compositor::role_error_message(surface, FULLSCREEN_SHELL_SURFACE_ROLE),
+    );
+    false
+}
+
+/// Cancels the mode switch pending on an output, as another surface is presented on it
+fn cancel_pending(pending: &RefCell<Vec<(WlOutput, ModeFeedback)>>, output: &WlOutput) {
+    pending.borrow_mut().retain(|(pending_output, feedback)| {
+        if feedback.answered() {
+            return false;
+        }
+        if pending_output == output {
+            feedback.present_cancelled();
+            return false;
+        }
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(method: PresentMethod, surface: (i32, i32)) -> Rectangle<i32, Logical> {
+        present_geometry(method, surface.into(), (1920, 1080).into())
+    }
+
+    #[test]
+    fn center() {
+        assert_eq!(
+            geometry(PresentMethod::Center, (800, 600)),
+            Rectangle::from_loc_and_size((560, 240), (800, 600))
+        );
+        assert_eq!(
+            geometry(PresentMethod::Default, (2000, 1080)),
+            Rectangle::from_loc_and_size((-40, 0), (2000, 1080))
+        );
+    }
+
+    #[test]
+    fn zoom() {
+        // pillarboxed
+        assert_eq!(
+            geometry(PresentMethod::Zoom, (800, 600)),
+            Rectangle::from_loc_and_size((240, 0), (1440, 1080))
+        );
+        // letterboxed
+        assert_eq!(
+            geometry(PresentMethod::Zoom, (2560, 1080)),
+            Rectangle::from_loc_and_size((0, 135), (1920, 810))
+        );
+    }
+
+    #[test]
+    fn zoom_crop_and_stretch() {
+        assert_eq!(
+            geometry(PresentMethod::ZoomCrop, (800, 600)),
+            Rectangle::from_loc_and_size((0, -180), (1920, 1440))
+        );
+        assert_eq!(
+            geometry(PresentMethod::Stretch, (800, 600)),
+            Rectangle::from_loc_and_size((0, 0), (1920, 1080))
+        );
+    }
+}
@@ -13,12 +13,15 @@
 //!   the current standard for desktop apps
 //! - The [`legacy`](legacy/index.html) module provides handlers for the `wl_shell` protocol, which
 //!   is now deprecated. You only need it if you want to support apps predating `xdg_shell`.
+//! - The [`fullscreen`](fullscreen/index.html) module provides handlers for the `zwp_fullscreen_shell_v1`
+//!   protocol, which presents a single surface per output for kiosk-like compositors.
 
 use super::Serial;
 use crate::wayland::compositor;
 use thiserror::Error;
 use wayland_server::protocol::wl_surface::WlSurface;
 
+pub mod fullscreen;
 pub mod legacy;
 pub mod xdg;
 
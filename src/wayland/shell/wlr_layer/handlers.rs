@@ -37,7 +37,7 @@ pub(super) fn layer_shell_implementation(
             if compositor::give_role(&surface, LAYER_SURFACE_ROLE).is_err() {
                 shell.as_ref().post_error(
                     zwlr_layer_shell_v1::Error::Role as u32,
-                    "Surface already has a role.".into(),
+                    compositor::role_error_message(&surface, LAYER_SURFACE_ROLE),
                 );
                 return;
             }
@@ -34,7 +34,7 @@ use std::{
 use wayland_protocols::wlr::unstable::layer_shell::v1::server::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
 use wayland_server::{
     protocol::{wl_output::WlOutput, wl_surface},
-    DispatchData, Display, Filter, Global, Main,
+    Client, DispatchData, Display, Filter, Global, Main,
 };
 
 use crate::{
@@ -161,6 +161,20 @@ impl LayerShellState {
     pub fn layer_surfaces(&self) -> &[LayerSurface] {
         &self.known_layers[..]
     }
+
+    /// Iterates over the layer surfaces of a given client
+    pub fn client_layer_surfaces<'a>(
+        &'a self,
+        client: &'a Client,
+    ) -> impl Iterator<Item = &'a LayerSurface> + 'a {
+        self.known_layers.iter().filter(move |layer| {
+            layer
+                .get_surface()
+                .and_then(|surface| surface.as_ref().client())
+                .map(|owner| owner.equals(client))
+                .unwrap_or(false)
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -81,7 +81,7 @@ use wayland_protocols::xdg_shell::server::{xdg_popup, xdg_positioner, xdg_toplev
 use wayland_server::DispatchData;
 use wayland_server::{
     protocol::{wl_output, wl_seat, wl_surface},
-    Display, Filter, Global, UserDataMap,
+    Client, Display, Filter, Global, UserDataMap,
 };
 
 use self::xdg_handlers::ShellSurfaceUserData;
@@ -330,7 +330,9 @@ xdg_role!(
         /// Maximum size requested for this surface
         ///
         /// A value of 0 on an axis means this axis is not constrained
-        pub max_size: Size<i32, Logical>
+        pub max_size: Size<i32, Logical>,
+
+        toplevel_handle: Option<xdg_toplevel::XdgToplevel>
     }
 );
 
@@ -395,6 +397,23 @@ xdg_role!(
     }
 );
 
+// Protocol errors detected when committing a popup
+enum PopupCommitError {
+    NoParent,
+    UnconfiguredBuffer,
+}
+
+/// Returns true if a buffer was attached since the last commit
+fn has_pending_buffer(states: &compositor::SurfaceData) -> bool {
+    matches!(
+        states
+            .cached_state
+            .pending::<compositor::SurfaceAttributes>()
+            .buffer,
+        Some(compositor::BufferAssignment::NewBuffer { .. })
+    )
+}
+
 /// Represents the state of the popup
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct PopupState {
@@ -820,6 +839,59 @@ impl ShellState {
     pub fn popup_surfaces(&self) -> &[PopupSurface] {
         &self.known_popups[..]
     }
+
+    /// Returns the clients owning at least one toplevel or popup surface
+    pub fn clients(&self) -> Vec<Client> {
+        let mut clients: Vec<Client> = Vec::new();
+        let surfaces = self
+            .known_toplevels
+            .iter()
+            .filter_map(|toplevel| toplevel.get_surface())
+            .chain(self.known_popups.iter().filter_map(|popup| popup.get_surface()));
+        for client in surfaces.filter_map(|surface| surface.as_ref().client()) {
+            if !clients.iter().any(|known| known.equals(&client)) {
+                clients.push(client);
+            }
+        }
+        clients
+    }
+
+    /// Iterates over the toplevel surfaces of a given client
+    pub fn client_toplevel_surfaces<'a>(
+        &'a self,
+        client: &'a Client,
+    ) -> impl Iterator<Item = &'a ToplevelSurface> + 'a {
+        self.known_toplevels
+            .iter()
+            .filter(move |toplevel| is_owned_by(toplevel.get_surface(), client))
+    }
+
+    /// Iterates over the popup surfaces of a given client
+    pub fn client_popup_surfaces<'a>(
+        &'a self,
+        client: &'a Client,
+    ) -> impl Iterator<Item = &'a PopupSurface> + 'a {
+        self.known_popups
+            .iter()
+            .filter(move |popup| is_owned_by(popup.get_surface(), client))
+    }
+
+    /// Returns a snapshot of the state of every toplevel surface that is still alive
+    ///
+    /// See [`ToplevelSurface::snapshot`].
+    pub fn toplevel_snapshots(&self) -> Vec<ToplevelSnapshot> {
+        self.known_toplevels
+            .iter()
+            .filter_map(ToplevelSurface::snapshot)
+            .collect()
+    }
+}
+
+fn is_owned_by(surface: Option<&wl_surface::WlSurface>, client: &Client) -> bool {
+    surface
+        .and_then(|surface| surface.as_ref().client())
+        .map(|owner| owner.equals(client))
+        .unwrap_or(false)
 }
 
 pub(crate) struct ShellClientData {
@@ -1044,18 +1116,29 @@ impl ToplevelSurface {
     /// This should be called when the underlying WlSurface
     /// handles a wl_surface.commit request.
     pub(crate) fn commit_hook(surface: &wl_surface::WlSurface) {
-        compositor::with_states(surface, |states| {
+        let send_error_to = compositor::with_states(surface, |states| {
             let mut guard = states
                 .data_map
                 .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
                 .unwrap()
                 .lock()
                 .unwrap();
+            if !guard.configured && has_pending_buffer(states) {
+                return guard.toplevel_handle.clone();
+            }
             if let Some(state) = guard.last_acked.clone() {
                 guard.current = state;
             }
+            None
         })
         .unwrap();
+        if let Some(handle) = send_error_to {
+            let data = handle.as_ref().user_data().get::<ShellSurfaceUserData>().unwrap();
+            data.xdg_surface.as_ref().post_error(
+                xdg_surface::Error::UnconfiguredBuffer as u32,
+                "A buffer was attached before the initial configure was acknowledged.".into(),
+            );
+        }
     }
 
     /// Make sure this surface was configured
@@ -1088,7 +1171,7 @@ impl ToplevelSurface {
                 .get::<self::xdg_handlers::ShellSurfaceUserData>()
                 .unwrap();
             data.xdg_surface.as_ref().post_error(
-                xdg_surface::Error::NotConstructed as u32,
+                xdg_surface::Error::UnconfiguredBuffer as u32,
                 "Surface has not been configured yet.".into(),
             );
         }
@@ -1172,6 +1255,37 @@ impl ToplevelSurface {
         xdg_handlers::get_parent(&self.shell_surface)
     }
 
+    /// Takes a snapshot of the state of this toplevel
+    ///
+    /// Returns `None` if the underlying surface has been destroyed.
+    pub fn snapshot(&self) -> Option<ToplevelSnapshot> {
+        if !self.alive() {
+            return None;
+        }
+
+        compositor::with_states(&self.wl_surface, |states| {
+            let attributes = states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            let cached = states.cached_state.current::<SurfaceCachedState>();
+
+            ToplevelSnapshot {
+                surface: self.wl_surface.clone(),
+                parent: attributes.parent.clone(),
+                title: attributes.title.clone(),
+                app_id: attributes.app_id.clone(),
+                state: attributes.current.clone(),
+                geometry: cached.geometry,
+                min_size: cached.min_size,
+                max_size: cached.max_size,
+            }
+        })
+        .ok()
+    }
+
     /// Sets the parent of this toplevel surface and returns whether the parent was successfully set.
     ///
     /// The parent must be another toplevel equivalent surface.
@@ -1190,6 +1304,30 @@ impl ToplevelSurface {
     }
 }
 
+/// Snapshot of the state of a toplevel surface, see [`ToplevelSurface::snapshot`]
+///
+/// Contains everything needed to describe a window to other clients, e.g. for
+/// `wlr-foreign-toplevel-management`, or to the user, e.g. in debugging tools.
+#[derive(Debug, Clone)]
+pub struct ToplevelSnapshot {
+    /// The underlying surface
+    pub surface: wl_surface::WlSurface,
+    /// The surface of the parent toplevel, if any
+    pub parent: Option<wl_surface::WlSurface>,
+    /// The title set by the client
+    pub title: Option<String>,
+    /// The app ID set by the client
+    pub app_id: Option<String>,
+    /// The current state, as acknowledged and committed by the client
+    pub state: ToplevelState,
+    /// The window geometry set by the client
+    pub geometry: Option<Rectangle<i32, Logical>>,
+    /// Minimum size requested by the client, `0` on an axis means it is not constrained
+    pub min_size: Size<i32, Logical>,
+    /// Maximum size requested by the client, `0` on an axis means it is not constrained
+    pub max_size: Size<i32, Logical>,
+}
+
 /// Represents the possible errors that
 /// can be returned from [`PopupSurface::send_configure`]
 #[derive(Debug, thiserror::Error)]
@@ -1380,22 +1518,36 @@ impl PopupSurface {
                 .lock()
                 .unwrap();
             if attributes.parent.is_none() {
-                attributes.popup_handle.clone()
+                attributes
+                    .popup_handle
+                    .clone()
+                    .map(|handle| (handle, PopupCommitError::NoParent))
+            } else if !attributes.configured && has_pending_buffer(states) {
+                attributes
+                    .popup_handle
+                    .clone()
+                    .map(|handle| (handle, PopupCommitError::UnconfiguredBuffer))
             } else {
                 None
             }
         })
         .unwrap_or(None);
-        if let Some(handle) = send_error_to {
+        if let Some((handle, error)) = send_error_to {
             let data = handle
                 .as_ref()
                 .user_data()
                 .get::<self::xdg_handlers::ShellSurfaceUserData>()
                 .unwrap();
-            data.xdg_surface.as_ref().post_error(
-                xdg_surface::Error::NotConstructed as u32,
-                "Surface has not been configured yet.".into(),
-            );
+            match error {
+                PopupCommitError::NoParent => data.wm_base.as_ref().post_error(
+                    xdg_wm_base::Error::InvalidPopupParent as u32,
+                    "xdg_popup was committed without a parent.".into(),
+                ),
+                PopupCommitError::UnconfiguredBuffer => data.xdg_surface.as_ref().post_error(
+                    xdg_surface::Error::UnconfiguredBuffer as u32,
+                    "A buffer was attached before the initial configure was acknowledged.".into(),
+                ),
+            }
             return;
         }
 
@@ -1449,7 +1601,7 @@ impl PopupSurface {
                 .get::<self::xdg_handlers::ShellSurfaceUserData>()
                 .unwrap();
             data.xdg_surface.as_ref().post_error(
-                xdg_surface::Error::NotConstructed as u32,
+                xdg_surface::Error::UnconfiguredBuffer as u32,
                 "Surface has not been configured yet.".into(),
             );
         }
@@ -1,7 +1,7 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{cell::RefCell, ops::Deref as _, sync::Mutex};
 
-use crate::wayland::compositor;
+use crate::wayland::compositor::{self, BufferAssignment, SurfaceAttributes};
 use crate::wayland::shell::xdg::{PopupState, XDG_POPUP_ROLE, XDG_TOPLEVEL_ROLE};
 use crate::wayland::Serial;
 use wayland_protocols::unstable::xdg_decoration::v1::server::zxdg_toplevel_decoration_v1;
@@ -71,6 +71,28 @@ fn wm_implementation(
             // Do not assign a role to the surface here
             // xdg_surface is not role, only xdg_toplevel and
             // xdg_popup are defined as roles
+            let role = compositor::get_role(&surface);
+            if role.is_some() && role != Some(XDG_TOPLEVEL_ROLE) && role != Some(XDG_POPUP_ROLE) {
+                shell.as_ref().post_error(
+                    xdg_wm_base::Error::Role as u32,
+                    compositor::role_error_message(&surface, "xdg_surface"),
+                );
+                return;
+            }
+            let has_buffer = compositor::with_states(&surface, |states| {
+                matches!(
+                    states.cached_state.pending::<SurfaceAttributes>().buffer,
+                    Some(BufferAssignment::NewBuffer { .. })
+                )
+            })
+            .unwrap_or(false);
+            if has_buffer {
+                shell.as_ref().post_error(
+                    xdg_wm_base::Error::InvalidSurfaceState as u32,
+                    "xdg_surface created for a wl_surface with a buffer attached".into(),
+                );
+                return;
+            }
             id.quick_assign(|surface, req, dispatch_data| {
                 xdg_surface_implementation(req, surface.deref().clone(), dispatch_data)
             });
@@ -234,10 +256,17 @@ fn xdg_surface_implementation(
             let surface = &data.wl_surface;
             let shell = &data.wm_base;
 
+            if data.has_active_role.load(Ordering::Acquire) {
+                xdg_surface.as_ref().post_error(
+                    xdg_surface::Error::AlreadyConstructed as u32,
+                    "xdg_surface already has a role object".into(),
+                );
+                return;
+            }
             if compositor::give_role(surface, XDG_TOPLEVEL_ROLE).is_err() {
                 shell.as_ref().post_error(
                     xdg_wm_base::Error::Role as u32,
-                    "Surface already has a role.".into(),
+                    compositor::role_error_message(surface, XDG_TOPLEVEL_ROLE),
                 );
                 return;
             }
@@ -247,7 +276,14 @@ fn xdg_surface_implementation(
             compositor::with_states(surface, |states| {
                 states
                     .data_map
-                    .insert_if_missing_threadsafe(|| Mutex::new(XdgToplevelSurfaceRoleAttributes::default()))
+                    .insert_if_missing_threadsafe(|| Mutex::new(XdgToplevelSurfaceRoleAttributes::default()));
+                states
+                    .data_map
+                    .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .toplevel_handle = Some(id.deref().clone());
             })
             .unwrap();
 
@@ -302,12 +338,20 @@ fn xdg_surface_implementation(
                     geometry: positioner_data.get_geometry(),
                     positioner: positioner_data,
                 }),
+                popup_handle: Some(id.deref().clone()),
                 ..Default::default()
             };
+            if data.has_active_role.load(Ordering::Acquire) {
+                xdg_surface.as_ref().post_error(
+                    xdg_surface::Error::AlreadyConstructed as u32,
+                    "xdg_surface already has a role object".into(),
+                );
+                return;
+            }
             if compositor::give_role(surface, XDG_POPUP_ROLE).is_err() {
                 shell.as_ref().post_error(
                     xdg_wm_base::Error::Role as u32,
-                    "Surface already has a role.".into(),
+                    compositor::role_error_message(surface, XDG_POPUP_ROLE),
                 );
                 return;
             }
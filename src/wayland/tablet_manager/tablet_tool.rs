@@ -265,7 +265,7 @@ impl TabletToolHandle {
                                     {
                                         tool.as_ref().post_error(
                                             zwp_tablet_tool_v2::Error::Role as u32,
-                                            "Given wl_surface has another role.".into(),
+                                            compositor::role_error_message(&surface, CURSOR_IMAGE_ROLE),
                                         );
                                         return;
                                     }
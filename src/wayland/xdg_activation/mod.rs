@@ -1,24 +1,44 @@
 //! Utilities for handling activation requests with the `xdg_activation` protocol
 //!
+//! Honoring every activation request lets any client steal the keyboard focus, e.g. while the
+//! user is typing into another window. A [`FocusStealingPolicy`], like the [`DefaultFocusStealingPolicy`],
+//! decides based on the token, the focused client and the last [`UserInteraction`], whether a
+//! surface is activated or only demands attention.
 //!
 //! ### Example
 //! ```no_run
 //! # extern crate wayland_server;
 //! #
-//! use smithay::wayland::xdg_activation::{init_xdg_activation_global, XdgActivationEvent};
+//! use smithay::wayland::xdg_activation::{
+//!     init_xdg_activation_global, ActivationDecision, ActivationRequest, DefaultFocusStealingPolicy,
+//!     FocusStealingPolicy, XdgActivationEvent,
+//! };
 //!
 //! # let mut display = wayland_server::Display::new();
+//! let mut policy = DefaultFocusStealingPolicy::default();
 //! let (state, _) = init_xdg_activation_global(
 //!     &mut display,
 //!     // your implementation
-//!     |state, req, dispatch_data| {
+//!     move |state, req, dispatch_data| {
 //!         match req{
 //!             XdgActivationEvent::RequestActivation { token, token_data, surface } => {
-//!                 if token_data.timestamp.elapsed().as_secs() < 10 {
-//!                     // Request surface activation
-//!                 } else{
-//!                     // Discard the request
-//!                     state.lock().unwrap().remove_request(&token);
+//!                 let request = ActivationRequest {
+//!                     token_data: &token_data,
+//!                     surface: &surface,
+//!                     focus: None, // the keyboard focus of the seat
+//!                     last_interaction: None, // the last input event of the seat
+//!                 };
+//!                 match policy.decide(&request) {
+//!                     ActivationDecision::Activate => {
+//!                         // Raise and focus the surface
+//!                     }
+//!                     ActivationDecision::DemandAttention => {
+//!                         // Mark the surface as urgent
+//!                     }
+//!                     ActivationDecision::Deny => {
+//!                         // Discard the request
+//!                         state.lock().unwrap().remove_request(&token);
+//!                     }
 //!                 }
 //!             },
 //!             XdgActivationEvent::DestroyActivationRequest {..} => {
@@ -50,6 +70,11 @@ use rand::distributions::{Alphanumeric, DistString};
 use crate::wayland::Serial;
 
 mod handlers;
+mod policy;
+
+pub use policy::{
+    ActivationDecision, ActivationRequest, DefaultFocusStealingPolicy, FocusStealingPolicy, UserInteraction,
+};
 
 /// Contains the unique string token of activation request
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -0,0 +1,207 @@
+//! Focus stealing prevention for activation requests
+
+use std::time::{Duration, Instant};
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use super::XdgActivationTokenData;
+use crate::wayland::Serial;
+
+/// User input, e.g. a key press or a pointer button press
+///
+/// Clients are expected to request activation tokens in response to user input and to pass
+/// the serial of that input, so input after it tells whether the user moved on in the meantime.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserInteraction {
+    /// Serial of the input event
+    pub serial: Serial,
+    /// Time the input event was processed
+    pub time: Instant,
+}
+
+/// What to do about an activation request, as decided by a [`FocusStealingPolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivationDecision {
+    /// Activate the surface, e.g. raise it and give it the keyboard focus
+    Activate,
+    /// Do not take away the focus, but draw the attention of the user to the surface,
+    /// e.g. by marking it as urgent
+    DemandAttention,
+    /// Discard the request, see [`XdgActivationState::remove_request`](super::XdgActivationState::remove_request)
+    Deny,
+}
+
+/// An activation request to be judged by a [`FocusStealingPolicy`]
+#[derive(Debug)]
+pub struct ActivationRequest<'a> {
+    /// Data of the token used for the request
+    pub token_data: &'a XdgActivationTokenData,
+    /// Surface to be activated
+    pub surface: &'a WlSurface,
+    /// Surface having the keyboard focus of the seat the surface would be activated on
+    pub focus: Option<&'a WlSurface>,
+    /// The most recent user input on that seat
+    pub last_interaction: Option<UserInteraction>,
+}
+
+impl ActivationRequest<'_> {
+    /// Returns whether the surface, that requested the token, belongs to the focused client
+    pub fn requested_by_focused_client(&self) -> bool {
+        match (self.token_data.surface.as_ref(), self.focus) {
+            (Some(requester), Some(focus)) => requester.as_ref().same_client_as(focus.as_ref()),
+            _ => false,
+        }
+    }
+
+    /// Returns whether the surface to be activated belongs to the focused client
+    pub fn targets_focused_client(&self) -> bool {
+        self.focus
+            .map(|focus| self.surface.as_ref().same_client_as(focus.as_ref()))
+            .unwrap_or(false)
+    }
+
+    /// Returns whether no user input happened after the input the token was requested for,
+    /// or after the token was created, if it carries no serial
+    pub fn no_later_interaction(&self) -> bool {
+        let interaction = match self.last_interaction {
+            Some(interaction) => interaction,
+            None => return true,
+        };
+        match &self.token_data.serial {
+            Some((serial, _)) => *serial >= interaction.serial,
+            None => interaction.time <= self.token_data.timestamp,
+        }
+    }
+}
+
+/// Decides whether activation requests may take away the keyboard focus
+///
+/// Implemented for closures taking an [`ActivationRequest`]. See [`DefaultFocusStealingPolicy`]
+/// for the policy recommended for most compositors.
+pub trait FocusStealingPolicy {
+    /// Judges an activation request
+    fn decide(&mut self, request: &ActivationRequest<'_>) -> ActivationDecision;
+}
+
+impl<F> FocusStealingPolicy for F
+where
+    F: FnMut(&ActivationRequest<'_>) -> ActivationDecision,
+{
+    fn decide(&mut self, request: &ActivationRequest<'_>) -> ActivationDecision {
+        self(request)
+    }
+}
+
+/// Focus stealing prevention based on the age of tokens, user input and client identity
+///
+/// - Requests with tokens older than [`max_token_age`](DefaultFocusStealingPolicy::max_token_age) are denied.
+/// - Surfaces are activated, if nothing is focused or the focused client activates its own surfaces.
+/// - Surfaces are activated, if the token was requested by the focused client and the user did
+///   not interact with the seat since the input the token was requested for.
+/// - Otherwise the surface only demands attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultFocusStealingPolicy {
+    /// Maximum age of tokens, 10 seconds by default
+    pub max_token_age: Duration,
+}
+
+impl Default for DefaultFocusStealingPolicy {
+    fn default() -> Self {
+        DefaultFocusStealingPolicy {
+            max_token_age: Duration::from_secs(10),
+        }
+    }
+}
+
+/// What [`DefaultFocusStealingPolicy`] knows about a request
+#[derive(Debug, Clone, Copy)]
+struct Facts {
+    token_age: Duration,
+    has_focus: bool,
+    targets_focused_client: bool,
+    requested_by_focused_client: bool,
+    no_later_interaction: bool,
+}
+
+impl DefaultFocusStealingPolicy {
+    fn evaluate(&self, facts: Facts) -> ActivationDecision {
+        if facts.token_age > self.max_token_age {
+            ActivationDecision::Deny
+        } else if !facts.has_focus
+            || facts.targets_focused_client
+            || (facts.requested_by_focused_client && facts.no_later_interaction)
+        {
+            ActivationDecision::Activate
+        } else {
+            ActivationDecision::DemandAttention
+        }
+    }
+}
+
+impl FocusStealingPolicy for DefaultFocusStealingPolicy {
+    fn decide(&mut self, request: &ActivationRequest<'_>) -> ActivationDecision {
+        self.evaluate(Facts {
+            token_age: request.token_data.timestamp.elapsed(),
+            has_focus: request.focus.is_some(),
+            targets_focused_client: request.targets_focused_client(),
+            requested_by_focused_client: request.requested_by_focused_client(),
+            no_later_interaction: request.no_later_interaction(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts() -> Facts {
+        Facts {
+            token_age: Duration::from_secs(1),
+            has_focus: true,
+            targets_focused_client: false,
+            requested_by_focused_client: true,
+            no_later_interaction: true,
+        }
+    }
+
+    #[test]
+    fn default_policy() {
+        let policy = DefaultFocusStealingPolicy::default();
+        assert_eq!(policy.evaluate(facts()), ActivationDecision::Activate);
+        assert_eq!(
+            policy.evaluate(Facts {
+                token_age: Duration::from_secs(11),
+                ..facts()
+            }),
+            ActivationDecision::Deny
+        );
+        // the user typed into another window after clicking the link
+        assert_eq!(
+            policy.evaluate(Facts {
+                no_later_interaction: false,
+                ..facts()
+            }),
+            ActivationDecision::DemandAttention
+        );
+        // a background client
+        let background = Facts {
+            requested_by_focused_client: false,
+            ..facts()
+        };
+        assert_eq!(policy.evaluate(background), ActivationDecision::DemandAttention);
+        assert_eq!(
+            policy.evaluate(Facts {
+                has_focus: false,
+                ..background
+            }),
+            ActivationDecision::Activate
+        );
+        assert_eq!(
+            policy.evaluate(Facts {
+                targets_focused_client: true,
+                ..background
+            }),
+            ActivationDecision::Activate
+        );
+    }
+}
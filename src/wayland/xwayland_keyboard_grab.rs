@@ -0,0 +1,154 @@
+//! Utilities for handling the `zwp_xwayland_keyboard_grab_manager_v1` protocol
+//!
+//! This protocol allows XWayland to grab the keyboard on behalf of X11 applications like
+//! virtual machines or remote desktop clients, which expect to receive all key events,
+//! including the ones usually bound to global shortcuts.
+//!
+//! Whether a grab is granted is up to the compositor: your callback receives every grab
+//! request as a [`XwaylandKeyboardGrab`], which is a [`KeyboardGrab`] that can be activated
+//! using [`KeyboardHandle::set_grab`](crate::wayland::seat::KeyboardHandle::set_grab).
+//! The protocol is only meant to be used by XWayland, so you should ignore requests of other
+//! clients. The filter passed to [`KeyboardHandle::input`](crate::wayland::seat::KeyboardHandle::input)
+//! still sees every key event, so compositor shortcuts keep working while the grab is active.
+//!
+//! The grab ends when the client destroys it or the grabbing surface is destroyed.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::{seat::Seat, xwayland_keyboard_grab::init_xwayland_keyboard_grab_manager, SERIAL_COUNTER};
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_xwayland_keyboard_grab_manager(
+//!     &mut display,
+//!     |grab, _dispatch_data| {
+//!         // make sure the request comes from XWayland here
+//!         if let Some(keyboard) = Seat::from_resource(grab.seat()).and_then(|seat| seat.get_keyboard()) {
+//!             let serial = SERIAL_COUNTER.next_serial();
+//!             keyboard.set_focus(Some(grab.surface()), serial);
+//!             keyboard.set_grab(grab, serial);
+//!         }
+//!     },
+//!     None, // put a logger if you want
+//! );
+//! ```
+
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use slog::{debug, o};
+use wayland_protocols::unstable::xwayland_keyboard_grab::v1::server::{
+    zwp_xwayland_keyboard_grab_manager_v1::{self, ZwpXwaylandKeyboardGrabManagerV1},
+    zwp_xwayland_keyboard_grab_v1::{self, ZwpXwaylandKeyboardGrabV1},
+};
+use wayland_server::{
+    protocol::{wl_keyboard::KeyState as WlKeyState, wl_seat::WlSeat, wl_surface::WlSurface},
+    DispatchData, Display, Filter, Global, Main,
+};
+
+use crate::wayland::{
+    seat::{KeyboardGrab, KeyboardGrabStartData as GrabStartData, KeyboardInnerHandle},
+    Serial,
+};
+
+/// A keyboard grab requested through the `zwp_xwayland_keyboard_grab_v1` protocol
+///
+/// While active, all key events are sent to the grabbing surface and focus changes are ignored.
+#[derive(Debug)]
+pub struct XwaylandKeyboardGrab {
+    grab: ZwpXwaylandKeyboardGrabV1,
+    seat: WlSeat,
+    start_data: GrabStartData,
+}
+
+impl XwaylandKeyboardGrab {
+    /// The surface requesting the grab
+    pub fn surface(&self) -> &WlSurface {
+        // the focus of the start data is always set
+        self.start_data.focus.as_ref().unwrap()
+    }
+
+    /// The seat whose keyboard should be grabbed
+    pub fn seat(&self) -> &WlSeat {
+        &self.seat
+    }
+
+    /// Checks if the client did not destroy the grab yet
+    pub fn alive(&self) -> bool {
+        self.grab.as_ref().is_alive() && self.surface().as_ref().is_alive()
+    }
+}
+
+impl KeyboardGrab for XwaylandKeyboardGrab {
+    fn input(
+        &mut self,
+        handle: &mut KeyboardInnerHandle<'_>,
+        keycode: u32,
+        key_state: WlKeyState,
+        modifiers: Option<(u32, u32, u32, u32)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        if !self.alive() {
+            handle.unset_grab(serial, true);
+        }
+        handle.input(keycode, key_state, modifiers, serial, time)
+    }
+
+    fn set_focus(&mut self, handle: &mut KeyboardInnerHandle<'_>, focus: Option<&WlSurface>, serial: Serial) {
+        // the focus stays on the grabbing surface until the grab ends
+        if !self.alive() {
+            handle.unset_grab(serial, false);
+            handle.set_focus(focus, serial);
+        }
+    }
+
+    fn start_data(&self) -> &GrabStartData {
+        &self.start_data
+    }
+}
+
+/// Creates a new `zwp_xwayland_keyboard_grab_manager_v1` global
+///
+/// The callback is invoked for every grab requested by a client, see the module documentation.
+pub fn init_xwayland_keyboard_grab_manager<F, L>(
+    display: &mut Display,
+    implementation: F,
+    logger: L,
+) -> Global<ZwpXwaylandKeyboardGrabManagerV1>
+where
+    F: FnMut(XwaylandKeyboardGrab, DispatchData<'_>) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xwayland_keyboard_grab"));
+    let implementation = Rc::new(RefCell::new(implementation));
+
+    display.create_global(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpXwaylandKeyboardGrabManagerV1>, _), _, _| {
+                let implementation = implementation.clone();
+                let log = log.clone();
+                manager.quick_assign(move |_, request, ddata| match request {
+                    zwp_xwayland_keyboard_grab_manager_v1::Request::GrabKeyboard { id, surface, seat } => {
+                        debug!(log, "Keyboard grab requested"; "surface" => ?surface);
+                        id.quick_assign(|_, request, _| match request {
+                            zwp_xwayland_keyboard_grab_v1::Request::Destroy => {
+                                // the grab ends with the next event of the keyboard
+                            }
+                            _ => unreachable!(),
+                        });
+                        let grab = XwaylandKeyboardGrab {
+                            grab: id.deref().clone(),
+                            seat,
+                            start_data: GrabStartData { focus: Some(surface) },
+                        };
+                        (*implementation.borrow_mut())(grab, ddata);
+                    }
+                    zwp_xwayland_keyboard_grab_manager_v1::Request::Destroy => {
+                        // All is handled by destructor.
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
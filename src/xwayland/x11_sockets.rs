@@ -8,8 +8,13 @@ use slog::{debug, info, warn};
 use nix::{errno::Errno, sys::socket};
 
 /// Find a free X11 display slot and setup
-pub(crate) fn prepare_x11_sockets(log: ::slog::Logger) -> Result<(X11Lock, [UnixStream; 2]), std::io::Error> {
-    for d in 0..33 {
+///
+/// The `preferred` display is tried first, so a restarted server can keep its display number.
+pub(crate) fn prepare_x11_sockets(
+    preferred: Option<u32>,
+    log: ::slog::Logger,
+) -> Result<(X11Lock, [UnixStream; 2]), std::io::Error> {
+    for d in preferred.into_iter().chain(0..33) {
         // if fails, try the next one
         if let Ok(lock) = X11Lock::grab(d, log.clone()) {
             // we got a lockfile, try and create the socket
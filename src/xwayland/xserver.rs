@@ -79,6 +79,12 @@ pub struct XWayland<Data> {
 /// Your WM code must be able to handle the XWayland server connecting then
 /// disconnecting several time in a row, but only a single connection will
 /// be active at any given time.
+///
+/// XWayland is not restarted automatically. All X11 clients are disconnected, when the server
+/// exits, so a WM wanting to survive crashes should call [`XWayland::start`] again on
+/// [`XWaylandEvent::Exited`] and restore what it can of the previous session, once the
+/// restarted server is [`Ready`](XWaylandEvent::Ready). The restarted server keeps the `DISPLAY`
+/// of the previous one, if it is still available, so clients started afterwards are not confused.
 #[derive(Debug)]
 pub enum XWaylandEvent {
     /// The XWayland server is ready
@@ -112,6 +118,7 @@ impl<Data: Any + 'static> XWayland<Data> {
             handle,
             wayland_display: display,
             instance: None,
+            last_display: None,
             sender,
             log: log.new(o!("smithay_module" => "XWayland")),
         }));
@@ -160,6 +167,8 @@ struct Inner<Data> {
     handle: LoopHandle<'static, Data>,
     wayland_display: Rc<RefCell<Display>>,
     instance: Option<XWaylandInstance>,
+    // display of the last successfully started instance, reused on restart
+    last_display: Option<u32>,
     log: ::slog::Logger,
 }
 
@@ -177,7 +186,7 @@ fn launch<Data: Any>(inner: &Rc<RefCell<Inner<Data>>>) -> std::io::Result<()> {
     let (x_wm_x11, x_wm_me) = UnixStream::pair()?;
     let (wl_x11, wl_me) = UnixStream::pair()?;
 
-    let (lock, x_fds) = prepare_x11_sockets(guard.log.clone())?;
+    let (lock, x_fds) = prepare_x11_sockets(guard.last_display, guard.log.clone())?;
 
     // we have now created all the required sockets
 
@@ -343,6 +352,7 @@ fn xwayland_ready<Data: 'static>(inner: &Rc<RefCell<Inner<Data>>>) {
     };
 
     if success {
+        guard.last_display = Some(instance.display_lock.display());
         // setup the environemnt
         ::std::env::set_var("DISPLAY", format!(":{}", instance.display_lock.display()));
 
@@ -358,10 +368,9 @@ fn xwayland_ready<Data: 'static>(inner: &Rc<RefCell<Inner<Data>>>) {
             client: instance.wayland_client.clone().unwrap(),
         });
     } else {
-        error!(
-            guard.log,
-            "XWayland crashed at startup, will not try to restart it."
-        );
+        error!(guard.log, "XWayland crashed at startup.");
+        // clean up, so the WM is notified and may start it again
+        guard.shutdown();
     }
 }
 
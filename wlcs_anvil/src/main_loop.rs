@@ -14,6 +14,7 @@ use smithay::{
             Client, Display,
         },
     },
+    utils::{Logical, Rectangle},
     wayland::{
         output::{Mode, Output, PhysicalProperties},
         seat::CursorImageStatus,
@@ -43,6 +44,7 @@ impl Backend for TestState {
 
     fn reset_buffers(&mut self, _output: &Output) {}
     fn early_import(&mut self, _surface: &wl_surface::WlSurface) {}
+    fn stylus_damage(&mut self, _damage: &[Rectangle<i32, Logical>]) {}
 }
 
 pub fn run(channel: Channel<WlcsEvent>) {
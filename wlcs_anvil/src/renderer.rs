@@ -4,7 +4,7 @@ use smithay::{
     backend::{
         allocator::dmabuf::Dmabuf,
         renderer::{Frame, ImportDma, ImportDmaWl, ImportMem, ImportMemWl, Renderer, Texture, TextureFilter},
-        SwapBuffersError,
+        SwapBuffersError, TemporaryFailureReason,
     },
     reexports::wayland_server::protocol::wl_buffer,
     utils::{Buffer, Physical, Rectangle, Size, Transform},
@@ -101,7 +101,10 @@ impl ImportMemWl for DummyRenderer {
 
         match ret {
             Ok((width, height)) => Ok(DummyTexture { width, height }),
-            Err(e) => Err(SwapBuffersError::TemporaryFailure(Box::new(e))),
+            Err(e) => Err(SwapBuffersError::temporary_failure(
+                TemporaryFailureReason::Other,
+                e,
+            )),
         }
     }
 }